@@ -0,0 +1,192 @@
+use crate::utils::retry_interrupted;
+use data_encoding::HEXLOWER;
+use fs4::FileExt;
+use rattler_digest::{Sha256, Sha256Hash};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// The name of the lock file that serializes pruning against linking.
+const LOCK_FILE: &str = ".lock";
+
+/// A content-addressable store for the files of unpacked wheels.
+///
+/// When a store is passed to [`super::wheel::Wheel::unpack`] through
+/// [`super::wheel::UnpackWheelOptions::content_store`] every file of the wheel is written to the
+/// store once, keyed by the sha256 hash of its contents, and hard linked into the environment.
+/// Identical files that are installed in many environments only take up disk space once.
+///
+/// Because the files in the environments are hard links, modifying an installed file in place
+/// also modifies it for every other environment that shares the file. If hard links are not
+/// supported (e.g. because the store and the environment are on different file systems) files
+/// are copied instead.
+///
+/// Linking files and [`ContentStore::prune`] are serialized through a lock file in the store, so a
+/// file that is being linked into an environment is never removed by a concurrent prune, even if
+/// it runs in another process.
+#[derive(Debug, Clone)]
+pub struct ContentStore {
+    root: PathBuf,
+}
+
+impl ContentStore {
+    /// Constructs a new store that keeps its files in `root`. The directory is created if it does
+    /// not exist.
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    /// Returns the root directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path in the store of a file with the given hash.
+    fn path_for(&self, hash: &Sha256Hash, executable: bool) -> PathBuf {
+        let hex = HEXLOWER.encode(hash);
+        // Executable and non-executable files with the same content are stored separately because
+        // hard links share their permissions.
+        let name = if executable {
+            format!("{hex}-x")
+        } else {
+            hex.clone()
+        };
+        self.root.join(&hex[..2]).join(name)
+    }
+
+    /// Opens the lock file of the store. Links hold a shared lock while a prune holds an exclusive
+    /// lock.
+    fn lock_file(&self) -> io::Result<File> {
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.root.join(LOCK_FILE))
+    }
+
+    /// Writes the contents of `reader` to the store, if it doesn't contain the contents yet, and
+    /// links it to `destination`. Returns the size and the hash of the contents.
+    pub(crate) fn link(
+        &self,
+        reader: &mut impl Read,
+        destination: &Path,
+        executable: bool,
+    ) -> io::Result<(u64, Sha256Hash)> {
+        // Write the contents to a temporary file in the store while computing the hash.
+        let mut temp_file = tempfile::NamedTempFile::new_in(&self.root)?;
+        let mut reader = rattler_digest::HashingReader::<_, Sha256>::new(reader);
+        let size = io::copy(&mut reader, temp_file.as_file_mut())?;
+        let (_, hash) = reader.finalize();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = if executable { 0o755 } else { 0o644 };
+            temp_file
+                .as_file()
+                .set_permissions(fs::Permissions::from_mode(mode))?;
+        }
+
+        // Make sure the stored file is not pruned before it is linked into the destination.
+        let lock = self.lock_file()?;
+        retry_interrupted(|| lock.lock_shared())?;
+
+        let stored_path = self.path_for(&hash, executable);
+        if !stored_path.is_file() {
+            if let Some(parent) = stored_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Another process might have stored the same file in the meantime, in which case the
+            // existing file is used.
+            if let Err(err) = temp_file.persist_noclobber(&stored_path) {
+                if !stored_path.is_file() {
+                    return Err(err.error);
+                }
+            }
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match fs::remove_file(destination) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        if fs::hard_link(&stored_path, destination).is_err() {
+            fs::copy(&stored_path, destination)?;
+        }
+
+        Ok((size, hash))
+    }
+
+    /// Removes the files from the store that are no longer linked into any environment. Returns
+    /// the paths of the removed files.
+    ///
+    /// Link counts are only available on unix, on other platforms nothing is removed.
+    pub fn prune(&self) -> io::Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        #[cfg(unix)]
+        {
+            let lock = self.lock_file()?;
+            retry_interrupted(|| lock.lock_exclusive())?;
+
+            use std::os::unix::fs::MetadataExt;
+            for directory in fs::read_dir(&self.root)? {
+                let directory = directory?;
+                if !directory.file_type()?.is_dir() {
+                    continue;
+                }
+                for entry in fs::read_dir(directory.path())? {
+                    let entry = entry?;
+                    let metadata = entry.metadata()?;
+                    if metadata.is_file() && metadata.nlink() == 1 {
+                        fs::remove_file(entry.path())?;
+                        removed.push(entry.path());
+                    }
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_link_deduplicates() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let env_dir = tempfile::tempdir().unwrap();
+        let store = ContentStore::new(store_dir.path()).unwrap();
+
+        let first = env_dir.path().join("a/module.py");
+        let second = env_dir.path().join("b/module.py");
+        let (size, hash) = store
+            .link(&mut "print('hello')".as_bytes(), &first, false)
+            .unwrap();
+        let (_, second_hash) = store
+            .link(&mut "print('hello')".as_bytes(), &second, false)
+            .unwrap();
+        assert_eq!(size, 14);
+        assert_eq!(hash, second_hash);
+        assert_eq!(fs::read_to_string(&second).unwrap(), "print('hello')");
+
+        // Only a single file is stored
+        let stored_files = fs::read_dir(store_dir.path())
+            .unwrap()
+            .map(|dir| fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum::<usize>();
+        assert_eq!(stored_files, 1);
+
+        #[cfg(unix)]
+        {
+            assert!(store.prune().unwrap().is_empty());
+            fs::remove_file(&first).unwrap();
+            fs::remove_file(&second).unwrap();
+            assert_eq!(store.prune().unwrap().len(), 1);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use super::wheel::UnpackedWheel;
+use crate::types::WheelFilename;
+use std::path::Path;
+
+/// The error returned by an [`InstallHooks`] implementation. Returning an error aborts the
+/// installation.
+pub type InstallHookError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Callbacks that are invoked while wheels are installed, so integrators can run custom logic
+/// like signature checks, telemetry or patching without forking the installer. Set them with
+/// [`super::wheel::UnpackWheelOptions::install_hooks`].
+///
+/// All methods have a default implementation that does nothing. Hooks may be called from
+/// multiple threads at the same time when wheels are unpacked concurrently.
+pub trait InstallHooks: Send + Sync {
+    /// Called before any file of `wheel` is written to `dest`. Returning an error prevents the
+    /// wheel from being installed.
+    fn before_install(&self, wheel: &WheelFilename, dest: &Path) -> Result<(), InstallHookError> {
+        let _ = (wheel, dest);
+        Ok(())
+    }
+
+    /// Called after `wheel` has been installed.
+    fn after_install(
+        &self,
+        wheel: &WheelFilename,
+        unpacked: &UnpackedWheel,
+    ) -> Result<(), InstallHookError> {
+        let _ = (wheel, unpacked);
+        Ok(())
+    }
+
+    /// Called by [`super::wheel::unpack_wheels`] before any of `wheels` is installed. Returning
+    /// an error prevents all wheels from being installed.
+    fn before_transaction(&self, wheels: &[&WheelFilename]) -> Result<(), InstallHookError> {
+        let _ = wheels;
+        Ok(())
+    }
+
+    /// Called by [`super::wheel::unpack_wheels`] after all wheels have been installed.
+    fn after_transaction(&self, unpacked: &[UnpackedWheel]) -> Result<(), InstallHookError> {
+        let _ = unpacked;
+        Ok(())
+    }
+}
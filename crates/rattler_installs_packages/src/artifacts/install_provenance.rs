@@ -0,0 +1,153 @@
+//! Records where the files of an installed package came from.
+//!
+//! The `INSTALLER` file of a distribution only tells which tool installed it. Environment audits
+//! also need to know whether the installed files are the files published on the index or the
+//! result of a local build. When [`super::wheel::UnpackWheelOptions::provenance`] is set an
+//! [`InstallProvenance`] is written to the `.dist-info` directory as [`PROVENANCE_FILE`], it is read
+//! back by [`crate::python_env::find_distributions_in_venv`].
+
+use super::Wheel;
+use crate::types::ArtifactInfo;
+use rattler_digest::{serde::SerializableHash, Sha256, Sha256Hash};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::BTreeMap;
+use std::path::Path;
+use url::Url;
+
+/// The name of the file in the `.dist-info` directory that contains the [`InstallProvenance`].
+pub const PROVENANCE_FILE: &str = "rip_provenance.json";
+
+/// Describes the artifact an installed package originates from, see the
+/// [module documentation](self).
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "kebab-case")]
+pub enum InstallProvenance {
+    /// The package was installed from a wheel that was downloaded as is.
+    Wheel {
+        /// The url the wheel was downloaded from
+        url: Url,
+
+        /// The sha256 hash of the wheel archive
+        #[serde_as(as = "Option<SerializableHash<Sha256>>")]
+        #[serde(default)]
+        sha256: Option<Sha256Hash>,
+    },
+
+    /// The package was installed from a wheel that was built locally from an sdist.
+    #[serde(rename = "sdist")]
+    SDist {
+        /// The url the sdist was downloaded from
+        url: Url,
+
+        /// The sha256 hash of the sdist archive
+        #[serde_as(as = "SerializableHash<Sha256>")]
+        sha256: Sha256Hash,
+
+        /// The options the wheel was built with
+        #[serde(default)]
+        build: BuildProvenance,
+    },
+}
+
+/// The options a wheel was built with, see [`InstallProvenance::SDist`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct BuildProvenance {
+    /// The python interpreter the build backend was run with, `None` for the system interpreter
+    pub python: Option<String>,
+
+    /// The names of the environment variables that were set for the build. The values are not
+    /// recorded because they might contain credentials.
+    pub environment_variables: Vec<String>,
+
+    /// The config settings that were passed to the build backend
+    pub config_settings: BTreeMap<String, Vec<String>>,
+
+    /// The platform tag the wheel was cross built for, if any
+    pub cross_build_target: Option<String>,
+}
+
+impl InstallProvenance {
+    /// Returns the provenance of `wheel` that is installed as it was downloaded from
+    /// `artifact_info`. The hash is computed from the archive, the hash published by the index is
+    /// only used if the archive cannot be read.
+    pub fn wheel(artifact_info: &ArtifactInfo, wheel: &Wheel) -> Self {
+        let sha256 = match wheel.sha256() {
+            Ok(sha256) => Some(sha256),
+            Err(err) => {
+                tracing::warn!("failed to hash {}: {err}", artifact_info.filename);
+                artifact_info
+                    .hashes
+                    .as_ref()
+                    .and_then(|hashes| hashes.sha256)
+            }
+        };
+        Self::Wheel {
+            url: artifact_info.url.clone(),
+            sha256,
+        }
+    }
+
+    /// Returns the provenance of a wheel that was built from the sdist of `artifact_info` with
+    /// the hash `sha256`.
+    pub fn sdist(artifact_info: &ArtifactInfo, sha256: Sha256Hash, build: BuildProvenance) -> Self {
+        Self::SDist {
+            url: artifact_info.url.clone(),
+            sha256,
+            build,
+        }
+    }
+
+    /// Returns true if the package was built locally.
+    pub fn is_built_locally(&self) -> bool {
+        matches!(self, Self::SDist { .. })
+    }
+
+    /// Reads the provenance from a `.dist-info` directory. Returns `None` if the package was not
+    /// installed with a provenance or the file cannot be parsed.
+    pub fn from_dist_info(dist_info: &Path) -> Option<Self> {
+        let contents = std::fs::read(dist_info.join(PROVENANCE_FILE)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_provenance_round_trip() {
+        let provenance = InstallProvenance::SDist {
+            url: "https://example.com/foo-1.0.tar.gz".parse().unwrap(),
+            sha256: rattler_digest::compute_bytes_digest::<Sha256>(b"sdist"),
+            build: BuildProvenance {
+                environment_variables: vec![String::from("CFLAGS")],
+                config_settings: BTreeMap::from([(
+                    String::from("--build-option"),
+                    vec![String::from("--plat-name=macosx_11_0_arm64")],
+                )]),
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&provenance).unwrap();
+        assert_eq!(json["source"], "sdist");
+        assert_eq!(json["build"]["environment-variables"][0], "CFLAGS");
+        assert_eq!(
+            serde_json::from_value::<InstallProvenance>(json).unwrap(),
+            provenance
+        );
+        assert!(provenance.is_built_locally());
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(InstallProvenance::from_dist_info(dir.path()), None);
+        std::fs::write(
+            dir.path().join(PROVENANCE_FILE),
+            r#"{"source": "wheel", "url": "https://example.com/foo-1.0-py3-none-any.whl"}"#,
+        )
+        .unwrap();
+        let provenance = InstallProvenance::from_dist_info(dir.path()).unwrap();
+        assert!(!provenance.is_built_locally());
+    }
+}
@@ -1,8 +1,15 @@
 //! Module containing artifacts that can be resolved and installed.
+mod content_store;
+mod install_hooks;
+mod install_provenance;
 mod sdist;
 
 /// Module for working with PyPA wheels. Contains the [`Wheel`] type, and related functionality.
 pub mod wheel;
 
-pub use sdist::SDist;
+pub use content_store::ContentStore;
+pub use install_hooks::{InstallHookError, InstallHooks};
+pub use install_provenance::{BuildProvenance, InstallProvenance, PROVENANCE_FILE};
+pub(crate) use sdist::{parse_pyproject_toml, SDistError};
+pub use sdist::{SDist, SDistBuildProbe};
 pub use wheel::Wheel;
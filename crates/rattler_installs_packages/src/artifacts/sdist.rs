@@ -1,13 +1,15 @@
-use crate::types::{Artifact, NormalizedPackageName, SDistFilename, SDistFormat};
+use crate::types::{Artifact, NormalizedPackageName, PackageName, SDistFilename, SDistFormat};
 use crate::types::{WheelCoreMetaDataError, WheelCoreMetadata};
-use crate::utils::ReadAndSeek;
+use crate::utils::{ReadAndSeek, SharedReader};
 use flate2::read::GzDecoder;
 use miette::IntoDiagnostic;
-use parking_lot::Mutex;
+use pep508_rs::Requirement;
+use rattler_digest::{Sha256, Sha256Hash};
 use serde::Serialize;
 use std::ffi::OsStr;
-use std::io::{ErrorKind, Read, Seek};
-use std::path::{Path, PathBuf};
+use std::io::{BufReader, ErrorKind, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
 use tar::Archive;
 
 /// Represents a source distribution artifact.
@@ -15,8 +17,9 @@ pub struct SDist {
     /// Name of the source distribution
     name: SDistFilename,
 
-    /// Source dist archive
-    file: Mutex<Box<dyn ReadAndSeek + Send>>,
+    /// Source dist archive. This is cheap to clone, every operation reads from its own clone so
+    /// multiple operations can run concurrently.
+    file: SharedReader,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,6 +44,9 @@ pub enum SDistError {
 
     #[error("Could not parse metadata")]
     WheelCoreMetaDataError(#[from] WheelCoreMetaDataError),
+
+    #[error("expected the sdist to contain a single top-level directory, found: {}", .0.join(", "))]
+    NoSingleTopLevelDirectory(Vec<String>),
 }
 
 impl SDist {
@@ -56,14 +62,29 @@ impl SDist {
             .ok_or_else(|| miette::miette!("path does not contain a filename"))?;
         let name =
             SDistFilename::from_filename(file_name, normalized_package_name).into_diagnostic()?;
-        let bytes = std::fs::File::open(path).into_diagnostic()?;
-        Self::new(name, Box::new(bytes))
+        Self::from_file(name, path)
+    }
+
+    /// Open a source distribution from the bytes of the archive. The name of the package is
+    /// inferred from the filename, e.g. `trio-0.18.0.tar.gz`.
+    pub fn from_bytes(file_name: &str, bytes: impl Into<Vec<u8>>) -> miette::Result<Self> {
+        let bytes: Vec<u8> = bytes.into();
+        Ok(Self {
+            name: SDistFilename::from_str(file_name).into_diagnostic()?,
+            file: SharedReader::from_bytes(bytes),
+        })
+    }
+
+    /// Returns a reader positioned at the start of the archive.
+    fn archive_reader(&self) -> std::io::Result<Archive<RawAndGzReader<BufReader<SharedReader>>>> {
+        let mut file = self.file.clone();
+        file.rewind()?;
+        generic_archive_reader(BufReader::new(file), self.name.format)
     }
 
     /// Find entry in tar archive
     fn find_entry(&self, name: impl AsRef<str>) -> std::io::Result<Option<Vec<u8>>> {
-        let mut lock = self.file.lock();
-        let mut archive = generic_archive_reader(&mut lock, self.name.format)?;
+        let mut archive = self.archive_reader()?;
 
         // Loop over entries
         for entry in archive.entries()? {
@@ -80,33 +101,28 @@ impl SDist {
     }
 
     /// Read .PKG-INFO from the archive
+    ///
+    /// The archive is only read up to the top-level `PKG-INFO` file. Multiple threads can query
+    /// the metadata of the same sdist concurrently.
     pub fn read_package_info(&self) -> Result<(Vec<u8>, WheelCoreMetadata), SDistError> {
-        if let Some(bytes) = self.find_entry("PKG-INFO")? {
-            let metadata = WheelCoreMetadata::try_from(bytes.as_slice())?;
-
-            Ok((bytes, metadata))
-        } else {
-            Err(SDistError::NoPkgInfoFound)
+        let mut archive = self.archive_reader()?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if is_top_level_entry(&entry.path()?, "PKG-INFO") {
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes)?;
+                let metadata = WheelCoreMetadata::try_from(bytes.as_slice())?;
+                return Ok((bytes, metadata));
+            }
         }
+        Err(SDistError::NoPkgInfoFound)
     }
 
     /// Read the build system info from the pyproject.toml
     #[allow(dead_code)]
     pub fn read_build_info(&self) -> Result<pyproject_toml::BuildSystem, SDistError> {
         if let Some(bytes) = self.find_entry("pyproject.toml")? {
-            let source = String::from_utf8(bytes).map_err(|e| {
-                SDistError::PyProjectTomlParseError(format!(
-                    "could not parse pyproject.toml (bad encoding): {}",
-                    e
-                ))
-            })?;
-            let project = pyproject_toml::PyProjectToml::new(&source).map_err(|e| {
-                SDistError::PyProjectTomlParseError(format!(
-                    "could not parse pyproject.toml (bad toml): {}",
-                    e
-                ))
-            })?;
-            Ok(project
+            Ok(parse_pyproject_toml(bytes)?
                 .build_system
                 .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "no build-system found"))?)
         } else {
@@ -114,14 +130,115 @@ impl SDist {
         }
     }
 
+    /// Inspects the archive to describe how the sdist is built without building it: its build
+    /// backend, its declared build requirements and whether it likely contains native extensions.
+    /// This allows callers to predict the cost of building the sdist and to warn users up front.
+    pub fn probe_build(&self) -> Result<SDistBuildProbe, SDistError> {
+        let mut probe = SDistBuildProbe::default();
+        let mut archive = self.archive_reader()?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            // Paths relative to the top-level directory of the sdist
+            let path = entry.path()?.into_owned();
+            let relative_path = path
+                .components()
+                .filter(|component| !matches!(component, Component::CurDir))
+                .skip(1)
+                .collect::<PathBuf>();
+
+            if is_top_level_entry(&path, "pyproject.toml") {
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes)?;
+                if let Some(build_system) = parse_pyproject_toml(bytes)?.build_system {
+                    probe.build_backend = build_system.build_backend;
+                    probe.build_requirements = build_system.requires;
+                }
+            } else if is_top_level_entry(&path, "setup.py") {
+                let mut source = String::new();
+                if entry.read_to_string(&mut source).is_ok() {
+                    probe.setup_py_ext_modules = ["ext_modules", "Extension(", "cythonize("]
+                        .iter()
+                        .any(|pattern| source.contains(pattern));
+                }
+            } else if relative_path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| NATIVE_BUILD_FILES.contains(&name))
+            {
+                probe.native_build_files.push(relative_path);
+            } else if relative_path
+                .extension()
+                .and_then(OsStr::to_str)
+                .is_some_and(|extension| NATIVE_SOURCE_EXTENSIONS.contains(&extension))
+            {
+                probe.native_sources.push(relative_path);
+            }
+        }
+        Ok(probe)
+    }
+
+    /// Computes the sha256 hash of the sdist archive.
+    pub fn sha256(&self) -> std::io::Result<Sha256Hash> {
+        let mut file = self.file.clone();
+        file.rewind()?;
+        let mut writer = rattler_digest::HashingWriter::<_, Sha256>::new(std::io::sink());
+        std::io::copy(&mut file, &mut writer)?;
+        let (_, sha256) = writer.finalize();
+        Ok(sha256)
+    }
+
     /// Extract the contents of the sdist archive to the given directory
     pub fn extract_to(&self, work_dir: &Path) -> std::io::Result<()> {
-        let mut lock = self.file.lock();
-        let mut archive = generic_archive_reader(&mut lock, self.name.format)?;
+        let mut archive = self.archive_reader()?;
         archive.unpack(work_dir)?;
         Ok(())
     }
 
+    /// Returns the name of the single directory that contains the files of the sdist. This is
+    /// usually `{name}-{version}` but some sdists use a different casing or name.
+    ///
+    /// Returns an error if the archive contains multiple top-level entries or none at all.
+    pub fn top_level_directory(&self) -> Result<PathBuf, SDistError> {
+        let mut archive = self.archive_reader()?;
+
+        let mut top_level_entries = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if entry.header().entry_type().is_pax_global_extensions() {
+                continue;
+            }
+
+            let path = entry.path()?;
+            let Some(first) = path
+                .components()
+                .find(|component| !matches!(component, Component::CurDir))
+            else {
+                continue;
+            };
+
+            let is_directory =
+                path.components().count() > 1 || entry.header().entry_type().is_dir();
+            let first = first.as_os_str().to_string_lossy().into_owned();
+            if !top_level_entries.iter().any(|(name, _)| name == &first) {
+                top_level_entries.push((first, is_directory));
+            }
+        }
+
+        match top_level_entries.as_slice() {
+            [(name, true)] => Ok(PathBuf::from(name)),
+            _ => Err(SDistError::NoSingleTopLevelDirectory(
+                top_level_entries
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect(),
+            )),
+        }
+    }
+
     /// Checks if this artifact implements PEP 643
     /// and returns the metadata if it does
     pub fn pep643_metadata(&self) -> Option<(Vec<u8>, WheelCoreMetadata)> {
@@ -141,7 +258,14 @@ impl Artifact for SDist {
     fn new(name: Self::Name, bytes: Box<dyn ReadAndSeek + Send>) -> miette::Result<Self> {
         Ok(Self {
             name,
-            file: Mutex::new(bytes),
+            file: SharedReader::from_reader(bytes),
+        })
+    }
+
+    fn from_file(name: Self::Name, path: &Path) -> miette::Result<Self> {
+        Ok(Self {
+            name,
+            file: SharedReader::from_path(path).into_diagnostic()?,
         })
     }
 
@@ -150,12 +274,115 @@ impl Artifact for SDist {
     }
 }
 
-enum RawAndGzReader<'a> {
-    Raw(&'a mut Box<dyn ReadAndSeek + Send>),
-    Gz(GzDecoder<&'a mut Box<dyn ReadAndSeek + Send>>),
+/// Parses the contents of a `pyproject.toml` file.
+pub(crate) fn parse_pyproject_toml(
+    bytes: Vec<u8>,
+) -> Result<pyproject_toml::PyProjectToml, SDistError> {
+    let source = String::from_utf8(bytes).map_err(|e| {
+        SDistError::PyProjectTomlParseError(format!(
+            "could not parse pyproject.toml (bad encoding): {}",
+            e
+        ))
+    })?;
+    pyproject_toml::PyProjectToml::new(&source).map_err(|e| {
+        SDistError::PyProjectTomlParseError(format!(
+            "could not parse pyproject.toml (bad toml): {}",
+            e
+        ))
+    })
 }
 
-impl<'a> Read for RawAndGzReader<'a> {
+/// Extensions of source files of compiled languages: C, C++, Cython, Fortran and Rust.
+const NATIVE_SOURCE_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx", "pyx", "f", "f90", "rs"];
+
+/// Files of build systems that are used to compile native code.
+const NATIVE_BUILD_FILES: &[&str] = &["CMakeLists.txt", "Cargo.toml", "meson.build"];
+
+/// Build backends that compile native code.
+const NATIVE_BUILD_BACKENDS: &[&str] = &["maturin", "scikit_build_core", "mesonpy"];
+
+/// Build requirements that indicate that native code is compiled.
+const NATIVE_BUILD_REQUIREMENTS: &[&str] = &[
+    "cython",
+    "maturin",
+    "setuptools-rust",
+    "scikit-build",
+    "scikit-build-core",
+    "meson-python",
+    "pybind11",
+    "cffi",
+];
+
+/// Describes how an sdist is built, see [`SDist::probe_build`].
+#[derive(Debug, Clone, Default)]
+pub struct SDistBuildProbe {
+    /// The build backend from the `[build-system]` table of `pyproject.toml`, e.g.
+    /// `hatchling.build`. `None` if the sdist does not specify a backend, in which case the legacy
+    /// setuptools backend is used.
+    pub build_backend: Option<String>,
+
+    /// The requirements from the `[build-system]` table of `pyproject.toml`. If this is empty
+    /// `setuptools` and `wheel` are used to build the sdist.
+    pub build_requirements: Vec<Requirement>,
+
+    /// True if the `setup.py` of the sdist declares extension modules.
+    pub setup_py_ext_modules: bool,
+
+    /// Source files of compiled languages (C, C++, Cython, Fortran and Rust), relative to the
+    /// top-level directory of the sdist.
+    pub native_sources: Vec<PathBuf>,
+
+    /// Files of native build systems like `CMakeLists.txt` or `Cargo.toml`, relative to the
+    /// top-level directory of the sdist.
+    pub native_build_files: Vec<PathBuf>,
+}
+
+impl SDistBuildProbe {
+    /// Returns the build backend that is used to build the sdist.
+    pub fn effective_build_backend(&self) -> &str {
+        self.build_backend
+            .as_deref()
+            .unwrap_or("setuptools.build_meta:__legacy__")
+    }
+
+    /// Returns true if building the sdist likely compiles native extensions. This requires a
+    /// compiler and usually takes a lot longer than building a pure python package.
+    pub fn has_native_extensions(&self) -> bool {
+        let backend = self.effective_build_backend();
+        self.setup_py_ext_modules
+            || !self.native_sources.is_empty()
+            || !self.native_build_files.is_empty()
+            || NATIVE_BUILD_BACKENDS
+                .iter()
+                .any(|native| backend.split(['.', ':']).next() == Some(*native))
+            || self.build_requirements.iter().any(|requirement| {
+                PackageName::from_str(&requirement.name).is_ok_and(|name| {
+                    NATIVE_BUILD_REQUIREMENTS
+                        .iter()
+                        .any(|native| name.as_str() == *native)
+                })
+            })
+    }
+}
+
+/// Returns true if `path` refers to the file `name` in the top-level directory of an sdist, e.g.
+/// `foo-1.0/PKG-INFO` but not `foo-1.0/src/foo.egg-info/PKG-INFO`.
+fn is_top_level_entry(path: &Path, name: &str) -> bool {
+    let mut components = path
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir));
+    match (components.next(), components.next(), components.next()) {
+        (Some(file), None, None) | (Some(_), Some(file), None) => file.as_os_str() == name,
+        _ => false,
+    }
+}
+
+enum RawAndGzReader<R> {
+    Raw(R),
+    Gz(GzDecoder<R>),
+}
+
+impl<R: Read> Read for RawAndGzReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             Self::Raw(r) => r.read(buf),
@@ -164,12 +391,11 @@ impl<'a> Read for RawAndGzReader<'a> {
     }
 }
 
-fn generic_archive_reader(
-    file: &mut Box<dyn ReadAndSeek + Send>,
+/// Returns a tar archive reader for `file` which must be positioned at the start of the archive.
+fn generic_archive_reader<R: Read>(
+    file: R,
     format: SDistFormat,
-) -> std::io::Result<Archive<RawAndGzReader>> {
-    file.rewind()?;
-
+) -> std::io::Result<Archive<RawAndGzReader<R>>> {
     match format {
         SDistFormat::TarGz => {
             let bytes = GzDecoder::new(file);
@@ -186,9 +412,11 @@ fn generic_archive_reader(
 #[cfg(test)]
 mod tests {
     use crate::artifacts::SDist;
-    use crate::python_env::Pep508EnvMakers;
-    use crate::wheel_builder::WheelBuilder;
-    use crate::{index::PackageDb, resolve::ResolveOptions};
+    use crate::index::PackageDb;
+    #[cfg(feature = "sdist-build")]
+    use crate::{
+        python_env::Pep508EnvMakers, resolve::ResolveOptions, wheel_builder::WheelBuilder,
+    };
     use insta::{assert_debug_snapshot, assert_ron_snapshot};
     use std::path::Path;
     use tempfile::TempDir;
@@ -240,6 +468,160 @@ mod tests {
         "###);
     }
 
+    #[test]
+    pub fn top_level_directory() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+        assert_eq!(
+            sdist.top_level_directory().unwrap(),
+            Path::new("rich-13.6.0")
+        );
+
+        // An archive with a differently named top-level directory
+        let sdist = tar_sdist(&["./Foo_Bar-1.0/PKG-INFO", "./Foo_Bar-1.0/setup.py"]);
+        assert_eq!(
+            sdist.top_level_directory().unwrap(),
+            Path::new("Foo_Bar-1.0")
+        );
+
+        // An archive with multiple top-level entries
+        let sdist = tar_sdist(&["foo-1.0/PKG-INFO", "setup.py"]);
+        assert!(matches!(
+            sdist.top_level_directory(),
+            Err(super::SDistError::NoSingleTopLevelDirectory(entries)) if entries == ["foo-1.0", "setup.py"]
+        ));
+    }
+
+    #[test]
+    pub fn read_top_level_package_info() {
+        // The PKG-INFO of the egg-info directory comes first but should not be used.
+        let sdist = tar_sdist_with_contents(&[
+            (
+                "foo-1.0/src/foo.egg-info/PKG-INFO",
+                b"Metadata-Version: 2.1\nName: bar\nVersion: 0.1\n",
+            ),
+            (
+                "foo-1.0/PKG-INFO",
+                b"Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n",
+            ),
+        ]);
+        let (_, metadata) = sdist.read_package_info().unwrap();
+        assert_eq!(metadata.name.as_str(), "foo");
+        assert_eq!(metadata.version, "1.0".parse().unwrap());
+
+        let sdist = tar_sdist(&["foo-1.0/setup.py"]);
+        assert!(matches!(
+            sdist.read_package_info(),
+            Err(super::SDistError::NoPkgInfoFound)
+        ));
+    }
+
+    #[test]
+    pub fn read_package_info_concurrently() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+
+        std::thread::scope(|s| {
+            let handles = (0..4)
+                .map(|_| s.spawn(|| sdist.read_package_info().unwrap().1))
+                .collect::<Vec<_>>();
+            for handle in handles {
+                let metadata = handle.join().unwrap();
+                assert_eq!(metadata.name.as_str(), "rich");
+                assert_eq!(metadata.version, "13.6.0".parse().unwrap());
+            }
+        });
+
+        // Reading the archive concurrently does not affect other users of the archive.
+        assert_eq!(
+            sdist.top_level_directory().unwrap(),
+            Path::new("rich-13.6.0")
+        );
+    }
+
+    #[test]
+    pub fn sdist_sha256() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+        let expected = rattler_digest::compute_file_digest::<Sha256>(&path).unwrap();
+        assert_eq!(sdist.sha256().unwrap(), expected);
+
+        // Hashing does not affect reading the archive
+        assert_eq!(sdist.read_package_info().unwrap().1.name.as_str(), "rich");
+        assert_eq!(sdist.sha256().unwrap(), expected);
+    }
+
+    #[test]
+    pub fn probe_build() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+        let probe = sdist.probe_build().unwrap();
+        assert_eq!(
+            probe.build_backend.as_deref(),
+            Some("poetry.core.masonry.api")
+        );
+        assert_eq!(probe.build_requirements.len(), 1);
+        assert!(!probe.has_native_extensions());
+
+        let sdist = tar_sdist_with_contents(&[
+            (
+                "foo-1.0/pyproject.toml",
+                b"[build-system]\nrequires = [\"maturin>=1,<2\"]\nbuild-backend = \"maturin\"\n",
+            ),
+            ("foo-1.0/Cargo.toml", b""),
+            ("foo-1.0/src/lib.rs", b""),
+            ("foo-1.0/python/foo/__init__.py", b""),
+        ]);
+        let probe = sdist.probe_build().unwrap();
+        assert_eq!(probe.effective_build_backend(), "maturin");
+        assert_eq!(probe.native_build_files, [Path::new("Cargo.toml")]);
+        assert_eq!(probe.native_sources, [Path::new("src/lib.rs")]);
+        assert!(probe.has_native_extensions());
+
+        // A legacy sdist with extension modules in its setup.py
+        let sdist = tar_sdist_with_contents(&[(
+            "foo-1.0/setup.py",
+            b"from setuptools import setup, Extension\nsetup(ext_modules=[Extension('foo', ['foo.c'])])\n",
+        )]);
+        let probe = sdist.probe_build().unwrap();
+        assert_eq!(
+            probe.effective_build_backend(),
+            "setuptools.build_meta:__legacy__"
+        );
+        assert!(probe.build_requirements.is_empty());
+        assert!(probe.setup_py_ext_modules);
+        assert!(probe.has_native_extensions());
+    }
+
+    /// Constructs a tar sdist in memory that contains empty files at the given paths.
+    fn tar_sdist(paths: &[&str]) -> SDist {
+        tar_sdist_with_contents(
+            &paths
+                .iter()
+                .map(|path| (*path, &b""[..]))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Constructs a tar sdist in memory that contains the given files.
+    fn tar_sdist_with_contents(files: &[(&str, &[u8])]) -> SDist {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        let bytes = builder.into_inner().unwrap();
+        SDist::from_bytes("foo-1.0.tar", bytes).unwrap()
+    }
+
+    #[cfg(feature = "sdist-build")]
     #[tokio::test(flavor = "multi_thread")]
     pub async fn sdist_metadata() {
         let path =
@@ -257,6 +639,7 @@ mod tests {
         assert_debug_snapshot!(result.1);
     }
 
+    #[cfg(feature = "sdist-build")]
     #[tokio::test(flavor = "multi_thread")]
     pub async fn build_rich_with_metadata() {
         let path =
@@ -279,6 +662,124 @@ mod tests {
         let (_, metadata) = wheel.metadata().unwrap();
         assert_debug_snapshot!(metadata);
     }
+    #[cfg(feature = "sdist-build")]
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_rich_with_python_location() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+
+        let package_db = get_package_db();
+        let env_markers = Pep508EnvMakers::from_env().await.unwrap();
+        let resolve_options = ResolveOptions {
+            python_location: crate::python_env::PythonLocation::Custom(
+                crate::python_env::system_python_executable().unwrap(),
+            ),
+            ..Default::default()
+        };
+        let wheel_builder = WheelBuilder::new(&package_db.0, &env_markers, None, &resolve_options);
+
+        let result = wheel_builder.build_wheel(&sdist).await.unwrap();
+        let wheel = crate::artifacts::Wheel::from_path(&result, &"rich".parse().unwrap()).unwrap();
+        let (_, metadata) = wheel.metadata().unwrap();
+        assert_eq!(metadata.name.as_str(), "rich");
+
+        // A fresh package database makes sure the wheel is not taken from the cache.
+        let package_db = get_package_db();
+        let wheel_builder = WheelBuilder::new(&package_db.0, &env_markers, None, &resolve_options)
+            .with_python_location(crate::python_env::PythonLocation::Custom(
+                package_db.1.path().join("missing-python"),
+            ));
+        assert!(wheel_builder.build_wheel(&sdist).await.is_err());
+    }
+
+    #[cfg(feature = "sdist-build")]
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn get_rich_editable_metadata() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+
+        let package_db = get_package_db();
+        let env_markers = Pep508EnvMakers::from_env().await.unwrap();
+        let resolve_options = ResolveOptions::default();
+        let wheel_builder = WheelBuilder::new(&package_db.0, &env_markers, None, &resolve_options);
+
+        let (_, metadata) = wheel_builder
+            .get_sdist_editable_metadata(&sdist)
+            .await
+            .unwrap();
+        assert_eq!(metadata.name.as_str(), "rich");
+        assert_eq!(metadata.version, "13.6.0".parse().unwrap());
+
+        // The editable metadata is prepared without building a wheel
+        assert!(package_db.0.built_wheels().wheels(sdist.name()).is_empty());
+    }
+
+    #[cfg(feature = "sdist-build")]
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_rich_editable_in_place() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/sdists/rich-13.6.0.tar.gz");
+        let sdist = SDist::from_path(&path, &"rich".parse().unwrap()).unwrap();
+        let project = tempfile::tempdir().unwrap();
+        sdist.extract_to(project.path()).unwrap();
+        let project_dir = project.path().join("rich-13.6.0");
+        let wheel_dir = tempfile::tempdir().unwrap();
+
+        let package_db = get_package_db();
+        let env_markers = Pep508EnvMakers::from_env().await.unwrap();
+        let resolve_options = ResolveOptions::default();
+        let wheel_builder = WheelBuilder::new(&package_db.0, &env_markers, None, &resolve_options);
+
+        let wheel_file = wheel_builder
+            .build_editable(&project_dir, wheel_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(wheel_file.parent(), Some(wheel_dir.path()));
+        assert!(wheel_file
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("rich-13.6.0-"));
+
+        // The editable points at the project directory itself
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&wheel_file).unwrap()).unwrap();
+        let mut pth = String::new();
+        for idx in 0..archive.len() {
+            let mut entry = archive.by_index(idx).unwrap();
+            if entry.name().ends_with(".pth") {
+                std::io::Read::read_to_string(&mut entry, &mut pth).unwrap();
+            }
+        }
+        let project_dir = project_dir.canonicalize().unwrap();
+        assert!(pth.contains(&*project_dir.to_string_lossy()), "{pth}");
+    }
+
+    #[cfg(feature = "sdist-build")]
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn egg_info_metadata_without_pkg_info() {
+        // An ancient sdist without a PKG-INFO or pyproject.toml
+        let sdist = tar_sdist_with_contents(&[(
+            "foo-1.0/setup.py",
+            b"from setuptools import setup\nsetup(name='foo', version='1.0')\n",
+        )]);
+        assert!(sdist.pep643_metadata().is_none());
+
+        let package_db = get_package_db();
+        let env_markers = Pep508EnvMakers::from_env().await.unwrap();
+        let resolve_options = ResolveOptions::default();
+        let wheel_builder = WheelBuilder::new(&package_db.0, &env_markers, None, &resolve_options);
+        let build_environment = wheel_builder.build_environment(&sdist).await.unwrap();
+
+        let (_, metadata) = build_environment.egg_info_metadata().unwrap().unwrap();
+        assert_eq!(metadata.name.as_str(), "foo");
+        assert_eq!(metadata.version, "1.0".parse().unwrap());
+    }
+
+    #[cfg(feature = "sdist-build")]
     #[tokio::test(flavor = "multi_thread")]
     pub async fn build_rich_no_metadata() {
         let path =
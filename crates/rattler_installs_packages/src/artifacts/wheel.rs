@@ -1,6 +1,10 @@
 use crate::{
+    artifacts::{ContentStore, InstallHookError, InstallHooks, InstallProvenance, PROVENANCE_FILE},
     python_env::PythonInterpreterVersion,
+    python_env::WheelTags,
+    session_log::{FileOperation, SessionEvent, SessionLog},
     types::Artifact,
+    types::ArtifactName,
     types::EntryPoint,
     types::Extra,
     types::NormalizedPackageName,
@@ -9,16 +13,18 @@ use crate::{
     types::WheelFilename,
     types::{Record, RecordEntry},
     types::{WheelCoreMetaDataError, WheelCoreMetadata},
-    utils::ReadAndSeek,
+    utils::{ReadAndSeek, SharedReader},
 };
 use async_http_range_reader::AsyncHttpRangeReader;
 use async_zip::base::read::seek::ZipFileReader;
 use configparser::ini::Ini;
 use data_encoding::BASE64URL_NOPAD;
+use indexmap::IndexMap;
+use itertools::Itertools;
 use miette::IntoDiagnostic;
 use parking_lot::Mutex;
 use pep440_rs::Version;
-use rattler_digest::Sha256;
+use rattler_digest::{Sha256, Sha256Hash};
 use std::fs::OpenOptions;
 use std::io::{BufRead, BufReader};
 use std::{
@@ -26,12 +32,15 @@ use std::{
     collections::HashMap,
     collections::HashSet,
     ffi::OsStr,
+    fmt::{Display, Formatter},
     fs,
-    fs::File,
     io::{Read, Write},
     iter::FromIterator,
+    num::NonZeroUsize,
     path::{Component, Path, PathBuf},
     str::FromStr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::Arc,
 };
 use thiserror::Error;
 use tokio_util::compat::TokioAsyncReadCompatExt;
@@ -44,7 +53,10 @@ use crate::win::launcher::{build_windows_launcher, LauncherType, WindowsLauncher
 /// for more information.
 pub struct Wheel {
     name: WheelFilename,
-    archive: Mutex<ZipArchive<Box<dyn ReadAndSeek + Send>>>,
+
+    /// The archive of the wheel. This is cheap to clone, every operation on the wheel reads from
+    /// its own clone so multiple operations can run concurrently.
+    archive: ZipArchive<SharedReader>,
 }
 
 impl Wheel {
@@ -59,8 +71,24 @@ impl Wheel {
             .ok_or_else(|| miette::miette!("path does not contain a filename"))?;
         let wheel_name =
             WheelFilename::from_filename(file_name, normalized_package_name).into_diagnostic()?;
-        let file = File::open(path).into_diagnostic()?;
-        Self::new(wheel_name, Box::new(file))
+        Self::from_file(wheel_name, path)
+    }
+
+    /// Open a wheel from the bytes of the archive. The name of the package is inferred from the
+    /// filename, e.g. `trio-0.18.0-py3-none-any.whl`.
+    pub fn from_bytes(file_name: &str, bytes: impl Into<Vec<u8>>) -> miette::Result<Self> {
+        let package_name = ArtifactName::infer_package_name(file_name).into_diagnostic()?;
+        let wheel_name =
+            WheelFilename::from_filename(file_name, &package_name).into_diagnostic()?;
+        let bytes: Vec<u8> = bytes.into();
+        Self::from_shared_reader(wheel_name, SharedReader::from_bytes(bytes))
+    }
+
+    fn from_shared_reader(name: WheelFilename, reader: SharedReader) -> miette::Result<Self> {
+        Ok(Self {
+            name,
+            archive: ZipArchive::new(reader).into_diagnostic()?,
+        })
     }
 }
 
@@ -68,10 +96,11 @@ impl Artifact for Wheel {
     type Name = WheelFilename;
 
     fn new(name: Self::Name, bytes: Box<dyn ReadAndSeek + Send>) -> miette::Result<Self> {
-        Ok(Self {
-            name,
-            archive: Mutex::new(ZipArchive::new(bytes).into_diagnostic()?),
-        })
+        Self::from_shared_reader(name, SharedReader::from_reader(bytes))
+    }
+
+    fn from_file(name: Self::Name, path: &Path) -> miette::Result<Self> {
+        Self::from_shared_reader(name, SharedReader::from_path(path).into_diagnostic()?)
     }
 
     fn name(&self) -> &Self::Name {
@@ -217,7 +246,7 @@ impl Wheel {
     }
 
     fn get_vitals(&self) -> Result<WheelVitals, WheelVitalsError> {
-        let mut archive = self.archive.lock();
+        let mut archive = self.archive.clone();
 
         // Determine the top level filenames in the wheel
         let top_level_names = archive
@@ -310,6 +339,16 @@ impl Wheel {
         Ok((metadata_blob, metadata))
     }
 
+    /// Computes the sha256 hash of the wheel archive.
+    pub fn sha256(&self) -> std::io::Result<Sha256Hash> {
+        let mut reader = self.archive.clone().into_inner();
+        std::io::Seek::rewind(&mut reader)?;
+        let mut writer = rattler_digest::HashingWriter::<_, Sha256>::new(std::io::sink());
+        std::io::copy(&mut reader, &mut writer)?;
+        let (_, sha256) = writer.finalize();
+        Ok(sha256)
+    }
+
     /// Read metadata from bytes-stream
     pub async fn read_metadata_bytes(
         name: &WheelFilename,
@@ -434,6 +473,25 @@ pub struct InstallPaths {
 }
 
 impl InstallPaths {
+    /// Constructs the mapping from the locations of the individual categories, e.g. from an
+    /// [`crate::python_env::InstallScheme`]. Relative locations are relative to the installation
+    /// destination.
+    pub fn new(
+        purelib: PathBuf,
+        platlib: PathBuf,
+        scripts: PathBuf,
+        data: PathBuf,
+        windows: bool,
+    ) -> Self {
+        Self {
+            purelib,
+            platlib,
+            scripts,
+            data,
+            windows,
+        }
+    }
+
     /// Populates mappings of installation targets for a virtualenv layout. The mapping depends on
     /// the python version and whether or not the installation targets windows. Specifically on
     /// windows some of the paths are different. :shrug:
@@ -541,6 +599,29 @@ pub enum UnpackError {
 
     #[error("could not create entry points because the windows architecture is unsupported")]
     UnsupportedWindowsArchitecture,
+
+    #[error("multiple wheels would write to the same files: {}", .0.iter().format("; "))]
+    FileConflicts(Vec<FileConflict>),
+
+    #[error(
+        "{wheel} cannot be installed because it is not compatible with the target environment \
+        (the environment supports {supported_tags}, ...). The requirements were probably resolved \
+        for a different platform or python interpreter, resolve them again for the target environment"
+    )]
+    IncompatibleWheel {
+        wheel: WheelFilename,
+        supported_tags: String,
+    },
+
+    #[error("the {hook} install hook failed")]
+    InstallHookFailed {
+        hook: String,
+        #[source]
+        source: InstallHookError,
+    },
+
+    #[error("the project directory of an editable install must be an absolute path: {0}")]
+    InvalidEditableProject(String),
 }
 
 impl UnpackError {
@@ -555,7 +636,10 @@ impl UnpackError {
 /// Additional optional settings to pass to [`Wheel::unpack`].
 ///
 /// Not all options in this struct are relevant. Typically you will default a number of fields.
-#[derive(Default)]
+/// New options may be added in any release, construct the options from
+/// [`UnpackWheelOptions::default`] and set the fields that are needed.
+#[derive(Default, Clone)]
+#[non_exhaustive]
 pub struct UnpackWheelOptions {
     /// When specified an INSTALLER file is written to the dist-info folder of the package.
     /// INSTALLER files are used to track the installer of a package. See [PEP 376](https://peps.python.org/pep-0376/) for more information.
@@ -571,6 +655,70 @@ pub struct UnpackWheelOptions {
     /// If this field is `None` the architecture will be determined based on the architecture of the
     /// current process.
     pub launcher_arch: Option<WindowsLauncherArch>,
+
+    /// The tags supported by the target environment. When specified the wheel is only unpacked if
+    /// at least one of its tags is compatible with the environment. This catches resolutions that
+    /// were made for a different platform or interpreter before anything is written to disk.
+    pub compatible_tags: Option<WheelTags>,
+
+    /// When specified every file that is written is recorded in this log.
+    pub session_log: Option<SessionLog>,
+
+    /// Determines how scripts and entry points refer to the python interpreter. Use
+    /// [`ShebangStyle::Relative`] or [`ShebangStyle::Env`] for environments that are moved to a
+    /// different location after installation, e.g. when baking them into a container image.
+    pub shebang: ShebangStyle,
+
+    /// When specified the files of the wheel are stored in this content-addressable store and
+    /// hard linked into the environment instead of being written directly. See
+    /// [`ContentStore`] for more information.
+    pub content_store: Option<ContentStore>,
+
+    /// Callbacks that are invoked before and after the wheel is installed. When the wheel is
+    /// installed with [`unpack_wheels`] the transaction callbacks are invoked as well.
+    pub install_hooks: Option<Arc<dyn InstallHooks>>,
+
+    /// When specified the wheel is an editable wheel ([PEP 660](https://peps.python.org/pep-0660/))
+    /// of the project in this directory. A `direct_url.json` is written to the dist-info folder
+    /// that marks the installation as editable, see
+    /// [PEP 610](https://peps.python.org/pep-0610/). The path must be absolute.
+    ///
+    /// The `.pth` files and import hooks of the editable are installed like any other file of the
+    /// wheel and are tracked in the RECORD, so the installation can be uninstalled like a regular
+    /// one.
+    pub editable_project: Option<PathBuf>,
+
+    /// When specified the origin of the wheel is written to the dist-info folder, see
+    /// [`InstallProvenance`]. This tells whether the package was installed from a downloaded wheel
+    /// or from a wheel that was built locally.
+    pub provenance: Option<InstallProvenance>,
+
+    /// The number of threads that are used to extract the files of the wheel. If `None`, wheels
+    /// with many files are extracted on the available parallelism of the system and smaller
+    /// wheels are extracted on the calling thread.
+    pub extract_threads: Option<NonZeroUsize>,
+}
+
+/// Wheels with at least this many files are extracted on multiple threads, unless specified
+/// otherwise by [`UnpackWheelOptions::extract_threads`].
+const PARALLEL_EXTRACT_MIN_ENTRIES: usize = 512;
+
+/// Determines the shebang of the scripts and entry points that are installed from a wheel.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ShebangStyle {
+    /// Use the absolute path of the python interpreter, e.g. `#!/path/to/venv/bin/python`. This
+    /// is what pip does.
+    #[default]
+    Absolute,
+
+    /// Use `#!/usr/bin/env python` to execute scripts with the first python interpreter on the
+    /// `PATH`.
+    Env,
+
+    /// Locate the python interpreter relative to the script. On unix this writes a small shell
+    /// preamble that resolves the location of the script at runtime, so the environment keeps
+    /// working after it has been moved. Windows launchers always use the absolute path.
+    Relative,
 }
 
 #[derive(Debug)]
@@ -584,6 +732,23 @@ pub struct UnpackedWheel {
 }
 
 impl Wheel {
+    /// Checks that at least one of the tags of this wheel is supported by the target environment
+    /// described by `compatible_tags`.
+    pub fn check_compatibility(&self, compatible_tags: &WheelTags) -> Result<(), UnpackError> {
+        if self
+            .name
+            .all_tags_iter()
+            .any(|tag| compatible_tags.is_compatible(&tag))
+        {
+            return Ok(());
+        }
+
+        Err(UnpackError::IncompatibleWheel {
+            wheel: self.name.clone(),
+            supported_tags: compatible_tags.tags().take(3).format(", ").to_string(),
+        })
+    }
+
     /// Unpacks a wheel to the given filesystem.
     /// TODO: Write better docs.
     /// The following functionality is still missing:
@@ -601,6 +766,28 @@ impl Wheel {
         python_executable: &Path,
         options: &UnpackWheelOptions,
     ) -> Result<UnpackedWheel, UnpackError> {
+        if let Some(compatible_tags) = &options.compatible_tags {
+            self.check_compatibility(compatible_tags)?;
+        }
+
+        let editable_url = options
+            .editable_project
+            .as_deref()
+            .map(|project| {
+                url::Url::from_directory_path(project)
+                    .map_err(|_| UnpackError::InvalidEditableProject(project.display().to_string()))
+            })
+            .transpose()?;
+
+        if let Some(hooks) = &options.install_hooks {
+            hooks.before_install(&self.name, dest).map_err(|source| {
+                UnpackError::InstallHookFailed {
+                    hook: format!("before_install ({})", self.name),
+                    source,
+                }
+            })?;
+        }
+
         let vitals = self
             .get_vitals()
             .map_err(UnpackError::FailedToParseWheelVitals)?;
@@ -613,6 +800,8 @@ impl Wheel {
 
         let trampoline_maker = TrampolineMaker {
             python_executable: python_executable.to_path_buf(),
+            scripts_dir: dest.join(paths.scripts()),
+            shebang: options.shebang,
             kind: if paths.is_windows() {
                 TrampolineMakerKind::Windows {
                     arch: options.launcher_arch,
@@ -623,7 +812,7 @@ impl Wheel {
         };
 
         let site_packages = dest.join(paths.site_packages());
-        let mut archive = self.archive.lock();
+        let mut archive = self.archive.clone();
 
         // Read the RECORD file from the wheel
         let record_filename = format!("{}/RECORD", &vitals.dist_info);
@@ -638,14 +827,17 @@ impl Wheel {
         let scripts =
             Scripts::from_wheel(&mut archive, &vitals.dist_info, options.extras.as_ref())?;
 
-        let mut resulting_records = Vec::new();
-        for index in 0..archive.len() {
+        // Extracts a single entry of the archive and returns the RECORD entry of the file that was
+        // written, if any.
+        let unpack_entry = |archive: &mut ZipArchive<SharedReader>,
+                            index: usize|
+         -> Result<Option<RecordEntry>, UnpackError> {
             let mut zip_entry = archive
                 .by_index(index)
                 .map_err(|e| UnpackError::from_zip_error(format!("<index {index}>"), e))?;
             let Some(relative_path) = zip_entry.enclosed_name().map(ToOwned::to_owned) else {
                 // Skip invalid paths
-                continue;
+                return Ok(None);
             };
 
             // Skip the RECORD file itself. We will overwrite it at the end of this operation to
@@ -658,14 +850,14 @@ impl Wheel {
                 || relative_path == record_relative_path.with_extension("jws")
                 || relative_path == record_relative_path.with_extension("p7s")
             {
-                continue;
+                return Ok(None);
             }
 
             // Determine the destination path.
             let Some((relative_destination, is_script)) =
                 transformer.analyze_path(&relative_path)?
             else {
-                continue;
+                return Ok(None);
             };
             let destination = dest.join(relative_destination);
 
@@ -673,7 +865,7 @@ impl Wheel {
             if zip_entry.is_dir() {
                 fs::create_dir_all(&destination)
                     .map_err(|err| UnpackError::IoError(destination.display().to_string(), err))?;
-                continue;
+                return Ok(None);
             }
 
             // Determine if the entry is executable
@@ -685,7 +877,7 @@ impl Wheel {
             // If the file is a script
             let (size, encoded_hash) = if is_script {
                 if scripts.is_entrypoint_wrapper(&destination) {
-                    continue;
+                    return Ok(None);
                 }
 
                 // Use a BufReader to make it easy to peek at the first few bytes without actually
@@ -720,17 +912,26 @@ impl Wheel {
                     let relative_path = pathdiff::diff_paths(&destination, &site_packages).expect("can always create relative path from site-packages to the scripts directory");
                     let record =
                         write_generated_file(&relative_path, &site_packages, trampoline, true)?;
-                    resulting_records.push(record);
 
                     // The hash has most likely changed so we don't check it.
-                    continue;
+                    return Ok(Some(record));
                 } else {
                     // Otherwise copy the file verbatim
-                    write_wheel_file(&mut buf_reader, &destination, true)?
+                    write_wheel_file(
+                        &mut buf_reader,
+                        &destination,
+                        true,
+                        options.content_store.as_ref(),
+                    )?
                 }
             } else {
                 // Otherwise copy the file to its final destination.
-                write_wheel_file(&mut zip_entry, &destination, executable)?
+                write_wheel_file(
+                    &mut zip_entry,
+                    &destination,
+                    executable,
+                    options.content_store.as_ref(),
+                )?
             };
 
             // Make sure the hash matches with what we expect
@@ -765,7 +966,7 @@ impl Wheel {
                 }
 
                 // Store the hash
-                resulting_records.push(RecordEntry {
+                Ok(Some(RecordEntry {
                     path: pathdiff::diff_paths(&destination, &site_packages)
                         .unwrap_or_else(|| {
                             dunce::canonicalize(&destination).expect("failed to canonicalize path")
@@ -779,9 +980,75 @@ impl Wheel {
                         .replace('\\', "/"),
                     hash: Some(encoded_hash),
                     size,
-                })
+                }))
+            } else {
+                Ok(None)
             }
-        }
+        };
+
+        let entry_count = archive.len();
+        let threads = options
+            .extract_threads
+            .or_else(|| {
+                (entry_count >= PARALLEL_EXTRACT_MIN_ENTRIES)
+                    .then(|| std::thread::available_parallelism().ok())
+                    .flatten()
+            })
+            .map_or(1, NonZeroUsize::get)
+            .min(entry_count.max(1));
+
+        let mut resulting_records = if threads <= 1 {
+            let mut records = Vec::new();
+            for index in 0..entry_count {
+                if let Some(record) = unpack_entry(&mut archive, index)? {
+                    records.push(record);
+                }
+            }
+            records
+        } else {
+            // Large wheels are extracted on multiple threads. Every thread reads from its own clone
+            // of the archive and processes a contiguous range of entries. The records are
+            // concatenated in the order of the entries so the RECORD file is the same as when
+            // extracting sequentially.
+            let chunk_size = (entry_count + threads - 1) / threads;
+            let failed = AtomicBool::new(false);
+            let chunks = std::thread::scope(|s| {
+                let handles = (0..entry_count)
+                    .step_by(chunk_size)
+                    .map(|start| {
+                        let (unpack_entry, failed) = (&unpack_entry, &failed);
+                        let end = (start + chunk_size).min(entry_count);
+                        s.spawn(move || {
+                            let mut archive = self.archive.clone();
+                            let mut records = Vec::new();
+                            for index in start..end {
+                                if failed.load(Ordering::Relaxed) {
+                                    break;
+                                }
+                                match unpack_entry(&mut archive, index) {
+                                    Ok(Some(record)) => records.push(record),
+                                    Ok(None) => {}
+                                    Err(err) => {
+                                        failed.store(true, Ordering::Relaxed);
+                                        return Err(err);
+                                    }
+                                }
+                            }
+                            Ok(records)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("extraction thread panicked"))
+                    .collect::<Vec<_>>()
+            });
+            let mut records = Vec::new();
+            for chunk in chunks {
+                records.extend(chunk?);
+            }
+            records
+        };
 
         // Generate the script entrypoints
         write_script_entrypoint(
@@ -818,188 +1085,1120 @@ impl Wheel {
             )?);
         }
 
+        // Record where the wheel came from if requested
+        if let Some(provenance) = &options.provenance {
+            resulting_records.push(write_generated_file(
+                Path::new(&format!("{}/{PROVENANCE_FILE}", &vitals.dist_info)),
+                &site_packages,
+                serde_json::to_string_pretty(provenance)
+                    .expect("the provenance can always be serialized"),
+                false,
+            )?);
+        }
+
+        // Mark editable installs with a direct_url.json
+        if let Some(url) = editable_url {
+            let direct_url = serde_json::json!({
+                "url": url.as_str().trim_end_matches('/'),
+                "dir_info": { "editable": true },
+            });
+            resulting_records.push(write_generated_file(
+                Path::new(&format!("{}/direct_url.json", &vitals.dist_info)),
+                &site_packages,
+                direct_url.to_string(),
+                false,
+            )?);
+        }
+
+        if let Some(session_log) = &options.session_log {
+            for record in &resulting_records {
+                session_log.record(SessionEvent::FileOperation {
+                    operation: FileOperation::Write,
+                    path: site_packages.join(&record.path),
+                });
+            }
+        }
+
         // Write the resulting RECORD file
         Record::from_iter(resulting_records)
             .write_to_path(&site_packages.join(record_relative_path))?;
 
-        Ok(UnpackedWheel {
+        let unpacked = UnpackedWheel {
             dist_info: site_packages.join(&vitals.dist_info),
             metadata: vitals.metadata,
-        })
+        };
+
+        if let Some(hooks) = &options.install_hooks {
+            hooks
+                .after_install(&self.name, &unpacked)
+                .map_err(|source| UnpackError::InstallHookFailed {
+                    hook: format!("after_install ({})", self.name),
+                    source,
+                })?;
+        }
+
+        Ok(unpacked)
     }
 }
 
-/// Construct trampolines for entry-points.
-fn write_script_entrypoint(
-    dest: &Path,
-    install_paths: &InstallPaths,
-    entry_points: &Vec<EntryPoint>,
-    trampoline_maker: &TrampolineMaker,
-    launcher_type: LauncherType,
-    records: &mut Vec<RecordEntry>,
-) -> Result<(), UnpackError> {
-    // Make sure the script directory exists
-    let scripts_dir = dest.join(install_paths.scripts());
-    fs::create_dir_all(&scripts_dir)
-        .map_err(|err| UnpackError::IoError(scripts_dir.display().to_string(), err))?;
-
-    for entry_point in entry_points {
-        // Determine the name of the script
-        let script_name = if install_paths.is_windows() {
-            // Convert the entry point filename. We strip `.py` from the filename and add `.exe`.
-            Cow::Owned(format!(
-                "{}.exe",
-                entry_point
-                    .script_name
-                    .strip_suffix(".py")
-                    .unwrap_or(&entry_point.script_name)
-            ))
-        } else {
-            Cow::Borrowed(entry_point.script_name.as_str())
+impl Wheel {
+    /// Returns all entry points defined in the `entry_points.txt` file of the wheel, grouped by
+    /// their section (e.g. `console_scripts`). Within a group the entry points are sorted by name.
+    ///
+    /// Entry points that depend on extras are always included.
+    pub fn entry_points(&self) -> Result<IndexMap<String, Vec<EntryPoint>>, UnpackError> {
+        let vitals = self
+            .get_vitals()
+            .map_err(UnpackError::FailedToParseWheelVitals)?;
+        let mut archive = self.archive.clone();
+        let Some(mapping) = read_entry_points_ini(&mut archive, &vitals.dist_info)? else {
+            return Ok(IndexMap::new());
         };
 
-        // Construct the trampoline
-        let launch_script = entry_point.launch_script();
-        let trampoline =
-            trampoline_maker.make_trampoline(launcher_type, launch_script.as_bytes())?;
+        mapping
+            .into_iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(group, entries)| {
+                let mut entry_points = parse_entry_points_from_ini_section(entries, None)?;
+                entry_points.sort_by(|a, b| a.script_name.cmp(&b.script_name));
+                Ok((group, entry_points))
+            })
+            .collect()
+    }
 
-        // Write the launcher to the destination
-        let script_path = dest
-            .join(install_paths.scripts())
-            .join(script_name.as_ref());
-        let site_packages = dest.join(install_paths.site_packages());
-        let relative_path = pathdiff::diff_paths(script_path, &site_packages).expect("should always be able to create relative path from site-packages to the scripts directory");
-        let record = write_generated_file(&relative_path, &site_packages, &trampoline, true)?;
-        records.push(record)
+    /// Returns the console scripts that would be created when installing this wheel, sorted by
+    /// name.
+    pub fn console_scripts(&self) -> Result<Vec<EntryPoint>, UnpackError> {
+        Ok(self
+            .entry_points()?
+            .remove("console_scripts")
+            .unwrap_or_default())
     }
 
-    Ok(())
-}
+    /// Returns the gui scripts that would be created when installing this wheel, sorted by name.
+    pub fn gui_scripts(&self) -> Result<Vec<EntryPoint>, UnpackError> {
+        Ok(self
+            .entry_points()?
+            .remove("gui_scripts")
+            .unwrap_or_default())
+    }
 
-/// An object that can be used to generate trampolines.
-///
-/// Trampolines are executable that execute a certain python script using a certain python
-/// interpreter. They are used to launch entry points.
-///
-/// On unix based systems this simply creates a script with a python shebang. On windows this
-/// creates a separate executable that launches the python interpreter with the given script. See
-/// [`crate::launcher`] for more information.
-struct TrampolineMaker {
-    python_executable: PathBuf,
-    kind: TrampolineMakerKind,
-}
+    /// Returns the names of the top-level modules and packages provided by this wheel, sorted by
+    /// name.
+    ///
+    /// If the wheel contains a `top_level.txt` file (as written by setuptools) its contents are
+    /// returned. Otherwise the modules are derived from the files that would be installed into
+    /// site-packages.
+    pub fn top_level_modules(&self) -> Result<Vec<String>, UnpackError> {
+        let vitals = self
+            .get_vitals()
+            .map_err(UnpackError::FailedToParseWheelVitals)?;
 
-/// The type of trampoline to create
-enum TrampolineMakerKind {
-    Windows { arch: Option<WindowsLauncherArch> },
-    Unix,
+        let top_level_path = format!("{}/top_level.txt", &vitals.dist_info);
+        {
+            let mut archive = self.archive.clone();
+            match archive.by_name(&top_level_path) {
+                Ok(mut file) => {
+                    let mut contents = String::new();
+                    file.read_to_string(&mut contents)
+                        .map_err(|err| UnpackError::IoError(top_level_path.clone(), err))?;
+                    return Ok(contents
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(ToOwned::to_owned)
+                        .sorted()
+                        .dedup()
+                        .collect());
+                }
+                Err(ZipError::FileNotFound) => {}
+                Err(err) => return Err(UnpackError::from_zip_error(top_level_path, err)),
+            }
+        }
+
+        // Derive the modules from the files that end up in site-packages. The exact interpreter
+        // version does not matter here because we only look at paths relative to site-packages.
+        let paths = InstallPaths::for_venv((3, 0, 0), false);
+        let files = self.planned_files(&paths)?;
+        let modules = files
+            .keys()
+            .filter_map(|path| path.strip_prefix(paths.site_packages()).ok())
+            .filter_map(|path| {
+                let mut components = path.components();
+                let first = components.next()?.as_os_str().to_str()?;
+                if components.next().is_some() {
+                    // A package directory
+                    (!first.ends_with(".dist-info") && !first.ends_with(".data"))
+                        .then(|| first.to_owned())
+                } else {
+                    // A single file module, either a python source file or an extension module.
+                    let (module, extension) = first.split_once('.')?;
+                    let extension = extension.rsplit('.').next()?;
+                    matches!(extension, "py" | "so" | "pyd").then(|| module.to_owned())
+                }
+            })
+            .sorted()
+            .dedup()
+            .collect();
+        Ok(modules)
+    }
+
+    /// Returns the files, relative to the installation destination, that would be written when
+    /// unpacking this wheel with the given [`InstallPaths`], sorted by path. This includes the
+    /// generated entry point launchers and the RECORD file but not optional files like INSTALLER.
+    ///
+    /// This does not modify the filesystem.
+    pub fn installed_files(&self, paths: &InstallPaths) -> Result<Vec<PathBuf>, UnpackError> {
+        let vitals = self
+            .get_vitals()
+            .map_err(UnpackError::FailedToParseWheelVitals)?;
+        let mut files = self.planned_files(paths)?.into_keys().collect::<Vec<_>>();
+        files.push(
+            paths
+                .site_packages()
+                .join(format!("{}/RECORD", &vitals.dist_info)),
+        );
+        files.sort();
+        Ok(files)
+    }
 }
 
-impl TrampolineMaker {
-    /// Returns the bytes of a launcher executable/script that can be used to launch the given
-    /// script.
-    pub fn make_trampoline(
-        &self,
-        launcher_type: LauncherType,
-        script: &[u8],
-    ) -> Result<Vec<u8>, UnpackError> {
-        let shebang = get_shebang(&self.python_executable);
-        match self.kind {
-            TrampolineMakerKind::Windows { arch } => {
-                let arch = match arch {
-                    Some(windows_launcher_arch) => windows_launcher_arch,
-                    None => match WindowsLauncherArch::current() {
-                        Some(arch) => arch,
-                        None => return Err(UnpackError::UnsupportedWindowsArchitecture),
-                    },
-                };
+/// Describes the content of a file that will be written when unpacking a wheel. Used to determine
+/// whether two wheels that write to the same file actually conflict.
+#[derive(Debug, Clone)]
+enum PlannedContent {
+    /// A file from the archive with the hash recorded in the RECORD file.
+    Archive(Option<String>),
 
-                Ok(build_windows_launcher(
-                    &shebang,
-                    script,
-                    arch,
-                    launcher_type,
-                ))
-            }
-            TrampolineMakerKind::Unix => {
-                let mut bytes = format!("{}\n", shebang).into_bytes();
-                bytes.extend_from_slice(script);
-                Ok(bytes)
-            }
+    /// A launcher that is generated for an entry point.
+    EntryPoint(String),
+}
+
+impl PlannedContent {
+    /// Returns true if both files are known to have the same content.
+    fn is_same_content(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PlannedContent::Archive(Some(a)), PlannedContent::Archive(Some(b))) => a == b,
+            (PlannedContent::EntryPoint(a), PlannedContent::EntryPoint(b)) => a == b,
+            _ => false,
         }
     }
 }
 
-/// Returns the shebang to use when calling a python script.
-/// TODO: In the future we should make this much more configurable. This is much more complex in pip:
-///  <https://github.com/pypa/pip/blob/7f8a6844037fb7255cfd0d34ff8e8cf44f2598d4/src/pip/_vendor/distlib/scripts.py#L158>
-fn get_shebang(python_executable: &Path) -> String {
-    format!(r"#!{}", dunce::simplified(python_executable).display())
+/// A file that would be written by more than one wheel with different content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileConflict {
+    /// The path of the file relative to the installation destination.
+    pub path: PathBuf,
+
+    /// The wheels that would write to the file, in the order in which they were specified.
+    pub wheels: Vec<WheelFilename>,
 }
 
-/// The scripts that should be installed as part of the wheel installation.
-#[derive(Debug, Default)]
-struct Scripts {
-    console_scripts: Vec<EntryPoint>,
-    gui_scripts: Vec<EntryPoint>,
+impl Display for FileConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (written by {})",
+            self.path.display(),
+            self.wheels.iter().format(", ")
+        )
+    }
 }
 
-impl Scripts {
-    /// Read the `entry_points.txt` file from the wheel archive and parse the scripts.
-    pub fn from_wheel(
-        archive: &mut ZipArchive<Box<dyn ReadAndSeek + Send>>,
-        dist_info_prefix: &str,
-        extras: Option<&HashSet<Extra>>,
-    ) -> Result<Self, UnpackError> {
-        // Read the `entry_points.txt` file from the archive
-        let entry_points_path = format!("{dist_info_prefix}/entry_points.txt");
-        let mut entry_points_file = match archive.by_name(&entry_points_path) {
-            Err(ZipError::FileNotFound) => return Ok(Default::default()),
-            Ok(file) => file,
-            Err(err) => return Err(UnpackError::from_zip_error(entry_points_path, err)),
-        };
+impl Wheel {
+    /// Returns all the files, relative to the installation destination, that would be written when
+    /// unpacking this wheel together with a description of their content. Files that are
+    /// regenerated during unpacking (like the RECORD file) are not included.
+    fn planned_files(
+        &self,
+        paths: &InstallPaths,
+    ) -> Result<HashMap<PathBuf, PlannedContent>, UnpackError> {
+        let vitals = self
+            .get_vitals()
+            .map_err(UnpackError::FailedToParseWheelVitals)?;
 
-        // Parse the `entry_points.txt` file as an ini file.
-        let mut entry_points_mapping = {
-            let mut ini_contents = String::new();
-            entry_points_file
-                .read_to_string(&mut ini_contents)
-                .map_err(|err| {
-                    UnpackError::EntryPointsInvalid(format!(
-                        "failed to read entry_points.txt contents: {}",
-                        err
-                    ))
-                })?;
-            Ini::new_cs().read(ini_contents).map_err(|err| {
-                UnpackError::EntryPointsInvalid(format!(
-                    "failed to parse entry_points.txt contents: {}",
-                    err
-                ))
-            })?
+        let transformer = WheelPathTransformer {
+            data: vitals.data,
+            root_is_purelib: vitals.root_is_purelib,
+            paths,
         };
 
-        // Parse the script entry points
-        let console_scripts = entry_points_mapping
-            .remove("console_scripts")
-            .map(|e| parse_entry_points_from_ini_section(e, extras))
-            .transpose()?
-            .unwrap_or_default();
-
-        let gui_scripts = entry_points_mapping
-            .remove("gui_scripts")
-            .map(|e| parse_entry_points_from_ini_section(e, extras))
-            .transpose()?
-            .unwrap_or_default();
+        let mut archive = self.archive.clone();
 
-        Ok(Scripts {
-            console_scripts,
-            gui_scripts,
-        })
-    }
+        let record_filename = format!("{}/RECORD", &vitals.dist_info);
+        let record = Record::from_reader(
+            &mut archive
+                .by_name(&record_filename)
+                .map_err(|err| WheelVitalsError::from_zip(record_filename.clone(), err))?,
+        )?;
+        let record_relative_path = Path::new(&record_filename);
 
-    /// Returns true if there is an entry point script with the given name.
-    pub fn contains(&self, name: &str) -> bool {
-        self.console_scripts.iter().any(|e| e.script_name == name)
+        let mut files = HashMap::new();
+        for index in 0..archive.len() {
+            let zip_entry = archive
+                .by_index_raw(index)
+                .map_err(|e| UnpackError::from_zip_error(format!("<index {index}>"), e))?;
+            if zip_entry.is_dir() {
+                continue;
+            }
+            let Some(relative_path) = zip_entry.enclosed_name() else {
+                continue;
+            };
+            if relative_path == record_relative_path
+                || relative_path == record_relative_path.with_extension("jws")
+                || relative_path == record_relative_path.with_extension("p7s")
+            {
+                continue;
+            }
+            let Some((destination, _)) = transformer.analyze_path(relative_path)? else {
+                continue;
+            };
+
+            let relative_path_string = relative_path.display().to_string();
+            let hash = record
+                .iter()
+                .find(|entry| entry.path.trim_start_matches('/') == relative_path_string)
+                .and_then(|entry| entry.hash.clone());
+            files.insert(destination, PlannedContent::Archive(hash));
+        }
+
+        // Entry points are generated and not part of the archive. We don't filter by extras here
+        // to be conservative.
+        let scripts = Scripts::from_wheel(&mut archive, &vitals.dist_info, None)?;
+        for entry_point in scripts
+            .console_scripts
+            .iter()
+            .chain(scripts.gui_scripts.iter())
+        {
+            files.insert(
+                paths
+                    .scripts()
+                    .join(script_file_name(paths, entry_point).as_ref()),
+                PlannedContent::EntryPoint(entry_point.launch_script()),
+            );
+        }
+
+        Ok(files)
+    }
+
+    /// Returns the number of entries in the archive. Used as a rough estimate of how much work it
+    /// is to unpack the wheel.
+    fn entry_count(&self) -> usize {
+        self.archive.len()
+    }
+}
+
+/// The severity of a [`WheelValidationFinding`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ValidationSeverity {
+    /// The wheel is unusual but can be installed
+    Warning,
+    /// The wheel is broken and should not be installed
+    Error,
+}
+
+/// A problem with the internal consistency of a wheel, see [`Wheel::validate`].
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum WheelValidationFinding {
+    /// The `.dist-info` directory could not be found
+    #[error("the .dist-info directory could not be determined: {0}")]
+    DistInfoMissing(String),
+
+    /// The archive contains an entry with an absolute path or a path outside of the archive
+    #[error("the archive contains the unsafe path {0}")]
+    UnsafePath(String),
+
+    /// The `WHEEL` file is missing or cannot be parsed
+    #[error("invalid WHEEL file: {0}")]
+    InvalidWheelFile(String),
+
+    /// The `Wheel-Version` of the `WHEEL` file is not supported
+    #[error("unsupported Wheel-Version {0}")]
+    UnsupportedWheelVersion(String),
+
+    /// The `Wheel-Version` is newer than the version this library was written for
+    #[error("Wheel-Version {0} is newer than 1.0")]
+    NewerWheelVersion(String),
+
+    /// The tags listed in the `WHEEL` file don't match the tags in the filename
+    #[error("the tags in the WHEEL file ({wheel_file}) don't match the filename ({filename})")]
+    TagMismatch {
+        /// The tags listed in the `WHEEL` file
+        wheel_file: String,
+        /// The tags of the filename
+        filename: String,
+    },
+
+    /// A tag does not make sense, e.g. a platform specific abi for any platform
+    #[error("suspicious tag {tag}: {reason}")]
+    SuspiciousTag {
+        /// The tag
+        tag: String,
+        /// Why the tag is suspicious
+        reason: String,
+    },
+
+    /// The `METADATA` file is missing or cannot be parsed
+    #[error("invalid METADATA file: {0}")]
+    InvalidMetadata(String),
+
+    /// The name in the `METADATA` file does not match the filename
+    #[error("the name in METADATA ({metadata}) does not match the filename ({filename})")]
+    NameMismatch {
+        /// The name in the `METADATA` file
+        metadata: String,
+        /// The name in the filename
+        filename: String,
+    },
+
+    /// The version in the `METADATA` file does not match the filename
+    #[error("the version in METADATA ({metadata}) does not match the filename ({filename})")]
+    VersionMismatch {
+        /// The version in the `METADATA` file
+        metadata: Version,
+        /// The version in the filename
+        filename: Version,
+    },
+
+    /// The `RECORD` file is missing or cannot be parsed
+    #[error("invalid RECORD file: {0}")]
+    InvalidRecord(String),
+
+    /// A file in the archive is not listed in the `RECORD` file
+    #[error("{0} is not listed in RECORD")]
+    NotInRecord(String),
+
+    /// A file that is listed in the `RECORD` file is missing from the archive
+    #[error("{0} is listed in RECORD but missing from the archive")]
+    MissingFromArchive(String),
+
+    /// A file in the `RECORD` file does not have a hash
+    #[error("{0} does not have a hash in RECORD")]
+    MissingHash(String),
+
+    /// The hash of a file does not match the hash in the `RECORD` file
+    #[error("the hash of {0} does not match RECORD")]
+    HashMismatch(String),
+
+    /// The size of a file does not match the size in the `RECORD` file
+    #[error("the size of {0} does not match RECORD")]
+    SizeMismatch(String),
+
+    /// A file is hashed with an algorithm other than sha256 and is therefore not verified
+    #[error("the hash of {path} uses {algorithm} and cannot be verified")]
+    UnverifiedHash {
+        /// The path of the file
+        path: String,
+        /// The hash algorithm
+        algorithm: String,
+    },
+}
+
+impl WheelValidationFinding {
+    /// Returns the severity of the finding.
+    pub fn severity(&self) -> ValidationSeverity {
+        match self {
+            WheelValidationFinding::NewerWheelVersion(_)
+            | WheelValidationFinding::TagMismatch { .. }
+            | WheelValidationFinding::SuspiciousTag { .. }
+            | WheelValidationFinding::UnverifiedHash { .. } => ValidationSeverity::Warning,
+            _ => ValidationSeverity::Error,
+        }
+    }
+}
+
+impl Wheel {
+    /// Checks the internal consistency of the wheel: the `WHEEL` file and its version, whether the
+    /// `RECORD` file lists every file in the archive with the correct hash and size, whether the
+    /// name and version in `METADATA` match the filename and whether the tags make sense.
+    ///
+    /// Returns all problems that were found, an empty list means the wheel is consistent. This is
+    /// useful before installing third-party wheels and after building wheels from sdists.
+    pub fn validate(&self) -> Vec<WheelValidationFinding> {
+        let mut findings = Vec::new();
+        let mut archive = self.archive.clone();
+
+        let top_level_names = archive
+            .file_names()
+            .map(|filename| {
+                filename
+                    .split_once(['/', '\\'])
+                    .map_or_else(|| filename, |(base, _)| base)
+                    .to_owned()
+            })
+            .collect::<HashSet<_>>();
+        let dist_info = match Wheel::find_special_wheel_dir(
+            top_level_names.iter().map(String::as_str),
+            &self.name.distribution,
+            &self.name.version,
+            ".dist-info",
+        ) {
+            Ok(Some(dist_info)) => dist_info.to_owned(),
+            Ok(None) => {
+                findings.push(WheelValidationFinding::DistInfoMissing(format!(
+                    "expected {}-{}.dist-info",
+                    self.name.distribution.as_source_str(),
+                    self.name.version
+                )));
+                return findings;
+            }
+            Err(err) => {
+                findings.push(WheelValidationFinding::DistInfoMissing(err.to_string()));
+                return findings;
+            }
+        };
+
+        match read_entry_to_end(&mut archive, &format!("{dist_info}/WHEEL")) {
+            Ok(bytes) => self.validate_wheel_file(&bytes, &mut findings),
+            Err(err) => findings.push(WheelValidationFinding::InvalidWheelFile(err.to_string())),
+        }
+        self.validate_tags(&mut findings);
+
+        match read_entry_to_end(&mut archive, &format!("{dist_info}/METADATA"))
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                WheelCoreMetadata::try_from(bytes.as_slice()).map_err(|err| err.to_string())
+            }) {
+            Ok(metadata) => {
+                if metadata.name != self.name.distribution {
+                    findings.push(WheelValidationFinding::NameMismatch {
+                        metadata: metadata.name.as_source_str().to_owned(),
+                        filename: self.name.distribution.as_source_str().to_owned(),
+                    });
+                }
+                if metadata.version != self.name.version {
+                    findings.push(WheelValidationFinding::VersionMismatch {
+                        metadata: metadata.version,
+                        filename: self.name.version.clone(),
+                    });
+                }
+            }
+            Err(err) => findings.push(WheelValidationFinding::InvalidMetadata(err)),
+        }
+
+        validate_record(&mut archive, &dist_info, &mut findings);
+
+        findings
+    }
+
+    /// Validates the contents of the `WHEEL` file.
+    fn validate_wheel_file(&self, bytes: &[u8], findings: &mut Vec<WheelValidationFinding>) {
+        let mut parsed = match RFC822ish::from_str(&String::from_utf8_lossy(bytes)) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                findings.push(WheelValidationFinding::InvalidWheelFile(err.to_string()));
+                return;
+            }
+        };
+
+        match parsed.take("Wheel-Version") {
+            Ok(version) if version == "1.0" => {}
+            Ok(version) if version.starts_with("1.") => {
+                findings.push(WheelValidationFinding::NewerWheelVersion(version))
+            }
+            Ok(version) => findings.push(WheelValidationFinding::UnsupportedWheelVersion(version)),
+            Err(_) => findings.push(WheelValidationFinding::InvalidWheelFile(String::from(
+                "missing Wheel-Version",
+            ))),
+        }
+
+        match parsed.take("Root-Is-Purelib").as_deref() {
+            Ok("true") => {
+                if let Some(platform) = self.name.arch_tags.iter().find(|arch| *arch != "any") {
+                    findings.push(WheelValidationFinding::SuspiciousTag {
+                        tag: platform.clone(),
+                        reason: String::from(
+                            "the wheel is pure python (Root-Is-Purelib) but platform specific",
+                        ),
+                    });
+                }
+            }
+            Ok("false") => {}
+            Ok(other) => findings.push(WheelValidationFinding::InvalidWheelFile(format!(
+                "expected 'true' or 'false' for Root-Is-Purelib, not {other}"
+            ))),
+            Err(_) => findings.push(WheelValidationFinding::InvalidWheelFile(String::from(
+                "missing Root-Is-Purelib",
+            ))),
+        }
+
+        // The Tag field is optional, but if present it should match the filename.
+        let wheel_file_tags = parsed.take_all("Tag");
+        if !wheel_file_tags.is_empty() {
+            let wheel_file_tags = wheel_file_tags.into_iter().collect::<HashSet<_>>();
+            let filename_tags = self
+                .name
+                .all_tags_iter()
+                .map(|tag| tag.to_string())
+                .collect::<HashSet<_>>();
+            if wheel_file_tags != filename_tags {
+                findings.push(WheelValidationFinding::TagMismatch {
+                    wheel_file: wheel_file_tags.iter().sorted().join(", "),
+                    filename: filename_tags.iter().sorted().join(", "),
+                });
+            }
+        }
+    }
+
+    /// Checks the tags of the filename for combinations that don't make sense.
+    fn validate_tags(&self, findings: &mut Vec<WheelValidationFinding>) {
+        for tag in self.name.all_tags_iter().unique() {
+            let reason = if tag.abi != "none" && tag.platform == "any" {
+                Some("an abi specific wheel must be platform specific")
+            } else if tag.abi == "abi3" && !tag.interpreter.starts_with("cp") {
+                Some("the abi3 abi is only defined for CPython")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                findings.push(WheelValidationFinding::SuspiciousTag {
+                    tag: tag.to_string(),
+                    reason: reason.to_owned(),
+                });
+            }
+        }
+    }
+}
+
+/// Checks that the `RECORD` file lists every file in the archive with the correct hash and size.
+fn validate_record<R: ReadAndSeek>(
+    archive: &mut ZipArchive<R>,
+    dist_info: &str,
+    findings: &mut Vec<WheelValidationFinding>,
+) {
+    let record_filename = format!("{dist_info}/RECORD");
+    let record = match archive
+        .by_name(&record_filename)
+        .map_err(|err| err.to_string())
+        .and_then(|mut entry| Record::from_reader(&mut entry).map_err(|err| err.to_string()))
+    {
+        Ok(record) => record,
+        Err(err) => {
+            findings.push(WheelValidationFinding::InvalidRecord(err));
+            return;
+        }
+    };
+    let mut recorded = record
+        .iter()
+        .map(|entry| (entry.path.trim_start_matches('/').to_owned(), entry))
+        .collect::<HashMap<_, _>>();
+
+    for index in 0..archive.len() {
+        let mut entry = match archive.by_index(index) {
+            Ok(entry) => entry,
+            Err(err) => {
+                findings.push(WheelValidationFinding::InvalidRecord(err.to_string()));
+                continue;
+            }
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let path = entry.name().to_owned();
+        if entry.enclosed_name().is_none() {
+            findings.push(WheelValidationFinding::UnsafePath(path));
+            continue;
+        }
+
+        // The RECORD file and its signatures are not listed in RECORD.
+        if path == record_filename
+            || path == format!("{record_filename}.jws")
+            || path == format!("{record_filename}.p7s")
+        {
+            recorded.remove(&path);
+            continue;
+        }
+
+        let Some(record_entry) = recorded.remove(&path) else {
+            findings.push(WheelValidationFinding::NotInRecord(path));
+            continue;
+        };
+        let Some((algorithm, expected_hash)) = record_entry
+            .hash
+            .as_deref()
+            .and_then(|hash| hash.split_once('='))
+        else {
+            findings.push(WheelValidationFinding::MissingHash(path));
+            continue;
+        };
+        if algorithm != "sha256" {
+            findings.push(WheelValidationFinding::UnverifiedHash {
+                path,
+                algorithm: algorithm.to_owned(),
+            });
+            continue;
+        }
+
+        let mut reader = rattler_digest::HashingReader::<_, Sha256>::new(&mut entry);
+        let size = match std::io::copy(&mut reader, &mut std::io::sink()) {
+            Ok(size) => size,
+            Err(err) => {
+                findings.push(WheelValidationFinding::InvalidRecord(format!(
+                    "failed to read {path}: {err}"
+                )));
+                continue;
+            }
+        };
+        let (_, digest) = reader.finalize();
+        if BASE64URL_NOPAD.encode(&digest) != expected_hash {
+            findings.push(WheelValidationFinding::HashMismatch(path));
+        } else if record_entry.size.is_some_and(|expected| expected != size) {
+            findings.push(WheelValidationFinding::SizeMismatch(path));
+        }
+    }
+
+    findings.extend(
+        recorded
+            .into_keys()
+            .sorted()
+            .map(WheelValidationFinding::MissingFromArchive),
+    );
+}
+
+/// Returns the top-level files and directories in `files`. For files inside site-packages this is
+/// the top-level package directory or module, for all other files this is the path of the file
+/// itself.
+///
+/// This is used to determine which wheels cannot safely be unpacked concurrently.
+fn install_roots<'a>(
+    files: impl IntoIterator<Item = &'a PathBuf>,
+    paths: &InstallPaths,
+) -> HashSet<PathBuf> {
+    files
+        .into_iter()
+        .filter_map(
+            |destination| match destination.strip_prefix(paths.site_packages()) {
+                Ok(in_site_packages) => in_site_packages
+                    .components()
+                    .next()
+                    .map(|first| paths.site_packages().join(first)),
+                Err(_) => Some(destination.clone()),
+            },
+        )
+        .collect()
+}
+
+/// Finds all files that would be written by more than one wheel with different content.
+fn find_conflicts(
+    wheels: &[&Wheel],
+    planned_files: &[HashMap<PathBuf, PlannedContent>],
+) -> Vec<FileConflict> {
+    let mut writers: HashMap<&PathBuf, Vec<(usize, &PlannedContent)>> = HashMap::new();
+    for (idx, files) in planned_files.iter().enumerate() {
+        for (path, content) in files {
+            writers.entry(path).or_default().push((idx, content));
+        }
+    }
+
+    let mut conflicts = writers
+        .into_iter()
+        .filter(|(_, writers)| {
+            writers
+                .iter()
+                .tuple_combinations()
+                .any(|((_, a), (_, b))| !a.is_same_content(b))
+        })
+        .map(|(path, mut writers)| {
+            writers.sort_by_key(|(idx, _)| *idx);
+            FileConflict {
+                path: path.clone(),
+                wheels: writers
+                    .into_iter()
+                    .map(|(idx, _)| wheels[idx].name.clone())
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    conflicts
+}
+
+/// Determines which files would be written by more than one of the given wheels with different
+/// content when unpacking them into the same destination. Files with identical content, like the
+/// `__init__.py` of a shared namespace package, are not considered to be conflicting.
+///
+/// This does not modify the filesystem.
+pub fn find_file_conflicts(
+    wheels: &[&Wheel],
+    paths: &InstallPaths,
+) -> Result<Vec<FileConflict>, UnpackError> {
+    let planned_files = wheels
+        .iter()
+        .map(|wheel| wheel.planned_files(paths))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(find_conflicts(wheels, &planned_files))
+}
+
+/// Unpacks multiple wheels into the same destination concurrently.
+///
+/// Wheels that write to the same top-level files or directories (e.g. packages that share a
+/// namespace package) are unpacked one after another in the order in which they are specified,
+/// so overlapping files end up exactly as if all wheels were unpacked sequentially. All other
+/// wheels are unpacked in parallel on up to `concurrency` threads, starting with the largest
+/// wheels. If `concurrency` is `None` the available parallelism of the system is used.
+///
+/// Before anything is written, the wheels are checked for files that would be written by more
+/// than one wheel with different content. If any are found [`UnpackError::FileConflicts`] is
+/// returned and the destination is left untouched. See [`find_file_conflicts`].
+///
+/// The unpacked wheels are returned in the same order as the input. If unpacking any of the
+/// wheels fails no new wheels are started and the first error is returned.
+pub fn unpack_wheels(
+    wheels: &[(&Wheel, &UnpackWheelOptions)],
+    dest: &Path,
+    paths: &InstallPaths,
+    python_executable: &Path,
+    concurrency: Option<NonZeroUsize>,
+) -> Result<Vec<UnpackedWheel>, UnpackError> {
+    // Make sure all wheels can be installed in the target environment before touching the
+    // filesystem.
+    for (wheel, options) in wheels {
+        if let Some(compatible_tags) = &options.compatible_tags {
+            wheel.check_compatibility(compatible_tags)?;
+        }
+    }
+
+    // Make sure that no two wheels overwrite each others files before touching the filesystem.
+    let planned_files = wheels
+        .iter()
+        .map(|(wheel, _)| wheel.planned_files(paths))
+        .collect::<Result<Vec<_>, _>>()?;
+    let conflicts = find_conflicts(
+        &wheels.iter().map(|(wheel, _)| *wheel).collect::<Vec<_>>(),
+        &planned_files,
+    );
+    if !conflicts.is_empty() {
+        return Err(UnpackError::FileConflicts(conflicts));
+    }
+
+    // Invoke the transaction hooks. Wheels usually share the same hooks, they are only invoked
+    // once per set of hooks.
+    let mut transaction_hooks: Vec<&Arc<dyn InstallHooks>> = Vec::new();
+    for hooks in wheels
+        .iter()
+        .filter_map(|(_, options)| options.install_hooks.as_ref())
+    {
+        if !transaction_hooks
+            .iter()
+            .any(|existing| Arc::ptr_eq(existing, hooks))
+        {
+            transaction_hooks.push(hooks);
+        }
+    }
+    let wheel_names = wheels
+        .iter()
+        .map(|(wheel, _)| &wheel.name)
+        .collect::<Vec<_>>();
+    for hooks in transaction_hooks.iter() {
+        hooks.before_transaction(&wheel_names).map_err(|source| {
+            UnpackError::InstallHookFailed {
+                hook: String::from("before_transaction"),
+                source,
+            }
+        })?;
+    }
+
+    // Group together all wheels that write to the same locations. Wheels in the same group are
+    // unpacked sequentially.
+    let mut group_of_wheel = (0..wheels.len()).collect::<Vec<_>>();
+    let mut owners: HashMap<PathBuf, usize> = HashMap::new();
+    for (idx, files) in planned_files.iter().enumerate() {
+        for root in install_roots(files.keys(), paths) {
+            let owner = *owners.entry(root).or_insert(idx);
+            let (a, b) = (
+                find_group(&mut group_of_wheel, owner),
+                find_group(&mut group_of_wheel, idx),
+            );
+            if a != b {
+                // Always keep the lowest index as the representative so groups stay ordered.
+                group_of_wheel[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_index: HashMap<usize, usize> = HashMap::new();
+    for idx in 0..wheels.len() {
+        let root = find_group(&mut group_of_wheel, idx);
+        let group = *group_index.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(idx);
+    }
+
+    // Start with the largest groups so they don't end up as stragglers at the end.
+    groups.sort_by_cached_key(|group| {
+        std::cmp::Reverse(
+            group
+                .iter()
+                .map(|&idx| wheels[idx].0.entry_count())
+                .sum::<usize>(),
+        )
+    });
+
+    let concurrency = concurrency
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(groups.len());
+
+    let next_group = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results = Mutex::new((0..wheels.len()).map(|_| None).collect::<Vec<_>>());
+    let first_error = Mutex::new(None);
+
+    std::thread::scope(|s| {
+        for _ in 0..concurrency {
+            s.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let Some(group) = groups.get(next_group.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    for &idx in group {
+                        let (wheel, options) = wheels[idx];
+                        match wheel.unpack(dest, paths, python_executable, options) {
+                            Ok(unpacked) => results.lock()[idx] = Some(unpacked),
+                            Err(err) => {
+                                failed.store(true, Ordering::Relaxed);
+                                first_error.lock().get_or_insert(err);
+                                return;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(err) = first_error.into_inner() {
+        return Err(err);
+    }
+
+    let unpacked = results
+        .into_inner()
+        .into_iter()
+        .map(|unpacked| unpacked.expect("all wheels have been unpacked"))
+        .collect::<Vec<_>>();
+
+    for hooks in transaction_hooks {
+        hooks
+            .after_transaction(&unpacked)
+            .map_err(|source| UnpackError::InstallHookFailed {
+                hook: String::from("after_transaction"),
+                source,
+            })?;
+    }
+
+    Ok(unpacked)
+}
+
+/// Finds the representative of the group `idx` belongs to.
+fn find_group(groups: &mut [usize], idx: usize) -> usize {
+    let mut root = idx;
+    while groups[root] != root {
+        root = groups[root];
+    }
+    groups[idx] = root;
+    root
+}
+
+/// Returns the filename of the launcher that is created for the given entry point.
+fn script_file_name<'a>(install_paths: &InstallPaths, entry_point: &'a EntryPoint) -> Cow<'a, str> {
+    if install_paths.is_windows() {
+        // Convert the entry point filename. We strip `.py` from the filename and add `.exe`.
+        Cow::Owned(format!(
+            "{}.exe",
+            entry_point
+                .script_name
+                .strip_suffix(".py")
+                .unwrap_or(&entry_point.script_name)
+        ))
+    } else {
+        Cow::Borrowed(entry_point.script_name.as_str())
+    }
+}
+
+/// Construct trampolines for entry-points.
+fn write_script_entrypoint(
+    dest: &Path,
+    install_paths: &InstallPaths,
+    entry_points: &Vec<EntryPoint>,
+    trampoline_maker: &TrampolineMaker,
+    launcher_type: LauncherType,
+    records: &mut Vec<RecordEntry>,
+) -> Result<(), UnpackError> {
+    // Make sure the script directory exists
+    let scripts_dir = dest.join(install_paths.scripts());
+    fs::create_dir_all(&scripts_dir)
+        .map_err(|err| UnpackError::IoError(scripts_dir.display().to_string(), err))?;
+
+    for entry_point in entry_points {
+        // Determine the name of the script
+        let script_name = script_file_name(install_paths, entry_point);
+
+        // Construct the trampoline
+        let launch_script = entry_point.launch_script();
+        let trampoline =
+            trampoline_maker.make_trampoline(launcher_type, launch_script.as_bytes())?;
+
+        // Write the launcher to the destination
+        let script_path = dest
+            .join(install_paths.scripts())
+            .join(script_name.as_ref());
+        let site_packages = dest.join(install_paths.site_packages());
+        let relative_path = pathdiff::diff_paths(script_path, &site_packages).expect("should always be able to create relative path from site-packages to the scripts directory");
+        let record = write_generated_file(&relative_path, &site_packages, &trampoline, true)?;
+        records.push(record)
+    }
+
+    Ok(())
+}
+
+/// An object that can be used to generate trampolines.
+///
+/// Trampolines are executable that execute a certain python script using a certain python
+/// interpreter. They are used to launch entry points.
+///
+/// On unix based systems this simply creates a script with a python shebang. On windows this
+/// creates a separate executable that launches the python interpreter with the given script. See
+/// [`crate::launcher`] for more information.
+struct TrampolineMaker {
+    python_executable: PathBuf,
+    scripts_dir: PathBuf,
+    shebang: ShebangStyle,
+    kind: TrampolineMakerKind,
+}
+
+/// The type of trampoline to create
+enum TrampolineMakerKind {
+    Windows { arch: Option<WindowsLauncherArch> },
+    Unix,
+}
+
+impl TrampolineMaker {
+    /// Returns the bytes of a launcher executable/script that can be used to launch the given
+    /// script.
+    pub fn make_trampoline(
+        &self,
+        launcher_type: LauncherType,
+        script: &[u8],
+    ) -> Result<Vec<u8>, UnpackError> {
+        match self.kind {
+            TrampolineMakerKind::Windows { arch } => {
+                // Windows launchers cannot resolve paths relative to the script.
+                let shebang = match self.shebang {
+                    ShebangStyle::Env => get_env_shebang(),
+                    ShebangStyle::Absolute | ShebangStyle::Relative => {
+                        get_shebang(&self.python_executable)
+                    }
+                };
+                let arch = match arch {
+                    Some(windows_launcher_arch) => windows_launcher_arch,
+                    None => match WindowsLauncherArch::current() {
+                        Some(arch) => arch,
+                        None => return Err(UnpackError::UnsupportedWindowsArchitecture),
+                    },
+                };
+
+                Ok(build_windows_launcher(
+                    &shebang,
+                    script,
+                    arch,
+                    launcher_type,
+                ))
+            }
+            TrampolineMakerKind::Unix => {
+                let shebang = match self.shebang {
+                    ShebangStyle::Absolute => get_shebang(&self.python_executable),
+                    ShebangStyle::Env => get_env_shebang(),
+                    ShebangStyle::Relative => {
+                        get_relative_shebang(&self.python_executable, &self.scripts_dir)
+                    }
+                };
+                let mut bytes = format!("{}\n", shebang).into_bytes();
+                bytes.extend_from_slice(script);
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// Returns the shebang to use when calling a python script.
+/// TODO: In the future we should make this much more configurable. This is much more complex in pip:
+///  <https://github.com/pypa/pip/blob/7f8a6844037fb7255cfd0d34ff8e8cf44f2598d4/src/pip/_vendor/distlib/scripts.py#L158>
+fn get_shebang(python_executable: &Path) -> String {
+    format!(r"#!{}", dunce::simplified(python_executable).display())
+}
+
+/// Returns a shebang that executes the first python interpreter on the `PATH`.
+fn get_env_shebang() -> String {
+    String::from("#!/usr/bin/env python")
+}
+
+/// Returns a shebang that locates the python interpreter relative to the directory of the script.
+///
+/// Shebangs cannot contain relative paths, so instead the script is started with `/bin/sh` which
+/// re-executes the script with the python interpreter. The shell code is wrapped in a string
+/// literal that python ignores. The directory of the script is resolved with `pwd -P` because
+/// `realpath` is not available everywhere, e.g. on older versions of macOS.
+fn get_relative_shebang(python_executable: &Path, scripts_dir: &Path) -> String {
+    match pathdiff::diff_paths(python_executable, scripts_dir) {
+        Some(relative_python) if relative_python.is_relative() => format!(
+            "#!/bin/sh\n'''exec' \"$(cd -- \"$(dirname -- \"$0\")\" && pwd -P)\"/{} \"$0\" \"$@\"\n' '''",
+            shell_quote(&relative_python.display().to_string())
+        ),
+        _ => get_shebang(python_executable),
+    }
+}
+
+/// Quotes `value` for a POSIX shell. Single quotes in the value end the quoted string, add an
+/// escaped quote and start a new quoted string.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The scripts that should be installed as part of the wheel installation.
+#[derive(Debug, Default)]
+struct Scripts {
+    console_scripts: Vec<EntryPoint>,
+    gui_scripts: Vec<EntryPoint>,
+}
+
+impl Scripts {
+    /// Read the `entry_points.txt` file from the wheel archive and parse the scripts.
+    pub fn from_wheel<R: ReadAndSeek>(
+        archive: &mut ZipArchive<R>,
+        dist_info_prefix: &str,
+        extras: Option<&HashSet<Extra>>,
+    ) -> Result<Self, UnpackError> {
+        let Some(mut entry_points_mapping) = read_entry_points_ini(archive, dist_info_prefix)?
+        else {
+            return Ok(Default::default());
+        };
+
+        // Parse the script entry points
+        let console_scripts = entry_points_mapping
+            .remove("console_scripts")
+            .map(|e| parse_entry_points_from_ini_section(e, extras))
+            .transpose()?
+            .unwrap_or_default();
+
+        let gui_scripts = entry_points_mapping
+            .remove("gui_scripts")
+            .map(|e| parse_entry_points_from_ini_section(e, extras))
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Scripts {
+            console_scripts,
+            gui_scripts,
+        })
+    }
+
+    /// Returns true if there is an entry point script with the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.console_scripts.iter().any(|e| e.script_name == name)
             || self.gui_scripts.iter().any(|e| e.script_name == name)
     }
 
@@ -1023,6 +2222,39 @@ impl Scripts {
     }
 }
 
+/// Reads and parses the `entry_points.txt` file from the wheel archive. Returns `None` if the
+/// wheel does not contain any entry points.
+#[allow(clippy::type_complexity)]
+fn read_entry_points_ini<R: ReadAndSeek>(
+    archive: &mut ZipArchive<R>,
+    dist_info_prefix: &str,
+) -> Result<Option<HashMap<String, HashMap<String, Option<String>>>>, UnpackError> {
+    // Read the `entry_points.txt` file from the archive
+    let entry_points_path = format!("{dist_info_prefix}/entry_points.txt");
+    let mut entry_points_file = match archive.by_name(&entry_points_path) {
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Ok(file) => file,
+        Err(err) => return Err(UnpackError::from_zip_error(entry_points_path, err)),
+    };
+
+    // Parse the `entry_points.txt` file as an ini file.
+    let mut ini_contents = String::new();
+    entry_points_file
+        .read_to_string(&mut ini_contents)
+        .map_err(|err| {
+            UnpackError::EntryPointsInvalid(format!(
+                "failed to read entry_points.txt contents: {}",
+                err
+            ))
+        })?;
+    Ini::new_cs().read(ini_contents).map(Some).map_err(|err| {
+        UnpackError::EntryPointsInvalid(format!(
+            "failed to parse entry_points.txt contents: {}",
+            err
+        ))
+    })
+}
+
 /// Parse entry points from a section in the `entry_points.txt` file.
 fn parse_entry_points_from_ini_section(
     entry_points: HashMap<String, Option<String>>,
@@ -1088,8 +2320,19 @@ fn write_generated_file(
 fn write_wheel_file(
     mut reader: &mut impl Read,
     destination: &Path,
-    _executable: bool,
+    executable: bool,
+    content_store: Option<&ContentStore>,
 ) -> Result<(Option<u64>, Option<String>), UnpackError> {
+    if let Some(content_store) = content_store {
+        let (size, digest) = content_store
+            .link(&mut reader, destination, executable)
+            .map_err(|err| UnpackError::IoError(destination.display().to_string(), err))?;
+        return Ok((
+            Some(size),
+            Some(format!("sha256={}", BASE64URL_NOPAD.encode(&digest))),
+        ));
+    }
+
     let mut reader = rattler_digest::HashingReader::<_, Sha256>::new(&mut reader);
 
     let mut options = fs::OpenOptions::new();
@@ -1097,7 +2340,7 @@ fn write_wheel_file(
     #[cfg(unix)]
     {
         use std::os::unix::fs::OpenOptionsExt;
-        if _executable {
+        if executable {
             options.mode(0o777);
         } else {
             options.mode(0o666);
@@ -1272,6 +2515,648 @@ mod test {
         assert_eq!(installer_content, format!("{INSTALLER}\n"));
     }
 
+    #[test]
+    fn test_extract_wheel_on_multiple_threads() {
+        let wheel = Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl"),
+            &"miniblack".parse().unwrap(),
+        )
+        .unwrap();
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+
+        let unpack = |extract_threads| {
+            let tmpdir = tempdir().unwrap();
+            let unpacked = wheel
+                .unpack(
+                    tmpdir.path(),
+                    &install_paths,
+                    Path::new("/invalid"),
+                    &UnpackWheelOptions {
+                        installer: Some(String::from(INSTALLER)),
+                        extract_threads: NonZeroUsize::new(extract_threads),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            let record = fs::read_to_string(unpacked.dist_info.join("RECORD")).unwrap();
+            (tmpdir, record)
+        };
+
+        // Extracting on multiple threads must produce exactly the same RECORD as extracting on a
+        // single thread.
+        let (_sequential_dir, sequential) = unpack(1);
+        let (parallel_dir, parallel) = unpack(4);
+        assert_eq!(sequential, parallel);
+        for entry in Record::from_path(
+            &parallel_dir
+                .path()
+                .join(install_paths.site_packages())
+                .join("miniblack-23.1.0.dist-info/RECORD"),
+        )
+        .unwrap()
+        .iter()
+        {
+            assert!(
+                parallel_dir
+                    .path()
+                    .join(install_paths.site_packages())
+                    .join(&entry.path)
+                    .exists(),
+                "{} was not extracted",
+                entry.path
+            );
+        }
+    }
+
+    #[test]
+    fn test_unpack_wheels_in_parallel() {
+        let wheels_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/wheels");
+        let miniblack = Wheel::from_path(
+            &wheels_dir.join("miniblack-23.1.0-py3-none-any.whl"),
+            &"miniblack".parse().unwrap(),
+        )
+        .unwrap();
+        let wordle = Wheel::from_path(
+            &wheels_dir.join("wordle_python-2.3.32-py3-none-any.whl"),
+            &"wordle_python".parse().unwrap(),
+        )
+        .unwrap();
+        let purelib_and_platlib = Wheel::from_path(
+            &wheels_dir.join("purelib_and_platlib-1.0.0-cp38-cp38-linux_x86_64.whl"),
+            &"purelib-and-platlib".parse().unwrap(),
+        )
+        .unwrap();
+
+        // Unpacking the same wheel twice writes to the same locations, these must not be
+        // unpacked at the same time.
+        let miniblack_again = Wheel::from_path(
+            &wheels_dir.join("miniblack-23.1.0-py3-none-any.whl"),
+            &"miniblack".parse().unwrap(),
+        )
+        .unwrap();
+
+        let tmpdir = tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let options = UnpackWheelOptions {
+            installer: Some(String::from(INSTALLER)),
+            ..Default::default()
+        };
+        let unpacked = unpack_wheels(
+            &[
+                (&miniblack, &options),
+                (&wordle, &options),
+                (&purelib_and_platlib, &options),
+                (&miniblack_again, &options),
+            ],
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            NonZeroUsize::new(4),
+        )
+        .unwrap();
+
+        let names = unpacked
+            .iter()
+            .map(|unpacked| unpacked.metadata.name.as_str().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "miniblack",
+                "wordle-python",
+                "purelib-and-platlib",
+                "miniblack"
+            ]
+        );
+        for unpacked in unpacked {
+            assert!(tmpdir
+                .path()
+                .join(&unpacked.dist_info)
+                .join("RECORD")
+                .is_file());
+        }
+    }
+
+    #[test]
+    fn test_inspect_wheel() {
+        let wheels_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/wheels");
+        let miniblack = Wheel::from_path(
+            &wheels_dir.join("miniblack-23.1.0-py3-none-any.whl"),
+            &"miniblack".parse().unwrap(),
+        )
+        .unwrap();
+
+        let console_scripts = miniblack.console_scripts().unwrap();
+        assert_eq!(
+            console_scripts
+                .iter()
+                .map(|e| e.script_name.as_str())
+                .collect::<Vec<_>>(),
+            ["black", "blackd"]
+        );
+        assert!(miniblack.gui_scripts().unwrap().is_empty());
+        assert_eq!(
+            miniblack.entry_points().unwrap().keys().collect::<Vec<_>>(),
+            ["console_scripts"]
+        );
+        assert_eq!(miniblack.top_level_modules().unwrap(), ["black", "blackd"]);
+
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let files = miniblack.installed_files(&install_paths).unwrap();
+        let site_packages = install_paths.site_packages();
+        assert!(files.contains(&site_packages.join("black/__init__.py")));
+        assert!(files.contains(&site_packages.join("miniblack-23.1.0.dist-info/RECORD")));
+        assert!(files.contains(&install_paths.scripts().join("blackd")));
+
+        // Uses `top_level.txt`
+        let wordle = Wheel::from_path(
+            &wheels_dir.join("wordle_python-2.3.32-py3-none-any.whl"),
+            &"wordle_python".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(wordle.top_level_modules().unwrap(), ["wordle"]);
+        assert!(wordle.entry_points().unwrap().is_empty());
+
+        // Modules in the data directory
+        let purelib_and_platlib = Wheel::from_path(
+            &wheels_dir.join("purelib_and_platlib-1.0.0-cp38-cp38-linux_x86_64.whl"),
+            &"purelib-and-platlib".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(purelib_and_platlib.top_level_modules().unwrap(), ["pure"]);
+    }
+
+    /// Constructs an in-memory wheel that contains the given files.
+    fn make_wheel(name: &str, files: &[(&str, &[u8])]) -> Wheel {
+        let dist_info = format!("{name}-1.0.dist-info");
+        let mut files = files
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.to_vec()))
+            .collect::<Vec<_>>();
+        files.push((
+            format!("{dist_info}/METADATA"),
+            format!("Metadata-Version: 2.1\nName: {name}\nVersion: 1.0\n").into_bytes(),
+        ));
+        files.push((
+            format!("{dist_info}/WHEEL"),
+            b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: true\nTag: py3-none-any\n"
+                .to_vec(),
+        ));
+
+        let mut record = String::new();
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (path, content) in files.iter() {
+            let hash = rattler_digest::compute_bytes_digest::<Sha256>(content);
+            record.push_str(&format!(
+                "{path},sha256={},{}\n",
+                BASE64URL_NOPAD.encode(&hash),
+                content.len()
+            ));
+            writer.start_file(path, Default::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        record.push_str(&format!("{dist_info}/RECORD,,\n"));
+        writer
+            .start_file(format!("{dist_info}/RECORD"), Default::default())
+            .unwrap();
+        writer.write_all(record.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        Wheel::from_bytes(&format!("{name}-1.0-py3-none-any.whl"), bytes).unwrap()
+    }
+
+    #[test]
+    fn test_validate_wheel() {
+        let wheel = make_wheel("a", &[("a/__init__.py", b"")]);
+        assert_eq!(wheel.validate(), vec![]);
+
+        let original_hash = BASE64URL_NOPAD.encode(
+            &rattler_digest::compute_bytes_digest::<Sha256>(b"print('original')"),
+        );
+        let files = [
+            ("b/__init__.py", String::from("print('tampered')")),
+            ("b/extra.py", String::new()),
+            (
+                "b-1.0.dist-info/METADATA",
+                String::from("Metadata-Version: 2.1\nName: b\nVersion: 2.0\n"),
+            ),
+            (
+                "b-1.0.dist-info/WHEEL",
+                String::from("Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py2-none-any\n"),
+            ),
+            (
+                "b-1.0.dist-info/RECORD",
+                format!(
+                    "b/__init__.py,sha256={original_hash},16\nb/missing.py,sha256=abc,1\n\
+                     b-1.0.dist-info/METADATA,,\nb-1.0.dist-info/WHEEL,,\nb-1.0.dist-info/RECORD,,\n"
+                ),
+            ),
+        ];
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (path, content) in files {
+            writer.start_file(path, Default::default()).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        let filename =
+            WheelFilename::from_filename("b-1.0-py3-none-any.whl", &"b".parse().unwrap()).unwrap();
+        let wheel = Wheel::new(filename, Box::new(bytes)).unwrap();
+
+        let findings = wheel.validate();
+        assert_eq!(
+            findings,
+            vec![
+                WheelValidationFinding::TagMismatch {
+                    wheel_file: String::from("py2-none-any"),
+                    filename: String::from("py3-none-any"),
+                },
+                WheelValidationFinding::VersionMismatch {
+                    metadata: "2.0".parse().unwrap(),
+                    filename: "1.0".parse().unwrap(),
+                },
+                WheelValidationFinding::HashMismatch(String::from("b/__init__.py")),
+                WheelValidationFinding::NotInRecord(String::from("b/extra.py")),
+                WheelValidationFinding::MissingHash(String::from("b-1.0.dist-info/METADATA")),
+                WheelValidationFinding::MissingHash(String::from("b-1.0.dist-info/WHEEL")),
+                WheelValidationFinding::MissingFromArchive(String::from("b/missing.py")),
+            ]
+        );
+        assert_eq!(findings[0].severity(), ValidationSeverity::Warning);
+        assert_eq!(findings[1].severity(), ValidationSeverity::Error);
+    }
+
+    #[test]
+    fn test_incompatible_wheel() {
+        let wheel = make_wheel("a", &[("a/__init__.py", b"")]);
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let tmpdir = tempdir().unwrap();
+
+        // A resolution made for another platform must be rejected before anything is written.
+        let options = UnpackWheelOptions {
+            compatible_tags: Some(WheelTags::from_iter(["cp311-cp311-win_amd64"
+                .parse()
+                .unwrap()])),
+            ..Default::default()
+        };
+        let result = unpack_wheels(
+            &[(&wheel, &options)],
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            None,
+        );
+        assert!(matches!(result, Err(UnpackError::IncompatibleWheel { .. })));
+        assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 0);
+
+        let options = UnpackWheelOptions {
+            compatible_tags: Some(WheelTags::from_iter(["py3-none-any".parse().unwrap()])),
+            ..Default::default()
+        };
+        wheel
+            .unpack(
+                tmpdir.path(),
+                &install_paths,
+                Path::new("/invalid"),
+                &options,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_file_conflicts() {
+        let a = make_wheel(
+            "a",
+            &[
+                ("ns/__init__.py", b"# namespace"),
+                ("shared/__init__.py", b"print('a')"),
+            ],
+        );
+        let b = make_wheel(
+            "b",
+            &[
+                ("ns/__init__.py", b"# namespace"),
+                ("shared/__init__.py", b"print('b')"),
+            ],
+        );
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+
+        // Identical files in the shared namespace package are not a conflict.
+        let conflicts = find_file_conflicts(&[&a, &b], &install_paths).unwrap();
+        assert_eq!(
+            conflicts,
+            vec![FileConflict {
+                path: install_paths.site_packages().join("shared/__init__.py"),
+                wheels: vec![a.name().clone(), b.name().clone()],
+            }]
+        );
+
+        // Unpacking must fail without touching the destination.
+        let tmpdir = tempdir().unwrap();
+        let options = UnpackWheelOptions::default();
+        let result = unpack_wheels(
+            &[(&a, &options), (&b, &options)],
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            None,
+        );
+        assert!(
+            matches!(result, Err(UnpackError::FileConflicts(conflicts)) if conflicts.len() == 1)
+        );
+        assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_install_hooks() {
+        #[derive(Default)]
+        struct RejectB {
+            transactions: std::sync::Mutex<Vec<usize>>,
+        }
+
+        impl InstallHooks for RejectB {
+            fn before_install(
+                &self,
+                wheel: &WheelFilename,
+                _dest: &Path,
+            ) -> Result<(), InstallHookError> {
+                if wheel.distribution.as_str() == "b" {
+                    return Err("b is not allowed".into());
+                }
+                Ok(())
+            }
+
+            fn before_transaction(
+                &self,
+                wheels: &[&WheelFilename],
+            ) -> Result<(), InstallHookError> {
+                self.transactions.lock().unwrap().push(wheels.len());
+                Ok(())
+            }
+        }
+
+        let a = make_wheel("a", &[("a/__init__.py", b"")]);
+        let b = make_wheel("b", &[("b/__init__.py", b"")]);
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let hooks = Arc::new(RejectB::default());
+        let options = UnpackWheelOptions {
+            install_hooks: Some(hooks.clone()),
+            ..Default::default()
+        };
+
+        let tmpdir = tempdir().unwrap();
+        unpack_wheels(
+            &[(&a, &options)],
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            None,
+        )
+        .unwrap();
+
+        let result = unpack_wheels(
+            &[(&b, &options)],
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            None,
+        );
+        assert!(matches!(result, Err(UnpackError::InstallHookFailed { .. })));
+        assert!(!tmpdir
+            .path()
+            .join(install_paths.site_packages())
+            .join("b")
+            .exists());
+        assert_eq!(*hooks.transactions.lock().unwrap(), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        let wheel = make_wheel("a", &[("a/__init__.py", b""), ("a/util.py", b"")]);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a-1.0-py3-none-any.whl");
+        let mut reader = wheel.archive.clone().into_inner();
+        std::io::Seek::rewind(&mut reader).unwrap();
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        std::fs::write(&path, bytes).unwrap();
+
+        for wheel in [
+            wheel,
+            Wheel::from_path(&path, &"a".parse().unwrap()).unwrap(),
+        ] {
+            std::thread::scope(|s| {
+                let handles = (0..4)
+                    .map(|_| {
+                        s.spawn(|| {
+                            assert_eq!(wheel.validate(), vec![]);
+                            wheel.top_level_modules().unwrap()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                for handle in handles {
+                    assert_eq!(handle.join().unwrap(), ["a"]);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_namespace_packages() {
+        // Two distributions that share the implicit namespace package `ns`, which does not have
+        // an `__init__.py` (PEP 420).
+        let a = make_wheel("ns_a", &[("ns/a/__init__.py", b"")]);
+        let b = make_wheel("ns_b", &[("ns/b/__init__.py", b""), ("ns/b/util.py", b"")]);
+        assert_eq!(a.validate(), vec![]);
+        assert_eq!(b.validate(), vec![]);
+        assert_eq!(a.top_level_modules().unwrap(), ["ns"]);
+
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        assert!(find_file_conflicts(&[&a, &b], &install_paths)
+            .unwrap()
+            .is_empty());
+
+        let tmpdir = tempdir().unwrap();
+        let options = UnpackWheelOptions::default();
+        let unpacked = unpack_wheels(
+            &[(&a, &options), (&b, &options)],
+            tmpdir.path(),
+            &install_paths,
+            Path::new("/invalid"),
+            None,
+        )
+        .unwrap();
+
+        let site_packages = tmpdir.path().join(install_paths.site_packages());
+        assert!(site_packages.join("ns/a/__init__.py").is_file());
+        assert!(site_packages.join("ns/b/util.py").is_file());
+        assert!(!site_packages.join("ns/__init__.py").exists());
+
+        // Uninstalling one of the distributions leaves the namespace package intact, uninstalling
+        // the last one removes it completely.
+        let dist_info = |idx: usize| {
+            unpacked[idx]
+                .dist_info
+                .strip_prefix(install_paths.site_packages())
+                .unwrap()
+                .to_path_buf()
+        };
+        crate::python_env::uninstall_distribution(&site_packages, &dist_info(0)).unwrap();
+        assert!(!site_packages.join("ns/a").exists());
+        assert!(site_packages.join("ns/b/__init__.py").is_file());
+
+        crate::python_env::uninstall_distribution(&site_packages, &dist_info(1)).unwrap();
+        assert!(!site_packages.join("ns").exists());
+        assert!(site_packages.is_dir());
+    }
+
+    #[test]
+    fn test_editable_install() {
+        // An editable wheel as built by setuptools, with a `.pth` file that installs an import
+        // hook from a finder module.
+        let wheel = make_wheel(
+            "foo",
+            &[
+                (
+                    "__editable__.foo-1.0.pth",
+                    b"import __editable___foo_1_0_finder; __editable___foo_1_0_finder.install()\n",
+                ),
+                ("__editable___foo_1_0_finder.py", b"def install(): pass\n"),
+            ],
+        );
+
+        let project = tempdir().unwrap();
+        let tmpdir = tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let unpacked = wheel
+            .unpack(
+                tmpdir.path(),
+                &install_paths,
+                Path::new("/invalid"),
+                &UnpackWheelOptions {
+                    editable_project: Some(project.path().to_path_buf()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let site_packages = tmpdir.path().join(install_paths.site_packages());
+        assert!(site_packages.join("__editable__.foo-1.0.pth").is_file());
+
+        // The installation is marked as editable and the marker is tracked in the RECORD
+        let direct_url: serde_json::Value =
+            serde_json::from_slice(&fs::read(unpacked.dist_info.join("direct_url.json")).unwrap())
+                .unwrap();
+        assert_eq!(direct_url["dir_info"]["editable"], true);
+        assert_eq!(
+            direct_url["url"],
+            Url::from_directory_path(project.path())
+                .unwrap()
+                .as_str()
+                .trim_end_matches('/')
+        );
+        let record = fs::read_to_string(unpacked.dist_info.join("RECORD")).unwrap();
+        assert!(record.contains("foo-1.0.dist-info/direct_url.json"));
+
+        let distributions =
+            crate::python_env::find_distributions_in_venv(tmpdir.path(), &install_paths).unwrap();
+        assert_eq!(
+            distributions[0].kind,
+            crate::python_env::DistributionKind::Editable {
+                target: project.path().to_path_buf()
+            }
+        );
+
+        // Python caches the bytecode of the finder module when the `.pth` file is processed,
+        // this is removed when uninstalling even though it is not part of the RECORD.
+        fs::create_dir(site_packages.join("__pycache__")).unwrap();
+        fs::write(
+            site_packages.join("__pycache__/__editable___foo_1_0_finder.cpython-38.pyc"),
+            b"",
+        )
+        .unwrap();
+
+        crate::python_env::uninstall_distribution(&site_packages, Path::new("foo-1.0.dist-info"))
+            .unwrap();
+        assert_eq!(site_packages.read_dir().unwrap().count(), 0);
+
+        // Relative project paths are rejected
+        let tmpdir = tempdir().unwrap();
+        assert!(matches!(
+            wheel.unpack(
+                tmpdir.path(),
+                &install_paths,
+                Path::new("/invalid"),
+                &UnpackWheelOptions {
+                    editable_project: Some(PathBuf::from("foo")),
+                    ..Default::default()
+                },
+            ),
+            Err(UnpackError::InvalidEditableProject(_))
+        ));
+    }
+
+    #[test]
+    fn test_shebang_styles() {
+        let trampoline = |shebang| {
+            let maker = TrampolineMaker {
+                python_executable: PathBuf::from("/venv/bin/python"),
+                scripts_dir: PathBuf::from("/venv/bin"),
+                shebang,
+                kind: TrampolineMakerKind::Unix,
+            };
+            let script = maker
+                .make_trampoline(LauncherType::Console, b"print('hello')\n")
+                .unwrap();
+            String::from_utf8(script).unwrap()
+        };
+
+        assert_eq!(
+            trampoline(ShebangStyle::Absolute),
+            "#!/venv/bin/python\nprint('hello')\n"
+        );
+        assert_eq!(
+            trampoline(ShebangStyle::Env),
+            "#!/usr/bin/env python\nprint('hello')\n"
+        );
+        assert_eq!(
+            trampoline(ShebangStyle::Relative),
+            "#!/bin/sh\n'''exec' \"$(cd -- \"$(dirname -- \"$0\")\" && pwd -P)\"/'python' \"$0\" \"$@\"\n' '''\nprint('hello')\n"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_relative_shebang_with_quote() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The interpreter lives in a directory with a quote in its name. It prints its arguments
+        // instead of running the script.
+        let tmpdir = tempdir().unwrap();
+        let python_dir = tmpdir.path().join("it's python");
+        let scripts_dir = tmpdir.path().join("bin");
+        fs::create_dir_all(&python_dir).unwrap();
+        fs::create_dir_all(&scripts_dir).unwrap();
+        let python_executable = python_dir.join("python");
+        fs::write(&python_executable, "#!/bin/sh\necho \"$@\"\n").unwrap();
+        fs::set_permissions(&python_executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let shebang = get_relative_shebang(&python_executable, &scripts_dir);
+        assert!(shebang.contains(r"'../it'\''s python/python'"), "{shebang}");
+
+        let script = scripts_dir.join("script");
+        fs::write(&script, format!("{shebang}\nprint('hello')\n")).unwrap();
+        let output = std::process::Command::new("sh")
+            .arg(&script)
+            .arg("argument")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            format!("{} argument", script.display())
+        );
+    }
+
     #[test]
     fn test_entry_points() {
         // Create a virtual environment in a temporary directory
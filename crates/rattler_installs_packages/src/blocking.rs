@@ -0,0 +1,237 @@
+//! Synchronous wrappers around the core workflows (resolve, fetch, build and install) for code
+//! that does not run an async runtime, similar to `reqwest::blocking`.
+//!
+//! The wrappers manage their own tokio runtime and block the current thread until the work is
+//! done. They must not be called from within an async runtime, use the async APIs there instead.
+//!
+//! ```no_run
+//! # use rattler_installs_packages::blocking::PackageDb;
+//! # use rattler_installs_packages::python_env::Pep508EnvMakers;
+//! # use rattler_installs_packages::resolve::ResolveOptions;
+//! # use std::{collections::HashMap, path::Path};
+//! # fn main() -> miette::Result<()> {
+//! let package_db = PackageDb::new(
+//!     Default::default(),
+//!     &["https://pypi.org/simple/".parse().unwrap()],
+//!     Path::new("/tmp/rip-cache"),
+//! )
+//! .unwrap();
+//! let env_markers = package_db
+//!     .block_on(Pep508EnvMakers::from_env())
+//!     .unwrap();
+//! let packages = package_db.resolve(
+//!     &["rich".parse().unwrap()],
+//!     &env_markers,
+//!     None,
+//!     HashMap::default(),
+//!     HashMap::default(),
+//!     &ResolveOptions::default(),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::artifacts::wheel::{InstallPaths, UnpackWheelOptions, UnpackedWheel};
+use crate::artifacts::SDist;
+use crate::index::{self, WarmCacheOptions, WarmCacheReport};
+use crate::install::InstallBuilder;
+use crate::python_env::WheelTags;
+use crate::resolve::{self, PinnedPackage, ResolveOptions};
+use crate::types::{Artifact, ArtifactInfo, NormalizedPackageName};
+use crate::wheel_builder::{WheelBuildError, WheelBuilder};
+use indexmap::IndexMap;
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// A blocking wrapper around an [`index::PackageDb`] that runs all operations on its own tokio
+/// runtime.
+pub struct PackageDb {
+    inner: index::PackageDb,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl PackageDb {
+    /// Constructs a new package database, see [`index::PackageDb::new`].
+    pub fn new(client: Client, index_urls: &[Url], cache_dir: &Path) -> std::io::Result<Self> {
+        Self::from_async(index::PackageDb::new(client, index_urls, cache_dir)?)
+    }
+
+    /// Wraps an existing async package database. This allows configuring the database with its
+    /// builder methods before wrapping it.
+    pub fn from_async(package_db: index::PackageDb) -> std::io::Result<Self> {
+        // The resolver blocks on futures from within the runtime which requires the multi-threaded
+        // runtime.
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            inner: package_db,
+            runtime,
+        })
+    }
+
+    /// Returns the wrapped async package database.
+    pub fn inner(&self) -> &index::PackageDb {
+        &self.inner
+    }
+
+    /// Runs a future on the runtime of this database and blocks until it completes. Use this to
+    /// call async functions that don't have a blocking wrapper.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Returns the available artifacts of a package, see [`index::PackageDb::available_artifacts`].
+    pub fn available_artifacts<P: Into<NormalizedPackageName>>(
+        &self,
+        p: P,
+    ) -> miette::Result<&IndexMap<Version, Vec<ArtifactInfo>>> {
+        self.block_on(self.inner.available_artifacts(p))
+    }
+
+    /// Downloads an artifact, see [`index::PackageDb::get_artifact`].
+    pub fn get_artifact<A: Artifact>(&self, artifact_info: &ArtifactInfo) -> miette::Result<A> {
+        self.block_on(self.inner.get_artifact(artifact_info))
+    }
+
+    /// Resolves an environment, see [`resolve::resolve`].
+    pub fn resolve<'db>(
+        &'db self,
+        requirements: &[Requirement],
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        locked_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+        favored_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+        options: &ResolveOptions,
+    ) -> miette::Result<Vec<PinnedPackage<'db>>> {
+        self.block_on(resolve::resolve(
+            &self.inner,
+            requirements,
+            env_markers,
+            compatible_tags,
+            locked_packages,
+            favored_packages,
+            options,
+        ))
+    }
+
+    /// Prefetches everything a resolve of the requirements needs, see
+    /// [`index::PackageDb::warm_cache`].
+    pub fn warm_cache(
+        &self,
+        requirements: &[Requirement],
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        options: &WarmCacheOptions,
+    ) -> miette::Result<WarmCacheReport> {
+        self.block_on(
+            self.inner
+                .warm_cache(requirements, env_markers, compatible_tags, options),
+        )
+    }
+
+    /// Builds a wheel from an sdist, see [`WheelBuilder::build_wheel`]. Returns the path of the
+    /// built wheel.
+    pub fn build_wheel(
+        &self,
+        sdist: &SDist,
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        options: &ResolveOptions,
+    ) -> Result<PathBuf, WheelBuildError> {
+        let wheel_builder = WheelBuilder::new(&self.inner, env_markers, compatible_tags, options);
+        self.block_on(wheel_builder.build_wheel(sdist))
+    }
+
+    /// Installs resolved packages into the environment at `dest`, see [`InstallBuilder`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn install(
+        &self,
+        packages: &[PinnedPackage<'_>],
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        options: &ResolveOptions,
+        dest: &Path,
+        install_paths: &InstallPaths,
+        python_executable: &Path,
+        unpack_options: &UnpackWheelOptions,
+    ) -> miette::Result<Vec<UnpackedWheel>> {
+        let mut builder = InstallBuilder::new(
+            &self.inner,
+            env_markers,
+            dest,
+            install_paths,
+            python_executable,
+        )
+        .with_resolve_options(options.clone())
+        .with_unpack_options(unpack_options.clone());
+        if let Some(compatible_tags) = compatible_tags {
+            builder = builder.with_compatible_tags(compatible_tags);
+        }
+        self.block_on(builder.install(packages))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::generate::generate_index;
+    use crate::index::test_server::{test_wheels_dir, TestIndexServer};
+    use std::str::FromStr;
+    use test_utils::linux_environment_markers;
+
+    #[test]
+    fn test_resolve_and_install() {
+        // The blocking database must not be used from within a runtime, so the server runs on its
+        // own runtime.
+        let server_runtime = tokio::runtime::Runtime::new().unwrap();
+        let index_dir = tempfile::tempdir().unwrap();
+        generate_index(&test_wheels_dir(), index_dir.path()).unwrap();
+        let server = server_runtime
+            .block_on(TestIndexServer::start(index_dir.path(), Default::default()))
+            .unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[server.url()], cache_dir.path()).unwrap();
+        let env_markers = linux_environment_markers("3.10.4");
+        let packages = package_db
+            .resolve(
+                &[Requirement::from_str("wordle_python").unwrap()],
+                &env_markers,
+                None,
+                HashMap::default(),
+                HashMap::default(),
+                &ResolveOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name.as_str(), "wordle-python");
+
+        let versions = package_db
+            .available_artifacts(packages[0].name.clone())
+            .unwrap();
+        assert!(versions.contains_key(&packages[0].version));
+
+        let target = tempfile::tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 10, 4), false);
+        let unpacked = package_db
+            .install(
+                &packages,
+                &env_markers,
+                None,
+                &ResolveOptions::default(),
+                target.path(),
+                &install_paths,
+                &target.path().join("bin/python"),
+                &UnpackWheelOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(unpacked.len(), 1);
+        assert!(unpacked[0].dist_info.is_dir());
+    }
+}
@@ -0,0 +1,968 @@
+//! Layered configuration read from `rip.toml` files and environment variables.
+//!
+//! The configuration is assembled from the following layers, later layers take precedence over
+//! earlier ones:
+//!
+//! 1. The system configuration, `/etc/rip/rip.toml` or `%PROGRAMDATA%\rip\rip.toml` on Windows.
+//! 2. The user configuration, `rip/rip.toml` in the configuration directory of the user (e.g.
+//!    `~/.config/rip/rip.toml`).
+//! 3. The project configuration, the `rip.toml` file in the project directory or the closest of
+//!    its ancestors.
+//! 4. The environment variables `RIP_INDEX_URL` (whitespace separated urls), `RIP_CACHE_DIR` and
+//!    `RIP_SDIST_RESOLUTION`.
+//!
+//! [`Config::load_pip_compatible`] additionally reads the configuration of pip, see the [`pip`]
+//! module.
+//!
+//! A configuration file looks like this, all keys are optional:
+//!
+//! ```toml
+//! index-urls = ["https://pypi.org/simple/"]
+//! extra-index-urls = ["https://download.pytorch.org/whl/cpu"]
+//! upstream-index = "https://pypi.org/simple/"
+//! strict-upstream-index = true
+//! find-links = ["https://example.com/wheels/"]
+//! cache-dir = "/var/cache/rip"
+//! sdist-resolution = "prefer-wheels"
+//!
+//! [auth."pypi.example.com"]
+//! username = "user"
+//! password = "secret"
+//!
+//! [build.environment.numpy]
+//! CFLAGS = "-O2"
+//!
+//! [build.config-settings.pyyaml]
+//! "--global-option" = ["--without-libyaml"]
+//!
+//! [http]
+//! http-version = "auto"
+//! pool-max-idle-per-host = 16
+//! pool-idle-timeout-secs = 90
+//! tcp-keepalive-secs = 60
+//! dns-cache-ttl-secs = 300
+//! max-retries = 3
+//! max-retry-delay-secs = 60
+//! bandwidth-limit = 10_000_000
+//!
+//! [[package-rules]]
+//! action = "deny"
+//! pattern = "*-nightly"
+//!
+//! [[package-rules]]
+//! action = "deny"
+//! pattern = "acme-*"
+//! index = "https://pypi.org/simple/"
+//!
+//! [package-aliases]
+//! PIL = "Pillow"
+//!
+//! [provided-packages]
+//! torch = "2.1.0"
+//! ```
+//!
+//! The `package-rules` of all layers are combined, the rules of layers with a higher precedence
+//! are evaluated first, see [`PackageRules`]. The `package-aliases` and `provided-packages` of all
+//! layers are combined as well, see [`PackageAliases`] and [`ProvidedPackages`].
+//!
+//! Use [`Config::load`] to read all layers and [`Config::layers`] to inspect where the effective
+//! configuration came from.
+
+use crate::index::{AuthStore, HttpClientOptions, HttpVersion, IndexSet, PackageDb, RetryPolicy};
+use crate::normalize_index_url;
+use crate::resolve::{
+    PackageAliases, PackageRule, PackageRules, ProvidedPackages, ResolveOptions, SDistResolution,
+};
+use crate::types::{NormalizedPackageName, Version};
+use itertools::Itertools;
+use reqwest::Client;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+pub mod pip;
+
+/// The name of configuration files.
+pub const CONFIG_FILE_NAME: &str = "rip.toml";
+
+/// An error that can occur when loading the configuration.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// A configuration file could not be read
+    #[error("failed to read {0}")]
+    Io(PathBuf, #[source] std::io::Error),
+
+    /// A configuration file is not valid
+    #[error("failed to parse {0}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+
+    /// An environment variable has an invalid value
+    #[error("invalid value for environment variable {name}: {reason}")]
+    InvalidEnvironmentVariable {
+        /// The name of the variable
+        name: &'static str,
+        /// Why the value is invalid
+        reason: String,
+    },
+
+    /// An option in the configuration of pip has an invalid value
+    #[error("invalid value for '{key}' in {location}: {reason}")]
+    InvalidPipOption {
+        /// The file or environment variable the option was read from
+        location: String,
+        /// The name of the option
+        key: String,
+        /// Why the value is invalid
+        reason: String,
+    },
+}
+
+/// Credentials to authenticate with an index.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct Credentials {
+    /// The username
+    pub username: String,
+
+    /// The password or token
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "****"))
+            .finish()
+    }
+}
+
+// Passwords are never serialized, so the effective configuration can be shown to users.
+impl Serialize for Credentials {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("Credentials", 2)?;
+        s.serialize_field("username", &self.username)?;
+        s.serialize_field("password", &self.password.as_ref().map(|_| "****"))?;
+        s.end()
+    }
+}
+
+/// Options that influence how sdists are built.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Environment variables that are set when building sdists of specific packages, see
+    /// [`ResolveOptions::build_environment_variables`].
+    #[serde(default)]
+    pub environment: BTreeMap<NormalizedPackageName, BTreeMap<String, String>>,
+
+    /// Config settings that are passed to the build backend when building sdists of specific
+    /// packages, see [`ResolveOptions::build_config_settings`].
+    #[serde(default)]
+    pub config_settings: BTreeMap<NormalizedPackageName, BTreeMap<String, Vec<String>>>,
+}
+
+/// Options to tune the HTTP client, see [`HttpClientOptions`]. Durations are given in seconds,
+/// a duration of `0` disables the corresponding feature.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct HttpConfig {
+    /// The HTTP version to use
+    pub http_version: Option<HttpVersion>,
+
+    /// The maximum number of idle connections per host that are kept open
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// How long idle connections are kept open
+    pub pool_idle_timeout_secs: Option<u64>,
+
+    /// The interval of TCP keep-alive probes
+    pub tcp_keepalive_secs: Option<u64>,
+
+    /// The interval of HTTP/2 pings that keep connections alive
+    pub http2_keep_alive_interval_secs: Option<u64>,
+
+    /// How long the addresses of a host are cached
+    pub dns_cache_ttl_secs: Option<u64>,
+
+    /// The timeout for establishing a connection
+    pub connect_timeout_secs: Option<u64>,
+
+    /// How often a request that is throttled by the server is retried
+    pub max_retries: Option<u32>,
+
+    /// The longest time to wait before retrying a throttled request
+    pub max_retry_delay_secs: Option<u64>,
+
+    /// The maximum total download rate in bytes per second
+    pub bandwidth_limit: Option<u64>,
+}
+
+impl HttpConfig {
+    /// Overrides the options with the options that are set in `other`.
+    fn merge(&mut self, other: &HttpConfig) {
+        fn merge<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                *value = other.clone();
+            }
+        }
+        merge(&mut self.http_version, &other.http_version);
+        merge(
+            &mut self.pool_max_idle_per_host,
+            &other.pool_max_idle_per_host,
+        );
+        merge(
+            &mut self.pool_idle_timeout_secs,
+            &other.pool_idle_timeout_secs,
+        );
+        merge(&mut self.tcp_keepalive_secs, &other.tcp_keepalive_secs);
+        merge(
+            &mut self.http2_keep_alive_interval_secs,
+            &other.http2_keep_alive_interval_secs,
+        );
+        merge(&mut self.dns_cache_ttl_secs, &other.dns_cache_ttl_secs);
+        merge(&mut self.connect_timeout_secs, &other.connect_timeout_secs);
+        merge(&mut self.max_retries, &other.max_retries);
+        merge(&mut self.max_retry_delay_secs, &other.max_retry_delay_secs);
+        merge(&mut self.bandwidth_limit, &other.bandwidth_limit);
+    }
+
+    /// Returns the policy to retry throttled requests, options that are not set keep their
+    /// default.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        RetryPolicy {
+            max_retries: self.max_retries.unwrap_or(defaults.max_retries),
+            max_delay: self
+                .max_retry_delay_secs
+                .map_or(defaults.max_delay, Duration::from_secs),
+            ..defaults
+        }
+    }
+
+    /// Returns the client options, options that are not set keep their default.
+    pub fn client_options(&self) -> HttpClientOptions {
+        let duration = |secs: Option<u64>, default: Option<Duration>| match secs {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => default,
+        };
+        let defaults = HttpClientOptions::default();
+        HttpClientOptions {
+            http_version: self.http_version.unwrap_or(defaults.http_version),
+            pool_max_idle_per_host: self
+                .pool_max_idle_per_host
+                .unwrap_or(defaults.pool_max_idle_per_host),
+            pool_idle_timeout: duration(self.pool_idle_timeout_secs, defaults.pool_idle_timeout),
+            tcp_keepalive: duration(self.tcp_keepalive_secs, defaults.tcp_keepalive),
+            http2_keep_alive_interval: duration(
+                self.http2_keep_alive_interval_secs,
+                defaults.http2_keep_alive_interval,
+            ),
+            dns_cache_ttl: duration(self.dns_cache_ttl_secs, defaults.dns_cache_ttl),
+            connect_timeout: duration(self.connect_timeout_secs, defaults.connect_timeout),
+        }
+    }
+}
+
+/// The configuration of a single layer, e.g. a single `rip.toml` file. Options that are not set
+/// are inherited from lower layers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConfigLayer {
+    /// The indexes to use
+    pub index_urls: Option<Vec<Url>>,
+
+    /// Indexes that are used in addition to the `index_urls`
+    pub extra_index_urls: Option<Vec<Url>>,
+
+    /// When true, no indexes are used at all, only the `find_links`
+    pub no_index: Option<bool>,
+
+    /// Flat HTML pages that link to artifacts or local directories of artifacts, see
+    /// [`PackageDb::with_find_links`]
+    pub find_links: Option<Vec<Url>>,
+
+    /// The canonical index that artifacts from mirrors are verified against, see
+    /// [`PackageDb::with_upstream_index`]
+    pub upstream_index: Option<Url>,
+
+    /// Fail for artifacts that cannot be verified against the `upstream_index`, see
+    /// [`PackageDb::with_strict_upstream_index`]
+    pub strict_upstream_index: Option<bool>,
+
+    /// The directory in which downloaded artifacts and metadata are cached
+    pub cache_dir: Option<PathBuf>,
+
+    /// How sdists are handled during resolution
+    pub sdist_resolution: Option<SDistResolution>,
+
+    /// Credentials keyed by the host of the index they are used for
+    #[serde(default)]
+    pub auth: BTreeMap<String, Credentials>,
+
+    /// Options to build sdists
+    #[serde(default)]
+    pub build: BuildConfig,
+
+    /// Options to tune the HTTP client
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Rules that allow or deny packages by name
+    #[serde(default)]
+    pub package_rules: Vec<PackageRule>,
+
+    /// Aliases of packages keyed by the alias
+    #[serde(default)]
+    pub package_aliases: BTreeMap<NormalizedPackageName, NormalizedPackageName>,
+
+    /// Packages that are provided by the platform and their versions
+    #[serde(default)]
+    pub provided_packages: BTreeMap<NormalizedPackageName, Version>,
+}
+
+impl FromStr for ConfigLayer {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s)
+    }
+}
+
+impl ConfigLayer {
+    /// Reads a configuration file. Returns `None` if the file does not exist.
+    pub fn from_path(path: &Path) -> Result<Option<Self>, ConfigError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ConfigError::Io(path.to_path_buf(), e)),
+        };
+        Self::from_str(&contents)
+            .map(Some)
+            .map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    /// Reads the options that are set through environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_env_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the options from environment variables that are looked up with `var`.
+    fn from_env_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let index_urls = var("RIP_INDEX_URL")
+            .map(|value| {
+                value
+                    .split_whitespace()
+                    .map(Url::parse)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| ConfigError::InvalidEnvironmentVariable {
+                        name: "RIP_INDEX_URL",
+                        reason: e.to_string(),
+                    })
+            })
+            .transpose()?;
+        let sdist_resolution = var("RIP_SDIST_RESOLUTION")
+            .map(|value| {
+                SDistResolution::deserialize(toml::Value::String(value)).map_err(|e| {
+                    ConfigError::InvalidEnvironmentVariable {
+                        name: "RIP_SDIST_RESOLUTION",
+                        reason: e.to_string(),
+                    }
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            index_urls,
+            cache_dir: var("RIP_CACHE_DIR").map(PathBuf::from),
+            sdist_resolution,
+            ..Default::default()
+        })
+    }
+
+    /// Overrides the options of this layer with the options that are set in `other`.
+    fn merge(&mut self, other: &ConfigLayer) {
+        if let Some(index_urls) = &other.index_urls {
+            self.index_urls = Some(index_urls.clone());
+        }
+        if let Some(extra_index_urls) = &other.extra_index_urls {
+            self.extra_index_urls = Some(extra_index_urls.clone());
+        }
+        if let Some(no_index) = other.no_index {
+            self.no_index = Some(no_index);
+        }
+        if let Some(find_links) = &other.find_links {
+            self.find_links = Some(find_links.clone());
+        }
+        if let Some(upstream_index) = &other.upstream_index {
+            self.upstream_index = Some(upstream_index.clone());
+        }
+        if let Some(strict_upstream_index) = other.strict_upstream_index {
+            self.strict_upstream_index = Some(strict_upstream_index);
+        }
+        if let Some(cache_dir) = &other.cache_dir {
+            self.cache_dir = Some(cache_dir.clone());
+        }
+        if let Some(sdist_resolution) = other.sdist_resolution {
+            self.sdist_resolution = Some(sdist_resolution);
+        }
+        self.auth
+            .extend(other.auth.iter().map(|(k, v)| (k.clone(), v.clone())));
+        for (package, variables) in &other.build.environment {
+            self.build
+                .environment
+                .entry(package.clone())
+                .or_default()
+                .extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        for (package, settings) in &other.build.config_settings {
+            self.build
+                .config_settings
+                .entry(package.clone())
+                .or_default()
+                .extend(settings.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        self.http.merge(&other.http);
+        self.package_rules = other
+            .package_rules
+            .iter()
+            .cloned()
+            .chain(self.package_rules.drain(..))
+            .collect();
+        self.package_aliases.extend(
+            other
+                .package_aliases
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+        self.provided_packages.extend(
+            other
+                .provided_packages
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+    }
+}
+
+/// The kind of a configuration layer, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigLayerKind {
+    /// A configuration file of pip
+    PipConfig,
+    /// The configuration of the system
+    System,
+    /// The configuration of the current user
+    User,
+    /// The configuration of the project
+    Project,
+    /// The `PIP_*` environment variables
+    PipEnvironment,
+    /// The environment variables
+    Environment,
+}
+
+/// Where a [`ConfigLayer`] was read from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigSource {
+    /// The kind of the layer
+    pub kind: ConfigLayerKind,
+
+    /// The file the layer was read from, `None` for environment variables
+    pub path: Option<PathBuf>,
+}
+
+impl ConfigSource {
+    fn environment(kind: ConfigLayerKind) -> Self {
+        Self { kind, path: None }
+    }
+}
+
+/// The effective configuration, the result of merging all configuration layers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Config {
+    /// The indexes to use, defaults to PyPI. This includes the extra indexes and is empty if
+    /// indexes are disabled.
+    pub index_urls: Vec<Url>,
+
+    /// Flat HTML pages that link to artifacts
+    pub find_links: Vec<Url>,
+
+    /// The canonical index that artifacts from mirrors are verified against
+    pub upstream_index: Option<Url>,
+
+    /// Whether artifacts that cannot be verified against the `upstream_index` are rejected
+    pub strict_upstream_index: bool,
+
+    /// The directory in which downloaded artifacts and metadata are cached
+    pub cache_dir: PathBuf,
+
+    /// How sdists are handled during resolution
+    pub sdist_resolution: SDistResolution,
+
+    /// Credentials keyed by the host of the index they are used for
+    pub auth: BTreeMap<String, Credentials>,
+
+    /// Options to build sdists
+    pub build: BuildConfig,
+
+    /// Options to tune the HTTP client
+    pub http: HttpConfig,
+
+    /// Rules that allow or deny packages by name, in the order they are evaluated
+    pub package_rules: Vec<PackageRule>,
+
+    /// Aliases of packages keyed by the alias
+    pub package_aliases: BTreeMap<NormalizedPackageName, NormalizedPackageName>,
+
+    /// Packages that are provided by the platform and their versions
+    pub provided_packages: BTreeMap<NormalizedPackageName, Version>,
+
+    /// The layers the configuration was assembled from, in order of precedence
+    #[serde(skip)]
+    layers: Vec<(ConfigSource, ConfigLayer)>,
+}
+
+impl Config {
+    /// Loads the system, user and project configuration files and the environment variables. The
+    /// project configuration is searched for in `project_dir` and its ancestors.
+    pub fn load(project_dir: Option<&Path>) -> Result<Self, ConfigError> {
+        Self::load_layers(project_dir, false)
+    }
+
+    /// Like [`Self::load`] but also reads the configuration files of pip and the `PIP_*`
+    /// environment variables, so rip picks up the indexes an environment configured for pip. The
+    /// pip configuration files have the lowest precedence, the `PIP_*` environment variables take
+    /// precedence over the `rip.toml` files but not over the `RIP_*` environment variables.
+    pub fn load_pip_compatible(project_dir: Option<&Path>) -> Result<Self, ConfigError> {
+        Self::load_layers(project_dir, true)
+    }
+
+    fn load_layers(project_dir: Option<&Path>, pip_compatible: bool) -> Result<Self, ConfigError> {
+        let mut layers = if pip_compatible {
+            pip::config_file_layers()?
+        } else {
+            Vec::new()
+        };
+        let mut read_file = |kind, path: PathBuf| -> Result<(), ConfigError> {
+            if let Some(layer) = ConfigLayer::from_path(&path)? {
+                tracing::debug!("loaded configuration from {}", path.display());
+                layers.push((
+                    ConfigSource {
+                        kind,
+                        path: Some(path),
+                    },
+                    layer,
+                ));
+            }
+            Ok(())
+        };
+
+        if let Some(path) = system_config_path() {
+            read_file(ConfigLayerKind::System, path)?;
+        }
+        if let Some(dir) = dirs::config_dir() {
+            read_file(
+                ConfigLayerKind::User,
+                dir.join("rip").join(CONFIG_FILE_NAME),
+            )?;
+        }
+        if let Some(path) = project_dir.and_then(find_project_config) {
+            read_file(ConfigLayerKind::Project, path)?;
+        }
+
+        if pip_compatible {
+            layers.push((
+                ConfigSource::environment(ConfigLayerKind::PipEnvironment),
+                pip::env_layer()?,
+            ));
+        }
+        layers.push((
+            ConfigSource::environment(ConfigLayerKind::Environment),
+            ConfigLayer::from_env()?,
+        ));
+
+        Ok(Self::from_layers(layers))
+    }
+
+    /// Merges the given layers, later layers take precedence over earlier ones.
+    pub fn from_layers(layers: Vec<(ConfigSource, ConfigLayer)>) -> Self {
+        let mut merged = ConfigLayer::default();
+        for (_, layer) in &layers {
+            merged.merge(layer);
+        }
+
+        let index_urls = if merged.no_index.unwrap_or(false) {
+            Vec::new()
+        } else {
+            merged
+                .index_urls
+                .unwrap_or_else(|| vec![Url::parse("https://pypi.org/simple/").unwrap()])
+                .into_iter()
+                .chain(merged.extra_index_urls.unwrap_or_default())
+                .map(normalize_index_url)
+                .unique()
+                .collect()
+        };
+
+        Self {
+            index_urls,
+            find_links: merged.find_links.unwrap_or_default(),
+            upstream_index: merged.upstream_index.map(normalize_index_url),
+            strict_upstream_index: merged.strict_upstream_index.unwrap_or(false),
+            cache_dir: merged.cache_dir.unwrap_or_else(default_cache_dir),
+            sdist_resolution: merged.sdist_resolution.unwrap_or_default(),
+            auth: merged.auth,
+            build: merged.build,
+            http: merged.http,
+            package_rules: merged.package_rules,
+            package_aliases: merged.package_aliases,
+            provided_packages: merged.provided_packages,
+            layers,
+        }
+    }
+
+    /// Returns the layers the configuration was assembled from, in order of precedence.
+    pub fn layers(&self) -> &[(ConfigSource, ConfigLayer)] {
+        &self.layers
+    }
+
+    /// Returns the configured credentials per host. They are added to the requests of the package
+    /// database instead of to the index urls, so they don't leak into logs or output.
+    pub fn auth_store(&self) -> AuthStore {
+        self.auth
+            .iter()
+            .fold(AuthStore::default(), |store, (host, credentials)| {
+                store.with_credentials(
+                    host.clone(),
+                    credentials.username.clone(),
+                    credentials.password.clone(),
+                )
+            })
+    }
+
+    /// Returns the resolve options that follow from the configuration.
+    pub fn resolve_options(&self) -> ResolveOptions {
+        ResolveOptions {
+            sdist_resolution: self.sdist_resolution,
+            build_environment_variables: self
+                .build
+                .environment
+                .iter()
+                .map(|(package, variables)| {
+                    (
+                        package.clone(),
+                        variables
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.clone()))
+                            .collect::<HashMap<_, _>>(),
+                    )
+                })
+                .collect(),
+            build_config_settings: self
+                .build
+                .config_settings
+                .iter()
+                .map(|(package, settings)| {
+                    (
+                        package.clone(),
+                        settings
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.clone()))
+                            .collect::<HashMap<_, _>>(),
+                    )
+                })
+                .collect(),
+            package_rules: PackageRules::new(self.package_rules.clone()),
+            package_aliases: PackageAliases::new(self.package_aliases.clone()),
+            provided_packages: ProvidedPackages::new(self.provided_packages.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the configured indexes. The first index url is the primary index, the others are
+    /// extra indexes and the find-links pages are flat indexes, see [`IndexSet`].
+    pub fn index_set(&self) -> IndexSet {
+        self.find_links.iter().cloned().fold(
+            IndexSet::from_urls(&self.index_urls),
+            IndexSet::with_flat_index,
+        )
+    }
+
+    /// Builds an HTTP client with the configured [`HttpConfig`].
+    pub fn client(&self) -> reqwest::Result<Client> {
+        self.http.client_options().build_client()
+    }
+
+    /// Constructs a package database for the configured indexes and cache directory.
+    pub fn package_db(&self, client: Client) -> std::io::Result<PackageDb> {
+        let mut package_db = PackageDb::from_indexes(client, self.index_set(), &self.cache_dir)?
+            .with_retry_policy(self.http.retry_policy())
+            .with_auth_store(self.auth_store());
+        if let Some(limit) = self.http.bandwidth_limit.and_then(NonZeroU64::new) {
+            package_db = package_db.with_bandwidth_limit(limit);
+        }
+        if let Some(upstream_index) = &self.upstream_index {
+            package_db = package_db
+                .with_upstream_index(upstream_index.clone())
+                .with_strict_upstream_index(self.strict_upstream_index);
+        }
+        Ok(package_db)
+    }
+}
+
+/// Returns the path of the system configuration file.
+fn system_config_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("PROGRAMDATA")
+            .map(|dir| PathBuf::from(dir).join("rip").join(CONFIG_FILE_NAME))
+    } else {
+        Some(PathBuf::from("/etc/rip").join(CONFIG_FILE_NAME))
+    }
+}
+
+/// Finds the configuration file of the project in `dir` or the closest of its ancestors.
+fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+fn default_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rattler/pypi")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn layer(kind: ConfigLayerKind, contents: &str) -> (ConfigSource, ConfigLayer) {
+        (
+            ConfigSource { kind, path: None },
+            ConfigLayer::from_str(contents).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_merge_layers() {
+        let config = Config::from_layers(vec![
+            layer(
+                ConfigLayerKind::User,
+                r#"
+                index-urls = ["https://pypi.example.com/simple"]
+                sdist-resolution = "only-wheels"
+
+                [auth."pypi.example.com"]
+                username = "user"
+                password = "secret"
+
+                [build.environment.numpy]
+                CFLAGS = "-O2"
+                NPY_BLAS_ORDER = "openblas"
+                "#,
+            ),
+            layer(
+                ConfigLayerKind::Project,
+                r#"
+                sdist-resolution = "prefer-sdists"
+
+                [build.environment.numpy]
+                CFLAGS = "-O3"
+
+                [build.config-settings.pyyaml]
+                "--global-option" = ["--without-libyaml"]
+                "#,
+            ),
+        ]);
+
+        assert_eq!(config.sdist_resolution, SDistResolution::PreferSDists);
+        assert_eq!(
+            config.index_set().primary().unwrap().url.as_str(),
+            "https://pypi.example.com/simple/"
+        );
+        assert_eq!(
+            config.auth_store(),
+            AuthStore::default().with_credentials(
+                "pypi.example.com",
+                "user",
+                Some(String::from("secret"))
+            )
+        );
+        let numpy = &config.build.environment[&"numpy".parse().unwrap()];
+        assert_eq!(numpy["CFLAGS"], "-O3");
+        assert_eq!(numpy["NPY_BLAS_ORDER"], "openblas");
+        let pyyaml = &config.resolve_options().build_config_settings[&"pyyaml".parse().unwrap()];
+        assert_eq!(pyyaml["--global-option"], ["--without-libyaml"]);
+
+        // Passwords are not shown when the configuration is inspected
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("secret"), "{json}");
+        assert_eq!(config.layers().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_http_config() {
+        let config = Config::from_layers(vec![
+            layer(
+                ConfigLayerKind::User,
+                r#"
+                [http]
+                http-version = "http1"
+                pool-max-idle-per-host = 4
+                dns-cache-ttl-secs = 60
+                "#,
+            ),
+            layer(
+                ConfigLayerKind::Project,
+                r#"
+                [http]
+                pool-max-idle-per-host = 16
+                tcp-keepalive-secs = 0
+                "#,
+            ),
+        ]);
+
+        let options = config.http.client_options();
+        assert_eq!(options.http_version, HttpVersion::Http1);
+        assert_eq!(options.pool_max_idle_per_host, 16);
+        assert_eq!(options.dns_cache_ttl, Some(Duration::from_secs(60)));
+        assert_eq!(options.tcp_keepalive, None);
+        assert_eq!(
+            options.pool_idle_timeout,
+            HttpClientOptions::default().pool_idle_timeout
+        );
+        config.client().unwrap();
+
+        let retry_policy = config.http.retry_policy();
+        assert_eq!(retry_policy.max_retries, RetryPolicy::default().max_retries);
+    }
+
+    #[test]
+    fn test_merge_package_rules() {
+        let config = Config::from_layers(vec![
+            layer(
+                ConfigLayerKind::User,
+                r#"
+                [[package-rules]]
+                action = "deny"
+                pattern = "*"
+                "#,
+            ),
+            layer(
+                ConfigLayerKind::Project,
+                r#"
+                package-rules = [{ action = "allow", pattern = "flask" }]
+                "#,
+            ),
+        ]);
+
+        // The rules of the project are evaluated before the rules of the user
+        let rules = config.resolve_options().package_rules;
+        assert_eq!(
+            rules
+                .rules()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["allow flask", "deny *"]
+        );
+        let flask: NormalizedPackageName = "flask".parse().unwrap();
+        let django: NormalizedPackageName = "django".parse().unwrap();
+        assert!(rules.blocked_by(&flask, None).is_none());
+        assert!(rules.blocked_by(&django, None).is_some());
+    }
+
+    #[test]
+    fn test_merge_package_aliases() {
+        let config = Config::from_layers(vec![
+            layer(
+                ConfigLayerKind::User,
+                r#"
+                [package-aliases]
+                PIL = "pillow"
+                sklearn = "scikit-learn"
+                "#,
+            ),
+            layer(
+                ConfigLayerKind::Project,
+                r#"
+                package-aliases = { PIL = "pillow-simd" }
+                "#,
+            ),
+        ]);
+
+        // The aliases of the project override the aliases of the user
+        let aliases = config.resolve_options().package_aliases;
+        let name = |name: &str| name.parse::<NormalizedPackageName>().unwrap();
+        assert_eq!(aliases.resolve(&name("pil")), Some(&name("pillow-simd")));
+        assert_eq!(
+            aliases.resolve(&name("sklearn")),
+            Some(&name("scikit-learn"))
+        );
+    }
+
+    #[test]
+    fn test_merge_provided_packages() {
+        let config = Config::from_layers(vec![
+            layer(
+                ConfigLayerKind::User,
+                r#"
+                [provided-packages]
+                torch = "2.0.1"
+                certifi = "2023.7.22"
+                "#,
+            ),
+            layer(
+                ConfigLayerKind::Project,
+                r#"
+                provided-packages = { torch = "2.1.0" }
+                "#,
+            ),
+        ]);
+
+        // The versions of the project override the versions of the user
+        let provided = config.resolve_options().provided_packages;
+        let name = |name: &str| name.parse::<NormalizedPackageName>().unwrap();
+        assert_eq!(provided.get(&name("torch")).unwrap().to_string(), "2.1.0");
+        assert!(provided.contains(&name("certifi")));
+    }
+
+    #[test]
+    fn test_env_vars() {
+        let vars = HashMap::from([
+            (
+                "RIP_INDEX_URL",
+                "https://a.example.com/simple https://b.example.com/simple",
+            ),
+            ("RIP_SDIST_RESOLUTION", "prefer-wheels"),
+        ]);
+        let layer =
+            ConfigLayer::from_env_vars(|name| vars.get(name).map(ToString::to_string)).unwrap();
+        assert_eq!(layer.index_urls.unwrap().len(), 2);
+        assert_eq!(layer.sdist_resolution, Some(SDistResolution::PreferWheels));
+        assert_eq!(layer.cache_dir, None);
+
+        let err = ConfigLayer::from_env_vars(|name| {
+            (name == "RIP_SDIST_RESOLUTION").then(|| String::from("sometimes"))
+        })
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::InvalidEnvironmentVariable {
+                name: "RIP_SDIST_RESOLUTION",
+                ..
+            }
+        ));
+
+        assert!(ConfigLayer::from_str("unknown-key = 1").is_err());
+    }
+}
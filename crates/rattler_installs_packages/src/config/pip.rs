@@ -0,0 +1,300 @@
+//! Reads the index configuration of pip, so rip can be used as a drop-in in environments that are
+//! already configured for pip.
+//!
+//! The `index-url`, `extra-index-url`, `no-index` and `find-links` options are read from the
+//! `[global]` and `[install]` sections of the pip configuration files and from the corresponding
+//! `PIP_INDEX_URL`, `PIP_EXTRA_INDEX_URL`, `PIP_NO_INDEX` and `PIP_FIND_LINKS` environment
+//! variables. The configuration files are located like pip does: the global files, the files of
+//! the user, the file of the active virtual environment and finally the file that
+//! `PIP_CONFIG_FILE` points to. All other options of pip are ignored.
+
+use super::{ConfigError, ConfigLayer, ConfigLayerKind, ConfigSource};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Reads the pip configuration files in the order pip reads them, later files take precedence.
+pub(super) fn config_file_layers() -> Result<Vec<(ConfigSource, ConfigLayer)>, ConfigError> {
+    let mut layers = Vec::new();
+    for path in config_file_paths() {
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(ConfigError::Io(path, e)),
+        };
+        tracing::debug!("loaded pip configuration from {}", path.display());
+        let layer = parse_config_file(&contents, &path.display().to_string())?;
+        layers.push((
+            ConfigSource {
+                kind: ConfigLayerKind::PipConfig,
+                path: Some(path),
+            },
+            layer,
+        ));
+    }
+    Ok(layers)
+}
+
+/// Reads the `PIP_*` environment variables.
+pub(super) fn env_layer() -> Result<ConfigLayer, ConfigError> {
+    to_layer(
+        |key| std::env::var(format!("PIP_{}", key.replace('-', "_").to_uppercase())).ok(),
+        "the environment",
+    )
+}
+
+/// Returns the paths of the pip configuration files in the order in which pip reads them.
+fn config_file_paths() -> Vec<PathBuf> {
+    let config_file = std::env::var_os("PIP_CONFIG_FILE").map(PathBuf::from);
+
+    // Pip doesn't read any configuration file if the variable points to the null device.
+    if let Some(path) = &config_file {
+        if path == Path::new("/dev/null") || path.as_os_str().eq_ignore_ascii_case("nul") {
+            return Vec::new();
+        }
+    }
+
+    let file_name = if cfg!(windows) { "pip.ini" } else { "pip.conf" };
+    let mut paths = Vec::new();
+
+    // The global configuration
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("PROGRAMDATA") {
+            paths.push(PathBuf::from(dir).join("pip").join(file_name));
+        }
+    } else if cfg!(target_os = "macos") {
+        paths.push(PathBuf::from("/Library/Application Support/pip").join(file_name));
+    } else {
+        let xdg_config_dirs =
+            std::env::var("XDG_CONFIG_DIRS").unwrap_or_else(|_| String::from("/etc/xdg"));
+        for dir in xdg_config_dirs.split(':').filter(|dir| !dir.is_empty()) {
+            paths.push(Path::new(dir).join("pip").join(file_name));
+        }
+        paths.push(PathBuf::from("/etc").join(file_name));
+    }
+
+    // The configuration of the user, both the legacy and the current location
+    if !cfg!(windows) {
+        if let Some(home) = dirs::home_dir() {
+            paths.push(home.join(".pip").join(file_name));
+        }
+    }
+    if let Some(dir) = dirs::config_dir() {
+        paths.push(dir.join("pip").join(file_name));
+    }
+
+    // The configuration of the active virtual environment
+    if let Some(venv) = std::env::var_os("VIRTUAL_ENV") {
+        paths.push(PathBuf::from(venv).join(file_name));
+    }
+
+    paths.extend(config_file);
+    paths.dedup();
+    paths
+}
+
+/// Parses the options of a pip configuration file. Options in the `[install]` section take
+/// precedence over the options in the `[global]` section.
+fn parse_config_file(contents: &str, location: &str) -> Result<ConfigLayer, ConfigError> {
+    let sections = parse_ini(contents);
+    let get = |key: &str| {
+        ["install", "global"]
+            .iter()
+            .find_map(|section| sections.get(*section)?.get(key).cloned())
+    };
+    to_layer(get, location)
+}
+
+/// Parses an ini file into its sections. Keys are normalized like pip does, so `index_url` and
+/// `index-url` are the same option. Values may continue on indented lines.
+fn parse_ini(contents: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut section = None;
+    let mut key: Option<String> = None;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if trimmed.is_empty() {
+            key = None;
+            continue;
+        }
+
+        // A continuation of the value of the previous key
+        if line.starts_with(char::is_whitespace) {
+            if let (Some(section), Some(key)) = (&section, &key) {
+                let values = sections.entry(section.clone()).or_default();
+                if let Some(value) = values.get_mut(key) {
+                    value.push('\n');
+                    value.push_str(trimmed);
+                }
+                continue;
+            }
+        }
+
+        if let Some(name) = trimmed
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            section = Some(name.trim().to_lowercase());
+            key = None;
+        } else if let Some((k, v)) = trimmed.split_once(['=', ':']) {
+            let k = k.trim().to_lowercase().replace('_', "-");
+            if let Some(section) = &section {
+                sections
+                    .entry(section.clone())
+                    .or_default()
+                    .insert(k.clone(), v.trim().to_owned());
+            }
+            key = Some(k);
+        }
+    }
+    sections
+}
+
+/// Converts the options of pip that are looked up with `get` into a configuration layer.
+fn to_layer(
+    get: impl Fn(&str) -> Option<String>,
+    location: &str,
+) -> Result<ConfigLayer, ConfigError> {
+    let invalid = |key: &str, reason: String| ConfigError::InvalidPipOption {
+        location: location.to_owned(),
+        key: key.to_owned(),
+        reason,
+    };
+    let urls = |key: &str| -> Result<Option<Vec<Url>>, ConfigError> {
+        get(key)
+            .map(|value| {
+                value
+                    .split_whitespace()
+                    .map(|url| parse_location(url).map_err(|reason| invalid(key, reason)))
+                    .collect()
+            })
+            .transpose()
+    };
+
+    let index_urls = urls("index-url")?;
+    if index_urls.as_ref().is_some_and(|urls| urls.len() > 1) {
+        return Err(invalid(
+            "index-url",
+            String::from("only a single url is allowed"),
+        ));
+    }
+    let no_index = get("no-index")
+        .map(|value| parse_bool(&value).ok_or_else(|| invalid("no-index", value)))
+        .transpose()?;
+
+    Ok(ConfigLayer {
+        index_urls,
+        extra_index_urls: urls("extra-index-url")?,
+        no_index,
+        find_links: urls("find-links")?,
+        ..Default::default()
+    })
+}
+
+/// Parses a url or an absolute path, pip accepts both.
+fn parse_location(location: &str) -> Result<Url, String> {
+    match Url::parse(location) {
+        Ok(url) => Ok(url),
+        Err(_) if Path::new(location).is_absolute() => {
+            Url::from_file_path(location).map_err(|_| format!("'{location}' is not a valid path"))
+        }
+        Err(e) => Err(format!("'{location}' is not a valid url: {e}")),
+    }
+}
+
+/// Parses a boolean like pip does.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "y" | "yes" | "t" | "true" | "on" | "1" => Some(true),
+        "n" | "no" | "f" | "false" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_parse_config_file() {
+        let layer = parse_config_file(
+            r#"
+# The index of the company
+[global]
+index-url = https://pypi.example.com/simple
+extra_index_url =
+    https://a.example.com/simple
+    https://b.example.com/simple
+timeout = 60
+
+[install]
+find-links: https://example.com/wheels/
+no-index = false
+
+[download]
+index-url = https://ignored.example.com/simple
+"#,
+            "pip.conf",
+        )
+        .unwrap();
+
+        assert_eq!(
+            layer.index_urls,
+            Some(vec![Url::parse("https://pypi.example.com/simple").unwrap()])
+        );
+        assert_eq!(layer.extra_index_urls.as_ref().map(Vec::len), Some(2));
+        assert_eq!(
+            layer.find_links,
+            Some(vec![Url::parse("https://example.com/wheels/").unwrap()])
+        );
+        assert_eq!(layer.no_index, Some(false));
+
+        let config = Config::from_layers(vec![(
+            ConfigSource::environment(ConfigLayerKind::PipConfig),
+            layer,
+        )]);
+        assert_eq!(
+            config
+                .index_urls
+                .iter()
+                .map(Url::as_str)
+                .collect::<Vec<_>>(),
+            vec![
+                "https://pypi.example.com/simple/",
+                "https://a.example.com/simple/",
+                "https://b.example.com/simple/",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let vars = HashMap::from([
+            ("no-index", "yes"),
+            (
+                "find-links",
+                "https://example.com/wheels/ https://example.com/more/",
+            ),
+        ]);
+        let layer = to_layer(|key| vars.get(key).map(ToString::to_string), "env").unwrap();
+        assert_eq!(layer.no_index, Some(true));
+        assert_eq!(layer.find_links.as_ref().map(Vec::len), Some(2));
+
+        let config = Config::from_layers(vec![(
+            ConfigSource::environment(ConfigLayerKind::PipEnvironment),
+            layer,
+        )]);
+        assert!(config.index_urls.is_empty());
+
+        let err = to_layer(
+            |key| (key == "no-index").then(|| String::from("maybe")),
+            "env",
+        )
+        .unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidPipOption { .. }));
+    }
+}
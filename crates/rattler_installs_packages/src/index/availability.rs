@@ -0,0 +1,280 @@
+//! Summarizes which artifacts are available for a version of a package. This allows tooling to
+//! warn users up front when installing a package on their platform requires building it from
+//! source.
+
+use crate::python_env::WheelTags;
+use crate::types::{ArtifactInfo, ArtifactName, WheelFilename};
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// The operating system a wheel was built for, derived from its platform tag.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OperatingSystem {
+    /// Linux distributions that use glibc, e.g. `manylinux_2_17_x86_64` or `linux_x86_64`.
+    Linux,
+    /// Linux distributions that use musl, e.g. `musllinux_1_1_x86_64`.
+    MuslLinux,
+    /// macOS, e.g. `macosx_11_0_arm64`.
+    MacOs,
+    /// Windows, e.g. `win_amd64`.
+    Windows,
+    /// Any other platform, e.g. `emscripten_3_1_45_wasm32`.
+    Other(String),
+}
+
+impl Display for OperatingSystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperatingSystem::Linux => write!(f, "manylinux"),
+            OperatingSystem::MuslLinux => write!(f, "musllinux"),
+            OperatingSystem::MacOs => write!(f, "macOS"),
+            OperatingSystem::Windows => write!(f, "Windows"),
+            OperatingSystem::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// An operating system and architecture a wheel was built for.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct WheelPlatform {
+    /// The operating system
+    pub os: OperatingSystem,
+    /// The architecture, e.g. `x86_64`, `arm64` or `universal2`.
+    pub arch: String,
+}
+
+impl WheelPlatform {
+    /// Parses a platform tag of a wheel, e.g. `manylinux_2_17_x86_64`. Returns `None` for the
+    /// `any` platform which is supported everywhere.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        if tag == "any" {
+            return None;
+        }
+
+        // Windows tags don't contain a version, e.g. `win32`, `win_amd64` and `win_arm64`.
+        if tag == "win32" {
+            return Some(Self::new(OperatingSystem::Windows, "x86"));
+        }
+        if let Some(arch) = tag.strip_prefix("win_") {
+            return Some(Self::new(OperatingSystem::Windows, arch));
+        }
+
+        let (os, rest) = if let Some(rest) = tag.strip_prefix("manylinux") {
+            (OperatingSystem::Linux, rest)
+        } else if let Some(rest) = tag.strip_prefix("musllinux") {
+            (OperatingSystem::MuslLinux, rest)
+        } else if let Some(rest) = tag.strip_prefix("linux") {
+            (OperatingSystem::Linux, rest)
+        } else if let Some(rest) = tag.strip_prefix("macosx") {
+            (OperatingSystem::MacOs, rest)
+        } else {
+            let (os, rest) = tag.split_once('_').unwrap_or((tag, ""));
+            (OperatingSystem::Other(os.to_owned()), rest)
+        };
+
+        // Skip the version of the platform, e.g. `_2_17` in `manylinux_2_17_x86_64` or `2014` in
+        // `manylinux2014_x86_64`.
+        let arch = rest
+            .split('_')
+            .skip_while(|part| part.is_empty() || part.chars().all(|c| c.is_ascii_digit()))
+            .join("_");
+        Some(Self::new(
+            os,
+            if arch.is_empty() { "unknown" } else { &arch },
+        ))
+    }
+
+    fn new(os: OperatingSystem, arch: &str) -> Self {
+        Self {
+            os,
+            arch: arch.to_owned(),
+        }
+    }
+}
+
+impl Display for WheelPlatform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.os, self.arch)
+    }
+}
+
+/// Describes which artifacts are available for a single version of a package.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArtifactAvailability {
+    /// True if a source distribution is available.
+    pub has_sdist: bool,
+
+    /// True if a wheel is available that can be installed on any platform.
+    pub has_universal_wheel: bool,
+
+    /// The python tags of all wheels, e.g. `cp39` or `py3`.
+    pub python_tags: BTreeSet<String>,
+
+    /// The platforms that wheels are available for, excluding the `any` platform.
+    pub platforms: BTreeSet<WheelPlatform>,
+
+    /// The filenames of all available wheels.
+    pub wheels: Vec<WheelFilename>,
+}
+
+impl ArtifactAvailability {
+    /// Summarizes the given artifacts. Yanked artifacts are ignored.
+    pub fn from_artifacts<'a>(artifacts: impl IntoIterator<Item = &'a ArtifactInfo>) -> Self {
+        let mut result = Self::default();
+        for artifact in artifacts.into_iter().filter(|a| !a.yanked.yanked) {
+            match &artifact.filename {
+                ArtifactName::SDist(_) => result.has_sdist = true,
+                ArtifactName::Wheel(wheel) => {
+                    result.python_tags.extend(wheel.py_tags.iter().cloned());
+                    for tag in &wheel.arch_tags {
+                        match WheelPlatform::from_tag(tag) {
+                            Some(platform) => {
+                                result.platforms.insert(platform);
+                            }
+                            None => result.has_universal_wheel = true,
+                        }
+                    }
+                    result.wheels.push(wheel.clone());
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns true if there are wheels that can be installed on the given operating system.
+    pub fn has_wheels_for(&self, os: &OperatingSystem) -> bool {
+        self.has_universal_wheel || self.platforms.iter().any(|p| &p.os == os)
+    }
+
+    /// Returns true if any of the wheels is compatible with the given tags. If this returns false
+    /// installing this version requires building the sdist.
+    pub fn has_compatible_wheel(&self, compatible_tags: &WheelTags) -> bool {
+        self.wheels.iter().any(|wheel| {
+            wheel
+                .all_tags_iter()
+                .any(|tag| compatible_tags.is_compatible(&tag))
+        })
+    }
+}
+
+impl Display for ArtifactAvailability {
+    /// Formats a short human readable summary, e.g.
+    /// `wheels for cp311, cp312 on manylinux x86_64, macOS arm64 (no Windows wheels), sdist`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if !self.wheels.is_empty() {
+            let mut wheels = format!("wheels for {}", self.python_tags.iter().join(", "));
+            if self.has_universal_wheel {
+                wheels.push_str(" on any platform");
+            } else {
+                let mut by_os: BTreeMap<&OperatingSystem, Vec<&str>> = BTreeMap::new();
+                for platform in &self.platforms {
+                    by_os.entry(&platform.os).or_default().push(&platform.arch);
+                }
+                let platforms = by_os
+                    .iter()
+                    .map(|(os, archs)| format!("{os} {}", archs.join("/")))
+                    .join(", ");
+                wheels.push_str(&format!(" on {platforms}"));
+
+                let missing = [
+                    OperatingSystem::Linux,
+                    OperatingSystem::MacOs,
+                    OperatingSystem::Windows,
+                ]
+                .into_iter()
+                .filter(|os| !self.has_wheels_for(os))
+                .join(", ");
+                if !missing.is_empty() {
+                    wheels.push_str(&format!(" (no {missing} wheels)"));
+                }
+            }
+            parts.push(wheels);
+        }
+        if self.has_sdist {
+            parts.push(String::from("sdist"));
+        }
+
+        if parts.is_empty() {
+            write!(f, "no artifacts")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{PackageName, Yanked};
+
+    fn artifact(filename: &str, yanked: bool) -> ArtifactInfo {
+        let name: PackageName = "numpy".parse().unwrap();
+        ArtifactInfo {
+            yanked: Yanked {
+                yanked,
+                reason: None,
+            },
+            ..ArtifactInfo::for_test(&name.into(), filename)
+        }
+    }
+
+    #[test]
+    fn test_wheel_platform_from_tag() {
+        let platform = |tag| WheelPlatform::from_tag(tag).map(|p| p.to_string());
+        assert_eq!(platform("any"), None);
+        assert_eq!(
+            platform("manylinux_2_17_x86_64").as_deref(),
+            Some("manylinux x86_64")
+        );
+        assert_eq!(
+            platform("manylinux2014_aarch64").as_deref(),
+            Some("manylinux aarch64")
+        );
+        assert_eq!(
+            platform("musllinux_1_1_x86_64").as_deref(),
+            Some("musllinux x86_64")
+        );
+        assert_eq!(
+            platform("macosx_11_0_arm64").as_deref(),
+            Some("macOS arm64")
+        );
+        assert_eq!(platform("win_amd64").as_deref(), Some("Windows amd64"));
+        assert_eq!(platform("win32").as_deref(), Some("Windows x86"));
+        assert_eq!(
+            platform("emscripten_3_1_45_wasm32").as_deref(),
+            Some("emscripten wasm32")
+        );
+    }
+
+    #[test]
+    fn test_availability() {
+        let artifacts = [
+            artifact("numpy-1.26.0-cp311-cp311-manylinux_2_17_x86_64.whl", false),
+            artifact("numpy-1.26.0-cp312-cp312-manylinux_2_17_x86_64.whl", false),
+            artifact("numpy-1.26.0-cp312-cp312-macosx_11_0_arm64.whl", false),
+            artifact("numpy-1.26.0-cp312-cp312-win_amd64.whl", true),
+            artifact("numpy-1.26.0.tar.gz", false),
+        ];
+        let availability = ArtifactAvailability::from_artifacts(&artifacts);
+        assert!(availability.has_sdist);
+        assert!(!availability.has_universal_wheel);
+        assert!(availability.has_wheels_for(&OperatingSystem::MacOs));
+        assert!(!availability.has_wheels_for(&OperatingSystem::Windows));
+        assert_eq!(availability.wheels.len(), 3);
+        assert_eq!(
+            availability.to_string(),
+            "wheels for cp311, cp312 on manylinux x86_64, macOS arm64 (no Windows wheels), sdist"
+        );
+
+        let availability = ArtifactAvailability::from_artifacts(&[artifact(
+            "numpy-1.26.0-py3-none-any.whl",
+            false,
+        )]);
+        assert!(availability.has_wheels_for(&OperatingSystem::Windows));
+        assert_eq!(availability.to_string(), "wheels for py3 on any platform");
+    }
+}
@@ -0,0 +1,171 @@
+//! Counters that track how much of the work of a [`super::PackageDb`] was served from its caches.
+
+use super::http::CacheStatus;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The number of hits and misses of a single cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheCounter {
+    /// The number of times data was served from the cache
+    pub hits: u64,
+
+    /// The number of times data had to be downloaded
+    pub misses: u64,
+}
+
+impl CacheCounter {
+    /// The total number of lookups.
+    pub fn total(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    /// The fraction of lookups that were served from the cache, or `None` if there were no
+    /// lookups at all.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        match self.total() {
+            0 => None,
+            total => Some(self.hits as f64 / total as f64),
+        }
+    }
+}
+
+impl std::ops::Add for CacheCounter {
+    type Output = CacheCounter;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        CacheCounter {
+            hits: self.hits + rhs.hits,
+            misses: self.misses + rhs.misses,
+        }
+    }
+}
+
+/// A snapshot of the cache hits and misses of a [`super::PackageDb`] since it was constructed,
+/// see [`super::PackageDb::cache_statistics`].
+///
+/// Index pages count as a hit if the cached page was used, which includes pages that were
+/// revalidated with the server and turned out to be unchanged. Metadata counts as a hit if it was
+/// read from the metadata cache or from a previously downloaded artifact. Artifacts count as a hit
+/// if they were read from the HTTP cache or the pip cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStatistics {
+    /// Simple index and find-links pages
+    pub index_pages: CacheCounter,
+
+    /// The metadata of artifacts
+    pub metadata: CacheCounter,
+
+    /// Wheels and sdists
+    pub artifacts: CacheCounter,
+}
+
+impl CacheStatistics {
+    /// The combined hits and misses of all caches.
+    pub fn total(&self) -> CacheCounter {
+        self.index_pages + self.metadata + self.artifacts
+    }
+}
+
+impl Display for CacheStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.total().hit_ratio() {
+            Some(ratio) => write!(f, "{:.0}% served from cache", ratio * 100.0)?,
+            None => write!(f, "no cache lookups")?,
+        }
+        write!(
+            f,
+            " (index pages: {}/{}, metadata: {}/{}, artifacts: {}/{})",
+            self.index_pages.hits,
+            self.index_pages.total(),
+            self.metadata.hits,
+            self.metadata.total(),
+            self.artifacts.hits,
+            self.artifacts.total()
+        )
+    }
+}
+
+/// The kind of data a cache lookup was for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CacheCategory {
+    IndexPage,
+    Metadata,
+    Artifact,
+}
+
+/// Thread-safe counters from which [`CacheStatistics`] are taken.
+#[derive(Debug, Default)]
+pub(crate) struct CacheStatsRecorder {
+    hits: [AtomicU64; 3],
+    misses: [AtomicU64; 3],
+}
+
+impl CacheStatsRecorder {
+    /// Records a single cache lookup.
+    pub(crate) fn record(&self, category: CacheCategory, hit: bool) {
+        let counters = if hit { &self.hits } else { &self.misses };
+        counters[category as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a lookup based on the [`CacheStatus`] of a response.
+    pub(crate) fn record_response<B>(&self, category: CacheCategory, response: &http::Response<B>) {
+        let hit = matches!(
+            response.extensions().get::<CacheStatus>(),
+            Some(CacheStatus::Fresh | CacheStatus::StaleButValidated)
+        );
+        self.record(category, hit);
+    }
+
+    /// Returns the current values of the counters.
+    pub(crate) fn snapshot(&self) -> CacheStatistics {
+        let counter = |category: CacheCategory| CacheCounter {
+            hits: self.hits[category as usize].load(Ordering::Relaxed),
+            misses: self.misses[category as usize].load(Ordering::Relaxed),
+        };
+        CacheStatistics {
+            index_pages: counter(CacheCategory::IndexPage),
+            metadata: counter(CacheCategory::Metadata),
+            artifacts: counter(CacheCategory::Artifact),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cache_statistics() {
+        let recorder = CacheStatsRecorder::default();
+        assert_eq!(
+            recorder.snapshot().to_string(),
+            "no cache lookups (index pages: 0/0, metadata: 0/0, artifacts: 0/0)"
+        );
+
+        let response = |status| {
+            let mut response = http::Response::new(());
+            response.extensions_mut().insert(status);
+            response
+        };
+        recorder.record_response(CacheCategory::IndexPage, &response(CacheStatus::Fresh));
+        recorder.record_response(
+            CacheCategory::IndexPage,
+            &response(CacheStatus::StaleButValidated),
+        );
+        recorder.record_response(CacheCategory::IndexPage, &response(CacheStatus::Miss));
+        recorder.record(CacheCategory::Metadata, true);
+        recorder.record(CacheCategory::Artifact, false);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.index_pages, CacheCounter { hits: 2, misses: 1 });
+        assert_eq!(stats.metadata.hit_ratio(), Some(1.0));
+        assert_eq!(stats.artifacts.hit_ratio(), Some(0.0));
+        assert_eq!(stats.total(), CacheCounter { hits: 3, misses: 2 });
+        assert_eq!(
+            stats.to_string(),
+            "60% served from cache (index pages: 2/3, metadata: 1/1, artifacts: 0/1)"
+        );
+    }
+}
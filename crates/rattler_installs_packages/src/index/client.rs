@@ -0,0 +1,204 @@
+//! Construction of the HTTP client that is used to talk to indexes.
+//!
+//! Large resolves issue hundreds of small requests to the same host. Reusing connections,
+//! multiplexing requests over HTTP/2 and caching DNS lookups saves a lot of latency compared to
+//! the default settings of [`reqwest::Client`].
+
+use parking_lot::Mutex;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Client, ClientBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The HTTP version that is used to talk to indexes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpVersion {
+    /// Use HTTP/2 with servers that support it, which is negotiated during the TLS handshake, and
+    /// HTTP/1.1 otherwise.
+    #[default]
+    Auto,
+
+    /// Only use HTTP/1.1.
+    Http1,
+
+    /// Assume that all servers support HTTP/2 without negotiating it ("prior knowledge"). This
+    /// also uses HTTP/2 for plain `http` urls, but fails for servers that don't support it.
+    Http2,
+}
+
+/// Options to tune the HTTP client that is used to talk to indexes, see
+/// [`HttpClientOptions::build_client`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpClientOptions {
+    /// The HTTP version to use
+    pub http_version: HttpVersion,
+
+    /// The maximum number of idle connections per host that are kept open to be reused. Defaults
+    /// to no limit.
+    pub pool_max_idle_per_host: usize,
+
+    /// How long idle connections are kept open, `None` keeps them open indefinitely. Defaults to
+    /// 90 seconds.
+    pub pool_idle_timeout: Option<Duration>,
+
+    /// The interval of TCP keep-alive probes, `None` disables them. Defaults to 60 seconds.
+    pub tcp_keepalive: Option<Duration>,
+
+    /// The interval of HTTP/2 pings that keep connections alive, also while they are idle. `None`
+    /// disables them, which is the default.
+    pub http2_keep_alive_interval: Option<Duration>,
+
+    /// How long the addresses of a host are cached, `None` looks up the host for every new
+    /// connection. Defaults to 5 minutes.
+    pub dns_cache_ttl: Option<Duration>,
+
+    /// The timeout for establishing a connection, `None` waits indefinitely, which is the
+    /// default.
+    pub connect_timeout: Option<Duration>,
+}
+
+impl Default for HttpClientOptions {
+    fn default() -> Self {
+        Self {
+            http_version: HttpVersion::default(),
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            http2_keep_alive_interval: None,
+            dns_cache_ttl: Some(Duration::from_secs(5 * 60)),
+            connect_timeout: None,
+        }
+    }
+}
+
+impl HttpClientOptions {
+    /// Returns a [`ClientBuilder`] that is configured with these options. Use this to further
+    /// configure the client, e.g. to set a user agent.
+    pub fn client_builder(&self) -> ClientBuilder {
+        let mut builder = Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .tcp_keepalive(self.tcp_keepalive)
+            .tcp_nodelay(true);
+
+        builder = match self.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        };
+        if let Some(interval) = self.http2_keep_alive_interval {
+            builder = builder
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_while_idle(true);
+        }
+        if let Some(ttl) = self.dns_cache_ttl {
+            builder = builder.dns_resolver(Arc::new(CachingResolver::new(ttl)));
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder
+    }
+
+    /// Builds a client with these options.
+    pub fn build_client(&self) -> reqwest::Result<Client> {
+        self.client_builder().build()
+    }
+}
+
+/// A DNS resolver that caches the addresses of hosts for a fixed amount of time. Lookups are done
+/// with the resolver of the operating system on a blocking thread.
+struct CachingResolver {
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>>,
+}
+
+impl CachingResolver {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Default::default(),
+        }
+    }
+
+    /// Returns the cached addresses of `host` if they did not expire yet.
+    fn cached(&self, host: &str) -> Option<Vec<SocketAddr>> {
+        let mut cache = self.cache.lock();
+        match cache.get(host) {
+            Some((resolved_at, addrs)) if resolved_at.elapsed() < self.ttl => Some(addrs.clone()),
+            Some(_) => {
+                cache.remove(host);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        if let Some(addrs) = self.cached(&host) {
+            return Box::pin(async move { Ok(Box::new(addrs.into_iter()) as Addrs) });
+        }
+
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let lookup_host = host.clone();
+            // The port is replaced by the connector
+            let addrs = tokio::task::spawn_blocking(move || {
+                (lookup_host.as_str(), 0)
+                    .to_socket_addrs()
+                    .map(Iterator::collect::<Vec<_>>)
+            })
+            .await??;
+            if !addrs.is_empty() {
+                cache.lock().insert(host, (Instant::now(), addrs.clone()));
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_client() {
+        for http_version in [HttpVersion::Auto, HttpVersion::Http1, HttpVersion::Http2] {
+            HttpClientOptions {
+                http_version,
+                http2_keep_alive_interval: Some(Duration::from_secs(30)),
+                connect_timeout: Some(Duration::from_secs(10)),
+                ..Default::default()
+            }
+            .build_client()
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_dns_cache_expires() {
+        let addrs = vec![SocketAddr::from(([127, 0, 0, 1], 0))];
+        let resolver = CachingResolver::new(Duration::from_secs(60));
+        resolver
+            .cache
+            .lock()
+            .insert("pypi.org".into(), (Instant::now(), addrs.clone()));
+        assert_eq!(resolver.cached("pypi.org"), Some(addrs.clone()));
+        assert_eq!(resolver.cached("example.com"), None);
+
+        let resolver = CachingResolver::new(Duration::ZERO);
+        resolver
+            .cache
+            .lock()
+            .insert("pypi.org".into(), (Instant::now(), addrs));
+        assert_eq!(resolver.cached("pypi.org"), None);
+        assert!(resolver.cache.lock().is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+//! A hook to review packages before they are downloaded for the first time.
+//!
+//! Compromised releases are usually discovered and yanked within days. Integrators can protect
+//! against them by only downloading releases that have been approved, or that have been public
+//! for a while. [`super::PackageDb::with_download_approval`] installs a [`DownloadApproval`] that
+//! is asked before any artifact, or the metadata of an artifact, that is not available locally is
+//! downloaded.
+
+use crate::types::{ArtifactInfo, NormalizedPackageName, Version};
+use miette::Diagnostic;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+/// An artifact that is about to be downloaded for the first time, see [`DownloadApproval`].
+#[derive(Debug, Clone, Copy)]
+pub struct FirstDownload<'a> {
+    /// The name of the package
+    pub name: &'a NormalizedPackageName,
+
+    /// The version of the package
+    pub version: &'a Version,
+
+    /// The artifact that is about to be downloaded, or the artifact whose metadata is about to be
+    /// downloaded
+    pub artifact_info: &'a ArtifactInfo,
+}
+
+/// The result of a [`DownloadApproval`] review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadDecision {
+    /// The artifact may be downloaded.
+    Approve,
+
+    /// The artifact must not be downloaded.
+    Reject {
+        /// Why the artifact was rejected, reported to the user
+        reason: String,
+    },
+}
+
+/// Reviews artifacts before they are downloaded for the first time, i.e. before they or their
+/// metadata are fetched from the network because they are not cached locally. Every artifact is
+/// reviewed on its own, an approved wheel does not approve the sdist of the same version.
+///
+/// Once an artifact is approved the hook is not asked again for that artifact by the same
+/// [`super::PackageDb`]. Versions that are already pinned, e.g. by a lock file, can be approved up
+/// front with [`super::PackageDb::approve_download`]. Rejected versions fail with a
+/// [`DownloadRejected`] error.
+///
+/// Any `Fn(&FirstDownload) -> DownloadDecision` closure implements this trait.
+pub trait DownloadApproval: Send + Sync {
+    /// Decides whether the artifact may be downloaded.
+    fn review(&self, download: &FirstDownload<'_>) -> DownloadDecision;
+}
+
+impl<F> DownloadApproval for F
+where
+    F: Fn(&FirstDownload<'_>) -> DownloadDecision + Send + Sync,
+{
+    fn review(&self, download: &FirstDownload<'_>) -> DownloadDecision {
+        self(download)
+    }
+}
+
+/// Only approves artifacts that were uploaded at least `min_age` ago, giving the community time to
+/// discover and yank malicious releases. Artifacts for which the index does not report an upload
+/// time are rejected, because their age cannot be checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinimumAge {
+    /// How long ago an artifact must have been uploaded
+    pub min_age: Duration,
+}
+
+impl DownloadApproval for MinimumAge {
+    fn review(&self, download: &FirstDownload<'_>) -> DownloadDecision {
+        let Some(upload_time) = download.artifact_info.upload_time else {
+            return DownloadDecision::Reject {
+                reason: String::from("the index does not report when it was uploaded"),
+            };
+        };
+        let now: chrono::DateTime<chrono::Utc> = SystemTime::now().into();
+        let age = (now - upload_time).to_std().unwrap_or_default();
+        if age >= self.min_age {
+            DownloadDecision::Approve
+        } else {
+            DownloadDecision::Reject {
+                reason: format!(
+                    "it was uploaded {} hours ago, which is less than the required {} hours",
+                    age.as_secs() / 3600,
+                    self.min_age.as_secs() / 3600
+                ),
+            }
+        }
+    }
+}
+
+/// The error returned when a [`DownloadApproval`] rejected a download.
+#[derive(Debug, Clone, Error, Diagnostic)]
+#[error("downloading {name} {version} was rejected: {reason}")]
+pub struct DownloadRejected {
+    /// The name of the rejected package
+    pub name: NormalizedPackageName,
+
+    /// The rejected version
+    pub version: Version,
+
+    /// Why the version was rejected
+    pub reason: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn artifact_info(upload_time: Option<chrono::DateTime<chrono::Utc>>) -> ArtifactInfo {
+        let name: NormalizedPackageName = "flask".parse().unwrap();
+        ArtifactInfo {
+            upload_time,
+            ..ArtifactInfo::for_test(&name, "flask-3.0.0-py3-none-any.whl")
+        }
+    }
+
+    #[test]
+    fn test_minimum_age() {
+        let now: chrono::DateTime<chrono::Utc> = SystemTime::now().into();
+        let policy = MinimumAge {
+            min_age: Duration::from_secs(7 * 24 * 3600),
+        };
+        let name: NormalizedPackageName = "flask".parse().unwrap();
+        let version: Version = "3.0.0".parse().unwrap();
+        let review = |artifact_info: &ArtifactInfo| {
+            policy.review(&FirstDownload {
+                name: &name,
+                version: &version,
+                artifact_info,
+            })
+        };
+
+        let old = artifact_info(Some(now - chrono::Duration::days(30)));
+        assert_eq!(review(&old), DownloadDecision::Approve);
+
+        let new = artifact_info(Some(now - chrono::Duration::days(2)));
+        assert!(matches!(review(&new), DownloadDecision::Reject { .. }));
+
+        let unknown = artifact_info(None);
+        assert!(matches!(review(&unknown), DownloadDecision::Reject { .. }));
+    }
+}
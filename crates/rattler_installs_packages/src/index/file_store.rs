@@ -115,6 +115,15 @@ impl FileStore {
         None
     }
 
+    /// Opens the data of an entry without locking it. Returns `None` if no such key exists in the
+    /// store.
+    ///
+    /// Entries are replaced atomically so this never observes a partially written entry, but the
+    /// entry might be replaced while it's being read.
+    pub fn peek<K: CacheKey>(&self, key: &K) -> Option<File> {
+        File::open(self.base.join(key.key())).ok()
+    }
+
     /// Locks a certain file in the cache for exclusive access.
     pub fn lock<K: CacheKey>(&self, key: &K) -> io::Result<FileLock> {
         let path = self.base.join(key.key());
@@ -0,0 +1,366 @@
+//! Functionality to generate a static "simple" package index from a directory of artifacts.
+//!
+//! The generated tree follows [PEP 503](https://peps.python.org/pep-0503/) for the HTML pages and
+//! [PEP 691](https://peps.python.org/pep-0691/) for the JSON pages. Metadata of every artifact is
+//! made available as a separate sidecar file as described in
+//! [PEP 658](https://peps.python.org/pep-0658/). The resulting directory can be served by any
+//! static file server or used directly through a `file://` url.
+
+use super::wheelhouse::scan_wheelhouse;
+use crate::artifacts::{SDist, Wheel};
+use crate::types::{ArtifactName, NormalizedPackageName};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_digest::{compute_bytes_digest, Sha256, Sha256Hash};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Information about a single artifact that was added to a generated index.
+#[derive(Debug, Clone)]
+pub struct GeneratedArtifact {
+    /// The name of the artifact
+    pub filename: ArtifactName,
+
+    /// The sha256 hash of the artifact
+    pub sha256: Sha256Hash,
+
+    /// The python requirement of the artifact as specified in its metadata.
+    pub requires_python: Option<String>,
+
+    /// The sha256 hash of the metadata sidecar file (`<filename>.metadata`) if the metadata could
+    /// be extracted from the artifact.
+    pub metadata_sha256: Option<Sha256Hash>,
+}
+
+/// Generates a static simple index in `dest` from all the wheels and sdists found directly in the
+/// `source` directory.
+///
+/// For every project a `<project>/index.html` and `<project>/index.json` file is written and the
+/// artifacts are copied next to them. If the metadata of an artifact can be read, a
+/// `<filename>.metadata` sidecar file is written as well. The artifacts are found with
+/// [`scan_wheelhouse`], files in `source` that are not recognized as artifacts are skipped.
+///
+/// Returns the artifacts that were added to the index grouped by project.
+pub fn generate_index(
+    source: &Path,
+    dest: &Path,
+) -> miette::Result<IndexMap<NormalizedPackageName, Vec<GeneratedArtifact>>> {
+    // Find all the artifacts in the source directory
+    let scan = scan_wheelhouse(source)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read directory '{}'", source.display()))?;
+    for err in &scan.errors {
+        tracing::warn!("skipping {err}");
+    }
+    let mut projects: BTreeMap<NormalizedPackageName, Vec<(ArtifactName, PathBuf, Sha256Hash)>> =
+        Default::default();
+    for artifact in scan.artifacts {
+        let path = artifact
+            .url
+            .to_file_path()
+            .expect("wheelhouse artifacts are local files");
+        let sha256 = artifact
+            .hashes
+            .and_then(|hashes| hashes.sha256)
+            .expect("wheelhouse artifacts are hashed");
+        projects
+            .entry(artifact.filename.distribution().clone().into())
+            .or_default()
+            .push((artifact.filename, path, sha256));
+    }
+
+    std::fs::create_dir_all(dest)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to create directory '{}'", dest.display()))?;
+
+    // Write the pages of the individual projects
+    let mut result = IndexMap::new();
+    for (project_name, mut artifacts) in projects {
+        artifacts.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        let project_dir = dest.join(project_name.as_str());
+        std::fs::create_dir_all(&project_dir).into_diagnostic()?;
+
+        let mut generated = Vec::with_capacity(artifacts.len());
+        for (artifact_name, path, sha256) in artifacts {
+            let file_name = artifact_name.to_string();
+            let target = project_dir.join(&file_name);
+            std::fs::copy(&path, &target)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to copy '{}'", path.display()))?;
+
+            // Write the metadata sidecar if we can extract the metadata from the artifact
+            let metadata = read_metadata(&artifact_name, &target, &project_name);
+            let (requires_python, metadata_sha256) = match metadata {
+                Some((bytes, requires_python)) => {
+                    std::fs::write(project_dir.join(format!("{file_name}.metadata")), &bytes)
+                        .into_diagnostic()?;
+                    (
+                        requires_python,
+                        Some(compute_bytes_digest::<Sha256>(&bytes)),
+                    )
+                }
+                None => (None, None),
+            };
+
+            generated.push(GeneratedArtifact {
+                filename: artifact_name,
+                sha256,
+                requires_python,
+                metadata_sha256,
+            });
+        }
+
+        std::fs::write(
+            project_dir.join("index.html"),
+            project_page_html(&project_name, &generated),
+        )
+        .into_diagnostic()?;
+        std::fs::write(
+            project_dir.join("index.json"),
+            project_page_json(&project_name, &generated)?,
+        )
+        .into_diagnostic()?;
+
+        result.insert(project_name, generated);
+    }
+
+    // Write the root pages
+    std::fs::write(dest.join("index.html"), root_page_html(result.keys())).into_diagnostic()?;
+    std::fs::write(dest.join("index.json"), root_page_json(result.keys())?).into_diagnostic()?;
+
+    Ok(result)
+}
+
+/// Reads the metadata from an artifact. Returns the raw bytes of the metadata and the python
+/// requirement if any. Returns `None` if the metadata could not be read.
+fn read_metadata(
+    artifact_name: &ArtifactName,
+    path: &Path,
+    project_name: &NormalizedPackageName,
+) -> Option<(Vec<u8>, Option<String>)> {
+    let result = match artifact_name {
+        ArtifactName::Wheel(_) => Wheel::from_path(path, project_name)
+            .and_then(|wheel| wheel.metadata())
+            .ok(),
+        ArtifactName::SDist(_) => SDist::from_path(path, project_name)
+            .ok()
+            .and_then(|sdist| sdist.pep643_metadata()),
+    };
+    if result.is_none() {
+        tracing::warn!("could not read metadata from '{}'", path.display());
+    }
+    result.map(|(bytes, metadata)| (bytes, metadata.requires_python.map(|spec| spec.to_string())))
+}
+
+fn project_page_html(
+    project_name: &NormalizedPackageName,
+    artifacts: &[GeneratedArtifact],
+) -> String {
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(html, "<html>").unwrap();
+    writeln!(html, "  <head>").unwrap();
+    writeln!(
+        html,
+        "    <meta name=\"pypi:repository-version\" content=\"1.0\">"
+    )
+    .unwrap();
+    writeln!(html, "    <title>Links for {}</title>", project_name).unwrap();
+    writeln!(html, "  </head>").unwrap();
+    writeln!(html, "  <body>").unwrap();
+    writeln!(html, "    <h1>Links for {}</h1>", project_name).unwrap();
+    for artifact in artifacts {
+        let file_name = artifact.filename.to_string();
+        write!(
+            html,
+            "    <a href=\"{}#sha256={:x}\"",
+            html_escape::encode_double_quoted_attribute(&file_name),
+            artifact.sha256
+        )
+        .unwrap();
+        if let Some(requires_python) = &artifact.requires_python {
+            write!(
+                html,
+                " data-requires-python=\"{}\"",
+                html_escape::encode_double_quoted_attribute(requires_python)
+            )
+            .unwrap();
+        }
+        if let Some(metadata_sha256) = &artifact.metadata_sha256 {
+            // `data-dist-info-metadata` has been renamed to `data-core-metadata` in PEP 714, we
+            // write both for compatibility with older clients.
+            write!(
+                html,
+                " data-dist-info-metadata=\"sha256={metadata_sha256:x}\" data-core-metadata=\"sha256={metadata_sha256:x}\""
+            )
+            .unwrap();
+        }
+        writeln!(html, ">{}</a><br/>", html_escape::encode_text(&file_name)).unwrap();
+    }
+    writeln!(html, "  </body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+    html
+}
+
+fn root_page_html<'a>(project_names: impl Iterator<Item = &'a NormalizedPackageName>) -> String {
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(html, "<html>").unwrap();
+    writeln!(html, "  <head>").unwrap();
+    writeln!(
+        html,
+        "    <meta name=\"pypi:repository-version\" content=\"1.0\">"
+    )
+    .unwrap();
+    writeln!(html, "    <title>Simple index</title>").unwrap();
+    writeln!(html, "  </head>").unwrap();
+    writeln!(html, "  <body>").unwrap();
+    for name in project_names {
+        writeln!(html, "    <a href=\"{name}/\">{name}</a>").unwrap();
+    }
+    writeln!(html, "  </body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+    html
+}
+
+/// The JSON representation of a project page as described in PEP 691.
+#[derive(Serialize)]
+struct JsonProjectPage<'a> {
+    meta: JsonMeta,
+    name: &'a str,
+    files: Vec<JsonFile>,
+}
+
+/// The JSON representation of the root page as described in PEP 691.
+#[derive(Serialize)]
+struct JsonRootPage<'a> {
+    meta: JsonMeta,
+    projects: Vec<JsonProject<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonProject<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonMeta {
+    #[serde(rename = "api-version")]
+    api_version: &'static str,
+}
+
+impl Default for JsonMeta {
+    fn default() -> Self {
+        Self { api_version: "1.0" }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonFile {
+    filename: String,
+    url: String,
+    hashes: BTreeMap<&'static str, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    requires_python: Option<String>,
+    dist_info_metadata: serde_json::Value,
+    core_metadata: serde_json::Value,
+    yanked: bool,
+}
+
+fn project_page_json(
+    project_name: &NormalizedPackageName,
+    artifacts: &[GeneratedArtifact],
+) -> miette::Result<String> {
+    let files = artifacts
+        .iter()
+        .map(|artifact| {
+            let metadata = match &artifact.metadata_sha256 {
+                Some(hash) => serde_json::json!({ "sha256": format!("{hash:x}") }),
+                None => serde_json::Value::Bool(false),
+            };
+            let file_name = artifact.filename.to_string();
+            JsonFile {
+                url: file_name.clone(),
+                filename: file_name,
+                hashes: BTreeMap::from_iter([("sha256", format!("{:x}", artifact.sha256))]),
+                requires_python: artifact.requires_python.clone(),
+                dist_info_metadata: metadata.clone(),
+                core_metadata: metadata,
+                yanked: false,
+            }
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&JsonProjectPage {
+        meta: JsonMeta::default(),
+        name: project_name.as_str(),
+        files,
+    })
+    .into_diagnostic()
+}
+
+fn root_page_json<'a>(
+    project_names: impl Iterator<Item = &'a NormalizedPackageName>,
+) -> miette::Result<String> {
+    serde_json::to_string_pretty(&JsonRootPage {
+        meta: JsonMeta::default(),
+        projects: project_names
+            .map(|name| JsonProject {
+                name: name.as_str(),
+            })
+            .collect_vec(),
+    })
+    .into_diagnostic()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::html::parse_project_info_html;
+    use url::Url;
+
+    #[test]
+    fn test_generate_index() {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/wheels");
+        let dest = tempfile::tempdir().unwrap();
+
+        let generated = generate_index(&source, dest.path()).unwrap();
+        assert_eq!(
+            generated.keys().map(|name| name.as_str()).collect_vec(),
+            vec!["miniblack", "purelib-and-platlib", "wordle-python"]
+        );
+
+        // Parse the generated html page back in and make sure we find the artifact again.
+        let project_dir = dest.path().join("miniblack");
+        let html = std::fs::read_to_string(project_dir.join("index.html")).unwrap();
+        let project_info =
+            parse_project_info_html(&Url::from_directory_path(&project_dir).unwrap(), &html)
+                .unwrap();
+        assert_eq!(project_info.files.len(), 1);
+        let artifact_info = &project_info.files[0];
+        assert_eq!(
+            artifact_info.filename.to_string(),
+            "miniblack-23.1.0-py3-none-any.whl"
+        );
+        assert!(artifact_info.dist_info_metadata.available);
+        assert_eq!(
+            artifact_info.hashes.as_ref().and_then(|h| h.sha256),
+            Some(generated["miniblack"][0].sha256)
+        );
+        assert!(project_dir
+            .join("miniblack-23.1.0-py3-none-any.whl.metadata")
+            .is_file());
+
+        // The json page must also be valid
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(project_dir.join("index.json")).unwrap())
+                .unwrap();
+        assert_eq!(json["name"], "miniblack");
+        assert_eq!(json["files"].as_array().unwrap().len(), 1);
+    }
+}
@@ -13,7 +13,8 @@ use url::Url;
 
 use crate::types::{ArtifactInfo, DistInfoMetadata, ProjectInfo, Yanked};
 
-fn parse_hash(s: &str) -> Option<ArtifactHashes> {
+/// Parses a hash in the form `sha256=<hex>`, like in the fragment of an artifact url.
+pub(crate) fn parse_hash(s: &str) -> Option<ArtifactHashes> {
     if let Some(("sha256", hex)) = s.split_once('=') {
         Some(ArtifactHashes {
             sha256: parse_digest_from_hex::<Sha256>(hex),
@@ -23,6 +24,45 @@ fn parse_hash(s: &str) -> Option<ArtifactHashes> {
     }
 }
 
+/// Parses the `requires-python` value of an artifact leniently. Some indexes list malformed values
+/// like `>=3.6,` or `>=3.6.*`, these are repaired where the intent is clear. Values that cannot be
+/// parsed are ignored with a warning, like pip does, instead of dropping the artifact.
+pub(crate) fn parse_requires_python(
+    raw: &str,
+    filename: &impl std::fmt::Display,
+) -> Option<VersionSpecifiers> {
+    let err = match VersionSpecifiers::from_str(raw) {
+        Ok(specifiers) => return Some(specifiers),
+        Err(err) => err,
+    };
+
+    let repaired = raw
+        .split(',')
+        .map(|specifier| specifier.trim().trim_matches(|c| c == '"' || c == '\''))
+        .filter(|specifier| !specifier.is_empty())
+        .map(|specifier| {
+            // Wildcards are only allowed with `==` and `!=`, and versions don't end with a dot.
+            let specifier = specifier.trim_end_matches('.');
+            if specifier.starts_with("==") || specifier.starts_with("!=") {
+                specifier
+            } else {
+                specifier.trim_end_matches(".*")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    match VersionSpecifiers::from_str(&repaired) {
+        Ok(specifiers) => {
+            tracing::debug!("repaired requires-python '{raw}' of {filename} to '{specifiers}'");
+            Some(specifiers)
+        }
+        Err(_) => {
+            tracing::warn!("ignoring invalid requires-python '{raw}' of {filename}: {err}");
+            None
+        }
+    }
+}
+
 fn into_artifact_info(
     base: &Url,
     normalized_package_name: &NormalizedPackageName,
@@ -41,16 +81,13 @@ fn into_artifact_info(
 
     // We found a valid link
     let hash = url.fragment().and_then(parse_hash);
-    let requires_python = attributes
+    let requires_python_raw = attributes
         .get("data-requires-python")
         .flatten()
-        .map(|a| {
-            VersionSpecifiers::from_str(
-                html_escape::decode_html_entities(a.as_utf8_str().as_ref()).as_ref(),
-            )
-        })
-        .transpose()
-        .ok()?;
+        .map(|a| html_escape::decode_html_entities(a.as_utf8_str().as_ref()).into_owned());
+    let requires_python = requires_python_raw
+        .as_deref()
+        .and_then(|raw| parse_requires_python(raw, &filename));
 
     let metadata_attr = attributes
         .get("data-dist-info-metadata")
@@ -72,6 +109,12 @@ fn into_artifact_info(
         },
     };
 
+    let provenance = attributes
+        .get("data-provenance")
+        .flatten()
+        .map(|a| html_escape::decode_html_entities(a.as_utf8_str().as_ref()).into_owned())
+        .and_then(|provenance| base.join(&provenance).ok());
+
     let yanked_reason = attributes
         .get("data-yanked")
         .flatten()
@@ -92,17 +135,17 @@ fn into_artifact_info(
         url,
         hashes: hash,
         requires_python,
+        requires_python_raw,
         dist_info_metadata,
         yanked,
+        size: None,
+        upload_time: None,
+        provenance,
     })
 }
 
 /// Parses information regarding the different artifacts for a project
 pub fn parse_project_info_html(base: &Url, body: &str) -> miette::Result<ProjectInfo> {
-    let dom = tl::parse(body, tl::ParserOptions::default()).into_diagnostic()?;
-    let variants = dom.query_selector("a");
-    let mut project_info = ProjectInfo::default();
-
     // Find the package name from the URL
     let last_non_empty_segment = base.path_segments().and_then(|segments| {
         segments
@@ -125,6 +168,30 @@ pub fn parse_project_info_html(base: &Url, body: &str) -> miette::Result<Project
         return Err(miette!("no package segments found in url: '{base}'"));
     };
 
+    parse_artifact_links(base, body, &normalized_package_name)
+}
+
+/// Parses the artifacts of a single package from a flat page that links to the artifacts of many
+/// packages, like the pages passed to pip with `--find-links`. Links to artifacts of other
+/// packages are ignored.
+pub fn parse_find_links_html(
+    base: &Url,
+    body: &str,
+    package_name: &NormalizedPackageName,
+) -> miette::Result<ProjectInfo> {
+    parse_artifact_links(base, body, package_name)
+}
+
+/// Parses the links to the artifacts of the given package from an HTML page.
+fn parse_artifact_links(
+    base: &Url,
+    body: &str,
+    normalized_package_name: &NormalizedPackageName,
+) -> miette::Result<ProjectInfo> {
+    let dom = tl::parse(body, tl::ParserOptions::default()).into_diagnostic()?;
+    let variants = dom.query_selector("a");
+    let mut project_info = ProjectInfo::default();
+
     // Select repository version
     project_info.meta.version = dom
         .query_selector("meta[name=\"pypi:repository-version\"]")
@@ -166,7 +233,7 @@ pub fn parse_project_info_html(base: &Url, body: &str) -> miette::Result<Project
 
         // Parse and add <a></a> tags
         for a in a_tags {
-            let artifact_info = into_artifact_info(&base, &normalized_package_name, a);
+            let artifact_info = into_artifact_info(&base, normalized_package_name, a);
             if let Some(artifact_info) = artifact_info {
                 project_info.files.push(artifact_info);
             }
@@ -197,6 +264,16 @@ pub fn parse_package_names_html(body: &str) -> miette::Result<Vec<String>> {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_parse_requires_python() {
+        let parse = |raw| parse_requires_python(raw, &"foo-1.0.tar.gz").map(|s| s.to_string());
+        assert_eq!(parse(">=3.7").as_deref(), Some(">=3.7"));
+        assert_eq!(parse(">=3.6,").as_deref(), Some(">=3.6"));
+        assert_eq!(parse(">=3.6.*").as_deref(), Some(">=3.6"));
+        assert_eq!(parse("'>=2.7', !=3.0.*").as_deref(), Some(">=2.7, !=3.0.*"));
+        assert_eq!(parse("python3 only"), None);
+    }
+
     #[test]
     fn test_sink_simple() {
         let parsed = parse_project_info_html(
@@ -232,6 +309,7 @@ mod test {
                 sha256: Some("0000000000000000000000000000000000000000000000000000000000000000"),
               )),
               r#requires-python: None,
+              r#requires-python-raw: None,
               r#dist-info-metadata: DistInfoMetadata(
                 available: false,
                 hashes: ArtifactHashes(),
@@ -240,6 +318,8 @@ mod test {
                 yanked: false,
                 reason: None,
               ),
+              size: None,
+              r#upload-time: None,
             ),
             ArtifactInfo(
               filename: SDist(SDistFilename(
@@ -250,6 +330,7 @@ mod test {
               url: "https://example.com/elsewhere/link-2.0.zip",
               hashes: None,
               r#requires-python: None,
+              r#requires-python-raw: None,
               r#dist-info-metadata: DistInfoMetadata(
                 available: false,
                 hashes: ArtifactHashes(),
@@ -258,6 +339,8 @@ mod test {
                 yanked: true,
                 reason: Some("some reason"),
               ),
+              size: None,
+              r#upload-time: None,
             ),
             ArtifactInfo(
               filename: SDist(SDistFilename(
@@ -268,6 +351,7 @@ mod test {
               url: "https://example.com/new-base/link-3.0.tar.gz",
               hashes: None,
               r#requires-python: Some(">=3.17"),
+              r#requires-python-raw: Some(">= 3.17"),
               r#dist-info-metadata: DistInfoMetadata(
                 available: false,
                 hashes: ArtifactHashes(),
@@ -276,8 +360,11 @@ mod test {
                 yanked: false,
                 reason: None,
               ),
+              size: None,
+              r#upload-time: None,
             ),
           ],
+          versions: [],
         )
         "###);
     }
@@ -383,4 +470,35 @@ mod test {
         ]
         "###);
     }
+
+    #[test]
+    fn test_parse_find_links() {
+        let parsed = parse_find_links_html(
+            &Url::parse("https://example.com/wheels/").unwrap(),
+            r#"<html>
+                <body>
+                  <a href="rich-13.6.0-py3-none-any.whl">rich-13.6.0-py3-none-any.whl</a>
+                  <a href="rich-13.5.0.tar.gz">rich-13.5.0.tar.gz</a>
+                  <a href="rich_click-1.7.0-py3-none-any.whl">rich_click-1.7.0-py3-none-any.whl</a>
+                  <a href="pygments-2.16.1-py3-none-any.whl">pygments-2.16.1-py3-none-any.whl</a>
+                </body>
+              </html>
+            "#,
+            &"rich".parse().unwrap(),
+        )
+        .unwrap();
+
+        let urls = parsed
+            .files
+            .iter()
+            .map(|artifact| artifact.url.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/wheels/rich-13.6.0-py3-none-any.whl",
+                "https://example.com/wheels/rich-13.5.0.tar.gz",
+            ]
+        );
+    }
 }
@@ -1,16 +1,25 @@
-use super::file_store::FileLock;
+use super::auth::{redact_url, AuthStore};
+use super::cache_stats::CacheStatsRecorder;
 use super::file_store::FileStore;
+use super::file_store::{FileLock, LockedWriter};
 use super::package_database::NotCached;
+use super::rate_limit::{BandwidthLimiter, RetryPolicy};
+use crate::session_log::{CacheKind, SessionEvent, SessionLog};
 use crate::utils::{ReadAndSeek, SeekSlice, StreamingOrLocal};
 use bytes::Bytes;
 use futures::{Stream, StreamExt, TryStreamExt};
-use http::header::{ACCEPT, CACHE_CONTROL};
+use http::header::{ACCEPT, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH};
 use http_cache_semantics::{AfterResponse, BeforeRequest, CachePolicy};
 use miette::Diagnostic;
+use parking_lot::Mutex;
+use rattler_digest::{Sha256, Sha256Hash};
 use reqwest::{header::HeaderMap, Client, Method};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::SystemTime;
@@ -39,11 +48,34 @@ pub enum CacheMode {
     NoStore,
 }
 
+/// Defines whether HTTP responses are recorded to or replayed from fixtures on disk. This makes it
+/// possible to capture the responses of a live index once and replay them later in tests without
+/// hitting the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Perform requests as usual but store every successful response in the given directory.
+    Record(PathBuf),
+    /// Never hit the network but serve all responses from the fixtures in the given directory.
+    /// Requests for which no fixture exists fail.
+    Replay(PathBuf),
+}
+
 #[derive(Debug, Clone)]
 pub struct Http {
     pub(crate) client: Client,
     http_cache: Arc<FileStore>,
     _hash_cache: Arc<FileStore>,
+    fixture_mode: Option<FixtureMode>,
+    session_log: Option<SessionLog>,
+    retry_policy: RetryPolicy,
+    bandwidth_limiter: Option<BandwidthLimiter>,
+    cache_stats: Arc<CacheStatsRecorder>,
+    auth: AuthStore,
+
+    /// The size and modification time of the cache entries whose body hash was verified. Hashing
+    /// the body of every cache hit is expensive for large artifacts, so an entry is only hashed
+    /// again if it changed on disk since it was verified.
+    verified_entries: Arc<Mutex<HashMap<Vec<u8>, (u64, SystemTime)>>>,
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -57,6 +89,16 @@ pub enum HttpRequestError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     NotCached(#[from] NotCached),
+
+    #[error("no recorded fixture found for {0}")]
+    FixtureNotFound(Url),
+
+    #[error("the download of {url} is incomplete, received {received} of {expected} bytes")]
+    IncompleteBody {
+        url: Url,
+        expected: u64,
+        received: u64,
+    },
 }
 
 impl Http {
@@ -66,9 +108,74 @@ impl Http {
             client,
             http_cache: Arc::new(http_cache),
             _hash_cache: Arc::new(hash_cache),
+            fixture_mode: None,
+            session_log: None,
+            retry_policy: RetryPolicy::default(),
+            bandwidth_limiter: None,
+            cache_stats: Default::default(),
+            auth: AuthStore::default(),
+            verified_entries: Default::default(),
+        }
+    }
+
+    /// Adds the credentials of the store to the requests to their hosts, see [`AuthStore`].
+    pub fn with_auth_store(self, auth: AuthStore) -> Self {
+        Self { auth, ..self }
+    }
+
+    /// Returns the credentials that are added to requests.
+    pub fn auth_store(&self) -> &AuthStore {
+        &self.auth
+    }
+
+    /// Sets how requests that are throttled by the server are retried.
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Limits the total rate at which response bodies are downloaded to the given number of bytes
+    /// per second.
+    pub fn with_bandwidth_limit(self, bytes_per_second: NonZeroU64) -> Self {
+        Self {
+            bandwidth_limiter: Some(BandwidthLimiter::new(bytes_per_second)),
+            ..self
+        }
+    }
+
+    /// Records every request in the given session log.
+    pub fn with_session_log(self, session_log: SessionLog) -> Self {
+        Self {
+            session_log: Some(session_log),
+            ..self
         }
     }
 
+    /// Returns the session log of this instance if any.
+    pub fn session_log(&self) -> Option<&SessionLog> {
+        self.session_log.as_ref()
+    }
+
+    /// Records responses to or replays responses from fixtures on disk.
+    pub fn with_fixture_mode(self, fixture_mode: FixtureMode) -> Self {
+        Self {
+            fixture_mode: Some(fixture_mode),
+            ..self
+        }
+    }
+
+    /// Returns the counters of cache hits and misses, which are shared by all clones.
+    pub(crate) fn cache_stats(&self) -> &CacheStatsRecorder {
+        &self.cache_stats
+    }
+
+    /// Returns the fixture mode of this instance if any.
+    pub fn fixture_mode(&self) -> Option<&FixtureMode> {
+        self.fixture_mode.as_ref()
+    }
+
     /// Performs a single request caching the result internally if requested.
     pub async fn request(
         &self,
@@ -77,19 +184,117 @@ impl Http {
         headers: HeaderMap,
         cache_mode: CacheMode,
     ) -> Result<http::Response<StreamingOrLocal>, HttpRequestError> {
-        tracing::info!(url=%url, cache_mode=?cache_mode, "executing request");
+        let session_log = self
+            .session_log
+            .as_ref()
+            .map(|session_log| (session_log, url.clone(), method.clone()));
+
+        let result = match &self.fixture_mode {
+            Some(FixtureMode::Replay(dir)) => {
+                tracing::info!(url=%url, "replaying request from fixture");
+                replay_fixture(dir, &url, method, &headers)
+            }
+            Some(FixtureMode::Record(dir)) => {
+                match self
+                    .request_inner(url.clone(), method.clone(), headers.clone(), cache_mode)
+                    .await
+                {
+                    Ok(response) => record_fixture(dir, &url, method, &headers, response).await,
+                    Err(err) => Err(err),
+                }
+            }
+            None => self.request_inner(url, method, headers, cache_mode).await,
+        };
+
+        // Record the request before propagating errors so failed requests show up in the log as
+        // well. Lookups that only consult the cache did not perform a request if they failed.
+        if let Some((session_log, url, method)) = session_log {
+            let event = match &result {
+                Ok(response) => match response.extensions().get::<CacheStatus>() {
+                    Some(CacheStatus::Fresh) => Some(SessionEvent::CacheHit {
+                        cache: CacheKind::Http,
+                        key: redact_url(&url).to_string(),
+                    }),
+                    cache_status => Some(SessionEvent::NetworkRequest {
+                        method: method.to_string(),
+                        url: redact_url(&url).to_string(),
+                        cache_status: match cache_status {
+                            Some(CacheStatus::StaleButValidated) => "stale_but_validated",
+                            Some(CacheStatus::StaleAndChanged) => "stale_and_changed",
+                            Some(CacheStatus::Uncacheable) => "uncacheable",
+                            _ => "miss",
+                        }
+                        .to_string(),
+                        error: None,
+                    }),
+                },
+                Err(HttpRequestError::NotCached(_) | HttpRequestError::FixtureNotFound(_)) => None,
+                Err(err) => Some(SessionEvent::NetworkRequest {
+                    method: method.to_string(),
+                    url: redact_url(&url).to_string(),
+                    cache_status: String::from("error"),
+                    error: Some(err.to_string()),
+                }),
+            };
+            if let Some(event) = event {
+                session_log.record(event);
+            }
+        }
+
+        result
+    }
+
+    /// Returns the size of the body and the modification time of the cached response of a request
+    /// without performing the request, taking a lock or recording anything in the session log.
+    /// Returns `None` if the response is not cached. Stale responses are still reported.
+    pub fn cached_response(
+        &self,
+        url: &Url,
+        method: Method,
+        headers: &HeaderMap,
+    ) -> io::Result<Option<(u64, SystemTime)>> {
+        let key = key_for_request(url, method, headers);
+        let Some(file) = self.http_cache.peek(&key.as_slice()) else {
+            return Ok(None);
+        };
+        let modified = file.metadata()?.modified()?;
+        Ok(read_cache_data(file)
+            .ok()
+            .map(|(data, _)| (data.body_len, modified)))
+    }
+
+    /// Removes the cached response of a request, e.g. because the body turned out to be corrupt.
+    /// The next request fetches the response again.
+    pub fn invalidate(&self, url: &Url, method: Method, headers: &HeaderMap) -> io::Result<()> {
+        let key = key_for_request(url, method, headers);
+        match self.http_cache.lock_if_exists(&key.as_slice()) {
+            Some(lock) => match lock.remove() {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    async fn request_inner(
+        &self,
+        url: Url,
+        method: Method,
+        headers: HeaderMap,
+        cache_mode: CacheMode,
+    ) -> Result<http::Response<StreamingOrLocal>, HttpRequestError> {
+        tracing::info!(url=%redact_url(&url), cache_mode=?cache_mode, "executing request");
 
         // Construct a request using the reqwest client.
         let request = self
-            .client
-            .request(method.clone(), url.clone())
+            .auth
+            .authorize(&url, self.client.request(method.clone(), url.clone()))
             .headers(headers.clone())
             .build()?;
 
         if cache_mode == CacheMode::NoStore {
-            let mut response =
-                convert_response(self.client.execute(request).await?.error_for_status()?)
-                    .map(body_to_streaming_or_local);
+            let mut response = convert_response(self.execute(request).await?.error_for_status()?)
+                .map(|body| body_to_streaming_or_local(self.throttle(body)));
 
             // Add the `CacheStatus` to the response
             response.extensions_mut().insert(CacheStatus::Uncacheable);
@@ -99,10 +304,36 @@ impl Http {
             let key = key_for_request(&url, method, &headers);
             let lock = self.http_cache.lock(&key.as_slice())?;
 
-            if let Some((old_policy, final_url, old_body)) = lock
-                .reader()
-                .and_then(|reader| read_cache(reader.detach_unlocked()).ok())
-            {
+            // Corrupted entries are ignored, they are overwritten when the response is fetched
+            // again.
+            let cached = lock.reader().and_then(|reader| {
+                let file = reader.detach_unlocked();
+                let stamp = file
+                    .metadata()
+                    .and_then(|metadata| Ok((metadata.len(), metadata.modified()?)))
+                    .ok();
+                let verify_hash =
+                    stamp.is_none() || self.verified_entries.lock().get(&key) != stamp.as_ref();
+                match read_cache(file, verify_hash) {
+                    Ok(cached) => {
+                        if let (true, Some(stamp)) = (verify_hash, stamp) {
+                            self.verified_entries.lock().insert(key.clone(), stamp);
+                        }
+                        Some(cached)
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                        tracing::warn!(
+                            "the cached response of {url} is corrupt, refetching: {err}"
+                        );
+                        None
+                    }
+                    Err(err) => {
+                        tracing::debug!("ignoring unreadable cached response of {url}: {err}");
+                        None
+                    }
+                }
+            });
+            if let Some((old_policy, final_url, old_body)) = cached {
                 match old_policy.before_request(&request, SystemTime::now()) {
                     BeforeRequest::Fresh(parts) => {
                         tracing::debug!(url=%url, "is fresh");
@@ -126,7 +357,6 @@ impl Http {
                         // to date or not.
                         let request = convert_request(self.client.clone(), new_parts)?;
                         let response = self
-                            .client
                             .execute(request.try_clone().expect("clone of request cannot fail"))
                             .await?;
                         let final_url = response.url().clone();
@@ -147,17 +377,22 @@ impl Http {
                                 tracing::debug!(url=%url, "stale, but *and* modified");
                                 drop(old_body);
                                 let new_body = if new_policy.is_storable() {
+                                    let expected_len =
+                                        expected_body_len(request.method(), response.headers());
                                     let new_body = fill_cache_async(
                                         &new_policy,
                                         &final_url,
-                                        response.bytes_stream(),
+                                        self.throttle(response.bytes_stream()),
+                                        expected_len,
                                         lock,
                                     )
                                     .await?;
                                     StreamingOrLocal::Local(Box::new(new_body))
                                 } else {
                                     lock.remove()?;
-                                    body_to_streaming_or_local(response.bytes_stream())
+                                    body_to_streaming_or_local(
+                                        self.throttle(response.bytes_stream()),
+                                    )
                                 };
                                 Ok(make_response(
                                     parts,
@@ -175,18 +410,19 @@ impl Http {
                 }
 
                 let response = self
-                    .client
                     .execute(request.try_clone().expect("failed to clone request?"))
                     .await?
                     .error_for_status()?;
                 let final_url = response.url().clone();
-                let response = convert_response(response);
+                let response = convert_response(response).map(|body| self.throttle(body));
 
                 let new_policy = CachePolicy::new(&request, &response);
                 let (parts, body) = response.into_parts();
 
                 let new_body = if new_policy.is_storable() {
-                    let new_body = fill_cache_async(&new_policy, &final_url, body, lock).await?;
+                    let expected_len = expected_body_len(request.method(), &parts.headers);
+                    let new_body =
+                        fill_cache_async(&new_policy, &final_url, body, expected_len, lock).await?;
                     StreamingOrLocal::Local(Box::new(new_body))
                 } else {
                     lock.remove()?;
@@ -196,8 +432,51 @@ impl Http {
             }
         }
     }
+
+    /// Executes a request, retrying it according to the [`RetryPolicy`] when the server throttles
+    /// it.
+    async fn execute(&self, request: reqwest::Request) -> reqwest::Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            // Requests with a streaming body cannot be cloned and are therefore never retried.
+            let Some(retry_request) = request.try_clone() else {
+                return self.client.execute(request).await;
+            };
+            let response = self.client.execute(retry_request).await?;
+            let Some(delay) =
+                self.retry_policy
+                    .retry_delay(attempt, response.status(), response.headers())
+            else {
+                return Ok(response);
+            };
+
+            tracing::info!(
+                url=%request.url(),
+                status=%response.status(),
+                "request was throttled, retrying in {}ms",
+                delay.as_millis()
+            );
+            drop(response);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Applies the bandwidth limit, if any, to a response body.
+    fn throttle(
+        &self,
+        body: impl Stream<Item = reqwest::Result<Bytes>> + Send + 'static,
+    ) -> BodyStream {
+        match &self.bandwidth_limiter {
+            Some(limiter) => limiter.throttle(body),
+            None => Box::pin(body),
+        }
+    }
 }
 
+/// The body of a response that is downloaded from the network.
+type BodyStream = std::pin::Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+
 /// Constructs a `http::Response` from parts.
 fn make_response(
     parts: http::response::Parts,
@@ -242,24 +521,167 @@ fn key_for_request(url: &Url, method: Method, headers: &HeaderMap) -> Vec<u8> {
     key
 }
 
-/// Read a HTTP cached value from a readable stream.
-fn read_cache<R>(mut f: R) -> std::io::Result<(CachePolicy, Url, impl ReadAndSeek)>
+/// The information stored on disk for a recorded response. The body is stored in a separate file.
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    method: String,
+    url: Url,
+    final_url: Url,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+/// Returns the path (without extension) of the fixture for the given request.
+fn fixture_path(dir: &Path, url: &Url, method: Method, headers: &HeaderMap) -> PathBuf {
+    let key = key_for_request(url, method, headers);
+    let hash = rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(key.as_slice());
+    dir.join(format!("{hash:x}"))
+}
+
+/// Stores the response in the fixture directory and returns a response that reads from the
+/// recorded body.
+async fn record_fixture(
+    dir: &Path,
+    url: &Url,
+    method: Method,
+    headers: &HeaderMap,
+    response: http::Response<StreamingOrLocal>,
+) -> Result<http::Response<StreamingOrLocal>, HttpRequestError> {
+    let (parts, body) = response.into_parts();
+    let mut bytes = Vec::new();
+    body.read_to_end(&mut bytes).await?;
+
+    let fixture = Fixture {
+        method: method.to_string(),
+        url: url.clone(),
+        final_url: parts.extensions.get::<Url>().unwrap_or(url).clone(),
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+            .collect(),
+    };
+
+    let path = fixture_path(dir, url, method, headers);
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(
+        path.with_extension("json"),
+        serde_json::to_vec_pretty(&fixture)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+    )?;
+    std::fs::write(path.with_extension("body"), &bytes)?;
+
+    Ok(http::Response::from_parts(
+        parts,
+        StreamingOrLocal::Local(Box::new(io::Cursor::new(bytes))),
+    ))
+}
+
+/// Constructs a response from a previously recorded fixture.
+fn replay_fixture(
+    dir: &Path,
+    url: &Url,
+    method: Method,
+    headers: &HeaderMap,
+) -> Result<http::Response<StreamingOrLocal>, HttpRequestError> {
+    let path = fixture_path(dir, url, method, headers);
+    let fixture = match std::fs::read(path.with_extension("json")) {
+        Ok(fixture) => fixture,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(HttpRequestError::FixtureNotFound(url.clone()))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let fixture: Fixture = serde_json::from_slice(&fixture)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    let body = std::fs::read(path.with_extension("body"))?;
+
+    let mut builder = http::Response::builder().status(fixture.status);
+    for (name, value) in fixture.headers.iter() {
+        builder = builder.header(name, value);
+    }
+    let mut response = builder
+        .body(StreamingOrLocal::Local(Box::new(io::Cursor::new(body))))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    response.extensions_mut().insert(CacheStatus::Fresh);
+    response.extensions_mut().insert(fixture.final_url);
+    Ok(response)
+}
+
+/// Read a HTTP cached value from a readable stream. The length of the body is verified so truncated
+/// entries are detected, and if `verify_hash` is true so is the hash of the body to detect
+/// corrupted entries. In both cases an error of kind [`std::io::ErrorKind::InvalidData`] is
+/// returned.
+fn read_cache<R>(
+    mut f: R,
+    verify_hash: bool,
+) -> std::io::Result<(CachePolicy, Url, impl ReadAndSeek)>
 where
     R: Read + Seek,
 {
-    let data: CacheData = ciborium::de::from_reader(&mut f)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-    let start = f.stream_position()?;
-    let end = f.seek(SeekFrom::End(0))?;
-    let mut body = SeekSlice::new(f, start, end)?;
+    let (data, body_end) = read_cache_data(&mut f)?;
+
+    // Verify the body
+    let corrupt = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+    if data.body_len != body_end {
+        return Err(corrupt("the body of the cache entry is truncated"));
+    }
+    if verify_hash {
+        f.rewind()?;
+        let mut reader = rattler_digest::HashingReader::<_, Sha256>::new((&mut f).take(body_end));
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+        let (_, body_sha256) = reader.finalize();
+        if format!("{body_sha256:x}") != data.body_sha256 {
+            return Err(corrupt(
+                "the hash of the body of the cache entry does not match",
+            ));
+        }
+    }
+
+    let mut body = SeekSlice::new(f, 0, body_end)?;
     body.rewind()?;
     Ok((data.policy, data.url, body))
 }
 
+/// Reads the [`CacheData`] of a cached value without verifying the body. Returns the data and the
+/// offset at which the body ends.
+fn read_cache_data<R>(mut f: R) -> std::io::Result<(CacheData, u64)>
+where
+    R: Read + Seek,
+{
+    let corrupt = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+
+    // The cache data is stored after the body and is followed by its length.
+    let end = f.seek(SeekFrom::End(0))?;
+    let data_len_start = end
+        .checked_sub(8)
+        .ok_or_else(|| corrupt("the cache entry is truncated"))?;
+    f.seek(SeekFrom::Start(data_len_start))?;
+    let mut data_len = [0u8; 8];
+    f.read_exact(&mut data_len)?;
+    let data_len = u64::from_le_bytes(data_len);
+    let body_end = data_len_start
+        .checked_sub(data_len)
+        .ok_or_else(|| corrupt("the cache entry is truncated"))?;
+    f.seek(SeekFrom::Start(body_end))?;
+    let data: CacheData = ciborium::de::from_reader(f.take(data_len))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    Ok((data, body_end))
+}
+
+/// The information stored with a cached response. It is stored after the body because the length
+/// and hash of the body are only known once the body has been written.
 #[derive(Serialize, Deserialize)]
 struct CacheData {
     policy: CachePolicy,
     url: Url,
+
+    /// The length of the body in bytes, used to detect truncated entries.
+    body_len: u64,
+
+    /// The hex encoded sha256 hash of the body, used to detect corrupted entries.
+    body_sha256: String,
 }
 
 /// Fill the cache with the
@@ -269,52 +691,86 @@ fn fill_cache<R: Read>(
     mut body: R,
     handle: FileLock,
 ) -> Result<impl Read + Seek, std::io::Error> {
-    let mut cache_writer = handle.begin()?;
-    ciborium::ser::into_writer(
-        &CacheData {
-            policy: policy.clone(),
-            url: url.clone(),
-        },
-        &mut cache_writer,
-    )
-    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-    let body_start = cache_writer.stream_position()?;
-    std::io::copy(&mut body, &mut cache_writer)?;
+    let mut cache_writer = rattler_digest::HashingWriter::<_, Sha256>::new(handle.begin()?);
+    let body_len = std::io::copy(&mut body, &mut cache_writer)?;
     drop(body);
-    let body_end = cache_writer.stream_position()?;
-    let cache_entry = cache_writer.commit()?.detach_unlocked();
-    SeekSlice::new(cache_entry, body_start, body_end)
+    let (cache_writer, body_sha256) = cache_writer.finalize();
+    commit_cache(cache_writer, policy, url, body_len, body_sha256)
 }
 
-/// Fill the cache with the
+/// Fill the cache with the body that is streamed from the network. If the length of the body is
+/// known from the `Content-Length` header the number of received bytes must match, otherwise the
+/// entry is not committed and [`HttpRequestError::IncompleteBody`] is returned.
 async fn fill_cache_async(
     policy: &CachePolicy,
     url: &Url,
     mut body: impl Stream<Item = reqwest::Result<Bytes>> + Send + Unpin,
+    expected_len: Option<u64>,
     handle: FileLock,
+) -> Result<impl Read + Seek, HttpRequestError> {
+    let mut cache_writer = rattler_digest::HashingWriter::<_, Sha256>::new(handle.begin()?);
+    let mut body_len = 0;
+    while let Some(bytes) = body.next().await {
+        let bytes = bytes.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        cache_writer.write_all(bytes.as_ref())?;
+        body_len += bytes.len() as u64;
+    }
+
+    if let Some(expected) = expected_len {
+        if body_len != expected {
+            return Err(HttpRequestError::IncompleteBody {
+                url: url.clone(),
+                expected,
+                received: body_len,
+            });
+        }
+    }
+
+    let (cache_writer, body_sha256) = cache_writer.finalize();
+    Ok(commit_cache(
+        cache_writer,
+        policy,
+        url,
+        body_len,
+        body_sha256,
+    )?)
+}
+
+/// Returns the number of bytes the body of a response should have according to its
+/// `Content-Length` header. The length of encoded bodies is not known because the header refers
+/// to the encoded body, and responses to `HEAD` requests never have a body.
+fn expected_body_len(method: &Method, headers: &HeaderMap) -> Option<u64> {
+    if method == Method::HEAD || headers.contains_key(CONTENT_ENCODING) {
+        return None;
+    }
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// Writes the [`CacheData`] after the body that was written to `cache_writer` and commits the
+/// entry. Returns a reader for the body.
+fn commit_cache(
+    mut cache_writer: LockedWriter<'_>,
+    policy: &CachePolicy,
+    url: &Url,
+    body_len: u64,
+    body_sha256: Sha256Hash,
 ) -> Result<impl Read + Seek, std::io::Error> {
-    let mut cache_writer = handle.begin()?;
+    let mut data = Vec::new();
     ciborium::ser::into_writer(
         &CacheData {
             policy: policy.clone(),
             url: url.clone(),
+            body_len,
+            body_sha256: format!("{body_sha256:x}"),
         },
-        &mut cache_writer,
+        &mut data,
     )
     .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-    let body_start = cache_writer.stream_position()?;
+    cache_writer.write_all(&data)?;
+    cache_writer.write_all(&(data.len() as u64).to_le_bytes())?;
 
-    while let Some(bytes) = body.next().await {
-        cache_writer.write_all(
-            bytes
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
-                .as_ref(),
-        )?;
-    }
-
-    let body_end = cache_writer.stream_position()?;
     let cache_entry = cache_writer.commit()?.detach_unlocked();
-    SeekSlice::new(cache_entry, body_start, body_end)
+    SeekSlice::new(cache_entry, 0, body_len)
 }
 
 /// Converts from a `http::request::Parts` into a `reqwest::Request`.
@@ -364,3 +820,150 @@ fn body_to_streaming_or_local(
             .compat(),
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_and_replay_fixture() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = Url::parse("https://example.com/simple/foo/").unwrap();
+
+        // Replaying a request that was never recorded must fail
+        let result = replay_fixture(dir.path(), &url, Method::GET, &HeaderMap::default());
+        assert!(matches!(result, Err(HttpRequestError::FixtureNotFound(_))));
+
+        // Record a response
+        let mut response = http::Response::builder()
+            .status(200)
+            .header("Content-Type", "text/html")
+            .body(StreamingOrLocal::Local(Box::new(io::Cursor::new(
+                b"<html></html>".to_vec(),
+            ))))
+            .unwrap();
+        response.extensions_mut().insert(url.clone());
+        let mut recorded_body = Vec::new();
+        record_fixture(
+            dir.path(),
+            &url,
+            Method::GET,
+            &HeaderMap::default(),
+            response,
+        )
+        .await
+        .unwrap()
+        .into_body()
+        .read_to_end(&mut recorded_body)
+        .await
+        .unwrap();
+        assert_eq!(recorded_body, b"<html></html>");
+
+        // And replay it
+        let replayed =
+            replay_fixture(dir.path(), &url, Method::GET, &HeaderMap::default()).unwrap();
+        assert_eq!(replayed.status().as_u16(), 200);
+        assert_eq!(replayed.headers().get("Content-Type").unwrap(), "text/html");
+        assert_eq!(replayed.extensions().get::<Url>(), Some(&url));
+        let mut body = Vec::new();
+        replayed.into_body().read_to_end(&mut body).await.unwrap();
+        assert_eq!(body, b"<html></html>");
+    }
+
+    #[tokio::test]
+    async fn test_failed_request_is_logged() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let log_dir = tempfile::tempdir().unwrap();
+        let log_path = log_dir.path().join("session.jsonl");
+        let http = Http::new(
+            Client::new(),
+            FileStore::new(&cache_dir.path().join("http")).unwrap(),
+            FileStore::new(&cache_dir.path().join("hash")).unwrap(),
+        )
+        .with_session_log(SessionLog::create(&log_path).unwrap());
+
+        // Nothing listens on port 1 so the request fails
+        let url = Url::parse("http://127.0.0.1:1/simple/foo/").unwrap();
+        let result = http
+            .request(url, Method::GET, HeaderMap::default(), CacheMode::NoStore)
+            .await;
+        assert!(result.is_err());
+        http.session_log().unwrap().flush().unwrap();
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let event: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(event["event"], "network_request");
+        assert_eq!(event["url"], "http://127.0.0.1:1/simple/foo/");
+        assert_eq!(event["cache_status"], "error");
+        assert!(event["error"].is_string());
+    }
+
+    #[test]
+    fn test_expected_body_len() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(expected_body_len(&Method::GET, &headers), None);
+
+        headers.insert(CONTENT_LENGTH, "1234".parse().unwrap());
+        assert_eq!(expected_body_len(&Method::GET, &headers), Some(1234));
+        assert_eq!(expected_body_len(&Method::HEAD, &headers), None);
+
+        // The length of encoded bodies is unknown
+        headers.insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+        assert_eq!(expected_body_len(&Method::GET, &headers), None);
+    }
+
+    #[test]
+    fn test_detect_corrupt_cache_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+        let url = Url::parse("https://example.com/simple/foo/").unwrap();
+        let key = b"key".as_slice();
+
+        let request = reqwest::Request::new(Method::GET, url.clone());
+        let response = http::Response::builder().status(200).body(()).unwrap();
+        let policy = CachePolicy::new(&request, &response);
+
+        let mut body = String::new();
+        fill_cache(
+            &policy,
+            &url,
+            io::Cursor::new(b"<html></html>"),
+            store.lock(&key).unwrap(),
+        )
+        .unwrap()
+        .read_to_string(&mut body)
+        .unwrap();
+        assert_eq!(body, "<html></html>");
+
+        let mut entry = Vec::new();
+        store
+            .lock(&key)
+            .unwrap()
+            .reader()
+            .unwrap()
+            .read_to_end(&mut entry)
+            .unwrap();
+
+        // An intact entry can be read back
+        let (_, cached_url, mut cached_body) =
+            read_cache(io::Cursor::new(entry.clone()), true).unwrap();
+        let mut body = String::new();
+        cached_body.read_to_string(&mut body).unwrap();
+        assert_eq!(cached_url, url);
+        assert_eq!(body, "<html></html>");
+
+        // A corrupted body is detected
+        let mut corrupted = entry.clone();
+        corrupted[0] = b'!';
+        let err = read_cache(io::Cursor::new(corrupted.clone()), true)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Without verifying the hash only the length is checked
+        assert!(read_cache(io::Cursor::new(corrupted), false).is_ok());
+
+        // And so is a truncated entry
+        assert!(read_cache(io::Cursor::new(entry[..entry.len() / 2].to_vec()), false).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+//! Configuration of the indexes that a [`super::PackageDb`] reads artifacts from.
+
+use super::auth::AuthStore;
+use crate::artifacts::SDist;
+use crate::types::{ArtifactInfo, Version};
+use indexmap::IndexMap;
+use url::Url;
+
+/// A package index, either a simple repository ([PEP 503](https://peps.python.org/pep-0503/)) or
+/// a flat HTML page that links to artifacts directly, like pip's `--find-links`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Index {
+    /// The url of the index
+    pub url: Url,
+
+    /// Whether the sdists of this index are used. When `false` only the wheels of the index are
+    /// considered. Defaults to `true`.
+    pub sdists: bool,
+}
+
+impl Index {
+    /// Constructs an index for the given url that provides both wheels and sdists.
+    pub fn new(url: Url) -> Self {
+        Self { url, sdists: true }
+    }
+
+    /// Sets whether the sdists of this index are used.
+    pub fn with_sdists(self, sdists: bool) -> Self {
+        Self { sdists, ..self }
+    }
+}
+
+impl From<Url> for Index {
+    fn from(url: Url) -> Self {
+        Self::new(url)
+    }
+}
+
+/// The indexes that are used to find the artifacts of a package, ordered by priority.
+///
+/// There is at most one primary index, any number of extra indexes and any number of flat
+/// indexes. The candidates for a package are merged in this order:
+///
+/// 1. the primary index,
+/// 2. the extra indexes, in the order they were added,
+/// 3. the flat indexes, in the order they were added.
+///
+/// A version of a package is only taken from the first index in this order that provides any
+/// artifacts for it. The same version on an index with a lower priority is shadowed and its
+/// artifacts are ignored, which prevents an index from injecting artifacts into a release that is
+/// hosted on another index. Versions that are only available on an index with a lower priority
+/// are still used. Artifacts of indexes that have sdists disabled are removed before merging, so
+/// a version that only has sdists on such an index does not shadow the version on other indexes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexSet {
+    primary: Option<Index>,
+    extra: Vec<Index>,
+    flat: Vec<Index>,
+}
+
+impl IndexSet {
+    /// Constructs a set with the given primary index.
+    pub fn new(primary: impl Into<Index>) -> Self {
+        Self {
+            primary: Some(primary.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Constructs a set from a list of simple index urls. The first url is the primary index and
+    /// all others are extra indexes.
+    pub fn from_urls(urls: &[Url]) -> Self {
+        let mut urls = urls.iter().cloned().map(Index::from);
+        Self {
+            primary: urls.next(),
+            extra: urls.collect(),
+            flat: Vec::new(),
+        }
+    }
+
+    /// Adds an extra simple index with a lower priority than the indexes that were added before.
+    pub fn with_extra_index(mut self, index: impl Into<Index>) -> Self {
+        self.extra.push(index.into());
+        self
+    }
+
+    /// Adds a flat index with a lower priority than the indexes that were added before. Flat
+    /// indexes are `http` or `https` urls of pages that link to artifacts, or `file` urls of local
+    /// directories that contain artifacts.
+    pub fn with_flat_index(mut self, index: impl Into<Index>) -> Self {
+        self.flat.push(index.into());
+        self
+    }
+
+    /// Replaces all flat indexes.
+    pub(crate) fn with_flat_indexes(self, flat: Vec<Index>) -> Self {
+        Self { flat, ..self }
+    }
+
+    /// Moves the credentials of the index urls to `auth`, see [`AuthStore`].
+    pub(crate) fn take_credentials(self, auth: &mut AuthStore) -> Self {
+        let mut take = |index: Index| Index {
+            url: auth.take_credentials(index.url),
+            ..index
+        };
+        Self {
+            primary: self.primary.map(&mut take),
+            extra: self.extra.into_iter().map(&mut take).collect(),
+            flat: self.flat.into_iter().map(&mut take).collect(),
+        }
+    }
+
+    /// Returns the primary index, if any.
+    pub fn primary(&self) -> Option<&Index> {
+        self.primary.as_ref()
+    }
+
+    /// Returns the extra indexes in order of priority.
+    pub fn extra(&self) -> &[Index] {
+        &self.extra
+    }
+
+    /// Returns the flat indexes in order of priority.
+    pub fn flat(&self) -> &[Index] {
+        &self.flat
+    }
+
+    /// Returns the simple indexes, the primary index followed by the extra indexes, in order of
+    /// priority.
+    pub fn simple_indexes(&self) -> impl Iterator<Item = &Index> {
+        self.primary.iter().chain(self.extra.iter())
+    }
+}
+
+/// Compares two index urls, ignoring credentials and trailing slashes.
+pub(crate) fn same_index_url(a: &Url, b: &Url) -> bool {
+    a.origin() == b.origin() && a.path().trim_end_matches('/') == b.path().trim_end_matches('/')
+}
+
+/// Merges the artifacts of a package found on multiple indexes as described in [`IndexSet`].
+/// `found` contains the artifacts per index and must be ordered by priority.
+///
+/// The result is ordered from the highest to the lowest version, the artifacts of a version are
+/// ordered by filename.
+pub(crate) fn merge_artifacts<'i>(
+    found: impl IntoIterator<Item = (&'i Index, Vec<ArtifactInfo>)>,
+) -> IndexMap<Version, Vec<ArtifactInfo>> {
+    let mut result: IndexMap<Version, Vec<ArtifactInfo>> = IndexMap::new();
+    for (index, artifacts) in found {
+        let mut by_version: IndexMap<Version, Vec<ArtifactInfo>> = IndexMap::new();
+        for artifact in artifacts {
+            if !index.sdists && artifact.is::<SDist>() {
+                continue;
+            }
+            let artifacts = by_version
+                .entry(artifact.filename.version().clone())
+                .or_default();
+            if !artifacts.iter().any(|a| a.url == artifact.url) {
+                artifacts.push(artifact);
+            }
+        }
+
+        for (version, artifacts) in by_version {
+            if result.contains_key(&version) {
+                tracing::debug!(
+                    "ignoring version {version} on {} because it is shadowed by an index with a higher priority",
+                    index.url
+                );
+                continue;
+            }
+            result.insert(version, artifacts);
+        }
+    }
+
+    // Sort the artifact infos by name, this is just to have a consistent order and make the
+    // resolution output consistent.
+    for artifact_infos in result.values_mut() {
+        artifact_infos.sort_by(|a, b| a.filename.cmp(&b.filename));
+    }
+
+    // Sort in descending order by version
+    result.sort_unstable_by(|v1, _, v2, _| v2.cmp(v1));
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::html::parse_find_links_html;
+    use crate::types::NormalizedPackageName;
+
+    fn artifacts(base: &str, filenames: &[&str]) -> Vec<ArtifactInfo> {
+        let base = Url::parse(base).unwrap();
+        let body = filenames
+            .iter()
+            .map(|filename| format!(r#"<a href="{filename}">{filename}</a>"#))
+            .collect::<String>();
+        let name: NormalizedPackageName = "foo".parse().unwrap();
+        parse_find_links_html(&base, &body, &name).unwrap().files
+    }
+
+    fn urls(merged: &IndexMap<Version, Vec<ArtifactInfo>>, version: &str) -> Vec<String> {
+        merged
+            .get(&version.parse::<Version>().unwrap())
+            .map(|artifacts| artifacts.iter().map(|a| a.url.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_index_set_order() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        let indexes = IndexSet::from_urls(&[url("https://a.com/simple/"), url("https://b.com/")])
+            .with_flat_index(url("https://c.com/links.html"))
+            .with_extra_index(Index::new(url("https://d.com/simple/")).with_sdists(false));
+
+        assert_eq!(indexes.primary().unwrap().url, url("https://a.com/simple/"));
+        assert_eq!(
+            indexes
+                .simple_indexes()
+                .map(|index| index.url.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://a.com/simple/",
+                "https://b.com/",
+                "https://d.com/simple/"
+            ]
+        );
+        assert!(!indexes.extra()[1].sdists);
+        assert_eq!(indexes.flat().len(), 1);
+    }
+
+    #[test]
+    fn test_cross_index_version_shadowing() {
+        let primary = Index::new(Url::parse("https://primary.com/").unwrap()).with_sdists(false);
+        let extra = Index::new(Url::parse("https://extra.com/").unwrap());
+        let flat = Index::new(Url::parse("https://flat.com/").unwrap());
+
+        let merged = merge_artifacts([
+            (
+                &primary,
+                artifacts(
+                    "https://primary.com/foo/",
+                    &["foo-1.0-py3-none-any.whl", "foo-2.0.tar.gz"],
+                ),
+            ),
+            (
+                &extra,
+                artifacts(
+                    "https://extra.com/foo/",
+                    &[
+                        "foo-1.0-py3-none-any.whl",
+                        "foo-1.0.tar.gz",
+                        "foo-1.5-py3-none-any.whl",
+                        "foo-2.0.tar.gz",
+                    ],
+                ),
+            ),
+            (
+                &flat,
+                artifacts(
+                    "https://flat.com/",
+                    &["foo-1.5-cp311-cp311-win_amd64.whl", "foo-3.0.tar.gz"],
+                ),
+            ),
+        ]);
+
+        // Ordered from the highest to the lowest version
+        assert_eq!(
+            merged.keys().map(ToString::to_string).collect::<Vec<_>>(),
+            ["3.0", "2.0", "1.5", "1.0"]
+        );
+
+        // 1.0 is on the primary index, which shadows all artifacts of 1.0 on the extra index
+        assert_eq!(
+            urls(&merged, "1.0"),
+            ["https://primary.com/foo/foo-1.0-py3-none-any.whl"]
+        );
+
+        // The primary index only has an sdist of 2.0 which is disabled, so 2.0 is taken from the
+        // extra index
+        assert_eq!(
+            urls(&merged, "2.0"),
+            ["https://extra.com/foo/foo-2.0.tar.gz"]
+        );
+
+        // The extra index shadows the flat index
+        assert_eq!(
+            urls(&merged, "1.5"),
+            ["https://extra.com/foo/foo-1.5-py3-none-any.whl"]
+        );
+
+        // Versions that are only on an index with a lower priority are used
+        assert_eq!(urls(&merged, "3.0"), ["https://flat.com/foo-3.0.tar.gz"]);
+    }
+}
@@ -0,0 +1,221 @@
+//! Module for parsing JSON responses of the simple repository API as described in
+//! [PEP 691](https://peps.python.org/pep-0691/) and [PEP 700](https://peps.python.org/pep-0700/).
+
+use super::html::parse_requires_python;
+use crate::types::{
+    ArtifactHashes, ArtifactInfo, ArtifactName, DistInfoMetadata, Meta, NormalizedPackageName,
+    ProjectInfo, Yanked,
+};
+use chrono::{DateTime, Utc};
+use miette::IntoDiagnostic;
+use rattler_digest::{parse_digest_from_hex, Sha256};
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::Url;
+
+/// The content type of JSON responses of the simple repository API.
+pub const SIMPLE_API_JSON_CONTENT_TYPE: &str = "application/vnd.pypi.simple.v1+json";
+
+#[derive(Deserialize)]
+struct RawProjectInfo {
+    meta: Meta,
+    name: String,
+    #[serde(default)]
+    files: Vec<serde_json::Value>,
+    #[serde(default)]
+    versions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawFile {
+    filename: String,
+    url: String,
+    #[serde(default)]
+    hashes: HashMap<String, String>,
+    requires_python: Option<String>,
+    core_metadata: Option<RawMetadata>,
+    #[serde(rename = "data-dist-info-metadata")]
+    dist_info_metadata: Option<RawMetadata>,
+    #[serde(default)]
+    yanked: Option<RawYanked>,
+    size: Option<u64>,
+    upload_time: Option<DateTime<Utc>>,
+    provenance: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawMetadata {
+    Available(bool),
+    WithHashes(HashMap<String, String>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawYanked {
+    NoReason(bool),
+    WithReason(String),
+}
+
+fn parse_hashes(hashes: &HashMap<String, String>) -> ArtifactHashes {
+    ArtifactHashes {
+        sha256: hashes
+            .get("sha256")
+            .and_then(|hex| parse_digest_from_hex::<Sha256>(hex)),
+    }
+}
+
+fn into_artifact_info(
+    base: &Url,
+    normalized_package_name: &NormalizedPackageName,
+    file: RawFile,
+) -> Option<ArtifactInfo> {
+    let filename = ArtifactName::from_filename(&file.filename, normalized_package_name).ok()?;
+    let url = base.join(&file.url).ok()?;
+    let hashes = Some(parse_hashes(&file.hashes)).filter(|hashes| !hashes.is_empty());
+
+    let requires_python = file
+        .requires_python
+        .as_deref()
+        .and_then(|raw| parse_requires_python(raw, &filename));
+
+    // `data-dist-info-metadata` was renamed to `core-metadata` by PEP 714.
+    let dist_info_metadata = match file.core_metadata.or(file.dist_info_metadata) {
+        None => DistInfoMetadata::default(),
+        Some(RawMetadata::Available(available)) => DistInfoMetadata {
+            available,
+            hashes: ArtifactHashes::default(),
+        },
+        Some(RawMetadata::WithHashes(hashes)) => DistInfoMetadata {
+            available: true,
+            hashes: parse_hashes(&hashes),
+        },
+    };
+
+    let yanked = match file.yanked {
+        None | Some(RawYanked::NoReason(false)) => Yanked::default(),
+        Some(RawYanked::NoReason(true)) => Yanked {
+            yanked: true,
+            reason: None,
+        },
+        Some(RawYanked::WithReason(reason)) => Yanked {
+            yanked: true,
+            reason: Some(reason),
+        },
+    };
+
+    Some(ArtifactInfo {
+        filename,
+        url,
+        hashes,
+        requires_python,
+        requires_python_raw: file.requires_python,
+        dist_info_metadata,
+        yanked,
+        size: file.size,
+        upload_time: file.upload_time,
+        provenance: file
+            .provenance
+            .and_then(|provenance| base.join(&provenance).ok()),
+    })
+}
+
+/// Parses the JSON response of the simple repository API for a single project. Relative urls of
+/// files are resolved relative to `base`. Files that cannot be parsed are skipped.
+pub fn parse_project_info_json(base: &Url, body: &[u8]) -> miette::Result<ProjectInfo> {
+    let raw: RawProjectInfo = serde_json::from_slice(body).into_diagnostic()?;
+    let normalized_package_name = raw
+        .name
+        .parse::<NormalizedPackageName>()
+        .into_diagnostic()?;
+
+    let files = raw
+        .files
+        .into_iter()
+        .filter_map(|file| serde_json::from_value::<RawFile>(file).ok())
+        .filter_map(|file| into_artifact_info(base, &normalized_package_name, file))
+        .collect();
+
+    // Versions that cannot be parsed are skipped, like files that cannot be parsed.
+    let versions = raw
+        .versions
+        .iter()
+        .filter_map(|version| version.parse().ok())
+        .collect();
+
+    Ok(ProjectInfo {
+        meta: raw.meta,
+        files,
+        versions,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::Version;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_parse_project_info_json() {
+        let body = br#"{
+            "meta": {"api-version": "1.1"},
+            "name": "rich",
+            "versions": ["13.5.0", "13.6.0", "not a version"],
+            "files": [
+                {
+                    "filename": "rich-13.6.0-py3-none-any.whl",
+                    "url": "../../packages/rich-13.6.0-py3-none-any.whl",
+                    "hashes": {"sha256": "2b38e2fe9ca72c9a00170a1a2d20c63c790d0e10ef1fe35eba76e1e7b1d7d245"},
+                    "requires-python": ">=3.7.0",
+                    "core-metadata": true,
+                    "size": 239874,
+                    "upload-time": "2023-10-01T13:42:17.123456Z"
+                },
+                {
+                    "filename": "rich-13.6.0.tar.gz",
+                    "url": "https://files.example.com/rich-13.6.0.tar.gz",
+                    "hashes": {},
+                    "yanked": "broken"
+                },
+                {
+                    "filename": "not-an-artifact.txt",
+                    "url": "https://files.example.com/not-an-artifact.txt",
+                    "hashes": {}
+                }
+            ]
+        }"#;
+
+        let base = Url::parse("https://example.com/simple/rich/").unwrap();
+        let project_info = parse_project_info_json(&base, body).unwrap();
+        assert_eq!(project_info.meta.version, "1.1");
+        assert_eq!(project_info.files.len(), 2);
+        assert_eq!(
+            project_info.versions,
+            vec![
+                Version::from_str("13.5.0").unwrap(),
+                Version::from_str("13.6.0").unwrap()
+            ]
+        );
+
+        let wheel = &project_info.files[0];
+        assert_eq!(
+            wheel.url.as_str(),
+            "https://example.com/packages/rich-13.6.0-py3-none-any.whl"
+        );
+        assert!(wheel.hashes.as_ref().unwrap().sha256.is_some());
+        assert!(wheel.requires_python.is_some());
+        assert!(wheel.dist_info_metadata.available);
+        assert_eq!(wheel.size, Some(239874));
+        assert_eq!(
+            wheel.upload_time,
+            Some("2023-10-01T13:42:17.123456Z".parse().unwrap())
+        );
+
+        let sdist = &project_info.files[1];
+        assert!(sdist.hashes.is_none());
+        assert_eq!(sdist.yanked.reason.as_deref(), Some("broken"));
+        assert_eq!(sdist.upload_time, None);
+    }
+}
@@ -1,11 +1,52 @@
 //! This module contains functions for working with PyPA packaging repositories.
 
+mod auth;
+mod availability;
+mod cache_stats;
+mod client;
+mod download_approval;
 mod file_store;
 
+pub mod generate;
+
 pub mod html;
 mod http;
+mod index_set;
+pub mod json;
 mod package_database;
+mod pip_cache;
+mod provenance;
+mod rate_limit;
+mod warm_cache;
+mod wheelhouse;
+
+#[cfg(feature = "proxy")]
+mod proxy;
+#[cfg(any(test, feature = "test-server"))]
+pub mod test_server;
 
-pub use package_database::PackageDb;
+pub use auth::{redact_url, AuthStore};
+pub use availability::{ArtifactAvailability, OperatingSystem, WheelPlatform};
+pub use cache_stats::{CacheCounter, CacheStatistics};
+pub use client::{HttpClientOptions, HttpVersion};
+pub use download_approval::{
+    DownloadApproval, DownloadDecision, DownloadRejected, FirstDownload, MinimumAge,
+};
+pub(crate) use index_set::same_index_url;
+pub use index_set::{Index, IndexSet};
+pub use package_database::{
+    ArtifactCacheStatus, CachedArtifact, CorruptArtifact, PackageDb, UpstreamHashMismatch,
+    UpstreamHashMissing,
+};
+pub use pip_cache::PipCache;
+pub use provenance::{
+    check_provenance, Attestation, AttestationBundle, AttestationPolicy, AttestationStatus,
+    Envelope, Provenance, Publisher,
+};
+#[cfg(feature = "proxy")]
+pub use proxy::CacheProxy;
+pub use rate_limit::RetryPolicy;
+pub use warm_cache::{WarmCacheOptions, WarmCacheReport};
+pub use wheelhouse::{scan_wheelhouse, WheelhouseError, WheelhouseScan};
 
-pub use self::http::CacheMode;
+pub use self::http::{CacheMode, FixtureMode};
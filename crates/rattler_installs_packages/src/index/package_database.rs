@@ -1,9 +1,27 @@
 use crate::artifacts::{SDist, Wheel};
+use crate::index::auth::{redact_url, AuthStore};
+use crate::index::availability::ArtifactAvailability;
+use crate::index::cache_stats::{CacheCategory, CacheStatistics};
+use crate::index::download_approval::{
+    DownloadApproval, DownloadDecision, DownloadRejected, FirstDownload,
+};
 use crate::index::file_store::FileStore;
-use crate::index::html::{parse_package_names_html, parse_project_info_html};
-use crate::index::http::{CacheMode, Http, HttpRequestError};
-use crate::types::{ArtifactInfo, ProjectInfo, WheelCoreMetadata};
-use crate::wheel_builder::WheelBuilder;
+use crate::index::html::{
+    parse_find_links_html, parse_hash, parse_package_names_html, parse_project_info_html,
+};
+use crate::index::http::{CacheMode, FixtureMode, Http, HttpRequestError};
+use crate::index::index_set::{merge_artifacts, same_index_url, Index, IndexSet};
+use crate::index::json::{parse_project_info_json, SIMPLE_API_JSON_CONTENT_TYPE};
+use crate::index::pip_cache::PipCache;
+use crate::index::provenance::{
+    check_provenance, AttestationStatus, Provenance, PROVENANCE_CONTENT_TYPE,
+};
+use crate::index::rate_limit::RetryPolicy;
+use crate::index::wheelhouse::scan_wheelhouse;
+use crate::session_log::{CacheKind, SessionEvent, SessionLog};
+use crate::types::{ArtifactHashes, ArtifactInfo, ArtifactName, ProjectInfo, WheelCoreMetadata};
+use crate::utils::ReadAndSeek;
+use crate::wheel_builder::{BuiltWheelCache, InFlightBuilds, RetentionPolicy, WheelBuilder};
 use crate::{
     types::Artifact, types::InnerAsArtifactName, types::NormalizedPackageName, types::Version,
     types::WheelFilename,
@@ -11,43 +29,527 @@ use crate::{
 use async_http_range_reader::{AsyncHttpRangeReader, CheckSupportMethod};
 use elsa::sync::FrozenMap;
 use futures::{pin_mut, stream, StreamExt};
-use http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, Method};
+use http::{
+    header::ACCEPT, header::CONTENT_LENGTH, header::CONTENT_TYPE, HeaderMap, HeaderValue, Method,
+};
 use indexmap::IndexMap;
-use miette::{self, Diagnostic, IntoDiagnostic};
+use miette::{self, Context, Diagnostic, IntoDiagnostic};
+use parking_lot::Mutex;
+use rattler_digest::{Sha256, Sha256Hash};
 use reqwest::{header::CACHE_CONTROL, Client, StatusCode};
-use std::{fmt::Display, io::Read, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::{Read, Seek, SeekFrom},
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use url::Url;
 
 /// Cache of the available packages, artifacts and their metadata.
 pub struct PackageDb {
     http: Http,
 
-    /// Index URLS to query
-    index_urls: Vec<Url>,
+    /// The indexes to query, see [`IndexSet`] for how their artifacts are merged.
+    indexes: IndexSet,
 
     /// A file store that stores metadata by hashes
     metadata_cache: FileStore,
 
     /// A cache of package name to version to artifacts.
     artifacts: FrozenMap<NormalizedPackageName, Box<IndexMap<Version, Vec<ArtifactInfo>>>>,
+
+    /// A cache of package name to all versions listed by the indexes.
+    versions: FrozenMap<NormalizedPackageName, Box<Vec<Version>>>,
+
+    /// Artifacts that are referenced directly by their url, e.g. `foo @ https://...`.
+    direct_references: FrozenMap<Url, Box<ArtifactInfo>>,
+
+    /// The url of the index on which an artifact was found, keyed by the url of the artifact.
+    artifact_indexes: FrozenMap<Url, Box<Url>>,
+
+    /// Artifacts for which the sha256 hash was computed because the index did not provide it.
+    hashed_artifacts: FrozenMap<Url, Box<ArtifactInfo>>,
+
+    /// An optional pip cache to read artifacts from before downloading them.
+    pip_cache: Option<PipCache>,
+
+    /// Wheels that were built from sdists.
+    built_wheels: BuiltWheelCache,
+
+    /// The canonical index that artifacts are verified against, see
+    /// [`PackageDb::with_upstream_index`].
+    upstream_index: Option<Url>,
+
+    /// Fail for artifacts that cannot be verified against the upstream index, see
+    /// [`PackageDb::with_strict_upstream_index`].
+    strict_upstream_index: bool,
+
+    /// The hashes published by the upstream index, keyed by package and filename.
+    upstream_hashes: FrozenMap<NormalizedPackageName, Box<HashMap<String, UpstreamHashes>>>,
+
+    /// Reviews package versions before they are downloaded for the first time.
+    download_approval: Option<Arc<dyn DownloadApproval>>,
+
+    /// The package versions that may be downloaded without asking `download_approval`.
+    approved_downloads: Mutex<HashSet<(NormalizedPackageName, Version)>>,
+
+    /// The urls of the artifacts that `download_approval` approved.
+    approved_artifacts: Mutex<HashSet<Url>>,
+
+    /// Metadata that was provided by the caller, see [`PackageDb::seed_metadata`].
+    seeded_metadata: Mutex<HashMap<(NormalizedPackageName, Version), WheelCoreMetadata>>,
+
+    /// The sdists that are currently being built by any [`WheelBuilder`] of this database.
+    in_flight_builds: InFlightBuilds,
 }
 
 impl PackageDb {
-    /// Constructs a new [`PackageDb`] that reads information from the specified URLs.
+    /// Constructs a new [`PackageDb`] that reads information from the specified URLs. The first url
+    /// is the primary index, all others are extra indexes with a decreasing priority, see
+    /// [`IndexSet`].
     pub fn new(client: Client, index_urls: &[Url], cache_dir: &Path) -> std::io::Result<Self> {
+        Self::from_indexes(client, IndexSet::from_urls(index_urls), cache_dir)
+    }
+
+    /// Constructs a new [`PackageDb`] that reads information from the given indexes.
+    ///
+    /// Credentials that are part of the index urls are moved to the [`AuthStore`] of the database,
+    /// so they never show up in the urls of artifacts or in the session log.
+    pub fn from_indexes(
+        client: Client,
+        indexes: IndexSet,
+        cache_dir: &Path,
+    ) -> std::io::Result<Self> {
+        let mut auth = AuthStore::default();
+        let indexes = indexes.take_credentials(&mut auth);
         Ok(Self {
             http: Http::new(
                 client,
                 FileStore::new(&cache_dir.join("http"))?,
                 FileStore::new(&cache_dir.join("by-hash"))?,
-            ),
-            index_urls: index_urls.into(),
+            )
+            .with_auth_store(auth),
+            indexes,
             metadata_cache: FileStore::new(&cache_dir.join("metadata"))?,
             artifacts: Default::default(),
+            versions: Default::default(),
+            direct_references: Default::default(),
+            artifact_indexes: Default::default(),
+            hashed_artifacts: Default::default(),
+            pip_cache: None,
+            built_wheels: BuiltWheelCache::new(cache_dir.join("built-wheels"))?,
+            upstream_index: None,
+            strict_upstream_index: false,
+            upstream_hashes: Default::default(),
+            download_approval: None,
+            approved_downloads: Default::default(),
+            approved_artifacts: Default::default(),
+            seeded_metadata: Default::default(),
+            in_flight_builds: Default::default(),
         })
     }
 
+    /// Sets the policy that determines how long wheels built from sdists are kept in the cache.
+    pub fn with_built_wheel_retention(self, retention_policy: RetentionPolicy) -> Self {
+        Self {
+            built_wheels: self.built_wheels.with_retention_policy(retention_policy),
+            ..self
+        }
+    }
+
+    /// Returns the cache that stores the wheels that were built from sdists.
+    pub fn built_wheels(&self) -> &BuiltWheelCache {
+        &self.built_wheels
+    }
+
+    /// Returns the sdists that are currently being built, see [`InFlightBuilds`].
+    pub(crate) fn in_flight_builds(&self) -> &InFlightBuilds {
+        &self.in_flight_builds
+    }
+
+    /// Reads artifacts from the given pip cache before downloading them. Only artifacts for which
+    /// the index provides a sha256 hash are read from the pip cache, and only if the hash matches.
+    pub fn with_pip_cache(self, pip_cache: PipCache) -> Self {
+        Self {
+            pip_cache: Some(pip_cache),
+            ..self
+        }
+    }
+
+    /// Also looks for artifacts on the given HTML pages, like pip's `--find-links`. Every page is
+    /// expected to link directly to the artifacts of any number of packages. A `file://` url to a
+    /// local directory is scanned for artifacts instead, see [`super::scan_wheelhouse`]. Looking
+    /// up artifacts fails for urls with other schemes than `http`, `https` and `file`.
+    ///
+    /// The pages replace the flat indexes of the [`IndexSet`] and have a lower priority than the
+    /// simple indexes.
+    pub fn with_find_links(self, find_links: Vec<Url>) -> Self {
+        let mut auth = self.http.auth_store().clone();
+        let indexes = self
+            .indexes
+            .with_flat_indexes(find_links.into_iter().map(Index::from).collect())
+            .take_credentials(&mut auth);
+        Self {
+            indexes,
+            http: self.http.with_auth_store(auth),
+            ..self
+        }
+    }
+
+    /// Adds credentials to the requests to the hosts of the store, e.g. for indexes that require
+    /// authentication. These take precedence over credentials that were part of the index urls.
+    pub fn with_auth_store(self, auth_store: AuthStore) -> Self {
+        let mut auth = self.http.auth_store().clone();
+        auth.extend(auth_store);
+        Self {
+            http: self.http.with_auth_store(auth),
+            ..self
+        }
+    }
+
+    /// Returns the indexes that are queried.
+    pub fn indexes(&self) -> &IndexSet {
+        &self.indexes
+    }
+
+    /// Returns the path of the artifact in the pip cache if it is available there.
+    fn find_in_pip_cache(&self, artifact_info: &ArtifactInfo) -> Option<PathBuf> {
+        let pip_cache = self.pip_cache.as_ref()?;
+        let sha256 = artifact_info.hashes.as_ref()?.sha256.as_ref()?;
+        pip_cache.find_artifact(&artifact_info.url, sha256)
+    }
+
+    /// Records all HTTP responses to, or replays them from, fixtures on disk. This allows running
+    /// network dependent code (like resolving or fetching metadata) offline and deterministically.
+    ///
+    /// Note that when a fixture mode is set, metadata is never read lazily through range requests
+    /// because those requests cannot be recorded.
+    pub fn with_fixture_mode(self, fixture_mode: FixtureMode) -> Self {
+        Self {
+            http: self.http.with_fixture_mode(fixture_mode),
+            ..self
+        }
+    }
+
+    /// Sets how requests that are throttled by the server (`429 Too Many Requests` or
+    /// `503 Service Unavailable`) are retried. By default they are retried a few times, honoring
+    /// the `Retry-After` header of the response.
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            http: self.http.with_retry_policy(retry_policy),
+            ..self
+        }
+    }
+
+    /// Limits the total download rate of all index pages and artifacts to the given number of
+    /// bytes per second.
+    pub fn with_bandwidth_limit(self, bytes_per_second: NonZeroU64) -> Self {
+        Self {
+            http: self.http.with_bandwidth_limit(bytes_per_second),
+            ..self
+        }
+    }
+
+    /// Writes every network request, cache hit and wheel build to the given session log.
+    pub fn with_session_log(self, session_log: SessionLog) -> Self {
+        Self {
+            http: self.http.with_session_log(session_log),
+            ..self
+        }
+    }
+
+    /// Verifies the artifacts that are downloaded from the other indexes against the sha256 hashes
+    /// published by `upstream_index`, e.g. PyPI when the configured indexes mirror it. Both the
+    /// hash listed by the mirror and the hash of the downloaded artifact must match the hash
+    /// published upstream, otherwise the download fails with an [`UpstreamHashMismatch`] error.
+    ///
+    /// Artifacts that are read from the pip cache or the http cache are verified as well. Metadata
+    /// of wheels that is read without downloading the artifact is verified against the metadata
+    /// hash published upstream, if any.
+    ///
+    /// Artifacts that the upstream index does not publish a sha256 hash for (e.g. packages that
+    /// only exist on a private index) are not verified and only logged, unless
+    /// [`PackageDb::with_strict_upstream_index`] is used.
+    pub fn with_upstream_index(self, upstream_index: Url) -> Self {
+        let mut auth = self.http.auth_store().clone();
+        let upstream_index = auth.take_credentials(upstream_index);
+        Self {
+            upstream_index: Some(upstream_index),
+            http: self.http.with_auth_store(auth),
+            ..self
+        }
+    }
+
+    /// When `strict` is true, artifacts that cannot be verified against the upstream index, see
+    /// [`PackageDb::with_upstream_index`], fail with an [`UpstreamHashMissing`] error instead of
+    /// only being logged. This includes packages that do not exist upstream. Metadata of wheels
+    /// that cannot be verified is not used, the whole wheel is downloaded and verified instead.
+    pub fn with_strict_upstream_index(self, strict: bool) -> Self {
+        Self {
+            strict_upstream_index: strict,
+            ..self
+        }
+    }
+
+    /// Returns the hashes that the upstream index publishes for the artifact, or `None` if the
+    /// artifact does not have to be verified. Artifacts that are not published upstream have no
+    /// hashes.
+    async fn upstream_hashes(
+        &self,
+        artifact_info: &ArtifactInfo,
+    ) -> miette::Result<Option<UpstreamHashes>> {
+        let Some(upstream_index) = &self.upstream_index else {
+            return Ok(None);
+        };
+
+        // Artifacts from the upstream index itself are trusted
+        if self
+            .artifact_index_url(artifact_info)
+            .is_some_and(|url| same_index_url(url, upstream_index))
+        {
+            return Ok(None);
+        }
+
+        let name = NormalizedPackageName::from(artifact_info.filename.distribution().clone());
+        let hashes = match self.upstream_hashes.get(&name) {
+            Some(hashes) => hashes,
+            None => {
+                let url = upstream_index
+                    .join(&format!("{}/", name.as_str()))
+                    .into_diagnostic()?;
+                // Packages that do not exist upstream have no hashes at all
+                let hashes = fetch_simple_api(&self.http, url)
+                    .await?
+                    .map(|project_info| project_info.files)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|artifact| {
+                        let hashes = UpstreamHashes {
+                            sha256: artifact.hashes.and_then(|hashes| hashes.sha256),
+                            metadata_sha256: artifact.dist_info_metadata.hashes.sha256,
+                        };
+                        (artifact.filename.to_string(), hashes)
+                    })
+                    .collect();
+                self.upstream_hashes.insert(name, Box::new(hashes))
+            }
+        };
+
+        Ok(Some(
+            hashes
+                .get(&artifact_info.filename.to_string())
+                .cloned()
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// Returns the sha256 hash that the upstream index publishes for the artifact, or `None` if
+    /// the artifact does not have to be verified or, unless strict, cannot be verified.
+    async fn upstream_sha256(
+        &self,
+        artifact_info: &ArtifactInfo,
+    ) -> miette::Result<Option<Sha256Hash>> {
+        let Some(hashes) = self.upstream_hashes(artifact_info).await? else {
+            return Ok(None);
+        };
+        if hashes.sha256.is_none() {
+            self.unverifiable(artifact_info)?;
+        }
+        Ok(hashes.sha256)
+    }
+
+    /// Reports an artifact that the upstream index does not publish a hash for. Fails if
+    /// verification is strict.
+    fn unverifiable(&self, artifact_info: &ArtifactInfo) -> Result<(), UpstreamHashMissing> {
+        let Some(upstream_index) = &self.upstream_index else {
+            return Ok(());
+        };
+        if self.strict_upstream_index {
+            return Err(UpstreamHashMissing {
+                filename: artifact_info.filename.clone(),
+                upstream_index: upstream_index.clone(),
+            });
+        }
+        tracing::warn!(
+            "{} cannot be verified because {upstream_index} does not publish a sha256 hash for it",
+            artifact_info.filename
+        );
+        Ok(())
+    }
+
+    /// Verifies metadata of an artifact that was not read from the verified artifact itself.
+    /// Returns false if the metadata cannot be verified and should not be used.
+    ///
+    /// The metadata of wheels is checked against the metadata hash published upstream. Metadata of
+    /// sdists is only ever computed from a downloaded (and therefore verified) sdist, so it is
+    /// enough that the hash it is cached under matches the hash published upstream.
+    async fn verify_upstream_metadata(
+        &self,
+        artifact_info: &ArtifactInfo,
+        metadata: &[u8],
+    ) -> miette::Result<bool> {
+        let (Some(hashes), Some(upstream_index)) = (
+            self.upstream_hashes(artifact_info).await?,
+            &self.upstream_index,
+        ) else {
+            return Ok(true);
+        };
+
+        if artifact_info.is::<SDist>() {
+            let listed = artifact_info
+                .hashes
+                .as_ref()
+                .and_then(|hashes| hashes.sha256.as_ref());
+            return match (hashes.sha256, listed) {
+                (Some(expected), Some(listed)) => {
+                    self.check_upstream_sha256(artifact_info, Some(&expected), listed)?;
+                    Ok(true)
+                }
+                _ => {
+                    self.unverifiable(artifact_info)?;
+                    Ok(true)
+                }
+            };
+        }
+
+        match hashes.metadata_sha256 {
+            Some(expected) => {
+                let actual = rattler_digest::compute_bytes_digest::<Sha256>(metadata);
+                if actual != expected {
+                    return Err(UpstreamHashMismatch {
+                        filename: artifact_info.filename.clone(),
+                        upstream_index: upstream_index.clone(),
+                        expected: format!("{expected:x}"),
+                        actual: format!("{actual:x}"),
+                    }
+                    .into());
+                }
+                Ok(true)
+            }
+            None => Ok(!self.strict_upstream_index),
+        }
+    }
+
+    /// Fails if `sha256` does not match the hash published by the upstream index.
+    fn check_upstream_sha256(
+        &self,
+        artifact_info: &ArtifactInfo,
+        upstream_sha256: Option<&Sha256Hash>,
+        sha256: &Sha256Hash,
+    ) -> Result<(), UpstreamHashMismatch> {
+        match (upstream_sha256, &self.upstream_index) {
+            (Some(expected), Some(upstream_index)) if expected != sha256 => {
+                Err(UpstreamHashMismatch {
+                    filename: artifact_info.filename.clone(),
+                    upstream_index: upstream_index.clone(),
+                    expected: format!("{expected:x}"),
+                    actual: format!("{sha256:x}"),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Asks `download_approval` before the metadata or artifacts of a package version are
+    /// downloaded for the first time. Downloads of versions that are rejected fail with a
+    /// [`DownloadRejected`] error.
+    pub fn with_download_approval(self, download_approval: Arc<dyn DownloadApproval>) -> Self {
+        Self {
+            download_approval: Some(download_approval),
+            ..self
+        }
+    }
+
+    /// Approves downloading the given package version without asking the [`DownloadApproval`],
+    /// e.g. because it is pinned by a lock file that was already reviewed.
+    pub fn approve_download(&self, name: NormalizedPackageName, version: Version) {
+        self.approved_downloads.lock().insert((name, version));
+    }
+
+    /// Provides the metadata of a package version up front, e.g. from a metadata service. When
+    /// the metadata of this version is requested it is returned as is, without reading the cache,
+    /// downloading an artifact or building an sdist. Only the index pages are still fetched to
+    /// discover which versions exist.
+    ///
+    /// The version is identified by the name and version of the metadata. If metadata was already
+    /// seeded for the version, the earlier metadata is kept.
+    pub fn seed_metadata(&self, metadata: WheelCoreMetadata) {
+        let key = (metadata.name.clone().into(), metadata.version.clone());
+        self.seeded_metadata.lock().entry(key).or_insert(metadata);
+    }
+
+    /// Returns true if metadata was provided with [`PackageDb::seed_metadata`].
+    pub fn has_seeded_metadata(&self) -> bool {
+        !self.seeded_metadata.lock().is_empty()
+    }
+
+    /// Asks the [`DownloadApproval`] whether the artifact, or its metadata, may be downloaded,
+    /// unless the artifact is available locally, the artifact was already approved or its version
+    /// was approved up front.
+    fn review_download(&self, artifact_info: &ArtifactInfo) -> miette::Result<()> {
+        let Some(download_approval) = &self.download_approval else {
+            return Ok(());
+        };
+
+        let name = NormalizedPackageName::from(artifact_info.filename.distribution().clone());
+        let version = artifact_info.filename.version();
+        if self.approved_artifacts.lock().contains(&artifact_info.url)
+            || self
+                .approved_downloads
+                .lock()
+                .contains(&(name.clone(), version.clone()))
+        {
+            return Ok(());
+        }
+
+        // Data that is already available locally was downloaded (and approved) before.
+        if self.find_in_pip_cache(artifact_info).is_some()
+            || self
+                .http
+                .cached_response(&artifact_info.url, Method::GET, &HeaderMap::default())
+                .into_diagnostic()?
+                .is_some()
+        {
+            return Ok(());
+        }
+
+        match download_approval.review(&FirstDownload {
+            name: &name,
+            version,
+            artifact_info,
+        }) {
+            DownloadDecision::Approve => {
+                self.approved_artifacts
+                    .lock()
+                    .insert(artifact_info.url.clone());
+                Ok(())
+            }
+            DownloadDecision::Reject { reason } => Err(DownloadRejected {
+                name,
+                version: version.clone(),
+                reason,
+            }
+            .into()),
+        }
+    }
+
+    /// Returns how many index pages, metadata and artifacts were served from the caches since this
+    /// instance was constructed, see [`CacheStatistics`].
+    pub fn cache_statistics(&self) -> CacheStatistics {
+        self.http.cache_stats().snapshot()
+    }
+
+    /// Returns the session log that events are written to, if any.
+    pub fn session_log(&self) -> Option<&SessionLog> {
+        self.http.session_log()
+    }
+
     /// Downloads and caches information about available artifiacts of a package from the index.
+    ///
+    /// The artifacts of all indexes are merged in the order described by [`IndexSet`].
     pub async fn available_artifacts<P: Into<NormalizedPackageName>>(
         &self,
         p: P,
@@ -56,46 +558,146 @@ impl PackageDb {
         if let Some(cached) = self.artifacts.get(&p) {
             Ok(cached)
         } else {
-            // Start downloading the information for each url.
+            // Start downloading the information for each index. The responses are kept in the
+            // order of the indexes because that determines their priority.
             let http = self.http.clone();
-            let request_iter = stream::iter(self.index_urls.iter())
-                .map(|url| url.join(&format!("{}/", p.as_str())).expect("invalid url"))
-                .map(|url| fetch_simple_api(&http, url))
-                .buffer_unordered(10)
-                .filter_map(|result| async { result.transpose() });
+            let request_iter = stream::iter(self.indexes.simple_indexes())
+                .map(|index| {
+                    let url = index
+                        .url
+                        .join(&format!("{}/", p.as_str()))
+                        .expect("invalid url");
+                    let http = &http;
+                    async move { fetch_simple_api(http, url).await.map(|info| (index, info)) }
+                })
+                .buffered(10);
 
             pin_mut!(request_iter);
 
-            // Add all the incoming results to the set of results
-            let mut result: IndexMap<Version, Vec<ArtifactInfo>> = Default::default();
+            let mut found = Vec::new();
+            let mut versions = Vec::new();
             while let Some(response) = request_iter.next().await {
-                for artifact in response?.files {
-                    result
-                        .entry(artifact.filename.version().clone())
-                        .or_default()
-                        .push(artifact);
-                }
+                let (index, Some(response)) = response? else {
+                    continue;
+                };
+                versions.extend(response.versions);
+                found.push((index, response.files));
+            }
+
+            // Add the artifacts of the package that are linked from the flat indexes
+            for index in self.indexes.flat() {
+                let response = fetch_find_links(&http, index.url.clone(), &p).await?;
+                found.push((index, response.files));
             }
 
-            // Sort the artifact infos by name, this is just to have a consistent order and make
-            // the resolution output consistent.
-            for artifact_infos in result.values_mut() {
-                artifact_infos.sort_by(|a, b| a.filename.cmp(&b.filename));
+            // Remember where the artifacts came from, the first index that links an artifact wins
+            for (index, artifacts) in found.iter() {
+                for artifact in artifacts.iter() {
+                    if self.artifact_indexes.get(&artifact.url).is_none() {
+                        self.artifact_indexes
+                            .insert(artifact.url.clone(), Box::new(index.url.clone()));
+                    }
+                }
             }
 
-            // Sort in descending order by version
-            result.sort_unstable_by(|v1, _, v2, _| v2.cmp(v1));
+            let result = merge_artifacts(found);
+
+            // Not all indexes list the versions of a project, so also include the versions of
+            // the artifacts.
+            versions.extend(result.keys().cloned());
+            versions.sort_unstable_by(|v1, v2| v2.cmp(v1));
+            versions.dedup();
+            self.versions.insert(p.clone(), Box::new(versions));
 
             Ok(self.artifacts.insert(p.clone(), Box::new(result)))
         }
     }
 
+    /// Returns the url of the index on which the artifact was found, or `None` if the artifact was
+    /// not found through [`Self::available_artifacts`], e.g. because it is a direct reference.
+    pub fn artifact_index_url(&self, artifact_info: &ArtifactInfo) -> Option<&Url> {
+        self.artifact_indexes.get(&artifact_info.url)
+    }
+
+    /// Returns all versions of a package that are available on the indexes ordered from highest
+    /// to lowest. Indexes that implement [PEP 700](https://peps.python.org/pep-0700/) may also
+    /// list versions that don't have any artifacts, e.g. because all artifacts were removed.
+    pub async fn available_versions<P: Into<NormalizedPackageName>>(
+        &self,
+        p: P,
+    ) -> miette::Result<&[Version]> {
+        let p = p.into();
+        self.available_artifacts(p.clone()).await?;
+        Ok(self
+            .versions
+            .get(&p)
+            .map(|versions| versions.as_slice())
+            .unwrap_or_default())
+    }
+
+    /// Returns the artifact that a direct reference (e.g. `foo @ https://example.com/foo-1.0.tar.gz`)
+    /// points to. The name and version of the artifact are derived from the filename in the url,
+    /// so the url must point to a wheel or an sdist. A `sha256` hash in the fragment of the url is
+    /// used to verify the artifact.
+    pub fn direct_reference_artifact(
+        &self,
+        name: &NormalizedPackageName,
+        url: &Url,
+    ) -> miette::Result<&ArtifactInfo> {
+        if let Some(artifact) = self.direct_references.get(url) {
+            return Ok(artifact);
+        }
+
+        if !matches!(url.scheme(), "http" | "https") {
+            miette::bail!("the direct reference {url} is not supported, only http(s) urls to wheels or sdists are supported");
+        }
+        let filename = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .ok_or_else(|| {
+                miette::miette!("the direct reference {url} does not contain a filename")
+            })?;
+        let filename = ArtifactName::from_filename(filename, name).map_err(|e| {
+            miette::miette!(
+                "the direct reference {url} does not point to an artifact of {name}: {e}"
+            )
+        })?;
+
+        Ok(self.direct_references.insert(
+            url.clone(),
+            Box::new(ArtifactInfo {
+                hashes: url.fragment().and_then(parse_hash),
+                ..ArtifactInfo::new(filename, url.clone())
+            }),
+        ))
+    }
+
+    /// Summarizes which artifacts are available for a specific version of a package, e.g. which
+    /// platforms have wheels. Returns `None` if the version has no artifacts on the indexes.
+    pub async fn artifact_availability<P: Into<NormalizedPackageName>>(
+        &self,
+        p: P,
+        version: &Version,
+    ) -> miette::Result<Option<ArtifactAvailability>> {
+        Ok(self
+            .available_artifacts(p)
+            .await?
+            .get(version)
+            .map(ArtifactAvailability::from_artifacts))
+    }
+
     /// Reads the metadata for the given artifact from the cache or return `None` if the metadata
     /// could not be found in the cache.
     fn metadata_from_cache(&self, ai: &ArtifactInfo) -> Option<Vec<u8>> {
         let mut data = self.metadata_cache.get(&ai.hashes.as_ref()?)?;
         let mut bytes = Vec::new();
         data.read_to_end(&mut bytes).ok()?;
+        if let Some(session_log) = self.session_log() {
+            session_log.record(SessionEvent::CacheHit {
+                cache: CacheKind::Metadata,
+                key: ai.filename.to_string(),
+            });
+        }
         Some(bytes)
     }
 
@@ -187,7 +789,9 @@ impl PackageDb {
             // Retrieve the metadata instead of the entire wheel
             // If the dist-info is available separately, we can use that instead
             if artifact_info.dist_info_metadata.available {
-                return Ok(Some(self.get_pep658_metadata(artifact_info).await?));
+                if let Some(result) = self.get_pep658_metadata(artifact_info).await? {
+                    return Ok(Some(result));
+                }
             }
 
             // Try to load the data by sparsely reading the artifact (if supported)
@@ -258,10 +862,31 @@ impl PackageDb {
         artifacts: &[&'a ArtifactInfo],
         wheel_builder: Option<&WheelBuilder<'a, 'i>>,
     ) -> miette::Result<Option<(&'a ArtifactInfo, WheelCoreMetadata)>> {
+        // Metadata that was provided up front takes precedence over everything else
+        if let Some(artifact_info) = artifacts.first() {
+            let key = (
+                artifact_info.filename.distribution().clone().into(),
+                artifact_info.filename.version().clone(),
+            );
+            let seeded = self.seeded_metadata.lock().get(&key).cloned();
+            if let Some(metadata) = seeded {
+                return Ok(Some((artifact_info, metadata)));
+            }
+        }
+
         // Check if we already have information about any of the artifacts cached.
         // Return if we do
         for artifact_info in artifacts.iter().copied() {
             if let Some(metadata_bytes) = self.metadata_from_cache(artifact_info) {
+                if !self
+                    .verify_upstream_metadata(artifact_info, &metadata_bytes)
+                    .await?
+                {
+                    continue;
+                }
+                self.http
+                    .cache_stats()
+                    .record(CacheCategory::Metadata, true);
                 return Ok(Some((
                     artifact_info,
                     WheelCoreMetadata::try_from(metadata_bytes.as_slice()).into_diagnostic()?,
@@ -274,8 +899,14 @@ impl PackageDb {
         // In this case we can just return it
         let result = self.metadata_for_cached_artifacts(artifacts).await?;
         if result.is_some() {
+            self.http
+                .cache_stats()
+                .record(CacheCategory::Metadata, true);
             return Ok(result);
         }
+        self.http
+            .cache_stats()
+            .record(CacheCategory::Metadata, false);
 
         // We have exhausted all options to read the metadata from the cache. We'll have to hit the
         // network to get to the information.
@@ -303,21 +934,43 @@ impl PackageDb {
         &self,
         artifact_info: &ArtifactInfo,
     ) -> miette::Result<Option<WheelCoreMetadata>> {
+        // Range requests bypass the fixtures, so we skip them altogether. Local artifacts are
+        // read in place instead.
+        if self.http.fixture_mode().is_some() || artifact_info.url.scheme() == "file" {
+            return Ok(None);
+        }
+
+        // Metadata that cannot be verified against the upstream index is not used
+        if self.strict_upstream_index
+            && self
+                .upstream_hashes(artifact_info)
+                .await?
+                .is_some_and(|hashes| hashes.metadata_sha256.is_none())
+        {
+            return Ok(None);
+        }
+
+        self.review_download(artifact_info)?;
         tracing::info!(url=%artifact_info.url, "lazy reading artifact");
 
         // Check if the artifact is the same type as the info.
         let name = WheelFilename::try_as(&artifact_info.filename)
             .expect("the specified artifact does not refer to type requested to read");
 
+        // The range reader cannot add headers to its requests, reqwest moves the credentials of
+        // the url to a header instead.
         if let Ok(mut reader) = AsyncHttpRangeReader::new(
             self.http.client.clone(),
-            artifact_info.url.clone(),
+            self.http.auth_store().authenticated_url(&artifact_info.url),
             CheckSupportMethod::Head,
         )
         .await
         {
             match Wheel::read_metadata_bytes(name, &mut reader).await {
                 Ok((blob, metadata)) => {
+                    if !self.verify_upstream_metadata(artifact_info, &blob).await? {
+                        return Ok(None);
+                    }
                     self.put_metadata_in_cache(artifact_info, &blob)?;
                     return Ok(Some(metadata));
                 }
@@ -336,11 +989,13 @@ impl PackageDb {
     async fn get_pep658_metadata<'a>(
         &self,
         artifact_info: &'a ArtifactInfo,
-    ) -> miette::Result<(&'a ArtifactInfo, WheelCoreMetadata)> {
+    ) -> miette::Result<Option<(&'a ArtifactInfo, WheelCoreMetadata)>> {
         // Check if the artifact is the same type as the info.
         WheelFilename::try_as(&artifact_info.filename)
             .expect("the specified artifact does not refer to type requested to read");
 
+        self.review_download(artifact_info)?;
+
         // Turn into PEP658 compliant URL
         let mut url = artifact_info.url.clone();
         url.set_path(&url.path().replace(".whl", ".whl.metadata"));
@@ -354,14 +1009,17 @@ impl PackageDb {
             .await
             .into_diagnostic()?;
 
+        if !self.verify_upstream_metadata(artifact_info, &bytes).await? {
+            return Ok(None);
+        }
         let metadata = WheelCoreMetadata::try_from(bytes.as_slice()).into_diagnostic()?;
         self.put_metadata_in_cache(artifact_info, &bytes)?;
-        Ok((artifact_info, metadata))
+        Ok(Some((artifact_info, metadata)))
     }
 
     /// Get all package names in the index.
     pub async fn get_package_names(&self) -> miette::Result<Vec<String>> {
-        let index_url = self.index_urls.first();
+        let index_url = self.indexes.primary().map(|index| &index.url);
         if let Some(url) = index_url {
             let response = self
                 .http
@@ -397,7 +1055,126 @@ impl PackageDb {
             )
         });
 
-        // Get the contents of the artifact
+        // Artifacts in local directories, e.g. find-links directories, are read in place
+        if artifact_info.url.scheme() == "file" {
+            let path = artifact_info
+                .url
+                .to_file_path()
+                .map_err(|_| miette::miette!("invalid file url '{}'", artifact_info.url))?;
+            if let Some(upstream_sha256) = self.upstream_sha256(artifact_info).await? {
+                let mut file = std::fs::File::open(&path).into_diagnostic()?;
+                let sha256 = sha256_of(&mut file).into_diagnostic()?;
+                self.check_upstream_sha256(artifact_info, Some(&upstream_sha256), &sha256)?;
+            }
+            return A::from_file(name.clone(), &path);
+        }
+
+        // Only look up the upstream hash when the artifact is actually used
+        if cache_mode == CacheMode::OnlyIfCached
+            && self.find_in_pip_cache(artifact_info).is_none()
+            && self
+                .http
+                .cached_response(&artifact_info.url, Method::GET, &HeaderMap::default())
+                .into_diagnostic()?
+                .is_none()
+        {
+            return Err(HttpRequestError::NotCached(NotCached).into());
+        }
+
+        // The hash listed by the index must match the hash published by the upstream index
+        let upstream_sha256 = self.upstream_sha256(artifact_info).await?;
+        if let Some(listed) = artifact_info
+            .hashes
+            .as_ref()
+            .and_then(|hashes| hashes.sha256.as_ref())
+        {
+            self.check_upstream_sha256(artifact_info, upstream_sha256.as_ref(), listed)?;
+        }
+
+        // Use the artifact from the pip cache if it's available there
+        if let Some(path) = self.find_in_pip_cache(artifact_info) {
+            tracing::debug!(
+                "using {} from the pip cache at {}",
+                artifact_info.filename,
+                path.display()
+            );
+            if let Some(session_log) = self.session_log() {
+                session_log.record(SessionEvent::CacheHit {
+                    cache: CacheKind::Pip,
+                    key: artifact_info.url.to_string(),
+                });
+            }
+            if cache_mode == CacheMode::Default {
+                self.http
+                    .cache_stats()
+                    .record(CacheCategory::Artifact, true);
+            }
+            if upstream_sha256.is_some() {
+                let mut file = std::fs::File::open(&path).into_diagnostic()?;
+                let sha256 = sha256_of(&mut file).into_diagnostic()?;
+                self.check_upstream_sha256(artifact_info, upstream_sha256.as_ref(), &sha256)?;
+            }
+            return A::from_file(name.clone(), &path);
+        }
+        if cache_mode != CacheMode::OnlyIfCached {
+            self.review_download(artifact_info)?;
+        }
+
+        // Get the contents of the artifact. Truncated or corrupt downloads are removed from the
+        // cache and downloaded once more before giving up.
+        let mut retried = false;
+        let mut bytes = loop {
+            let reason = match self.fetch_artifact_bytes(artifact_info, cache_mode).await {
+                Ok(mut bytes) => {
+                    match check_artifact_integrity(&artifact_info.filename, &mut bytes) {
+                        Ok(()) => break bytes,
+                        Err(reason) => reason,
+                    }
+                }
+                Err(err @ HttpRequestError::IncompleteBody { .. }) => err.to_string(),
+                Err(err) => return Err(err.into()),
+            };
+
+            self.http
+                .invalidate(&artifact_info.url, Method::GET, &HeaderMap::default())
+                .into_diagnostic()?;
+            if cache_mode == CacheMode::OnlyIfCached {
+                tracing::warn!(
+                    "removed corrupt {} from the cache: {reason}",
+                    artifact_info.filename
+                );
+                return Err(HttpRequestError::NotCached(NotCached).into());
+            }
+            if retried {
+                return Err(CorruptArtifact {
+                    filename: artifact_info.filename.clone(),
+                    reason,
+                }
+                .into());
+            }
+            tracing::warn!(
+                "{} is corrupt, downloading it again: {reason}",
+                artifact_info.filename
+            );
+            retried = true;
+        };
+
+        // Verify the downloaded artifact against the upstream index
+        if upstream_sha256.is_some() {
+            let sha256 = sha256_of(&mut bytes).into_diagnostic()?;
+            self.check_upstream_sha256(artifact_info, upstream_sha256.as_ref(), &sha256)?;
+            bytes.rewind().into_diagnostic()?;
+        }
+
+        A::new(name.clone(), bytes)
+    }
+
+    /// Requests the contents of an artifact and turns the response into a seekable body.
+    async fn fetch_artifact_bytes(
+        &self,
+        artifact_info: &ArtifactInfo,
+        cache_mode: CacheMode,
+    ) -> Result<Box<dyn ReadAndSeek + Send>, HttpRequestError> {
         let artifact_bytes = self
             .http
             .request(
@@ -408,13 +1185,196 @@ impl PackageDb {
             )
             .await?;
 
-        // Turn the response into a seekable response.
-        let bytes = artifact_bytes
+        // Only count actual fetches, not the lookups that merely check whether the artifact is
+        // cached.
+        if cache_mode == CacheMode::Default {
+            self.http
+                .cache_stats()
+                .record_response(CacheCategory::Artifact, &artifact_bytes);
+        }
+
+        Ok(artifact_bytes.into_body().into_local().await?)
+    }
+
+    /// Returns the artifact info with a sha256 hash. If the index did not provide a sha256 hash for
+    /// the artifact, the artifact is downloaded (or read from the cache) and its hash is computed.
+    /// The computed hash is remembered for the lifetime of the database.
+    pub async fn with_sha256<'a>(
+        &'a self,
+        artifact_info: &'a ArtifactInfo,
+    ) -> miette::Result<&'a ArtifactInfo> {
+        if artifact_info
+            .hashes
+            .as_ref()
+            .map_or(false, |hashes| hashes.sha256.is_some())
+        {
+            return Ok(artifact_info);
+        }
+        if let Some(hashed) = self.hashed_artifacts.get(&artifact_info.url) {
+            return Ok(hashed);
+        }
+
+        self.review_download(artifact_info)?;
+        tracing::info!(url=%artifact_info.url, "computing the sha256 hash of artifact");
+        let mut body = self
+            .http
+            .request(
+                artifact_info.url.clone(),
+                Method::GET,
+                HeaderMap::default(),
+                CacheMode::Default,
+            )
+            .await?
             .into_body()
             .into_local()
             .await
             .into_diagnostic()?;
-        A::new(name.clone(), bytes)
+        let sha256 = sha256_of(&mut body).into_diagnostic()?;
+        let upstream_sha256 = self.upstream_sha256(artifact_info).await?;
+        self.check_upstream_sha256(artifact_info, upstream_sha256.as_ref(), &sha256)?;
+
+        Ok(self.hashed_artifacts.insert(
+            artifact_info.url.clone(),
+            Box::new(ArtifactInfo {
+                hashes: Some(ArtifactHashes {
+                    sha256: Some(sha256),
+                }),
+                ..artifact_info.clone()
+            }),
+        ))
+    }
+
+    /// Fetches the [PEP 740](https://peps.python.org/pep-0740/) attestations of an artifact and
+    /// checks that they attest to the artifact, see [`check_provenance`]. Returns
+    /// [`AttestationStatus::NotPublished`] if the index does not publish attestations for it.
+    pub async fn artifact_attestations(
+        &self,
+        artifact_info: &ArtifactInfo,
+    ) -> miette::Result<AttestationStatus> {
+        let Some(url) = &artifact_info.provenance else {
+            return Ok(AttestationStatus::NotPublished);
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(PROVENANCE_CONTENT_TYPE));
+        let mut bytes = Vec::new();
+        self.http
+            .request(url.clone(), Method::GET, headers, CacheMode::Default)
+            .await?
+            .into_body()
+            .read_to_end(&mut bytes)
+            .await
+            .into_diagnostic()?;
+
+        Ok(match serde_json::from_slice::<Provenance>(&bytes) {
+            Ok(provenance) => check_provenance(artifact_info, &provenance),
+            Err(err) => AttestationStatus::Failed {
+                reason: format!("invalid provenance: {err}"),
+            },
+        })
+    }
+
+    /// Returns the size in bytes of the specified artifact if it is available in the local cache.
+    /// Returns `None` if the artifact has not been cached.
+    pub async fn cached_artifact_size(
+        &self,
+        artifact_info: &ArtifactInfo,
+    ) -> miette::Result<Option<u64>> {
+        if let Some(path) = self.find_in_pip_cache(artifact_info) {
+            return Ok(Some(std::fs::metadata(path).into_diagnostic()?.len()));
+        }
+
+        let response = match self
+            .http
+            .request(
+                artifact_info.url.clone(),
+                Method::GET,
+                HeaderMap::default(),
+                CacheMode::OnlyIfCached,
+            )
+            .await
+        {
+            Ok(response) => response,
+            Err(HttpRequestError::NotCached(_) | HttpRequestError::FixtureNotFound(_)) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut body = response.into_body().into_local().await.into_diagnostic()?;
+        Ok(Some(body.seek(SeekFrom::End(0)).into_diagnostic()?))
+    }
+
+    /// Returns whether the artifact and its metadata are available locally, without downloading
+    /// anything or recording anything in the session log. This allows distinguishing work that
+    /// can be served from the cache from work that requires downloads.
+    pub fn cache_status(
+        &self,
+        artifact_info: &ArtifactInfo,
+    ) -> miette::Result<ArtifactCacheStatus> {
+        let artifact = if let Some(path) = self.find_in_pip_cache(artifact_info) {
+            let metadata = std::fs::metadata(path).into_diagnostic()?;
+            Some(CachedArtifact {
+                cache: CacheKind::Pip,
+                size: metadata.len(),
+                age: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.elapsed().ok()),
+            })
+        } else {
+            self.http
+                .cached_response(&artifact_info.url, Method::GET, &HeaderMap::default())
+                .into_diagnostic()?
+                .map(|(size, modified)| CachedArtifact {
+                    cache: CacheKind::Http,
+                    size,
+                    age: modified.elapsed().ok(),
+                })
+        };
+
+        let metadata = artifact_info
+            .hashes
+            .as_ref()
+            .is_some_and(|hashes| self.metadata_cache.peek(hashes).is_some());
+
+        Ok(ArtifactCacheStatus { artifact, metadata })
+    }
+
+    /// Determines the size in bytes of the specified artifact on the remote. The size reported by
+    /// the index is used if available, otherwise the `Content-Length` of a `HEAD` request is used.
+    /// Returns `None` if the size could not be determined.
+    pub async fn remote_artifact_size(
+        &self,
+        artifact_info: &ArtifactInfo,
+    ) -> miette::Result<Option<u64>> {
+        if let Some(size) = artifact_info.size {
+            return Ok(Some(size));
+        }
+
+        // We cannot perform HEAD requests when replaying fixtures.
+        if self.http.fixture_mode().is_some() {
+            return Ok(None);
+        }
+
+        // Like every other request the HEAD request is authorized, retried and logged by `Http`,
+        // but the empty response is not cached.
+        let response = self
+            .http
+            .request(
+                artifact_info.url.clone(),
+                Method::HEAD,
+                HeaderMap::default(),
+                CacheMode::NoStore,
+            )
+            .await
+            .into_diagnostic()?;
+
+        Ok(response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()))
     }
 
     /// Opens the specified artifact info. Downloads the artifact data from the remote location if
@@ -432,9 +1392,19 @@ async fn fetch_simple_api(http: &Http, url: Url) -> miette::Result<Option<Projec
     let mut headers = HeaderMap::new();
     headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=0"));
 
+    // Prefer the JSON API, it contains more information like the upload time of files.
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static(
+            "application/vnd.pypi.simple.v1+json, application/vnd.pypi.simple.v1+html;q=0.2, text/html;q=0.1",
+        ),
+    );
+
     let response = http
         .request(url, Method::GET, headers, CacheMode::Default)
         .await?;
+    http.cache_stats()
+        .record_response(CacheCategory::IndexPage, &response);
 
     // If the resource could not be found we simply return.
     if response.status() == StatusCode::NOT_FOUND {
@@ -463,22 +1433,220 @@ async fn fetch_simple_api(http: &Http, url: Url) -> miette::Result<Option<Projec
         content_type.type_().as_str(),
         content_type.subtype().as_str(),
     ) {
-        ("text", "html") => {
+        ("text", "html") | ("application", "vnd.pypi.simple.v1+html") => {
             parse_project_info_html(&url, std::str::from_utf8(&bytes).into_diagnostic()?).map(Some)
         }
+        _ if content_type.essence_str() == SIMPLE_API_JSON_CONTENT_TYPE => {
+            parse_project_info_json(&url, &bytes).map(Some)
+        }
         _ => miette::bail!(
-            "simple API page expected Content-Type: text/html, but got {}",
+            "simple API page expected Content-Type: text/html or {SIMPLE_API_JSON_CONTENT_TYPE}, but got {}",
             &content_type
         ),
     }
 }
 
+/// Fetches a find-links page and parses the artifacts of the package `p` from it. A `file://`
+/// url to a directory is scanned for the artifacts of `p` instead.
+async fn fetch_find_links(
+    http: &Http,
+    url: Url,
+    p: &NormalizedPackageName,
+) -> miette::Result<ProjectInfo> {
+    if url.scheme() == "file" {
+        return scan_find_links_dir(&url, p).await;
+    }
+    if !matches!(url.scheme(), "http" | "https") {
+        miette::bail!(
+            "unsupported find-links location '{}', only http(s) urls and local directories are supported",
+            redact_url(&url)
+        );
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=0"));
+    headers.insert(ACCEPT, HeaderValue::from_static("text/html"));
+
+    let response = http
+        .request(url, Method::GET, headers, CacheMode::Default)
+        .await?;
+    http.cache_stats()
+        .record_response(CacheCategory::IndexPage, &response);
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(ProjectInfo::default());
+    }
+
+    let url = response.extensions().get::<Url>().unwrap().to_owned();
+    let mut bytes = Vec::new();
+    response
+        .into_body()
+        .read_to_end(&mut bytes)
+        .await
+        .into_diagnostic()?;
+    parse_find_links_html(&url, std::str::from_utf8(&bytes).into_diagnostic()?, p)
+}
+
+/// Scans the local find-links directory at `url` for the artifacts of the package `p`. Files that
+/// look like artifacts but cannot be read are skipped with a warning.
+async fn scan_find_links_dir(url: &Url, p: &NormalizedPackageName) -> miette::Result<ProjectInfo> {
+    let dir = url
+        .to_file_path()
+        .map_err(|_| miette::miette!("invalid find-links location '{url}'"))?;
+    let scan = tokio::task::spawn_blocking(move || {
+        scan_wheelhouse(&dir)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read find-links directory '{}'", dir.display()))
+    })
+    .await
+    .into_diagnostic()??;
+
+    for err in &scan.errors {
+        tracing::warn!("skipping find-links artifact: {err}");
+    }
+    Ok(ProjectInfo {
+        files: scan
+            .artifacts
+            .into_iter()
+            .filter(|artifact| {
+                &NormalizedPackageName::from(artifact.filename.distribution().clone()) == p
+            })
+            .collect(),
+        ..ProjectInfo::default()
+    })
+}
+
+/// Describes which information of an artifact is available locally, see
+/// [`PackageDb::cache_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactCacheStatus {
+    /// The cached artifact, `None` if the artifact has to be downloaded.
+    pub artifact: Option<CachedArtifact>,
+
+    /// True if the metadata of the artifact is cached. The metadata is only cached for artifacts
+    /// with a known hash.
+    pub metadata: bool,
+}
+
+/// Describes an artifact that is available locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedArtifact {
+    /// The cache that contains the artifact, either [`CacheKind::Http`] or [`CacheKind::Pip`].
+    pub cache: CacheKind,
+
+    /// The size of the artifact in bytes.
+    pub size: u64,
+
+    /// How long ago the artifact was stored in the cache, if known.
+    pub age: Option<Duration>,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::index::test_server::{test_wheels_dir, TestIndex, TestIndexServerOptions};
     use crate::types::PackageName;
     use tempfile::TempDir;
 
+    #[tokio::test]
+    async fn test_credentials_are_not_part_of_urls() {
+        let index = TestIndex::start_with_options(TestIndexServerOptions {
+            basic_auth: Some((String::from("user"), String::from("secret"))),
+            ..Default::default()
+        })
+        .await;
+        let mut index_url = index.server.url();
+        index_url.set_username("user").unwrap();
+        index_url.set_password(Some("secret")).unwrap();
+
+        let cache_dir = TempDir::new().unwrap();
+        let log_path = cache_dir.path().join("session.jsonl");
+        let package_db = PackageDb::new(Client::new(), &[index_url], cache_dir.path())
+            .unwrap()
+            .with_session_log(SessionLog::create(&log_path).unwrap());
+        assert_eq!(
+            package_db.indexes().primary().unwrap().url,
+            index.server.url()
+        );
+
+        // The credentials are still used for the index pages and the artifacts
+        let artifacts = package_db
+            .available_artifacts("wordle_python".parse::<PackageName>().unwrap())
+            .await
+            .unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        assert!(artifact_info.url.username().is_empty());
+        assert!(artifact_info.url.password().is_none());
+        package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+
+        // So is the request that determines the size of an artifact
+        let unsized_artifact = ArtifactInfo {
+            size: None,
+            ..artifact_info.clone()
+        };
+        let size = package_db
+            .remote_artifact_size(&unsized_artifact)
+            .await
+            .unwrap();
+        assert!(size.is_some_and(|size| size > 0));
+
+        package_db.session_log().unwrap().flush().unwrap();
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains(index.server.url().as_str()), "{log}");
+        assert!(log.contains(r#""method":"HEAD""#), "{log}");
+        assert!(!log.contains("secret"), "{log}");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_find_links() {
+        let cache_dir = TempDir::new().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path())
+            .unwrap()
+            .with_find_links(vec![Url::parse("ftp://example.com/wheels/").unwrap()]);
+
+        let err = package_db
+            .available_artifacts("wordle_python".parse::<PackageName>().unwrap())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported find-links location"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_find_links_directory() {
+        let cache_dir = TempDir::new().unwrap();
+        let wheelhouse = TempDir::new().unwrap();
+        let wheel = "wordle_python-2.3.32-py3-none-any.whl";
+        std::fs::copy(test_wheels_dir().join(wheel), wheelhouse.path().join(wheel)).unwrap();
+        std::fs::write(wheelhouse.path().join("broken.whl"), b"").unwrap();
+
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path())
+            .unwrap()
+            .with_find_links(vec![Url::from_directory_path(wheelhouse.path()).unwrap()]);
+
+        let artifacts = package_db
+            .available_artifacts("wordle_python".parse::<PackageName>().unwrap())
+            .await
+            .unwrap();
+        let artifact_infos = artifacts.values().flatten().collect::<Vec<_>>();
+        assert_eq!(artifact_infos.len(), 1);
+        let artifact_info = artifact_infos[0];
+        assert_eq!(artifact_info.filename.to_string(), wheel);
+        assert_eq!(artifact_info.url.scheme(), "file");
+
+        // The artifact is read from the directory
+        let (_, metadata) = package_db
+            .get_metadata(&[artifact_info], None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.name.as_str(), "wordle-python");
+    }
+
     #[tokio::test]
     async fn test_available_packages() {
         let cache_dir = TempDir::new().unwrap();
@@ -532,8 +1700,235 @@ mod test {
             .find(|a| a.dist_info_metadata.available)
             .unwrap();
 
-        let (_artifact, _metadata) = package_db.get_pep658_metadata(artifact_info).await.unwrap();
+        let (_artifact, _metadata) = package_db
+            .get_pep658_metadata(artifact_info)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_cache_status() {
+        let cache_dir = TempDir::new().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path()).unwrap();
+        let name: NormalizedPackageName = "my-package".parse::<PackageName>().unwrap().into();
+        let url = Url::parse("https://example.com/my_package-1.0-py3-none-any.whl#sha256=0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let artifact_info = package_db.direct_reference_artifact(&name, &url).unwrap();
+
+        let status = package_db.cache_status(artifact_info).unwrap();
+        assert_eq!(status.artifact, None);
+        assert!(!status.metadata);
+
+        package_db
+            .put_metadata_in_cache(artifact_info, b"Metadata-Version: 2.1")
+            .unwrap();
+        assert!(package_db.cache_status(artifact_info).unwrap().metadata);
+    }
+
+    #[test]
+    fn test_direct_reference_artifact() {
+        let cache_dir = TempDir::new().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path()).unwrap();
+        let name: NormalizedPackageName = "my-package".parse::<PackageName>().unwrap().into();
+
+        let url = Url::parse("https://example.com/my_package-1.0-py3-none-any.whl#sha256=0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+        let artifact = package_db.direct_reference_artifact(&name, &url).unwrap();
+        assert!(artifact.is::<Wheel>());
+        assert_eq!(artifact.filename.version().to_string(), "1.0");
+        assert!(artifact.hashes.as_ref().is_some_and(|h| h.sha256.is_some()));
+
+        // The url must point to an artifact of the package
+        let url = Url::parse("https://example.com/other-1.0.tar.gz").unwrap();
+        assert!(package_db.direct_reference_artifact(&name, &url).is_err());
+
+        // Only http(s) urls are supported
+        let url = Url::parse("git+https://example.com/my-package.git").unwrap();
+        assert!(package_db.direct_reference_artifact(&name, &url).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_approval() {
+        let cache_dir = TempDir::new().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path())
+            .unwrap()
+            .with_download_approval(Arc::new(|download: &FirstDownload<'_>| {
+                DownloadDecision::Reject {
+                    reason: format!("{} is not on the approval list", download.name),
+                }
+            }));
+        let name: NormalizedPackageName = "my-package".parse::<PackageName>().unwrap().into();
+        let url = Url::parse("https://example.com/my_package-1.0-py3-none-any.whl").unwrap();
+        let artifact_info = package_db.direct_reference_artifact(&name, &url).unwrap();
+
+        // The download is rejected before anything is requested
+        let err = package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "downloading my-package 1.0 was rejected: my-package is not on the approval list"
+        );
+
+        // Unless the version was approved up front
+        package_db.approve_download(name, "1.0".parse().unwrap());
+        assert!(package_db.review_download(artifact_info).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_download_approval_per_artifact() {
+        let cache_dir = TempDir::new().unwrap();
+        let reviewed = Arc::new(Mutex::new(Vec::new()));
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path())
+            .unwrap()
+            .with_download_approval(Arc::new({
+                let reviewed = reviewed.clone();
+                move |download: &FirstDownload<'_>| {
+                    let filename = download.artifact_info.filename.to_string();
+                    reviewed.lock().push(filename.clone());
+                    if filename.ends_with(".whl") {
+                        DownloadDecision::Approve
+                    } else {
+                        DownloadDecision::Reject {
+                            reason: String::from("sdists are not approved"),
+                        }
+                    }
+                }
+            }));
+        let name: NormalizedPackageName = "my-package".parse::<PackageName>().unwrap().into();
+        let wheel = package_db
+            .direct_reference_artifact(
+                &name,
+                &Url::parse("https://example.com/my_package-1.0-py3-none-any.whl").unwrap(),
+            )
+            .unwrap();
+        let sdist = package_db
+            .direct_reference_artifact(
+                &name,
+                &Url::parse("https://example.com/my_package-1.0.tar.gz").unwrap(),
+            )
+            .unwrap();
+
+        // Approving an artifact does not approve the other artifacts of the same version
+        assert!(package_db.review_download(wheel).is_ok());
+        assert!(package_db.review_download(sdist).is_err());
+
+        // But the approved artifact is not reviewed again
+        assert!(package_db.review_download(wheel).is_ok());
+        assert_eq!(
+            reviewed.lock().as_slice(),
+            ["my_package-1.0-py3-none-any.whl", "my_package-1.0.tar.gz"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_seed_metadata() {
+        let cache_dir = TempDir::new().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[], cache_dir.path()).unwrap();
+        let name: NormalizedPackageName = "my-package".parse::<PackageName>().unwrap().into();
+        let url = Url::parse("http://127.0.0.1:1/my_package-1.0-py3-none-any.whl").unwrap();
+        let artifact_info = package_db.direct_reference_artifact(&name, &url).unwrap();
+
+        package_db.seed_metadata(WheelCoreMetadata::new(
+            "My_Package".parse().unwrap(),
+            "1.0".parse().unwrap(),
+            vec!["requests>=2".parse().unwrap()],
+        ));
+
+        // The seeded metadata is returned without downloading the unreachable wheel
+        let (_, metadata) = package_db
+            .get_metadata(&[artifact_info], None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.name.as_str(), "my-package");
+        assert_eq!(
+            metadata
+                .requires_dist
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["requests>=2"]
+        );
+    }
+}
+
+/// The error returned when an artifact does not match the sha256 hash published by the upstream
+/// index, see [`PackageDb::with_upstream_index`].
+#[derive(Debug, Clone, thiserror::Error, Diagnostic)]
+#[error("the sha256 hash of {filename} ({actual}) does not match the hash published by {upstream_index} ({expected}), the index it was downloaded from might have been tampered with")]
+pub struct UpstreamHashMismatch {
+    /// The artifact that failed verification
+    pub filename: ArtifactName,
+
+    /// The upstream index that publishes the expected hash
+    pub upstream_index: Url,
+
+    /// The hex encoded sha256 hash published by the upstream index
+    pub expected: String,
+
+    /// The hex encoded sha256 hash of the artifact, or the hash listed by the index it was
+    /// downloaded from
+    pub actual: String,
+}
+
+/// The error returned when strict verification against the upstream index is enabled and the
+/// upstream index does not publish a sha256 hash for an artifact, see
+/// [`PackageDb::with_strict_upstream_index`].
+#[derive(Debug, Clone, thiserror::Error, Diagnostic)]
+#[error(
+    "{filename} cannot be verified because {upstream_index} does not publish a sha256 hash for it"
+)]
+pub struct UpstreamHashMissing {
+    /// The artifact that could not be verified
+    pub filename: ArtifactName,
+
+    /// The upstream index that was checked
+    pub upstream_index: Url,
+}
+
+/// The hashes the upstream index publishes for a file, see [`PackageDb::with_upstream_index`].
+#[derive(Debug, Clone, Default)]
+struct UpstreamHashes {
+    sha256: Option<Sha256Hash>,
+    metadata_sha256: Option<Sha256Hash>,
+}
+
+/// Computes the sha256 hash of the contents of `reader`.
+fn sha256_of(reader: &mut impl Read) -> std::io::Result<Sha256Hash> {
+    let mut writer = rattler_digest::HashingWriter::<_, Sha256>::new(std::io::sink());
+    std::io::copy(reader, &mut writer)?;
+    Ok(writer.finalize().1)
+}
+
+/// The error returned when a downloaded artifact is still corrupt after downloading it again,
+/// e.g. because the server keeps sending a truncated file.
+#[derive(Debug, Clone, thiserror::Error, Diagnostic)]
+#[error("the download of {filename} is corrupt: {reason}")]
+#[diagnostic(help("the index or a proxy in between might be serving a broken file"))]
+pub struct CorruptArtifact {
+    /// The artifact that is corrupt
+    pub filename: ArtifactName,
+
+    /// What is wrong with the download
+    pub reason: String,
+}
+
+/// Performs a cheap check on the contents of an artifact to detect truncated downloads. The
+/// central directory of wheels must be readable, which is stored at the end of the file. Returns
+/// the reason if the check fails.
+fn check_artifact_integrity(
+    filename: &ArtifactName,
+    bytes: &mut Box<dyn ReadAndSeek + Send>,
+) -> Result<(), String> {
+    if filename.as_wheel().is_none() {
+        return Ok(());
     }
+    let result = zip::ZipArchive::new(&mut *bytes)
+        .map(|_| ())
+        .map_err(|err| format!("the zip archive is invalid ({err})"));
+    bytes.rewind().map_err(|err| err.to_string())?;
+    result
 }
 
 #[derive(Debug, Diagnostic)]
@@ -0,0 +1,131 @@
+//! Read-only access to the HTTP cache of pip.
+//!
+//! Many machines already have a warm pip cache. Pip (since version 23.3) stores the bodies of
+//! cached HTTP responses as separate files in the `http-v2` directory of its cache. The location of
+//! a response is derived from the sha224 hash of the requested url. This allows us to find the
+//! artifacts pip downloaded before without having to understand the rest of pip's cache format.
+//!
+//! Older versions of pip store responses in the `http` directory as a single msgpack encoded file.
+//! These entries are not supported.
+
+use rattler_digest::{compute_bytes_digest, compute_file_digest, Sha256, Sha256Hash};
+use sha2::Sha224;
+use std::path::{Path, PathBuf};
+use url::{Position, Url};
+
+/// The pip HTTP cache located in a directory on disk. See the [module](self) documentation for
+/// more information.
+#[derive(Debug, Clone)]
+pub struct PipCache {
+    root: PathBuf,
+}
+
+impl PipCache {
+    /// Constructs a new instance for the pip cache located at `root`. This is the directory pip
+    /// reports with `pip cache dir`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Locates the pip cache of the current user. The `PIP_CACHE_DIR` environment variable is
+    /// respected. Returns `None` if the directory does not exist.
+    pub fn from_env() -> Option<Self> {
+        let root = match std::env::var_os("PIP_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None if cfg!(windows) => dirs::cache_dir()?.join("pip").join("Cache"),
+            None => dirs::cache_dir()?.join("pip"),
+        };
+        root.is_dir().then(|| Self::new(root))
+    }
+
+    /// Returns the root directory of the cache.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path of the cached body of the response for the given `url` if it exists.
+    ///
+    /// Note that the content of the file is not verified, use [`Self::find_artifact`] to only
+    /// return files that match a known hash.
+    pub fn find_body(&self, url: &Url) -> Option<PathBuf> {
+        let key = format!("{:x}", compute_bytes_digest::<Sha224>(cache_url(url)));
+        let mut path = self.root.join("http-v2");
+        for c in key.chars().take(5) {
+            path.push(c.to_string());
+        }
+        path.push(format!("{key}.body"));
+        path.is_file().then_some(path)
+    }
+
+    /// Returns the path of the cached response for `url` if it exists and its content matches the
+    /// given sha256 hash.
+    pub fn find_artifact(&self, url: &Url, sha256: &Sha256Hash) -> Option<PathBuf> {
+        let path = self.find_body(url)?;
+        match compute_file_digest::<Sha256>(&path) {
+            Ok(hash) if &hash == sha256 => Some(path),
+            Ok(_) => {
+                tracing::debug!(
+                    "ignoring {} from the pip cache because the hash does not match",
+                    path.display()
+                );
+                None
+            }
+            Err(err) => {
+                tracing::debug!(
+                    "failed to read {} from the pip cache: {err}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Normalizes the url the same way pip does before it is used as a cache key. The fragment is
+/// removed but the query is retained.
+fn cache_url(url: &Url) -> &str {
+    &url[..Position::AfterQuery]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_artifact() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let url: Url =
+            "https://files.pythonhosted.org/packages/foo-1.0-py3-none-any.whl#sha256=abc"
+                .parse()
+                .unwrap();
+
+        // sha224 of the url without the fragment
+        let key = format!(
+            "{:x}",
+            compute_bytes_digest::<Sha224>(
+                "https://files.pythonhosted.org/packages/foo-1.0-py3-none-any.whl"
+            )
+        );
+        let dir = cache_dir
+            .path()
+            .join("http-v2")
+            .join(&key[0..1])
+            .join(&key[1..2])
+            .join(&key[2..3])
+            .join(&key[3..4])
+            .join(&key[4..5]);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{key}.body")), b"wheel contents").unwrap();
+
+        let cache = PipCache::new(cache_dir.path());
+        let sha256 = compute_bytes_digest::<Sha256>(b"wheel contents");
+        assert_eq!(
+            cache.find_artifact(&url, &sha256),
+            Some(dir.join(format!("{key}.body")))
+        );
+
+        // A different hash should not match
+        let other = compute_bytes_digest::<Sha256>(b"other contents");
+        assert_eq!(cache.find_artifact(&url, &other), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Artifact attestations as specified in [PEP 740](https://peps.python.org/pep-0740/).
+//!
+//! Indexes that support attestations link a provenance object from every file they have
+//! attestations for. The provenance groups the attestations by the trusted publisher (e.g. a
+//! GitHub Actions workflow) that produced them. Every attestation is a signed
+//! [in-toto statement](https://github.com/in-toto/attestation) whose subject is the artifact.
+//!
+//! [`check_provenance`] checks that the attestations are well-formed and that they claim to attest
+//! to the exact artifact, i.e. that the subject matches the filename and sha256 hash of the
+//! artifact. The Sigstore signatures and certificates of the attestations are *not* verified,
+//! which requires a Sigstore client and its trust root. An attestation that passes the check is
+//! therefore only [`AttestationStatus::Unverified`]: it detects indexes that serve other files than
+//! the ones that were attested, but anyone who controls the index can forge it.
+
+use crate::types::ArtifactInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// The content type of provenance objects.
+pub(crate) const PROVENANCE_CONTENT_TYPE: &str = "application/vnd.pypi.integrity.v1+json";
+
+/// The provenance of an artifact, a list of attestation bundles.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Provenance {
+    /// The version of the provenance object, currently always `1`
+    pub version: u32,
+
+    /// The attestations grouped by the publisher that produced them
+    pub attestation_bundles: Vec<AttestationBundle>,
+}
+
+/// The attestations of a single publisher.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AttestationBundle {
+    /// The trusted publisher that produced the attestations
+    pub publisher: Publisher,
+
+    /// The attestations
+    pub attestations: Vec<Attestation>,
+}
+
+/// A trusted publisher, e.g. a GitHub Actions workflow of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Publisher {
+    /// The kind of publisher, e.g. `GitHub` or `GitLab`
+    pub kind: String,
+
+    /// The claims that identify the publisher, which depend on the kind, e.g. `repository` and
+    /// `workflow` for GitHub
+    #[serde(flatten)]
+    pub claims: BTreeMap<String, serde_json::Value>,
+}
+
+impl Publisher {
+    /// Returns the value of a claim if it is a string, e.g. `repository`.
+    pub fn claim(&self, name: &str) -> Option<&str> {
+        self.claims.get(name)?.as_str()
+    }
+}
+
+impl Display for Publisher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.claim("repository") {
+            Some(repository) => write!(f, "{} ({repository})", self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// A single attestation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Attestation {
+    /// The version of the attestation, currently always `1`
+    pub version: u32,
+
+    /// The material to verify the signature, i.e. the signing certificate and the transparency log
+    /// entries
+    pub verification_material: serde_json::Value,
+
+    /// The signed statement
+    pub envelope: Envelope,
+}
+
+/// The envelope of an attestation that contains the statement and its signature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Envelope {
+    /// The base64 encoded in-toto statement
+    pub statement: String,
+
+    /// The base64 encoded signature over the statement
+    pub signature: String,
+}
+
+/// The parts of an in-toto statement that are checked.
+#[derive(Deserialize)]
+struct Statement {
+    #[serde(rename = "_type")]
+    statement_type: String,
+    subject: Vec<Subject>,
+    #[serde(rename = "predicateType")]
+    predicate_type: String,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    name: String,
+    digest: BTreeMap<String, String>,
+}
+
+/// The type of in-toto statements.
+const IN_TOTO_STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+
+/// The result of checking the attestations of an artifact, see [`check_provenance`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AttestationStatus {
+    /// The index does not publish attestations for the artifact.
+    NotPublished,
+
+    /// All attestations claim to attest to the artifact. Their signatures are not verified.
+    Unverified {
+        /// The publishers that produced the attestations
+        publishers: Vec<Publisher>,
+
+        /// The predicate types of the attestations, e.g. `https://docs.pypi.org/attestations/publish/v1`
+        predicate_types: Vec<String>,
+    },
+
+    /// The attestations are malformed or do not attest to the artifact.
+    Failed {
+        /// Why the check failed
+        reason: String,
+    },
+}
+
+/// Defines how attestations are used when artifacts are checked with
+/// [`crate::resolve::check_attestations`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AttestationPolicy {
+    /// Attestations are not fetched.
+    #[default]
+    Ignore,
+
+    /// Attestations are fetched and checked if the index publishes them. Artifacts without
+    /// attestations are accepted, artifacts with attestations that fail the check are not.
+    CheckIfPublished,
+}
+
+impl AttestationPolicy {
+    /// Returns true if an artifact with the given status is acceptable under this policy.
+    pub fn accepts(&self, status: &AttestationStatus) -> bool {
+        match self {
+            AttestationPolicy::Ignore => true,
+            AttestationPolicy::CheckIfPublished => {
+                !matches!(status, AttestationStatus::Failed { .. })
+            }
+        }
+    }
+}
+
+/// Checks that the attestations in `provenance` attest to `artifact_info`. Every attestation must
+/// contain an in-toto statement with a subject that has the filename and the sha256 hash of the
+/// artifact. The artifact must therefore have a known sha256 hash.
+pub fn check_provenance(
+    artifact_info: &ArtifactInfo,
+    provenance: &Provenance,
+) -> AttestationStatus {
+    match check_provenance_inner(artifact_info, provenance) {
+        Ok(status) => status,
+        Err(reason) => AttestationStatus::Failed { reason },
+    }
+}
+
+fn check_provenance_inner(
+    artifact_info: &ArtifactInfo,
+    provenance: &Provenance,
+) -> Result<AttestationStatus, String> {
+    if provenance.version != 1 {
+        return Err(format!(
+            "unsupported provenance version {}",
+            provenance.version
+        ));
+    }
+    let sha256 = artifact_info
+        .hashes
+        .as_ref()
+        .and_then(|hashes| hashes.sha256.as_ref())
+        .map(|sha256| format!("{sha256:x}"))
+        .ok_or_else(|| String::from("the sha256 hash of the artifact is unknown"))?;
+    let filename = artifact_info.filename.to_string();
+
+    let mut publishers = Vec::new();
+    let mut predicate_types = Vec::new();
+    for bundle in provenance.attestation_bundles.iter() {
+        if bundle.attestations.is_empty() {
+            return Err(format!("the bundle of {} is empty", bundle.publisher));
+        }
+        for attestation in bundle.attestations.iter() {
+            let statement = data_encoding::BASE64
+                .decode(attestation.envelope.statement.as_bytes())
+                .map_err(|err| format!("the statement is not valid base64: {err}"))?;
+            let statement: Statement = serde_json::from_slice(&statement)
+                .map_err(|err| format!("the statement is not a valid in-toto statement: {err}"))?;
+            if statement.statement_type != IN_TOTO_STATEMENT_TYPE {
+                return Err(format!(
+                    "unsupported statement type '{}'",
+                    statement.statement_type
+                ));
+            }
+
+            let attests_artifact = statement.subject.iter().any(|subject| {
+                subject.name == filename
+                    && subject
+                        .digest
+                        .get("sha256")
+                        .map_or(false, |digest| digest.eq_ignore_ascii_case(&sha256))
+            });
+            if !attests_artifact {
+                return Err(format!(
+                    "an attestation of {} does not attest to {filename} with sha256 {sha256}",
+                    bundle.publisher
+                ));
+            }
+
+            if !predicate_types.contains(&statement.predicate_type) {
+                predicate_types.push(statement.predicate_type);
+            }
+        }
+        publishers.push(bundle.publisher.clone());
+    }
+
+    if publishers.is_empty() {
+        return Err(String::from(
+            "the provenance does not contain any attestations",
+        ));
+    }
+
+    Ok(AttestationStatus::Unverified {
+        publishers,
+        predicate_types,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{ArtifactHashes, NormalizedPackageName};
+
+    const SHA256: &str = "e5c7ffac26fed654fe62045898f55b551a0dc120badf3d116bcd364418f3ec16";
+
+    fn artifact_info() -> ArtifactInfo {
+        let name: NormalizedPackageName = "click".parse().unwrap();
+        ArtifactInfo {
+            hashes: Some(ArtifactHashes {
+                sha256: rattler_digest::parse_digest_from_hex::<rattler_digest::Sha256>(SHA256),
+            }),
+            ..ArtifactInfo::for_test(&name, "click-8.1.7-py3-none-any.whl")
+        }
+    }
+
+    fn provenance(subject_name: &str, subject_sha256: &str) -> Provenance {
+        let statement = serde_json::json!({
+            "_type": IN_TOTO_STATEMENT_TYPE,
+            "subject": [{"name": subject_name, "digest": {"sha256": subject_sha256}}],
+            "predicateType": "https://docs.pypi.org/attestations/publish/v1",
+            "predicate": null,
+        });
+        serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "attestation_bundles": [{
+                "publisher": {
+                    "kind": "GitHub",
+                    "repository": "pallets/click",
+                    "workflow": "publish.yaml",
+                },
+                "attestations": [{
+                    "version": 1,
+                    "verification_material": {"certificate": "", "transparency_entries": []},
+                    "envelope": {
+                        "statement": data_encoding::BASE64.encode(statement.to_string().as_bytes()),
+                        "signature": "",
+                    },
+                }],
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_provenance() {
+        let artifact_info = artifact_info();
+
+        let status = check_provenance(
+            &artifact_info,
+            &provenance("click-8.1.7-py3-none-any.whl", SHA256),
+        );
+        let AttestationStatus::Unverified {
+            publishers,
+            predicate_types,
+        } = &status
+        else {
+            panic!("expected the attestations to match: {status:?}");
+        };
+        assert_eq!(publishers[0].to_string(), "GitHub (pallets/click)");
+        assert_eq!(publishers[0].claim("workflow"), Some("publish.yaml"));
+        assert_eq!(
+            predicate_types,
+            &["https://docs.pypi.org/attestations/publish/v1"]
+        );
+        assert!(AttestationPolicy::CheckIfPublished.accepts(&status));
+
+        // An attestation for another artifact fails
+        let status = check_provenance(
+            &artifact_info,
+            &provenance("click-8.1.6-py3-none-any.whl", SHA256),
+        );
+        assert!(matches!(status, AttestationStatus::Failed { .. }));
+        assert!(!AttestationPolicy::CheckIfPublished.accepts(&status));
+        assert!(AttestationPolicy::Ignore.accepts(&status));
+
+        // So does an attestation with another hash
+        let other_sha256 = SHA256.replace('e', "f");
+        let status = check_provenance(
+            &artifact_info,
+            &provenance("click-8.1.7-py3-none-any.whl", &other_sha256),
+        );
+        assert!(matches!(status, AttestationStatus::Failed { .. }));
+
+        // Artifacts without attestations are accepted
+        let status = AttestationStatus::NotPublished;
+        assert!(AttestationPolicy::CheckIfPublished.accepts(&status));
+    }
+}
@@ -0,0 +1,466 @@
+//! A caching proxy that serves the indexes and caches of a [`PackageDb`] as a simple index over
+//! HTTP.
+//!
+//! This allows multiple machines or containers on a local network to share one warm cache: point
+//! their index url at the proxy and every artifact is only downloaded from the upstream indexes
+//! once. The proxy serves the following pages:
+//!
+//! * `/simple/<project>/`: a [PEP 503](https://peps.python.org/pep-0503/) project page with the
+//!   artifacts that the [`PackageDb`] finds for the project on its indexes.
+//! * `/files/<project>/<filename>`: an artifact, read from the caches of the [`PackageDb`] or
+//!   downloaded (and cached) if it is not available locally.
+//!
+//! The root page `/simple/` does not list any projects. Metadata files
+//! ([PEP 658](https://peps.python.org/pep-0658/)) are not served, clients download the artifacts
+//! instead.
+//!
+//! This module is only available when the `proxy` feature is enabled.
+
+use super::PackageDb;
+use crate::types::{Artifact, ArtifactInfo, ArtifactName, NormalizedPackageName};
+use crate::utils::ReadAndSeek;
+use std::fmt::Write as _;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Take};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use url::Url;
+
+/// A server that serves the artifacts of a [`PackageDb`] as a simple index, see the [module
+/// documentation](self).
+pub struct CacheProxy {
+    listener: TcpListener,
+    package_db: Arc<PackageDb>,
+}
+
+impl CacheProxy {
+    /// Binds the proxy to the given address, e.g. `0.0.0.0:8080` to serve all machines on the
+    /// network. Requests are only handled once [`Self::serve`] is called.
+    pub async fn bind(package_db: Arc<PackageDb>, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            package_db,
+        })
+    }
+
+    /// Returns the address the proxy is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns the url of the simple index served by the proxy on this machine.
+    pub fn url(&self) -> io::Result<Url> {
+        Ok(
+            Url::parse(&format!("http://{}/simple/", self.local_addr()?))
+                .expect("must be a valid url"),
+        )
+    }
+
+    /// Handles requests until the listener fails.
+    pub async fn serve(self) -> io::Result<()> {
+        loop {
+            let (stream, peer) = self.listener.accept().await?;
+            let package_db = self.package_db.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle_connection(stream, &package_db).await {
+                    tracing::debug!("cache proxy failed to handle connection from {peer}: {err}");
+                }
+            });
+        }
+    }
+}
+
+/// The body of a response.
+enum Body {
+    Bytes(Vec<u8>),
+    Artifact(Box<dyn ReadAndSeek + Send>),
+}
+
+/// A response that is sent back to the client.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Body,
+}
+
+impl Response {
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: Body::Bytes(body.into().into_bytes()),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self::text("404 Not Found", "not found")
+    }
+}
+
+/// The maximum size of the request line and headers of a request.
+const MAX_REQUEST_HEAD_SIZE: u64 = 16 * 1024;
+
+/// How long a client may take to send the request line and headers.
+const REQUEST_HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The size of the chunks in which artifacts are sent.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+async fn handle_connection(stream: TcpStream, package_db: &PackageDb) -> io::Result<()> {
+    // Bound the size of the request head so a client cannot make us buffer an endless line
+    let mut stream = BufReader::new(stream.take(MAX_REQUEST_HEAD_SIZE));
+    let head = tokio::time::timeout(REQUEST_HEAD_TIMEOUT, read_request_head(&mut stream))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out reading the request"))?;
+
+    let (method, response) = match head? {
+        RequestHead::Complete { method, target } => {
+            let response = if method != "GET" && method != "HEAD" {
+                Response::text("405 Method Not Allowed", "method not allowed")
+            } else {
+                route(package_db, &target).await.unwrap_or_else(|err| {
+                    tracing::warn!("cache proxy failed to serve {target}: {err:?}");
+                    Response::text("502 Bad Gateway", format!("{err}"))
+                })
+            };
+            (method, response)
+        }
+        RequestHead::TooLarge => (
+            String::from("GET"),
+            Response::text(
+                "431 Request Header Fields Too Large",
+                "request header fields too large",
+            ),
+        ),
+        RequestHead::Empty => return Ok(()),
+    };
+
+    // Write the response. Artifacts are read from files, which blocks, so that happens on the
+    // blocking thread pool.
+    let stream = stream.get_mut().get_mut();
+    let (content_length, body) = match response.body {
+        Body::Bytes(bytes) => (bytes.len() as u64, Body::Bytes(bytes)),
+        Body::Artifact(reader) => {
+            let (reader, content_length) = blocking(move || {
+                let mut reader = reader;
+                let content_length = reader.seek(SeekFrom::End(0))?;
+                reader.rewind()?;
+                Ok((reader, content_length))
+            })
+            .await?;
+            (content_length, Body::Artifact(reader))
+        }
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {content_length}\r\nConnection: close\r\n\r\n",
+        response.status, response.content_type
+    );
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" {
+        match body {
+            Body::Bytes(bytes) => stream.write_all(&bytes).await?,
+            Body::Artifact(mut reader) => {
+                let mut buffer = vec![0; CHUNK_SIZE];
+                loop {
+                    let read;
+                    (reader, buffer, read) = blocking(move || {
+                        let (mut reader, mut buffer) = (reader, buffer);
+                        let read = reader.read(&mut buffer)?;
+                        Ok((reader, buffer, read))
+                    })
+                    .await?;
+                    if read == 0 {
+                        break;
+                    }
+                    stream.write_all(&buffer[..read]).await?;
+                }
+            }
+        }
+    }
+    stream.flush().await?;
+    stream.shutdown().await
+}
+
+/// The request line and headers of a request.
+enum RequestHead {
+    /// The client closed the connection without sending a request.
+    Empty,
+
+    /// The request head exceeds [`MAX_REQUEST_HEAD_SIZE`].
+    TooLarge,
+
+    /// A complete request head.
+    Complete { method: String, target: String },
+}
+
+/// Reads the request line, e.g. `GET /simple/flask/ HTTP/1.1`, and skips the headers.
+async fn read_request_head(stream: &mut BufReader<Take<TcpStream>>) -> io::Result<RequestHead> {
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+    if !request_line.ends_with('\n') {
+        return Ok(if stream.get_ref().limit() == 0 {
+            RequestHead::TooLarge
+        } else {
+            RequestHead::Empty
+        });
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(RequestHead::Empty);
+    };
+    let head = RequestHead::Complete {
+        method: method.to_owned(),
+        target: target.to_owned(),
+    };
+
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await?;
+        if !line.ends_with('\n') {
+            return Ok(if stream.get_ref().limit() == 0 {
+                RequestHead::TooLarge
+            } else {
+                RequestHead::Empty
+            });
+        }
+        if line.trim_end().is_empty() {
+            return Ok(head);
+        }
+    }
+}
+
+/// Runs blocking file io on the blocking thread pool.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+}
+
+/// Determines the response for the requested target.
+async fn route(package_db: &PackageDb, target: &str) -> miette::Result<Response> {
+    // Strip any query or fragment from the target
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    match segments.as_slice() {
+        ["simple"] => Ok(Response {
+            status: "200 OK",
+            content_type: "text/html",
+            body: Body::Bytes(root_page_html().into_bytes()),
+        }),
+        ["simple", project] => {
+            let Ok(name) = NormalizedPackageName::from_str(project) else {
+                return Ok(Response::not_found());
+            };
+            let artifacts = package_db.available_artifacts(name.clone()).await?;
+            if artifacts.is_empty() {
+                return Ok(Response::not_found());
+            }
+            Ok(Response {
+                status: "200 OK",
+                content_type: "text/html",
+                body: Body::Bytes(
+                    project_page_html(&name, artifacts.values().flatten()).into_bytes(),
+                ),
+            })
+        }
+        ["files", project, filename] => {
+            let Ok(name) = NormalizedPackageName::from_str(project) else {
+                return Ok(Response::not_found());
+            };
+            let artifacts = package_db.available_artifacts(name).await?;
+            let Some(artifact_info) = artifacts
+                .values()
+                .flatten()
+                .find(|artifact| artifact.filename.to_string() == *filename)
+            else {
+                return Ok(Response::not_found());
+            };
+            let artifact = package_db
+                .get_artifact::<RawArtifact>(artifact_info)
+                .await?;
+            Ok(Response {
+                status: "200 OK",
+                content_type: "application/octet-stream",
+                body: Body::Artifact(artifact.bytes),
+            })
+        }
+        _ => Ok(Response::not_found()),
+    }
+}
+
+fn root_page_html() -> String {
+    String::from(
+        "<!DOCTYPE html>\n<html>\n  <head>\n    <meta name=\"pypi:repository-version\" content=\"1.0\">\n    <title>Simple index</title>\n  </head>\n  <body>\n  </body>\n</html>\n",
+    )
+}
+
+fn project_page_html<'a>(
+    project_name: &NormalizedPackageName,
+    artifacts: impl Iterator<Item = &'a ArtifactInfo>,
+) -> String {
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(html, "<html>").unwrap();
+    writeln!(html, "  <head>").unwrap();
+    writeln!(
+        html,
+        "    <meta name=\"pypi:repository-version\" content=\"1.0\">"
+    )
+    .unwrap();
+    writeln!(html, "    <title>Links for {}</title>", project_name).unwrap();
+    writeln!(html, "  </head>").unwrap();
+    writeln!(html, "  <body>").unwrap();
+    writeln!(html, "    <h1>Links for {}</h1>", project_name).unwrap();
+    for artifact in artifacts {
+        let file_name = artifact.filename.to_string();
+        let href = format!("/files/{}/{file_name}", project_name.as_str());
+        write!(
+            html,
+            "    <a href=\"{}",
+            html_escape::encode_double_quoted_attribute(&href)
+        )
+        .unwrap();
+        if let Some(sha256) = artifact
+            .hashes
+            .as_ref()
+            .and_then(|hashes| hashes.sha256.as_ref())
+        {
+            write!(html, "#sha256={sha256:x}").unwrap();
+        }
+        write!(html, "\"").unwrap();
+        if let Some(requires_python) = &artifact.requires_python_raw {
+            write!(
+                html,
+                " data-requires-python=\"{}\"",
+                html_escape::encode_double_quoted_attribute(requires_python)
+            )
+            .unwrap();
+        }
+        if artifact.yanked.yanked {
+            write!(
+                html,
+                " data-yanked=\"{}\"",
+                html_escape::encode_double_quoted_attribute(
+                    artifact.yanked.reason.as_deref().unwrap_or_default()
+                )
+            )
+            .unwrap();
+        }
+        writeln!(html, ">{}</a><br/>", html_escape::encode_text(&file_name)).unwrap();
+    }
+    writeln!(html, "  </body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+    html
+}
+
+/// An artifact of any kind of which only the bytes are used.
+struct RawArtifact {
+    name: ArtifactName,
+    bytes: Box<dyn ReadAndSeek + Send>,
+}
+
+impl Artifact for RawArtifact {
+    type Name = ArtifactName;
+
+    fn new(name: Self::Name, bytes: Box<dyn ReadAndSeek + Send>) -> miette::Result<Self> {
+        Ok(Self { name, bytes })
+    }
+
+    fn name(&self) -> &Self::Name {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::artifacts::Wheel;
+    use crate::index::test_server::TestIndex;
+    use crate::types::PackageName;
+    use reqwest::Client;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cache_proxy() {
+        let TestIndex {
+            server: upstream,
+            package_db,
+            index_dir: _index_dir,
+            cache_dir: _cache_dir,
+        } = TestIndex::start().await;
+
+        // Start a proxy in front of the upstream index
+        let proxy = CacheProxy::bind(Arc::new(package_db), "127.0.0.1:0")
+            .await
+            .unwrap();
+        let proxy_url = proxy.url().unwrap();
+        tokio::spawn(proxy.serve());
+
+        // A client of the proxy sees the same artifacts as the upstream index
+        let client_cache_dir = tempfile::tempdir().unwrap();
+        let client_db =
+            PackageDb::new(Client::new(), &[proxy_url.clone()], client_cache_dir.path()).unwrap();
+        let name = "miniblack".parse::<PackageName>().unwrap();
+        let artifacts = client_db.available_artifacts(name).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        assert_eq!(
+            artifact_info.filename.to_string(),
+            "miniblack-23.1.0-py3-none-any.whl"
+        );
+        assert!(artifact_info
+            .url
+            .as_str()
+            .starts_with(proxy_url.join("/files/miniblack/").unwrap().as_str()));
+
+        // The artifact is served by the proxy and the hash matches
+        client_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+        let requests = upstream.request_count();
+
+        // A second client is served from the cache of the proxy
+        let client_cache_dir = tempfile::tempdir().unwrap();
+        let client_db =
+            PackageDb::new(Client::new(), &[proxy_url.clone()], client_cache_dir.path()).unwrap();
+        let name = "miniblack".parse::<PackageName>().unwrap();
+        let artifacts = client_db.available_artifacts(name).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        client_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+        assert_eq!(upstream.request_count(), requests);
+
+        // Unknown projects are not found
+        let response = Client::new()
+            .get(proxy_url.join("unknown/").unwrap())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+
+        // Requests with an endless request line are cut off. Exactly the maximum is sent so the
+        // connection is not reset because of unread data.
+        let mut stream = TcpStream::connect(proxy_url.socket_addrs(|| None).unwrap()[0])
+            .await
+            .unwrap();
+        let request = format!("GET /{}", "a".repeat(MAX_REQUEST_HEAD_SIZE as usize - 5));
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 431"),
+            "unexpected response: {response}"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Politeness towards servers: retrying throttled requests and limiting the download bandwidth.
+//!
+//! Servers (and proxies in front of them) signal that a client sends too many requests with a
+//! `429 Too Many Requests` or `503 Service Unavailable` response, optionally with a `Retry-After`
+//! header that tells the client how long to wait. Ignoring that header is a good way to get banned
+//! on shared CI runners, so these responses are retried after the requested delay.
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use http::header::RETRY_AFTER;
+use http::{HeaderMap, StatusCode};
+use parking_lot::Mutex;
+use std::num::NonZeroU64;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Determines how requests that are throttled by the server are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is retried. `0` disables retrying.
+    pub max_retries: u32,
+
+    /// The longest time to wait before retrying a request. If the server asks to wait longer than
+    /// this with a `Retry-After` header the request is not retried and the response is returned
+    /// as is.
+    pub max_delay: Duration,
+
+    /// The delay before the first retry if the server did not send a `Retry-After` header. The
+    /// delay doubles for every subsequent retry.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            max_delay: Duration::from_secs(60),
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries requests.
+    pub fn never() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Returns how long to wait before retrying a request that was answered with `status` and
+    /// `headers`, or `None` if the request should not be retried. `attempt` is the number of
+    /// retries that were already done.
+    pub(crate) fn retry_delay(
+        &self,
+        attempt: u32,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Option<Duration> {
+        if attempt >= self.max_retries
+            || !matches!(
+                status,
+                StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+            )
+        {
+            return None;
+        }
+
+        match parse_retry_after(headers, SystemTime::now()) {
+            Some(delay) if delay > self.max_delay => {
+                tracing::warn!(
+                    "the server asked to retry after {}s which is longer than the maximum of {}s, giving up",
+                    delay.as_secs(),
+                    self.max_delay.as_secs()
+                );
+                None
+            }
+            Some(delay) => Some(delay),
+            None => Some(
+                self.backoff
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(self.max_delay),
+            ),
+        }
+    }
+}
+
+/// Parses the `Retry-After` header, which is either a number of seconds or an HTTP date, into the
+/// duration to wait from `now`. Dates in the past result in a duration of zero.
+pub(crate) fn parse_retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let date = SystemTime::UNIX_EPOCH + Duration::from_secs(u64::try_from(date.timestamp()).ok()?);
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Limits the rate at which bytes are read from response bodies. The limit is shared by all
+/// clones, so it applies to the total bandwidth of all concurrent downloads.
+#[derive(Debug, Clone)]
+pub(crate) struct BandwidthLimiter {
+    bytes_per_second: NonZeroU64,
+
+    /// The moment at which the bytes that were already consumed have been "transferred" at the
+    /// configured rate.
+    available_at: Arc<Mutex<Instant>>,
+}
+
+impl BandwidthLimiter {
+    /// Constructs a limiter that allows `bytes_per_second` bytes per second.
+    pub(crate) fn new(bytes_per_second: NonZeroU64) -> Self {
+        Self {
+            bytes_per_second,
+            available_at: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Reserves `bytes` bytes and returns how long the caller has to wait before it may use them.
+    fn reserve(&self, bytes: u64, now: Instant) -> Duration {
+        let transfer_time =
+            Duration::from_secs_f64(bytes as f64 / self.bytes_per_second.get() as f64);
+        let mut available_at = self.available_at.lock();
+        let start = (*available_at).max(now);
+        *available_at = start + transfer_time;
+        start - now
+    }
+
+    /// Waits until `bytes` bytes may be used.
+    async fn consume(&self, bytes: u64) {
+        let delay = self.reserve(bytes, Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Wraps a stream of response body chunks so it is read at the limited rate.
+    pub(crate) fn throttle<E: Send + 'static>(
+        &self,
+        stream: impl Stream<Item = Result<Bytes, E>> + Send + 'static,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, E>> + Send>> {
+        let limiter = self.clone();
+        Box::pin(stream.then(move |chunk| {
+            let limiter = limiter.clone();
+            async move {
+                if let Ok(bytes) = &chunk {
+                    limiter.consume(bytes.len() as u64).await;
+                }
+                chunk
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http::HeaderValue;
+
+    fn retry_after(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        // Wed, 21 Oct 2015 07:28:00 GMT
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1445412480);
+
+        assert_eq!(
+            parse_retry_after(&retry_after("120"), now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after(&retry_after("Wed, 21 Oct 2015 07:28:30 GMT"), now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after(&retry_after("Wed, 21 Oct 2015 07:00:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after(&retry_after("soon"), now), None);
+        assert_eq!(parse_retry_after(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+        let no_header = HeaderMap::new();
+
+        // Only throttling responses are retried
+        assert_eq!(
+            policy.retry_delay(0, StatusCode::NOT_FOUND, &no_header),
+            None
+        );
+        assert_eq!(
+            policy.retry_delay(0, StatusCode::TOO_MANY_REQUESTS, &retry_after("5")),
+            Some(Duration::from_secs(5))
+        );
+
+        // Without a header the delay backs off exponentially
+        assert_eq!(
+            policy.retry_delay(2, StatusCode::SERVICE_UNAVAILABLE, &no_header),
+            Some(Duration::from_secs(4))
+        );
+
+        // Requests are not retried indefinitely or for too long
+        assert_eq!(
+            policy.retry_delay(3, StatusCode::TOO_MANY_REQUESTS, &no_header),
+            None
+        );
+        assert_eq!(
+            policy.retry_delay(0, StatusCode::TOO_MANY_REQUESTS, &retry_after("3600")),
+            None
+        );
+        assert_eq!(
+            RetryPolicy::never().retry_delay(0, StatusCode::TOO_MANY_REQUESTS, &no_header),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bandwidth_limiter() {
+        let limiter = BandwidthLimiter::new(NonZeroU64::new(1000).unwrap());
+        let now = Instant::now();
+
+        // The first chunk can be used immediately, subsequent chunks have to wait until the
+        // previous chunks have been transferred at the limited rate.
+        assert_eq!(limiter.reserve(500, now), Duration::ZERO);
+        assert_eq!(limiter.reserve(1000, now), Duration::from_millis(500));
+
+        // The limit is shared between clones
+        assert_eq!(limiter.clone().reserve(1, now), Duration::from_millis(1500));
+
+        // Idle time is not saved up
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(1000, later), Duration::ZERO);
+    }
+}
@@ -0,0 +1,549 @@
+//! A small in-process HTTP server that serves a simple index from a directory on disk.
+//!
+//! This is useful to write deterministic integration tests that do not rely on the public PyPI
+//! index. The directory can be populated by hand or by using [`super::generate::generate_index`].
+//! The server can be configured to misbehave in a number of ways (latency, authentication, rate
+//! limiting and broken html) to test how clients deal with that.
+//!
+//! This module is only available when the `test-server` feature is enabled, it is always
+//! available to the tests of this crate.
+
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use url::Url;
+
+#[cfg(test)]
+use super::PackageDb;
+
+/// Options to configure the behavior of a [`TestIndexServer`].
+#[derive(Debug, Clone, Default)]
+pub struct TestIndexServerOptions {
+    /// Artificial latency that is added before every response.
+    pub latency: Option<Duration>,
+
+    /// If specified the server requires basic authentication with the given username and password.
+    pub basic_auth: Option<(String, String)>,
+
+    /// The number of requests that are answered with a `429 Too Many Requests` before the server
+    /// starts responding normally.
+    pub too_many_requests: usize,
+
+    /// When `true` all html pages are served truncated and without closing tags.
+    pub broken_html: bool,
+}
+
+/// An HTTP server running in the background that serves the files from a directory as a simple
+/// index. The server is stopped when this instance is dropped.
+pub struct TestIndexServer {
+    addr: SocketAddr,
+    requests: Arc<AtomicUsize>,
+    handle: JoinHandle<()>,
+}
+
+struct ServerState {
+    root: PathBuf,
+    options: TestIndexServerOptions,
+    requests: Arc<AtomicUsize>,
+}
+
+impl TestIndexServer {
+    /// Starts a new server on a random local port that serves the files in `root`.
+    pub async fn start(
+        root: impl Into<PathBuf>,
+        options: TestIndexServerOptions,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let state = Arc::new(ServerState {
+            root: root.into(),
+            options,
+            requests: requests.clone(),
+        });
+
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, &state).await {
+                        tracing::debug!("test index server failed to handle connection: {err}");
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            requests,
+            handle,
+        })
+    }
+
+    /// Returns the url of the root of the index.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/", self.addr)).expect("must be a valid url")
+    }
+
+    /// Returns the number of requests the server received so far.
+    pub fn request_count(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for TestIndexServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// An index generated from the wheels in `test-data/wheels` that is served by a
+/// [`TestIndexServer`], together with a [`PackageDb`] that uses it. Shared by the tests of this
+/// crate.
+#[cfg(test)]
+pub(crate) struct TestIndex {
+    pub server: TestIndexServer,
+    pub package_db: PackageDb,
+    pub index_dir: tempfile::TempDir,
+    pub cache_dir: tempfile::TempDir,
+}
+
+#[cfg(test)]
+impl TestIndex {
+    /// Starts a server with default options.
+    pub async fn start() -> Self {
+        Self::start_with_options(Default::default()).await
+    }
+
+    /// Starts a server with the given options.
+    pub async fn start_with_options(options: TestIndexServerOptions) -> Self {
+        let index_dir = tempfile::tempdir().unwrap();
+        super::generate::generate_index(&test_wheels_dir(), index_dir.path()).unwrap();
+        let server = TestIndexServer::start(index_dir.path(), options)
+            .await
+            .unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db =
+            PackageDb::new(reqwest::Client::new(), &[server.url()], cache_dir.path()).unwrap();
+        Self {
+            server,
+            package_db,
+            index_dir,
+            cache_dir,
+        }
+    }
+}
+
+/// Returns the directory that contains the wheels the [`TestIndex`] is generated from.
+#[cfg(test)]
+pub(crate) fn test_wheels_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/wheels")
+}
+
+/// A response that is sent back to the client.
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", String::from("text/plain"))],
+            body: body.into(),
+        }
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: &ServerState) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+
+    // Read the request line, e.g. `GET /simple/ HTTP/1.1`
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(());
+    };
+    let method = method.to_owned();
+    let target = target.to_owned();
+
+    // Read the headers
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_owned());
+            }
+        }
+    }
+
+    let request_idx = state.requests.fetch_add(1, Ordering::SeqCst);
+    if let Some(latency) = state.options.latency {
+        tokio::time::sleep(latency).await;
+    }
+
+    let response = if request_idx < state.options.too_many_requests {
+        let mut response = Response::new("429 Too Many Requests", "too many requests");
+        response.headers.push(("Retry-After", String::from("0")));
+        response
+    } else if !is_authorized(&state.options, authorization.as_deref()) {
+        let mut response = Response::new("401 Unauthorized", "unauthorized");
+        response
+            .headers
+            .push(("WWW-Authenticate", String::from("Basic realm=\"test\"")));
+        response
+    } else if method != "GET" && method != "HEAD" {
+        Response::new("405 Method Not Allowed", "method not allowed")
+    } else {
+        serve_file(state, &target).await
+    };
+
+    // Write the response
+    let stream = stream.get_mut();
+    let mut head = format!("HTTP/1.1 {}\r\n", response.status);
+    for (name, value) in response.headers.iter() {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" {
+        stream.write_all(&response.body).await?;
+    }
+    stream.flush().await?;
+    stream.shutdown().await
+}
+
+/// Returns true if the request is authorized according to the options of the server.
+fn is_authorized(options: &TestIndexServerOptions, authorization: Option<&str>) -> bool {
+    let Some((username, password)) = &options.basic_auth else {
+        return true;
+    };
+    let expected = data_encoding::BASE64.encode(format!("{username}:{password}").as_bytes());
+    authorization
+        .and_then(|value| value.strip_prefix("Basic "))
+        .map_or(false, |value| value.trim() == expected)
+}
+
+/// Finds the file that is requested by `target` and returns it as a response.
+async fn serve_file(state: &ServerState, target: &str) -> Response {
+    // Strip any query or fragment from the target
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+
+    // Make sure we never serve anything outside of the root directory.
+    let relative_path = Path::new(path.trim_start_matches('/'));
+    if relative_path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Response::new("404 Not Found", "not found");
+    }
+
+    let mut file_path = state.root.join(relative_path);
+    if path.ends_with('/') || file_path.is_dir() {
+        file_path = file_path.join("index.html");
+    }
+
+    let body = match tokio::fs::read(&file_path).await {
+        Ok(body) => body,
+        Err(_) => return Response::new("404 Not Found", "not found"),
+    };
+
+    let (content_type, body) = match file_path.extension().and_then(|e| e.to_str()) {
+        Some("html") if state.options.broken_html => {
+            // Cut the page in half, this leaves unclosed and half written tags.
+            let body = body[..body.len() / 2].to_vec();
+            ("text/html", body)
+        }
+        Some("html") => ("text/html", body),
+        Some("json") => ("application/vnd.pypi.simple.v1+json", body),
+        _ => ("application/octet-stream", body),
+    };
+
+    Response {
+        status: "200 OK",
+        headers: vec![("Content-Type", String::from(content_type))],
+        body,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::artifacts::Wheel;
+    use crate::index::{
+        CacheCounter, CorruptArtifact, RetryPolicy, UpstreamHashMismatch, UpstreamHashMissing,
+    };
+    use crate::types::{ArtifactInfo, PackageName};
+    use reqwest::Client;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_available_artifacts() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+
+        let artifacts = package_db
+            .available_artifacts("miniblack".parse::<PackageName>().unwrap())
+            .await
+            .unwrap();
+        let artifact_info = artifacts
+            .iter()
+            .flat_map(|(_, artifacts)| artifacts.iter())
+            .collect::<Vec<_>>();
+        assert_eq!(artifact_info.len(), 1);
+
+        let (_, metadata) = package_db
+            .get_metadata(&artifact_info, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.name.as_str(), "miniblack");
+
+        // Nothing was cached yet
+        let stats = package_db.cache_statistics();
+        assert_eq!(stats.index_pages, CacheCounter { hits: 0, misses: 1 });
+        assert_eq!(stats.metadata, CacheCounter { hits: 0, misses: 1 });
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_compute_missing_sha256() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+
+        let artifacts = package_db
+            .available_artifacts("miniblack".parse::<PackageName>().unwrap())
+            .await
+            .unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        let expected = artifact_info.hashes.clone().unwrap().sha256.unwrap();
+
+        // Artifacts that already have a hash are returned as is
+        let hashed = package_db.with_sha256(artifact_info).await.unwrap();
+        assert!(std::ptr::eq(hashed, artifact_info));
+
+        // Otherwise the hash is computed from the downloaded artifact
+        let without_hash = ArtifactInfo {
+            hashes: None,
+            ..artifact_info.clone()
+        };
+        let hashed = package_db.with_sha256(&without_hash).await.unwrap();
+        assert_eq!(hashed.hashes.as_ref().unwrap().sha256, Some(expected));
+        assert_eq!(hashed.url, artifact_info.url);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_verify_mirror_against_upstream() {
+        let upstream = TestIndex::start().await;
+        let mirror = TestIndex::start().await;
+        let name = "miniblack".parse::<PackageName>().unwrap();
+
+        // An intact mirror passes verification
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[mirror.server.url()], cache_dir.path())
+            .unwrap()
+            .with_upstream_index(upstream.server.url());
+        let artifacts = package_db.available_artifacts(name.clone()).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+
+        // Replace the wheel on the mirror with another file
+        std::fs::copy(
+            test_wheels_dir().join("wordle_python-2.3.32-py3-none-any.whl"),
+            mirror
+                .index_dir
+                .path()
+                .join("miniblack/miniblack-23.1.0-py3-none-any.whl"),
+        )
+        .unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[mirror.server.url()], cache_dir.path())
+            .unwrap()
+            .with_upstream_index(upstream.server.url());
+        let artifacts = package_db.available_artifacts(name).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        let err = package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap_err();
+        let mismatch = err.downcast_ref::<UpstreamHashMismatch>().unwrap();
+        assert_eq!(
+            Some(mismatch.expected.clone()),
+            artifact_info
+                .hashes
+                .as_ref()
+                .and_then(|hashes| hashes.sha256.as_ref())
+                .map(|sha256| format!("{sha256:x}"))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_strict_upstream_index() {
+        let upstream = TestIndex::start().await;
+        let mirror = TestIndex::start().await;
+        let name = "miniblack".parse::<PackageName>().unwrap();
+
+        // Download the wheel once without verification so it is cached
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db =
+            PackageDb::new(Client::new(), &[mirror.server.url()], cache_dir.path()).unwrap();
+        let artifacts = package_db.available_artifacts(name.clone()).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+
+        // The package does not exist upstream, which is only logged unless verification is strict
+        std::fs::remove_dir_all(upstream.index_dir.path().join("miniblack")).unwrap();
+        let package_db = PackageDb::new(Client::new(), &[mirror.server.url()], cache_dir.path())
+            .unwrap()
+            .with_upstream_index(upstream.server.url());
+        let artifacts = package_db.available_artifacts(name.clone()).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+
+        // Cached artifacts and their metadata are verified as well
+        let package_db = PackageDb::new(Client::new(), &[mirror.server.url()], cache_dir.path())
+            .unwrap()
+            .with_upstream_index(upstream.server.url())
+            .with_strict_upstream_index(true);
+        let artifacts = package_db.available_artifacts(name).await.unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        let err = package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<UpstreamHashMissing>().is_some());
+        let err = package_db
+            .get_metadata(&[artifact_info], None)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<UpstreamHashMissing>().is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_redownload_truncated_wheel() {
+        let TestIndex {
+            server,
+            package_db,
+            index_dir,
+            cache_dir: _cache_dir,
+        } = TestIndex::start().await;
+        let artifacts = package_db
+            .available_artifacts("miniblack".parse::<PackageName>().unwrap())
+            .await
+            .unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+
+        // Serve a truncated wheel
+        let wheel_path = index_dir
+            .path()
+            .join("miniblack/miniblack-23.1.0-py3-none-any.whl");
+        let wheel = std::fs::read(&wheel_path).unwrap();
+        std::fs::write(&wheel_path, &wheel[..wheel.len() / 2]).unwrap();
+
+        // The wheel is downloaded twice before giving up
+        let requests = server.request_count();
+        let err = package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<CorruptArtifact>().is_some());
+        assert_eq!(server.request_count(), requests + 2);
+
+        // Once the server is fixed the wheel is downloaded again instead of read from the cache
+        std::fs::write(&wheel_path, &wheel).unwrap();
+        package_db
+            .get_artifact::<Wheel>(artifact_info)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let index = TestIndex::start_with_options(TestIndexServerOptions {
+            basic_auth: Some((String::from("user"), String::from("pass"))),
+            ..Default::default()
+        })
+        .await;
+
+        let client = Client::new();
+        let response = client.get(index.server.url()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+
+        let response = client
+            .get(index.server.url())
+            .basic_auth("user", Some("pass"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retry_throttled_requests() {
+        let index = TestIndex::start_with_options(TestIndexServerOptions {
+            too_many_requests: 2,
+            ..Default::default()
+        })
+        .await;
+        let name = "miniblack".parse::<PackageName>().unwrap();
+
+        // Without retries the throttled request fails
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db = PackageDb::new(Client::new(), &[index.server.url()], cache_dir.path())
+            .unwrap()
+            .with_retry_policy(RetryPolicy::never());
+        assert!(package_db.available_artifacts(name.clone()).await.is_err());
+
+        // The server sends `Retry-After: 0`, so the second attempt succeeds immediately
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db =
+            PackageDb::new(Client::new(), &[index.server.url()], cache_dir.path()).unwrap();
+        let artifacts = package_db.available_artifacts(name).await.unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(index.server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_too_many_requests() {
+        let index = TestIndex::start_with_options(TestIndexServerOptions {
+            too_many_requests: 1,
+            ..Default::default()
+        })
+        .await;
+
+        let client = Client::new();
+        let response = client.get(index.server.url()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 429);
+        let response = client.get(index.server.url()).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(index.server.request_count(), 2);
+    }
+}
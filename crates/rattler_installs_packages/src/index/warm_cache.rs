@@ -0,0 +1,140 @@
+//! Prefetches everything a resolve of a set of requirements needs, so a later resolve can run
+//! from the cache. This is useful to prime the cache in CI or before going offline.
+
+use super::PackageDb;
+use crate::artifacts::{SDist, Wheel};
+use crate::python_env::WheelTags;
+use crate::resolve::{resolve, ResolveOptions};
+use crate::types::{ArtifactName, NormalizedPackageName, Version};
+use futures::{stream, StreamExt, TryStreamExt};
+use pep508_rs::{MarkerEnvironment, Requirement};
+use std::collections::HashMap;
+
+/// Options for [`PackageDb::warm_cache`].
+#[derive(Default, Clone)]
+pub struct WarmCacheOptions {
+    /// The options that are used to resolve the requirements. These should match the options of
+    /// the resolves that should be able to run from the cache.
+    pub resolve_options: ResolveOptions,
+
+    /// When true, the most compatible artifact of every resolved package is downloaded as well.
+    /// Otherwise only the index pages and metadata are cached.
+    pub download_artifacts: bool,
+}
+
+/// Describes what was cached by [`PackageDb::warm_cache`].
+#[derive(Debug, Clone, Default)]
+pub struct WarmCacheReport {
+    /// The packages the requirements resolved to, sorted by name.
+    pub packages: Vec<(NormalizedPackageName, Version)>,
+
+    /// The artifacts that were downloaded, empty unless
+    /// [`WarmCacheOptions::download_artifacts`] is set.
+    pub downloaded_artifacts: Vec<ArtifactName>,
+}
+
+impl PackageDb {
+    /// Downloads the index pages and metadata that resolving `requirements` requires and
+    /// optionally the artifacts of the resolved packages, so a future resolve with the same
+    /// requirements, markers and tags can be served from the cache.
+    ///
+    /// The requirements are resolved like [`resolve`] does, sdists whose metadata is required are
+    /// therefore built and their wheels are cached as well.
+    pub async fn warm_cache(
+        &self,
+        requirements: impl IntoIterator<Item = &Requirement>,
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+        options: &WarmCacheOptions,
+    ) -> miette::Result<WarmCacheReport> {
+        let mut packages = resolve(
+            self,
+            requirements,
+            env_markers,
+            compatible_tags,
+            HashMap::default(),
+            HashMap::default(),
+            &options.resolve_options,
+        )
+        .await?;
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let downloaded_artifacts = if options.download_artifacts {
+            // The artifacts of a package are ordered by compatibility, the first one is the one
+            // that would be installed.
+            stream::iter(packages.iter().filter_map(|p| p.artifacts.first().copied()))
+                .map(|artifact_info| async move {
+                    tracing::info!("downloading {}", artifact_info.filename);
+                    if artifact_info.is::<Wheel>() {
+                        self.get_artifact::<Wheel>(artifact_info).await?;
+                    } else {
+                        self.get_artifact::<SDist>(artifact_info).await?;
+                    }
+                    Ok::<_, miette::Report>(artifact_info.filename.clone())
+                })
+                .buffer_unordered(8)
+                .try_collect()
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        Ok(WarmCacheReport {
+            packages: packages
+                .into_iter()
+                .map(|package| (package.name, package.version))
+                .collect(),
+            downloaded_artifacts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::types::PackageName;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_warm_cache() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let requirements: [Requirement; 1] = ["wordle_python".parse().unwrap()];
+        let options = WarmCacheOptions {
+            download_artifacts: true,
+            ..Default::default()
+        };
+
+        let report = package_db
+            .warm_cache(
+                &requirements,
+                &linux_environment_markers("3.10.4"),
+                None,
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.packages.len(), 1);
+        assert_eq!(report.packages[0].0.as_str(), "wordle-python");
+        assert_eq!(report.packages[0].1.to_string(), "2.3.32");
+        assert_eq!(
+            report
+                .downloaded_artifacts
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["wordle_python-2.3.32-py3-none-any.whl"]
+        );
+
+        // Both the artifact and its metadata are now available locally
+        let artifacts = package_db
+            .available_artifacts("wordle_python".parse::<PackageName>().unwrap())
+            .await
+            .unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+        let status = package_db.cache_status(artifact_info).unwrap();
+        assert!(status.artifact.is_some());
+        assert!(status.metadata);
+    }
+}
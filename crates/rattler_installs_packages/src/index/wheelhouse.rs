@@ -0,0 +1,156 @@
+//! Scanning of a local directory of artifacts, often called a wheelhouse.
+//!
+//! Directories like `./dist` or the output of `pip wheel -w wheelhouse` contain artifacts that are
+//! not served by an index. [`scan_wheelhouse`] turns the files in such a directory into
+//! [`ArtifactInfo`]s with a `file://` url and a sha256 hash so they can be treated like the
+//! artifacts of an index. Such directories can be passed as find-links to
+//! [`super::PackageDb::with_find_links`] and are the input of [`super::generate::generate_index`].
+
+use crate::types::{ArtifactHashes, ArtifactInfo, ArtifactName, ParseArtifactNameError};
+use miette::Diagnostic;
+use rattler_digest::{compute_file_digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use url::Url;
+
+/// The result of [`scan_wheelhouse`].
+#[derive(Debug, Default)]
+pub struct WheelhouseScan {
+    /// The artifacts in the directory, sorted by their filename
+    pub artifacts: Vec<ArtifactInfo>,
+
+    /// The files that look like artifacts but could not be read
+    pub errors: Vec<WheelhouseError>,
+}
+
+/// An error for a single file in a wheelhouse, see [`scan_wheelhouse`].
+#[derive(Debug, Error, Diagnostic)]
+#[allow(missing_docs)]
+pub enum WheelhouseError {
+    #[error("'{}' is not a valid artifact filename", path.display())]
+    #[diagnostic(help(
+        "artifacts are named like 'name-1.0-py3-none-any.whl' or 'name-1.0.tar.gz'"
+    ))]
+    InvalidFilename {
+        path: PathBuf,
+        #[source]
+        source: ParseArtifactNameError,
+    },
+
+    #[error("failed to read '{}'", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Scans the files directly in `dir` for wheels and sdists, see the [module documentation](self).
+///
+/// Files without the extension of a wheel or an sdist, and hidden files, are skipped. Files that
+/// have such an extension but whose name cannot be parsed or that cannot be read are reported in
+/// [`WheelhouseScan::errors`] instead of failing the whole scan. An error is only returned if the
+/// directory itself cannot be read.
+pub fn scan_wheelhouse(dir: &Path) -> std::io::Result<WheelhouseScan> {
+    let dir = dir.canonicalize()?;
+    let mut paths = dir
+        .read_dir()?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    let mut scan = WheelhouseScan::default();
+    for path in paths {
+        let Some(file_name) = path.file_name().and_then(|f| f.to_str()) else {
+            continue;
+        };
+        if file_name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+        match scan_artifact(&path, file_name) {
+            Ok(artifact) => scan.artifacts.push(artifact),
+            Err(WheelhouseError::InvalidFilename {
+                source: ParseArtifactNameError::InvalidExtension(_),
+                ..
+            }) => {
+                tracing::debug!("skipping '{}', not a recognized artifact", path.display());
+            }
+            Err(err) => scan.errors.push(err),
+        }
+    }
+    scan.artifacts
+        .sort_by(|a, b| a.filename.to_string().cmp(&b.filename.to_string()));
+    Ok(scan)
+}
+
+/// Parses the filename and computes the hash of a single artifact.
+fn scan_artifact(path: &Path, file_name: &str) -> Result<ArtifactInfo, WheelhouseError> {
+    let filename =
+        ArtifactName::from_str(file_name).map_err(|source| WheelhouseError::InvalidFilename {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let io_error = |source| WheelhouseError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let size = path.metadata().map_err(io_error)?.len();
+    let sha256 = compute_file_digest::<Sha256>(path).map_err(io_error)?;
+    let url = Url::from_file_path(path).expect("the path is absolute");
+
+    Ok(ArtifactInfo {
+        hashes: Some(ArtifactHashes {
+            sha256: Some(sha256),
+        }),
+        size: Some(size),
+        ..ArtifactInfo::new(filename, url)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::test_wheels_dir;
+
+    #[test]
+    fn test_scan_wheelhouse() {
+        let dir = tempfile::tempdir().unwrap();
+        let wheel = "wordle_python-2.3.32-py3-none-any.whl";
+        std::fs::copy(test_wheels_dir().join(wheel), dir.path().join(wheel)).unwrap();
+        std::fs::write(dir.path().join("Foo_Bar-1.0.tar.gz"), b"sdist").unwrap();
+        std::fs::write(dir.path().join("broken.whl"), b"").unwrap();
+        std::fs::write(dir.path().join("README.md"), b"").unwrap();
+        std::fs::write(dir.path().join(".hidden-1.0.tar.gz"), b"").unwrap();
+
+        let scan = scan_wheelhouse(dir.path()).unwrap();
+        assert_eq!(
+            scan.artifacts
+                .iter()
+                .map(|artifact| artifact.filename.to_string())
+                .collect::<Vec<_>>(),
+            ["Foo_Bar-1.0.tar.gz", wheel]
+        );
+
+        let sdist = &scan.artifacts[0];
+        assert!(sdist.is::<crate::artifacts::SDist>());
+        assert_eq!(sdist.size, Some(5));
+        assert_eq!(
+            sdist.hashes.as_ref().unwrap().sha256,
+            Some(rattler_digest::compute_bytes_digest::<Sha256>(b"sdist"))
+        );
+        assert_eq!(
+            sdist.url.to_file_path().unwrap(),
+            dir.path()
+                .canonicalize()
+                .unwrap()
+                .join("Foo_Bar-1.0.tar.gz")
+        );
+
+        assert_eq!(scan.errors.len(), 1);
+        assert!(matches!(
+            &scan.errors[0],
+            WheelhouseError::InvalidFilename { path, .. } if path.ends_with("broken.whl")
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+//! Installation of resolved packages into an environment.
+//!
+//! [`InstallBuilder`] downloads the artifacts of the packages returned by the resolver, builds
+//! wheels from sdists where needed and unpacks the wheels with [`unpack_wheels`]. Like
+//! [`crate::resolve::ResolveBuilder`] it only requires the inputs that every installation needs and
+//! sets everything else through `with_*` methods.
+
+use crate::artifacts::wheel::{unpack_wheels, InstallPaths, UnpackWheelOptions, UnpackedWheel};
+use crate::artifacts::{InstallProvenance, SDist, Wheel};
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::resolve::{PinnedPackage, ResolveOptions};
+use crate::types::ArtifactName;
+use crate::wheel_builder::WheelBuilder;
+use miette::IntoDiagnostic;
+use pep508_rs::MarkerEnvironment;
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+/// Configures and runs the installation of resolved packages, see the [module
+/// documentation](self).
+///
+/// ```no_run
+/// # use rattler_installs_packages::index::PackageDb;
+/// # use rattler_installs_packages::install::InstallBuilder;
+/// # use rattler_installs_packages::artifacts::wheel::InstallPaths;
+/// # use rattler_installs_packages::resolve::PinnedPackage;
+/// # use pep508_rs::MarkerEnvironment;
+/// # use std::path::Path;
+/// # async fn example(package_db: &PackageDb, env_markers: &MarkerEnvironment, packages: &[PinnedPackage<'_>]) -> miette::Result<()> {
+/// let install_paths = InstallPaths::for_venv((3, 11, 0), false);
+/// let python = Path::new("/tmp/venv/bin/python");
+/// InstallBuilder::new(package_db, env_markers, Path::new("/tmp/venv"), &install_paths, python)
+///     .install(packages)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct InstallBuilder<'db, 'i> {
+    package_db: &'db PackageDb,
+    env_markers: &'i MarkerEnvironment,
+    dest: &'i Path,
+    install_paths: &'i InstallPaths,
+    python_executable: &'i Path,
+    compatible_tags: Option<&'i WheelTags>,
+    resolve_options: ResolveOptions,
+    unpack_options: UnpackWheelOptions,
+    concurrency: Option<NonZeroUsize>,
+}
+
+impl<'db, 'i> InstallBuilder<'db, 'i> {
+    /// Constructs a builder that installs into the environment at `dest` with the given layout.
+    /// `python_executable` is the interpreter of the environment, it is used in the shebang of
+    /// scripts and entry points.
+    pub fn new(
+        package_db: &'db PackageDb,
+        env_markers: &'i MarkerEnvironment,
+        dest: &'i Path,
+        install_paths: &'i InstallPaths,
+        python_executable: &'i Path,
+    ) -> Self {
+        Self {
+            package_db,
+            env_markers,
+            dest,
+            install_paths,
+            python_executable,
+            compatible_tags: None,
+            resolve_options: ResolveOptions::default(),
+            unpack_options: UnpackWheelOptions {
+                installer: Some(String::from("rip")),
+                ..Default::default()
+            },
+            concurrency: None,
+        }
+    }
+
+    /// The tags supported by the environment. Wheels that are built from sdists must be compatible
+    /// with these tags.
+    pub fn with_compatible_tags(self, compatible_tags: &'i WheelTags) -> Self {
+        Self {
+            compatible_tags: Some(compatible_tags),
+            ..self
+        }
+    }
+
+    /// Sets the options that are used to build wheels from sdists. These should be the options the
+    /// packages were resolved with.
+    pub fn with_resolve_options(self, resolve_options: ResolveOptions) -> Self {
+        Self {
+            resolve_options,
+            ..self
+        }
+    }
+
+    /// Sets the options that are used to unpack every wheel, see [`UnpackWheelOptions`]. By default
+    /// the installer is recorded as `rip`. The [`UnpackWheelOptions::provenance`] is set for every
+    /// package from the artifact it is installed from.
+    pub fn with_unpack_options(self, unpack_options: UnpackWheelOptions) -> Self {
+        Self {
+            unpack_options,
+            ..self
+        }
+    }
+
+    /// Limits the number of wheels that are unpacked in parallel, see [`unpack_wheels`].
+    pub fn with_concurrency(self, concurrency: NonZeroUsize) -> Self {
+        Self {
+            concurrency: Some(concurrency),
+            ..self
+        }
+    }
+
+    /// Installs the packages. For every package the first artifact is downloaded, sdists are
+    /// built into wheels first. The unpacked wheels are returned in the order of `packages`.
+    ///
+    /// Every package records whether it was installed from a downloaded wheel or from a wheel that
+    /// was built locally, see [`InstallProvenance`].
+    ///
+    /// Packages that are provided by the platform according to the resolve options are skipped,
+    /// see [`crate::resolve::ProvidedPackages`].
+    pub async fn install(
+        self,
+        packages: &[PinnedPackage<'_>],
+    ) -> miette::Result<Vec<UnpackedWheel>> {
+        let wheel_builder = WheelBuilder::new(
+            self.package_db,
+            self.env_markers,
+            self.compatible_tags,
+            &self.resolve_options,
+        );
+        let mut wheels = Vec::with_capacity(packages.len());
+        for package in packages {
+            if self
+                .resolve_options
+                .provided_packages
+                .contains(&package.name)
+            {
+                tracing::debug!("skipping {}, it is provided by the platform", package.name);
+                continue;
+            }
+            let Some(artifact_info) = package.artifacts.first() else {
+                miette::bail!(
+                    "there are no artifacts available for {} {}",
+                    package.name,
+                    package.version
+                );
+            };
+            let (wheel, provenance) = match &artifact_info.filename {
+                ArtifactName::Wheel(_) => {
+                    let wheel = self.package_db.get_artifact::<Wheel>(artifact_info).await?;
+                    let provenance = InstallProvenance::wheel(artifact_info, &wheel);
+                    (wheel, provenance)
+                }
+                ArtifactName::SDist(_) => {
+                    let sdist = self.package_db.get_artifact::<SDist>(artifact_info).await?;
+                    let path = wheel_builder.build_wheel(&sdist).await.into_diagnostic()?;
+                    let provenance = InstallProvenance::sdist(
+                        artifact_info,
+                        sdist.sha256().into_diagnostic()?,
+                        wheel_builder.build_provenance(&package.name, &path),
+                    );
+                    (Wheel::from_path(&path, &package.name)?, provenance)
+                }
+            };
+            let mut unpack_options = self.unpack_options.clone();
+            unpack_options.provenance = Some(provenance);
+            wheels.push((wheel, unpack_options));
+        }
+
+        let wheels = wheels
+            .iter()
+            .map(|(wheel, unpack_options)| (wheel, unpack_options))
+            .collect::<Vec<_>>();
+        unpack_wheels(
+            &wheels,
+            self.dest,
+            self.install_paths,
+            self.python_executable,
+            self.concurrency,
+        )
+        .into_diagnostic()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::{test_wheels_dir, TestIndex};
+    use crate::resolve::{resolve, ProvidedPackages};
+    use pep508_rs::Requirement;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+        let packages = resolve(
+            package_db,
+            &[Requirement::from_str("wordle_python").unwrap()],
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let venv = tempfile::tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 10, 4), false);
+        let python = venv.path().join("bin/python");
+        let unpacked = InstallBuilder::new(
+            package_db,
+            &env_markers,
+            venv.path(),
+            &install_paths,
+            &python,
+        )
+        .install(&packages)
+        .await
+        .unwrap();
+
+        assert_eq!(unpacked.len(), 1);
+        let dist_info = venv.path().join(&unpacked[0].dist_info);
+        assert_eq!(unpacked[0].metadata.name.as_str(), "wordle-python");
+        assert_eq!(
+            std::fs::read_to_string(dist_info.join("INSTALLER")).unwrap(),
+            "rip\n"
+        );
+
+        // The provenance contains the hash of the installed archive
+        let sha256 = rattler_digest::compute_file_digest::<rattler_digest::Sha256>(
+            test_wheels_dir().join("wordle_python-2.3.32-py3-none-any.whl"),
+        )
+        .unwrap();
+        assert_eq!(
+            InstallProvenance::from_dist_info(&dist_info),
+            Some(InstallProvenance::Wheel {
+                url: packages[0].artifacts[0].url.clone(),
+                sha256: Some(sha256),
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_install_skips_provided_packages() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+        let packages = resolve(
+            package_db,
+            &[Requirement::from_str("wordle_python").unwrap()],
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        // The platform provides the package, so nothing is installed
+        let resolve_options = ResolveOptions {
+            provided_packages: ProvidedPackages::default()
+                .with_package(packages[0].name.clone(), packages[0].version.clone()),
+            ..Default::default()
+        };
+        let venv = tempfile::tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 10, 4), false);
+        let python = venv.path().join("bin/python");
+        let unpacked = InstallBuilder::new(
+            package_db,
+            &env_markers,
+            venv.path(),
+            &install_paths,
+            &python,
+        )
+        .with_resolve_options(resolve_options)
+        .install(&packages)
+        .await
+        .unwrap();
+
+        assert!(unpacked.is_empty());
+        assert!(!venv
+            .path()
+            .join(install_paths.site_packages())
+            .join(format!("wordle_python-{}.dist-info", packages[0].version))
+            .exists());
+    }
+}
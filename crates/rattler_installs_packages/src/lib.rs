@@ -15,10 +15,19 @@ mod utils;
 
 pub mod resolve;
 
-mod wheel_builder;
+pub mod install;
+
+pub mod session_log;
+
+pub mod wheel_builder;
 
 mod win;
 
 pub mod artifacts;
 
+pub mod config;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
 pub use utils::normalize_index_url;
@@ -5,6 +5,7 @@
 //! which is based on [PEP 376](https://peps.python.org/pep-0376/) and [PEP 627](https://peps.python.org/pep-0627/).
 
 use crate::artifacts::wheel::InstallPaths;
+use crate::artifacts::InstallProvenance;
 use crate::python_env::WheelTag;
 use crate::{types::NormalizedPackageName, types::PackageName, types::RFC822ish};
 use indexmap::IndexSet;
@@ -36,6 +37,43 @@ pub struct Distribution {
     /// The specific tags of the distribution that was installed or `None` if this information
     /// could not be retrieved.
     pub tags: Option<IndexSet<WheelTag>>,
+
+    /// How the distribution was installed. For legacy installs `dist_info` refers to the
+    /// `.egg-info` or `.egg-link` instead of a `.dist-info` directory.
+    #[serde(default)]
+    pub kind: DistributionKind,
+
+    /// Where the installed files came from, if the installer recorded it. See
+    /// [`InstallProvenance`].
+    #[serde(default)]
+    pub provenance: Option<InstallProvenance>,
+}
+
+/// Describes how a [`Distribution`] was installed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DistributionKind {
+    /// A regular installation with a `.dist-info` directory as described by
+    /// [PEP 376](https://peps.python.org/pep-0376/).
+    #[default]
+    DistInfo,
+
+    /// A legacy installation by setuptools or distutils with an `.egg-info` directory or file.
+    EggInfo,
+
+    /// A legacy editable installation (`setup.py develop`) with an `.egg-link` file that points to
+    /// the source directory of the project.
+    EggLink {
+        /// The directory that contains the project.
+        target: PathBuf,
+    },
+
+    /// An editable installation of an editable wheel
+    /// ([PEP 660](https://peps.python.org/pep-0660/)) with a `.dist-info` directory. The
+    /// `direct_url.json` of the distribution marks it as editable.
+    Editable {
+        /// The directory that contains the project.
+        target: PathBuf,
+    },
 }
 
 /// An error that can occur when running `find_distributions_in_venv`.
@@ -73,14 +111,19 @@ pub fn find_distributions_in_venv(
     for location in locations {
         for entry in location.read_dir()? {
             let entry = entry?;
-            if entry.file_type()?.is_dir() {
-                if let Some(dist) = analyze_distribution(entry.path())? {
-                    result.push(Distribution {
-                        dist_info: pathdiff::diff_paths(&dist.dist_info, root)
-                            .unwrap_or(dist.dist_info),
-                        ..dist
-                    })
-                }
+            let path = entry.path();
+            let dist = match path.extension().and_then(OsStr::to_str) {
+                Some("dist-info") if entry.file_type()?.is_dir() => analyze_distribution(path)?,
+                Some("egg-info") => analyze_egg_info(path)?,
+                Some("egg-link") => analyze_egg_link(path)?,
+                _ => None,
+            };
+            if let Some(dist) = dist {
+                result.push(Distribution {
+                    dist_info: pathdiff::diff_paths(&dist.dist_info, root)
+                        .unwrap_or(dist.dist_info),
+                    ..dist
+                })
             }
         }
     }
@@ -146,12 +189,113 @@ fn analyze_distribution(
         None
     };
 
+    let kind = match editable_target(&dist_info_path) {
+        Some(target) => DistributionKind::Editable { target },
+        None => DistributionKind::DistInfo,
+    };
+    let provenance = InstallProvenance::from_dist_info(&dist_info_path);
+
     Ok(Some(Distribution {
         dist_info: dist_info_path,
         name: name.into(),
         version,
         installer,
         tags,
+        kind,
+        provenance,
+    }))
+}
+
+/// Returns the project directory of an editable installation according to the `direct_url.json`
+/// in its `.dist-info` directory ([PEP 610](https://peps.python.org/pep-0610/)), or `None` if the
+/// distribution was not installed as editable.
+fn editable_target(dist_info_path: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read(dist_info_path.join("direct_url.json")).ok()?;
+    let direct_url: serde_json::Value = serde_json::from_slice(&contents).ok()?;
+    if direct_url.pointer("/dir_info/editable")?.as_bool() != Some(true) {
+        return None;
+    }
+    url::Url::parse(direct_url.get("url")?.as_str()?)
+        .ok()?
+        .to_file_path()
+        .ok()
+}
+
+/// Reads the name and version from a `PKG-INFO` file of a legacy installation. Returns `None` if
+/// the file cannot be read or does not contain a valid name and version.
+fn read_pkg_info(path: &Path) -> Option<(PackageName, Version)> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let mut parsed = RFC822ish::from_str(&contents).ok()?;
+    let name = PackageName::from_str(&parsed.take("Name").ok()?).ok()?;
+    let version = Version::from_str(&parsed.take("Version").ok()?).ok()?;
+    Some((name, version))
+}
+
+/// Analyzes a legacy `.egg-info` directory or file. Setuptools writes a directory containing a
+/// `PKG-INFO` file, distutils writes the `PKG-INFO` contents directly to a file with the
+/// `.egg-info` extension.
+fn analyze_egg_info(egg_info_path: PathBuf) -> Result<Option<Distribution>, FindDistributionError> {
+    let pkg_info_path = if egg_info_path.is_dir() {
+        egg_info_path.join("PKG-INFO")
+    } else {
+        egg_info_path.clone()
+    };
+
+    let Some((name, version)) = read_pkg_info(&pkg_info_path) else {
+        // If this does not look like a valid distribution we just skip it.
+        return Ok(None);
+    };
+
+    Ok(Some(Distribution {
+        name: name.into(),
+        version,
+        installer: None,
+        dist_info: egg_info_path,
+        tags: None,
+        kind: DistributionKind::EggInfo,
+        provenance: None,
+    }))
+}
+
+/// Analyzes a legacy `.egg-link` file. The first line of the file contains the path to the project
+/// directory which contains the `.egg-info` directory of the project.
+fn analyze_egg_link(egg_link_path: PathBuf) -> Result<Option<Distribution>, FindDistributionError> {
+    let contents = std::fs::read_to_string(&egg_link_path)?;
+    let Some(target) = contents
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    // Relative paths are relative to the directory that contains the .egg-link file.
+    let target = match egg_link_path.parent() {
+        Some(parent) => parent.join(target),
+        None => PathBuf::from(target),
+    };
+
+    // Find the .egg-info directory in the project directory.
+    let Ok(entries) = target.read_dir() else {
+        return Ok(None);
+    };
+    let egg_info = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| path.extension() == Some(OsStr::new("egg-info")) && path.is_dir());
+    let Some((name, version)) = egg_info.and_then(|p| read_pkg_info(&p.join("PKG-INFO"))) else {
+        return Ok(None);
+    };
+
+    Ok(Some(Distribution {
+        name: name.into(),
+        version,
+        installer: None,
+        dist_info: egg_link_path,
+        tags: None,
+        kind: DistributionKind::EggLink { target },
+        provenance: None,
     }))
 }
 
@@ -159,6 +303,100 @@ fn analyze_distribution(
 mod test {
     use super::*;
 
+    #[test]
+    fn test_find_legacy_distributions() {
+        let venv_dir = tempfile::tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let site_packages = venv_dir.path().join(install_paths.site_packages());
+
+        // A setuptools .egg-info directory
+        let egg_info = site_packages.join("legacy_pkg-1.0-py3.8.egg-info");
+        std::fs::create_dir_all(&egg_info).unwrap();
+        std::fs::write(
+            egg_info.join("PKG-INFO"),
+            "Metadata-Version: 1.0\nName: legacy-pkg\nVersion: 1.0\n",
+        )
+        .unwrap();
+
+        // A distutils .egg-info file
+        std::fs::write(
+            site_packages.join("distutils_pkg-2.0-py3.8.egg-info"),
+            "Metadata-Version: 1.0\nName: distutils-pkg\nVersion: 2.0\n",
+        )
+        .unwrap();
+
+        // A setuptools develop install
+        let project_dir = venv_dir.path().join("src/develop_pkg");
+        std::fs::create_dir_all(project_dir.join("develop_pkg.egg-info")).unwrap();
+        std::fs::write(
+            project_dir.join("develop_pkg.egg-info/PKG-INFO"),
+            "Metadata-Version: 1.0\nName: develop-pkg\nVersion: 3.0\n",
+        )
+        .unwrap();
+        std::fs::write(
+            site_packages.join("develop-pkg.egg-link"),
+            format!("{}\n.", project_dir.display()),
+        )
+        .unwrap();
+
+        let mut distributions =
+            find_distributions_in_venv(venv_dir.path(), &install_paths).unwrap();
+        distributions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let summary = distributions
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.to_string(), d.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "develop-pkg",
+                    String::from("3.0"),
+                    DistributionKind::EggLink {
+                        target: project_dir.clone()
+                    }
+                ),
+                (
+                    "distutils-pkg",
+                    String::from("2.0"),
+                    DistributionKind::EggInfo
+                ),
+                ("legacy-pkg", String::from("1.0"), DistributionKind::EggInfo),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_distribution_provenance() {
+        let venv_dir = tempfile::tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let dist_info = venv_dir
+            .path()
+            .join(install_paths.site_packages())
+            .join("foo-1.0.dist-info");
+        std::fs::create_dir_all(&dist_info).unwrap();
+        std::fs::write(
+            dist_info.join("METADATA"),
+            "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n",
+        )
+        .unwrap();
+        std::fs::write(dist_info.join("INSTALLER"), "rip\n").unwrap();
+        std::fs::write(
+            dist_info.join(crate::artifacts::PROVENANCE_FILE),
+            r#"{"source": "sdist", "url": "https://example.com/foo-1.0.tar.gz", "sha256": "64bed0c2bc71ab3e3e8fb5a83ec28b34e2ebf4e33b1b0a67bd2af56d8b9a5dd5"}"#,
+        )
+        .unwrap();
+
+        let distributions = find_distributions_in_venv(venv_dir.path(), &install_paths).unwrap();
+        assert_eq!(distributions.len(), 1);
+        assert_eq!(distributions[0].installer.as_deref(), Some("rip"));
+        assert!(distributions[0]
+            .provenance
+            .as_ref()
+            .is_some_and(InstallProvenance::is_built_locally));
+    }
+
     #[test]
     fn test_find_distributions() {
         // Describe the virtual environment
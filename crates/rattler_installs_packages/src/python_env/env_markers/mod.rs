@@ -3,6 +3,8 @@ use std::ops::Deref;
 
 mod from_env;
 
+pub(crate) use from_env::FromPythonError;
+
 /// Describes the environment markers that can be used in dependency specifications to enable or
 /// disable certain dependencies based on runtime environment.
 ///
@@ -0,0 +1,39 @@
+import json
+import os
+import sys
+import sysconfig
+
+# The paths that are reported, see `InstallScheme` on the rust side.
+KEYS = ["purelib", "platlib", "scripts", "data", "include"]
+
+
+def default_scheme() -> str:
+    if hasattr(sysconfig, "get_default_scheme"):
+        return sysconfig.get_default_scheme()
+    # Python < 3.10
+    return sysconfig._get_default_scheme()
+
+
+def prefix_scheme() -> str:
+    if hasattr(sysconfig, "get_preferred_scheme"):
+        return sysconfig.get_preferred_scheme("prefix")
+    return "nt" if os.name == "nt" else "posix_prefix"
+
+
+if __name__ == "__main__":
+    if len(sys.argv) > 1:
+        # Install into the given prefix, like `pip install --prefix`
+        prefix = sys.argv[1]
+        paths = sysconfig.get_paths(
+            scheme=prefix_scheme(),
+            vars={
+                "base": prefix,
+                "platbase": prefix,
+                "installed_base": prefix,
+                "installed_platbase": prefix,
+            },
+        )
+    else:
+        paths = sysconfig.get_paths(scheme=default_scheme())
+
+    print(json.dumps({key: paths[key] for key in KEYS}))
@@ -0,0 +1,169 @@
+//! The locations python packages are installed to.
+//!
+//! Where the files of a wheel end up depends on the interpreter: the `sysconfig` module of the
+//! interpreter defines an install scheme that maps the categories of a wheel (`purelib`,
+//! `platlib`, `scripts`, `data` and the headers in `include`) to directories. Distributions can
+//! patch these schemes, e.g. Debian installs into `/usr/local`, so the only reliable way to find
+//! them is to ask the interpreter. [`InstallScheme::from_python`] and [`InstallScheme::for_prefix`]
+//! do that, [`InstallScheme::for_venv`] computes the well known layout of a virtual environment
+//! without running python.
+
+use super::PythonInterpreterVersion;
+use crate::artifacts::wheel::InstallPaths;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use thiserror::Error;
+
+/// An error that can occur when querying the [`InstallScheme`] of an interpreter.
+#[derive(Debug, Error)]
+pub enum InstallSchemeError {
+    /// The interpreter could not be executed
+    #[error("failed to execute {0}")]
+    FailedToExecute(PathBuf, #[source] io::Error),
+
+    /// The interpreter exited with an error
+    #[error("{0} failed with {1}")]
+    FailedToRun(PathBuf, ExitStatus),
+
+    /// The output of the interpreter could not be parsed
+    #[error("failed to parse the install scheme reported by {0}")]
+    FailedToParse(PathBuf, #[source] serde_json::Error),
+}
+
+/// The directories the categories of a wheel are installed to, see the [module
+/// documentation](self).
+///
+/// The paths of a queried scheme are absolute, the paths of [`InstallScheme::for_venv`] are
+/// relative to the root of the virtual environment.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InstallScheme {
+    /// Pure python modules
+    pub purelib: PathBuf,
+
+    /// Platform specific modules, often the same as `purelib`
+    pub platlib: PathBuf,
+
+    /// Scripts and the launchers of entry points
+    pub scripts: PathBuf,
+
+    /// Data files, the root the `.data/data` directory of a wheel is installed to
+    pub data: PathBuf,
+
+    /// C headers
+    pub include: PathBuf,
+}
+
+impl InstallScheme {
+    /// Queries the default install scheme of the interpreter at `python`. For the interpreter of
+    /// a virtual environment this is the layout of the virtual environment.
+    pub async fn from_python(python: &Path) -> Result<Self, InstallSchemeError> {
+        Self::query(python, None).await
+    }
+
+    /// Queries the install scheme of the interpreter at `python` for installing into `prefix`
+    /// instead of the environment of the interpreter, like `pip install --prefix`. The packages
+    /// are only importable if `prefix` is added to the search path of the interpreter.
+    pub async fn for_prefix(python: &Path, prefix: &Path) -> Result<Self, InstallSchemeError> {
+        Self::query(python, Some(prefix)).await
+    }
+
+    /// Computes the install scheme of a virtual environment for the given python version, relative
+    /// to the root of the virtual environment. This matches the layout `python -m venv` creates.
+    pub fn for_venv<V: Into<PythonInterpreterVersion>>(version: V, windows: bool) -> Self {
+        let version = version.into();
+        let install_paths =
+            InstallPaths::for_venv((version.major, version.minor, version.patch), windows);
+        let include = if windows {
+            PathBuf::from("Include")
+        } else {
+            Path::new("include")
+                .join("site")
+                .join(format!("python{}.{}", version.major, version.minor))
+        };
+        Self {
+            purelib: install_paths.purelib().to_path_buf(),
+            platlib: install_paths.platlib().to_path_buf(),
+            scripts: install_paths.scripts().to_path_buf(),
+            data: install_paths.data().to_path_buf(),
+            include,
+        }
+    }
+
+    /// Returns the [`InstallPaths`] to unpack wheels with this scheme. Wheels are unpacked
+    /// relative to a destination directory, the absolute paths of a queried scheme can be used
+    /// with any destination.
+    pub fn install_paths(&self, windows: bool) -> InstallPaths {
+        InstallPaths::new(
+            self.purelib.clone(),
+            self.platlib.clone(),
+            self.scripts.clone(),
+            self.data.clone(),
+            windows,
+        )
+    }
+
+    /// Runs the interpreter to determine its install scheme, optionally for a prefix.
+    async fn query(python: &Path, prefix: Option<&Path>) -> Result<Self, InstallSchemeError> {
+        let mut command = tokio::process::Command::new(python);
+        command.arg("-c").arg(include_str!("install_scheme.py"));
+        if let Some(prefix) = prefix {
+            command.arg(prefix);
+        }
+        let output = command
+            .output()
+            .await
+            .map_err(|e| InstallSchemeError::FailedToExecute(python.to_path_buf(), e))?;
+        if !output.status.success() {
+            return Err(InstallSchemeError::FailedToRun(
+                python.to_path_buf(),
+                output.status,
+            ));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        serde_json::from_str(stdout.trim())
+            .map_err(|e| InstallSchemeError::FailedToParse(python.to_path_buf(), e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::python_env::system_python_executable;
+
+    #[test]
+    fn test_venv_install_scheme() {
+        let scheme = InstallScheme::for_venv((3, 11, 0), false);
+        assert_eq!(scheme.purelib, Path::new("lib/python3.11/site-packages"));
+        assert_eq!(scheme.scripts, Path::new("bin"));
+        assert_eq!(scheme.include, Path::new("include/site/python3.11"));
+
+        let scheme = InstallScheme::for_venv((3, 11, 0), true);
+        assert_eq!(scheme.platlib, Path::new("Lib").join("site-packages"));
+        assert_eq!(scheme.scripts, Path::new("Scripts"));
+
+        let install_paths = scheme.install_paths(true);
+        assert!(install_paths.is_windows());
+        assert_eq!(install_paths.scripts(), Path::new("Scripts"));
+    }
+
+    #[tokio::test]
+    async fn test_prefix_install_scheme() {
+        let Ok(python) = system_python_executable() else {
+            // This is fine, the test machine does not include a python binary.
+            return;
+        };
+        let prefix = tempfile::tempdir().unwrap();
+        let scheme = InstallScheme::for_prefix(&python, prefix.path())
+            .await
+            .unwrap();
+        assert!(scheme.purelib.starts_with(prefix.path()));
+        assert!(scheme.scripts.starts_with(prefix.path()));
+        assert_eq!(scheme.data, prefix.path());
+
+        let scheme = InstallScheme::from_python(&python).await.unwrap();
+        assert!(scheme.purelib.is_absolute());
+    }
+}
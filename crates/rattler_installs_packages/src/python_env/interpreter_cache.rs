@@ -0,0 +1,169 @@
+//! Caches information about python interpreters that is expensive to query.
+
+use super::{env_markers, tags};
+use crate::python_env::{
+    system_python_executable, FindPythonError, Pep508EnvMakers, WheelTag, WheelTags,
+};
+use data_encoding::HEXLOWER;
+use rattler_digest::{compute_bytes_digest, Sha256};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use thiserror::Error;
+
+/// An error that can occur when querying an [`InterpreterCache`].
+#[derive(Debug, Error)]
+pub enum InterpreterCacheError {
+    /// The system python interpreter could not be found
+    #[error(transparent)]
+    CouldNotFindPythonExecutable(#[from] FindPythonError),
+
+    /// Failed to determine the environment markers of the interpreter
+    #[error("failed to determine the environment markers of {0}")]
+    EnvMarkers(PathBuf, #[source] env_markers::FromPythonError),
+
+    /// Failed to determine the compatible wheel tags of the interpreter
+    #[error("failed to determine the compatible wheel tags of {0}")]
+    WheelTags(PathBuf, #[source] tags::FromPythonError),
+}
+
+/// Caches the environment markers and the compatible wheel tags of python interpreters on disk.
+///
+/// Determining these requires running the interpreter, which is slow. Entries are keyed by the
+/// canonical path, the modification time and the size of the interpreter executable, so the
+/// information is queried again when the interpreter is replaced or upgraded. Failing to read or
+/// write an entry is not an error, the interpreter is simply queried instead.
+#[derive(Debug, Clone)]
+pub struct InterpreterCache {
+    root: PathBuf,
+}
+
+impl InterpreterCache {
+    /// Constructs a new cache that stores its entries in `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the root directory of the cache.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the environment markers of the interpreter at `python`.
+    pub async fn env_markers(
+        &self,
+        python: &Path,
+    ) -> Result<Pep508EnvMakers, InterpreterCacheError> {
+        let entry = self.entry_path(python, "markers");
+        if let Some(markers) = entry.as_deref().and_then(read_entry) {
+            return Ok(markers);
+        }
+
+        let markers = Pep508EnvMakers::from_python(python)
+            .await
+            .map_err(|e| InterpreterCacheError::EnvMarkers(python.to_path_buf(), e))?;
+        if let Some(entry) = entry {
+            write_entry(&entry, &markers);
+        }
+        Ok(markers)
+    }
+
+    /// Returns the wheel tags that are compatible with the interpreter at `python`.
+    pub async fn wheel_tags(&self, python: &Path) -> Result<WheelTags, InterpreterCacheError> {
+        let entry = self.entry_path(python, "tags");
+        if let Some(tags) = entry.as_deref().and_then(read_entry::<Vec<WheelTag>>) {
+            return Ok(WheelTags::from_iter(tags));
+        }
+
+        let tags = WheelTags::from_python(python)
+            .await
+            .map_err(|e| InterpreterCacheError::WheelTags(python.to_path_buf(), e))?;
+        if let Some(entry) = entry {
+            write_entry(&entry, &tags.tags().cloned().collect::<Vec<_>>());
+        }
+        Ok(tags)
+    }
+
+    /// Returns the environment markers of the system python interpreter.
+    pub async fn system_env_markers(&self) -> Result<Pep508EnvMakers, InterpreterCacheError> {
+        self.env_markers(&system_python_executable()?).await
+    }
+
+    /// Returns the wheel tags that are compatible with the system python interpreter.
+    pub async fn system_wheel_tags(&self) -> Result<WheelTags, InterpreterCacheError> {
+        self.wheel_tags(&system_python_executable()?).await
+    }
+
+    /// Returns the path of the cache entry of the given kind for the interpreter at `python`, or
+    /// `None` if the interpreter cannot be identified.
+    fn entry_path(&self, python: &Path, kind: &str) -> Option<PathBuf> {
+        let python = python.canonicalize().ok()?;
+        let metadata = python.metadata().ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        let key = format!("{}\0{}\0{}", python.display(), modified, metadata.len());
+        let hash = HEXLOWER.encode(&compute_bytes_digest::<Sha256>(key.as_bytes()));
+        Some(self.root.join(format!("{hash}-{kind}.json")))
+    }
+}
+
+/// Reads a cache entry, returns `None` if the entry does not exist or cannot be parsed.
+fn read_entry<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let contents = std::fs::read(path).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// Atomically writes a cache entry. Failures are logged and otherwise ignored.
+fn write_entry<T: Serialize>(path: &Path, value: &T) {
+    let result = (|| -> std::io::Result<()> {
+        let dir = path
+            .parent()
+            .expect("cache entries are always in a directory");
+        std::fs::create_dir_all(dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(&serde_json::to_vec(value)?)?;
+        file.persist(path)?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        tracing::warn!(
+            "failed to write interpreter cache entry {}: {err}",
+            path.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_entry_path() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = InterpreterCache::new(cache_dir.path());
+
+        let python = cache_dir.path().join("python");
+        assert_eq!(cache.entry_path(&python, "tags"), None);
+
+        std::fs::write(&python, "#!/bin/sh").unwrap();
+        let entry = cache.entry_path(&python, "tags").unwrap();
+        assert_eq!(cache.entry_path(&python, "tags"), Some(entry.clone()));
+        assert_ne!(cache.entry_path(&python, "markers"), Some(entry.clone()));
+
+        // Replacing the interpreter invalidates the entry
+        std::fs::write(&python, "#!/bin/bash").unwrap();
+        assert_ne!(cache.entry_path(&python, "tags"), Some(entry.clone()));
+
+        write_entry(&entry, &vec![WheelTag::from_str("py3-none-any").unwrap()]);
+        assert_eq!(
+            read_entry::<Vec<WheelTag>>(&entry),
+            Some(vec![WheelTag::from_str("py3-none-any").unwrap()])
+        );
+    }
+}
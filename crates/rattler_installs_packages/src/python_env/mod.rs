@@ -7,18 +7,38 @@ mod distribution_finder;
 
 mod env_markers;
 
+mod install_scheme;
+
+mod interpreter_cache;
+
+mod pyodide;
+
+mod satisfied;
+
+mod site_hooks;
+
 mod system_python;
 
 mod uninstall;
 mod venv;
 
-pub use tags::{WheelTag, WheelTags};
+pub use tags::{LinuxLibc, WheelTag, WheelTags};
 
-pub use distribution_finder::{find_distributions_in_venv, Distribution, FindDistributionError};
+pub use distribution_finder::{
+    find_distributions_in_venv, Distribution, DistributionKind, FindDistributionError,
+};
 pub use env_markers::Pep508EnvMakers;
+pub use install_scheme::{InstallScheme, InstallSchemeError};
+pub use interpreter_cache::{InterpreterCache, InterpreterCacheError};
+pub use pyodide::PyodideTarget;
+pub use satisfied::{satisfied_by, Unsatisfied, UnsatisfiedReason};
+pub use site_hooks::{SiteHookError, SiteHooks};
 pub(crate) use system_python::{
     system_python_executable, FindPythonError, ParsePythonInterpreterVersionError,
     PythonInterpreterVersion,
 };
-pub use uninstall::{uninstall_distribution, UninstallDistributionError};
-pub(crate) use venv::{PythonLocation, VEnv};
+pub use uninstall::{
+    uninstall_distribution, uninstall_distribution_with_log, UninstallDistributionError,
+};
+pub use venv::PythonLocation;
+pub(crate) use venv::{VEnv, VEnvError};
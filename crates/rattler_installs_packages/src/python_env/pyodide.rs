@@ -0,0 +1,101 @@
+use super::tags::cpython_tags;
+use super::WheelTags;
+use crate::resolve::{ResolveOptions, SDistResolution};
+use pep508_rs::MarkerEnvironment;
+
+/// Describes a [Pyodide](https://pyodide.org) distribution, CPython compiled to WebAssembly with
+/// emscripten. Use it to resolve packages that can be installed into Pyodide, typically from an
+/// index that hosts emscripten wheels.
+///
+/// Pyodide cannot build sdists, so only wheels are ever selected for this target.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PyodideTarget {
+    /// The version of the CPython interpreter of the distribution
+    pub python_version: (u32, u32, u32),
+
+    /// The version of emscripten the distribution was built with
+    pub emscripten_version: (u32, u32, u32),
+}
+
+impl Default for PyodideTarget {
+    /// Pyodide 0.25
+    fn default() -> Self {
+        Self {
+            python_version: (3, 11, 3),
+            emscripten_version: (3, 1, 46),
+        }
+    }
+}
+
+impl PyodideTarget {
+    /// Returns the environment markers of the Pyodide interpreter.
+    pub fn env_markers(&self) -> MarkerEnvironment {
+        let (major, minor, patch) = self.python_version;
+        let (em_major, em_minor, em_patch) = self.emscripten_version;
+        let full_version = format!("{major}.{minor}.{patch}");
+        MarkerEnvironment {
+            implementation_name: String::from("cpython"),
+            implementation_version: full_version.parse().expect("invalid python version"),
+            os_name: String::from("posix"),
+            platform_machine: String::from("wasm32"),
+            platform_python_implementation: String::from("CPython"),
+            platform_release: format!("{em_major}.{em_minor}.{em_patch}"),
+            platform_system: String::from("Emscripten"),
+            platform_version: String::from("#1"),
+            python_full_version: full_version.parse().expect("invalid python version"),
+            python_version: format!("{major}.{minor}")
+                .parse()
+                .expect("invalid python version"),
+            sys_platform: String::from("emscripten"),
+        }
+    }
+
+    /// Returns the wheel tags supported by the Pyodide interpreter, e.g.
+    /// `cp311-cp311-emscripten_3_1_46_wasm32`.
+    pub fn wheel_tags(&self) -> WheelTags {
+        let (major, minor, _) = self.python_version;
+        let (em_major, em_minor, em_patch) = self.emscripten_version;
+        cpython_tags(
+            (major, minor),
+            &[format!(
+                "emscripten_{em_major}_{em_minor}_{em_patch}_wasm32"
+            )],
+        )
+    }
+
+    /// Returns the options to resolve packages for Pyodide. Sdists are never selected because they
+    /// cannot be built for the target and the `Requires-Python` of artifacts is respected.
+    pub fn resolve_options(&self) -> ResolveOptions {
+        ResolveOptions {
+            sdist_resolution: SDistResolution::OnlyWheels,
+            check_requires_python: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pyodide_target() {
+        let target = PyodideTarget::default();
+
+        let markers = target.env_markers();
+        assert_eq!(markers.sys_platform, "emscripten");
+        assert_eq!(markers.platform_release, "3.1.46");
+        assert_eq!(markers.python_version.string, "3.11");
+
+        let tags = target.wheel_tags();
+        assert_eq!(
+            tags.tags().next().unwrap().to_string(),
+            "cp311-cp311-emscripten_3_1_46_wasm32"
+        );
+        assert!(tags.is_compatible(&"py3-none-any".parse().unwrap()));
+        assert!(!tags.is_compatible(&"cp311-cp311-manylinux_2_17_x86_64".parse().unwrap()));
+        assert!(!tags.is_compatible(&"cp311-cp311-emscripten_3_1_45_wasm32".parse().unwrap()));
+
+        assert!(!target.resolve_options().sdist_resolution.allow_sdists());
+    }
+}
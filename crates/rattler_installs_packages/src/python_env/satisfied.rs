@@ -0,0 +1,250 @@
+//! Checks whether requirements are already met by the distributions installed in an environment,
+//! so callers can skip the resolver when there is nothing to do.
+
+use crate::python_env::{Distribution, DistributionKind};
+use crate::types::{NormalizedPackageName, PackageName, WheelCoreMetadata};
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+
+/// A requirement that is not met by an environment, see [`satisfied_by`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsatisfied {
+    /// The requirement that is not met
+    pub requirement: Requirement,
+
+    /// The installed package that depends on the requirement, or `None` if the requirement was
+    /// passed to [`satisfied_by`] directly.
+    pub required_by: Option<NormalizedPackageName>,
+
+    /// Why the requirement is not met
+    pub reason: UnsatisfiedReason,
+}
+
+/// The reason why a requirement is not met by an environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsatisfiedReason {
+    /// There is no distribution installed with the name of the requirement.
+    NotInstalled,
+
+    /// The installed version does not match the version specifiers of the requirement.
+    VersionMismatch {
+        /// The version that is installed
+        installed: Version,
+    },
+
+    /// The requirement refers to a url but the distribution was not installed from that url
+    /// according to its `direct_url.json` ([PEP 610](https://peps.python.org/pep-0610/)).
+    UrlMismatch,
+}
+
+/// Evaluates `requirements` against the `distributions` installed in the environment at `root`
+/// (see [`super::find_distributions_in_venv`]) and returns the requirements that are not met.
+/// An empty result means the environment already satisfies all requirements.
+///
+/// The dependencies of the installed distributions are checked as well, including the
+/// dependencies of the requested extras. Requirements are only considered if their environment
+/// markers match `env_markers`. The dependencies of legacy `.egg-info` installs are not known and
+/// therefore not checked.
+pub fn satisfied_by<'r>(
+    root: &Path,
+    distributions: &[Distribution],
+    requirements: impl IntoIterator<Item = &'r Requirement>,
+    env_markers: &MarkerEnvironment,
+) -> Vec<Unsatisfied> {
+    let distributions: HashMap<NormalizedPackageName, &Distribution> = distributions
+        .iter()
+        .map(|distribution| (distribution.name.clone(), distribution))
+        .collect();
+
+    let mut queue = requirements
+        .into_iter()
+        .filter(|requirement| {
+            requirement
+                .marker
+                .as_ref()
+                .map_or(true, |markers| markers.evaluate(env_markers, &[]))
+        })
+        .map(|requirement| (requirement.clone(), None))
+        .collect::<VecDeque<_>>();
+
+    let mut unsatisfied = Vec::new();
+    let mut metadata_cache: HashMap<NormalizedPackageName, Vec<Requirement>> = HashMap::new();
+    let mut visited: HashSet<(NormalizedPackageName, Option<String>)> = HashSet::new();
+    while let Some((requirement, required_by)) = queue.pop_front() {
+        let name: Option<NormalizedPackageName> = PackageName::from_str(&requirement.name)
+            .ok()
+            .map(Into::into);
+        let Some(distribution) = name.as_ref().and_then(|name| distributions.get(name)) else {
+            unsatisfied.push(Unsatisfied {
+                requirement,
+                required_by,
+                reason: UnsatisfiedReason::NotInstalled,
+            });
+            continue;
+        };
+
+        let reason = match &requirement.version_or_url {
+            Some(VersionOrUrl::VersionSpecifier(specifiers))
+                if !specifiers.contains(&distribution.version) =>
+            {
+                Some(UnsatisfiedReason::VersionMismatch {
+                    installed: distribution.version.clone(),
+                })
+            }
+            Some(VersionOrUrl::Url(url))
+                if direct_url(root, distribution).as_deref() != Some(url.as_str()) =>
+            {
+                Some(UnsatisfiedReason::UrlMismatch)
+            }
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            unsatisfied.push(Unsatisfied {
+                requirement,
+                required_by,
+                reason,
+            });
+            continue;
+        }
+
+        // Queue the dependencies of the distribution itself and of the requested extras.
+        let requires_dist = metadata_cache
+            .entry(distribution.name.clone())
+            .or_insert_with(|| requires_dist(root, distribution));
+        let extras = std::iter::once(None).chain(
+            requirement
+                .extras
+                .iter()
+                .flatten()
+                .map(|extra| Some(extra.as_str())),
+        );
+        for extra in extras {
+            if !visited.insert((distribution.name.clone(), extra.map(ToOwned::to_owned))) {
+                continue;
+            }
+            for dependency in requires_dist.iter() {
+                let applies = match (&dependency.marker, extra) {
+                    (None, None) => true,
+                    (None, Some(_)) => false,
+                    (Some(markers), None) => markers.evaluate(env_markers, &[]),
+                    // Only add the dependencies that are enabled by the extra, the others are
+                    // already added without extras.
+                    (Some(markers), Some(extra)) => {
+                        markers.evaluate(env_markers, &[extra])
+                            && !markers.evaluate(env_markers, &[])
+                    }
+                };
+                if applies {
+                    queue.push_back((dependency.clone(), Some(distribution.name.clone())));
+                }
+            }
+        }
+    }
+
+    unsatisfied
+}
+
+/// Reads the dependencies of an installed distribution from its `METADATA` file. Returns an empty
+/// list if they cannot be determined.
+fn requires_dist(root: &Path, distribution: &Distribution) -> Vec<Requirement> {
+    if !matches!(
+        distribution.kind,
+        DistributionKind::DistInfo | DistributionKind::Editable { .. }
+    ) {
+        return Vec::new();
+    }
+    std::fs::read(root.join(&distribution.dist_info).join("METADATA"))
+        .ok()
+        .and_then(|bytes| WheelCoreMetadata::try_from(bytes.as_slice()).ok())
+        .map(|metadata| metadata.requires_dist)
+        .unwrap_or_default()
+}
+
+/// Reads the url a distribution was installed from from its `direct_url.json` file.
+fn direct_url(root: &Path, distribution: &Distribution) -> Option<String> {
+    let contents =
+        std::fs::read(root.join(&distribution.dist_info).join("direct_url.json")).ok()?;
+    let direct_url: serde_json::Value = serde_json::from_slice(&contents).ok()?;
+    direct_url.get("url")?.as_str().map(ToOwned::to_owned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::artifacts::wheel::InstallPaths;
+    use crate::python_env::find_distributions_in_venv;
+    use test_utils::linux_environment_markers;
+
+    #[test]
+    fn test_satisfied_by() {
+        let venv_dir = tempfile::tempdir().unwrap();
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let site_packages = venv_dir.path().join(install_paths.site_packages());
+        let install = |name: &str, version: &str, requires_dist: &[&str]| {
+            let dist_info = site_packages.join(format!("{name}-{version}.dist-info"));
+            std::fs::create_dir_all(&dist_info).unwrap();
+            let mut metadata = format!("Metadata-Version: 2.1\nName: {name}\nVersion: {version}\n");
+            for requirement in requires_dist {
+                metadata.push_str(&format!("Requires-Dist: {requirement}\n"));
+            }
+            std::fs::write(dist_info.join("METADATA"), metadata).unwrap();
+        };
+        install(
+            "a",
+            "1.0",
+            &["b>=2", "c; extra == 'extra'", "d; python_version < '3'"],
+        );
+        install("b", "1.5", &[]);
+
+        let env_markers = linux_environment_markers("3.8.5");
+        let distributions = find_distributions_in_venv(venv_dir.path(), &install_paths).unwrap();
+        let check = |requirements: &[&str]| {
+            let requirements = requirements
+                .iter()
+                .map(|requirement| Requirement::from_str(requirement).unwrap())
+                .collect::<Vec<_>>();
+            satisfied_by(venv_dir.path(), &distributions, &requirements, &env_markers)
+                .into_iter()
+                .map(|unsatisfied| {
+                    (
+                        unsatisfied.requirement.name,
+                        unsatisfied.required_by.map(|name| name.as_str().to_owned()),
+                        unsatisfied.reason,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Requirements that don't apply to the environment are skipped
+        assert_eq!(check(&["b<2", "e; python_version < '3'"]), vec![]);
+
+        // Dependencies of installed packages are checked
+        assert_eq!(
+            check(&["a"]),
+            vec![(
+                String::from("b"),
+                Some(String::from("a")),
+                UnsatisfiedReason::VersionMismatch {
+                    installed: "1.5".parse().unwrap()
+                }
+            )]
+        );
+
+        // Extras enable additional dependencies
+        install("b", "2.0", &[]);
+        std::fs::remove_dir_all(site_packages.join("b-1.5.dist-info")).unwrap();
+        let distributions = find_distributions_in_venv(venv_dir.path(), &install_paths).unwrap();
+        let unsatisfied = satisfied_by(
+            venv_dir.path(),
+            &distributions,
+            &[Requirement::from_str("a[extra]").unwrap()],
+            &env_markers,
+        );
+        assert_eq!(unsatisfied.len(), 1);
+        assert_eq!(unsatisfied[0].requirement.name, "c");
+        assert_eq!(unsatisfied[0].reason, UnsatisfiedReason::NotInstalled);
+    }
+}
@@ -0,0 +1,282 @@
+//! Startup hooks of an environment: `.pth` files and `sitecustomize.py`.
+//!
+//! The `site` module of python processes the `.pth` files in site-packages and imports
+//! `sitecustomize` whenever the interpreter starts. Tooling uses this to instrument an environment,
+//! e.g. to start coverage measurement in subprocesses or to install import hooks. These files do
+//! not belong to any distribution, so they are not removed when packages are uninstalled and
+//! nothing tells who created them.
+//!
+//! [`SiteHooks`] adds and removes these files and records the files it wrote, with their hashes,
+//! in a manifest in site-packages. Files that were not created through [`SiteHooks`] are never
+//! overwritten or removed, neither are hooks that were modified after they were written.
+
+use data_encoding::HEXLOWER;
+use rattler_digest::{compute_bytes_digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The name of the manifest in site-packages that records the hooks written by [`SiteHooks`].
+const MANIFEST_FILE: &str = ".rip-site-hooks.json";
+
+/// The name of the `sitecustomize` module.
+const SITECUSTOMIZE: &str = "sitecustomize.py";
+
+/// An error that can occur when managing the startup hooks of an environment, see [`SiteHooks`].
+#[derive(Debug, Error)]
+pub enum SiteHookError {
+    /// The name of a `.pth` file is not a plain file name
+    #[error("'{0}' is not a valid name for a .pth file")]
+    InvalidName(String),
+
+    /// The file already exists but was not created through [`SiteHooks`]
+    #[error("{} already exists and is not managed by rip", .0.display())]
+    NotManaged(PathBuf),
+
+    /// The file was modified after it was written
+    #[error("{} was modified after it was written by rip", .0.display())]
+    Modified(PathBuf),
+
+    /// The manifest of the managed hooks is invalid
+    #[error("the manifest of the site hooks in {} is invalid", .0.display())]
+    InvalidManifest(PathBuf, #[source] serde_json::Error),
+
+    /// An IO error occurred
+    #[error("failed to access {}", .0.display())]
+    Io(PathBuf, #[source] std::io::Error),
+}
+
+/// The hooks written by [`SiteHooks`], keyed by their file name in site-packages.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// The sha256 hashes of the contents of the hooks, hex encoded
+    files: BTreeMap<String, String>,
+}
+
+/// Manages the `.pth` files and the `sitecustomize.py` in the site-packages directory of an
+/// environment, see the [module documentation](self).
+///
+/// ```no_run
+/// # use rattler_installs_packages::python_env::SiteHooks;
+/// # use rattler_installs_packages::artifacts::wheel::InstallPaths;
+/// # use std::path::Path;
+/// let install_paths = InstallPaths::for_venv((3, 11, 0), false);
+/// let hooks = SiteHooks::new(Path::new("/tmp/venv").join(install_paths.site_packages()));
+/// hooks.add_pth("coverage", "import coverage; coverage.process_startup()\n")?;
+/// // ... later
+/// hooks.remove("coverage.pth")?;
+/// # Ok::<(), rattler_installs_packages::python_env::SiteHookError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct SiteHooks {
+    site_packages: PathBuf,
+}
+
+impl SiteHooks {
+    /// Manages the hooks in the given site-packages directory.
+    pub fn new(site_packages: impl Into<PathBuf>) -> Self {
+        Self {
+            site_packages: site_packages.into(),
+        }
+    }
+
+    /// Returns the site-packages directory the hooks are written to.
+    pub fn site_packages(&self) -> &Path {
+        &self.site_packages
+    }
+
+    /// Writes a `.pth` file to site-packages. The `.pth` extension is added to `name` if it is
+    /// missing. Lines of the file that start with `import` are executed when the interpreter
+    /// starts, other lines are added to `sys.path`.
+    ///
+    /// A `.pth` file that was written before by [`SiteHooks`] is replaced, unless it was modified
+    /// in the meantime. Returns the path of the written file.
+    pub fn add_pth(&self, name: &str, contents: &str) -> Result<PathBuf, SiteHookError> {
+        let file_name = pth_file_name(name)?;
+        self.write(&file_name, contents)
+    }
+
+    /// Writes the `sitecustomize.py` module that is imported when the interpreter starts. Fails if
+    /// the environment already has a `sitecustomize.py` that was not written by [`SiteHooks`].
+    /// Returns the path of the written file.
+    pub fn set_sitecustomize(&self, contents: &str) -> Result<PathBuf, SiteHookError> {
+        self.write(SITECUSTOMIZE, contents)
+    }
+
+    /// Removes a hook, e.g. `coverage.pth` or `sitecustomize.py`. Returns false if there is no
+    /// managed hook with this name. Hooks that were modified after they were written are not
+    /// removed.
+    pub fn remove(&self, file_name: &str) -> Result<bool, SiteHookError> {
+        let mut manifest = self.read_manifest()?;
+        let Some(hash) = manifest.files.get(file_name) else {
+            return Ok(false);
+        };
+
+        let path = self.site_packages.join(file_name);
+        match std::fs::read(&path) {
+            Ok(existing) if &hash_contents(&existing) != hash => {
+                return Err(SiteHookError::Modified(path))
+            }
+            Ok(_) => std::fs::remove_file(&path).map_err(|e| SiteHookError::Io(path, e))?,
+            // The hook was already removed by someone else
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(SiteHookError::Io(path, err)),
+        }
+
+        manifest.files.remove(file_name);
+        self.write_manifest(&manifest)?;
+        Ok(true)
+    }
+
+    /// Removes all managed hooks, see [`Self::remove`].
+    pub fn remove_all(&self) -> Result<(), SiteHookError> {
+        for file_name in self.hooks()? {
+            self.remove(&file_name)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the file names of the managed hooks, sorted by name.
+    pub fn hooks(&self) -> Result<Vec<String>, SiteHookError> {
+        Ok(self.read_manifest()?.files.into_keys().collect())
+    }
+
+    /// Writes a hook and records it in the manifest.
+    fn write(&self, file_name: &str, contents: &str) -> Result<PathBuf, SiteHookError> {
+        let mut manifest = self.read_manifest()?;
+        let path = self.site_packages.join(file_name);
+        match (std::fs::read(&path), manifest.files.get(file_name)) {
+            (Ok(existing), Some(hash)) if &hash_contents(&existing) != hash => {
+                return Err(SiteHookError::Modified(path))
+            }
+            (Ok(_), None) => return Err(SiteHookError::NotManaged(path)),
+            (Err(err), _) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(SiteHookError::Io(path, err))
+            }
+            _ => {}
+        }
+
+        write_atomically(&path, contents.as_bytes())?;
+        manifest
+            .files
+            .insert(file_name.to_string(), hash_contents(contents.as_bytes()));
+        self.write_manifest(&manifest)?;
+        Ok(path)
+    }
+
+    fn read_manifest(&self) -> Result<Manifest, SiteHookError> {
+        let path = self.site_packages.join(MANIFEST_FILE);
+        match std::fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| SiteHookError::InvalidManifest(path, e)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(err) => Err(SiteHookError::Io(path, err)),
+        }
+    }
+
+    fn write_manifest(&self, manifest: &Manifest) -> Result<(), SiteHookError> {
+        let path = self.site_packages.join(MANIFEST_FILE);
+        if manifest.files.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    Err(SiteHookError::Io(path, err))
+                }
+                _ => Ok(()),
+            };
+        }
+        let contents =
+            serde_json::to_vec_pretty(manifest).expect("the manifest can always be serialized");
+        write_atomically(&path, &contents)
+    }
+}
+
+/// Returns the file name of a `.pth` file. Names must be plain file names so hooks cannot be
+/// written outside of site-packages, hidden files are not processed by `site` and are rejected as
+/// well.
+fn pth_file_name(name: &str) -> Result<String, SiteHookError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(SiteHookError::InvalidName(name.to_string()));
+    }
+    Ok(if name.ends_with(".pth") {
+        name.to_string()
+    } else {
+        format!("{name}.pth")
+    })
+}
+
+fn hash_contents(contents: &[u8]) -> String {
+    HEXLOWER.encode(&compute_bytes_digest::<Sha256>(contents))
+}
+
+/// Writes a file through a temporary file, so the interpreter never sees a partially written hook.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), SiteHookError> {
+    let io_error = |e| SiteHookError::Io(path.to_path_buf(), e);
+    let dir = path.parent().expect("hooks are always in site-packages");
+    std::fs::create_dir_all(dir).map_err(io_error)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir).map_err(io_error)?;
+    file.write_all(contents).map_err(io_error)?;
+    file.persist(path).map_err(|e| io_error(e.error))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_site_hooks() {
+        let site_packages = tempfile::tempdir().unwrap();
+        let hooks = SiteHooks::new(site_packages.path());
+
+        let pth = hooks
+            .add_pth("coverage", "import coverage; coverage.process_startup()\n")
+            .unwrap();
+        assert_eq!(pth, site_packages.path().join("coverage.pth"));
+        hooks.set_sitecustomize("print('hello')\n").unwrap();
+        assert_eq!(hooks.hooks().unwrap(), ["coverage.pth", "sitecustomize.py"]);
+
+        // Managed hooks can be replaced
+        hooks.add_pth("coverage.pth", "import coverage\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&pth).unwrap(), "import coverage\n");
+
+        // Names must be plain file names
+        assert!(matches!(
+            hooks.add_pth("../evil", ""),
+            Err(SiteHookError::InvalidName(_))
+        ));
+        assert!(matches!(
+            hooks.add_pth(".hidden", ""),
+            Err(SiteHookError::InvalidName(_))
+        ));
+
+        // Files that are not managed are left alone
+        std::fs::write(site_packages.path().join("distutils-precedence.pth"), "").unwrap();
+        assert!(matches!(
+            hooks.add_pth("distutils-precedence", ""),
+            Err(SiteHookError::NotManaged(_))
+        ));
+        assert!(!hooks.remove("distutils-precedence.pth").unwrap());
+
+        // Modified hooks are not removed
+        std::fs::write(site_packages.path().join(SITECUSTOMIZE), "changed").unwrap();
+        assert!(matches!(
+            hooks.remove(SITECUSTOMIZE),
+            Err(SiteHookError::Modified(_))
+        ));
+
+        assert!(hooks.remove("coverage.pth").unwrap());
+        assert!(!pth.exists());
+        assert_eq!(hooks.hooks().unwrap(), [SITECUSTOMIZE]);
+        assert!(site_packages
+            .path()
+            .join("distutils-precedence.pth")
+            .exists());
+    }
+}
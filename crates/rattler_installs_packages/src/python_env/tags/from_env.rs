@@ -1,4 +1,6 @@
-use crate::python_env::{system_python_executable, FindPythonError, WheelTag, WheelTags};
+use crate::python_env::{
+    system_python_executable, FindPythonError, LinuxLibc, WheelTag, WheelTags,
+};
 use crate::utils::VENDORED_PACKAGING_DIR;
 use serde::Deserialize;
 use std::io;
@@ -72,8 +74,8 @@ impl WheelTags {
 
         // Convert the JSON
         let stdout = String::from_utf8_lossy(&output.stdout);
-        match serde_json::from_str(stdout.trim())? {
-            Result::Tags(tags) => Ok(Self {
+        let tags = match serde_json::from_str(stdout.trim())? {
+            Result::Tags(tags) => Self {
                 tags: tags
                     .into_iter()
                     .map(|(interpreter, abi, platform)| WheelTag {
@@ -82,9 +84,16 @@ impl WheelTags {
                         platform,
                     })
                     .collect(),
-            }),
-            Result::Error(err) => Err(FromPythonError::PythonError(err)),
-        }
+            },
+            Result::Error(err) => return Err(FromPythonError::PythonError(err)),
+        };
+
+        // The interpreter does not always know which libc the system actually provides, e.g. on
+        // Alpine or on distributions with an old glibc. Remove the tags the system cannot support.
+        Ok(match LinuxLibc::detect() {
+            Some(libc) => tags.restrict_to_libc(libc),
+            None => tags,
+        })
     }
 }
 
@@ -0,0 +1,234 @@
+use super::WheelTags;
+use std::path::Path;
+use std::process::Command;
+
+/// The C standard library of a linux system. Wheels for linux are built against a specific libc,
+/// `manylinux` wheels require glibc and `musllinux` wheels require musl.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LinuxLibc {
+    /// The GNU C library with the given version.
+    Glibc {
+        /// The major version
+        major: u32,
+        /// The minor version
+        minor: u32,
+    },
+
+    /// The musl C library with the given version.
+    Musl {
+        /// The major version
+        major: u32,
+        /// The minor version
+        minor: u32,
+    },
+}
+
+impl LinuxLibc {
+    /// Probes the libc of the current system. Returns `None` if the system is not linux or if the
+    /// libc could not be determined.
+    ///
+    /// The version of glibc is read from the C library this process is linked against if possible.
+    /// Otherwise the musl dynamic loader and `ldd --version` are queried, similar to what the
+    /// `packaging` python module does.
+    pub fn detect() -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        glibc_from_process()
+            .or_else(musl_from_loader)
+            .or_else(libc_from_ldd)
+    }
+
+    /// Returns true if a wheel with the given platform tag can run against this libc. Platforms
+    /// that are not `manylinux` or `musllinux` platforms are always considered compatible.
+    pub fn supports_platform(&self, platform: &str) -> bool {
+        if let Some((major, minor)) = manylinux_version(platform) {
+            matches!(*self, LinuxLibc::Glibc { major: m, minor: n } if (major, minor) <= (m, n))
+        } else if let Some((major, minor)) = musllinux_version(platform) {
+            matches!(*self, LinuxLibc::Musl { major: m, minor: n } if (major, minor) <= (m, n))
+        } else {
+            true
+        }
+    }
+}
+
+impl WheelTags {
+    /// Removes all `manylinux` and `musllinux` tags that are not supported by the given libc. The
+    /// order of the remaining tags is preserved.
+    ///
+    /// Python interpreters sometimes report tags that do not match the system they run on, e.g. a
+    /// python linked against glibc running on Alpine or an interpreter built on a newer distro.
+    pub fn restrict_to_libc(mut self, libc: LinuxLibc) -> Self {
+        self.tags
+            .retain(|tag| libc.supports_platform(&tag.platform));
+        self
+    }
+}
+
+/// Returns the minimum glibc version of a `manylinux` platform tag.
+fn manylinux_version(platform: &str) -> Option<(u32, u32)> {
+    if platform.starts_with("manylinux1_") {
+        Some((2, 5))
+    } else if platform.starts_with("manylinux2010_") {
+        Some((2, 12))
+    } else if platform.starts_with("manylinux2014_") {
+        Some((2, 17))
+    } else {
+        parse_versioned_platform(platform.strip_prefix("manylinux_")?)
+    }
+}
+
+/// Returns the minimum musl version of a `musllinux` platform tag.
+fn musllinux_version(platform: &str) -> Option<(u32, u32)> {
+    parse_versioned_platform(platform.strip_prefix("musllinux_")?)
+}
+
+/// Parses the `<major>_<minor>_<arch>` part of a platform tag.
+fn parse_versioned_platform(rest: &str) -> Option<(u32, u32)> {
+    let mut parts = rest.splitn(3, '_');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Reads the glibc version from the C library this process is linked against.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn glibc_from_process() -> Option<LinuxLibc> {
+    extern "C" {
+        fn gnu_get_libc_version() -> *const std::ffi::c_char;
+    }
+
+    // SAFETY: `gnu_get_libc_version` returns a pointer to a static null terminated string.
+    let version = unsafe { std::ffi::CStr::from_ptr(gnu_get_libc_version()) };
+    let (major, minor) = parse_major_minor(version.to_str().ok()?)?;
+    Some(LinuxLibc::Glibc { major, minor })
+}
+
+/// The C library of this process is only known to be glibc on gnu targets.
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn glibc_from_process() -> Option<LinuxLibc> {
+    None
+}
+
+/// Runs the musl dynamic loader which prints its version when invoked without arguments.
+fn musl_from_loader() -> Option<LinuxLibc> {
+    let loader = std::fs::read_dir(Path::new("/lib"))
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("ld-musl-") && name.ends_with(".so.1"))
+        })?;
+    let output = Command::new(loader).output().ok()?;
+    parse_musl_output(&String::from_utf8_lossy(&output.stderr))
+}
+
+/// Determines the libc from the output of `ldd --version`.
+fn libc_from_ldd() -> Option<LinuxLibc> {
+    let output = Command::new("ldd").arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    parse_ldd_output(&stdout).or_else(|| parse_musl_output(&stderr))
+}
+
+/// Parses the output of `ldd --version` of glibc, e.g. `ldd (GNU libc) 2.31`.
+fn parse_ldd_output(output: &str) -> Option<LinuxLibc> {
+    let first_line = output.lines().next()?;
+    let lowercase = first_line.to_lowercase();
+    if !lowercase.contains("glibc") && !lowercase.contains("gnu libc") {
+        return None;
+    }
+    let (major, minor) = parse_major_minor(first_line.split_whitespace().last()?)?;
+    Some(LinuxLibc::Glibc { major, minor })
+}
+
+/// Parses the output of the musl loader or musl's `ldd`, which contains a line like
+/// `Version 1.2.4`.
+fn parse_musl_output(output: &str) -> Option<LinuxLibc> {
+    if !output.starts_with("musl libc") {
+        return None;
+    }
+    let version = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Version "))?;
+    let (major, minor) = parse_major_minor(version.trim())?;
+    Some(LinuxLibc::Musl { major, minor })
+}
+
+/// Parses the major and minor component from a version like `2.35` or `1.2.4`.
+fn parse_major_minor(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts
+        .next()?
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn test_parse_libc_output() {
+        assert_eq!(
+            parse_ldd_output("ldd (Ubuntu GLIBC 2.35-0ubuntu3.4) 2.35\nCopyright (C) 2022"),
+            Some(LinuxLibc::Glibc {
+                major: 2,
+                minor: 35
+            })
+        );
+        assert_eq!(
+            parse_ldd_output("ldd (GNU libc) 2.17\n"),
+            Some(LinuxLibc::Glibc {
+                major: 2,
+                minor: 17
+            })
+        );
+        assert_eq!(
+            parse_musl_output("musl libc (x86_64)\nVersion 1.2.4\nDynamic Program Loader\n"),
+            Some(LinuxLibc::Musl { major: 1, minor: 2 })
+        );
+        assert_eq!(parse_ldd_output("musl libc (x86_64)\nVersion 1.2.4"), None);
+    }
+
+    #[test]
+    fn test_restrict_to_libc() {
+        let tags = [
+            "cp311-cp311-manylinux_2_28_x86_64",
+            "cp311-cp311-manylinux_2_17_x86_64",
+            "cp311-cp311-manylinux2014_x86_64",
+            "cp311-cp311-manylinux1_x86_64",
+            "cp311-cp311-musllinux_1_1_x86_64",
+            "cp311-cp311-linux_x86_64",
+            "py3-none-any",
+        ]
+        .into_iter()
+        .map(|tag| tag.parse().unwrap())
+        .collect::<WheelTags>();
+
+        let glibc = tags.clone().restrict_to_libc(LinuxLibc::Glibc {
+            major: 2,
+            minor: 17,
+        });
+        assert_eq!(
+            glibc.tags().format(" ").to_string(),
+            "cp311-cp311-manylinux_2_17_x86_64 cp311-cp311-manylinux2014_x86_64 \
+            cp311-cp311-manylinux1_x86_64 cp311-cp311-linux_x86_64 py3-none-any"
+        );
+
+        let musl = tags.restrict_to_libc(LinuxLibc::Musl { major: 1, minor: 2 });
+        assert_eq!(
+            musl.tags().format(" ").to_string(),
+            "cp311-cp311-musllinux_1_1_x86_64 cp311-cp311-linux_x86_64 py3-none-any"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Generates the tags supported by CPython builds for iOS ([PEP 730](https://peps.python.org/pep-0730/))
+//! and Android ([PEP 738](https://peps.python.org/pep-0738/)).
+//!
+//! Mobile interpreters cannot be executed on the machine that plans the installation, so the tags
+//! cannot be queried from the interpreter like [`WheelTags::from_python`] does. Instead they are
+//! generated the same way the `packaging` python module does.
+
+use super::{cpython_tags, WheelTags};
+
+/// The oldest iOS version that is known to support CPython.
+const MIN_IOS_MAJOR_VERSION: u32 = 12;
+
+/// The oldest Android API level that is supported by CPython.
+const MIN_ANDROID_API_LEVEL: u32 = 16;
+
+impl WheelTags {
+    /// Returns the tags supported by a CPython interpreter with the given `python_version` running
+    /// on iOS.
+    ///
+    /// `ios_version` is the minimum iOS version the app targets and `multiarch` the architecture
+    /// and SDK of the build, e.g. `arm64_iphoneos`, `arm64_iphonesimulator` or
+    /// `x86_64_iphonesimulator`. Wheels built for any iOS version from 12.0 up to `ios_version` are
+    /// compatible, e.g. `ios_13_0_arm64_iphoneos`.
+    pub fn ios(python_version: (u32, u32), ios_version: (u32, u32), multiarch: &str) -> Self {
+        cpython_tags(python_version, &ios_platforms(ios_version, multiarch))
+    }
+
+    /// Returns the tags supported by a CPython interpreter with the given `python_version` running
+    /// on Android.
+    ///
+    /// `api_level` is the minimum Android API level the app targets and `abi` the Android ABI of
+    /// the build, e.g. `arm64_v8a`, `armeabi_v7a`, `x86` or `x86_64`. Wheels built for any API level
+    /// from 16 up to `api_level` are compatible, e.g. `android_21_arm64_v8a`.
+    pub fn android(python_version: (u32, u32), api_level: u32, abi: &str) -> Self {
+        cpython_tags(python_version, &android_platforms(api_level, abi))
+    }
+}
+
+/// Returns the iOS platforms in order of preference.
+fn ios_platforms((major, minor): (u32, u32), multiarch: &str) -> Vec<String> {
+    if major < MIN_IOS_MAJOR_VERSION {
+        return Vec::new();
+    }
+
+    // Every minor version up to .9 is considered for older major versions. Not all of them exist
+    // but that does not hurt and saves us from keeping a list of known iOS versions.
+    (0..=minor)
+        .rev()
+        .map(|minor| (major, minor))
+        .chain(
+            (MIN_IOS_MAJOR_VERSION..major)
+                .rev()
+                .flat_map(|major| (0..=9).rev().map(move |minor| (major, minor))),
+        )
+        .map(|(major, minor)| format!("ios_{major}_{minor}_{multiarch}"))
+        .collect()
+}
+
+/// Returns the Android platforms in order of preference.
+fn android_platforms(api_level: u32, abi: &str) -> Vec<String> {
+    (MIN_ANDROID_API_LEVEL..=api_level)
+        .rev()
+        .map(|api_level| format!("android_{api_level}_{abi}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn test_ios_tags() {
+        let tags = WheelTags::ios((3, 13), (13, 2), "arm64_iphoneos");
+        assert_eq!(
+            tags.tags().take(4).format(" ").to_string(),
+            "cp313-cp313-ios_13_2_arm64_iphoneos cp313-cp313-ios_13_1_arm64_iphoneos \
+            cp313-cp313-ios_13_0_arm64_iphoneos cp313-cp313-ios_12_9_arm64_iphoneos"
+        );
+        assert!(tags.is_compatible(&"cp313-cp313-ios_12_0_arm64_iphoneos".parse().unwrap()));
+        assert!(tags.is_compatible(&"cp310-abi3-ios_13_0_arm64_iphoneos".parse().unwrap()));
+        assert!(tags.is_compatible(&"py3-none-any".parse().unwrap()));
+        assert!(!tags.is_compatible(&"cp313-cp313-ios_13_3_arm64_iphoneos".parse().unwrap()));
+        assert!(!tags.is_compatible(
+            &"cp313-cp313-ios_13_0_arm64_iphonesimulator"
+                .parse()
+                .unwrap()
+        ));
+
+        // iOS versions before 12 are not supported by CPython
+        assert!(WheelTags::ios((3, 13), (11, 0), "arm64_iphoneos")
+            .tags()
+            .all(|tag| tag.platform == "any"));
+    }
+
+    #[test]
+    fn test_android_tags() {
+        let tags = WheelTags::android((3, 13), 24, "arm64_v8a");
+        assert_eq!(
+            tags.tags().take(2).format(" ").to_string(),
+            "cp313-cp313-android_24_arm64_v8a cp313-cp313-android_23_arm64_v8a"
+        );
+        assert!(tags.is_compatible(&"cp313-cp313-android_16_arm64_v8a".parse().unwrap()));
+        assert!(tags.is_compatible(&"py3-none-android_21_arm64_v8a".parse().unwrap()));
+        assert!(!tags.is_compatible(&"cp313-cp313-android_25_arm64_v8a".parse().unwrap()));
+        assert!(!tags.is_compatible(&"cp313-cp313-android_21_x86_64".parse().unwrap()));
+        assert!(!tags.is_compatible(&"cp313-cp313-manylinux_2_17_aarch64".parse().unwrap()));
+    }
+}
@@ -3,6 +3,11 @@
 //! running Python interpreter supports and determining if a wheel is compatible with a set of tags.
 
 mod from_env;
+mod linux_libc;
+mod mobile;
+
+pub(crate) use from_env::FromPythonError;
+pub use linux_libc::LinuxLibc;
 
 use indexmap::IndexSet;
 use itertools::Itertools;
@@ -101,16 +106,74 @@ impl WheelTags {
     /// `Some(i)` where `i` indicates the compatibility level. The higher the number the more
     /// specific the tag is to the platform. The wheel artifact with the highest number should be
     /// preferred over others.
+    ///
+    /// `abi3` tags are also compatible with newer CPython versions than the one they mention, e.g.
+    /// a `cp38-abi3` wheel is compatible if this set contains `cp311-abi3` for the same platform,
+    /// even if `cp38-abi3` is not part of the set itself.
     pub fn compatibility(&self, tag: &WheelTag) -> Option<i32> {
-        self.tags.get_index_of(tag).map(|score| -(score as i32))
+        if let Some(index) = self.tags.get_index_of(tag) {
+            return Some(-(index as i32));
+        }
+
+        // The stable ABI of an older CPython version is supported by all newer versions.
+        let (major, minor) = abi3_python_version(tag)?;
+        self.tags
+            .iter()
+            .enumerate()
+            .filter(|(_, supported)| {
+                supported.platform == tag.platform
+                    && abi3_python_version(supported).is_some_and(|(m, n)| m == major && n >= minor)
+            })
+            .map(|(index, _)| -(index as i32))
+            .max()
     }
 
-    /// Returns if the specified tag is compatible with this set.
+    /// Returns if the specified tag is compatible with this set. See [`Self::compatibility`].
     pub fn is_compatible(&self, tag: &WheelTag) -> bool {
-        self.tags.contains(tag)
+        self.compatibility(tag).is_some()
+    }
+
+    /// Returns how close the specified tag is to being compatible with this set: the highest
+    /// number of parts (interpreter, abi and platform) that the tag has in common with any of the
+    /// tags in this set. Compatible tags always return 3. This is used to point out the wheels
+    /// that come closest to being usable when none of them are compatible.
+    pub fn similarity(&self, tag: &WheelTag) -> usize {
+        if self.is_compatible(tag) {
+            return 3;
+        }
+        self.tags
+            .iter()
+            .map(|supported| {
+                usize::from(supported.interpreter == tag.interpreter)
+                    + usize::from(supported.abi == tag.abi)
+                    + usize::from(supported.platform == tag.platform)
+            })
+            .max()
+            .unwrap_or(0)
     }
 }
 
+impl WheelTag {
+    /// Returns true if this tag refers to the stable ABI of CPython (`abi3`). Wheels with this
+    /// tag can be used with the CPython version in the tag and all newer versions.
+    pub fn is_abi3(&self) -> bool {
+        abi3_python_version(self).is_some()
+    }
+}
+
+/// Returns the CPython version of an `abi3` tag, e.g. `(3, 8)` for `cp38-abi3-any`.
+fn abi3_python_version(tag: &WheelTag) -> Option<(u32, u32)> {
+    if tag.abi != "abi3" {
+        return None;
+    }
+    let version = tag.interpreter.strip_prefix("cp")?;
+    if version.len() < 2 || !version.is_char_boundary(1) {
+        return None;
+    }
+    let (major, minor) = version.split_at(1);
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
 impl FromIterator<WheelTag> for WheelTags {
     fn from_iter<T: IntoIterator<Item = WheelTag>>(iter: T) -> Self {
         Self {
@@ -119,6 +182,48 @@ impl FromIterator<WheelTag> for WheelTags {
     }
 }
 
+/// Returns the tags supported by CPython on the given platforms, in the same order as the
+/// `packaging` python module.
+pub(super) fn cpython_tags((major, minor): (u32, u32), platforms: &[String]) -> WheelTags {
+    let tag = |interpreter: &str, abi: &str, platform: &str| WheelTag {
+        interpreter: interpreter.to_owned(),
+        abi: abi.to_owned(),
+        platform: platform.to_owned(),
+    };
+    let interpreter = format!("cp{major}{minor}");
+    let use_abi3 = major == 3 && minor >= 2;
+
+    // The interpreter specific tags
+    let mut tags = Vec::new();
+    for abi in [interpreter.as_str(), "abi3", "none"] {
+        if abi == "abi3" && !use_abi3 {
+            continue;
+        }
+        tags.extend(platforms.iter().map(|p| tag(&interpreter, abi, p)));
+    }
+    if use_abi3 {
+        for older_minor in (2..minor).rev() {
+            let interpreter = format!("cp{major}{older_minor}");
+            tags.extend(platforms.iter().map(|p| tag(&interpreter, "abi3", p)));
+        }
+    }
+
+    // The generic python tags
+    let py_interpreters = std::iter::once(format!("py{major}{minor}"))
+        .chain(std::iter::once(format!("py{major}")))
+        .chain((0..minor).rev().map(|minor| format!("py{major}{minor}")))
+        .collect::<Vec<_>>();
+    for py_interpreter in &py_interpreters {
+        tags.extend(platforms.iter().map(|p| tag(py_interpreter, "none", p)));
+    }
+    tags.push(tag(&interpreter, "none", "any"));
+    for py_interpreter in &py_interpreters {
+        tags.push(tag(py_interpreter, "none", "any"));
+    }
+
+    tags.into_iter().collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -130,4 +235,46 @@ mod test {
         assert_eq!(tag.abi, "none");
         assert_eq!(tag.platform, "any");
     }
+
+    #[test]
+    fn test_abi3_compatibility() {
+        let tags: WheelTags = [
+            "cp311-cp311-manylinux_2_17_x86_64",
+            "cp311-abi3-manylinux_2_17_x86_64",
+            "py3-none-any",
+        ]
+        .into_iter()
+        .map(|tag| tag.parse().unwrap())
+        .collect();
+
+        let tag = |s: &str| WheelTag::from_str(s).unwrap();
+        assert!(tag("cp38-abi3-manylinux_2_17_x86_64").is_abi3());
+        assert!(!tag("cp38-cp38-manylinux_2_17_x86_64").is_abi3());
+
+        assert_eq!(
+            tags.compatibility(&tag("cp38-abi3-manylinux_2_17_x86_64")),
+            tags.compatibility(&tag("cp311-abi3-manylinux_2_17_x86_64"))
+        );
+        assert!(tags.is_compatible(&tag("cp310-abi3-manylinux_2_17_x86_64")));
+        assert!(!tags.is_compatible(&tag("cp312-abi3-manylinux_2_17_x86_64")));
+        assert!(!tags.is_compatible(&tag("cp38-abi3-macosx_11_0_arm64")));
+        assert!(!tags.is_compatible(&tag("cp38-cp38-manylinux_2_17_x86_64")));
+    }
+
+    #[test]
+    fn test_similarity() {
+        let tags: WheelTags = ["cp311-cp311-win_amd64", "py3-none-any"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+
+        let tag = |s: &str| WheelTag::from_str(s).unwrap();
+        assert_eq!(tags.similarity(&tag("py3-none-any")), 3);
+        assert_eq!(
+            tags.similarity(&tag("cp311-cp311-manylinux_2_17_x86_64")),
+            2
+        );
+        assert_eq!(tags.similarity(&tag("cp310-cp310-win_amd64")), 1);
+        assert_eq!(tags.similarity(&tag("cp310-cp310-macosx_11_0_arm64")), 0);
+    }
 }
@@ -1,9 +1,14 @@
 //! Functionality to remove python distributions from an environment.
 
+use crate::session_log::{FileOperation, SessionEvent, SessionLog};
 use crate::types::Record;
 use indexmap::IndexSet;
 use itertools::Itertools;
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
 use thiserror::Error;
 
 /// An error that can occur during the uninstallation of a python distribution.
@@ -29,6 +34,20 @@ pub enum UninstallDistributionError {
     /// Failed to delete a directory
     #[error("failed to delete {0}")]
     FailedToDeleteDirectory(String, #[source] std::io::Error),
+
+    /// The distribution is a legacy setuptools editable install (`.egg-link`). These point to a
+    /// source directory elsewhere on the system and are not managed by us.
+    #[error("{0} is a legacy editable install (setuptools develop), remove it with `pip uninstall` or `python setup.py develop --uninstall`")]
+    LegacyEditableInstall(String),
+
+    /// The distribution is a legacy `.egg-info` install that does not record which files belong
+    /// to it.
+    #[error("{0} is a legacy .egg-info install without a list of installed files, it cannot be uninstalled safely")]
+    LegacyEggInfoWithoutFileList(String),
+
+    /// Failed to read the `installed-files.txt` of a legacy `.egg-info` install.
+    #[error("failed to read the installed files of {0}")]
+    FailedToReadFileList(String, #[source] std::io::Error),
 }
 
 /// Uninstall a python distribution from an environment
@@ -37,10 +56,44 @@ pub enum UninstallDistributionError {
 /// * dist_info_dir: The path off the `.dist-info` dir relative to `site_packages_dir`.
 ///
 /// This function will delete all the files specified in the `RECORD` file of the distribution.
+///
+/// Legacy setuptools `.egg-info` distributions are uninstalled using their `installed-files.txt`.
+/// If that file is missing, or the distribution is a legacy editable install (`.egg-link`), an
+/// error is returned because there is no reliable way to tell which files belong to it.
 pub fn uninstall_distribution(
     site_packages_dir: &Path,
     dist_info_dir: &Path,
 ) -> Result<(), UninstallDistributionError> {
+    uninstall_distribution_inner(site_packages_dir, dist_info_dir, None)
+}
+
+/// Uninstall a python distribution from an environment like [`uninstall_distribution`] and record
+/// every removed file and directory in the given session log.
+pub fn uninstall_distribution_with_log(
+    site_packages_dir: &Path,
+    dist_info_dir: &Path,
+    session_log: &SessionLog,
+) -> Result<(), UninstallDistributionError> {
+    uninstall_distribution_inner(site_packages_dir, dist_info_dir, Some(session_log))
+}
+
+fn uninstall_distribution_inner(
+    site_packages_dir: &Path,
+    dist_info_dir: &Path,
+    session_log: Option<&SessionLog>,
+) -> Result<(), UninstallDistributionError> {
+    match dist_info_dir.extension().and_then(OsStr::to_str) {
+        Some("egg-info") => {
+            return uninstall_egg_info(site_packages_dir, dist_info_dir, session_log)
+        }
+        Some("egg-link") => {
+            return Err(UninstallDistributionError::LegacyEditableInstall(
+                dist_info_dir.display().to_string(),
+            ))
+        }
+        _ => {}
+    }
+
     // Load the RECORD file
     let record = match Record::from_path(&site_packages_dir.join(dist_info_dir).join("RECORD")) {
         Ok(record) => record,
@@ -56,19 +109,190 @@ pub fn uninstall_distribution(
     };
 
     // Delete all the files specified in the RECORD file
+    remove_files(
+        site_packages_dir,
+        record
+            .into_iter()
+            .map(|entry| site_packages_dir.join(entry.path)),
+        session_log,
+    )
+}
+
+/// Uninstalls a legacy setuptools `.egg-info` distribution. These do not contain a RECORD file but
+/// might contain an `installed-files.txt` file with paths relative to the `.egg-info` directory.
+fn uninstall_egg_info(
+    site_packages_dir: &Path,
+    egg_info: &Path,
+    session_log: Option<&SessionLog>,
+) -> Result<(), UninstallDistributionError> {
+    let egg_info_path = site_packages_dir.join(egg_info);
+    if !egg_info_path.is_dir() {
+        // A single .egg-info file is written by distutils, there is no way to tell which files
+        // belong to the distribution.
+        return Err(UninstallDistributionError::LegacyEggInfoWithoutFileList(
+            egg_info.display().to_string(),
+        ));
+    }
+
+    let installed_files = match std::fs::read_to_string(egg_info_path.join("installed-files.txt")) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(UninstallDistributionError::LegacyEggInfoWithoutFileList(
+                egg_info.display().to_string(),
+            ))
+        }
+        Err(e) => {
+            return Err(UninstallDistributionError::FailedToReadFileList(
+                egg_info.display().to_string(),
+                e,
+            ))
+        }
+    };
+
+    remove_files(
+        site_packages_dir,
+        installed_files
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| normalize_path(&egg_info_path.join(line)))
+            .collect::<Vec<_>>(),
+        session_log,
+    )?;
+
+    // Remove whatever remains of the .egg-info directory itself.
+    match std::fs::remove_dir_all(&egg_info_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(UninstallDistributionError::FailedToDeleteDirectory(
+                egg_info_path.to_string_lossy().to_string(),
+                e,
+            ))
+        }
+        Err(_) => Ok(()),
+        Ok(()) => {
+            log_removal(session_log, &egg_info_path);
+            Ok(())
+        }
+    }
+}
+
+/// Records the removal of a file or directory in the session log if there is one.
+fn log_removal(session_log: Option<&SessionLog>, path: &Path) {
+    if let Some(session_log) = session_log {
+        session_log.record(SessionEvent::FileOperation {
+            operation: FileOperation::Remove,
+            path: path.to_path_buf(),
+        });
+    }
+}
+
+/// Lexically normalizes a path by removing `.` and resolving `..` components.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// Returns true if `path` is a directory strictly below `root`.
+fn is_inside(path: &Path, root: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|relative| {
+        relative.components().next().is_some()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+    })
+}
+
+/// Removes the bytecode that python cached for the source file at `path` in the `__pycache__`
+/// directory next to it ([PEP 3147](https://peps.python.org/pep-3147/)). Bytecode that is written
+/// after installation, e.g. for the import hooks of editables, is not listed in the RECORD.
+///
+/// Returns the `__pycache__` directory if it exists.
+fn remove_cached_bytecode(
+    path: &Path,
+    session_log: Option<&SessionLog>,
+) -> Result<Option<PathBuf>, UninstallDistributionError> {
+    let (Some(parent), Some(stem)) = (path.parent(), path.file_stem().and_then(OsStr::to_str))
+    else {
+        return Ok(None);
+    };
+    let pycache = parent.join("__pycache__");
+    let Ok(entries) = pycache.read_dir() else {
+        return Ok(None);
+    };
+
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let is_cached_bytecode = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(stem))
+            .and_then(|rest| rest.strip_prefix('.'))
+            .is_some_and(|rest| rest.ends_with(".pyc"));
+        if !is_cached_bytecode {
+            continue;
+        }
+        match std::fs::remove_file(entry.path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(UninstallDistributionError::FailedToDeleteFile(
+                    entry.path().display().to_string(),
+                    e,
+                ))
+            }
+            Err(_) => {}
+            Ok(()) => log_removal(session_log, &entry.path()),
+        }
+    }
+
+    Ok(Some(pycache))
+}
+
+/// Deletes the specified files and afterwards removes any directories that became empty.
+fn remove_files(
+    site_packages_dir: &Path,
+    files: impl IntoIterator<Item = PathBuf>,
+    session_log: Option<&SessionLog>,
+) -> Result<(), UninstallDistributionError> {
     let mut directories = HashSet::new();
-    for entry in record.into_iter() {
-        let entry_path = site_packages_dir.join(&entry.path);
-        if let Err(e) = std::fs::remove_file(&entry_path) {
-            if e.kind() != std::io::ErrorKind::NotFound {
+    for entry_path in files {
+        match std::fs::remove_file(&entry_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
                 return Err(UninstallDistributionError::FailedToDeleteFile(
-                    entry.path, e,
+                    pathdiff::diff_paths(&entry_path, site_packages_dir)
+                        .unwrap_or(entry_path)
+                        .display()
+                        .to_string(),
+                    e,
                 ));
             }
+            Err(_) => {}
+            Ok(()) => log_removal(session_log, &entry_path),
         }
         if let Some(parent) = entry_path.parent() {
             directories.insert(parent.to_path_buf());
         }
+        if entry_path.extension() == Some(OsStr::new("py")) {
+            if let Some(pycache) = remove_cached_bytecode(&entry_path, session_log)? {
+                directories.insert(pycache);
+            }
+        }
+
+        // Implicit namespace packages (PEP 420) don't contain an `__init__.py`, so a directory
+        // like `google` in `google/cloud/storage/__init__.py` might not contain any files of its
+        // own. Also consider all ancestors inside site-packages so these don't linger when they
+        // become empty.
+        let mut ancestor = entry_path.parent().and_then(Path::parent);
+        while let Some(dir) = ancestor.filter(|dir| is_inside(dir, site_packages_dir)) {
+            directories.insert(dir.to_path_buf());
+            ancestor = dir.parent();
+        }
     }
 
     // Sort the directories by length, so that we delete the deepest directories first.
@@ -83,14 +307,17 @@ pub fn uninstall_distribution(
                         e,
                     )
                 })?;
+                log_removal(session_log, &directory);
             }
             _ => {
                 // The directory is not empty which means our parent directory is also not empty,
                 // recursively remove the parent directory from the set as well.
-                while let Some(parent) = directory.parent() {
+                let mut current = directory.as_path();
+                while let Some(parent) = current.parent() {
                     if !directories.shift_remove(parent) {
                         break;
                     }
+                    current = parent;
                 }
             }
         }
@@ -166,4 +393,87 @@ mod test {
         assert!(site_packages_dir.join("test/module/extra.py").is_file());
         assert!(!site_packages_dir.join("test/module/__init__.py").is_file());
     }
+
+    #[test]
+    fn test_uninstall_egg_info() {
+        let temp_dir = tempdir().unwrap();
+        let site_packages_dir = temp_dir.path().join("site-packages");
+        let egg_info_dir = Path::new("legacy_pkg-1.0-py3.8.egg-info");
+        std::fs::create_dir_all(site_packages_dir.join(egg_info_dir)).unwrap();
+        std::fs::create_dir_all(site_packages_dir.join("legacy_pkg")).unwrap();
+        std::fs::write(site_packages_dir.join("legacy_pkg/__init__.py"), "").unwrap();
+        std::fs::write(site_packages_dir.join(egg_info_dir).join("PKG-INFO"), "").unwrap();
+        std::fs::write(
+            site_packages_dir
+                .join(egg_info_dir)
+                .join("installed-files.txt"),
+            "../legacy_pkg/__init__.py\nPKG-INFO\ninstalled-files.txt\n",
+        )
+        .unwrap();
+
+        uninstall_distribution(&site_packages_dir, egg_info_dir).unwrap();
+
+        assert!(site_packages_dir.is_dir());
+        assert!(!site_packages_dir.join("legacy_pkg").exists());
+        assert!(!site_packages_dir.join(egg_info_dir).exists());
+    }
+
+    #[test]
+    fn test_uninstall_is_logged() {
+        let temp_dir = tempdir().unwrap();
+        let site_packages_dir = temp_dir.path().join("site-packages");
+        let dist_info_dir = Path::new("test-1.0.0.dist-info");
+        std::fs::create_dir_all(site_packages_dir.join(dist_info_dir)).unwrap();
+        std::fs::create_dir_all(site_packages_dir.join("test")).unwrap();
+        std::fs::write(site_packages_dir.join("test/__init__.py"), "").unwrap();
+        let record = Record::from_iter(["test-1.0.0.dist-info/RECORD", "test/__init__.py"].map(
+            |path| RecordEntry {
+                path: path.to_string(),
+                hash: None,
+                size: None,
+            },
+        ));
+        record
+            .write_to_path(&site_packages_dir.join(dist_info_dir).join("RECORD"))
+            .unwrap();
+
+        let log_path = temp_dir.path().join("session.jsonl");
+        let session_log = SessionLog::create(&log_path).unwrap();
+        uninstall_distribution_with_log(&site_packages_dir, dist_info_dir, &session_log).unwrap();
+        session_log.flush().unwrap();
+
+        let removed = std::fs::read_to_string(&log_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|event| event["event"] == "file_operation" && event["operation"] == "remove")
+            .map(|event| PathBuf::from(event["path"].as_str().unwrap()))
+            .collect::<HashSet<_>>();
+        assert!(removed.contains(&site_packages_dir.join("test/__init__.py")));
+        assert!(removed.contains(&site_packages_dir.join("test")));
+        assert!(removed.contains(&site_packages_dir.join(dist_info_dir)));
+    }
+
+    #[test]
+    fn test_refuse_uninstall_legacy() {
+        let temp_dir = tempdir().unwrap();
+        let site_packages_dir = temp_dir.path();
+
+        // An .egg-info without a list of installed files
+        let egg_info_dir = Path::new("legacy_pkg-1.0-py3.8.egg-info");
+        std::fs::create_dir_all(site_packages_dir.join(egg_info_dir)).unwrap();
+        assert!(matches!(
+            uninstall_distribution(site_packages_dir, egg_info_dir),
+            Err(UninstallDistributionError::LegacyEggInfoWithoutFileList(_))
+        ));
+
+        // A legacy editable install
+        let egg_link = Path::new("develop-pkg.egg-link");
+        std::fs::write(site_packages_dir.join(egg_link), "/some/project\n.").unwrap();
+        assert!(matches!(
+            uninstall_distribution(site_packages_dir, egg_link),
+            Err(UninstallDistributionError::LegacyEditableInstall(_))
+        ));
+        assert!(site_packages_dir.join(egg_link).is_file());
+    }
 }
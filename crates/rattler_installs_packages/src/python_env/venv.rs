@@ -3,12 +3,13 @@
 //! Later on we can look into actually creating the environment by linking to the python library,
 //! and creating the necessary files. See: [VEnv](https://packaging.python.org/en/latest/specifications/virtual-environments/#declaring-installation-environments-as-python-virtual-environments)
 #![allow(dead_code)]
+use crate::artifacts::wheel::{unpack_wheels, UnpackError, UnpackedWheel};
 use crate::artifacts::wheel::{InstallPaths, UnpackWheelOptions, Wheel};
-use crate::artifacts::wheel::{UnpackError, UnpackedWheel};
 use crate::python_env::{
     system_python_executable, FindPythonError, ParsePythonInterpreterVersionError,
     PythonInterpreterVersion,
 };
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use thiserror::Error;
@@ -19,7 +20,7 @@ pub enum PythonLocation {
     /// Use system interpreter
     #[default]
     System,
-    // Use custom interpreter
+    /// Use the interpreter at the given path
     Custom(PathBuf),
 }
 
@@ -76,6 +77,22 @@ impl VEnv {
         )
     }
 
+    /// Install multiple wheels into this virtual environment concurrently. See
+    /// [`unpack_wheels`] for more information.
+    pub fn install_wheels(
+        &self,
+        wheels: &[(&Wheel, &UnpackWheelOptions)],
+        concurrency: Option<NonZeroUsize>,
+    ) -> Result<Vec<UnpackedWheel>, UnpackError> {
+        unpack_wheels(
+            wheels,
+            &self.location,
+            &self.install_paths,
+            &self.python_executable(),
+            concurrency,
+        )
+    }
+
     /// Execute python script in venv
     pub fn execute_script(&self, script: &Path) -> std::io::Result<Output> {
         let mut cmd = Command::new(self.python_executable());
@@ -113,6 +130,42 @@ impl VEnv {
             .join(executable)
     }
 
+    /// Rewrites the configuration of the virtual environment so it keeps working after it has
+    /// been moved to a different location, e.g. when it is baked into a container image.
+    ///
+    /// This marks the environment as relocatable in `pyvenv.cfg` and makes the `activate` script
+    /// determine the location of the environment at runtime. Combine this with
+    /// [`crate::artifacts::wheel::ShebangStyle::Relative`] when installing wheels so scripts
+    /// don't refer to the original location either.
+    pub fn make_relocatable(&self) -> std::io::Result<()> {
+        let pyvenv_cfg = self.location.join("pyvenv.cfg");
+        let mut config = std::fs::read_to_string(&pyvenv_cfg)?;
+        if !config.lines().any(|line| line.starts_with("relocatable")) {
+            if !config.is_empty() && !config.ends_with('\n') {
+                config.push('\n');
+            }
+            config.push_str("relocatable = true\n");
+            std::fs::write(&pyvenv_cfg, config)?;
+        }
+
+        // The activate scripts for other shells than sh compatible ones are left untouched.
+        let activate = self
+            .location
+            .join(self.install_paths.scripts())
+            .join("activate");
+        if activate.is_file() {
+            let location = if self.location.is_absolute() {
+                self.location.clone()
+            } else {
+                std::env::current_dir()?.join(&self.location)
+            };
+            let script = std::fs::read_to_string(&activate)?;
+            std::fs::write(&activate, relocatable_activate_script(&script, &location))?;
+        }
+
+        Ok(())
+    }
+
     /// Create a virtual environment at specified directory
     /// for the platform we are running on
     pub fn create(venv_dir: &Path, python: PythonLocation) -> Result<VEnv, VEnvError> {
@@ -150,6 +203,59 @@ impl VEnv {
     }
 }
 
+/// Rewrites the `activate` script of a virtual environment at `location` to determine the location
+/// of the environment from the location of the script. The script is sourced by any POSIX shell, so
+/// the code that is added must not use bash specific syntax.
+fn relocatable_activate_script(script: &str, location: &Path) -> String {
+    let location = location.display().to_string();
+
+    // Older versions of python quote the location with double quotes, newer versions use single
+    // quotes.
+    let script = script.replace(&format!("'{location}'"), "\"$_RIP_VENV_DIR\"");
+    let script = replace_path(&script, &location, "$_RIP_VENV_DIR");
+
+    // bash exposes the path of a sourced script in `BASH_SOURCE` and zsh in `$0`. Other shells, like
+    // dash, don't expose it at all, in that case the environment is assumed not to have moved.
+    let quoted_location = location.replace('\'', r#"'\''"#);
+    format!(
+        r#"# Determine the location of this relocatable environment from the location of this script.
+_RIP_ACTIVATE_SCRIPT="${{BASH_SOURCE:-}}"
+if [ -z "$_RIP_ACTIVATE_SCRIPT" ] && [ -n "${{ZSH_VERSION:-}}" ]; then
+    _RIP_ACTIVATE_SCRIPT="$0"
+fi
+if [ -n "$_RIP_ACTIVATE_SCRIPT" ]; then
+    _RIP_VENV_DIR="$(cd "$(dirname -- "$_RIP_ACTIVATE_SCRIPT")"/.. && pwd)"
+else
+    _RIP_VENV_DIR='{quoted_location}'
+fi
+unset _RIP_ACTIVATE_SCRIPT
+
+{script}"#
+    )
+}
+
+/// Replaces the occurrences of the path `location` in `text` with `replacement`. Only complete
+/// paths, followed by a path separator, a quote or the end of `text`, are replaced, longer paths
+/// that merely start with `location` (e.g. `/venv2` for `/venv`) are kept.
+fn replace_path(text: &str, location: &str, replacement: &str) -> String {
+    if location.is_empty() {
+        return text.to_owned();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find(location) {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx + location.len()..];
+        if rest.is_empty() || rest.starts_with(['/', '\\', '\'', '"']) {
+            result.push_str(replacement);
+        } else {
+            result.push_str(location);
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::VEnv;
@@ -187,4 +293,48 @@ mod tests {
             "('A   d   i   E   u   ', False)"
         );
     }
+
+    #[test]
+    pub fn replace_path() {
+        let text = "A='/venv'\nB=/venv/bin\nC=\"/venv2/bin\"\nD=/venv";
+        assert_eq!(
+            super::replace_path(text, "/venv", "$DIR"),
+            "A='$DIR'\nB=$DIR/bin\nC=\"/venv2/bin\"\nD=$DIR"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn relocatable_activate_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().join("venv");
+        let script = format!(
+            "VIRTUAL_ENV='{}'\nexport VIRTUAL_ENV\necho \"$VIRTUAL_ENV\"\n",
+            location.display()
+        );
+        let script = super::relocatable_activate_script(&script, &location);
+        let moved = dir.path().join("moved");
+        std::fs::create_dir_all(moved.join("bin")).unwrap();
+        std::fs::write(moved.join("bin/activate"), script).unwrap();
+
+        // The script must be valid for any POSIX shell
+        let status = std::process::Command::new("sh")
+            .arg("-n")
+            .arg(moved.join("bin/activate"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        // Shells that expose the location of the script find the moved environment
+        if let Ok(output) = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(format!(". '{}'", moved.join("bin/activate").display()))
+            .output()
+        {
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap().trim(),
+                dunce::canonicalize(&moved).unwrap().display().to_string()
+            );
+        }
+    }
 }
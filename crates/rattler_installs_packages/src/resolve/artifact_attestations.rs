@@ -0,0 +1,67 @@
+use super::PinnedPackage;
+use crate::index::{AttestationPolicy, AttestationStatus, PackageDb};
+use crate::types::{ArtifactName, NormalizedPackageName};
+use futures::{stream, StreamExt, TryStreamExt};
+
+/// The result of checking the attestations of an artifact of a package, see
+/// [`check_attestations`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactAttestation {
+    /// The name of the package
+    pub package: NormalizedPackageName,
+
+    /// The artifact that was checked
+    pub artifact: ArtifactName,
+
+    /// The result of checking the attestations of the artifact
+    pub status: AttestationStatus,
+
+    /// Whether the artifact is acceptable under the policy that was used
+    pub accepted: bool,
+}
+
+/// Checks the [PEP 740](https://peps.python.org/pep-0740/) attestations of every artifact of the
+/// given packages, because any of them may end up being installed. Returns the result for every
+/// artifact in the order of `packages`, so callers can report the publishers of the artifacts or
+/// refuse to install rejected artifacts.
+///
+/// The signatures of the attestations are not verified, see [`crate::index::check_provenance`].
+///
+/// Nothing is fetched with [`AttestationPolicy::Ignore`], all artifacts are reported as accepted
+/// with [`AttestationStatus::NotPublished`].
+pub async fn check_attestations(
+    package_db: &PackageDb,
+    packages: &[PinnedPackage<'_>],
+    policy: AttestationPolicy,
+) -> miette::Result<Vec<ArtifactAttestation>> {
+    stream::iter(packages.iter().flat_map(|package| {
+        package
+            .artifacts
+            .iter()
+            .map(move |artifact_info| (package, *artifact_info))
+    }))
+    .map(|(package, artifact_info)| async move {
+        let status = if policy == AttestationPolicy::Ignore || artifact_info.provenance.is_none() {
+            AttestationStatus::NotPublished
+        } else {
+            // The attestations are checked against the hash of the artifact
+            let artifact_info = package_db.with_sha256(artifact_info).await?;
+            package_db.artifact_attestations(artifact_info).await?
+        };
+        if let AttestationStatus::Failed { reason } = &status {
+            tracing::warn!(
+                "the attestations of {} do not match the artifact: {reason}",
+                artifact_info.filename
+            );
+        }
+        Ok::<_, miette::Report>(ArtifactAttestation {
+            package: package.name.clone(),
+            artifact: artifact_info.filename.clone(),
+            accepted: policy.accepts(&status),
+            status,
+        })
+    })
+    .buffered(10)
+    .try_collect()
+    .await
+}
@@ -0,0 +1,195 @@
+//! Resolution that reports the requirements that cannot be satisfied instead of failing.
+//!
+//! [`resolve`] stops at the first conflict it finds. Tools that want to show the user all
+//! problems at once can use [`resolve_best_effort`] instead, which returns the environment for the
+//! largest set of requirements it can satisfy together with every requirement it had to drop.
+//!
+//! Requirements are added to the resolution one at a time in the given order, a requirement that
+//! conflicts with the requirements before it is dropped. The order therefore decides which of two
+//! conflicting requirements is kept: the earlier one wins.
+
+use super::solve::compute_missing_hashes;
+use super::{resolve, NoCompatibleArtifacts, PinnedPackage, ResolveOptions, Unsolvable};
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::types::NormalizedPackageName;
+use pep508_rs::{MarkerEnvironment, Requirement};
+use std::collections::HashMap;
+
+/// A requirement that had to be dropped by [`resolve_best_effort`].
+#[derive(Debug, Clone)]
+pub struct UnsatisfiableRequirement {
+    /// The requirement that was dropped
+    pub requirement: Requirement,
+
+    /// Why the requirement could not be satisfied together with the requirements before it
+    pub reason: String,
+
+    /// Advice on how to resolve the problem, if any
+    pub help: Option<String>,
+}
+
+/// The result of [`resolve_best_effort`].
+#[derive(Debug, Clone)]
+pub struct PartialResolution<'db> {
+    /// The environment that satisfies all requirements except the unsatisfiable ones
+    pub packages: Vec<PinnedPackage<'db>>,
+
+    /// The requirements that could not be satisfied, in the order they were passed in
+    pub unsatisfiable: Vec<UnsatisfiableRequirement>,
+}
+
+impl PartialResolution<'_> {
+    /// Returns true if all requirements were satisfied.
+    pub fn is_complete(&self) -> bool {
+        self.unsatisfiable.is_empty()
+    }
+}
+
+/// Like [`resolve`] but instead of failing at the first conflict, returns the environment of all
+/// requirements that can be satisfied together and the requirements that had to be dropped. See
+/// the [module documentation](self) for more information.
+///
+/// If all requirements can be satisfied this costs a single resolution, otherwise a resolution
+/// per requirement.
+pub async fn resolve_best_effort<'db>(
+    package_db: &'db PackageDb,
+    requirements: impl IntoIterator<Item = &Requirement>,
+    env_markers: &MarkerEnvironment,
+    compatible_tags: Option<&WheelTags>,
+    locked_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    favored_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    options: &ResolveOptions,
+) -> miette::Result<PartialResolution<'db>> {
+    let requirements = requirements.into_iter().cloned().collect::<Vec<_>>();
+
+    // Hashes are only computed for the final solution instead of for every attempt
+    let attempt_options = ResolveOptions {
+        compute_missing_hashes: false,
+        ..options.clone()
+    };
+    let attempt = |requirements: Vec<Requirement>| {
+        let locked_packages = locked_packages.clone();
+        let favored_packages = favored_packages.clone();
+        let attempt_options = &attempt_options;
+        async move {
+            resolve(
+                package_db,
+                &requirements,
+                env_markers,
+                compatible_tags,
+                locked_packages,
+                favored_packages,
+                attempt_options,
+            )
+            .await
+        }
+    };
+
+    // Most of the time all requirements can be satisfied
+    let (mut packages, unsatisfiable) = match attempt(requirements.clone()).await {
+        Ok(packages) => (packages, Vec::new()),
+        Err(err) if !is_unsolvable(&err) => return Err(err),
+        Err(_) => {
+            let mut satisfiable = Vec::with_capacity(requirements.len());
+            let mut packages = Vec::new();
+            let mut unsatisfiable = Vec::new();
+            for requirement in requirements {
+                satisfiable.push(requirement);
+                match attempt(satisfiable.clone()).await {
+                    Ok(solution) => packages = solution,
+                    Err(err) if !is_unsolvable(&err) => return Err(err),
+                    Err(err) => {
+                        let requirement = satisfiable.pop().expect("just pushed");
+                        tracing::warn!("dropping requirement '{requirement}': {err}");
+                        unsatisfiable.push(UnsatisfiableRequirement {
+                            requirement,
+                            reason: err.to_string(),
+                            help: err.help().map(|help| help.to_string()),
+                        });
+                    }
+                }
+            }
+            (packages, unsatisfiable)
+        }
+    };
+
+    if options.compute_missing_hashes {
+        compute_missing_hashes(package_db, &mut packages).await?;
+    }
+
+    Ok(PartialResolution {
+        packages,
+        unsatisfiable,
+    })
+}
+
+/// Returns true if `err` means that the requirements cannot be satisfied, as opposed to for
+/// instance a failure to reach the index.
+pub(super) fn is_unsolvable(err: &miette::Report) -> bool {
+    err.downcast_ref::<Unsolvable>().is_some()
+        || err.downcast_ref::<NoCompatibleArtifacts>().is_some()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use std::str::FromStr;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_best_effort() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+
+        let requirements = [
+            Requirement::from_str("wordle_python").unwrap(),
+            Requirement::from_str("wordle_python<2").unwrap(),
+            Requirement::from_str("purelib_and_platlib").unwrap(),
+            Requirement::from_str("does-not-exist").unwrap(),
+        ];
+        let resolution = resolve_best_effort(
+            package_db,
+            &requirements,
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!resolution.is_complete());
+        assert_eq!(
+            resolution
+                .unsatisfiable
+                .iter()
+                .map(|unsatisfiable| unsatisfiable.requirement.to_string())
+                .collect::<Vec<_>>(),
+            ["wordle_python<2", "does-not-exist"]
+        );
+        let names = resolution
+            .packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"wordle-python"));
+        assert!(names.contains(&"purelib-and-platlib"));
+    }
+
+    #[test]
+    fn test_only_unsolvable_errors_drop_requirements() {
+        assert!(is_unsolvable(
+            &Unsolvable {
+                message: String::from("foo 1.0 cannot be installed"),
+            }
+            .into()
+        ));
+        assert!(!is_unsolvable(&miette::miette!(
+            "failed to reach the index"
+        )));
+    }
+}
@@ -0,0 +1,171 @@
+//! A builder for [`resolve`].
+//!
+//! [`resolve`] takes all of its inputs as positional arguments, so every new input is a breaking
+//! change for all callers. [`ResolveBuilder`] only requires the inputs that every resolution needs
+//! and sets everything else through `with_*` methods, new inputs are added as new methods.
+
+use super::{resolve, PinnedPackage, ResolveOptions};
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::types::NormalizedPackageName;
+use pep508_rs::{MarkerEnvironment, Requirement};
+use std::collections::HashMap;
+
+/// Configures and runs a resolution, see the [module documentation](self).
+///
+/// ```no_run
+/// # use rattler_installs_packages::index::PackageDb;
+/// # use rattler_installs_packages::resolve::{ResolveBuilder, ResolveOptions, SDistResolution};
+/// # use pep508_rs::MarkerEnvironment;
+/// # async fn example(package_db: &PackageDb, env_markers: &MarkerEnvironment) -> miette::Result<()> {
+/// let mut options = ResolveOptions::default();
+/// options.sdist_resolution = SDistResolution::OnlyWheels;
+/// let packages = ResolveBuilder::new(package_db, env_markers)
+///     .with_requirement("rich".parse().unwrap())
+///     .with_options(options)
+///     .resolve()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ResolveBuilder<'db, 'i> {
+    package_db: &'db PackageDb,
+    env_markers: &'i MarkerEnvironment,
+    requirements: Vec<Requirement>,
+    compatible_tags: Option<&'i WheelTags>,
+    locked_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    favored_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    options: ResolveOptions,
+}
+
+impl<'db, 'i> ResolveBuilder<'db, 'i> {
+    /// Constructs a builder that resolves for the python interpreter described by `env_markers`
+    /// using the packages of `package_db`. Without requirements the resolution is empty.
+    pub fn new(package_db: &'db PackageDb, env_markers: &'i MarkerEnvironment) -> Self {
+        Self {
+            package_db,
+            env_markers,
+            requirements: Vec::new(),
+            compatible_tags: None,
+            locked_packages: HashMap::default(),
+            favored_packages: HashMap::default(),
+            options: ResolveOptions::default(),
+        }
+    }
+
+    /// Adds a requirement of a package that must be present in the environment.
+    pub fn with_requirement(mut self, requirement: Requirement) -> Self {
+        self.requirements.push(requirement);
+        self
+    }
+
+    /// Adds requirements of packages that must be present in the environment.
+    pub fn with_requirements(
+        mut self,
+        requirements: impl IntoIterator<Item = Requirement>,
+    ) -> Self {
+        self.requirements.extend(requirements);
+        self
+    }
+
+    /// Only select artifacts that are compatible with the given tags. By default artifacts are not
+    /// filtered by their tags.
+    pub fn with_compatible_tags(self, compatible_tags: &'i WheelTags) -> Self {
+        Self {
+            compatible_tags: Some(compatible_tags),
+            ..self
+        }
+    }
+
+    /// Packages that must be selected with exactly this version, see [`resolve`].
+    pub fn with_locked_packages(
+        self,
+        locked_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    ) -> Self {
+        Self {
+            locked_packages,
+            ..self
+        }
+    }
+
+    /// Packages of which this version is preferred if it satisfies the requirements, see
+    /// [`resolve`].
+    pub fn with_favored_packages(
+        self,
+        favored_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    ) -> Self {
+        Self {
+            favored_packages,
+            ..self
+        }
+    }
+
+    /// Sets the options that influence the resolver, see [`ResolveOptions`].
+    pub fn with_options(self, options: ResolveOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Returns the requirements that are resolved.
+    pub fn requirements(&self) -> &[Requirement] {
+        &self.requirements
+    }
+
+    /// Returns the options that influence the resolver.
+    pub fn options(&self) -> &ResolveOptions {
+        &self.options
+    }
+
+    /// Resolves the environment, see [`resolve`].
+    pub async fn resolve(self) -> miette::Result<Vec<PinnedPackage<'db>>> {
+        resolve(
+            self.package_db,
+            &self.requirements,
+            self.env_markers,
+            self.compatible_tags,
+            self.locked_packages,
+            self.favored_packages,
+            &self.options,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_builder() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+        let compatible_tags: WheelTags = ["cp310-cp310-linux_x86_64", "py3-none-any"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+
+        let packages = ResolveBuilder::new(package_db, &env_markers)
+            .with_requirement("wordle_python".parse().unwrap())
+            .with_compatible_tags(&compatible_tags)
+            .resolve()
+            .await
+            .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name.as_str(), "wordle-python");
+        assert!(packages[0]
+            .artifacts
+            .iter()
+            .all(|artifact| artifact.is::<crate::artifacts::Wheel>()));
+
+        // The tags of the linux wheel are not compatible
+        assert!(ResolveBuilder::new(package_db, &env_markers)
+            .with_requirements(["purelib_and_platlib".parse().unwrap()])
+            .with_compatible_tags(&compatible_tags)
+            .resolve()
+            .await
+            .is_err());
+    }
+}
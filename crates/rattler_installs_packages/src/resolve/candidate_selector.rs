@@ -0,0 +1,52 @@
+use crate::types::{ArtifactInfo, NormalizedPackageName, Version};
+use std::cmp::Ordering;
+
+/// A hook that allows callers to veto or reorder the versions of a package that the resolver
+/// considers, e.g. to enforce a policy like "no packages that were released less than 14 days
+/// ago". Set it with [`super::ResolveOptions::candidate_selector`].
+///
+/// The hook is invoked when the available versions of a package are collected, before any
+/// metadata of these versions is fetched. Locked and favored versions are not passed to the hook.
+///
+/// ```
+/// # use rattler_installs_packages::resolve::CandidateSelector;
+/// # use rattler_installs_packages::types::{ArtifactInfo, NormalizedPackageName, Version};
+/// /// Never select post releases.
+/// struct SkipPostReleases;
+///
+/// impl CandidateSelector for SkipPostReleases {
+///     fn exclude(
+///         &self,
+///         _name: &NormalizedPackageName,
+///         version: &Version,
+///         _artifacts: &[ArtifactInfo],
+///     ) -> Option<String> {
+///         version
+///             .post
+///             .is_some()
+///             .then(|| String::from("post releases are not allowed"))
+///     }
+/// }
+/// ```
+pub trait CandidateSelector: Send + Sync {
+    /// Returns the reason why `version` of the package `name` must not be selected, or `None` if
+    /// the version may be selected. `artifacts` contains all the artifacts of the version that
+    /// are available on the index. The reason is shown to the user if no solution can be found.
+    fn exclude(
+        &self,
+        name: &NormalizedPackageName,
+        version: &Version,
+        artifacts: &[ArtifactInfo],
+    ) -> Option<String> {
+        let _ = (name, version, artifacts);
+        None
+    }
+
+    /// Orders two versions of the package `name`. Versions that are ordered first are tried
+    /// first by the resolver. Return [`Ordering::Equal`] to fall back to the default order, which
+    /// selects the highest version.
+    fn compare(&self, name: &NormalizedPackageName, a: &Version, b: &Version) -> Ordering {
+        let _ = (name, a, b);
+        Ordering::Equal
+    }
+}
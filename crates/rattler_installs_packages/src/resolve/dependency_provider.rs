@@ -1,4 +1,7 @@
-use super::SDistResolution;
+use super::no_compatible_artifacts::{
+    IncompatibleWheels, NoCompatibleArtifacts, SDistAvailability,
+};
+use super::{LocalVersionPreference, ResolveEvent, SDistResolution};
 use crate::artifacts::SDist;
 use crate::artifacts::Wheel;
 use crate::index::PackageDb;
@@ -9,14 +12,16 @@ use crate::types::{
 };
 use crate::wheel_builder::WheelBuilder;
 use elsa::FrozenMap;
+use indexmap::IndexMap;
 use itertools::Itertools;
 use pep440_rs::{Operator, Version, VersionSpecifier, VersionSpecifiers};
 use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
 use resolvo::{
     Candidates, Dependencies, DependencyProvider, NameId, Pool, SolvableId, SolverCache, VersionSet,
 };
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use tokio::runtime::Handle;
@@ -47,10 +52,60 @@ impl Display for PypiVersionSet {
 /// This is a wrapper around [`Version`] that serves a version
 /// within the [`PypiVersionSet`] version set.
 #[derive(Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
-#[allow(dead_code)]
 pub(crate) enum PypiVersion {
     Version(Version),
-    Url(Url),
+
+    /// An artifact that is referenced directly by its url. The version is derived from the
+    /// filename of the artifact.
+    Url {
+        url: Url,
+        version: Version,
+
+        /// Whether the url is referenced by one of the requirements passed to the resolver. Those
+        /// are all known before solving starts, so only they also satisfy version requirements.
+        /// Direct references of dependencies are discovered while solving and only satisfy
+        /// requirements on their url, otherwise the selected version would depend on the order
+        /// in which the solver visits the packages.
+        root: bool,
+    },
+}
+
+impl PypiVersion {
+    /// Returns the version of the package
+    pub fn version(&self) -> &Version {
+        match self {
+            PypiVersion::Version(version) => version,
+            PypiVersion::Url { version, .. } => version,
+        }
+    }
+
+    /// Returns a version set that only contains this version
+    fn as_exact_version_set(&self) -> PypiVersionSet {
+        match self {
+            PypiVersion::Version(version) => {
+                let specifiers = VersionSpecifiers::from_iter([VersionSpecifier::new(
+                    Operator::ExactEqual,
+                    version.clone(),
+                    false,
+                )
+                .expect("failed to construct equality version specifier")]);
+                Some(VersionOrUrl::VersionSpecifier(specifiers)).into()
+            }
+            PypiVersion::Url { url, .. } => Some(VersionOrUrl::Url(url.clone())).into(),
+        }
+    }
+
+    /// Returns a version set that contains neither this version nor, if this is a direct
+    /// reference, the referenced artifact.
+    fn as_excluding_version_set(&self) -> PypiVersionSet {
+        let specifiers = VersionSpecifiers::from_iter([VersionSpecifier::new(
+            Operator::NotEqual,
+            self.version().clone(),
+            false,
+        )
+        .expect("failed to construct inequality version specifier")]);
+        Some(VersionOrUrl::VersionSpecifier(specifiers)).into()
+    }
 }
 
 impl VersionSet for PypiVersionSet {
@@ -58,9 +113,19 @@ impl VersionSet for PypiVersionSet {
 
     fn contains(&self, v: &Self::V) -> bool {
         match (self.0.as_ref(), v) {
-            (Some(VersionOrUrl::Url(a)), PypiVersion::Url(b)) => a == b,
-            (Some(VersionOrUrl::VersionSpecifier(spec)), PypiVersion::Version(v)) => {
-                spec.contains(v)
+            (Some(VersionOrUrl::Url(a)), PypiVersion::Url { url: b, .. }) => a == b,
+            // A direct reference of the root requirements satisfies a version requirement if the
+            // version of the artifact matches.
+            (
+                Some(VersionOrUrl::VersionSpecifier(spec)),
+                PypiVersion::Url {
+                    version,
+                    root: true,
+                    ..
+                },
+            ) => specifiers_contain(spec, version),
+            (Some(VersionOrUrl::VersionSpecifier(spec)), PypiVersion::Version(version)) => {
+                specifiers_contain(spec, version)
             }
             (None, _) => true,
             _ => false,
@@ -68,11 +133,27 @@ impl VersionSet for PypiVersionSet {
     }
 }
 
+/// Returns true if `version` matches all `specifiers`. As required by PEP 440, the local version
+/// label of `version` is ignored when matching against a specifier without a local label, e.g.
+/// `torch==2.1.0` matches `2.1.0+cpu`.
+fn specifiers_contain(specifiers: &VersionSpecifiers, version: &Version) -> bool {
+    let public_version = version.local.is_some().then(|| Version {
+        local: None,
+        ..version.clone()
+    });
+    specifiers.iter().all(|specifier| match &public_version {
+        Some(public_version) if specifier.version().local.is_none() => {
+            specifier.contains(public_version)
+        }
+        _ => specifier.contains(version),
+    })
+}
+
 impl Display for PypiVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            PypiVersion::Version(v) => write!(f, "{v}"),
-            PypiVersion::Url(u) => write!(f, "{u}"),
+            PypiVersion::Version(version) => write!(f, "{version}"),
+            PypiVersion::Url { url, version, .. } => write!(f, "{version} @ {url}"),
         }
     }
 }
@@ -127,6 +208,25 @@ pub(crate) struct PypiDependencyProvider<'db, 'i> {
     favored_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
     locked_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
 
+    /// The urls of direct references (e.g. `foo @ https://...`) per package and whether they are
+    /// part of the root requirements.
+    direct_references: RefCell<HashMap<NormalizedPackageName, IndexMap<Url, bool>>>,
+
+    /// The aliases of [`ResolveOptions::package_aliases`] that have been reported as used.
+    reported_aliases: RefCell<HashSet<NormalizedPackageName>>,
+
+    /// The version of each package (or extra) whose dependencies were requested last, to detect
+    /// when the solver backtracks.
+    considered_versions: RefCell<HashMap<NameId, SolvableId>>,
+
+    /// The packages of which versions were excluded because none of their wheels are compatible
+    /// with [`Self::compatible_tags`], as long as no version of the package can be selected.
+    incompatible_wheels: RefCell<HashMap<NormalizedPackageName, IncompatibleWheels>>,
+
+    /// The errors that occurred while retrieving the metadata of candidates, e.g. because the
+    /// download was rejected. These candidates are excluded from the solution.
+    metadata_errors: RefCell<Vec<miette::Report>>,
+
     options: &'i ResolveOptions,
 }
 
@@ -152,16 +252,194 @@ impl<'db, 'i> PypiDependencyProvider<'db, 'i> {
             cached_artifacts: Default::default(),
             favored_packages,
             locked_packages,
+            direct_references: Default::default(),
+            reported_aliases: Default::default(),
+            considered_versions: Default::default(),
+            incompatible_wheels: Default::default(),
+            metadata_errors: Default::default(),
             options,
         })
     }
 
-    fn filter_candidates<'a>(
+    /// Returns the name of the package that `name` is an alias of, or `name` itself if it is not
+    /// an alias. `required_by` is the package that requires `name`, or `None` for the
+    /// requirements passed to the resolver. The first use of each alias is reported.
+    pub fn resolve_alias(
+        &self,
+        name: NormalizedPackageName,
+        required_by: Option<&NormalizedPackageName>,
+    ) -> NormalizedPackageName {
+        let Some(resolved) = self.options.package_aliases.resolve(&name) else {
+            return name;
+        };
+
+        if self.reported_aliases.borrow_mut().insert(name.clone()) {
+            match required_by {
+                Some(required_by) => tracing::warn!(
+                    "{required_by} requires '{name}' which is an alias of '{resolved}', using '{resolved}' instead"
+                ),
+                None => tracing::warn!(
+                    "'{name}' is an alias of '{resolved}', using '{resolved}' instead"
+                ),
+            }
+            self.send_event(|| ResolveEvent::AliasUsed {
+                alias: name.clone(),
+                name: resolved.clone(),
+                required_by: required_by.cloned(),
+            });
+        }
+
+        resolved.clone()
+    }
+
+    /// Records the url of `version_or_url` if it is a direct reference, so the artifact it points
+    /// to becomes a candidate of the package. `from_root` specifies whether the requirement is
+    /// part of the requirements passed to the resolver.
+    pub fn add_direct_reference(
+        &self,
+        name: &NormalizedPackageName,
+        version_or_url: &Option<VersionOrUrl>,
+        from_root: bool,
+    ) {
+        if let Some(VersionOrUrl::Url(url)) = version_or_url {
+            let mut direct_references = self.direct_references.borrow_mut();
+            let is_root = direct_references
+                .entry(name.clone())
+                .or_default()
+                .entry(url.clone())
+                .or_default();
+            *is_root |= from_root;
+        }
+    }
+
+    /// Adds the artifacts of direct references to a package as candidates.
+    fn add_direct_reference_candidates(&self, name: NameId, candidates: &mut Candidates) {
+        let package_name = self.pool.resolve_package_name(name);
+        let direct_references = self.direct_references.borrow();
+        let Some(urls) = direct_references.get(package_name.base()) else {
+            return;
+        };
+
+        for (url, &from_root) in urls {
+            let artifact = match self
+                .package_db
+                .direct_reference_artifact(package_name.base(), url)
+            {
+                Ok(artifact) => artifact,
+                Err(err) => {
+                    tracing::error!("{err}, skipping..");
+                    continue;
+                }
+            };
+
+            let solvable_id = self.pool.intern_solvable(
+                name,
+                PypiVersion::Url {
+                    url: url.clone(),
+                    version: artifact.filename.version().clone(),
+                    root: from_root,
+                },
+            );
+            candidates.candidates.push(solvable_id);
+            self.cached_artifacts.insert(solvable_id, vec![artifact]);
+
+            if let Some(reason) = self.exclude_direct_reference(artifact, from_root) {
+                candidates
+                    .excluded
+                    .push((solvable_id, self.pool.intern_string(reason)));
+            }
+        }
+    }
+
+    /// Returns the only candidate of a package that is provided by the platform. It has no
+    /// artifacts, so it also has no dependencies.
+    fn provided_candidate(&self, name: NameId, version: &Version) -> Candidates {
+        let solvable_id = self
+            .pool
+            .intern_solvable(name, PypiVersion::Version(version.clone()));
+        self.cached_artifacts.insert(solvable_id, Vec::new());
+
+        let package_name = self.pool.resolve_package_name(name);
+        self.send_event(|| ResolveEvent::CandidatesCollected {
+            name: package_name.base().clone(),
+            extra: package_name.extra().cloned(),
+            candidates: 1,
+            excluded: 0,
+        });
+
+        Candidates {
+            candidates: vec![solvable_id],
+            ..Candidates::default()
+        }
+    }
+
+    /// Returns the reason why the artifact of a direct reference cannot be selected, if any.
+    fn exclude_direct_reference(
+        &self,
+        artifact: &ArtifactInfo,
+        from_root: bool,
+    ) -> Option<&'static str> {
+        if !self.options.direct_references.allows(from_root) {
+            return Some("direct references are not allowed");
+        }
+        match &artifact.filename {
+            ArtifactName::Wheel(_) if !self.options.sdist_resolution.allow_wheels() => {
+                Some("it is a wheel and wheels are not allowed")
+            }
+            ArtifactName::SDist(_) if !self.options.sdist_resolution.allow_sdists() => {
+                Some("it is an sdist and sdists are not allowed")
+            }
+            ArtifactName::Wheel(wheel) => self
+                .compatible_tags
+                .filter(|tags| !wheel.all_tags_iter().any(|tag| tags.is_compatible(&tag)))
+                .map(|_| {
+                    "the wheel is not compatible with the Python interpreter or glibc version"
+                }),
+            ArtifactName::SDist(_) => None,
+        }
+    }
+
+    /// Removes the artifacts that are denied by the package rules. Returns the rule that blocked
+    /// the version if all of its artifacts are denied.
+    fn filter_package_rules<'a>(
         &self,
+        name: &NormalizedPackageName,
         artifacts: &'a [ArtifactInfo],
+    ) -> Result<Vec<&'a ArtifactInfo>, String> {
+        let package_rules = &self.options.package_rules;
+        if package_rules.is_empty() {
+            return Ok(artifacts.iter().collect());
+        }
+
+        let mut blocked = None;
+        let allowed = artifacts
+            .iter()
+            .filter(|artifact| {
+                let index_url = self.package_db.artifact_index_url(artifact);
+                match package_rules.blocked_by(name, index_url) {
+                    Some(blocked_by) => {
+                        tracing::debug!("ignoring {} because {blocked_by}", artifact.filename);
+                        blocked.get_or_insert(blocked_by);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        match blocked {
+            Some(blocked_by) if allowed.is_empty() => Err(blocked_by.to_string()),
+            _ => Ok(allowed),
+        }
+    }
+
+    fn filter_candidates<'a>(
+        &self,
+        name: &NormalizedPackageName,
+        artifacts: Vec<&'a ArtifactInfo>,
     ) -> Result<Vec<&'a ArtifactInfo>, &'static str> {
         let mut artifacts = artifacts
-            .iter()
+            .into_iter()
             .filter(|a| a.filename.version().pre.is_none() && a.filename.version().dev.is_none())
             .collect::<Vec<_>>();
 
@@ -176,12 +454,33 @@ impl<'db, 'i> PypiDependencyProvider<'db, 'i> {
             return Err("there are no packages available");
         }
 
+        // Filter artifacts that were uploaded after the cut-off time
+        if let Some(exclude_newer) = self.options.exclude_newer {
+            artifacts.retain(|a| a.upload_time.is_some_and(|time| time <= exclude_newer));
+            if artifacts.is_empty() {
+                return Err("none of the artifacts were uploaded before the cut-off time");
+            }
+        }
+
         // Filter yanked artifacts
         artifacts.retain(|a| !a.yanked.yanked);
         if artifacts.is_empty() {
             return Err("it is yanked");
         }
 
+        // Filter artifacts that do not support the python version of the environment
+        if self.options.check_requires_python {
+            let python_version = &self.markers.python_full_version.version;
+            artifacts.retain(|a| {
+                a.requires_python.as_ref().map_or(true, |requires_python| {
+                    requires_python.contains(python_version)
+                })
+            });
+            if artifacts.is_empty() {
+                return Err("none of the artifacts support the python version of the environment");
+            }
+        }
+
         // This should keep only the wheels
         let mut wheels = if self.options.sdist_resolution.allow_wheels() {
             let wheels = artifacts
@@ -239,28 +538,53 @@ impl<'db, 'i> PypiDependencyProvider<'db, 'i> {
                         .any(|t| compatible_tags.is_compatible(&t)),
                     ArtifactName::SDist(_) => false,
                 });
+            }
 
-                // Sort the artifacts from most compatible to least compatible, this ensures that we
-                // check the most compatible artifacts for dependencies first.
-                // this only needs to be done for wheels
-                wheels.sort_by_cached_key(|a| {
-                    -a.filename
-                        .as_wheel()
-                        .expect("only wheels are considered")
+            // Sort the artifacts from most compatible to least compatible, this ensures that we
+            // check the most compatible artifacts for dependencies first. Wheels that are equally
+            // compatible are ordered by their build tag, the highest build tag first.
+            // this only needs to be done for wheels
+            wheels.sort_by_cached_key(|a| {
+                let wheel = a.filename.as_wheel().expect("only wheels are considered");
+                let compatibility = self.compatible_tags.map_or(0, |compatible_tags| {
+                    wheel
                         .all_tags_iter()
                         .filter_map(|tag| compatible_tags.compatibility(&tag))
                         .max()
                         .unwrap_or(0)
                 });
+                (Reverse(compatibility), Reverse(wheel.build_tag.clone()))
+            });
+
+            // Move abi3 wheels to the front while keeping the compatibility order within each
+            // group.
+            if self.options.prefer_abi3 {
+                wheels.sort_by_key(|a| {
+                    !a.filename
+                        .as_wheel()
+                        .is_some_and(|wheel| wheel.all_tags_iter().any(|tag| tag.is_abi3()))
+                });
             }
 
             if !self.options.sdist_resolution.allow_sdists() && wheels.is_empty() {
+                let sdists = if artifacts.iter().any(|a| a.is::<SDist>()) {
+                    SDistAvailability::ExcludedByPolicy
+                } else {
+                    SDistAvailability::Missing
+                };
+                self.record_incompatible_wheels(name, &artifacts, sdists);
                 return Err(
                     "none of the artifacts are compatible with the Python interpreter or glibc version",
                 );
             }
 
             if wheels.is_empty() && sdists.is_empty() {
+                let sdists = if artifacts.iter().any(|a| a.is::<SDist>()) {
+                    SDistAvailability::Unsupported
+                } else {
+                    SDistAvailability::Missing
+                };
+                self.record_incompatible_wheels(name, &artifacts, sdists);
                 return Err("none of the artifacts are compatible with the Python interpreter or glibc version and there are no supported sdists");
             }
         }
@@ -276,6 +600,45 @@ impl<'db, 'i> PypiDependencyProvider<'db, 'i> {
         Ok(artifacts)
     }
 
+    /// Records that none of the wheels in `artifacts` are compatible with the environment.
+    fn record_incompatible_wheels(
+        &self,
+        name: &NormalizedPackageName,
+        artifacts: &[&ArtifactInfo],
+        sdists: SDistAvailability,
+    ) {
+        let tags = artifacts
+            .iter()
+            .filter_map(|a| a.filename.as_wheel())
+            .flat_map(|wheel| wheel.all_tags_iter());
+        self.incompatible_wheels
+            .borrow_mut()
+            .entry(name.clone())
+            .or_default()
+            .add_version(tags, sdists);
+    }
+
+    /// Takes the errors that occurred while retrieving the metadata of candidates, in the order
+    /// in which they occurred.
+    pub fn take_metadata_errors(&self) -> Vec<miette::Report> {
+        std::mem::take(&mut *self.metadata_errors.borrow_mut())
+    }
+
+    /// Returns the errors for the packages that have versions on the index but of which no
+    /// version can be selected because none of their wheels are compatible with the environment,
+    /// ordered by name.
+    pub fn no_compatible_artifacts(&self) -> Vec<NoCompatibleArtifacts> {
+        let Some(compatible_tags) = self.compatible_tags else {
+            return Vec::new();
+        };
+        self.incompatible_wheels
+            .take()
+            .into_iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(name, incompatible)| incompatible.into_error(name, compatible_tags))
+            .collect()
+    }
+
     fn solvable_has_artifact_type<S: Artifact>(&self, solvable_id: SolvableId) -> bool {
         self.cached_artifacts
             .get(&solvable_id)
@@ -283,6 +646,13 @@ impl<'db, 'i> PypiDependencyProvider<'db, 'i> {
             .iter()
             .any(|a| a.is::<S>())
     }
+
+    /// Sends an event to [`ResolveOptions::events`] if events are requested.
+    fn send_event(&self, event: impl FnOnce() -> ResolveEvent) {
+        if let Some(events) = &self.options.events {
+            events.send(event());
+        }
+    }
 }
 
 impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
@@ -321,18 +691,28 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
 
             let solvable_a = solver.pool().resolve_solvable(a);
             let solvable_b = solver.pool().resolve_solvable(b);
+            let package_name = solver.pool().resolve_package_name(solvable_a.name_id());
 
             match (&solvable_a.inner(), &solvable_b.inner()) {
                 // Sort Urls alphabetically
                 // TODO: Do better
-                (PypiVersion::Url(a), PypiVersion::Url(b)) => a.cmp(b),
+                (PypiVersion::Url { url: a, .. }, PypiVersion::Url { url: b, .. }) => a.cmp(b),
 
                 // Prefer Urls over versions
-                (PypiVersion::Url(_), PypiVersion::Version(_)) => Ordering::Greater,
-                (PypiVersion::Version(_), PypiVersion::Url(_)) => Ordering::Less,
+                (PypiVersion::Url { .. }, PypiVersion::Version(_)) => Ordering::Less,
+                (PypiVersion::Version(_), PypiVersion::Url { .. }) => Ordering::Greater,
 
-                // Sort versions from highest to lowest
-                (PypiVersion::Version(a), PypiVersion::Version(b)) => b.cmp(a),
+                // Sort versions from highest to lowest, taking the order of the candidate selector
+                // and the preference for local versions into account.
+                (PypiVersion::Version(a), PypiVersion::Version(b)) => self
+                    .options
+                    .candidate_selector
+                    .as_ref()
+                    .map_or(Ordering::Equal, |selector| {
+                        selector.compare(package_name.base(), a, b)
+                    })
+                    .then_with(|| compare_local_versions(a, b, self.options.local_versions))
+                    .then_with(|| b.cmp(a)),
             }
         })
     }
@@ -340,6 +720,16 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
     fn get_candidates(&self, name: NameId) -> Option<Candidates> {
         let package_name = self.pool.resolve_package_name(name);
         tracing::info!("collecting {}", package_name);
+        self.send_event(|| ResolveEvent::CollectingCandidates {
+            name: package_name.base().clone(),
+            extra: package_name.extra().cloned(),
+        });
+
+        // Provided packages are not looked up on the index
+        if let Some(version) = self.options.provided_packages.get(package_name.base()) {
+            tracing::info!("{} is provided by the platform", package_name.base());
+            return Some(self.provided_candidate(name, version));
+        }
 
         // Get all the metadata for this package
         let result = task::block_in_place(move || {
@@ -375,8 +765,32 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
                 .intern_solvable(name, PypiVersion::Version(version.clone()));
             candidates.candidates.push(solvable_id);
 
+            // Ask the candidate selector whether this version may be selected at all
+            if let Some(reason) = self
+                .options
+                .candidate_selector
+                .as_ref()
+                .and_then(|selector| selector.exclude(package_name.base(), version, artifacts))
+            {
+                candidates
+                    .excluded
+                    .push((solvable_id, self.pool.intern_string(reason)));
+                continue;
+            }
+
+            // Remove the artifacts that are denied by the package rules
+            let artifacts = match self.filter_package_rules(package_name.base(), artifacts) {
+                Ok(artifacts) => artifacts,
+                Err(reason) => {
+                    candidates
+                        .excluded
+                        .push((solvable_id, self.pool.intern_string(reason)));
+                    continue;
+                }
+            };
+
             // Determine the candidates
-            match self.filter_candidates(artifacts) {
+            match self.filter_candidates(package_name.base(), artifacts) {
                 Ok(artifacts) => {
                     self.cached_artifacts.insert(solvable_id, artifacts);
                 }
@@ -410,21 +824,60 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
                 .insert(solvable_id, favored.artifacts.clone());
         }
 
+        // Add the artifacts that are referenced directly by url
+        self.add_direct_reference_candidates(name, &mut candidates);
+
+        // Incompatible wheels are only worth reporting if no version can be selected at all
+        if candidates.candidates.len() > candidates.excluded.len() {
+            self.incompatible_wheels
+                .borrow_mut()
+                .remove(package_name.base());
+        }
+
+        self.send_event(|| ResolveEvent::CandidatesCollected {
+            name: package_name.base().clone(),
+            extra: package_name.extra().cloned(),
+            candidates: candidates.candidates.len() - candidates.excluded.len(),
+            excluded: candidates.excluded.len(),
+        });
+
         Some(candidates)
     }
 
     fn get_dependencies(&self, solvable_id: SolvableId) -> Dependencies {
         let solvable = self.pool.resolve_solvable(solvable_id);
         let package_name = self.pool.resolve_package_name(solvable.name_id());
-        let PypiVersion::Version(package_version) = solvable.inner() else {
-            unimplemented!("cannot get dependencies of wheels by url yet")
-        };
+        let package_version = solvable.inner().version();
 
         tracing::info!(
             "obtaining dependency information from {}={}",
             package_name,
             package_version
         );
+        // The solver only requests the dependencies of the version it selects, so if it requests
+        // the dependencies of another version of the same package it abandoned the previous one.
+        let previous = self
+            .considered_versions
+            .borrow_mut()
+            .insert(solvable.name_id(), solvable_id);
+        if let Some(previous) = previous.filter(|&previous| previous != solvable_id) {
+            self.send_event(|| ResolveEvent::Backtracked {
+                name: package_name.base().clone(),
+                extra: package_name.extra().cloned(),
+                from: self
+                    .pool
+                    .resolve_solvable(previous)
+                    .inner()
+                    .version()
+                    .clone(),
+                to: package_version.clone(),
+            });
+        }
+        self.send_event(|| ResolveEvent::ConsideringCandidate {
+            name: package_name.base().clone(),
+            extra: package_name.extra().cloned(),
+            version: package_version.clone(),
+        });
 
         let mut dependencies = Dependencies::default();
 
@@ -435,16 +888,9 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
                 .pool
                 .lookup_package_name(&PypiPackageName::Base(package_name.clone()))
                 .expect("base package not found while resolving extra");
-            let specifiers = VersionSpecifiers::from_iter([VersionSpecifier::new(
-                Operator::ExactEqual,
-                package_version.clone(),
-                false,
-            )
-            .expect("failed to construct equality version specifier")]);
-            let version_set_id = self.pool.intern_version_set(
-                base_name_id,
-                Some(VersionOrUrl::VersionSpecifier(specifiers)).into(),
-            );
+            let version_set_id = self
+                .pool
+                .intern_version_set(base_name_id, solvable.inner().as_exact_version_set());
             dependencies.requirements.push(version_set_id);
         }
 
@@ -459,15 +905,32 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
             return dependencies;
         }
 
-        let Some((_, metadata)) = task::block_in_place(|| {
+        let metadata = task::block_in_place(|| {
             // First try getting wheels
-            Handle::current()
-                .block_on(
-                    self.package_db
-                        .get_metadata(artifacts, Some(&self.wheel_builder)),
-                )
-                .unwrap()
-        }) else {
+            Handle::current().block_on(
+                self.package_db
+                    .get_metadata(artifacts, Some(&self.wheel_builder)),
+            )
+        });
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                // Without its metadata the candidate cannot be selected. Requiring another version
+                // of the package itself makes the solver reject it, the error is reported when no
+                // solution can be found.
+                tracing::debug!("excluding {} {}: {err}", package_name, package_version);
+                self.metadata_errors.borrow_mut().push(err);
+                let name_id = self
+                    .pool
+                    .intern_package_name(PypiPackageName::Base(package_name.base().clone()));
+                let version_set_id = self
+                    .pool
+                    .intern_version_set(name_id, solvable.inner().as_excluding_version_set());
+                dependencies.requirements.push(version_set_id);
+                return dependencies;
+            }
+        };
+        let Some((_, metadata)) = metadata else {
             panic!(
                 "could not find metadata for any sdist or wheel for {} {}. The following artifacts are available:\n{}",
                 package_name, package_version, artifacts.iter().format_with("\n", |a, f| f(&format_args!("- {}", a.filename)))
@@ -481,16 +944,9 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
                 let extra_name_id = self
                     .pool
                     .intern_package_name(PypiPackageName::Extra(package_name.clone(), extra));
-                let specifiers = VersionSpecifiers::from_iter([VersionSpecifier::new(
-                    Operator::ExactEqual,
-                    package_version.clone(),
-                    false,
-                )
-                .expect("failed to construct equality version specifier")]);
-                let version_set_id = self.pool.intern_version_set(
-                    extra_name_id,
-                    Some(VersionOrUrl::VersionSpecifier(specifiers)).into(),
-                );
+                let version_set_id = self
+                    .pool
+                    .intern_version_set(extra_name_id, solvable.inner().as_exact_version_set());
                 dependencies.constrains.push(version_set_id);
             }
         }
@@ -516,13 +972,33 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
                 ..
             } = requirement;
 
-            let name = PackageName::from_str(&name).expect("invalid package name");
+            let name: NormalizedPackageName = PackageName::from_str(&name)
+                .expect("invalid package name")
+                .into();
+            let name = self.resolve_alias(name, Some(package_name.base()));
+            let version_set = if &name == package_name.base() {
+                // A package that requires itself with other extras (e.g. `foo[all]` requiring
+                // `foo[a,b]`) refers to this very version of itself. Pin the requirement so the
+                // extras are unioned on this version instead of being resolved independently. If
+                // the requirement excludes this version it's kept as is so this candidate is
+                // rejected.
+                let version_set = PypiVersionSet::from(version_or_url);
+                if version_set.contains(solvable.inner()) {
+                    solvable.inner().as_exact_version_set()
+                } else {
+                    version_set
+                }
+            } else {
+                self.add_direct_reference(&name, &version_or_url, false);
+                version_or_url.into()
+            };
+
             let dependency_name_id = self
                 .pool
-                .intern_package_name(PypiPackageName::Base(name.clone().into()));
+                .intern_package_name(PypiPackageName::Base(name.clone()));
             let version_set_id = self
                 .pool
-                .intern_version_set(dependency_name_id, version_or_url.clone().into());
+                .intern_version_set(dependency_name_id, version_set.clone());
             dependencies.requirements.push(version_set_id);
 
             // Add a unique package for each extra/optional dependency
@@ -530,10 +1006,10 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
                 let extra = Extra::from_str(&extra).expect("invalid extra name");
                 let dependency_name_id = self
                     .pool
-                    .intern_package_name(PypiPackageName::Extra(name.clone().into(), extra));
+                    .intern_package_name(PypiPackageName::Extra(name.clone(), extra));
                 let version_set_id = self
                     .pool
-                    .intern_version_set(dependency_name_id, version_or_url.clone().into());
+                    .intern_version_set(dependency_name_id, version_set.clone());
                 dependencies.requirements.push(version_set_id);
             }
         }
@@ -541,3 +1017,129 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName>
         dependencies
     }
 }
+
+/// Orders two versions that only differ in their local version label (e.g. `2.1.0` and
+/// `2.1.0+cu118`) according to the given preference. Returns [`Ordering::Equal`] if the public
+/// versions differ or if local versions should be ordered like any other version.
+fn compare_local_versions(
+    a: &Version,
+    b: &Version,
+    preference: LocalVersionPreference,
+) -> Ordering {
+    if preference == LocalVersionPreference::Normal || a.local.is_some() == b.local.is_some() {
+        return Ordering::Equal;
+    }
+
+    let public_a = Version {
+        local: None,
+        ..a.clone()
+    };
+    let public_b = Version {
+        local: None,
+        ..b.clone()
+    };
+    if public_a != public_b {
+        return Ordering::Equal;
+    }
+
+    match preference {
+        LocalVersionPreference::Prefer => b.local.is_some().cmp(&a.local.is_some()),
+        LocalVersionPreference::Avoid => a.local.is_some().cmp(&b.local.is_some()),
+        LocalVersionPreference::Normal => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare_local_versions() {
+        let version = |v: &str| Version::from_str(v).unwrap();
+        let public = version("2.1.0");
+        let local = version("2.1.0+cu118");
+
+        assert_eq!(
+            compare_local_versions(&local, &public, LocalVersionPreference::Normal),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_local_versions(&local, &public, LocalVersionPreference::Prefer),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_local_versions(&local, &public, LocalVersionPreference::Avoid),
+            Ordering::Greater
+        );
+
+        // Different public versions are ordered by version
+        assert_eq!(
+            compare_local_versions(
+                &version("2.0.0+cu118"),
+                &public,
+                LocalVersionPreference::Prefer
+            ),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_matches_public_version() {
+        let spec = |s: &str| {
+            PypiVersionSet::from(Some(VersionOrUrl::VersionSpecifier(
+                VersionSpecifiers::from_str(s).unwrap(),
+            )))
+        };
+        let version = |v: &str| PypiVersion::Version(Version::from_str(v).unwrap());
+
+        // Specifiers without a local label ignore the local label of the version
+        assert!(spec("==2.1.0").contains(&version("2.1.0+cpu")));
+        assert!(spec(">=2.1,<2.2").contains(&version("2.1.0+cu118")));
+        assert!(!spec("!=2.1.0").contains(&version("2.1.0+cpu")));
+        assert!(!spec("==2.0.0").contains(&version("2.1.0+cpu")));
+
+        // Specifiers with a local label still require that label
+        assert!(spec("==2.1.0+cpu").contains(&version("2.1.0+cpu")));
+        assert!(!spec("==2.1.0+cu118").contains(&version("2.1.0+cpu")));
+    }
+
+    #[test]
+    fn test_direct_reference_version_set() {
+        let url = Url::parse("https://example.com/foo-1.0-py3-none-any.whl").unwrap();
+        let direct_reference = PypiVersion::Url {
+            url: url.clone(),
+            version: Version::from_str("1.0").unwrap(),
+            root: true,
+        };
+        let spec = |s: &str| {
+            PypiVersionSet::from(Some(VersionOrUrl::VersionSpecifier(
+                VersionSpecifiers::from_str(s).unwrap(),
+            )))
+        };
+
+        // A direct reference matches its own url and version requirements on its version
+        assert!(
+            PypiVersionSet::from(Some(VersionOrUrl::Url(url.clone()))).contains(&direct_reference)
+        );
+        assert!(spec(">=1.0").contains(&direct_reference));
+        assert!(!spec(">1.0").contains(&direct_reference));
+        assert!(direct_reference
+            .as_exact_version_set()
+            .contains(&direct_reference));
+
+        // A direct reference of a dependency only matches its own url
+        let dependency_reference = PypiVersion::Url {
+            url: url.clone(),
+            version: Version::from_str("1.0").unwrap(),
+            root: false,
+        };
+        assert!(PypiVersionSet::from(Some(VersionOrUrl::Url(url.clone())))
+            .contains(&dependency_reference));
+        assert!(!spec(">=1.0").contains(&dependency_reference));
+
+        // A url never matches a version from the index
+        let other_url = Url::parse("https://example.com/foo-1.0.tar.gz").unwrap();
+        assert!(!PypiVersionSet::from(Some(VersionOrUrl::Url(other_url)))
+            .contains(&PypiVersion::Version(Version::from_str("1.0").unwrap())));
+    }
+}
@@ -0,0 +1,325 @@
+//! Compares two resolutions or lock files and reports what changed between them.
+
+use super::{LockedPackage, PinnedPackage};
+use crate::types::NormalizedPackageName;
+use pep440_rs::Version;
+use rattler_digest::Sha256Hash;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// The most significant part of a version that changed between two versions.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VersionDelta {
+    /// The epoch or the major version changed.
+    Major,
+
+    /// The minor version changed.
+    Minor,
+
+    /// The patch version changed.
+    Patch,
+
+    /// Only another part of the version changed (e.g. a pre-release or post-release segment).
+    Other,
+}
+
+impl VersionDelta {
+    /// Determines the most significant part of the version that differs between `from` and `to`.
+    pub fn between(from: &Version, to: &Version) -> Self {
+        if from.epoch != to.epoch {
+            return VersionDelta::Major;
+        }
+        let segment =
+            |version: &Version, idx: usize| version.release.get(idx).copied().unwrap_or(0);
+        if segment(from, 0) != segment(to, 0) {
+            VersionDelta::Major
+        } else if segment(from, 1) != segment(to, 1) {
+            VersionDelta::Minor
+        } else if segment(from, 2) != segment(to, 2) {
+            VersionDelta::Patch
+        } else {
+            VersionDelta::Other
+        }
+    }
+}
+
+impl Display for VersionDelta {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionDelta::Major => write!(f, "major"),
+            VersionDelta::Minor => write!(f, "minor"),
+            VersionDelta::Patch => write!(f, "patch"),
+            VersionDelta::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// A single change between two resolutions, computed by [`diff_resolutions`] or
+/// [`diff_locked_packages`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PackageChange {
+    /// The package is only present in the new resolution.
+    Added {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The version of the package in the new resolution
+        version: Version,
+    },
+
+    /// The package is only present in the old resolution.
+    Removed {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The version of the package in the old resolution
+        version: Version,
+    },
+
+    /// The version of the package increased.
+    Upgraded {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The version of the package in the old resolution
+        from: Version,
+        /// The version of the package in the new resolution
+        to: Version,
+        /// The most significant part of the version that changed
+        delta: VersionDelta,
+    },
+
+    /// The version of the package decreased.
+    Downgraded {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The version of the package in the old resolution
+        from: Version,
+        /// The version of the package in the new resolution
+        to: Version,
+        /// The most significant part of the version that changed
+        delta: VersionDelta,
+    },
+
+    /// The version of the package is the same but its artifacts differ, e.g. because a new wheel
+    /// was published or the hash of an artifact changed.
+    ArtifactsChanged {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The version of the package in both resolutions
+        version: Version,
+    },
+}
+
+impl PackageChange {
+    /// Returns the name of the package that changed.
+    pub fn name(&self) -> &NormalizedPackageName {
+        match self {
+            PackageChange::Added { name, .. }
+            | PackageChange::Removed { name, .. }
+            | PackageChange::Upgraded { name, .. }
+            | PackageChange::Downgraded { name, .. }
+            | PackageChange::ArtifactsChanged { name, .. } => name,
+        }
+    }
+}
+
+impl Display for PackageChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageChange::Added { name, version } => write!(f, "+ {name} {version}"),
+            PackageChange::Removed { name, version } => write!(f, "- {name} {version}"),
+            PackageChange::Upgraded {
+                name,
+                from,
+                to,
+                delta,
+            } => write!(f, "↑ {name} {from} -> {to} ({delta})"),
+            PackageChange::Downgraded {
+                name,
+                from,
+                to,
+                delta,
+            } => write!(f, "↓ {name} {from} -> {to} ({delta})"),
+            PackageChange::ArtifactsChanged { name, version } => {
+                write!(f, "~ {name} {version} (artifacts changed)")
+            }
+        }
+    }
+}
+
+/// The state of a single package that is compared.
+struct PackageState<'a> {
+    version: &'a Version,
+    artifacts: BTreeSet<(String, Option<&'a Sha256Hash>)>,
+}
+
+/// Computes the changes between two resolutions. The changes are sorted by package name.
+pub fn diff_resolutions(
+    old: &[PinnedPackage<'_>],
+    new: &[PinnedPackage<'_>],
+) -> Vec<PackageChange> {
+    diff(pinned_states(old), pinned_states(new))
+}
+
+/// Computes the changes between the packages of two lock files, see
+/// [`super::parse_lock_file`]. The changes are sorted by package name.
+pub fn diff_locked_packages(old: &[LockedPackage], new: &[LockedPackage]) -> Vec<PackageChange> {
+    diff(locked_states(old), locked_states(new))
+}
+
+fn pinned_states<'a>(
+    packages: &'a [PinnedPackage<'_>],
+) -> BTreeMap<&'a NormalizedPackageName, PackageState<'a>> {
+    packages
+        .iter()
+        .map(|package| {
+            let artifacts = package
+                .artifacts
+                .iter()
+                .map(|artifact| {
+                    (
+                        artifact.filename.to_string(),
+                        artifact.hashes.as_ref().and_then(|h| h.sha256.as_ref()),
+                    )
+                })
+                .collect();
+            (
+                &package.name,
+                PackageState {
+                    version: &package.version,
+                    artifacts,
+                },
+            )
+        })
+        .collect()
+}
+
+fn locked_states(packages: &[LockedPackage]) -> BTreeMap<&NormalizedPackageName, PackageState<'_>> {
+    packages
+        .iter()
+        .map(|package| {
+            let artifacts = package
+                .files
+                .iter()
+                .map(|file| (file.filename.clone(), file.sha256.as_ref()))
+                .collect();
+            (
+                &package.name,
+                PackageState {
+                    version: &package.version,
+                    artifacts,
+                },
+            )
+        })
+        .collect()
+}
+
+fn diff(
+    old: BTreeMap<&NormalizedPackageName, PackageState<'_>>,
+    mut new: BTreeMap<&NormalizedPackageName, PackageState<'_>>,
+) -> Vec<PackageChange> {
+    let mut changes = Vec::new();
+    for (name, old_state) in old {
+        let Some(new_state) = new.remove(name) else {
+            changes.push(PackageChange::Removed {
+                name: name.clone(),
+                version: old_state.version.clone(),
+            });
+            continue;
+        };
+
+        let from = old_state.version;
+        let to = new_state.version;
+        if from < to {
+            changes.push(PackageChange::Upgraded {
+                name: name.clone(),
+                from: from.clone(),
+                to: to.clone(),
+                delta: VersionDelta::between(from, to),
+            });
+        } else if from > to {
+            changes.push(PackageChange::Downgraded {
+                name: name.clone(),
+                from: from.clone(),
+                to: to.clone(),
+                delta: VersionDelta::between(from, to),
+            });
+        } else if old_state.artifacts != new_state.artifacts {
+            changes.push(PackageChange::ArtifactsChanged {
+                name: name.clone(),
+                version: to.clone(),
+            });
+        }
+    }
+
+    changes.extend(new.into_iter().map(|(name, state)| PackageChange::Added {
+        name: name.clone(),
+        version: state.version.clone(),
+    }));
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resolve::LockedFile;
+    use crate::types::PackageName;
+    use itertools::Itertools;
+    use std::str::FromStr;
+
+    fn locked(name: &str, version: &str, files: &[&str]) -> LockedPackage {
+        LockedPackage {
+            name: PackageName::from_str(name).unwrap().into(),
+            version: version.parse().unwrap(),
+            extras: Default::default(),
+            files: files
+                .iter()
+                .map(|filename| LockedFile {
+                    filename: filename.to_string(),
+                    url: None,
+                    sha256: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_locked_packages() {
+        let old = [
+            locked("black", "22.12.0", &["black-22.12.0-py3-none-any.whl"]),
+            locked("click", "8.1.7", &["click-8.1.7-py3-none-any.whl"]),
+            locked("idna", "3.4", &["idna-3.4-py3-none-any.whl"]),
+            locked("six", "1.16.0", &["six-1.16.0.tar.gz"]),
+            locked("urllib3", "2.0.7", &["urllib3-2.0.7-py3-none-any.whl"]),
+        ];
+        let new = [
+            locked("black", "23.1.0", &["black-23.1.0-py3-none-any.whl"]),
+            locked("click", "8.1.7", &["click-8.1.7-py3-none-any.whl"]),
+            locked("requests", "2.31.0", &["requests-2.31.0-py3-none-any.whl"]),
+            locked(
+                "six",
+                "1.16.0",
+                &["six-1.16.0.tar.gz", "six-1.16.0-py2.py3-none-any.whl"],
+            ),
+            locked("urllib3", "1.26.18", &["urllib3-1.26.18-py3-none-any.whl"]),
+        ];
+
+        let changes = diff_locked_packages(&old, &new);
+        insta::assert_snapshot!(changes.iter().format("\n"), @r###"
+        ↑ black 22.12.0 -> 23.1.0 (major)
+        - idna 3.4
+        + requests 2.31.0
+        ~ six 1.16.0 (artifacts changed)
+        ↓ urllib3 2.0.7 -> 1.26.18 (major)
+        "###);
+
+        assert_eq!(
+            VersionDelta::between(&"1.2.3".parse().unwrap(), &"1.3".parse().unwrap()),
+            VersionDelta::Minor
+        );
+        assert_eq!(
+            VersionDelta::between(&"1.2".parse().unwrap(), &"1.2.0.post1".parse().unwrap()),
+            VersionDelta::Other
+        );
+        assert!(diff_locked_packages(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use super::PinnedPackage;
+use crate::index::PackageDb;
+use crate::types::{ArtifactInfo, ArtifactName};
+use futures::{stream, StreamExt, TryStreamExt};
+
+/// The estimated number of bytes that need to be downloaded to install a set of
+/// [`PinnedPackage`]s. Computed by [`estimate_download_size`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadSizeEstimate {
+    /// The total number of bytes that still have to be downloaded.
+    pub download_bytes: u64,
+
+    /// The total number of bytes of artifacts that are already available in the local cache.
+    pub cached_bytes: u64,
+
+    /// The artifacts for which the size could not be determined, either because the remote did
+    /// not report it or because requesting it failed. These are not included in `download_bytes`.
+    pub unknown: Vec<ArtifactName>,
+}
+
+impl DownloadSizeEstimate {
+    /// Returns true if the size of some artifacts could not be determined, in which case
+    /// `download_bytes` is a lower bound of the actual number of bytes to download.
+    pub fn is_partial(&self) -> bool {
+        !self.unknown.is_empty()
+    }
+}
+
+/// The size of a single artifact.
+enum ArtifactSize {
+    Cached(u64),
+    Download(u64),
+    Unknown,
+}
+
+/// Estimates how many bytes need to be downloaded to install the given packages. For every package
+/// the first (most compatible) artifact is considered.
+///
+/// Artifacts that are already present in the cache of the `package_db` are counted as cached bytes.
+/// For other artifacts the size reported by the index is used, or if the index does not report it,
+/// the size is requested from the remote with a `HEAD` request. If that request fails the artifact
+/// is reported as unknown and the estimate is partial, see [`DownloadSizeEstimate::is_partial`].
+pub async fn estimate_download_size(
+    package_db: &PackageDb,
+    packages: &[PinnedPackage<'_>],
+) -> miette::Result<DownloadSizeEstimate> {
+    let sizes: Vec<(&ArtifactInfo, ArtifactSize)> =
+        stream::iter(packages.iter().filter_map(|p| p.artifacts.first().copied()))
+            .map(|artifact_info| async move {
+                let size =
+                    if let Some(size) = package_db.cached_artifact_size(artifact_info).await? {
+                        ArtifactSize::Cached(size)
+                    } else {
+                        match package_db.remote_artifact_size(artifact_info).await {
+                            Ok(Some(size)) => ArtifactSize::Download(size),
+                            Ok(None) => ArtifactSize::Unknown,
+                            Err(err) => {
+                                tracing::warn!(
+                                    "failed to determine the size of {}: {err}",
+                                    artifact_info.filename
+                                );
+                                ArtifactSize::Unknown
+                            }
+                        }
+                    };
+                Ok::<_, miette::Report>((artifact_info, size))
+            })
+            .buffer_unordered(10)
+            .try_collect()
+            .await?;
+
+    let mut estimate = DownloadSizeEstimate::default();
+    for (artifact_info, size) in sizes {
+        match size {
+            ArtifactSize::Cached(size) => estimate.cached_bytes += size,
+            ArtifactSize::Download(size) => estimate.download_bytes += size,
+            ArtifactSize::Unknown => estimate.unknown.push(artifact_info.filename.clone()),
+        }
+    }
+
+    Ok(estimate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{NormalizedPackageName, PackageName};
+    use reqwest::Client;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_size_from_index() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let index_url = url::Url::parse("http://127.0.0.1:1/simple/").unwrap();
+        let package_db = PackageDb::new(Client::new(), &[index_url], cache_dir.path()).unwrap();
+
+        let name: NormalizedPackageName = PackageName::from_str("miniblack").unwrap().into();
+        let artifact_info = ArtifactInfo {
+            url: url::Url::parse("http://127.0.0.1:1/miniblack-23.1.0-py3-none-any.whl").unwrap(),
+            size: Some(1234),
+            ..ArtifactInfo::for_test(&name, "miniblack-23.1.0-py3-none-any.whl")
+        };
+
+        let packages = [PinnedPackage {
+            name,
+            version: "23.1.0".parse().unwrap(),
+            extras: HashSet::new(),
+            artifacts: vec![&artifact_info],
+        }];
+
+        let estimate = estimate_download_size(&package_db, &packages)
+            .await
+            .unwrap();
+        assert_eq!(
+            estimate,
+            DownloadSizeEstimate {
+                download_bytes: 1234,
+                cached_bytes: 0,
+                unknown: vec![],
+            }
+        );
+        assert!(!estimate.is_partial());
+    }
+
+    #[tokio::test]
+    async fn test_failed_head_request_is_partial() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let index_url = url::Url::parse("http://127.0.0.1:1/simple/").unwrap();
+        let package_db = PackageDb::new(Client::new(), &[index_url], cache_dir.path()).unwrap();
+
+        let name: NormalizedPackageName = PackageName::from_str("miniblack").unwrap().into();
+        let artifact_info = |filename: &str, size| ArtifactInfo {
+            url: url::Url::parse(&format!("http://127.0.0.1:1/{filename}")).unwrap(),
+            size,
+            ..ArtifactInfo::for_test(&name, filename)
+        };
+        let known = artifact_info("miniblack-23.1.0-py3-none-any.whl", Some(1234));
+        // Nothing listens on port 1 so the HEAD request for this artifact fails.
+        let unreachable = artifact_info("miniblack-23.1.0.tar.gz", None);
+
+        let packages = [
+            PinnedPackage {
+                name: name.clone(),
+                version: "23.1.0".parse().unwrap(),
+                extras: HashSet::new(),
+                artifacts: vec![&known],
+            },
+            PinnedPackage {
+                name: name.clone(),
+                version: "23.1.0".parse().unwrap(),
+                extras: HashSet::new(),
+                artifacts: vec![&unreachable],
+            },
+        ];
+
+        let estimate = estimate_download_size(&package_db, &packages)
+            .await
+            .unwrap();
+        assert_eq!(estimate.download_bytes, 1234);
+        assert_eq!(estimate.unknown, vec![unreachable.filename.clone()]);
+        assert!(estimate.is_partial());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::types::{Extra, NormalizedPackageName, Version};
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A decision or step taken by the resolver, see [`resolve_event_channel`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ResolveEvent {
+    /// The resolver started collecting the available versions of a package.
+    CollectingCandidates {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The extra of the package if the candidates of an extra are requested
+        extra: Option<Extra>,
+    },
+
+    /// The available versions of a package have been collected.
+    CandidatesCollected {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The extra of the package if the candidates of an extra are requested
+        extra: Option<Extra>,
+        /// The number of versions that can be selected
+        candidates: usize,
+        /// The number of versions that were excluded, e.g. because none of their artifacts are
+        /// compatible with the environment
+        excluded: usize,
+    },
+
+    /// The resolver considers selecting a version of a package and requested its dependencies.
+    /// The resolver may still backtrack and select another version later.
+    ConsideringCandidate {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The extra of the package if the dependencies of an extra are requested
+        extra: Option<Extra>,
+        /// The version that is considered
+        version: Version,
+    },
+
+    /// The resolver abandoned a version it considered before and considers another version of
+    /// the same package instead, because the previous version led to a conflict.
+    Backtracked {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The extra of the package if the dependencies of an extra are requested
+        extra: Option<Extra>,
+        /// The version that was considered before
+        from: Version,
+        /// The version that is considered now
+        to: Version,
+    },
+
+    /// A package was required under an alias and the requirement was replaced with a requirement
+    /// on the package the alias refers to, see [`super::PackageAliases`]. Each alias is only
+    /// reported the first time it is used.
+    AliasUsed {
+        /// The name the package was required as
+        alias: NormalizedPackageName,
+        /// The name of the package the alias refers to
+        name: NormalizedPackageName,
+        /// The package that required the alias, `None` if the alias was used in the requirements
+        /// passed to the resolver
+        required_by: Option<NormalizedPackageName>,
+    },
+
+    /// No solution could be found, `message` explains the conflict.
+    Conflict {
+        /// A user friendly explanation of the conflict
+        message: String,
+    },
+
+    /// A version of a package is part of the solution. Sent for every package in the solution
+    /// right before [`ResolveEvent::Solved`].
+    PackageSelected {
+        /// The name of the package
+        name: NormalizedPackageName,
+        /// The selected version
+        version: Version,
+    },
+
+    /// A solution was found.
+    Solved {
+        /// The number of packages in the solution
+        packages: usize,
+    },
+}
+
+/// The sending half of a channel created with [`resolve_event_channel`]. Assign it to
+/// [`super::ResolveOptions::events`] to receive the events of a resolution.
+#[derive(Debug, Clone)]
+pub struct ResolveEventSender(mpsc::UnboundedSender<ResolveEvent>);
+
+impl ResolveEventSender {
+    /// Sends an event. Events are dropped if the receiving end has been closed.
+    pub(crate) fn send(&self, event: ResolveEvent) {
+        let _ = self.0.unbounded_send(event);
+    }
+}
+
+/// A [`Stream`] of the [`ResolveEvent`]s of a resolution. The stream ends when the resolution has
+/// finished and all [`ResolveEventSender`]s have been dropped.
+#[derive(Debug)]
+pub struct ResolveEvents(mpsc::UnboundedReceiver<ResolveEvent>);
+
+impl Stream for ResolveEvents {
+    type Item = ResolveEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// Creates a channel to observe the decisions of the resolver while it is running, e.g. to show
+/// live progress.
+///
+/// The resolver runs on the task that calls [`super::resolve`], so the returned [`ResolveEvents`]
+/// should be consumed on another task:
+///
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use rattler_installs_packages::resolve::{resolve_event_channel, ResolveOptions};
+/// let (sender, mut events) = resolve_event_channel();
+/// let mut options = ResolveOptions::default();
+/// options.events = Some(sender);
+/// tokio::spawn(async move {
+///     while let Some(event) = events.next().await {
+///         println!("{event:?}");
+///     }
+/// });
+/// // Call `resolve` with `options`
+/// ```
+pub fn resolve_event_channel() -> (ResolveEventSender, ResolveEvents) {
+    let (sender, receiver) = mpsc::unbounded();
+    (ResolveEventSender(sender), ResolveEvents(receiver))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_channel() {
+        let (sender, events) = resolve_event_channel();
+        sender.send(ResolveEvent::Solved { packages: 1 });
+        sender.send(ResolveEvent::Conflict {
+            message: String::from("no solution"),
+        });
+        drop(sender);
+
+        let events = events.collect::<Vec<_>>().await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], ResolveEvent::Solved { packages: 1 });
+    }
+}
+
+#[cfg(test)]
+mod resolve_test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::resolve::{resolve, ResolveOptions};
+    use pep508_rs::Requirement;
+    use std::collections::HashMap;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_events() {
+        let index = TestIndex::start().await;
+        let (sender, events) = resolve_event_channel();
+        let options = ResolveOptions {
+            events: Some(sender),
+            ..Default::default()
+        };
+        let requirements: [Requirement; 1] = ["wordle_python".parse().unwrap()];
+        resolve(
+            &index.package_db,
+            &requirements,
+            &linux_environment_markers("3.10.4"),
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+
+        drop(options);
+        let events = events.collect::<Vec<_>>().await;
+        let name: NormalizedPackageName = "wordle-python".parse().unwrap();
+        let version: Version = "2.3.32".parse().unwrap();
+        assert!(events.contains(&ResolveEvent::ConsideringCandidate {
+            name: name.clone(),
+            extra: None,
+            version: version.clone(),
+        }));
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, ResolveEvent::Backtracked { .. })));
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                ResolveEvent::PackageSelected { name, version },
+                ResolveEvent::Solved { packages: 1 }
+            ]
+        );
+    }
+}
@@ -0,0 +1,432 @@
+//! Exposes the dependency graph of a resolved environment.
+//!
+//! The graph is stored as a [`petgraph::Graph`] so it can be analyzed with any of the algorithms
+//! provided by [`petgraph`]. It can also be serialized to the DOT format of graphviz or to a
+//! mermaid flowchart for visualization.
+
+use super::PinnedPackage;
+use crate::index::PackageDb;
+use crate::types::{ArtifactName, Extra, NormalizedPackageName, PackageName};
+use itertools::Itertools;
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use petgraph::graph::{Graph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The kind of artifact that is used to install a package.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DependencyArtifactKind {
+    /// The package is installed from a wheel.
+    Wheel,
+
+    /// The package is installed from a source distribution.
+    SDist,
+}
+
+/// A package in a [`DependencyGraph`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DependencyNode {
+    /// The name of the package
+    pub name: NormalizedPackageName,
+
+    /// The selected version
+    pub version: Version,
+
+    /// The extras that are selected for this package, sorted by name.
+    pub extras: Vec<Extra>,
+
+    /// The kind of the most compatible artifact of the package. This is `None` if the package has
+    /// no artifacts.
+    pub artifact_kind: Option<DependencyArtifactKind>,
+}
+
+/// A dependency between two packages in a [`DependencyGraph`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DependencyEdge {
+    /// The version specifiers or url of the requirement.
+    pub version_or_url: Option<VersionOrUrl>,
+
+    /// The extras of the dependency that are requested, sorted by name.
+    pub extras: Vec<Extra>,
+
+    /// The extra of the dependent package that introduced this dependency. `None` if this is a
+    /// regular dependency.
+    pub via_extra: Option<Extra>,
+}
+
+/// The dependency graph of a set of [`PinnedPackage`]s, constructed with [`dependency_graph`].
+///
+/// Every package is a node in the graph and an edge points from a package to each of its
+/// dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    graph: Graph<DependencyNode, DependencyEdge>,
+}
+
+impl DependencyGraph {
+    /// Returns the underlying graph.
+    pub fn graph(&self) -> &Graph<DependencyNode, DependencyEdge> {
+        &self.graph
+    }
+
+    /// Consumes this instance and returns the underlying graph.
+    pub fn into_graph(self) -> Graph<DependencyNode, DependencyEdge> {
+        self.graph
+    }
+
+    /// Returns the index of the node of the package with the given name.
+    pub fn node_index(&self, name: &NormalizedPackageName) -> Option<NodeIndex> {
+        self.graph
+            .node_indices()
+            .find(|&idx| &self.graph[idx].name == name)
+    }
+
+    /// Returns the packages that are not a dependency of any other package in the graph.
+    pub fn roots(&self) -> Vec<NodeIndex> {
+        self.graph
+            .node_indices()
+            .filter(|&idx| {
+                self.graph
+                    .neighbors_directed(idx, Direction::Incoming)
+                    .next()
+                    .is_none()
+            })
+            .collect()
+    }
+
+    /// Serializes the graph in the DOT format of graphviz.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("digraph dependencies {\n");
+        for idx in self.sorted_nodes() {
+            let node = &self.graph[idx];
+            writeln!(
+                result,
+                "    \"{}\" [label=\"{}\"];",
+                node.name.as_str(),
+                escape_dot(&node_label(node, "\\n"))
+            )
+            .unwrap();
+        }
+        for (from, to, edge) in self.sorted_edges() {
+            writeln!(
+                result,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                self.graph[from].name.as_str(),
+                self.graph[to].name.as_str(),
+                escape_dot(&edge_label(edge))
+            )
+            .unwrap();
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    /// Serializes the graph as a mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let mut result = String::from("flowchart TD\n");
+        let ids: HashMap<NodeIndex, usize> = self
+            .sorted_nodes()
+            .into_iter()
+            .enumerate()
+            .map(|(id, idx)| (idx, id))
+            .collect();
+        for idx in self.sorted_nodes() {
+            writeln!(
+                result,
+                "    n{}[\"{}\"]",
+                ids[&idx],
+                escape_mermaid(&node_label(&self.graph[idx], "<br>"))
+            )
+            .unwrap();
+        }
+        for (from, to, edge) in self.sorted_edges() {
+            writeln!(
+                result,
+                "    n{} -->|\"{}\"| n{}",
+                ids[&from],
+                escape_mermaid(&edge_label(edge)),
+                ids[&to]
+            )
+            .unwrap();
+        }
+        result
+    }
+
+    /// Returns the nodes sorted by package name so the serialized output is deterministic.
+    fn sorted_nodes(&self) -> Vec<NodeIndex> {
+        self.graph
+            .node_indices()
+            .sorted_by(|&a, &b| self.graph[a].name.cmp(&self.graph[b].name))
+            .collect()
+    }
+
+    /// Returns the edges sorted by the names of the packages they connect.
+    fn sorted_edges(&self) -> Vec<(NodeIndex, NodeIndex, &DependencyEdge)> {
+        self.graph
+            .edge_references()
+            .map(|edge| (edge.source(), edge.target(), edge.weight()))
+            .sorted_by(|a, b| {
+                (&self.graph[a.0].name, &self.graph[a.1].name)
+                    .cmp(&(&self.graph[b.0].name, &self.graph[b.1].name))
+            })
+            .collect()
+    }
+}
+
+/// Constructs the [`DependencyGraph`] of a resolved environment. The dependencies of every package
+/// are read from the metadata of its artifacts, which is usually already cached in the
+/// `package_db` by the resolution. Dependencies are evaluated against `env_markers`, which should
+/// be the same markers that were used for the resolution.
+///
+/// Only dependencies on packages in `packages` are included in the graph.
+pub async fn dependency_graph(
+    package_db: &PackageDb,
+    packages: &[PinnedPackage<'_>],
+    env_markers: &MarkerEnvironment,
+) -> miette::Result<DependencyGraph> {
+    let mut nodes = Vec::with_capacity(packages.len());
+    for package in packages {
+        let requires_dist = if package.artifacts.is_empty() {
+            Vec::new()
+        } else {
+            let Some((_, metadata)) = package_db.get_metadata(&package.artifacts, None).await?
+            else {
+                miette::bail!(
+                    "could not determine the dependencies of {} {}",
+                    package.name,
+                    package.version
+                );
+            };
+            metadata.requires_dist
+        };
+
+        let node = DependencyNode {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            extras: package.extras.iter().cloned().sorted().collect(),
+            artifact_kind: package.artifacts.first().map(|a| match &a.filename {
+                ArtifactName::Wheel(_) => DependencyArtifactKind::Wheel,
+                ArtifactName::SDist(_) => DependencyArtifactKind::SDist,
+            }),
+        };
+        nodes.push((node, requires_dist));
+    }
+
+    Ok(build_graph(nodes, env_markers))
+}
+
+/// Constructs the graph from the nodes and the requirements of each node.
+fn build_graph(
+    nodes: Vec<(DependencyNode, Vec<Requirement>)>,
+    env_markers: &MarkerEnvironment,
+) -> DependencyGraph {
+    let mut graph = Graph::with_capacity(nodes.len(), 0);
+    let mut indices = HashMap::with_capacity(nodes.len());
+    let mut requirements = Vec::with_capacity(nodes.len());
+    for (node, requires_dist) in nodes {
+        let name = node.name.clone();
+        let idx = graph.add_node(node);
+        indices.insert(name, idx);
+        requirements.push((idx, requires_dist));
+    }
+
+    for (idx, requires_dist) in requirements {
+        let extras = graph[idx].extras.clone();
+        for requirement in requires_dist {
+            // Determine whether the requirement applies and if so, through which extra.
+            let via_extra = match requirement.marker.as_ref() {
+                None => None,
+                Some(markers) if markers.evaluate(env_markers, &[]) => None,
+                Some(markers) => {
+                    match extras
+                        .iter()
+                        .find(|extra| markers.evaluate(env_markers, &[extra.as_str()]))
+                    {
+                        Some(extra) => Some(extra.clone()),
+                        None => continue,
+                    }
+                }
+            };
+
+            let Ok(name) = PackageName::from_str(&requirement.name) else {
+                continue;
+            };
+            let Some(&dependency_idx) = indices.get(&NormalizedPackageName::from(name)) else {
+                continue;
+            };
+
+            // Only record the first requirement between two packages. A package that requires
+            // itself with other extras (e.g. `foo[all]` requiring `foo[a,b]`) already has those
+            // extras in its node.
+            if dependency_idx == idx || graph.contains_edge(idx, dependency_idx) {
+                continue;
+            }
+
+            let edge = DependencyEdge {
+                version_or_url: requirement.version_or_url,
+                extras: requirement
+                    .extras
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|extra| Extra::from_str(&extra).ok())
+                    .sorted()
+                    .collect(),
+                via_extra,
+            };
+            graph.add_edge(idx, dependency_idx, edge);
+        }
+    }
+
+    DependencyGraph { graph }
+}
+
+fn node_label(node: &DependencyNode, separator: &str) -> String {
+    let mut label = node.name.as_str().to_owned();
+    if !node.extras.is_empty() {
+        write!(
+            label,
+            "[{}]",
+            node.extras.iter().map(Extra::as_str).join(",")
+        )
+        .unwrap();
+    }
+    write!(label, " {}", node.version).unwrap();
+    match node.artifact_kind {
+        Some(DependencyArtifactKind::Wheel) => write!(label, "{separator}(wheel)").unwrap(),
+        Some(DependencyArtifactKind::SDist) => write!(label, "{separator}(sdist)").unwrap(),
+        None => {}
+    }
+    label
+}
+
+fn edge_label(edge: &DependencyEdge) -> String {
+    let mut label = String::new();
+    if !edge.extras.is_empty() {
+        write!(
+            label,
+            "[{}]",
+            edge.extras.iter().map(Extra::as_str).join(",")
+        )
+        .unwrap();
+    }
+    match &edge.version_or_url {
+        None => label.push('*'),
+        Some(VersionOrUrl::VersionSpecifier(specifiers)) => write!(label, "{specifiers}").unwrap(),
+        Some(VersionOrUrl::Url(url)) => write!(label, "@ {url}").unwrap(),
+    }
+    if let Some(extra) = &edge.via_extra {
+        write!(label, " (extra: {})", extra.as_str()).unwrap();
+    }
+    label
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('"', "\\\"")
+}
+
+fn escape_mermaid(value: &str) -> String {
+    value.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_utils::linux_environment_markers;
+
+    fn node(
+        name: &str,
+        version: &str,
+        extras: &[&str],
+        requires_dist: &[&str],
+    ) -> (DependencyNode, Vec<Requirement>) {
+        (
+            DependencyNode {
+                name: PackageName::from_str(name).unwrap().into(),
+                version: version.parse().unwrap(),
+                extras: extras.iter().map(|e| Extra::from_str(e).unwrap()).collect(),
+                artifact_kind: Some(DependencyArtifactKind::Wheel),
+            },
+            requires_dist
+                .iter()
+                .map(|r| Requirement::from_str(r).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let graph = build_graph(
+            vec![
+                node(
+                    "black",
+                    "23.1.0",
+                    &["d"],
+                    &[
+                        "click>=8.0",
+                        "aiohttp>=3.7; extra == 'd'",
+                        "colorama; sys_platform == 'win32'",
+                    ],
+                ),
+                node(
+                    "click",
+                    "8.1.7",
+                    &[],
+                    &["colorama; platform_system == 'Windows'"],
+                ),
+                node("aiohttp", "3.9.0", &[], &[]),
+            ],
+            &linux_environment_markers("3.10.4"),
+        );
+
+        let black = graph
+            .node_index(&PackageName::from_str("black").unwrap().into())
+            .unwrap();
+        assert_eq!(graph.roots(), vec![black]);
+        assert_eq!(graph.graph().edge_count(), 2);
+
+        insta::assert_snapshot!(graph.to_dot(), @r###"
+        digraph dependencies {
+            "aiohttp" [label="aiohttp 3.9.0\n(wheel)"];
+            "black" [label="black[d] 23.1.0\n(wheel)"];
+            "click" [label="click 8.1.7\n(wheel)"];
+            "black" -> "aiohttp" [label=">=3.7 (extra: d)"];
+            "black" -> "click" [label=">=8.0"];
+        }
+        "###);
+
+        insta::assert_snapshot!(graph.to_mermaid(), @r###"
+        flowchart TD
+            n0["aiohttp 3.9.0<br>(wheel)"]
+            n1["black[d] 23.1.0<br>(wheel)"]
+            n2["click 8.1.7<br>(wheel)"]
+            n1 -->|">=3.7 (extra: d)"| n0
+            n1 -->|">=8.0"| n2
+        "###);
+    }
+
+    #[test]
+    fn test_self_referential_extras() {
+        let graph = build_graph(
+            vec![
+                node(
+                    "foo",
+                    "1.0",
+                    &["all", "a", "b"],
+                    &["foo[a,b]; extra == 'all'", "bar; extra == 'a'"],
+                ),
+                node("bar", "2.0", &[], &[]),
+            ],
+            &linux_environment_markers("3.10.4"),
+        );
+
+        let foo = graph
+            .node_index(&PackageName::from_str("foo").unwrap().into())
+            .unwrap();
+        assert_eq!(graph.roots(), vec![foo]);
+        assert_eq!(graph.graph().edge_count(), 1);
+    }
+}
@@ -0,0 +1,688 @@
+//! Importers for lock files of other Python package managers.
+//!
+//! This allows reusing an existing `poetry.lock`, `pdm.lock` or `uv.lock` as the locked packages
+//! passed to [`super::resolve`], or to install the exact locked versions directly.
+
+use super::PinnedPackage;
+use crate::index::PackageDb;
+use crate::types::{Extra, NormalizedPackageName, PackageName};
+use indexmap::IndexMap;
+use miette::Diagnostic;
+use pep440_rs::Version;
+use rattler_digest::{parse_digest_from_hex, Sha256, Sha256Hash};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use url::Url;
+
+/// The lock file formats that can be imported.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LockFileFormat {
+    /// A `poetry.lock` file written by [Poetry](https://python-poetry.org/).
+    Poetry,
+
+    /// A `pdm.lock` file written by [PDM](https://pdm-project.org/).
+    Pdm,
+
+    /// A `uv.lock` file written by [uv](https://github.com/astral-sh/uv).
+    Uv,
+}
+
+impl LockFileFormat {
+    /// Determines the format of a lock file from its filename.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.file_name()?.to_str()? {
+            "poetry.lock" => Some(LockFileFormat::Poetry),
+            "pdm.lock" => Some(LockFileFormat::Pdm),
+            "uv.lock" => Some(LockFileFormat::Uv),
+            _ => None,
+        }
+    }
+}
+
+/// An error that can occur when importing a lock file.
+#[derive(Debug, Error, Diagnostic)]
+#[allow(missing_docs)]
+pub enum LockFileError {
+    #[error("could not determine the format of lock file {0}")]
+    UnknownFormat(PathBuf),
+
+    #[error("failed to read lock file {0}")]
+    IoError(PathBuf, #[source] std::io::Error),
+
+    #[error("failed to parse lock file")]
+    InvalidToml(#[from] toml::de::Error),
+
+    #[error("invalid package name '{0}' in lock file")]
+    InvalidPackageName(String),
+
+    #[error("invalid version '{1}' for package '{0}' in lock file")]
+    InvalidVersion(String, String),
+
+    #[error("invalid extra '{1}' for package '{0}' in lock file")]
+    InvalidExtra(String, String),
+}
+
+/// A file of a [`LockedPackage`] as recorded in a lock file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LockedFile {
+    /// The filename of the artifact.
+    pub filename: String,
+
+    /// The url of the artifact if the lock file records it.
+    pub url: Option<Url>,
+
+    /// The sha256 hash of the artifact if the lock file records it.
+    pub sha256: Option<Sha256Hash>,
+}
+
+/// A package pinned by an imported lock file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LockedPackage {
+    /// The name of the package
+    pub name: NormalizedPackageName,
+
+    /// The locked version
+    pub version: Version,
+
+    /// The extras of the package that are used by other packages in the lock file.
+    pub extras: HashSet<Extra>,
+
+    /// The artifacts that the lock file allows for this package.
+    pub files: Vec<LockedFile>,
+}
+
+impl LockedPackage {
+    /// Converts this instance into a [`PinnedPackage`]. The artifacts of the package are looked up
+    /// in the `package_db` and only those artifacts that are recorded in the lock file are kept. If
+    /// the lock file records a hash for an artifact, the hash of the artifact in the index must
+    /// match.
+    pub async fn to_pinned<'db>(
+        &self,
+        package_db: &'db PackageDb,
+    ) -> miette::Result<PinnedPackage<'db>> {
+        let artifacts = package_db
+            .available_artifacts(self.name.clone())
+            .await?
+            .get(&self.version)
+            .into_iter()
+            .flatten()
+            .filter(|artifact_info| {
+                let filename = artifact_info.filename.to_string();
+                let sha256 = artifact_info
+                    .hashes
+                    .as_ref()
+                    .and_then(|h| h.sha256.as_ref());
+                self.files.iter().any(|file| {
+                    file.filename == filename
+                        && match (&file.sha256, sha256) {
+                            (Some(expected), Some(actual)) => expected == actual,
+                            _ => true,
+                        }
+                })
+            })
+            .collect();
+
+        Ok(PinnedPackage {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            extras: self.extras.clone(),
+            artifacts,
+        })
+    }
+}
+
+/// Converts the packages from a lock file into [`PinnedPackage`]s that can be passed as locked
+/// packages to [`super::resolve`]. See [`LockedPackage::to_pinned`].
+pub async fn pin_locked_packages<'db>(
+    packages: &[LockedPackage],
+    package_db: &'db PackageDb,
+) -> miette::Result<HashMap<NormalizedPackageName, PinnedPackage<'db>>> {
+    let mut result = HashMap::with_capacity(packages.len());
+    for package in packages {
+        result.insert(package.name.clone(), package.to_pinned(package_db).await?);
+    }
+    Ok(result)
+}
+
+/// Reads the packages from a lock file on disk. The format is determined from the filename, see
+/// [`LockFileFormat::from_path`].
+pub fn read_lock_file(path: &Path) -> Result<Vec<LockedPackage>, LockFileError> {
+    let format = LockFileFormat::from_path(path)
+        .ok_or_else(|| LockFileError::UnknownFormat(path.to_path_buf()))?;
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| LockFileError::IoError(path.to_path_buf(), err))?;
+    parse_lock_file(&contents, format)
+}
+
+/// Parses the contents of a lock file in the given format. Packages that do not come from a
+/// package index (e.g. local projects, git or url dependencies) are skipped.
+///
+/// Multiple entries for the same package are merged. The packages are returned in the order in
+/// which they first appear in the lock file.
+pub fn parse_lock_file(
+    contents: &str,
+    format: LockFileFormat,
+) -> Result<Vec<LockedPackage>, LockFileError> {
+    let mut builder = LockBuilder::default();
+    match format {
+        LockFileFormat::Poetry => parse_poetry(contents, &mut builder)?,
+        LockFileFormat::Pdm => parse_pdm(contents, &mut builder)?,
+        LockFileFormat::Uv => parse_uv(contents, &mut builder)?,
+    }
+    builder.finish()
+}
+
+/// A file entry as it is found in poetry and pdm lock files.
+#[derive(Deserialize)]
+struct RawFile {
+    file: Option<String>,
+    url: Option<Url>,
+    hash: Option<String>,
+}
+
+impl RawFile {
+    fn into_locked_file(self) -> Option<LockedFile> {
+        let filename = match (self.file, &self.url) {
+            (Some(file), _) => file,
+            (None, Some(url)) => filename_from_url(url)?,
+            (None, None) => return None,
+        };
+        Some(LockedFile {
+            filename,
+            url: self.url,
+            sha256: self.hash.as_deref().and_then(parse_sha256),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct PoetryLock {
+    #[serde(default)]
+    package: Vec<PoetryPackage>,
+    #[serde(default)]
+    metadata: PoetryMetadata,
+}
+
+#[derive(Deserialize, Default)]
+struct PoetryMetadata {
+    /// Older versions of poetry store the files of all packages in the metadata.
+    #[serde(default)]
+    files: HashMap<String, Vec<RawFile>>,
+}
+
+#[derive(Deserialize)]
+struct PoetryPackage {
+    name: String,
+    version: String,
+    #[serde(default)]
+    files: Vec<RawFile>,
+    #[serde(default)]
+    dependencies: IndexMap<String, toml::Value>,
+    source: Option<PoetrySource>,
+}
+
+#[derive(Deserialize)]
+struct PoetrySource {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+fn parse_poetry(contents: &str, builder: &mut LockBuilder) -> Result<(), LockFileError> {
+    let mut lock: PoetryLock = toml::from_str(contents)?;
+    for package in lock.package {
+        // Record the extras that are requested by this package. This is also done for packages
+        // that are not locked themselves, like local projects, because they are usually the root
+        // of the dependency tree.
+        for (name, spec) in &package.dependencies {
+            let specs = match spec {
+                toml::Value::Array(specs) => specs.iter().collect(),
+                spec => vec![spec],
+            };
+            for spec in specs {
+                if let Some(extras) = spec.get("extras").and_then(toml::Value::as_array) {
+                    let extras = extras
+                        .iter()
+                        .filter_map(toml::Value::as_str)
+                        .collect::<Vec<_>>();
+                    builder.add_extras(name, &extras)?;
+                }
+            }
+        }
+
+        // Only packages from an index can be locked
+        if let Some(source) = &package.source {
+            if source.kind != "legacy" {
+                continue;
+            }
+        }
+
+        let mut files = package.files;
+        if files.is_empty() {
+            files = lock
+                .metadata
+                .files
+                .remove(&package.name)
+                .unwrap_or_default();
+        }
+
+        builder.add(&package.name, &package.version, &[], files)?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct PdmLock {
+    #[serde(default)]
+    package: Vec<PdmPackage>,
+    #[serde(default)]
+    metadata: PdmMetadata,
+}
+
+#[derive(Deserialize, Default)]
+struct PdmMetadata {
+    /// Older versions of pdm store the files of all packages in the metadata keyed by
+    /// `"<name> <version>"`.
+    #[serde(default)]
+    files: HashMap<String, Vec<RawFile>>,
+}
+
+#[derive(Deserialize)]
+struct PdmPackage {
+    name: String,
+    version: Option<String>,
+    #[serde(default)]
+    extras: Vec<String>,
+    #[serde(default)]
+    files: Vec<RawFile>,
+    path: Option<String>,
+    git: Option<String>,
+    url: Option<String>,
+}
+
+fn parse_pdm(contents: &str, builder: &mut LockBuilder) -> Result<(), LockFileError> {
+    let mut lock: PdmLock = toml::from_str(contents)?;
+    for package in lock.package {
+        // Only packages from an index can be locked
+        let Some(version) = package.version else {
+            continue;
+        };
+        if package.path.is_some() || package.git.is_some() || package.url.is_some() {
+            continue;
+        }
+
+        let mut files = package.files;
+        if files.is_empty() {
+            files = lock
+                .metadata
+                .files
+                .remove(&format!("{} {}", package.name, version))
+                .unwrap_or_default();
+        }
+
+        let extras = package
+            .extras
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        builder.add(&package.name, &version, &extras, files)?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct UvLock {
+    #[serde(default)]
+    package: Vec<UvPackage>,
+}
+
+#[derive(Deserialize)]
+struct UvPackage {
+    name: String,
+    version: Option<String>,
+    source: Option<UvSource>,
+    #[serde(default)]
+    dependencies: Vec<UvDependency>,
+    #[serde(default, rename = "optional-dependencies")]
+    optional_dependencies: IndexMap<String, Vec<UvDependency>>,
+    #[serde(default, rename = "dev-dependencies")]
+    dev_dependencies: IndexMap<String, Vec<UvDependency>>,
+    sdist: Option<UvFile>,
+    #[serde(default)]
+    wheels: Vec<UvFile>,
+}
+
+#[derive(Deserialize)]
+struct UvSource {
+    registry: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UvDependency {
+    name: String,
+    #[serde(default)]
+    extra: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct UvFile {
+    url: Option<Url>,
+    hash: Option<String>,
+}
+
+fn parse_uv(contents: &str, builder: &mut LockBuilder) -> Result<(), LockFileError> {
+    let lock: UvLock = toml::from_str(contents)?;
+    for package in lock.package {
+        // Record the extras that are requested by this package. This is also done for packages
+        // that are not locked themselves, like the editable root project, because they are
+        // usually the root of the dependency tree.
+        for dependency in package
+            .dependencies
+            .iter()
+            .chain(package.optional_dependencies.values().flatten())
+            .chain(package.dev_dependencies.values().flatten())
+        {
+            let extras = dependency
+                .extra
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            builder.add_extras(&dependency.name, &extras)?;
+        }
+
+        // Only packages from an index can be locked
+        let Some(version) = package.version else {
+            continue;
+        };
+        if package
+            .source
+            .as_ref()
+            .and_then(|s| s.registry.as_ref())
+            .is_none()
+        {
+            continue;
+        }
+
+        let files = package
+            .sdist
+            .into_iter()
+            .chain(package.wheels)
+            .map(|file| RawFile {
+                file: None,
+                url: file.url,
+                hash: file.hash,
+            })
+            .collect();
+        builder.add(&package.name, &version, &[], files)?;
+    }
+    Ok(())
+}
+
+/// Collects packages from a lock file and merges duplicate entries.
+#[derive(Default)]
+struct LockBuilder {
+    packages: IndexMap<NormalizedPackageName, LockedPackage>,
+
+    /// Extras requested for packages. These are applied when all packages have been added because
+    /// a package might be referenced before it is defined.
+    extras: HashMap<NormalizedPackageName, HashSet<Extra>>,
+}
+
+impl LockBuilder {
+    fn add(
+        &mut self,
+        name: &str,
+        version: &str,
+        extras: &[&str],
+        files: Vec<RawFile>,
+    ) -> Result<(), LockFileError> {
+        let normalized_name = parse_name(name)?;
+        let version = Version::from_str(version)
+            .map_err(|_| LockFileError::InvalidVersion(name.to_owned(), version.to_owned()))?;
+        self.add_extras(name, extras)?;
+
+        let package = self
+            .packages
+            .entry(normalized_name.clone())
+            .or_insert_with(|| LockedPackage {
+                name: normalized_name,
+                version,
+                extras: HashSet::new(),
+                files: Vec::new(),
+            });
+        for file in files.into_iter().filter_map(RawFile::into_locked_file) {
+            if !package.files.contains(&file) {
+                package.files.push(file);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_extras(&mut self, name: &str, extras: &[&str]) -> Result<(), LockFileError> {
+        if extras.is_empty() {
+            return Ok(());
+        }
+        let entry = self.extras.entry(parse_name(name)?).or_default();
+        for extra in extras {
+            let parsed = Extra::from_str(extra)
+                .map_err(|_| LockFileError::InvalidExtra(name.to_owned(), extra.to_string()))?;
+            entry.insert(parsed);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<LockedPackage>, LockFileError> {
+        for (name, extras) in self.extras {
+            if let Some(package) = self.packages.get_mut(&name) {
+                package.extras.extend(extras);
+            }
+        }
+        Ok(self.packages.into_values().collect())
+    }
+}
+
+fn parse_name(name: &str) -> Result<NormalizedPackageName, LockFileError> {
+    PackageName::from_str(name)
+        .map(Into::into)
+        .map_err(|_| LockFileError::InvalidPackageName(name.to_owned()))
+}
+
+/// Parses a hash in the form `sha256:<hex>`. Other hash algorithms are ignored.
+fn parse_sha256(hash: &str) -> Option<Sha256Hash> {
+    let hex = hash.strip_prefix("sha256:")?;
+    parse_digest_from_hex::<Sha256>(hex)
+}
+
+/// Returns the filename of the artifact at the given url.
+fn filename_from_url(url: &Url) -> Option<String> {
+    url.path_segments()?.next_back().map(ToOwned::to_owned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn summary(packages: &[LockedPackage]) -> Vec<(String, String, Vec<String>, Vec<String>)> {
+        packages
+            .iter()
+            .map(|p| {
+                let mut extras = p
+                    .extras
+                    .iter()
+                    .map(|e| e.as_str().to_owned())
+                    .collect::<Vec<_>>();
+                extras.sort();
+                (
+                    p.name.as_str().to_owned(),
+                    p.version.to_string(),
+                    extras,
+                    p.files.iter().map(|f| f.filename.clone()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_poetry_lock() {
+        let packages = parse_lock_file(
+            r#"
+[[package]]
+name = "requests"
+version = "2.31.0"
+optional = false
+python-versions = ">=3.7"
+files = [
+    {file = "requests-2.31.0-py3-none-any.whl", hash = "sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f"},
+    {file = "requests-2.31.0.tar.gz", hash = "sha256:942c5a758f98d790eaed1a29cb6eefc7ffb0d1cf7af05c3d2791656dbd6ad1e1"},
+]
+
+[package.dependencies]
+PySocks = {version = ">=1.5.6,!=1.5.7", optional = true}
+urllib3 = {version = ">=1.21.1,<3", extras = ["socks"]}
+
+[[package]]
+name = "urllib3"
+version = "2.0.7"
+optional = false
+python-versions = ">=3.7"
+files = [
+    {file = "urllib3-2.0.7-py3-none-any.whl", hash = "sha256:fdb6d215c776278489906c2f8916e6e7d4f5a9b602ccbcfdf7f016fc8da0596e"},
+]
+
+[[package]]
+name = "my-project"
+version = "0.1.0"
+optional = false
+python-versions = "*"
+files = []
+
+[package.dependencies]
+requests = {version = "^2.31", extras = ["socks"]}
+
+[package.source]
+type = "directory"
+url = "../my-project"
+
+[metadata]
+lock-version = "2.0"
+python-versions = "^3.8"
+content-hash = "abc"
+"#,
+            LockFileFormat::Poetry,
+        )
+        .unwrap();
+
+        assert_eq!(
+            summary(&packages),
+            vec![
+                (
+                    String::from("requests"),
+                    String::from("2.31.0"),
+                    vec![String::from("socks")],
+                    vec![
+                        String::from("requests-2.31.0-py3-none-any.whl"),
+                        String::from("requests-2.31.0.tar.gz")
+                    ]
+                ),
+                (
+                    String::from("urllib3"),
+                    String::from("2.0.7"),
+                    vec![String::from("socks")],
+                    vec![String::from("urllib3-2.0.7-py3-none-any.whl")]
+                ),
+            ]
+        );
+        assert!(packages[0].files[0].sha256.is_some());
+    }
+
+    #[test]
+    fn test_pdm_lock() {
+        let packages = parse_lock_file(
+            r#"
+[metadata]
+groups = ["default"]
+lock_version = "4.4"
+
+[[package]]
+name = "requests"
+version = "2.31.0"
+requires_python = ">=3.7"
+dependencies = ["urllib3<3,>=1.21.1"]
+files = [
+    {file = "requests-2.31.0-py3-none-any.whl", hash = "sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f"},
+]
+
+[[package]]
+name = "requests"
+version = "2.31.0"
+extras = ["socks"]
+requires_python = ">=3.7"
+dependencies = ["PySocks!=1.5.7,>=1.5.6", "requests==2.31.0"]
+files = [
+    {file = "requests-2.31.0-py3-none-any.whl", hash = "sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f"},
+]
+
+[[package]]
+name = "local-project"
+path = "./local-project"
+"#,
+            LockFileFormat::Pdm,
+        )
+        .unwrap();
+
+        assert_eq!(
+            summary(&packages),
+            vec![(
+                String::from("requests"),
+                String::from("2.31.0"),
+                vec![String::from("socks")],
+                vec![String::from("requests-2.31.0-py3-none-any.whl")]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_uv_lock() {
+        let packages = parse_lock_file(
+            r#"
+version = 1
+requires-python = ">=3.12"
+
+[[package]]
+name = "my-project"
+version = "0.1.0"
+source = { editable = "." }
+dependencies = [
+    { name = "requests", extra = ["socks"] },
+]
+
+[[package]]
+name = "requests"
+version = "2.31.0"
+source = { registry = "https://pypi.org/simple" }
+sdist = { url = "https://files.pythonhosted.org/packages/9d/be/requests-2.31.0.tar.gz", hash = "sha256:942c5a758f98d790eaed1a29cb6eefc7ffb0d1cf7af05c3d2791656dbd6ad1e1", size = 110794 }
+wheels = [
+    { url = "https://files.pythonhosted.org/packages/70/8e/requests-2.31.0-py3-none-any.whl", hash = "sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f", size = 62574 },
+]
+"#,
+            LockFileFormat::Uv,
+        )
+        .unwrap();
+
+        assert_eq!(
+            summary(&packages),
+            vec![(
+                String::from("requests"),
+                String::from("2.31.0"),
+                vec![String::from("socks")],
+                vec![
+                    String::from("requests-2.31.0.tar.gz"),
+                    String::from("requests-2.31.0-py3-none-any.whl")
+                ]
+            )]
+        );
+        assert!(packages[0].files.iter().all(|f| f.url.is_some()));
+    }
+}
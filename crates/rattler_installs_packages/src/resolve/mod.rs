@@ -8,7 +8,67 @@
 //! See the `rip_bin` crate for an example of how to use the [`resolve`] function in the: [RIP Repo](https://github.com/prefix-dev/rip)
 //!
 
+mod artifact_attestations;
+mod attestation;
+mod best_effort;
+mod builder;
+mod candidate_selector;
 mod dependency_provider;
+mod diff;
+mod download_size;
+mod events;
+mod graph;
+mod lock_import;
+mod multi_root;
+mod no_compatible_artifacts;
+mod no_deps;
+mod outdated;
+mod owned_package;
+mod package_aliases;
+mod package_rules;
+mod provided_packages;
+mod python_compat;
+mod requirements_txt;
+mod snapshot;
+mod solution_cache;
 mod solve;
+mod workspace;
 
-pub use solve::{resolve, PinnedPackage, ResolveOptions, SDistResolution};
+pub use artifact_attestations::{check_attestations, ArtifactAttestation};
+pub use attestation::{
+    attest_requirements_txt, verify_requirements_txt, AttestationError, LockAttestation,
+    ResolutionInputs,
+};
+pub use best_effort::{resolve_best_effort, PartialResolution, UnsatisfiableRequirement};
+pub use builder::ResolveBuilder;
+pub use candidate_selector::CandidateSelector;
+pub use diff::{diff_locked_packages, diff_resolutions, PackageChange, VersionDelta};
+pub use download_size::{estimate_download_size, DownloadSizeEstimate};
+pub use events::{resolve_event_channel, ResolveEvent, ResolveEventSender, ResolveEvents};
+pub use graph::{
+    dependency_graph, DependencyArtifactKind, DependencyEdge, DependencyGraph, DependencyNode,
+};
+pub use lock_import::{
+    parse_lock_file, pin_locked_packages, read_lock_file, LockFileError, LockFileFormat,
+    LockedFile, LockedPackage,
+};
+pub use multi_root::{resolve_many, ResolutionRoot};
+pub use no_compatible_artifacts::{NoCompatibleArtifacts, SDistAvailability};
+pub use no_deps::install_without_dependencies;
+pub use outdated::{find_outdated_packages, OutdatedPackage};
+pub use owned_package::OwnedPinnedPackage;
+pub use package_aliases::PackageAliases;
+pub use package_rules::{BlockedBy, NamePattern, PackageRule, PackageRules, RuleAction};
+pub use provided_packages::ProvidedPackages;
+pub use python_compat::{python_compatibility, PythonCompatibility};
+pub use requirements_txt::{to_requirements_txt, RequirementsTxtError};
+pub use snapshot::{
+    export_environment, export_installed_environment, import_environment, EnvironmentSnapshot,
+    SnapshotPackage,
+};
+pub use solution_cache::{resolve_cached, SolutionCache, SolutionKey};
+pub use solve::{
+    resolve, DirectReferencePolicy, LocalVersionPreference, PinnedPackage, ResolveOptions,
+    SDistResolution, Unsolvable,
+};
+pub use workspace::{resolve_workspace, Workspace, WorkspaceError, WorkspaceProject};
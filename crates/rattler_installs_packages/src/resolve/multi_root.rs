@@ -0,0 +1,289 @@
+//! Resolution of several independent sets of requirements in one session.
+//!
+//! Lock files often contain more than one environment, e.g. one per extra, per dependency group
+//! or per platform. These environments are resolved independently but mostly share the same
+//! packages. [`resolve_many`] resolves all of them against the same [`PackageDb`] so index pages
+//! and metadata are fetched only once, and it fetches the pages and metadata that the resolutions
+//! are likely to need for all environments concurrently before resolving.
+
+use super::{resolve, PinnedPackage, ResolveOptions};
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::types::{ArtifactInfo, NormalizedPackageName, PackageName};
+use futures::{stream, StreamExt};
+use indexmap::IndexMap;
+use pep508_rs::{MarkerEnvironment, Requirement};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::str::FromStr;
+use tokio::runtime::Handle;
+use tokio::task;
+
+/// The maximum number of packages that are prefetched concurrently.
+const PREFETCH_CONCURRENCY: usize = 10;
+
+/// A set of requirements and the environment they are resolved for, see [`resolve_many`].
+#[derive(Debug, Clone)]
+pub struct ResolutionRoot<'i> {
+    /// The requirements of packages that must be present in the environment
+    pub requirements: Vec<Requirement>,
+
+    /// Information about the python interpreter of the environment
+    pub env_markers: &'i MarkerEnvironment,
+
+    /// When set, only artifacts that are compatible with these tags are selected
+    pub compatible_tags: Option<&'i WheelTags>,
+}
+
+impl<'i> ResolutionRoot<'i> {
+    /// Constructs a root that resolves `requirements` for the given environment without filtering
+    /// artifacts by their tags.
+    pub fn new(requirements: Vec<Requirement>, env_markers: &'i MarkerEnvironment) -> Self {
+        Self {
+            requirements,
+            env_markers,
+            compatible_tags: None,
+        }
+    }
+
+    /// Only select artifacts that are compatible with the given tags.
+    pub fn with_compatible_tags(self, compatible_tags: &'i WheelTags) -> Self {
+        Self {
+            compatible_tags: Some(compatible_tags),
+            ..self
+        }
+    }
+}
+
+/// Resolves several independent sets of requirements that share one [`PackageDb`], see the
+/// [module documentation](self).
+///
+/// Every root is resolved like [`resolve`] would, the roots are resolved concurrently. The result
+/// of each root is returned under its key in the order the roots were passed in. A root that
+/// cannot be resolved does not affect the other roots. An error is returned if the same key is
+/// passed more than once.
+pub async fn resolve_many<'db, 'i, K: Hash + Eq + Debug>(
+    package_db: &'db PackageDb,
+    roots: impl IntoIterator<Item = (K, ResolutionRoot<'i>)>,
+    options: &ResolveOptions,
+) -> miette::Result<IndexMap<K, miette::Result<Vec<PinnedPackage<'db>>>>> {
+    let mut unique_roots = IndexMap::new();
+    for (key, root) in roots {
+        if unique_roots.contains_key(&key) {
+            miette::bail!("the resolution root {key:?} is passed more than once");
+        }
+        unique_roots.insert(key, root);
+    }
+    let roots = unique_roots;
+
+    prefetch(package_db, roots.values()).await;
+
+    // The resolver blocks the thread it runs on so every root is resolved on a thread of its own.
+    // The solutions borrow from `package_db` which rules out `spawn_blocking`, scoped threads are
+    // used instead.
+    let handle = Handle::current();
+    let solutions = task::block_in_place(|| {
+        std::thread::scope(|scope| {
+            let handle = &handle;
+            let mut threads = Vec::with_capacity(roots.len());
+            for root in roots.values() {
+                threads.push(scope.spawn(move || {
+                    handle.block_on(resolve(
+                        package_db,
+                        &root.requirements,
+                        root.env_markers,
+                        root.compatible_tags,
+                        HashMap::default(),
+                        HashMap::default(),
+                        options,
+                    ))
+                }));
+            }
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+
+    Ok(roots.into_keys().zip(solutions).collect())
+}
+
+/// Concurrently fetches the index pages of the packages the roots require and the metadata of the
+/// latest version of each of them, following the dependencies of those versions. This is a guess
+/// of what the resolutions need: failures are ignored and reported by the resolutions instead.
+async fn prefetch<'r, 'i: 'r>(
+    package_db: &PackageDb,
+    roots: impl Iterator<Item = &'r ResolutionRoot<'i>>,
+) {
+    let roots = &roots.collect::<Vec<_>>();
+    let markers_apply = |requirement: &Requirement| {
+        roots.iter().any(|root| {
+            requirement
+                .marker
+                .as_ref()
+                .map_or(true, |marker| marker.evaluate(root.env_markers, &[]))
+        })
+    };
+
+    let mut seen = HashSet::new();
+    let mut pending = roots
+        .iter()
+        .flat_map(|root| root.requirements.iter())
+        .filter(|requirement| markers_apply(requirement))
+        .filter_map(package_name)
+        .filter(|name| seen.insert(name.clone()))
+        .collect::<Vec<_>>();
+
+    while !pending.is_empty() {
+        let dependencies = stream::iter(pending.drain(..))
+            .map(|name| async move {
+                let artifacts = match package_db.available_artifacts(name.clone()).await {
+                    Ok(artifacts) => artifacts,
+                    Err(err) => {
+                        tracing::debug!("failed to prefetch '{name}': {err}");
+                        return Vec::new();
+                    }
+                };
+
+                // Only wheels are considered, the metadata of sdists requires a build
+                let Some(wheels) = artifacts.values().rev().find_map(|artifacts| {
+                    let wheels = artifacts
+                        .iter()
+                        .filter(|artifact| is_compatible_with_any(artifact, roots))
+                        .collect::<Vec<_>>();
+                    (!wheels.is_empty()).then_some(wheels)
+                }) else {
+                    return Vec::new();
+                };
+                match package_db.get_metadata(&wheels, None).await {
+                    Ok(Some((_, metadata))) => metadata.requires_dist,
+                    Ok(None) => Vec::new(),
+                    Err(err) => {
+                        tracing::debug!("failed to prefetch the metadata of '{name}': {err}");
+                        Vec::new()
+                    }
+                }
+            })
+            .buffer_unordered(PREFETCH_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        pending.extend(
+            dependencies
+                .iter()
+                .flatten()
+                .filter(|requirement| markers_apply(requirement))
+                .filter_map(package_name)
+                .filter(|name| seen.insert(name.clone())),
+        );
+    }
+}
+
+/// Returns the normalized name of the package a requirement refers to.
+fn package_name(requirement: &Requirement) -> Option<NormalizedPackageName> {
+    PackageName::from_str(&requirement.name)
+        .ok()
+        .map(Into::into)
+}
+
+/// Returns true if the artifact is a wheel that is compatible with at least one of the roots.
+fn is_compatible_with_any(artifact: &ArtifactInfo, roots: &[&ResolutionRoot<'_>]) -> bool {
+    let Some(wheel) = artifact.filename.as_wheel() else {
+        return false;
+    };
+    roots.iter().any(|root| {
+        root.compatible_tags.map_or(true, |tags| {
+            wheel.all_tags_iter().any(|tag| tags.is_compatible(&tag))
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_many() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+        let requirements = |requirements: &[&str]| {
+            requirements
+                .iter()
+                .map(|requirement| Requirement::from_str(requirement).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let results = resolve_many(
+            package_db,
+            [
+                (
+                    "default",
+                    ResolutionRoot::new(requirements(&["wordle_python"]), &env_markers),
+                ),
+                (
+                    "native",
+                    ResolutionRoot::new(
+                        requirements(&["wordle_python", "purelib_and_platlib"]),
+                        &env_markers,
+                    ),
+                ),
+                (
+                    "broken",
+                    ResolutionRoot::new(requirements(&["does-not-exist"]), &env_markers),
+                ),
+            ],
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            results.keys().copied().collect::<Vec<_>>(),
+            ["default", "native", "broken"]
+        );
+        let names = |key: &str| {
+            let mut names = results[key]
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|package| package.name.as_str().to_owned())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        assert_eq!(names("default"), ["wordle-python"]);
+        assert_eq!(names("native"), ["purelib-and-platlib", "wordle-python"]);
+        assert!(results["broken"].is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_many_duplicate_keys() {
+        let index = TestIndex::start().await;
+        let env_markers = linux_environment_markers("3.10.4");
+        let root = ResolutionRoot::new(
+            vec![Requirement::from_str("wordle_python").unwrap()],
+            &env_markers,
+        );
+
+        let error = resolve_many(
+            &index.package_db,
+            [("default", root.clone()), ("default", root)],
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the resolution root \"default\" is passed more than once"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Reporting of packages that are available on the index but cannot be installed in the
+//! environment because none of their artifacts are compatible with it.
+//!
+//! This commonly happens for packages that only publish wheels for a few platforms, e.g. a package
+//! without Windows wheels that is resolved for Windows. Instead of the generic "no viable options"
+//! conflict the resolver then reports a [`NoCompatibleArtifacts`] error that lists the tags of the
+//! wheels that come closest to being compatible and whether an sdist could have been used instead.
+
+use crate::python_env::{WheelTag, WheelTags};
+use crate::types::NormalizedPackageName;
+use itertools::Itertools;
+use miette::Diagnostic;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt::Display;
+use thiserror::Error;
+
+/// The maximum number of tags listed in [`NoCompatibleArtifacts::closest_tags`].
+const MAX_CLOSEST_TAGS: usize = 3;
+
+/// Whether the sdists of a package could have been used instead of its incompatible wheels. The
+/// variants are ordered from least to most actionable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SDistAvailability {
+    /// None of the versions have an sdist.
+    Missing,
+
+    /// There are sdists but their archive format is not supported.
+    Unsupported,
+
+    /// There are sdists but sdists are not allowed by
+    /// [`super::ResolveOptions::sdist_resolution`].
+    ExcludedByPolicy,
+}
+
+/// The error returned by [`super::resolve`] if a required package has versions on the index but
+/// none of them has an artifact that is compatible with the environment, see the [module
+/// documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "none of the versions of '{name}' have an artifact that is compatible with the environment"
+)]
+pub struct NoCompatibleArtifacts {
+    /// The name of the package
+    pub name: NormalizedPackageName,
+
+    /// The tags of the available wheels that come closest to being compatible with the
+    /// environment, the closest first. See [`WheelTags::similarity`].
+    pub closest_tags: Vec<WheelTag>,
+
+    /// Whether sdists of the package are available
+    pub sdists: SDistAvailability,
+}
+
+impl Diagnostic for NoCompatibleArtifacts {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let mut help = String::new();
+        if !self.closest_tags.is_empty() {
+            help.push_str(&format!(
+                "the closest wheels are tagged {}\n",
+                self.closest_tags.iter().format(", ")
+            ));
+        }
+        help.push_str(&match self.sdists {
+            SDistAvailability::Missing => String::from("there are no sdists to build from"),
+            SDistAvailability::Unsupported => {
+                String::from("there are sdists but their archive format is not supported")
+            }
+            SDistAvailability::ExcludedByPolicy => format!(
+                "there are sdists but they are excluded by the sdist resolution, allow sdists to build '{}' from source",
+                self.name
+            ),
+        });
+        Some(Box::new(help))
+    }
+}
+
+/// Collects the incompatible wheels of the versions of a single package while its candidates are
+/// filtered.
+#[derive(Debug, Default)]
+pub(crate) struct IncompatibleWheels {
+    tags: HashSet<WheelTag>,
+    sdists: Option<SDistAvailability>,
+}
+
+impl IncompatibleWheels {
+    /// Records a version of which none of the wheels with the given tags are compatible.
+    pub fn add_version(
+        &mut self,
+        tags: impl IntoIterator<Item = WheelTag>,
+        sdists: SDistAvailability,
+    ) {
+        self.tags.extend(tags);
+        self.sdists = Some(self.sdists.map_or(sdists, |current| current.max(sdists)));
+    }
+
+    /// Constructs the error for the package `name` whose wheels are incompatible with
+    /// `compatible_tags`.
+    pub fn into_error(
+        self,
+        name: NormalizedPackageName,
+        compatible_tags: &WheelTags,
+    ) -> NoCompatibleArtifacts {
+        let mut tags = self.tags.into_iter().collect::<Vec<_>>();
+        tags.sort_by_cached_key(|tag| (Reverse(compatible_tags.similarity(tag)), tag.to_string()));
+        tags.truncate(MAX_CLOSEST_TAGS);
+        NoCompatibleArtifacts {
+            name,
+            closest_tags: tags,
+            sdists: self.sdists.unwrap_or(SDistAvailability::Missing),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_no_compatible_artifacts() {
+        let compatible_tags: WheelTags = ["cp311-cp311-win_amd64", "py3-none-any"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let tag = |s: &str| s.parse::<WheelTag>().unwrap();
+
+        let mut incompatible = IncompatibleWheels::default();
+        incompatible.add_version(
+            [
+                tag("cp310-cp310-macosx_11_0_arm64"),
+                tag("cp311-cp311-manylinux_2_17_x86_64"),
+            ],
+            SDistAvailability::Missing,
+        );
+        incompatible.add_version(
+            [
+                tag("cp311-cp311-macosx_11_0_arm64"),
+                tag("cp310-cp310-win_amd64"),
+                tag("cp310-cp310-manylinux_2_17_x86_64"),
+            ],
+            SDistAvailability::ExcludedByPolicy,
+        );
+
+        let error = incompatible.into_error("foo".parse().unwrap(), &compatible_tags);
+        assert_eq!(
+            error.closest_tags,
+            [
+                tag("cp311-cp311-macosx_11_0_arm64"),
+                tag("cp311-cp311-manylinux_2_17_x86_64"),
+                tag("cp310-cp310-win_amd64"),
+            ]
+        );
+        assert_eq!(error.sdists, SDistAvailability::ExcludedByPolicy);
+        assert_eq!(
+            error.help().unwrap().to_string(),
+            "the closest wheels are tagged cp311-cp311-macosx_11_0_arm64, cp311-cp311-manylinux_2_17_x86_64, cp310-cp310-win_amd64\n\
+             there are sdists but they are excluded by the sdist resolution, allow sdists to build 'foo' from source"
+        );
+    }
+}
+
+#[cfg(test)]
+mod resolve_test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::resolve::{resolve, ResolveOptions};
+    use pep508_rs::Requirement;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use test_utils::windows_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_no_compatible_artifacts() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = windows_environment_markers("3.11.4");
+        let compatible_tags: WheelTags = ["cp311-cp311-win_amd64", "py3-none-any"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+
+        // The package only has a wheel for linux
+        let error = resolve(
+            package_db,
+            &[Requirement::from_str("purelib_and_platlib").unwrap()],
+            &env_markers,
+            Some(&compatible_tags),
+            HashMap::default(),
+            HashMap::default(),
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        let error = error.downcast_ref::<NoCompatibleArtifacts>().unwrap();
+        assert_eq!(error.name.as_str(), "purelib-and-platlib");
+        assert_eq!(
+            error.closest_tags,
+            ["cp38-cp38-linux_x86_64".parse::<WheelTag>().unwrap()]
+        );
+        assert_eq!(error.sdists, SDistAvailability::Missing);
+    }
+}
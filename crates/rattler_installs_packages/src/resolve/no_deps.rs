@@ -0,0 +1,265 @@
+//! Install packages without resolving their dependencies, like `pip install --no-deps`.
+//!
+//! This is useful for layered image builds and controlled deployments where the dependencies
+//! of a package are installed separately and the resolver must not pull in anything else.
+
+use super::outdated::{is_compatible, is_prerelease};
+use crate::artifacts::wheel::{unpack_wheels, InstallPaths, UnpackWheelOptions};
+use crate::artifacts::Wheel;
+use crate::index::PackageDb;
+use crate::python_env::{find_distributions_in_venv, WheelTags};
+use crate::resolve::PinnedPackage;
+use crate::types::{ArtifactInfo, Extra, NormalizedPackageName, PackageName};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Installs exactly the packages named by `requirements` into the environment at `dest` without
+/// resolving or installing any of their dependencies.
+///
+/// For every requirement the highest version that satisfies its version specifiers and that has a
+/// wheel compatible with `compatible_tags` and the python version of `env_markers` is selected.
+/// Yanked versions are skipped and pre-releases are only selected if a specifier explicitly
+/// mentions a pre-release. Direct url requirements and packages that only provide sdists are not
+/// supported.
+///
+/// If `check_dependencies` is set the dependencies of the selected wheels, evaluated against
+/// `env_markers` and the requested extras, must already be met by the distributions installed in
+/// `dest` or by the other packages that are installed. Otherwise an error listing the unmet
+/// dependencies is returned before anything is written. Extras of dependencies are not verified.
+///
+/// Returns the installed packages in the order of `requirements`.
+#[allow(clippy::too_many_arguments)]
+pub async fn install_without_dependencies<'db>(
+    package_db: &'db PackageDb,
+    requirements: &[Requirement],
+    env_markers: &MarkerEnvironment,
+    compatible_tags: Option<&WheelTags>,
+    check_dependencies: bool,
+    dest: &Path,
+    install_paths: &InstallPaths,
+    python_executable: &Path,
+    unpack_options: &UnpackWheelOptions,
+) -> miette::Result<Vec<PinnedPackage<'db>>> {
+    let mut packages = Vec::with_capacity(requirements.len());
+    let mut wheels = Vec::with_capacity(requirements.len());
+    for requirement in requirements {
+        let name: NormalizedPackageName = PackageName::from_str(&requirement.name)
+            .into_diagnostic()?
+            .into();
+        let artifacts = package_db.available_artifacts(name.clone()).await?;
+        let Some((version, artifact_info)) =
+            select_wheel(artifacts, requirement, env_markers, compatible_tags)?
+        else {
+            miette::bail!("there is no compatible wheel available for {requirement}");
+        };
+
+        wheels.push(package_db.get_artifact::<Wheel>(artifact_info).await?);
+        packages.push(PinnedPackage {
+            name,
+            version: version.clone(),
+            extras: requirement
+                .extras
+                .iter()
+                .flatten()
+                .map(|extra| Extra::from_str(extra).into_diagnostic())
+                .collect::<miette::Result<_>>()?,
+            artifacts: vec![artifact_info],
+        });
+    }
+
+    if check_dependencies {
+        let unmet = unmet_dependencies(&packages, &wheels, env_markers, dest, install_paths)?;
+        if !unmet.is_empty() {
+            miette::bail!(
+                "the dependencies of the packages are not met by the environment:\n{}",
+                unmet
+                    .iter()
+                    .map(|(name, requirement)| format!("  - {name} requires {requirement}"))
+                    .join("\n")
+            );
+        }
+    }
+
+    let wheels = wheels
+        .iter()
+        .map(|wheel| (wheel, unpack_options))
+        .collect::<Vec<_>>();
+    unpack_wheels(&wheels, dest, install_paths, python_executable, None).into_diagnostic()?;
+
+    Ok(packages)
+}
+
+/// Selects the highest version that satisfies `requirement` and returns it together with its most
+/// compatible wheel.
+fn select_wheel<'a>(
+    artifacts: &'a IndexMap<Version, Vec<ArtifactInfo>>,
+    requirement: &Requirement,
+    env_markers: &MarkerEnvironment,
+    compatible_tags: Option<&WheelTags>,
+) -> miette::Result<Option<(&'a Version, &'a ArtifactInfo)>> {
+    let specifiers = match &requirement.version_or_url {
+        Some(VersionOrUrl::Url(_)) => {
+            miette::bail!("direct url requirements are not supported: {requirement}")
+        }
+        Some(VersionOrUrl::VersionSpecifier(specifiers)) => Some(specifiers),
+        None => None,
+    };
+    let allow_prereleases = specifiers.is_some_and(|specifiers| {
+        specifiers
+            .iter()
+            .any(|specifier| is_prerelease(specifier.version()))
+    });
+
+    Ok(artifacts
+        .iter()
+        .filter(|(version, _)| allow_prereleases || !is_prerelease(version))
+        .filter(|(version, _)| specifiers.map_or(true, |specifiers| specifiers.contains(version)))
+        .sorted_by(|(a, _), (b, _)| b.cmp(a))
+        .find_map(|(version, artifacts)| {
+            let wheel = artifacts
+                .iter()
+                .filter(|artifact| artifact.is::<Wheel>())
+                .filter(|artifact| is_compatible(artifact, Some(env_markers), compatible_tags))
+                .max_by_key(|artifact| {
+                    let wheel = artifact
+                        .filename
+                        .as_wheel()
+                        .expect("only wheels are selected");
+                    compatible_tags.map_or(0, |compatible_tags| {
+                        wheel
+                            .all_tags_iter()
+                            .filter_map(|tag| compatible_tags.compatibility(&tag))
+                            .max()
+                            .unwrap_or(i32::MIN)
+                    })
+                })?;
+            Some((version, wheel))
+        }))
+}
+
+/// Returns the dependencies of `packages` that are neither met by the distributions installed in
+/// `dest` nor by `packages` themselves, together with the name of the package that requires them.
+fn unmet_dependencies(
+    packages: &[PinnedPackage<'_>],
+    wheels: &[Wheel],
+    env_markers: &MarkerEnvironment,
+    dest: &Path,
+    install_paths: &InstallPaths,
+) -> miette::Result<Vec<(NormalizedPackageName, Requirement)>> {
+    // The packages that are installed replace the installed distributions with the same name.
+    let mut available: HashMap<NormalizedPackageName, Version> =
+        find_distributions_in_venv(dest, install_paths)
+            .into_diagnostic()?
+            .into_iter()
+            .map(|distribution| (distribution.name, distribution.version))
+            .collect();
+    available.extend(
+        packages
+            .iter()
+            .map(|package| (package.name.clone(), package.version.clone())),
+    );
+
+    let mut unmet = Vec::new();
+    let mut seen = HashSet::new();
+    for (package, wheel) in packages.iter().zip(wheels) {
+        let (_, metadata) = wheel.metadata()?;
+        let extras = package
+            .extras
+            .iter()
+            .map(|extra| extra.as_str())
+            .collect::<Vec<_>>();
+        for requirement in metadata.requires_dist {
+            if let Some(markers) = requirement.marker.as_ref() {
+                if !markers.evaluate(env_markers, &extras) {
+                    continue;
+                }
+            }
+
+            let name: NormalizedPackageName = PackageName::from_str(&requirement.name)
+                .into_diagnostic()?
+                .into();
+            let is_met =
+                available
+                    .get(&name)
+                    .is_some_and(|version| match &requirement.version_or_url {
+                        Some(VersionOrUrl::VersionSpecifier(specifiers)) => {
+                            specifiers.contains(version)
+                        }
+                        Some(VersionOrUrl::Url(_)) | None => true,
+                    });
+            if !is_met && seen.insert((package.name.clone(), requirement.to_string())) {
+                unmet.push((package.name.clone(), requirement));
+            }
+        }
+    }
+
+    Ok(unmet)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_utils::linux_environment_markers;
+
+    fn artifacts(filenames: &[&str]) -> IndexMap<Version, Vec<ArtifactInfo>> {
+        let name: NormalizedPackageName = PackageName::from_str("foo").unwrap().into();
+        let mut result: IndexMap<Version, Vec<ArtifactInfo>> = IndexMap::new();
+        for filename in filenames {
+            let artifact_info = ArtifactInfo::for_test(&name, filename);
+            result
+                .entry(artifact_info.filename.version().clone())
+                .or_default()
+                .push(artifact_info);
+        }
+        result
+    }
+
+    #[test]
+    fn test_select_wheel() {
+        let artifacts = artifacts(&[
+            "foo-1.0.0-py3-none-any.whl",
+            "foo-1.1.0-cp38-cp38-win_amd64.whl",
+            "foo-1.1.0-cp38-cp38-manylinux2014_x86_64.whl",
+            "foo-1.1.0-py3-none-any.whl",
+            "foo-1.2.0.tar.gz",
+            "foo-2.0.0a1-py3-none-any.whl",
+        ]);
+        let env_markers = linux_environment_markers("3.8.5");
+        let compatible_tags = WheelTags::from_iter([
+            "cp38-cp38-manylinux2014_x86_64".parse().unwrap(),
+            "py3-none-any".parse().unwrap(),
+        ]);
+        let select = |requirement: &str| {
+            let requirement = Requirement::from_str(requirement).unwrap();
+            select_wheel(
+                &artifacts,
+                &requirement,
+                &env_markers,
+                Some(&compatible_tags),
+            )
+            .unwrap()
+            .map(|(_, artifact)| artifact.filename.to_string())
+        };
+
+        // Versions with only sdists and pre-releases are skipped, the most compatible wheel is used
+        assert_eq!(
+            select("foo").as_deref(),
+            Some("foo-1.1.0-cp38-cp38-manylinux2014_x86_64.whl")
+        );
+        assert_eq!(
+            select("foo<1.1").as_deref(),
+            Some("foo-1.0.0-py3-none-any.whl")
+        );
+        assert_eq!(
+            select("foo>=2.0.0a1").as_deref(),
+            Some("foo-2.0.0a1-py3-none-any.whl")
+        );
+        assert_eq!(select("foo>3"), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Reports packages for which newer versions are available in the index, like
+//! `pip list --outdated`.
+
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::types::{ArtifactInfo, ArtifactName, NormalizedPackageName, PackageName};
+use indexmap::IndexMap;
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use std::str::FromStr;
+
+/// A package for which a newer version is available, computed by [`find_outdated_packages`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OutdatedPackage {
+    /// The name of the package
+    pub name: NormalizedPackageName,
+
+    /// The version that is currently installed or locked.
+    pub current: Version,
+
+    /// The latest compatible version that satisfies the requirements on the package. This is
+    /// `None` if none of the versions satisfy the requirements.
+    pub latest_in_spec: Option<Version>,
+
+    /// The latest compatible version regardless of the requirements on the package.
+    pub latest: Version,
+}
+
+/// Queries the index for newer versions of the given packages. The packages can be the
+/// distributions of an installed environment or the packages of a lock file.
+///
+/// Only versions that are not yanked and that have at least one compatible artifact are considered.
+/// If `env_markers` are specified the `Requires-Python` of an artifact must include the python
+/// version of the environment. If `compatible_tags` are specified wheels must be compatible with
+/// those tags, sdists are always considered compatible. Pre-releases are only considered if the
+/// current version is a pre-release.
+///
+/// The latest version that satisfies the version specifiers of all `requirements` on a package is
+/// reported as the latest version in spec.
+///
+/// Only packages for which a newer version exists are returned, in the order they were passed in.
+pub async fn find_outdated_packages(
+    package_db: &PackageDb,
+    packages: impl IntoIterator<Item = (NormalizedPackageName, Version)>,
+    requirements: &[Requirement],
+    env_markers: Option<&MarkerEnvironment>,
+    compatible_tags: Option<&WheelTags>,
+) -> miette::Result<Vec<OutdatedPackage>> {
+    let mut result = Vec::new();
+    for (name, current) in packages {
+        let requirements = requirements
+            .iter()
+            .filter(|requirement| {
+                PackageName::from_str(&requirement.name)
+                    .is_ok_and(|n| NormalizedPackageName::from(n) == name)
+            })
+            .collect::<Vec<_>>();
+        let artifacts = package_db.available_artifacts(name.clone()).await?;
+        let latest = find_latest_versions(
+            artifacts,
+            &current,
+            &requirements,
+            env_markers,
+            compatible_tags,
+        );
+
+        if let Some((latest, latest_in_spec)) = latest {
+            if latest > &current {
+                result.push(OutdatedPackage {
+                    name,
+                    current,
+                    latest_in_spec: latest_in_spec.cloned(),
+                    latest: latest.clone(),
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the latest compatible version and the latest compatible version that satisfies the
+/// `requirements`.
+fn find_latest_versions<'a>(
+    artifacts: &'a IndexMap<Version, Vec<ArtifactInfo>>,
+    current: &Version,
+    requirements: &[&Requirement],
+    env_markers: Option<&MarkerEnvironment>,
+    compatible_tags: Option<&WheelTags>,
+) -> Option<(&'a Version, Option<&'a Version>)> {
+    let allow_prereleases = is_prerelease(current);
+    let mut compatible_versions = artifacts
+        .iter()
+        .filter(|(version, _)| allow_prereleases || !is_prerelease(version))
+        .filter(|(_, artifacts)| {
+            artifacts
+                .iter()
+                .any(|artifact| is_compatible(artifact, env_markers, compatible_tags))
+        })
+        .map(|(version, _)| version)
+        .collect::<Vec<_>>();
+    compatible_versions.sort();
+
+    let latest = *compatible_versions.last()?;
+    let latest_in_spec = compatible_versions.into_iter().rev().find(|version| {
+        requirements
+            .iter()
+            .all(|requirement| match &requirement.version_or_url {
+                Some(VersionOrUrl::VersionSpecifier(specifiers)) => specifiers.contains(version),
+                Some(VersionOrUrl::Url(_)) => false,
+                None => true,
+            })
+    });
+    Some((latest, latest_in_spec))
+}
+
+pub(super) fn is_prerelease(version: &Version) -> bool {
+    version.pre.is_some() || version.dev.is_some()
+}
+
+pub(super) fn is_compatible(
+    artifact: &ArtifactInfo,
+    env_markers: Option<&MarkerEnvironment>,
+    compatible_tags: Option<&WheelTags>,
+) -> bool {
+    if artifact.yanked.yanked {
+        return false;
+    }
+
+    if let (Some(requires_python), Some(env_markers)) = (&artifact.requires_python, env_markers) {
+        if !requires_python.contains(&env_markers.python_full_version.version) {
+            return false;
+        }
+    }
+
+    match (&artifact.filename, compatible_tags) {
+        (ArtifactName::Wheel(wheel), Some(compatible_tags)) => wheel
+            .all_tags_iter()
+            .any(|tag| compatible_tags.is_compatible(&tag)),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::Yanked;
+    use test_utils::linux_environment_markers;
+
+    fn artifacts(entries: &[(&str, &str, bool)]) -> IndexMap<Version, Vec<ArtifactInfo>> {
+        let name: NormalizedPackageName = PackageName::from_str("foo").unwrap().into();
+        let mut result: IndexMap<Version, Vec<ArtifactInfo>> = IndexMap::new();
+        for (version, requires_python, yanked) in entries {
+            let filename = format!("foo-{version}-py3-none-any.whl");
+            result
+                .entry(version.parse().unwrap())
+                .or_default()
+                .push(ArtifactInfo {
+                    requires_python: Some(requires_python.parse().unwrap()),
+                    yanked: Yanked {
+                        yanked: *yanked,
+                        reason: None,
+                    },
+                    ..ArtifactInfo::for_test(&name, &filename)
+                });
+        }
+        result
+    }
+
+    #[test]
+    fn test_find_latest_versions() {
+        let artifacts = artifacts(&[
+            ("1.0.0", ">=3.7", false),
+            ("1.1.0", ">=3.7", false),
+            ("2.0.0", ">=3.8", false),
+            ("2.1.0", ">=3.8", true),
+            ("3.0.0", ">=3.12", false),
+            ("4.0.0a1", ">=3.8", false),
+        ]);
+        let env_markers = linux_environment_markers("3.10.4");
+        let requirement = Requirement::from_str("foo<2").unwrap();
+        let current: Version = "1.0.0".parse().unwrap();
+
+        // Yanked versions, pre-releases and versions that require a newer python are skipped
+        let (latest, latest_in_spec) = find_latest_versions(
+            &artifacts,
+            &current,
+            &[&requirement],
+            Some(&env_markers),
+            None,
+        )
+        .unwrap();
+        assert_eq!(latest.to_string(), "2.0.0");
+        assert_eq!(latest_in_spec.unwrap().to_string(), "1.1.0");
+
+        // Without environment markers the python requirement is ignored
+        let (latest, _) = find_latest_versions(&artifacts, &current, &[], None, None).unwrap();
+        assert_eq!(latest.to_string(), "3.0.0");
+
+        // Pre-releases are considered if the current version is a pre-release
+        let current: Version = "3.0.0rc1".parse().unwrap();
+        let (latest, latest_in_spec) =
+            find_latest_versions(&artifacts, &current, &[], Some(&env_markers), None).unwrap();
+        assert_eq!(latest.to_string(), "4.0.0a1");
+        assert_eq!(latest_in_spec.unwrap().to_string(), "4.0.0a1");
+    }
+}
@@ -0,0 +1,207 @@
+//! An owned representation of the packages of a resolution.
+//!
+//! A [`PinnedPackage`] borrows the artifacts it refers to from the [`crate::index::PackageDb`] it
+//! was resolved with, which ties a resolution to the lifetime of the database. An
+//! [`OwnedPinnedPackage`] owns copies of the artifacts instead so it can be stored, sent to other
+//! tasks or serialized. [`OwnedPinnedPackage::as_pinned`] borrows it as a [`PinnedPackage`] again
+//! for the functions that expect one, e.g. to pass a stored resolution as locked packages.
+//!
+//! The artifacts are serialized in a format of their own instead of the format of the simple
+//! API that [`ArtifactInfo`] is read from, so a stored resolution does not depend on how an index
+//! happens to describe its files.
+
+use super::PinnedPackage;
+use crate::types::{
+    ArtifactHashes, ArtifactInfo, ArtifactName, DistInfoMetadata, Extra, NormalizedPackageName,
+    Version, Yanked,
+};
+use chrono::{DateTime, Utc};
+use pep440_rs::VersionSpecifiers;
+use rattler_digest::{serde::SerializableHash, Sha256, Sha256Hash};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, skip_serializing_none, DisplayFromStr, FromInto};
+use std::collections::HashSet;
+use url::Url;
+
+/// A [`PinnedPackage`] that owns its artifacts, see the [module documentation](self).
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnedPinnedPackage {
+    /// The name of the package
+    pub name: NormalizedPackageName,
+
+    /// The selected version
+    pub version: Version,
+
+    /// The extras that where selected either by the user or as part of the resolution.
+    #[serde(default)]
+    pub extras: HashSet<Extra>,
+
+    /// The applicable artifacts for this package, in the order of [`PinnedPackage::artifacts`].
+    #[serde(default)]
+    #[serde_as(as = "Vec<FromInto<StoredArtifact>>")]
+    pub artifacts: Vec<ArtifactInfo>,
+}
+
+/// The serialized form of an artifact of an [`OwnedPinnedPackage`], see the
+/// [module documentation](self).
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct StoredArtifact {
+    filename: ArtifactName,
+    url: Url,
+    #[serde_as(as = "Option<SerializableHash<Sha256>>")]
+    sha256: Option<Sha256Hash>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    requires_python: Option<VersionSpecifiers>,
+    #[serde(default)]
+    requires_python_raw: Option<String>,
+    #[serde(default)]
+    metadata_available: bool,
+    #[serde_as(as = "Option<SerializableHash<Sha256>>")]
+    metadata_sha256: Option<Sha256Hash>,
+    #[serde(default)]
+    yanked: bool,
+    #[serde(default)]
+    yanked_reason: Option<String>,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    upload_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    provenance: Option<Url>,
+}
+
+impl From<ArtifactInfo> for StoredArtifact {
+    fn from(artifact: ArtifactInfo) -> Self {
+        Self {
+            filename: artifact.filename,
+            url: artifact.url,
+            sha256: artifact.hashes.and_then(|hashes| hashes.sha256),
+            requires_python: artifact.requires_python,
+            requires_python_raw: artifact.requires_python_raw,
+            metadata_available: artifact.dist_info_metadata.available,
+            metadata_sha256: artifact.dist_info_metadata.hashes.sha256,
+            yanked: artifact.yanked.yanked,
+            yanked_reason: artifact.yanked.reason,
+            size: artifact.size,
+            upload_time: artifact.upload_time,
+            provenance: artifact.provenance,
+        }
+    }
+}
+
+impl From<StoredArtifact> for ArtifactInfo {
+    fn from(artifact: StoredArtifact) -> Self {
+        Self {
+            filename: artifact.filename,
+            url: artifact.url,
+            hashes: artifact.sha256.map(|sha256| ArtifactHashes {
+                sha256: Some(sha256),
+            }),
+            requires_python: artifact.requires_python,
+            requires_python_raw: artifact.requires_python_raw,
+            dist_info_metadata: DistInfoMetadata {
+                available: artifact.metadata_available,
+                hashes: ArtifactHashes {
+                    sha256: artifact.metadata_sha256,
+                },
+            },
+            yanked: Yanked {
+                yanked: artifact.yanked,
+                reason: artifact.yanked_reason,
+            },
+            size: artifact.size,
+            upload_time: artifact.upload_time,
+            provenance: artifact.provenance,
+        }
+    }
+}
+
+impl OwnedPinnedPackage {
+    /// Borrows the package as a [`PinnedPackage`].
+    pub fn as_pinned(&self) -> PinnedPackage<'_> {
+        PinnedPackage {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            extras: self.extras.clone(),
+            artifacts: self.artifacts.iter().collect(),
+        }
+    }
+}
+
+impl PinnedPackage<'_> {
+    /// Copies the artifacts of the package so it no longer borrows from the package database,
+    /// see [`OwnedPinnedPackage`].
+    pub fn into_owned(self) -> OwnedPinnedPackage {
+        OwnedPinnedPackage {
+            name: self.name,
+            version: self.version,
+            extras: self.extras,
+            artifacts: self.artifacts.into_iter().cloned().collect(),
+        }
+    }
+}
+
+impl From<PinnedPackage<'_>> for OwnedPinnedPackage {
+    fn from(package: PinnedPackage<'_>) -> Self {
+        package.into_owned()
+    }
+}
+
+impl<'a> From<&'a OwnedPinnedPackage> for PinnedPackage<'a> {
+    fn from(package: &'a OwnedPinnedPackage) -> Self {
+        package.as_pinned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_owned_pinned_package() {
+        let name: NormalizedPackageName = "wordle-python".parse().unwrap();
+        let artifact = ArtifactInfo {
+            dist_info_metadata: DistInfoMetadata {
+                available: true,
+                hashes: Default::default(),
+            },
+            yanked: Yanked {
+                yanked: true,
+                reason: Some(String::from("broken")),
+            },
+            size: Some(1024),
+            ..ArtifactInfo::for_test(&name, "wordle_python-2.3.32-py3-none-any.whl")
+        };
+        let package = PinnedPackage {
+            name: name.clone(),
+            version: "2.3.32".parse().unwrap(),
+            extras: HashSet::from_iter(["cli".parse().unwrap()]),
+            artifacts: vec![&artifact],
+        };
+
+        let owned = package.into_owned();
+        drop(artifact);
+        assert_eq!(owned.artifacts[0].size, Some(1024));
+
+        // The owned package survives a round trip through its serialized form
+        let serialized = serde_json::to_value(&owned).unwrap();
+        assert_eq!(serialized["artifacts"][0]["metadata-available"], true);
+        assert_eq!(serialized["artifacts"][0]["yanked"], true);
+        assert_eq!(serialized["artifacts"][0]["yanked-reason"], "broken");
+        let deserialized: OwnedPinnedPackage = serde_json::from_value(serialized).unwrap();
+        assert_eq!(deserialized, owned);
+
+        let pinned = deserialized.as_pinned();
+        assert_eq!(pinned.name, name);
+        assert_eq!(pinned.version, owned.version);
+        assert_eq!(pinned.extras, owned.extras);
+        assert_eq!(
+            pinned.artifacts[0].filename.to_string(),
+            "wordle_python-2.3.32-py3-none-any.whl"
+        );
+    }
+}
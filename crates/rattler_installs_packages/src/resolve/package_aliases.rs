@@ -0,0 +1,161 @@
+//! Aliases for packages that are known under more than one name.
+//!
+//! Some packages are required under a name that is not the name they are published under on the
+//! index, e.g. old metadata that still requires `PIL` while the maintained fork is published as
+//! `Pillow`, or a project that was renamed. Names that only differ in case or punctuation
+//! (`Pillow` and `pillow`, `typing_extensions` and `typing-extensions`) are already the same
+//! package after [normalization](crate::types::NormalizedPackageName) and do not need an alias.
+//!
+//! The resolver replaces every requirement on an alias with a requirement on the name the alias
+//! refers to, both in the requirements passed to the resolver and in the dependencies of packages.
+//! Every alias that is used is reported once as a [`super::ResolveEvent::AliasUsed`].
+
+use crate::types::NormalizedPackageName;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A map from aliases to the names of the packages they refer to, see the [module
+/// documentation](self).
+///
+/// ```
+/// # use rattler_installs_packages::resolve::PackageAliases;
+/// let aliases = PackageAliases::default().with_alias("PIL".parse().unwrap(), "Pillow".parse().unwrap());
+/// assert_eq!(aliases.resolve(&"pil".parse().unwrap()).unwrap().as_str(), "pillow");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PackageAliases {
+    aliases: BTreeMap<NormalizedPackageName, NormalizedPackageName>,
+}
+
+impl PackageAliases {
+    /// Constructs the aliases from a map of aliases to the names they refer to.
+    pub fn new(aliases: BTreeMap<NormalizedPackageName, NormalizedPackageName>) -> Self {
+        Self { aliases }
+    }
+
+    /// Adds an alias. A requirement on `alias` is resolved as a requirement on `name`.
+    pub fn with_alias(mut self, alias: NormalizedPackageName, name: NormalizedPackageName) -> Self {
+        self.aliases.insert(alias, name);
+        self
+    }
+
+    /// Returns the aliases and the names they refer to.
+    pub fn aliases(
+        &self,
+    ) -> impl Iterator<Item = (&NormalizedPackageName, &NormalizedPackageName)> {
+        self.aliases.iter()
+    }
+
+    /// Returns true if no aliases are defined.
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Returns the name of the package that `name` is an alias of, or `None` if `name` is not an
+    /// alias. Aliases of aliases are followed, an alias that (indirectly) refers to itself is
+    /// ignored.
+    pub fn resolve(&self, name: &NormalizedPackageName) -> Option<&NormalizedPackageName> {
+        let mut resolved = self.aliases.get(name)?;
+        for _ in 0..self.aliases.len() {
+            match self.aliases.get(resolved) {
+                Some(next) => resolved = next,
+                None => return (resolved != name).then_some(resolved),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn name(name: &str) -> NormalizedPackageName {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_alias() {
+        let aliases: PackageAliases =
+            serde_json::from_str(r#"{"PIL": "Pillow", "sklearn": "scikit_learn"}"#).unwrap();
+        assert_eq!(aliases.resolve(&name("pil")), Some(&name("pillow")));
+        assert_eq!(
+            aliases.resolve(&name("sklearn")),
+            Some(&name("scikit-learn"))
+        );
+        assert_eq!(aliases.resolve(&name("pillow")), None);
+
+        // Aliases of aliases are followed
+        let aliases = aliases.with_alias(name("python-imaging"), name("PIL"));
+        assert_eq!(
+            aliases.resolve(&name("python-imaging")),
+            Some(&name("pillow"))
+        );
+
+        // Cycles are ignored
+        let aliases = PackageAliases::default()
+            .with_alias(name("a"), name("b"))
+            .with_alias(name("b"), name("a"));
+        assert_eq!(aliases.resolve(&name("a")), None);
+    }
+}
+
+#[cfg(test)]
+mod resolve_test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::resolve::{resolve, resolve_event_channel, ResolveEvent, ResolveOptions};
+    use futures::StreamExt;
+    use pep508_rs::Requirement;
+    use std::collections::HashMap;
+    use std::str::FromStr;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_alias() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+
+        let (sender, events) = resolve_event_channel();
+        let options = ResolveOptions {
+            package_aliases: PackageAliases::default()
+                .with_alias("Wordle".parse().unwrap(), "wordle_python".parse().unwrap()),
+            events: Some(sender),
+            ..Default::default()
+        };
+        let packages = resolve(
+            package_db,
+            &[Requirement::from_str("wordle").unwrap()],
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+        assert!(packages
+            .iter()
+            .any(|package| package.name.as_str() == "wordle-python"));
+        assert!(packages
+            .iter()
+            .all(|package| package.name.as_str() != "wordle"));
+
+        drop(options);
+        let events = events.collect::<Vec<_>>().await;
+        let alias_events = events
+            .iter()
+            .filter(|event| matches!(event, ResolveEvent::AliasUsed { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            alias_events,
+            [&ResolveEvent::AliasUsed {
+                alias: "wordle".parse().unwrap(),
+                name: "wordle-python".parse().unwrap(),
+                required_by: None,
+            }]
+        );
+    }
+}
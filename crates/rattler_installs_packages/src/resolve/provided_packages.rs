@@ -0,0 +1,123 @@
+//! Packages that are provided by the platform instead of being installed from an index.
+//!
+//! Some environments already contain packages that are not managed by rip, e.g. `torch` that is
+//! part of a system image with a matching CUDA installation, or `certifi` that is managed by the
+//! operating system. Requirements on these packages must not pull in another version from the
+//! index.
+//!
+//! The resolver selects a provided package with the version it is provided with without looking
+//! at the index. The selected package has no artifacts and no dependencies, the dependencies of a
+//! provided package are provided as well. A requirement that does not match the provided version,
+//! or that references an artifact by its url, cannot be satisfied. Provided packages are skipped
+//! when the packages are installed with [`crate::install::InstallBuilder`].
+
+use crate::types::{NormalizedPackageName, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The packages that are provided by the platform and their versions, see the [module
+/// documentation](self).
+///
+/// ```
+/// # use rattler_installs_packages::resolve::ProvidedPackages;
+/// let provided = ProvidedPackages::default().with_package("torch".parse().unwrap(), "2.1.0".parse().unwrap());
+/// assert_eq!(provided.get(&"torch".parse().unwrap()).unwrap().to_string(), "2.1.0");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ProvidedPackages {
+    packages: BTreeMap<NormalizedPackageName, Version>,
+}
+
+impl ProvidedPackages {
+    /// Constructs the provided packages from a map of package names to the provided versions.
+    pub fn new(packages: BTreeMap<NormalizedPackageName, Version>) -> Self {
+        Self { packages }
+    }
+
+    /// Adds a package that is provided with the given version.
+    pub fn with_package(mut self, name: NormalizedPackageName, version: Version) -> Self {
+        self.packages.insert(name, version);
+        self
+    }
+
+    /// Returns the provided packages and their versions.
+    pub fn packages(&self) -> impl Iterator<Item = (&NormalizedPackageName, &Version)> {
+        self.packages.iter()
+    }
+
+    /// Returns true if no packages are provided.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Returns the version `name` is provided with, or `None` if the package is not provided.
+    pub fn get(&self, name: &NormalizedPackageName) -> Option<&Version> {
+        self.packages.get(name)
+    }
+
+    /// Returns true if `name` is provided by the platform.
+    pub fn contains(&self, name: &NormalizedPackageName) -> bool {
+        self.packages.contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::resolve::{resolve, ResolveOptions};
+    use pep508_rs::Requirement;
+    use std::collections::HashMap;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_provided_packages() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let env_markers = linux_environment_markers("3.10.4");
+
+        // A provided package is selected without consulting the index, even a version the index
+        // does not have.
+        let options = ResolveOptions {
+            provided_packages: ProvidedPackages::default()
+                .with_package("wordle-python".parse().unwrap(), "9.0".parse().unwrap())
+                .with_package("torch".parse().unwrap(), "2.1.0".parse().unwrap()),
+            ..Default::default()
+        };
+        let requirements: [Requirement; 2] = [
+            "wordle_python>=2".parse().unwrap(),
+            "torch".parse().unwrap(),
+        ];
+        let mut packages = resolve(
+            package_db,
+            &requirements,
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )
+        .await
+        .unwrap();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name.as_str(), "torch");
+        assert_eq!(packages[1].version.to_string(), "9.0");
+        assert!(packages.iter().all(|package| package.artifacts.is_empty()));
+
+        // Requirements that do not match the provided version cannot be satisfied
+        let requirements: [Requirement; 1] = ["wordle_python<3".parse().unwrap()];
+        assert!(resolve(
+            package_db,
+            &requirements,
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )
+        .await
+        .is_err());
+    }
+}
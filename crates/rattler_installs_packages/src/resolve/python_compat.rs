@@ -0,0 +1,196 @@
+//! Determines which python versions a set of requirements can be installed on.
+
+use super::best_effort::is_unsolvable;
+use super::{resolve, ResolveOptions};
+use crate::index::PackageDb;
+use pep440_rs::{Operator, Version, VersionSpecifier, VersionSpecifiers};
+use pep508_rs::{MarkerEnvironment, Requirement};
+use std::collections::HashMap;
+
+/// The result of [`python_compatibility`].
+#[derive(Debug, Clone, Default)]
+pub struct PythonCompatibility {
+    /// The python versions for which a valid resolution exists, sorted from low to high.
+    pub supported: Vec<Version>,
+
+    /// The python versions for which no valid resolution exists together with the reason the
+    /// resolution failed, sorted from low to high.
+    pub unsupported: Vec<(Version, String)>,
+}
+
+impl PythonCompatibility {
+    /// Returns a `requires-python` specifier that covers the supported python versions, starting
+    /// at the lowest supported version up to the first unsupported version above it. Returns
+    /// `None` if none of the python versions are supported.
+    pub fn suggested_requires_python(&self) -> Option<VersionSpecifiers> {
+        let lowest = self.supported.first()?;
+        let mut specifiers =
+            vec![
+                VersionSpecifier::new(Operator::GreaterThanEqual, lowest.clone(), false)
+                    .expect("failed to construct version specifier"),
+            ];
+        if let Some((upper, _)) = self
+            .unsupported
+            .iter()
+            .find(|(version, _)| version > lowest)
+        {
+            specifiers.push(
+                VersionSpecifier::new(Operator::LessThan, upper.clone(), false)
+                    .expect("failed to construct version specifier"),
+            );
+        }
+        Some(VersionSpecifiers::from_iter(specifiers))
+    }
+}
+
+/// Determines for which of the given `python_versions` a valid resolution of `requirements` exists.
+///
+/// For every python version the requirements are resolved with `env_markers` adjusted to that
+/// python version. Artifacts whose `Requires-Python` metadata does not include the python version
+/// are not selected (see [`ResolveOptions::check_requires_python`]), so the result combines the
+/// python requirements of all packages in the dependency tree. Because the supported wheel tags
+/// depend on the python version, artifacts are not filtered by their tags.
+///
+/// `python_versions` are typically the minor versions a library author wants to support, e.g.
+/// `3.8` to `3.12`.
+///
+/// Only resolutions that fail because the requirements cannot be satisfied mark a python version
+/// as unsupported. Other errors, e.g. failing to reach the index or to build an sdist, are
+/// returned.
+pub async fn python_compatibility(
+    package_db: &PackageDb,
+    requirements: &[Requirement],
+    env_markers: &MarkerEnvironment,
+    python_versions: &[Version],
+    options: &ResolveOptions,
+) -> miette::Result<PythonCompatibility> {
+    let options = ResolveOptions {
+        check_requires_python: true,
+        ..options.clone()
+    };
+
+    let mut python_versions = python_versions.to_vec();
+    python_versions.sort();
+    python_versions.dedup();
+
+    let mut result = PythonCompatibility::default();
+    for python_version in python_versions {
+        let env_markers = markers_for_python_version(env_markers, &python_version);
+        match resolve(
+            package_db,
+            requirements,
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )
+        .await
+        {
+            Ok(_) => result.supported.push(python_version),
+            Err(err) if is_unsolvable(&err) => {
+                result.unsupported.push((python_version, err.to_string()))
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns a copy of `env_markers` with the python version replaced by `python_version`.
+fn markers_for_python_version(
+    env_markers: &MarkerEnvironment,
+    python_version: &Version,
+) -> MarkerEnvironment {
+    let release = |idx: usize| python_version.release.get(idx).copied().unwrap_or(0);
+    let full_version = format!("{}.{}.{}", release(0), release(1), release(2));
+    MarkerEnvironment {
+        implementation_version: full_version
+            .parse()
+            .expect("failed to parse python version"),
+        python_full_version: full_version
+            .parse()
+            .expect("failed to parse python version"),
+        python_version: format!("{}.{}", release(0), release(1))
+            .parse()
+            .expect("failed to parse python version"),
+        ..env_markers.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::index::{DownloadDecision, DownloadRejected, FirstDownload};
+    use std::sync::Arc;
+    use test_utils::linux_environment_markers;
+
+    #[test]
+    fn test_suggested_requires_python() {
+        let version = |v: &str| v.parse::<Version>().unwrap();
+        let compatibility = PythonCompatibility {
+            supported: vec![version("3.9"), version("3.10"), version("3.11")],
+            unsupported: vec![
+                (version("3.8"), String::from("no solution")),
+                (version("3.12"), String::from("no solution")),
+            ],
+        };
+        let requires_python = compatibility.suggested_requires_python().unwrap();
+        assert!(requires_python.contains(&version("3.9")));
+        assert!(requires_python.contains(&version("3.11.5")));
+        assert!(!requires_python.contains(&version("3.8")));
+        assert!(!requires_python.contains(&version("3.12")));
+
+        let compatibility = PythonCompatibility {
+            supported: vec![version("3.11"), version("3.12")],
+            unsupported: vec![(version("3.10"), String::from("no solution"))],
+        };
+        let requires_python = compatibility.suggested_requires_python().unwrap();
+        assert!(requires_python.contains(&version("3.13")));
+        assert!(!requires_python.contains(&version("3.10")));
+
+        assert!(PythonCompatibility::default()
+            .suggested_requires_python()
+            .is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_python_compatibility_error() {
+        let TestIndex {
+            server: _server,
+            package_db,
+            index_dir: _index_dir,
+            cache_dir: _cache_dir,
+        } = TestIndex::start().await;
+        let package_db =
+            package_db.with_download_approval(Arc::new(|download: &FirstDownload<'_>| {
+                DownloadDecision::Reject {
+                    reason: format!("{} is not approved", download.name),
+                }
+            }));
+
+        // A failure that is unrelated to the python version is returned instead of marking the
+        // python version as unsupported
+        let err = python_compatibility(
+            &package_db,
+            &["wordle_python".parse().unwrap()],
+            &linux_environment_markers("3.10.4"),
+            &["3.10".parse().unwrap()],
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.downcast_ref::<DownloadRejected>().is_some());
+    }
+
+    #[test]
+    fn test_markers_for_python_version() {
+        let env_markers = linux_environment_markers("3.10.4");
+        let markers = markers_for_python_version(&env_markers, &"3.12".parse().unwrap());
+        assert_eq!(markers.python_version.string, "3.12");
+        assert_eq!(markers.python_full_version.string, "3.12.0");
+        assert_eq!(markers.sys_platform, "linux");
+    }
+}
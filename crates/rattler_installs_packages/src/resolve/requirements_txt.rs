@@ -0,0 +1,129 @@
+use super::PinnedPackage;
+use crate::types::NormalizedPackageName;
+use itertools::Itertools;
+use pep508_rs::MarkerEnvironment;
+use std::fmt::Write;
+use thiserror::Error;
+
+/// An error that can occur when exporting a resolution with [`to_requirements_txt`].
+#[derive(Debug, Error)]
+pub enum RequirementsTxtError {
+    /// None of the artifacts of a package have a known sha256 hash. Pip refuses to install any
+    /// requirement without a hash as soon as a single requirement specifies a hash.
+    #[error("cannot export {0} because none of its artifacts have a known sha256 hash")]
+    MissingHashes(NormalizedPackageName),
+}
+
+/// Exports a resolution to a pip compatible `requirements.txt`. This allows installing an
+/// environment resolved by rip with `pip install --require-hashes -r requirements.txt`.
+///
+/// Every package is pinned to the resolved version with the sha256 hashes of all its artifacts so
+/// pip can select the artifact that suits it best. When `env_markers` are specified every
+/// requirement is restricted to environments with the same python version, platform and machine
+/// as the environment the resolution was made for.
+pub fn to_requirements_txt(
+    packages: &[PinnedPackage<'_>],
+    env_markers: Option<&MarkerEnvironment>,
+) -> Result<String, RequirementsTxtError> {
+    let marker = env_markers.map(|env| {
+        format!(
+            "python_version == \"{}\" and sys_platform == \"{}\" and platform_machine == \"{}\"",
+            env.python_version.string, env.sys_platform, env.platform_machine
+        )
+    });
+
+    let mut result = String::from("# This file was generated by rip\n");
+    for package in packages.iter().sorted_by(|a, b| a.name.cmp(&b.name)) {
+        let hashes = package
+            .artifacts
+            .iter()
+            .filter_map(|artifact| artifact.hashes.as_ref()?.sha256.as_ref())
+            .map(|hash| format!("{hash:x}"))
+            .sorted()
+            .dedup()
+            .collect::<Vec<_>>();
+        if hashes.is_empty() {
+            return Err(RequirementsTxtError::MissingHashes(package.name.clone()));
+        }
+
+        result.push_str(package.name.as_str());
+        if !package.extras.is_empty() {
+            let extras = package.extras.iter().map(|e| e.as_str()).sorted().join(",");
+            write!(result, "[{extras}]").unwrap();
+        }
+        write!(result, "=={}", package.version).unwrap();
+        if let Some(marker) = &marker {
+            write!(result, " ; {marker}").unwrap();
+        }
+        for hash in hashes {
+            write!(result, " \\\n    --hash=sha256:{hash}").unwrap();
+        }
+        result.push('\n');
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{ArtifactHashes, ArtifactInfo, Extra, PackageName};
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    fn artifact_info(name: &NormalizedPackageName, filename: &str, content: &[u8]) -> ArtifactInfo {
+        ArtifactInfo {
+            hashes: Some(ArtifactHashes {
+                sha256: Some(
+                    rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(content),
+                ),
+            }),
+            ..ArtifactInfo::for_test(name, filename)
+        }
+    }
+
+    #[test]
+    fn test_requirements_txt() {
+        let black: NormalizedPackageName = PackageName::from_str("black").unwrap().into();
+        let click: NormalizedPackageName = PackageName::from_str("click").unwrap().into();
+        let black_wheel = artifact_info(&black, "black-23.1.0-py3-none-any.whl", b"wheel");
+        let black_sdist = artifact_info(&black, "black-23.1.0.tar.gz", b"sdist");
+        let click_wheel = artifact_info(&click, "click-8.1.7-py3-none-any.whl", b"click");
+
+        let packages = [
+            PinnedPackage {
+                name: click.clone(),
+                version: "8.1.7".parse().unwrap(),
+                extras: HashSet::new(),
+                artifacts: vec![&click_wheel],
+            },
+            PinnedPackage {
+                name: black.clone(),
+                version: "23.1.0".parse().unwrap(),
+                extras: HashSet::from_iter([Extra::from_str("d").unwrap()]),
+                artifacts: vec![&black_wheel, &black_sdist],
+            },
+        ];
+
+        insta::assert_snapshot!(to_requirements_txt(&packages, None).unwrap(), @r###"
+        # This file was generated by rip
+        black[d]==23.1.0 \
+            --hash=sha256:714772a9f82b2aeb4fa5f7092d00fe4ac4c9cdeb6800840b6ed39ea64c4d785a \
+            --hash=sha256:ba59926159d2aa256eb8739b8da7e2b574b960e1202c6d624cbe981cef996c91
+        click==8.1.7 \
+            --hash=sha256:e5c7ffac26fed654fe62045898f55b551a0dc120badf3d116bcd364418f3ec16
+        "###);
+
+        // A package without any hashes cannot be exported.
+        let packages = [PinnedPackage {
+            name: click.clone(),
+            version: "8.1.7".parse().unwrap(),
+            extras: HashSet::new(),
+            artifacts: vec![],
+        }];
+        assert!(matches!(
+            to_requirements_txt(&packages, None),
+            Err(RequirementsTxtError::MissingHashes(name)) if name == click
+        ));
+    }
+}
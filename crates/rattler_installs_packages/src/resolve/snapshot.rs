@@ -0,0 +1,360 @@
+//! Export a resolved environment to a portable archive and recreate it elsewhere.
+//!
+//! An archive is a tar file that contains a `manifest.json` describing the packages of the
+//! environment and the wheels that were installed. When the environment is recreated the wheels
+//! from the archive are reused if they are compatible with the target interpreter. Only the
+//! packages whose wheels are interpreter or platform specific and incompatible with the target
+//! are resolved again. This makes archives well suited to cache environments on CI.
+
+use crate::artifacts::wheel::{unpack_wheels, InstallPaths, UnpackWheelOptions};
+use crate::artifacts::{InstallProvenance, Wheel};
+use crate::index::PackageDb;
+use crate::python_env::{Distribution, DistributionKind, WheelTags};
+use crate::resolve::{resolve, PinnedPackage, ResolveOptions};
+use crate::types::{
+    Artifact, ArtifactInfo, ArtifactName, Extra, NormalizedPackageName, Version, WheelFilename,
+};
+use crate::utils::ReadAndSeek;
+use miette::IntoDiagnostic;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const MANIFEST_PATH: &str = "manifest.json";
+const WHEELS_DIR: &str = "wheels";
+
+/// Describes the contents of an environment archive, see [`export_environment`].
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EnvironmentSnapshot {
+    /// The packages of the environment
+    pub packages: Vec<SnapshotPackage>,
+}
+
+/// A single package in an [`EnvironmentSnapshot`].
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SnapshotPackage {
+    /// The name of the package
+    pub name: NormalizedPackageName,
+
+    /// The version of the package
+    pub version: Version,
+
+    /// The extras of the package that were selected
+    #[serde(default)]
+    pub extras: Vec<Extra>,
+
+    /// The filename of the wheel that is stored in the archive. This is `None` if the package
+    /// did not have a wheel, in which case it is always resolved again.
+    pub wheel: Option<String>,
+}
+
+/// An artifact that only holds the raw bytes of a wheel, used to copy wheels into an archive
+/// without parsing them.
+struct WheelBytes {
+    name: WheelFilename,
+    bytes: Box<dyn ReadAndSeek + Send>,
+}
+
+impl Artifact for WheelBytes {
+    type Name = WheelFilename;
+
+    fn new(name: Self::Name, bytes: Box<dyn ReadAndSeek + Send>) -> miette::Result<Self> {
+        Ok(Self { name, bytes })
+    }
+
+    fn name(&self) -> &Self::Name {
+        &self.name
+    }
+}
+
+/// Writes the resolved `packages` of an environment together with their wheels as a tar archive
+/// to `writer`. For every package the first wheel of its artifacts is stored, which is the most
+/// compatible wheel if the packages were resolved with compatible tags.
+///
+/// Use [`import_environment`] to recreate the environment from the archive.
+pub async fn export_environment(
+    package_db: &PackageDb,
+    packages: &[PinnedPackage<'_>],
+    writer: impl Write,
+) -> miette::Result<EnvironmentSnapshot> {
+    let entries = packages.iter().map(|package| {
+        let mut extras = package.extras.iter().cloned().collect::<Vec<_>>();
+        extras.sort();
+        let wheel = package
+            .artifacts
+            .iter()
+            .find(|artifact| artifact.is::<Wheel>());
+        (package.name.clone(), package.version.clone(), extras, wheel)
+    });
+    write_archive(package_db, entries.collect(), writer).await
+}
+
+/// Writes the `distributions` that are installed in an environment together with their wheels as
+/// a tar archive to `writer`. The wheels are looked up on the index: the wheel the distribution
+/// was installed from is preferred, followed by a wheel that matches the installed tags.
+///
+/// Distributions that are not available as a wheel on the index, like legacy or locally built
+/// installs, are stored without a wheel and are resolved again by [`import_environment`].
+pub async fn export_installed_environment(
+    package_db: &PackageDb,
+    distributions: &[Distribution],
+    writer: impl Write,
+) -> miette::Result<EnvironmentSnapshot> {
+    let mut entries = Vec::with_capacity(distributions.len());
+    for dist in distributions {
+        let wheel = if dist.kind == DistributionKind::DistInfo {
+            installed_wheel(package_db, dist).await?
+        } else {
+            None
+        };
+        entries.push((dist.name.clone(), dist.version.clone(), Vec::new(), wheel));
+    }
+    write_archive(package_db, entries, writer).await
+}
+
+/// Finds the wheel on the index that an installed distribution was most likely installed from.
+async fn installed_wheel<'db>(
+    package_db: &'db PackageDb,
+    dist: &Distribution,
+) -> miette::Result<Option<&'db ArtifactInfo>> {
+    let Ok(artifacts) = package_db.available_artifacts(dist.name.clone()).await else {
+        return Ok(None);
+    };
+    let Some(artifacts) = artifacts.get(&dist.version) else {
+        return Ok(None);
+    };
+    let mut wheels = artifacts.iter().filter(|artifact| artifact.is::<Wheel>());
+
+    // Prefer the exact wheel that was recorded when the distribution was installed
+    if let Some(InstallProvenance::Wheel { url, .. }) = &dist.provenance {
+        if let Some(wheel) = wheels.clone().find(|artifact| &artifact.url == url) {
+            return Ok(Some(wheel));
+        }
+    }
+
+    // Otherwise select a wheel with the same tags as the installed distribution
+    if let Some(tags) = &dist.tags {
+        let matching = wheels.clone().find(|artifact| match &artifact.filename {
+            ArtifactName::Wheel(name) => name.all_tags_iter().any(|tag| tags.contains(&tag)),
+            _ => false,
+        });
+        if matching.is_some() {
+            return Ok(matching);
+        }
+    }
+
+    Ok(wheels.next())
+}
+
+/// A package to store in an archive together with the wheel to store for it.
+type ArchiveEntry<'a> = (
+    NormalizedPackageName,
+    Version,
+    Vec<Extra>,
+    Option<&'a ArtifactInfo>,
+);
+
+/// Writes the wheels of the given packages and the manifest describing them to a tar archive.
+async fn write_archive(
+    package_db: &PackageDb,
+    entries: Vec<ArchiveEntry<'_>>,
+    writer: impl Write,
+) -> miette::Result<EnvironmentSnapshot> {
+    let mut builder = tar::Builder::new(writer);
+    let mut snapshot = EnvironmentSnapshot {
+        packages: Vec::with_capacity(entries.len()),
+    };
+
+    for (name, version, extras, wheel) in entries {
+        let wheel = match wheel {
+            Some(artifact_info) => {
+                let mut artifact = package_db.get_artifact::<WheelBytes>(artifact_info).await?;
+                let mut bytes = Vec::new();
+                artifact.bytes.read_to_end(&mut bytes).into_diagnostic()?;
+
+                let filename = artifact.name.to_string();
+                append_file(&mut builder, &format!("{WHEELS_DIR}/{filename}"), &bytes)?;
+                Some(filename)
+            }
+            None => None,
+        };
+
+        snapshot.packages.push(SnapshotPackage {
+            name,
+            version,
+            extras,
+            wheel,
+        });
+    }
+
+    let manifest = serde_json::to_vec_pretty(&snapshot).into_diagnostic()?;
+    append_file(&mut builder, MANIFEST_PATH, &manifest)?;
+    builder.into_inner().into_diagnostic()?;
+
+    Ok(snapshot)
+}
+
+/// Appends a file with the given contents to a tar archive.
+fn append_file<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    contents: &[u8],
+) -> miette::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, path, contents)
+        .into_diagnostic()
+}
+
+/// Recreates an environment from an archive created with [`export_environment`] by installing
+/// its packages into `dest`.
+///
+/// Wheels from the archive that are compatible with `compatible_tags` are installed as is.
+/// Packages without a compatible wheel are resolved again for the target interpreter, while the
+/// other packages are locked to their archived versions. The packages that are resolved again
+/// must have a wheel available. Returns the packages of the recreated environment.
+#[allow(clippy::too_many_arguments)]
+pub async fn import_environment<'db>(
+    package_db: &'db PackageDb,
+    reader: impl Read,
+    env_markers: &MarkerEnvironment,
+    compatible_tags: &WheelTags,
+    resolve_options: &ResolveOptions,
+    dest: &Path,
+    install_paths: &InstallPaths,
+    python_executable: &Path,
+) -> miette::Result<Vec<PinnedPackage<'db>>> {
+    let archive_dir = tempfile::tempdir().into_diagnostic()?;
+    tar::Archive::new(reader)
+        .unpack(archive_dir.path())
+        .into_diagnostic()?;
+
+    let manifest = std::fs::read(archive_dir.path().join(MANIFEST_PATH)).into_diagnostic()?;
+    let snapshot: EnvironmentSnapshot = serde_json::from_slice(&manifest).into_diagnostic()?;
+
+    // Split the packages in the ones that can be installed from the archive and the ones that
+    // need to be resolved again.
+    let mut archived_wheels = Vec::new();
+    let mut locked_packages = HashMap::new();
+    let mut requirements = Vec::new();
+    for package in snapshot.packages {
+        let wheel = package.wheel.as_ref().and_then(|filename| {
+            let name = WheelFilename::from_filename(filename, &package.name).ok()?;
+            name.all_tags_iter()
+                .any(|tag| compatible_tags.is_compatible(&tag))
+                .then_some(name)
+        });
+
+        match wheel {
+            Some(name) => {
+                let path = archive_dir.path().join(WHEELS_DIR).join(name.to_string());
+                archived_wheels.push(Wheel::from_path(&path, &package.name)?);
+                locked_packages.insert(
+                    package.name.clone(),
+                    PinnedPackage {
+                        name: package.name,
+                        version: package.version,
+                        extras: package.extras.into_iter().collect(),
+                        artifacts: Vec::new(),
+                    },
+                );
+            }
+            None => {
+                let specifier = format!("=={}", package.version);
+                requirements.push(Requirement {
+                    name: package.name.as_str().to_string(),
+                    extras: Some(
+                        package
+                            .extras
+                            .iter()
+                            .map(|extra| extra.as_str().to_string())
+                            .collect(),
+                    ),
+                    version_or_url: Some(VersionOrUrl::VersionSpecifier(
+                        FromStr::from_str(&specifier).into_diagnostic()?,
+                    )),
+                    marker: None,
+                });
+            }
+        }
+    }
+
+    // Resolve the packages that are not compatible with the target interpreter
+    let mut packages = locked_packages.values().cloned().collect::<Vec<_>>();
+    let mut resolved_wheels = Vec::new();
+    if !requirements.is_empty() {
+        let resolved = resolve(
+            package_db,
+            requirements.iter(),
+            env_markers,
+            Some(compatible_tags),
+            locked_packages.clone(),
+            HashMap::default(),
+            resolve_options,
+        )
+        .await?;
+
+        for package in resolved {
+            if locked_packages.contains_key(&package.name) {
+                continue;
+            }
+            let artifact_info = package
+                .artifacts
+                .iter()
+                .find(|artifact| matches!(artifact.filename, ArtifactName::Wheel(_)))
+                .ok_or_else(|| {
+                    miette::miette!(
+                        "there is no wheel available for {}=={}",
+                        package.name.as_str(),
+                        package.version
+                    )
+                })?;
+            resolved_wheels.push(package_db.get_artifact::<Wheel>(artifact_info).await?);
+            packages.push(package);
+        }
+    }
+
+    // Install all the wheels
+    let options = UnpackWheelOptions {
+        compatible_tags: Some(compatible_tags.clone()),
+        session_log: package_db.session_log().cloned(),
+        ..Default::default()
+    };
+    let wheels = archived_wheels
+        .iter()
+        .chain(resolved_wheels.iter())
+        .map(|wheel| (wheel, &options))
+        .collect::<Vec<_>>();
+    unpack_wheels(&wheels, dest, install_paths, python_executable, None).into_diagnostic()?;
+
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let snapshot = EnvironmentSnapshot {
+            packages: vec![SnapshotPackage {
+                name: "rich".parse().unwrap(),
+                version: "13.6.0".parse().unwrap(),
+                extras: vec!["jupyter".parse().unwrap()],
+                wheel: Some(String::from("rich-13.6.0-py3-none-any.whl")),
+            }],
+        };
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<EnvironmentSnapshot>(&json).unwrap(),
+            snapshot
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! A persistent cache of complete resolutions.
+//!
+//! Resolving the same requirements over and over again, e.g. in every job of a CI matrix, spends
+//! most of its time fetching and parsing metadata. The [`SolutionCache`] stores the result of a
+//! resolution on disk, keyed by the [`ResolutionInputs`] and the compatible wheel tags, so an
+//! identical resolution can be reused without running the resolver.
+//!
+//! Next to the solution every entry records a hash of the *index state*: the versions and
+//! artifacts that the indexes list for every package in the solution. When an entry is read, the
+//! index state is computed again from the project pages (which are usually served from the HTTP
+//! cache) and the entry is discarded if anything changed, e.g. because a new version was released
+//! or an artifact was yanked. Entries can also be given a maximum age, or be removed explicitly
+//! with [`SolutionCache::invalidate`] and [`SolutionCache::clear`].
+//!
+//! Resolutions with locked or favored packages are not cached. Neither are resolutions that use a
+//! [`crate::resolve::CandidateSelector`], a build requirements hook or metadata that was seeded
+//! with [`PackageDb::seed_metadata`], because their effect cannot be part of the key.
+
+use super::solve::compute_missing_hashes;
+use super::{resolve, PinnedPackage, ResolutionInputs, ResolveOptions};
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::types::{ArtifactInfo, Extra, NormalizedPackageName, Version};
+use chrono::{DateTime, Utc};
+use data_encoding::HEXLOWER;
+use pep508_rs::{MarkerEnvironment, Requirement};
+use rattler_digest::{compute_bytes_digest, Sha256, Sha256Hash};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The version of the format of the cache entries. Entries with another version are ignored.
+const ENTRY_VERSION: u32 = 1;
+
+/// Identifies a resolution in a [`SolutionCache`], see [`SolutionCache::key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SolutionKey(Sha256Hash);
+
+impl SolutionKey {
+    /// Returns the key as a hex string, which is also the name of the entry on disk.
+    pub fn to_hex(&self) -> String {
+        HEXLOWER.encode(&self.0)
+    }
+}
+
+/// A cache entry as it is stored on disk.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    version: u32,
+    created: DateTime<Utc>,
+    index_state: String,
+    packages: Vec<EntryPackage>,
+}
+
+/// A package of a cached solution.
+#[derive(Serialize, Deserialize)]
+struct EntryPackage {
+    name: NormalizedPackageName,
+    version: Version,
+    #[serde(default)]
+    extras: Vec<Extra>,
+    /// The filenames of the artifacts in the order the resolver returned them
+    artifacts: Vec<String>,
+}
+
+/// Stores complete resolutions on disk, see the [module documentation](self).
+///
+/// Failing to read or write an entry is not an error, the resolution is simply not reused.
+#[derive(Debug, Clone)]
+pub struct SolutionCache {
+    root: PathBuf,
+    max_age: Option<Duration>,
+}
+
+impl SolutionCache {
+    /// Constructs a new cache that stores its entries in `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_age: None,
+        }
+    }
+
+    /// Entries that are older than `max_age` are not reused, even if the index state did not
+    /// change. By default entries are reused as long as the index state is the same.
+    pub fn with_max_age(self, max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            ..self
+        }
+    }
+
+    /// Returns the root directory of the cache.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Computes the key of a resolution from its inputs, the environment markers and the wheel
+    /// tags it was resolved for.
+    ///
+    /// Unlike the [`ResolutionInputs`], the key includes the kernel release and version of the
+    /// environment markers, because requirements can depend on them.
+    pub fn key(
+        inputs: &ResolutionInputs,
+        env_markers: &MarkerEnvironment,
+        compatible_tags: Option<&WheelTags>,
+    ) -> SolutionKey {
+        let mut key = format!(
+            "{:x}\0{}\0{}",
+            inputs.sha256(),
+            env_markers.platform_release,
+            env_markers.platform_version
+        );
+        match compatible_tags {
+            Some(tags) => {
+                for tag in tags.tags() {
+                    key.push('\0');
+                    key.push_str(&tag.to_string());
+                }
+            }
+            None => key.push_str("\0any"),
+        }
+        SolutionKey(compute_bytes_digest::<Sha256>(key.as_bytes()))
+    }
+
+    /// Returns the cached solution for `key`, or `None` if there is no entry, the entry is too old
+    /// or the index state changed since the entry was written. Stale entries are removed.
+    pub async fn get<'db>(
+        &self,
+        package_db: &'db PackageDb,
+        key: &SolutionKey,
+    ) -> miette::Result<Option<Vec<PinnedPackage<'db>>>> {
+        let path = self.entry_path(key);
+        let Some(entry) = read_entry(&path) else {
+            return Ok(None);
+        };
+
+        if let Some(max_age) = self.max_age {
+            let now: DateTime<Utc> = SystemTime::now().into();
+            if (now - entry.created).to_std().unwrap_or_default() > max_age {
+                tracing::debug!("cached solution {} expired", key.to_hex());
+                self.invalidate(key);
+                return Ok(None);
+            }
+        }
+
+        // Find the artifacts of the solution again, they must all still be available
+        let mut packages = Vec::with_capacity(entry.packages.len());
+        for package in entry.packages {
+            let available = package_db
+                .available_artifacts(package.name.clone())
+                .await?
+                .get(&package.version);
+            let artifacts = package
+                .artifacts
+                .iter()
+                .map(|filename| {
+                    available?
+                        .iter()
+                        .find(|artifact_info| artifact_info.filename.to_string() == *filename)
+                })
+                .collect::<Option<Vec<_>>>();
+            let Some(artifacts) = artifacts else {
+                tracing::debug!(
+                    "cached solution {} is stale, {} {} changed",
+                    key.to_hex(),
+                    package.name,
+                    package.version
+                );
+                self.invalidate(key);
+                return Ok(None);
+            };
+            packages.push(PinnedPackage {
+                name: package.name,
+                version: package.version,
+                extras: package.extras.into_iter().collect(),
+                artifacts,
+            });
+        }
+
+        if index_state(package_db, &packages).await? != entry.index_state {
+            tracing::debug!(
+                "cached solution {} is stale, the index state changed",
+                key.to_hex()
+            );
+            self.invalidate(key);
+            return Ok(None);
+        }
+
+        Ok(Some(packages))
+    }
+
+    /// Stores the solution for `key`, replacing any existing entry.
+    pub async fn insert(
+        &self,
+        package_db: &PackageDb,
+        key: &SolutionKey,
+        packages: &[PinnedPackage<'_>],
+    ) -> miette::Result<()> {
+        let mut entry_packages = packages
+            .iter()
+            .map(|package| {
+                let mut extras = package.extras.iter().cloned().collect::<Vec<_>>();
+                extras.sort();
+                EntryPackage {
+                    name: package.name.clone(),
+                    version: package.version.clone(),
+                    extras,
+                    artifacts: package
+                        .artifacts
+                        .iter()
+                        .map(|artifact_info| artifact_info.filename.to_string())
+                        .collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+        entry_packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let entry = Entry {
+            version: ENTRY_VERSION,
+            created: SystemTime::now().into(),
+            index_state: index_state(package_db, packages).await?,
+            packages: entry_packages,
+        };
+        write_entry(&self.entry_path(key), &entry);
+        Ok(())
+    }
+
+    /// Removes the entry for `key`. Returns true if an entry was removed.
+    pub fn invalidate(&self, key: &SolutionKey) -> bool {
+        std::fs::remove_file(self.entry_path(key)).is_ok()
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.root) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the path of the entry for `key`.
+    fn entry_path(&self, key: &SolutionKey) -> PathBuf {
+        self.root.join(format!("{}.json", key.to_hex()))
+    }
+}
+
+/// Computes a hash over the versions and artifacts that are available for the packages of a
+/// solution.
+async fn index_state(
+    package_db: &PackageDb,
+    packages: &[PinnedPackage<'_>],
+) -> miette::Result<String> {
+    let mut names = packages
+        .iter()
+        .map(|package| &package.name)
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    let mut state = String::new();
+    for name in names {
+        state.push_str(name.as_str());
+        state.push('\n');
+        for (version, artifacts) in package_db.available_artifacts(name.clone()).await? {
+            for artifact_info in artifacts {
+                state.push_str(&artifact_state(version, artifact_info));
+                state.push('\n');
+            }
+        }
+    }
+    Ok(HEXLOWER.encode(&compute_bytes_digest::<Sha256>(state.as_bytes())))
+}
+
+/// Describes the parts of an artifact that influence the resolution.
+fn artifact_state(version: &Version, artifact_info: &ArtifactInfo) -> String {
+    let sha256 = artifact_info
+        .hashes
+        .as_ref()
+        .and_then(|hashes| hashes.sha256.as_ref())
+        .map(|sha256| format!("{sha256:x}"))
+        .unwrap_or_default();
+    format!(
+        "{version} {} {sha256} {}",
+        artifact_info.filename, artifact_info.yanked.yanked
+    )
+}
+
+/// Reads a cache entry, returns `None` if the entry does not exist, cannot be parsed or was
+/// written in another format.
+fn read_entry(path: &Path) -> Option<Entry> {
+    let contents = std::fs::read(path).ok()?;
+    let entry: Entry = serde_json::from_slice(&contents).ok()?;
+    (entry.version == ENTRY_VERSION).then_some(entry)
+}
+
+/// Atomically writes a cache entry. Failures are logged and otherwise ignored.
+fn write_entry(path: &Path, entry: &Entry) {
+    let result = (|| -> std::io::Result<()> {
+        let dir = path
+            .parent()
+            .expect("cache entries are always in a directory");
+        std::fs::create_dir_all(dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(&serde_json::to_vec(entry)?)?;
+        file.persist(path)?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        tracing::warn!(
+            "failed to write solution cache entry {}: {err}",
+            path.display()
+        );
+    }
+}
+
+/// Resolves the `requirements` like [`resolve`] but reuses a solution from the `cache` if the
+/// same requirements were resolved before with the same indexes, options and platform. The
+/// solution is stored in the cache after a successful resolution. Returns the packages and
+/// whether they were read from the cache.
+///
+/// Missing hashes are computed for cached solutions as well if
+/// [`ResolveOptions::compute_missing_hashes`] is set.
+///
+/// The cache is bypassed if the `options` contain a candidate selector or a build requirements
+/// hook, or if metadata was seeded into the `package_db`.
+pub async fn resolve_cached<'db>(
+    cache: &SolutionCache,
+    package_db: &'db PackageDb,
+    requirements: &[Requirement],
+    env_markers: &MarkerEnvironment,
+    compatible_tags: Option<&WheelTags>,
+    options: &ResolveOptions,
+) -> miette::Result<(Vec<PinnedPackage<'db>>, bool)> {
+    let resolve_uncached = || {
+        resolve(
+            package_db,
+            requirements,
+            env_markers,
+            compatible_tags,
+            Default::default(),
+            Default::default(),
+            options,
+        )
+    };
+    if options.candidate_selector.is_some()
+        || options.build_requirements_hook.is_some()
+        || package_db.has_seeded_metadata()
+    {
+        tracing::debug!(
+            "not caching the solution, it depends on a candidate selector, build hook or seeded metadata"
+        );
+        return Ok((resolve_uncached().await?, false));
+    }
+
+    let inputs = ResolutionInputs::new(requirements, package_db.indexes(), options, env_markers);
+    let key = SolutionCache::key(&inputs, env_markers, compatible_tags);
+    if let Some(mut packages) = cache.get(package_db, &key).await? {
+        tracing::info!("reusing cached solution {}", key.to_hex());
+        if options.compute_missing_hashes {
+            compute_missing_hashes(package_db, &mut packages).await?;
+        }
+        return Ok((packages, true));
+    }
+
+    let packages = resolve_uncached().await?;
+    cache.insert(package_db, &key, &packages).await?;
+    Ok((packages, false))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use std::str::FromStr;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_cached() {
+        let index = TestIndex::start().await;
+        let package_db = &index.package_db;
+        let cache = SolutionCache::new(index.cache_dir.path().join("solutions"));
+        let requirements = [Requirement::from_str("wordle_python").unwrap()];
+        let options = ResolveOptions::default();
+
+        let (packages, cached) = resolve_cached(
+            &cache,
+            package_db,
+            &requirements,
+            &linux_environment_markers("3.10.4"),
+            None,
+            &options,
+        )
+        .await
+        .unwrap();
+        assert!(!cached);
+
+        // The same resolution is served from the cache
+        let (cached_packages, cached) = resolve_cached(
+            &cache,
+            package_db,
+            &requirements,
+            &linux_environment_markers("3.10.4"),
+            None,
+            &options,
+        )
+        .await
+        .unwrap();
+        assert!(cached);
+        let sorted = |mut packages: Vec<PinnedPackage<'_>>| {
+            packages.sort_by(|a, b| a.name.cmp(&b.name));
+            packages
+        };
+        assert_eq!(sorted(cached_packages), sorted(packages.clone()));
+
+        // Other options use another entry
+        let other_options = ResolveOptions {
+            prefer_abi3: true,
+            ..Default::default()
+        };
+        let inputs = ResolutionInputs::new(
+            &requirements,
+            package_db.indexes(),
+            &other_options,
+            &linux_environment_markers("3.10.4"),
+        );
+        let other_key = SolutionCache::key(&inputs, &linux_environment_markers("3.10.4"), None);
+        assert!(cache.get(package_db, &other_key).await.unwrap().is_none());
+
+        // Invalidating the entry resolves again
+        let inputs = ResolutionInputs::new(
+            &requirements,
+            package_db.indexes(),
+            &options,
+            &linux_environment_markers("3.10.4"),
+        );
+        let key = SolutionCache::key(&inputs, &linux_environment_markers("3.10.4"), None);
+        assert!(cache.invalidate(&key));
+        assert!(cache.get(package_db, &key).await.unwrap().is_none());
+
+        // Expired entries are not reused
+        cache.insert(package_db, &key, &packages).await.unwrap();
+        let expiring = cache.clone().with_max_age(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(expiring.get(package_db, &key).await.unwrap().is_none());
+
+        // So are environments with another kernel
+        let other_kernel = MarkerEnvironment {
+            platform_release: String::from("6.0.0"),
+            ..linux_environment_markers("3.10.4")
+        };
+        assert_ne!(SolutionCache::key(&inputs, &other_kernel, None), key);
+
+        // Resolutions with seeded metadata are not cached
+        cache.clear().unwrap();
+        package_db.seed_metadata(crate::types::WheelCoreMetadata::new(
+            "wordle_python".parse().unwrap(),
+            "2.3.32".parse().unwrap(),
+            Vec::new(),
+        ));
+        let (_, cached) = resolve_cached(
+            &cache,
+            package_db,
+            &requirements,
+            &linux_environment_markers("3.10.4"),
+            None,
+            &options,
+        )
+        .await
+        .unwrap();
+        assert!(!cached);
+        assert!(!cache.root().exists());
+    }
+}
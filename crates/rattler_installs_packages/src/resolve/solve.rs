@@ -1,16 +1,38 @@
+use super::candidate_selector::CandidateSelector;
 use super::dependency_provider::PypiPackageName;
-use crate::index::PackageDb;
-use crate::python_env::WheelTags;
-use crate::resolve::dependency_provider::{PypiDependencyProvider, PypiVersion};
+use super::events::{ResolveEvent, ResolveEventSender};
+use super::package_aliases::PackageAliases;
+use super::package_rules::PackageRules;
+use super::provided_packages::ProvidedPackages;
+use crate::index::{DownloadRejected, PackageDb};
+use crate::python_env::{PythonLocation, WheelTags};
+use crate::resolve::dependency_provider::PypiDependencyProvider;
 use crate::types::PackageName;
+use crate::wheel_builder::{BuildRequirementsHook, ConfigSettings, CrossBuildTarget};
 use crate::{types::ArtifactInfo, types::Extra, types::NormalizedPackageName, types::Version};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use itertools::Itertools;
+use miette::Diagnostic;
 use pep508_rs::{MarkerEnvironment, Requirement};
 use resolvo::{DefaultSolvableDisplay, Solver};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
 
 use std::collections::HashSet;
 
+/// The error returned by [`resolve`] if the requirements conflict with each other or cannot be
+/// satisfied by the packages on the index. The message explains the conflict.
+#[derive(Debug, Clone, Error, Diagnostic)]
+#[error("{message}")]
+pub struct Unsolvable {
+    /// The user friendly explanation of the conflict
+    pub message: String,
+}
+
 /// Represents a single locked down distribution (python package) after calling [`resolve`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PinnedPackage<'db> {
@@ -31,7 +53,8 @@ pub struct PinnedPackage<'db> {
 }
 
 /// Defines how to handle sdists during resolution.
-#[derive(Default, Clone, Copy, Eq, PartialOrd, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialOrd, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SDistResolution {
     /// Both versions with wheels and/or sdists are allowed to be selected during resolution. But
     /// during resolution the metadata from wheels is preferred over sdists.
@@ -91,6 +114,7 @@ pub enum SDistResolution {
     /// Then the version@1 will be selected even though the highest version is 2. This is because
     /// version 2 has no sdists available. If version 1 would not exist though then version 2 is
     /// selected because there are no other versions with an sdist.
+    #[serde(rename = "prefer-sdists")]
     PreferSDists,
 
     /// Don't select sdists during resolution
@@ -123,6 +147,7 @@ pub enum SDistResolution {
     ///
     /// Then version 1 will be selected because it has an sdist and version 2 does not. If version 1
     /// would not exist there would be no solution because none of the versions have sdists.
+    #[serde(rename = "only-sdists")]
     OnlySDists,
 }
 
@@ -138,14 +163,149 @@ impl SDistResolution {
     }
 }
 
+/// Defines how versions with a local version label (e.g. `2.1.0+cu118`) are ordered relative to
+/// the version without a label (e.g. `2.1.0`). Indexes like the one of PyTorch publish the same
+/// version with and without local labels for different hardware.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocalVersionPreference {
+    /// Order local versions like any other version. Following PEP 440 `2.1.0+cu118` is considered
+    /// newer than `2.1.0`.
+    #[default]
+    Normal,
+
+    /// Select a version with a local label over the same version without one.
+    Prefer,
+
+    /// Select a version without a local label over the same version with one.
+    Avoid,
+}
+
 /// Additional options that may influence the solver. In general passing [`Default::default`] to
 /// the [`resolve`] function should provide sane defaults, however if you want to fine tune the
 /// resolver you can do so via this struct.
+///
+/// New options may be added in any release, construct the options from
+/// [`ResolveOptions::default`] and set the fields that are needed.
 #[derive(Default, Clone)]
+#[non_exhaustive]
 pub struct ResolveOptions {
     /// Defines how to handle sdists during resolution. By default sdists will be treated the same
     /// as wheels.
     pub sdist_resolution: SDistResolution,
+
+    /// When true, artifacts whose `Requires-Python` does not include the python version of the
+    /// environment markers are not selected. By default the python requirement of artifacts is
+    /// ignored.
+    pub check_requires_python: bool,
+
+    /// A callback that can inspect and override the build requirements of sdists that are built
+    /// during resolution. See [`BuildRequirementsHook`] for more information.
+    pub build_requirements_hook: Option<BuildRequirementsHook>,
+
+    /// Environment variables that are set when building sdists of specific packages, e.g.
+    /// `CMAKE_ARGS` for a single package. The variables are only set for the build of that
+    /// package and do not affect any other builds.
+    pub build_environment_variables: HashMap<NormalizedPackageName, HashMap<String, String>>,
+
+    /// Config settings that are passed to the build backend when building sdists of specific
+    /// packages, like the `--config-settings` of pip. See [`ConfigSettings`] for how these are
+    /// passed to sdists that use the legacy setuptools backend.
+    pub build_config_settings: HashMap<NormalizedPackageName, ConfigSettings>,
+
+    /// When set, sdists are built for this platform instead of the platform of the python
+    /// interpreter, see [`CrossBuildTarget`]. The compatible tags should contain the tags of the
+    /// target platform so the built wheels are accepted.
+    pub cross_build_target: Option<CrossBuildTarget>,
+
+    /// The python interpreter that is used to build sdists. This should be the interpreter of the
+    /// environment that is resolved for, otherwise the built wheels might not be compatible with
+    /// it. Defaults to the python interpreter found on the `PATH`.
+    pub python_location: PythonLocation,
+
+    /// When true, `abi3` wheels are preferred over wheels that are built for a specific python
+    /// version, even if the latter are a closer match for the environment. A single `abi3` wheel
+    /// can be shared by environments with different python versions which keeps caches small.
+    pub prefer_abi3: bool,
+
+    /// Defines how versions with a local version label are ordered, see
+    /// [`LocalVersionPreference`].
+    pub local_versions: LocalVersionPreference,
+
+    /// When set, the decisions of the resolver are sent to this channel while the resolver runs.
+    /// See [`super::resolve_event_channel`].
+    pub events: Option<ResolveEventSender>,
+
+    /// A hook to veto or reorder the versions of packages before they are considered by the
+    /// resolver, see [`CandidateSelector`].
+    pub candidate_selector: Option<Arc<dyn CandidateSelector>>,
+
+    /// When set, only artifacts that were uploaded to the index before this time are selected.
+    /// This resolves the environment as if the index was queried at that point in time, e.g. to
+    /// reproduce a historical environment.
+    ///
+    /// The upload time is only available from indexes that implement the JSON simple API with
+    /// [PEP 700](https://peps.python.org/pep-0700/). Artifacts without an upload time are never
+    /// selected.
+    pub exclude_newer: Option<DateTime<Utc>>,
+
+    /// Defines whether requirements may reference an artifact directly by its url (e.g.
+    /// `foo @ https://example.com/foo-1.0-py3-none-any.whl`), see [`DirectReferencePolicy`].
+    pub direct_references: DirectReferencePolicy,
+
+    /// When true, the sha256 hashes of selected artifacts that the index did not provide a hash
+    /// for are computed by downloading the artifacts after resolution, see
+    /// [`PackageDb::with_sha256`]. This makes sure every artifact of a [`PinnedPackage`] has a
+    /// hash, e.g. to write a lock file. By default artifacts without a hash are left as is.
+    pub compute_missing_hashes: bool,
+
+    /// Rules that allow or deny packages by name, optionally only on specific indexes. Versions of
+    /// which all artifacts are denied are excluded, see [`PackageRules`].
+    pub package_rules: PackageRules,
+
+    /// Aliases for packages that are required under another name than the name they are
+    /// published under, e.g. `PIL` for `Pillow`. Requirements on an alias are resolved as
+    /// requirements on the package it refers to, see [`PackageAliases`].
+    pub package_aliases: PackageAliases,
+
+    /// Packages that are provided by the platform, e.g. by a system image. These are selected with
+    /// the version they are provided with and are never downloaded, see [`ProvidedPackages`].
+    pub provided_packages: ProvidedPackages,
+}
+
+/// Defines which requirements may reference an artifact directly by its url as specified in
+/// [PEP 440](https://peps.python.org/pep-0440/#direct-references), e.g.
+/// `foo @ https://example.com/foo-1.0-py3-none-any.whl`.
+///
+/// The url must point to a wheel or an sdist, the name and version of the package are derived from
+/// its filename.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirectReferencePolicy {
+    /// Direct references are allowed both in the requirements passed to the resolver and in the
+    /// dependencies of packages.
+    #[default]
+    Allow,
+
+    /// Direct references are only allowed in the requirements passed to the resolver. Packages
+    /// whose dependencies contain direct references cannot be selected. This prevents packages
+    /// from the index from pulling in artifacts from arbitrary locations.
+    RootOnly,
+
+    /// Direct references are not allowed at all.
+    Forbid,
+}
+
+impl DirectReferencePolicy {
+    /// Returns true if a direct reference is allowed. `from_root` specifies whether the reference
+    /// is part of the requirements passed to the resolver.
+    pub fn allows(&self, from_root: bool) -> bool {
+        match self {
+            DirectReferencePolicy::Allow => true,
+            DirectReferencePolicy::RootOnly => from_root,
+            DirectReferencePolicy::Forbid => false,
+        }
+    }
 }
 
 /// Resolves an environment that contains the given requirements and all dependencies of those
@@ -157,7 +317,15 @@ pub struct ResolveOptions {
 ///
 /// If `compatible_tags` is defined then the available artifacts of a distribution are filtered to
 /// include only artifacts that are compatible with the specified tags. If `None` is passed, the
-/// artifacts are not filtered at all.
+/// artifacts are not filtered at all. If no solution can be found because none of the artifacts of
+/// a requested package, or of a package that is part of the conflict, are compatible, a
+/// [`super::NoCompatibleArtifacts`] error is returned. Other conflicts are reported as an
+/// [`Unsolvable`] error.
+///
+/// Versions whose metadata cannot be retrieved are not selected. If that prevents a solution, for
+/// instance because a [`crate::index::DownloadApproval`] rejected the download, the error of the
+/// first such version is returned instead, e.g. a [`crate::index::DownloadRejected`] error. Other
+/// failures than rejected downloads are returned even if a solution was found.
 pub async fn resolve<'db>(
     package_db: &'db PackageDb,
     requirements: impl IntoIterator<Item = &Requirement>,
@@ -184,6 +352,7 @@ pub async fn resolve<'db>(
     let requirement_count = requirements.size_hint();
     let mut root_requirements =
         Vec::with_capacity(requirement_count.1.unwrap_or(requirement_count.0));
+    let mut root_names = HashSet::new();
     for Requirement {
         name,
         version_or_url,
@@ -192,16 +361,18 @@ pub async fn resolve<'db>(
     } in requirements
     {
         let name = PackageName::from_str(name).expect("invalid package name");
-        let dependency_package_name =
-            pool.intern_package_name(PypiPackageName::Base(name.clone().into()));
+        let name = provider.resolve_alias(name.into(), None);
+        root_names.insert(name.clone());
+        provider.add_direct_reference(&name, version_or_url, true);
+        let dependency_package_name = pool.intern_package_name(PypiPackageName::Base(name.clone()));
         let version_set_id =
             pool.intern_version_set(dependency_package_name, version_or_url.clone().into());
         root_requirements.push(version_set_id);
 
         for extra in extras.iter().flatten() {
             let extra: Extra = extra.parse().expect("invalid extra");
-            let dependency_package_name = pool
-                .intern_package_name(PypiPackageName::Extra(name.clone().into(), extra.clone()));
+            let dependency_package_name =
+                pool.intern_package_name(PypiPackageName::Extra(name.clone(), extra.clone()));
             let version_set_id =
                 pool.intern_version_set(dependency_package_name, version_or_url.clone().into());
             root_requirements.push(version_set_id);
@@ -213,23 +384,59 @@ pub async fn resolve<'db>(
     let solvables = match solver.solve(root_requirements) {
         Ok(solvables) => solvables,
         Err(e) => {
-            return Err(miette::miette!(
-                "{}",
-                e.display_user_friendly(&solver, &DefaultSolvableDisplay)
-                    .to_string()
-                    .trim()
-            ))
+            let message = e
+                .display_user_friendly(&solver, &DefaultSolvableDisplay)
+                .to_string()
+                .trim()
+                .to_owned();
+            if let Some(events) = &options.events {
+                events.send(ResolveEvent::Conflict {
+                    message: message.clone(),
+                });
+            }
+
+            // Versions that were excluded because their metadata could not be retrieved are the
+            // more likely cause of the failure.
+            if let Some(err) = provider.take_metadata_errors().into_iter().next() {
+                tracing::debug!("{message}");
+                return Err(err);
+            }
+
+            // Point out packages that exist but cannot be installed in this environment at all.
+            // Such a package is only the cause of the failure if it was requested or if it is
+            // part of the conflict, otherwise it is merely mentioned next to the conflict.
+            let incompatible = provider.no_compatible_artifacts();
+            if let Some(error) = incompatible.iter().find(|error| {
+                root_names.contains(&error.name) || mentions_package(&message, &error.name)
+            }) {
+                tracing::debug!("{message}");
+                return Err(error.clone().into());
+            }
+            let message = if incompatible.is_empty() {
+                message
+            } else {
+                format!("{}\n\n{}", message, incompatible.iter().format("\n"))
+            };
+            return Err(Unsolvable { message }.into());
         }
     };
 
+    // Only rejected downloads are a reason to select other versions, any other error could have
+    // excluded the versions that should have been selected.
+    if let Some(err) = provider
+        .take_metadata_errors()
+        .into_iter()
+        .find(|err| err.downcast_ref::<DownloadRejected>().is_none())
+    {
+        return Err(err);
+    }
+
     let mut result = HashMap::new();
     for solvable_id in solvables {
         let pool = solver.pool();
         let solvable = pool.resolve_solvable(solvable_id);
         let name = pool.resolve_package_name(solvable.name_id());
-        let PypiVersion::Version(version) = solvable.inner() else {
-            unreachable!("urls are not yet supported")
-        };
+        let version = solvable.inner().version();
 
         // Get the entry in the result
         let entry = result
@@ -253,8 +460,226 @@ pub async fn resolve<'db>(
         }
     }
 
-    Ok(result.into_values().collect())
+    if let Some(events) = &options.events {
+        for package in result.values().sorted_by(|a, b| a.name.cmp(&b.name)) {
+            events.send(ResolveEvent::PackageSelected {
+                name: package.name.clone(),
+                version: package.version.clone(),
+            });
+        }
+        events.send(ResolveEvent::Solved {
+            packages: result.len(),
+        });
+    }
+
+    let mut result = result.into_values().collect::<Vec<_>>();
+    if options.compute_missing_hashes {
+        compute_missing_hashes(package_db, &mut result).await?;
+    }
+
+    Ok(result)
+}
+
+/// The maximum number of artifacts that are downloaded at the same time to compute their hashes.
+const MAX_CONCURRENT_HASH_DOWNLOADS: usize = 8;
+
+/// Replaces the artifacts of `packages` that have no sha256 hash with artifacts whose hash was
+/// computed by downloading them, see [`PackageDb::with_sha256`].
+pub(crate) async fn compute_missing_hashes<'db>(
+    package_db: &'db PackageDb,
+    packages: &mut [PinnedPackage<'db>],
+) -> miette::Result<()> {
+    let hashed: Vec<_> = futures::stream::iter(packages.iter().enumerate().flat_map(
+        |(package_idx, package)| {
+            package.artifacts.iter().copied().enumerate().map(
+                move |(artifact_idx, artifact_info)| (package_idx, artifact_idx, artifact_info),
+            )
+        },
+    ))
+    .map(|(package_idx, artifact_idx, artifact_info)| async move {
+        let artifact_info = package_db.with_sha256(artifact_info).await?;
+        Ok::<_, miette::Report>((package_idx, artifact_idx, artifact_info))
+    })
+    .buffer_unordered(MAX_CONCURRENT_HASH_DOWNLOADS)
+    .try_collect()
+    .await?;
+
+    for (package_idx, artifact_idx, artifact_info) in hashed {
+        packages[package_idx].artifacts[artifact_idx] = artifact_info;
+    }
+    Ok(())
+}
+
+/// Returns true if `message` refers to the package `name`, either on its own or with extras.
+fn mentions_package(message: &str, name: &NormalizedPackageName) -> bool {
+    message
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+        .any(|word| word == name.as_str())
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+    use crate::index::test_server::TestIndex;
+    use crate::index::{DownloadDecision, FirstDownload};
+    use crate::types::WheelCoreMetadata;
+    use test_utils::linux_environment_markers;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_direct_reference_and_version_requirement() {
+        let index = TestIndex::start().await;
+        let env_markers = linux_environment_markers("3.10.4");
+        let url = index
+            .server
+            .url()
+            .join("wordle-python/wordle_python-2.3.32-py3-none-any.whl")
+            .unwrap();
+        let direct_reference: Requirement = format!("wordle_python @ {url}").parse().unwrap();
+        let resolve_requirements = |requirements: Vec<Requirement>| {
+            let package_db = &index.package_db;
+            let env_markers = &env_markers;
+            async move {
+                resolve(
+                    package_db,
+                    &requirements,
+                    env_markers,
+                    None,
+                    HashMap::default(),
+                    HashMap::default(),
+                    &ResolveOptions::default(),
+                )
+                .await
+            }
+        };
+
+        // The direct reference satisfies the version requirement, regardless of the order of the
+        // requirements.
+        for requirements in [
+            vec![
+                direct_reference.clone(),
+                "wordle_python>=2".parse().unwrap(),
+            ],
+            vec![
+                "wordle_python>=2".parse().unwrap(),
+                direct_reference.clone(),
+            ],
+        ] {
+            let packages = resolve_requirements(requirements).await.unwrap();
+            assert_eq!(packages.len(), 1);
+            assert_eq!(packages[0].version.to_string(), "2.3.32");
+            assert_eq!(packages[0].artifacts.len(), 1);
+            assert_eq!(packages[0].artifacts[0].url, url);
+        }
+
+        // A version requirement the direct reference does not satisfy is a conflict
+        let err = resolve_requirements(vec![
+            direct_reference.clone(),
+            "wordle_python>=3".parse().unwrap(),
+        ])
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("wordle"), "{err}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_self_referential_extras() {
+        let index = TestIndex::start().await;
+        let env_markers = linux_environment_markers("3.10.4");
+
+        // `wordle_python[all]` requires `wordle_python[a]` which requires `miniblack`
+        let mut metadata = WheelCoreMetadata::new(
+            "wordle_python".parse().unwrap(),
+            "2.3.32".parse().unwrap(),
+            vec![
+                "wordle_python[a]; extra == 'all'".parse().unwrap(),
+                "miniblack; extra == 'a'".parse().unwrap(),
+            ],
+        );
+        metadata.extras = ["all", "a"]
+            .into_iter()
+            .map(|extra| extra.parse().unwrap())
+            .collect();
+        index.package_db.seed_metadata(metadata);
+
+        let requirements: [Requirement; 1] = ["wordle_python[all]".parse().unwrap()];
+        let mut packages = resolve(
+            &index.package_db,
+            &requirements,
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(
+            packages
+                .iter()
+                .map(|package| package.name.as_str())
+                .collect::<Vec<_>>(),
+            ["miniblack", "wordle-python"]
+        );
+        let wordle = &packages[1];
+        assert_eq!(wordle.version.to_string(), "2.3.32");
+        let mut extras = wordle
+            .extras
+            .iter()
+            .map(|extra| extra.as_str())
+            .collect::<Vec<_>>();
+        extras.sort();
+        assert_eq!(extras, ["a", "all"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resolve_rejected_download() {
+        let TestIndex {
+            server: _server,
+            package_db,
+            index_dir: _index_dir,
+            cache_dir: _cache_dir,
+        } = TestIndex::start().await;
+        let package_db =
+            package_db.with_download_approval(Arc::new(|download: &FirstDownload<'_>| {
+                DownloadDecision::Reject {
+                    reason: format!("{} is not approved", download.name),
+                }
+            }));
+        let env_markers = linux_environment_markers("3.10.4");
+
+        // No version can be selected, the rejection is reported instead of a conflict
+        let requirements: [Requirement; 1] = ["wordle_python".parse().unwrap()];
+        let err = resolve(
+            &package_db,
+            &requirements,
+            &env_markers,
+            None,
+            HashMap::default(),
+            HashMap::default(),
+            &ResolveOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        let rejected = err.downcast_ref::<DownloadRejected>().unwrap();
+        assert_eq!(rejected.name.as_str(), "wordle-python");
+        assert_eq!(rejected.reason, "wordle-python is not approved");
+    }
+
+    #[test]
+    fn test_mentions_package() {
+        let name: NormalizedPackageName = "foo-bar".parse().unwrap();
+        assert!(mentions_package("foo-bar >=1.0 cannot be installed", &name));
+        assert!(mentions_package("foo-bar[baz] 1.0 would require", &name));
+        assert!(mentions_package(
+            "no candidates were found for foo-bar.",
+            &name
+        ));
+        assert!(!mentions_package(
+            "foo-bar-baz 1.0 cannot be installed",
+            &name
+        ));
+        assert!(!mentions_package("foo 1.0 cannot be installed", &name));
+    }
+}
@@ -0,0 +1,347 @@
+//! Resolution of several local projects into a single environment.
+//!
+//! A [`Workspace`] consists of multiple projects on disk, each described by its own
+//! `pyproject.toml`. Dependencies between the projects of a workspace are treated as path
+//! dependencies: they are never looked up in the index, instead the dependencies of the referenced
+//! project are added to the resolution.
+
+use super::{resolve, PinnedPackage, ResolveOptions};
+use crate::index::PackageDb;
+use crate::python_env::WheelTags;
+use crate::types::{Extra, NormalizedPackageName, PackageName};
+use indexmap::IndexMap;
+use miette::Diagnostic;
+use pep440_rs::Version;
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// An error that can occur when loading or resolving a [`Workspace`].
+#[derive(Debug, Error, Diagnostic)]
+#[allow(missing_docs)]
+pub enum WorkspaceError {
+    #[error("failed to read {0}")]
+    IoError(PathBuf, #[source] std::io::Error),
+
+    #[error("failed to parse {0}")]
+    InvalidPyProject(PathBuf, #[source] toml::de::Error),
+
+    #[error("{0} does not contain a [project] table")]
+    MissingProjectTable(PathBuf),
+
+    #[error("invalid project name '{0}'")]
+    InvalidPackageName(String),
+
+    #[error("invalid extra '{1}' in project '{0}'")]
+    InvalidExtra(String, String),
+
+    #[error("the dependencies of project '{0}' are dynamic, this is not supported")]
+    DynamicDependencies(NormalizedPackageName),
+
+    #[error("project '{0}' is defined more than once in the workspace")]
+    DuplicateProject(NormalizedPackageName),
+
+    #[error("'{dependent}' requires '{requirement}' but the workspace contains {name} {version}")]
+    IncompatibleProject {
+        dependent: NormalizedPackageName,
+        requirement: String,
+        name: NormalizedPackageName,
+        version: Version,
+    },
+}
+
+/// A single local project of a [`Workspace`].
+#[derive(Debug, Clone)]
+pub struct WorkspaceProject {
+    /// The normalized name of the project
+    pub name: NormalizedPackageName,
+
+    /// The version of the project, `None` if the version is dynamic.
+    pub version: Option<Version>,
+
+    /// The directory that contains the `pyproject.toml` of the project.
+    pub path: PathBuf,
+
+    /// The dependencies of the project
+    pub dependencies: Vec<Requirement>,
+
+    /// The optional dependencies of the project grouped by extra.
+    pub optional_dependencies: IndexMap<Extra, Vec<Requirement>>,
+}
+
+impl WorkspaceProject {
+    /// Reads the project from the `pyproject.toml` in the given directory.
+    pub fn from_path(path: &Path) -> Result<Self, WorkspaceError> {
+        let pyproject_path = path.join("pyproject.toml");
+        let contents = std::fs::read_to_string(&pyproject_path)
+            .map_err(|err| WorkspaceError::IoError(pyproject_path.clone(), err))?;
+        let pyproject = pyproject_toml::PyProjectToml::new(&contents)
+            .map_err(|err| WorkspaceError::InvalidPyProject(pyproject_path.clone(), err))?;
+        let project = pyproject
+            .project
+            .ok_or(WorkspaceError::MissingProjectTable(pyproject_path))?;
+
+        let name: NormalizedPackageName = PackageName::from_str(&project.name)
+            .map_err(|_| WorkspaceError::InvalidPackageName(project.name.clone()))?
+            .into();
+
+        // Without building the project we cannot know its dependencies
+        let dynamic = project.dynamic.unwrap_or_default();
+        if dynamic
+            .iter()
+            .any(|field| field == "dependencies" || field == "optional-dependencies")
+        {
+            return Err(WorkspaceError::DynamicDependencies(name));
+        }
+
+        let optional_dependencies = project
+            .optional_dependencies
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(extra, requirements)| {
+                Extra::from_str(&extra)
+                    .map(|extra| (extra, requirements))
+                    .map_err(|_| WorkspaceError::InvalidExtra(project.name.clone(), extra))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name,
+            version: project.version,
+            path: path.to_path_buf(),
+            dependencies: project.dependencies.unwrap_or_default(),
+            optional_dependencies,
+        })
+    }
+}
+
+/// A set of local projects that are resolved together into one consistent environment. See the
+/// [module](self) documentation for more information.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace {
+    projects: IndexMap<NormalizedPackageName, WorkspaceProject>,
+}
+
+impl Workspace {
+    /// Constructs a new workspace from the given projects. Every project must have a unique name.
+    pub fn new(
+        projects: impl IntoIterator<Item = WorkspaceProject>,
+    ) -> Result<Self, WorkspaceError> {
+        let mut result = IndexMap::new();
+        for project in projects {
+            let name = project.name.clone();
+            if result.insert(name.clone(), project).is_some() {
+                return Err(WorkspaceError::DuplicateProject(name));
+            }
+        }
+        Ok(Self { projects: result })
+    }
+
+    /// Reads the projects in the given directories and constructs a workspace from them.
+    pub fn from_paths(
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> Result<Self, WorkspaceError> {
+        let projects = paths
+            .into_iter()
+            .map(|path| WorkspaceProject::from_path(path.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(projects)
+    }
+
+    /// Returns the projects in this workspace.
+    pub fn projects(&self) -> impl Iterator<Item = &WorkspaceProject> + '_ {
+        self.projects.values()
+    }
+
+    /// Returns the project with the given name if it is part of this workspace.
+    pub fn project(&self, name: &NormalizedPackageName) -> Option<&WorkspaceProject> {
+        self.projects.get(name)
+    }
+
+    /// Returns the requirements on packages outside of the workspace that are needed to install
+    /// all projects of the workspace in the environment described by `env_markers`.
+    ///
+    /// Requirements on other projects in the workspace are replaced by the dependencies of those
+    /// projects, including the dependencies of any requested extras. Requirements whose markers do
+    /// not apply to `env_markers` are skipped.
+    pub fn requirements(
+        &self,
+        env_markers: &MarkerEnvironment,
+    ) -> Result<Vec<Requirement>, WorkspaceError> {
+        let mut result = Vec::new();
+
+        // Every project is installed without extras, extras of projects are only included if
+        // another project requests them.
+        let mut queue: VecDeque<(&NormalizedPackageName, Option<Extra>)> =
+            self.projects.keys().map(|name| (name, None)).collect();
+        let mut seen: HashSet<(&NormalizedPackageName, Option<Extra>)> =
+            queue.iter().cloned().collect();
+
+        while let Some((name, extra)) = queue.pop_front() {
+            let project = &self.projects[name];
+            let (requirements, extras) = match &extra {
+                None => (project.dependencies.as_slice(), vec![]),
+                Some(extra) => (
+                    project
+                        .optional_dependencies
+                        .get(extra)
+                        .map_or(&[][..], Vec::as_slice),
+                    vec![extra.as_str()],
+                ),
+            };
+
+            for requirement in requirements {
+                if let Some(markers) = requirement.marker.as_ref() {
+                    if !markers.evaluate(env_markers, &extras) {
+                        continue;
+                    }
+                }
+
+                let dependency_name: NormalizedPackageName =
+                    PackageName::from_str(&requirement.name)
+                        .map_err(|_| WorkspaceError::InvalidPackageName(requirement.name.clone()))?
+                        .into();
+                let Some((dependency_name, dependency)) =
+                    self.projects.get_key_value(&dependency_name)
+                else {
+                    result.push(requirement.clone());
+                    continue;
+                };
+
+                // The project in the workspace must satisfy the requirement
+                if let (Some(VersionOrUrl::VersionSpecifier(specifiers)), Some(version)) =
+                    (&requirement.version_or_url, &dependency.version)
+                {
+                    if !specifiers.contains(version) {
+                        return Err(WorkspaceError::IncompatibleProject {
+                            dependent: project.name.clone(),
+                            requirement: requirement.to_string(),
+                            name: dependency.name.clone(),
+                            version: version.clone(),
+                        });
+                    }
+                }
+
+                let mut dependency_extras = vec![None];
+                for extra in requirement.extras.iter().flatten() {
+                    let extra = Extra::from_str(extra).map_err(|_| {
+                        WorkspaceError::InvalidExtra(requirement.name.clone(), extra.clone())
+                    })?;
+                    dependency_extras.push(Some(extra));
+                }
+                for extra in dependency_extras {
+                    let key = (dependency_name, extra);
+                    if seen.insert(key.clone()) {
+                        queue.push_back(key);
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Resolves an environment that contains all projects of the given [`Workspace`] and all their
+/// dependencies.
+///
+/// This works like [`resolve`] but the requirements are derived from the projects of the
+/// workspace with [`Workspace::requirements`]. The projects themselves are not part of the
+/// returned packages because they are installed from their local paths.
+pub async fn resolve_workspace<'db>(
+    package_db: &'db PackageDb,
+    workspace: &Workspace,
+    env_markers: &MarkerEnvironment,
+    compatible_tags: Option<&WheelTags>,
+    locked_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    favored_packages: HashMap<NormalizedPackageName, PinnedPackage<'db>>,
+    options: &ResolveOptions,
+) -> miette::Result<Vec<PinnedPackage<'db>>> {
+    let requirements = workspace.requirements(env_markers)?;
+    resolve(
+        package_db,
+        requirements.iter(),
+        env_markers,
+        compatible_tags,
+        locked_packages,
+        favored_packages,
+        options,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use test_utils::linux_environment_markers;
+
+    fn write_project(root: &Path, dir: &str, pyproject: &str) -> PathBuf {
+        let path = root.join(dir);
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("pyproject.toml"), pyproject).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_workspace_requirements() {
+        let root = tempfile::tempdir().unwrap();
+        let app = write_project(
+            root.path(),
+            "app",
+            r#"
+[project]
+name = "my_app"
+version = "1.0.0"
+dependencies = ["my-lib[fast]>=0.2", "click>=8", "pywin32; sys_platform == 'win32'"]
+"#,
+        );
+        let lib = write_project(
+            root.path(),
+            "lib",
+            r#"
+[project]
+name = "my-lib"
+version = "0.2.0"
+dependencies = ["requests"]
+
+[project.optional-dependencies]
+fast = ["orjson"]
+slow = ["simplejson"]
+"#,
+        );
+
+        let workspace = Workspace::from_paths([&app, &lib]).unwrap();
+        let requirements = workspace
+            .requirements(&linux_environment_markers("3.10.4"))
+            .unwrap()
+            .into_iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(requirements, vec!["click>=8", "requests", "orjson"]);
+
+        // The workspace project does not satisfy the requirement
+        let other = write_project(
+            root.path(),
+            "other",
+            r#"
+[project]
+name = "other"
+version = "1.0.0"
+dependencies = ["my-lib>=1"]
+"#,
+        );
+        let workspace = Workspace::from_paths([&lib, &other]).unwrap();
+        assert!(matches!(
+            workspace.requirements(&linux_environment_markers("3.10.4")),
+            Err(WorkspaceError::IncompatibleProject { .. })
+        ));
+
+        // Projects must be unique
+        assert!(matches!(
+            Workspace::from_paths([&lib, &lib]),
+            Err(WorkspaceError::DuplicateProject(_))
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+//! A machine-readable log of everything that happens during a session.
+//!
+//! When a [`SessionLog`] is configured every network request, cache hit, wheel build and file
+//! operation is written as a single JSON object per line. Every line contains the `timestamp` of
+//! the event in milliseconds since the unix epoch and the kind of `event`, e.g.:
+//!
+//! ```json
+//! {"timestamp":1697454000000,"event":"network_request","method":"GET","url":"https://pypi.org/simple/rich/","cache_status":"miss"}
+//! {"timestamp":1697454000120,"event":"cache_hit","cache":"metadata","key":"rich-13.6.0-py3-none-any.whl"}
+//! ```
+//!
+//! Integrators can ask users to attach the log to bug reports or use it to audit which artifacts
+//! an environment was built from.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The cache an entry was read from, see [`SessionEvent::CacheHit`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKind {
+    /// The HTTP cache of the package database
+    Http,
+    /// The cache of wheel metadata
+    Metadata,
+    /// The pip cache (see [`crate::index::PipCache`])
+    Pip,
+    /// The cache of wheels built from sdists
+    BuiltWheels,
+}
+
+/// The kind of operation performed on a file, see [`SessionEvent::FileOperation`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOperation {
+    /// A file was created or overwritten
+    Write,
+    /// A file was copied to the path
+    Copy,
+    /// A file was removed
+    Remove,
+}
+
+/// A single event in a [`SessionLog`].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// A request that was sent to an index or a server hosting artifacts.
+    NetworkRequest {
+        /// The HTTP method of the request
+        method: String,
+        /// The requested url
+        url: String,
+        /// How the request interacted with the HTTP cache, e.g. `miss` or `stale_but_validated`,
+        /// or `error` if the request failed
+        cache_status: String,
+        /// The error if the request failed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+
+    /// Data was served from a local cache without accessing the network.
+    CacheHit {
+        /// The cache the data was read from
+        cache: CacheKind,
+        /// Identifies the entry in the cache, e.g. a url or a filename
+        key: String,
+    },
+
+    /// A wheel was built from an sdist.
+    Build {
+        /// The filename of the sdist
+        sdist: String,
+        /// The path of the resulting wheel if the build succeeded
+        wheel: Option<PathBuf>,
+        /// The time the build took in milliseconds
+        duration_ms: u64,
+        /// The error if the build failed
+        error: Option<String>,
+    },
+
+    /// A file was modified on disk.
+    FileOperation {
+        /// The kind of operation
+        operation: FileOperation,
+        /// The path of the file
+        path: PathBuf,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a SessionEvent,
+}
+
+/// Writes [`SessionEvent`]s as JSON lines. See the [module](self) documentation for more
+/// information.
+///
+/// Cloning a log is cheap, all clones write to the same destination.
+#[derive(Clone)]
+pub struct SessionLog {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl std::fmt::Debug for SessionLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionLog").finish_non_exhaustive()
+    }
+}
+
+impl SessionLog {
+    /// Creates a log that writes to the file at `path`. An existing file is truncated.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Ok(Self::from_writer(BufWriter::new(file)))
+    }
+
+    /// Creates a log that writes to the given writer.
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// Appends an event to the log. Failing to write the log never fails the operation that is
+    /// logged, instead a warning is emitted.
+    pub fn record(&self, event: SessionEvent) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let line = Line {
+            timestamp,
+            event: &event,
+        };
+
+        let mut writer = self.writer.lock();
+        let result = serde_json::to_writer(&mut *writer, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(err) = result {
+            tracing::warn!("failed to write to the session log: {err}");
+        }
+    }
+
+    /// Flushes all buffered events to the underlying writer.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.writer.lock().flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_session_log() {
+        let buffer = SharedBuffer::default();
+        let log = SessionLog::from_writer(buffer.clone());
+        log.record(SessionEvent::CacheHit {
+            cache: CacheKind::Metadata,
+            key: String::from("rich-13.6.0-py3-none-any.whl"),
+        });
+        log.record(SessionEvent::FileOperation {
+            operation: FileOperation::Write,
+            path: PathBuf::from("site-packages/rich/__init__.py"),
+        });
+
+        let contents = String::from_utf8(buffer.0.lock().clone()).unwrap();
+        let lines = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        assert!(lines[0]["timestamp"].as_u64().unwrap() > 0);
+        assert_eq!(lines[0]["event"], "cache_hit");
+        assert_eq!(lines[0]["cache"], "metadata");
+        assert_eq!(lines[0]["key"], "rich-13.6.0-py3-none-any.whl");
+
+        assert_eq!(lines[1]["event"], "file_operation");
+        assert_eq!(lines[1]["operation"], "write");
+        assert_eq!(lines[1]["path"], "site-packages/rich/__init__.py");
+    }
+}
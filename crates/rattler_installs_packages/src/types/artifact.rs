@@ -1,5 +1,7 @@
 use super::artifact_name::InnerAsArtifactName;
 use crate::utils::ReadAndSeek;
+use miette::IntoDiagnostic;
+use std::path::Path;
 
 /// Trait that represents an artifact type in the PyPI ecosystem.
 pub trait Artifact: Sized {
@@ -12,6 +14,12 @@ pub trait Artifact: Sized {
     /// Construct a new artifact from the given bytes
     fn new(name: Self::Name, bytes: Box<dyn ReadAndSeek + Send>) -> miette::Result<Self>;
 
+    /// Construct a new artifact from a file on disk. Artifacts that support concurrent reads
+    /// override this to open the file for every reader instead of sharing a single handle.
+    fn from_file(name: Self::Name, path: &Path) -> miette::Result<Self> {
+        Self::new(name, Box::new(std::fs::File::open(path).into_diagnostic()?))
+    }
+
     /// Returns the name of this instance
     fn name(&self) -> &Self::Name;
 }
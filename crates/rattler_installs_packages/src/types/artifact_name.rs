@@ -36,6 +36,14 @@ pub enum ArtifactName {
 }
 
 impl ArtifactName {
+    /// Returns the name of the distribution of the artifact
+    pub fn distribution(&self) -> &PackageName {
+        match self {
+            ArtifactName::Wheel(name) => &name.distribution,
+            ArtifactName::SDist(name) => &name.distribution,
+        }
+    }
+
     /// Returns the version of the artifact
     pub fn version(&self) -> &Version {
         match self {
@@ -79,6 +87,13 @@ impl Display for ArtifactName {
 /// See: [File Name Convention](https://www.python.org/dev/peps/pep-0427/#file-name-convention),
 /// and: [PyPA Conventions](https://packaging.python.org/en/latest/specifications/),
 /// for more details regarding the structure of a wheel name.
+///
+/// A wheel filename can be parsed with [`WheelFilename::from_filename`] if the name of the
+/// package is known, or with [`FromStr`] which infers the name from the filename. Formatting a
+/// parsed filename with [`Display`] yields the original filename, including a version that is not
+/// in its normalized form, so parsing and formatting round-trips. [`WheelFilename::to_escaped_string`] formats the filename with the escaping rules
+/// of the [binary distribution format](https://packaging.python.org/en/latest/specifications/binary-distribution-format/#escaping-and-unicode)
+/// that build backends use when they write new wheels.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct WheelFilename {
     /// Distribution name, e.g. ‘django’, ‘pyramid’.
@@ -101,9 +116,43 @@ pub struct WheelFilename {
     /// Architecture specific tags
     /// E.g. ‘linux_x86_64’, ‘any’, ‘manylinux_2_17_x86_64’
     pub arch_tags: Vec<String>,
+
+    /// The version as it is written in the parsed filename if it differs from the normalized
+    /// form of `version`, e.g. `01.0` for `1.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    raw_version: Option<String>,
 }
 
 impl WheelFilename {
+    /// Returns the compressed tag set of the wheel, e.g. `py2.py3-none-any`.
+    pub fn compressed_tag(&self) -> String {
+        format!(
+            "{}-{}-{}",
+            self.py_tags.join("."),
+            self.abi_tags.join("."),
+            self.arch_tags.join(".")
+        )
+    }
+
+    /// Formats the filename with the escaping rules of the binary distribution format. Runs of
+    /// `-`, `_` and `.` in the distribution name are replaced by a single `_` and the name is
+    /// lowercased, e.g. `Foo.Bar-1.0-py3-none-any.whl` becomes `foo_bar-1.0-py3-none-any.whl`.
+    /// The version is written in its normalized form.
+    ///
+    /// The escaped filename parses to the same package, version, build tag and tags.
+    pub fn to_escaped_string(&self) -> String {
+        format!(
+            "{dist}-{ver}{build}-{tag}.whl",
+            dist = escape_distribution_name(self.distribution.as_source_str()),
+            ver = self.version,
+            build = self
+                .build_tag
+                .as_ref()
+                .map_or_else(|| String::from(""), |tag| format!("-{tag}")),
+            tag = self.compressed_tag(),
+        )
+    }
+
     /// Creates a set of all tags that are contained in this wheel name.
     pub fn all_tags(&self) -> HashSet<WheelTag> {
         HashSet::from_iter(self.all_tags_iter())
@@ -131,7 +180,10 @@ impl Display for WheelFilename {
             f,
             "{dist}-{ver}{build}-{py_tags}-{abi_tags}-{arch_tags}.whl",
             dist = self.distribution.as_source_str(),
-            ver = self.version,
+            ver = match &self.raw_version {
+                Some(raw_version) => raw_version.clone(),
+                None => self.version.to_string(),
+            },
             build = self
                 .build_tag
                 .as_ref()
@@ -154,6 +206,27 @@ pub struct BuildTag {
     name: String,
 }
 
+impl BuildTag {
+    /// Constructs a build tag from the leading number and the remainder of the tag, e.g. `1` and
+    /// `local` for `1local`.
+    pub fn new(number: u32, name: impl Into<String>) -> Self {
+        Self {
+            number,
+            name: name.into(),
+        }
+    }
+
+    /// Returns the leading number of the build tag.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// Returns the remainder of the build tag after the leading number.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl Display for BuildTag {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}{}", self.number, &self.name)
@@ -345,11 +418,12 @@ impl WheelFilename {
             .map_err(ParseArtifactNameError::InvalidPackageName)?;
 
         // Parse the version
-        let Some((version, rest)) = rest.split_once('-') else {
+        let Some((raw_version, rest)) = rest.split_once('-') else {
             return Err(ParseArtifactNameError::InvalidName);
         };
-        let version = Version::from_str(version)
+        let version = Version::from_str(raw_version)
             .map_err(|e| ParseArtifactNameError::InvalidVersion(e.to_string()))?;
+        let raw_version = (version.to_string() != raw_version).then(|| raw_version.to_owned());
 
         // Parse the platform tag
         let Some((rest, platform_tags)) = rest.rsplit_once('-') else {
@@ -380,10 +454,60 @@ impl WheelFilename {
             py_tags,
             abi_tags,
             arch_tags,
+            raw_version,
         })
     }
 }
 
+/// Escapes a distribution name for use in a wheel filename, see
+/// [`WheelFilename::to_escaped_string`].
+fn escape_distribution_name(name: &str) -> String {
+    name.split(|c| matches!(c, '-' | '_' | '.'))
+        .filter(|part| !part.is_empty())
+        .join("_")
+        .to_lowercase()
+}
+
+impl FromStr for WheelFilename {
+    type Err = ParseArtifactNameError;
+
+    /// Parses a wheel filename without knowing the name of the package. The distribution name of
+    /// a wheel cannot contain dashes so it is the part before the first dash, see
+    /// [`ArtifactName::infer_package_name`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.ends_with(".whl") {
+            return Err(ParseArtifactNameError::InvalidExtension(s.to_string()));
+        }
+        let name = ArtifactName::infer_package_name(s)?;
+        Self::from_filename(s, &name)
+    }
+}
+
+impl FromStr for SDistFilename {
+    type Err = ParseArtifactNameError;
+
+    /// Parses an sdist filename without knowing the name of the package, see
+    /// [`ArtifactName::infer_package_name`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.ends_with(".whl") {
+            return Err(ParseArtifactNameError::InvalidExtension(s.to_string()));
+        }
+        let name = ArtifactName::infer_package_name(s)?;
+        Self::from_filename(s, &name)
+    }
+}
+
+impl FromStr for ArtifactName {
+    type Err = ParseArtifactNameError;
+
+    /// Parses the filename of a wheel or an sdist without knowing the name of the package, see
+    /// [`ArtifactName::infer_package_name`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = ArtifactName::infer_package_name(s)?;
+        Self::from_filename(s, &name)
+    }
+}
+
 impl ArtifactName {
     /// Parse the artifact name for a filename string
     /// e.g "trio-0.18.0-py3-none-any.whl"
@@ -414,10 +538,41 @@ impl ArtifactName {
     }
 }
 
+impl ArtifactName {
+    /// Infers the normalized package name from the filename of an artifact, e.g. `trio` from
+    /// `trio-0.18.0-py3-none-any.whl` or `create-ap-gui` from `create_ap-gui-1.3.1.tar.gz`.
+    ///
+    /// The distribution name of a wheel cannot contain dashes so it is the part before the first
+    /// dash. Sdists are not always named consistently, both the name and the version may contain
+    /// dashes (`foo-bar-1.0-1.tar.gz`). The first dash that is followed by a valid version
+    /// separates the name from the version, if there is none the last dash is used.
+    pub fn infer_package_name(
+        filename: &str,
+    ) -> Result<NormalizedPackageName, ParseArtifactNameError> {
+        let name = if filename.ends_with(".whl") {
+            filename.split_once('-').map(|(name, _)| name)
+        } else {
+            let stem = [".zip", ".tar.gz", ".tar.bz2", ".tar.xz", ".tar.Z", ".tar"]
+                .into_iter()
+                .find_map(|extension| filename.strip_suffix(extension))
+                .ok_or_else(|| ParseArtifactNameError::InvalidExtension(filename.to_string()))?;
+            stem.match_indices('-')
+                .map(|(idx, _)| (&stem[..idx], &stem[idx + 1..]))
+                .find(|(name, version)| {
+                    PackageName::from_str(name).is_ok() && Version::from_str(version).is_ok()
+                })
+                .or_else(|| stem.rsplit_once('-'))
+                .map(|(name, _)| name)
+        }
+        .ok_or(ParseArtifactNameError::InvalidName)?;
+        Ok(PackageName::from_str(name)?.into())
+    }
+}
+
 /// A trait to convert the general [`ArtifactName`] into a specialized artifact name. This is useful
 /// to generically fetch the underlying specialized name.
 ///
-/// Currently we provide implementations for wheels and sdists.
+/// Currently we provide implementations for wheels, sdists and the general name itself.
 pub trait InnerAsArtifactName {
     /// Tries to convert the general [`ArtifactName`] into a specialized artifact name.
     fn try_as(name: &ArtifactName) -> Option<&Self>;
@@ -435,6 +590,12 @@ impl InnerAsArtifactName for SDistFilename {
     }
 }
 
+impl InnerAsArtifactName for ArtifactName {
+    fn try_as(name: &ArtifactName) -> Option<&Self> {
+        Some(name)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -454,6 +615,30 @@ mod test {
         assert_eq!(rest, "0.18.0-py3-none-any.whl");
     }
 
+    #[test]
+    fn test_infer_package_name() {
+        let infer = |filename| {
+            ArtifactName::infer_package_name(filename)
+                .unwrap()
+                .as_str()
+                .to_owned()
+        };
+        assert_eq!(infer("trio-0.18.0-py3-none-any.whl"), "trio");
+        assert_eq!(infer("Foo_Bar-1.0-1-py3-none-any.whl"), "foo-bar");
+        assert_eq!(infer("create_ap-gui-1.3.1.tar.gz"), "create-ap-gui");
+        assert_eq!(infer("trio-0.19a0.zip"), "trio");
+        assert_eq!(infer("Foo_Bar-1.0-1.tar.gz"), "foo-bar");
+        assert_eq!(infer("foo-bar-2-baz-1.0.tar.gz"), "foo-bar-2-baz");
+        assert!(matches!(
+            ArtifactName::infer_package_name("trio.whl"),
+            Err(ParseArtifactNameError::InvalidName)
+        ));
+        assert!(matches!(
+            ArtifactName::infer_package_name("trio-1.0.exe"),
+            Err(ParseArtifactNameError::InvalidExtension(_))
+        ));
+    }
+
     #[test]
     fn test_sdist_name_from_str() {
         let sn =
@@ -473,6 +658,35 @@ mod test {
         assert_eq!(sn.version, "1.3.1".parse().unwrap());
     }
 
+    #[test]
+    fn test_sdist_name_inferred_from_str() {
+        let sn = SDistFilename::from_str("Create_AP-gui-1.3.1.tar.gz").unwrap();
+        assert_eq!(
+            NormalizedPackageName::from(sn.distribution.clone()).as_str(),
+            "create-ap-gui"
+        );
+        assert_eq!(sn.version, "1.3.1".parse().unwrap());
+        assert_eq!(sn.format, SDistFormat::TarGz);
+        assert_eq!(sn.to_string(), "Create_AP-gui-1.3.1.tar.gz");
+
+        let sn = SDistFilename::from_str("foo-bar-1.0-1.zip").unwrap();
+        assert_eq!(sn.distribution, "foo-bar".parse().unwrap());
+        assert_eq!(sn.version, "1.0-1".parse().unwrap());
+
+        assert!(matches!(
+            SDistFilename::from_str("trio-0.18.0-py3-none-any.whl"),
+            Err(ParseArtifactNameError::InvalidExtension(_))
+        ));
+        assert!(matches!(
+            ArtifactName::from_str("trio-0.18.0-py3-none-any.whl"),
+            Ok(ArtifactName::Wheel(_))
+        ));
+        assert!(matches!(
+            ArtifactName::from_str("Trio-0.18.0.tar.gz"),
+            Ok(ArtifactName::SDist(_))
+        ));
+    }
+
     #[test]
     fn test_name_double_dash_from_str() {
         let sn = SDistFilename::from_filename(
@@ -537,4 +751,48 @@ mod test {
 
         assert_eq!(n.to_string(), "foo.bar-0.1b3-1local-py2.py3-none-any.whl");
     }
+
+    #[test]
+    fn test_wheel_name_round_trip() {
+        for filename in [
+            "trio-0.18.0-py3-none-any.whl",
+            "Foo.Bar-0.1b3-1local-py2.py3-none-any.whl",
+            "numpy-1.26.0-pp39-pypy39_pp73-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+            // Versions that are not normalized are kept as they are
+            "foo-01.0-py3-none-any.whl",
+        ] {
+            let n = WheelFilename::from_str(filename).unwrap();
+            assert_eq!(n.to_string(), filename);
+            assert_eq!(WheelFilename::from_str(&n.to_string()).unwrap(), n);
+
+            // The escaped filename describes the same wheel
+            let escaped = WheelFilename::from_str(&n.to_escaped_string()).unwrap();
+            assert_eq!(
+                NormalizedPackageName::from(escaped.distribution.clone()),
+                NormalizedPackageName::from(n.distribution.clone())
+            );
+            assert_eq!(escaped.version, n.version);
+            assert_eq!(escaped.build_tag, n.build_tag);
+            assert_eq!(escaped.all_tags(), n.all_tags());
+        }
+
+        let n = WheelFilename::from_str("Foo.Bar-0.1b3-1local-py2.py3-none-any.whl").unwrap();
+        assert_eq!(
+            n.to_escaped_string(),
+            "foo_bar-0.1b3-1local-py2.py3-none-any.whl"
+        );
+        assert_eq!(n.compressed_tag(), "py2.py3-none-any");
+
+        let n = WheelFilename::from_str("foo-01.0-py3-none-any.whl").unwrap();
+        assert_eq!(n.version, "1.0".parse().unwrap());
+        assert_eq!(n.to_escaped_string(), "foo-1.0-py3-none-any.whl");
+        let build_tag = n.build_tag.unwrap();
+        assert_eq!(build_tag, BuildTag::new(1, "local"));
+        assert_eq!((build_tag.number(), build_tag.name()), (1, "local"));
+
+        assert!(matches!(
+            WheelFilename::from_str("trio-0.18.0.tar.gz"),
+            Err(ParseArtifactNameError::InvalidExtension(_))
+        ));
+    }
 }
@@ -31,6 +31,24 @@ pub struct WheelCoreMetadata {
     pub extras: HashSet<Extra>,
 }
 
+impl WheelCoreMetadata {
+    /// Constructs the metadata of a package version from its requirements, e.g. to provide
+    /// metadata that was obtained elsewhere with [`crate::index::PackageDb::seed_metadata`]. The
+    /// metadata has no python requirement and declares no extras, set the fields to add them.
+    pub fn new(name: PackageName, version: Version, requires_dist: Vec<Requirement>) -> Self {
+        Self {
+            name,
+            version,
+            metadata_version: MetadataVersion(
+                Version::from_str("2.1").expect("cannot parse 2.1 version string"),
+            ),
+            requires_dist,
+            requires_python: None,
+            extras: HashSet::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// Wrapper around a PEP440 version
 /// specifically for the metadata version
@@ -17,6 +17,8 @@ mod entry_points;
 mod project_info;
 
 mod rfc822ish;
+
+mod requirement;
 pub use artifact::Artifact;
 
 pub use artifact_name::{
@@ -32,6 +34,8 @@ pub use package_name::{NormalizedPackageName, PackageName, ParsePackageNameError
 
 pub use extra::Extra;
 
+pub use requirement::{NotAnIndexRequirement, ParseRequirementError, RequirementSpec, VcsKind};
+
 pub use entry_points::{EntryPoint, ParseEntryPointError};
 
 pub use project_info::{ArtifactHashes, ArtifactInfo, DistInfoMetadata, Meta, ProjectInfo, Yanked};
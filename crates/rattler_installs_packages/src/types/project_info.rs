@@ -2,7 +2,7 @@
 
 use crate::types::Artifact;
 use crate::types::ArtifactName;
-use pep440_rs::VersionSpecifiers;
+use pep440_rs::{Version, VersionSpecifiers};
 use rattler_digest::{serde::SerializableHash, Sha256};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none, DisplayFromStr, VecSkipError};
@@ -17,6 +17,13 @@ pub struct ProjectInfo {
     /// All the available files for this project
     #[serde_as(as = "VecSkipError<_>")]
     pub files: Vec<ArtifactInfo>,
+
+    /// All the versions of this project that are available on the index, including versions
+    /// without any files. Only provided by indexes that implement the JSON API with
+    /// [PEP 700](https://peps.python.org/pep-0700/), otherwise this is empty.
+    #[serde(default)]
+    #[serde_as(as = "VecSkipError<DisplayFromStr>")]
+    pub versions: Vec<Version>,
 }
 
 /// Describes a single artifact that is available for download.
@@ -30,9 +37,14 @@ pub struct ArtifactInfo {
     pub url: url::Url,
     /// Hashes of the artifact
     pub hashes: Option<ArtifactHashes>,
-    /// Python requirement
+    /// Python requirement, `None` if the index doesn't specify one or if it could not be parsed.
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub requires_python: Option<VersionSpecifiers>,
+    /// The python requirement exactly as the index specified it. Some indexes list malformed
+    /// values, this keeps them around even if they could not be parsed into
+    /// [`Self::requires_python`].
+    #[serde(default)]
+    pub requires_python_raw: Option<String>,
     #[serde(default)]
     /// This attribute specified if the metadata is available
     /// as a separate download described in [PEP 658](https://www.python.org/dev/peps/pep-0658/)
@@ -40,15 +52,56 @@ pub struct ArtifactInfo {
     /// Yanked information
     #[serde(default)]
     pub yanked: Yanked,
+    /// The size of the artifact in bytes, if the index provides it. See
+    /// [PEP 700](https://peps.python.org/pep-0700/).
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// The time the artifact was uploaded to the index, if the index provides it. See
+    /// [PEP 700](https://peps.python.org/pep-0700/).
+    #[serde(default)]
+    pub upload_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// The url of the provenance of the artifact, which contains its attestations, if the index
+    /// provides it. See [PEP 740](https://peps.python.org/pep-0740/).
+    #[serde(default)]
+    pub provenance: Option<url::Url>,
 }
 
 impl ArtifactInfo {
+    /// Constructs an artifact that is hosted at `url` without any of the optional information an
+    /// index can provide.
+    pub(crate) fn new(filename: ArtifactName, url: url::Url) -> Self {
+        Self {
+            filename,
+            url,
+            hashes: None,
+            requires_python: None,
+            requires_python_raw: None,
+            dist_info_metadata: Default::default(),
+            yanked: Default::default(),
+            size: None,
+            upload_time: None,
+            provenance: None,
+        }
+    }
+
     /// Returns true if this artifact describes an instance of `T`.
     pub fn is<T: Artifact>(&self) -> bool {
         self.filename.as_inner::<T::Name>().is_some()
     }
 }
 
+#[cfg(test)]
+impl ArtifactInfo {
+    /// Returns an artifact of `name` that is hosted at `https://example.com/{filename}` without any
+    /// of the optional information an index can provide. Used to construct artifacts in tests.
+    pub(crate) fn for_test(name: &crate::types::NormalizedPackageName, filename: &str) -> Self {
+        Self::new(
+            ArtifactName::from_filename(filename, name).unwrap(),
+            format!("https://example.com/{filename}").parse().unwrap(),
+        )
+    }
+}
+
 /// Describes a set of hashes for a certain artifact. In theory all hash algorithms available via
 /// Pythons `hashlib` are supported but we only support some common ones.
 #[serde_as]
@@ -0,0 +1,532 @@
+//! A requirement as it is written by users: besides the [PEP 508](https://peps.python.org/pep-0508/)
+//! requirements that the resolver understands, this includes local paths, editables and version
+//! control urls.
+
+use super::{Extra, PackageName};
+use itertools::Itertools;
+use pep508_rs::{MarkerTree, Requirement, VersionOrUrl};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use thiserror::Error;
+use url::Url;
+
+/// A requirement on a package. This covers all the forms a requirement can be written in, like
+/// pip accepts them on the command line or in a `requirements.txt`:
+///
+/// * `flask>=2` or `flask[async]; python_version >= '3.8'` ([`RequirementSpec::Named`])
+/// * `flask @ https://example.com/flask-3.0.0-py3-none-any.whl` ([`RequirementSpec::Url`])
+/// * `./projects/app`, `-e ./projects/app` or `app @ file:///projects/app`
+///   ([`RequirementSpec::Path`])
+/// * `app @ git+https://github.com/org/app@v1.0` ([`RequirementSpec::Vcs`])
+///
+/// Only named and url requirements can be resolved from a package index, see
+/// [`RequirementSpec::to_pep508`]. Formatting a requirement with [`Display`] yields a string that
+/// parses to the same requirement.
+#[derive(Debug, Clone, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
+pub enum RequirementSpec {
+    /// A requirement on a package from an index, optionally restricted by version specifiers.
+    Named(Requirement),
+
+    /// A requirement that references a wheel or sdist directly by its url.
+    Url(Requirement),
+
+    /// A requirement on a project or artifact on the local file system.
+    Path {
+        /// The name of the package, if it was specified
+        name: Option<PackageName>,
+
+        /// The path of the project or artifact
+        path: PathBuf,
+
+        /// The extras of the package that are requested
+        extras: Vec<Extra>,
+
+        /// True if the project should be installed in editable mode (`-e`)
+        editable: bool,
+
+        /// The environment markers that decide whether the requirement applies
+        marker: Option<MarkerTree>,
+    },
+
+    /// A requirement on a package that is built from a version control repository.
+    Vcs {
+        /// The name of the package
+        name: PackageName,
+
+        /// The extras of the package that are requested
+        extras: Vec<Extra>,
+
+        /// The version control system
+        vcs: VcsKind,
+
+        /// The url of the repository, without the `git+` prefix and the revision
+        url: Url,
+
+        /// The branch, tag or commit to check out, e.g. `v1.0` for `...app.git@v1.0`
+        rev: Option<String>,
+
+        /// The directory in the repository that contains the project
+        subdirectory: Option<String>,
+
+        /// The environment markers that decide whether the requirement applies
+        marker: Option<MarkerTree>,
+    },
+}
+
+/// A version control system that a [`RequirementSpec::Vcs`] refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VcsKind {
+    /// [Git](https://git-scm.com/)
+    Git,
+
+    /// [Mercurial](https://www.mercurial-scm.org/)
+    Hg,
+
+    /// [Subversion](https://subversion.apache.org/)
+    Svn,
+
+    /// [Bazaar](https://bazaar.canonical.com/)
+    Bzr,
+}
+
+impl VcsKind {
+    /// Returns the prefix of the url scheme of the version control system, e.g. `git`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VcsKind::Git => "git",
+            VcsKind::Hg => "hg",
+            VcsKind::Svn => "svn",
+            VcsKind::Bzr => "bzr",
+        }
+    }
+
+    fn from_scheme_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "git" => Some(VcsKind::Git),
+            "hg" => Some(VcsKind::Hg),
+            "svn" => Some(VcsKind::Svn),
+            "bzr" => Some(VcsKind::Bzr),
+            _ => None,
+        }
+    }
+}
+
+impl Display for VcsKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An error that can occur when parsing a [`RequirementSpec`].
+#[derive(Debug, Error)]
+#[allow(missing_docs)]
+pub enum ParseRequirementError {
+    #[error(transparent)]
+    Pep508(#[from] pep508_rs::Pep508Error),
+
+    #[error("invalid package name '{0}'")]
+    InvalidPackageName(String),
+
+    #[error("invalid extra '{0}'")]
+    InvalidExtra(String),
+
+    #[error("'{0}' is not a valid path")]
+    InvalidPath(String),
+
+    #[error("'{0}' is not a valid url")]
+    InvalidUrl(String),
+
+    #[error("only local paths can be installed in editable mode, got '{0}'")]
+    EditableNotAPath(String),
+}
+
+/// The error returned by [`RequirementSpec::to_pep508`] for requirements that cannot be resolved from
+/// a package index.
+#[derive(Debug, Clone, Error, miette::Diagnostic)]
+#[error(
+    "'{0}' cannot be resolved from a package index, only named and url requirements are supported"
+)]
+pub struct NotAnIndexRequirement(pub String);
+
+impl RequirementSpec {
+    /// Returns the name of the required package, `None` if it is a local path without a name.
+    pub fn name(&self) -> Option<PackageName> {
+        match self {
+            RequirementSpec::Named(requirement) | RequirementSpec::Url(requirement) => {
+                PackageName::from_str(&requirement.name).ok()
+            }
+            RequirementSpec::Path { name, .. } => name.clone(),
+            RequirementSpec::Vcs { name, .. } => Some(name.clone()),
+        }
+    }
+
+    /// Returns true if the requirement can be resolved from a package index.
+    pub fn is_index_requirement(&self) -> bool {
+        matches!(self, RequirementSpec::Named(_) | RequirementSpec::Url(_))
+    }
+
+    /// Returns the requirement as a PEP 508 [`Requirement`] that can be passed to the resolver.
+    /// Fails for local paths and version control urls.
+    pub fn to_pep508(&self) -> Result<Requirement, NotAnIndexRequirement> {
+        match self {
+            RequirementSpec::Named(requirement) | RequirementSpec::Url(requirement) => {
+                Ok(requirement.clone())
+            }
+            _ => Err(NotAnIndexRequirement(self.to_string())),
+        }
+    }
+
+    /// Parses a requirement that is a local path, optionally followed by extras and environment
+    /// markers, e.g. `./projects/app[test] ; python_version >= '3.8'`.
+    fn parse_path(s: &str, editable: bool) -> Result<Self, ParseRequirementError> {
+        // Like pip, everything after the first semicolon are the environment markers
+        let (s, marker) = match s.split_once(';') {
+            Some((path, marker)) => (path.trim_end(), Some(MarkerTree::from_str(marker.trim())?)),
+            None => (s, None),
+        };
+        let (path, extras) = match s.strip_suffix(']').and_then(|s| s.rsplit_once('[')) {
+            Some((path, extras)) => (path, parse_extras(extras.split(','))?),
+            None => (s, Vec::new()),
+        };
+        if path.is_empty() {
+            return Err(ParseRequirementError::InvalidPath(s.to_owned()));
+        }
+        Ok(RequirementSpec::Path {
+            name: None,
+            path: PathBuf::from(path),
+            extras,
+            editable,
+            marker,
+        })
+    }
+}
+
+impl TryFrom<Requirement> for RequirementSpec {
+    type Error = ParseRequirementError;
+
+    /// Determines the kind of requirement from the url of a direct reference, `file:` urls are
+    /// local paths and urls with a `git+` (or other version control) scheme are [`RequirementSpec::Vcs`].
+    fn try_from(requirement: Requirement) -> Result<Self, Self::Error> {
+        let url = match &requirement.version_or_url {
+            Some(VersionOrUrl::Url(url)) => url.clone(),
+            _ => return Ok(RequirementSpec::Named(requirement)),
+        };
+
+        let name = PackageName::from_str(&requirement.name)
+            .map_err(|_| ParseRequirementError::InvalidPackageName(requirement.name.clone()))?;
+        let extras = parse_extras(requirement.extras.iter().flatten().map(String::as_str))?;
+
+        if url.scheme() == "file" {
+            let path = url
+                .to_file_path()
+                .map_err(|_| ParseRequirementError::InvalidPath(url.to_string()))?;
+            return Ok(RequirementSpec::Path {
+                name: Some(name),
+                path,
+                extras,
+                editable: false,
+                marker: requirement.marker,
+            });
+        }
+
+        let vcs = url
+            .scheme()
+            .split_once('+')
+            .and_then(|(prefix, _)| VcsKind::from_scheme_prefix(prefix));
+        let Some(vcs) = vcs else {
+            return Ok(RequirementSpec::Url(requirement));
+        };
+
+        // Split off the vcs prefix, the revision and the subdirectory
+        let mut repository = Url::parse(&url.as_str()[vcs.as_str().len() + 1..])
+            .map_err(|_| ParseRequirementError::InvalidUrl(url.to_string()))?;
+        let subdirectory = repository.fragment().and_then(|fragment| {
+            fragment
+                .split('&')
+                .find_map(|part| part.strip_prefix("subdirectory="))
+                .map(ToOwned::to_owned)
+        });
+        repository.set_fragment(None);
+        let rev = match repository.path().rsplit_once('@') {
+            Some((path, rev)) => {
+                let (path, rev) = (path.to_owned(), rev.to_owned());
+                repository.set_path(&path);
+                Some(rev)
+            }
+            _ => None,
+        };
+
+        Ok(RequirementSpec::Vcs {
+            name,
+            extras,
+            vcs,
+            url: repository,
+            rev,
+            subdirectory,
+            marker: requirement.marker,
+        })
+    }
+}
+
+/// Parses a list of extras.
+fn parse_extras<'a>(
+    extras: impl IntoIterator<Item = &'a str>,
+) -> Result<Vec<Extra>, ParseRequirementError> {
+    extras
+        .into_iter()
+        .map(str::trim)
+        .filter(|extra| !extra.is_empty())
+        .map(|extra| {
+            Extra::from_str(extra).map_err(|_| ParseRequirementError::InvalidExtra(extra.into()))
+        })
+        .collect()
+}
+
+/// The extensions of artifacts that can be installed from a path without a directory, e.g.
+/// `flask-3.0.0-py3-none-any.whl`.
+const ARTIFACT_EXTENSIONS: [&str; 7] = [
+    ".whl", ".zip", ".tar.gz", ".tar.bz2", ".tar.xz", ".tar.Z", ".tar",
+];
+
+/// Returns true if the string looks like a path rather than a PEP 508 requirement.
+///
+/// Package names cannot contain slashes and a PEP 508 url is always preceded by a name and an
+/// `@`, so a first word that contains a slash (and is not a url) is a relative path like
+/// `projects/app`. So is a first word that ends with the extension of an artifact.
+fn looks_like_path(s: &str) -> bool {
+    let first_word = s
+        .split(|c: char| c.is_whitespace() || c == ';' || c == '[')
+        .next()
+        .unwrap_or_default();
+    s.starts_with('.')
+        || s.starts_with('/')
+        || s.starts_with('~')
+        || s.contains('\\')
+        || (s.len() > 2 && s.as_bytes()[1] == b':' && s.as_bytes()[0].is_ascii_alphabetic())
+        || (first_word.contains('/') && !first_word.contains("://"))
+        || ARTIFACT_EXTENSIONS
+            .iter()
+            .any(|extension| first_word.ends_with(extension))
+}
+
+impl FromStr for RequirementSpec {
+    type Err = ParseRequirementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        // Editables are always local paths
+        let editable = s
+            .strip_prefix("-e ")
+            .or_else(|| s.strip_prefix("--editable "))
+            .or_else(|| s.strip_prefix("--editable="));
+        if let Some(editable) = editable {
+            let editable = editable.trim();
+            if !looks_like_path(editable) {
+                return match RequirementSpec::from_str(editable)? {
+                    RequirementSpec::Path {
+                        name,
+                        path,
+                        extras,
+                        marker,
+                        ..
+                    } => Ok(RequirementSpec::Path {
+                        name,
+                        path,
+                        extras,
+                        editable: true,
+                        marker,
+                    }),
+                    _ => Err(ParseRequirementError::EditableNotAPath(editable.to_owned())),
+                };
+            }
+            return RequirementSpec::parse_path(editable, true);
+        }
+
+        if looks_like_path(s) {
+            return RequirementSpec::parse_path(s, false);
+        }
+
+        RequirementSpec::try_from(Requirement::from_str(s)?)
+    }
+}
+
+impl Display for RequirementSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let write_extras = |f: &mut Formatter<'_>, extras: &[Extra]| {
+            if extras.is_empty() {
+                Ok(())
+            } else {
+                write!(f, "[{}]", extras.iter().map(Extra::as_source_str).join(","))
+            }
+        };
+        let write_marker = |f: &mut Formatter<'_>, marker: &Option<MarkerTree>| match marker {
+            Some(marker) => write!(f, " ; {marker}"),
+            None => Ok(()),
+        };
+
+        match self {
+            RequirementSpec::Named(requirement) | RequirementSpec::Url(requirement) => {
+                write!(f, "{requirement}")
+            }
+            RequirementSpec::Path {
+                name,
+                path,
+                extras,
+                editable,
+                marker,
+            } => {
+                if *editable {
+                    write!(f, "-e ")?;
+                }
+                match (name, Url::from_file_path(path)) {
+                    (Some(name), Ok(url)) => {
+                        write!(f, "{}", name.as_source_str())?;
+                        write_extras(f, extras)?;
+                        write!(f, " @ {url}")?;
+                        write_marker(f, marker)
+                    }
+                    _ => {
+                        write!(f, "{}", path.display())?;
+                        write_extras(f, extras)?;
+                        write_marker(f, marker)
+                    }
+                }
+            }
+            RequirementSpec::Vcs {
+                name,
+                extras,
+                vcs,
+                url,
+                rev,
+                subdirectory,
+                marker,
+            } => {
+                write!(f, "{}", name.as_source_str())?;
+                write_extras(f, extras)?;
+                write!(f, " @ {vcs}+{url}")?;
+                if let Some(rev) = rev {
+                    write!(f, "@{rev}")?;
+                }
+                if let Some(subdirectory) = subdirectory {
+                    write!(f, "#subdirectory={subdirectory}")?;
+                }
+                write_marker(f, marker)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> RequirementSpec {
+        RequirementSpec::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_requirement() {
+        assert!(matches!(parse("flask>=2"), RequirementSpec::Named(_)));
+        assert!(matches!(
+            parse("flask @ https://example.com/flask-3.0.0-py3-none-any.whl"),
+            RequirementSpec::Url(_)
+        ));
+
+        let RequirementSpec::Path {
+            name,
+            path,
+            extras,
+            editable,
+            ..
+        } = parse("-e ./projects/app[test]")
+        else {
+            panic!("expected a path requirement");
+        };
+        assert_eq!(name, None);
+        assert_eq!(path, PathBuf::from("./projects/app"));
+        assert_eq!(extras, vec![Extra::from_str("test").unwrap()]);
+        assert!(editable);
+
+        let RequirementSpec::Vcs {
+            name,
+            vcs,
+            url,
+            rev,
+            subdirectory,
+            ..
+        } = parse("app @ git+https://github.com/org/app.git@v1.0#subdirectory=lib")
+        else {
+            panic!("expected a vcs requirement");
+        };
+        assert_eq!(name.as_str(), "app");
+        assert_eq!(vcs, VcsKind::Git);
+        assert_eq!(url.as_str(), "https://github.com/org/app.git");
+        assert_eq!(rev.as_deref(), Some("v1.0"));
+        assert_eq!(subdirectory.as_deref(), Some("lib"));
+
+        assert!(matches!(
+            RequirementSpec::from_str("-e flask>=2"),
+            Err(ParseRequirementError::EditableNotAPath(_))
+        ));
+
+        // The markers of a path without a name are not part of the path
+        let RequirementSpec::Path { path, marker, .. } =
+            parse("./projects/app ; python_version >= '3.8'")
+        else {
+            panic!("expected a path requirement");
+        };
+        assert_eq!(path, PathBuf::from("./projects/app"));
+        assert_eq!(
+            marker,
+            Some(MarkerTree::from_str("python_version >= '3.8'").unwrap())
+        );
+
+        // Relative paths do not have to start with a dot
+        for requirement in [
+            "projects/app",
+            "projects/app[test]",
+            "flask-3.0.0-py3-none-any.whl",
+        ] {
+            let RequirementSpec::Path { path, .. } = parse(requirement) else {
+                panic!("expected {requirement} to be a path requirement");
+            };
+            assert!(requirement.starts_with(path.to_str().unwrap()));
+        }
+        assert!(matches!(
+            parse("flask; sys_platform == 'linux'"),
+            RequirementSpec::Named(_)
+        ));
+    }
+
+    #[test]
+    fn test_requirement_round_trip() {
+        for requirement in [
+            "flask>=2",
+            "./projects/app[test]",
+            "-e ./projects/app",
+            "./projects/app ; python_version >= \"3.8\"",
+            "app @ git+https://github.com/org/app.git@v1.0#subdirectory=lib",
+        ] {
+            let parsed = parse(requirement);
+            assert_eq!(parse(&parsed.to_string()), parsed);
+        }
+        #[cfg(unix)]
+        {
+            let parsed = parse("app @ file:///projects/app");
+            assert!(matches!(parsed, RequirementSpec::Path { .. }));
+            assert_eq!(parsed.to_string(), "app @ file:///projects/app");
+        }
+    }
+
+    #[test]
+    fn test_to_pep508() {
+        assert_eq!(
+            parse("flask>=2").to_pep508().unwrap().to_string(),
+            "flask>=2"
+        );
+        assert!(parse("./projects/app").to_pep508().is_err());
+    }
+}
@@ -2,6 +2,7 @@ mod read_and_seek;
 mod streaming_or_local;
 
 mod seek_slice;
+mod shared_reader;
 
 use include_dir::{include_dir, Dir};
 use url::Url;
@@ -10,6 +11,7 @@ pub use read_and_seek::ReadAndSeek;
 pub use streaming_or_local::StreamingOrLocal;
 
 pub use seek_slice::SeekSlice;
+pub use shared_reader::SharedReader;
 
 /// Keep retrying a certain IO function until it either succeeds or until it doesn't return
 /// [`std::io::ErrorKind::Interrupted`].
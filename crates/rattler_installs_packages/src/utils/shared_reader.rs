@@ -0,0 +1,186 @@
+use crate::utils::ReadAndSeek;
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A reader over the bytes of an artifact that is cheap to clone. Every clone keeps track of its
+/// own position, which allows multiple threads to read from the same artifact at the same time.
+///
+/// Readers created with [`SharedReader::from_path`] open their own handle to the file and readers
+/// created with [`SharedReader::from_bytes`] share an immutable buffer. Readers created with
+/// [`SharedReader::from_reader`] share the underlying reader and only lock it for the duration of
+/// a single read or seek.
+pub struct SharedReader {
+    source: Source,
+    position: u64,
+}
+
+enum Source {
+    /// A file on disk. The file is opened lazily by every clone.
+    File {
+        path: Arc<PathBuf>,
+        file: Option<File>,
+    },
+
+    /// Bytes in memory.
+    Bytes(Arc<[u8]>),
+
+    /// Any other reader that is shared between all clones.
+    Shared(Arc<Mutex<Box<dyn ReadAndSeek + Send>>>),
+}
+
+impl SharedReader {
+    /// Creates a reader for the file at the given path. Fails if the file cannot be opened.
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self {
+            source: Source::File {
+                path: Arc::new(path.to_path_buf()),
+                file: Some(file),
+            },
+            position: 0,
+        })
+    }
+
+    /// Creates a reader over an in-memory buffer.
+    pub fn from_bytes(bytes: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            source: Source::Bytes(bytes.into()),
+            position: 0,
+        }
+    }
+
+    /// Creates a reader that shares `reader` between all of its clones. The reader is read from
+    /// the start.
+    pub fn from_reader(reader: Box<dyn ReadAndSeek + Send>) -> Self {
+        Self {
+            source: Source::Shared(Arc::new(Mutex::new(reader))),
+            position: 0,
+        }
+    }
+
+    /// Returns the handle to the file, opening it if this clone didn't open it yet.
+    fn file(path: &Path, file: &mut Option<File>, position: u64) -> io::Result<&mut File> {
+        match file {
+            Some(file) => Ok(file),
+            None => {
+                let mut opened = File::open(path)?;
+                opened.seek(SeekFrom::Start(position))?;
+                Ok(file.insert(opened))
+            }
+        }
+    }
+}
+
+impl Clone for SharedReader {
+    fn clone(&self) -> Self {
+        let source = match &self.source {
+            Source::File { path, .. } => Source::File {
+                path: path.clone(),
+                file: None,
+            },
+            Source::Bytes(bytes) => Source::Bytes(bytes.clone()),
+            Source::Shared(reader) => Source::Shared(reader.clone()),
+        };
+        Self {
+            source,
+            position: self.position,
+        }
+    }
+}
+
+impl Read for SharedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match &mut self.source {
+            Source::File { path, file } => Self::file(path, file, self.position)?.read(buf)?,
+            Source::Bytes(bytes) => {
+                let start = usize::try_from(self.position)
+                    .map_or(bytes.len(), |position| position.min(bytes.len()));
+                let mut remaining = &bytes[start..];
+                remaining.read(buf)?
+            }
+            Source::Shared(reader) => {
+                let mut reader = reader.lock();
+                reader.seek(SeekFrom::Start(self.position))?;
+                reader.read(buf)?
+            }
+        };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for SharedReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let len = match &mut self.source {
+                    Source::File { path, file } => {
+                        Self::file(path, file, self.position)?.metadata()?.len()
+                    }
+                    Source::Bytes(bytes) => bytes.len() as u64,
+                    Source::Shared(reader) => reader.lock().seek(SeekFrom::End(0))?,
+                };
+                len.checked_add_signed(offset)
+            }
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if let Source::File {
+            file: Some(file), ..
+        } = &mut self.source
+        {
+            file.seek(SeekFrom::Start(position))?;
+        }
+        self.position = position;
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read_all(mut reader: impl Read) -> String {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_clones_have_their_own_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        std::fs::write(&path, "hello world").unwrap();
+
+        for mut reader in [
+            SharedReader::from_path(&path).unwrap(),
+            SharedReader::from_bytes(b"hello world".to_vec()),
+            SharedReader::from_reader(Box::new(Cursor::new(b"hello world".to_vec()))),
+        ] {
+            reader.seek(SeekFrom::Start(6)).unwrap();
+            let mut clone = reader.clone();
+            assert_eq!(read_all(&mut reader), "world");
+
+            // The clone starts at the position of the original and is not affected by reading
+            // from the original.
+            assert_eq!(read_all(&mut clone), "world");
+            clone.rewind().unwrap();
+            assert_eq!(read_all(&mut clone.clone()), "hello world");
+
+            assert_eq!(reader.seek(SeekFrom::End(-5)).unwrap(), 6);
+            assert_eq!(reader.seek(SeekFrom::Current(-6)).unwrap(), 0);
+            assert!(reader.seek(SeekFrom::Current(-1)).is_err());
+        }
+    }
+}
@@ -3,36 +3,105 @@ use crate::artifacts::{SDist, Wheel};
 use crate::index::PackageDb;
 use crate::python_env::{PythonLocation, VEnv, WheelTags};
 use crate::resolve::{resolve, PinnedPackage, ResolveOptions};
-use crate::types::Artifact;
-use crate::wheel_builder::{build_requirements, WheelBuildError};
+use crate::types::{Artifact, SDistFilename, WheelCoreMetadata};
+use crate::wheel_builder::project::ProjectDir;
+use crate::wheel_builder::stage::{
+    BuildTarget, EggInfoResult, RequiresResult, Stage, StageResult, StageStatus, RESULT_VERSION,
+};
+use crate::wheel_builder::{
+    build_requirements, BuildRequirementSource, BuildRequirementsHook, ConfigSettings,
+    WheelBuildError,
+};
 use pep508_rs::{MarkerEnvironment, Requirement};
+use serde::de::DeserializeOwned;
 use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::str::FromStr;
 
 // include static build_frontend.py string
 const BUILD_FRONTEND_PY: &str = include_str!("./wheel_builder_frontend.py");
+
+/// The source code a [`BuildEnvironment`] builds.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum BuildSource<'a> {
+    /// An sdist, which is extracted to the work directory.
+    SDist(&'a SDist),
+
+    /// A project directory, which is built in place.
+    Project(&'a ProjectDir),
+}
+
+impl<'a> BuildSource<'a> {
+    /// Returns the filename that identifies the source in hooks and errors.
+    pub(crate) fn name(&self) -> &'a SDistFilename {
+        match self {
+            BuildSource::SDist(sdist) => sdist.name(),
+            BuildSource::Project(project) => &project.name,
+        }
+    }
+
+    /// Returns the `[build-system]` table of the source.
+    fn build_system(&self) -> pyproject_toml::BuildSystem {
+        match self {
+            BuildSource::SDist(sdist) => {
+                sdist
+                    .read_build_info()
+                    .unwrap_or_else(|_| pyproject_toml::BuildSystem {
+                        requires: Vec::new(),
+                        build_backend: None,
+                        backend_path: None,
+                    })
+            }
+            BuildSource::Project(project) => project.build_system.clone(),
+        }
+    }
+
+    /// Returns the directory the build backend runs in.
+    fn package_dir(&self, work_dir: &Path) -> Result<PathBuf, WheelBuildError> {
+        match self {
+            // The single top-level directory of the sdist which is not always named
+            // `{name}-{version}`.
+            BuildSource::SDist(sdist) => Ok(work_dir.join(sdist.top_level_directory()?)),
+            BuildSource::Project(project) => Ok(project.path.clone()),
+        }
+    }
+}
 /// A build environment for building wheels
 /// This struct contains the virtualenv and everything that is needed
 /// to execute the PEP517 build backend hools
+///
+/// A build environment can be obtained with [`super::WheelBuilder::build_environment`] to run
+/// additional commands in it, e.g. to run tests against the extracted sdist. The work directory
+/// is removed when the environment is dropped unless [`Self::persist`] is called.
 #[derive(Debug)]
-pub(crate) struct BuildEnvironment<'db> {
+pub struct BuildEnvironment<'db> {
     work_dir: tempfile::TempDir,
     package_dir: PathBuf,
     #[allow(dead_code)]
     build_system: pyproject_toml::BuildSystem,
     entry_point: String,
     build_requirements: Vec<Requirement>,
+    requirement_sources: HashMap<Requirement, BuildRequirementSource>,
     resolved_wheels: Vec<PinnedPackage<'db>>,
     venv: VEnv,
+    env_variables: HashMap<String, String>,
+    config_settings: ConfigSettings,
 }
 
 impl<'db> BuildEnvironment<'db> {
-    /// Extract the wheel and write the build_frontend.py to the work folder
-    pub(crate) fn install_build_files(&self, sdist: &SDist) -> std::io::Result<()> {
-        // Extract the sdist to the work folder
-        sdist.extract_to(self.work_dir.path())?;
+    /// Extract the sdist, if any, and write the build_frontend.py to the work folder
+    pub(crate) fn install_build_files(&self, source: BuildSource<'_>) -> std::io::Result<()> {
+        // Extract the sdist to the work folder, projects are built in place
+        if let BuildSource::SDist(sdist) = source {
+            sdist.extract_to(self.work_dir.path())?;
+        }
+        // Write the config settings for the build backend to the work folder
+        std::fs::write(
+            self.work_dir.path().join("config_settings.json"),
+            serde_json::to_string(&self.config_settings)?,
+        )?;
         // Write the python frontend to the work folder
         std::fs::write(
             self.work_dir.path().join("build_frontend.py"),
@@ -40,54 +109,153 @@ impl<'db> BuildEnvironment<'db> {
         )
     }
 
-    pub(crate) fn work_dir(&self) -> &Path {
+    /// Returns the temporary directory that contains the extracted sdist, the virtual
+    /// environment and the results of the build backend.
+    pub fn work_dir(&self) -> &Path {
         self.work_dir.path()
     }
 
+    /// Returns the directory of the extracted sdist, or the project directory for editables that
+    /// are built in place. Commands of the build backend are executed in this directory.
+    pub fn package_dir(&self) -> &Path {
+        &self.package_dir
+    }
+
+    /// Returns the virtual environment the build requirements are installed in.
+    pub fn venv(&self) -> &VEnv {
+        &self.venv
+    }
+
+    /// Returns the path to the python executable of the virtual environment.
+    pub fn python_executable(&self) -> PathBuf {
+        self.venv.python_executable()
+    }
+
+    /// Returns the requirements of the `[build-system]` table of the sdist.
+    pub fn build_requirements(&self) -> &[Requirement] {
+        &self.build_requirements
+    }
+
+    /// Returns where a build requirement originates from, e.g. the `pyproject.toml` of the sdist
+    /// or the build backend. Returns `None` if `requirement` is not a build requirement of this
+    /// environment.
+    pub fn build_requirement_source(
+        &self,
+        requirement: &Requirement,
+    ) -> Option<BuildRequirementSource> {
+        self.requirement_sources.get(requirement).copied()
+    }
+
+    /// Returns the packages that are installed in the virtual environment, including the
+    /// requirements that were reported by the build backend.
+    pub fn installed_packages(&self) -> &[PinnedPackage<'db>] {
+        &self.resolved_wheels
+    }
+
+    /// Returns a command that executes `program` in the same way the build backend is executed:
+    /// in the package directory, with the virtual environment activated and with the
+    /// environment variables configured for the package.
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        let scripts_dir = self.venv.root().join(self.venv.install_paths().scripts());
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let path =
+            std::env::join_paths(std::iter::once(scripts_dir).chain(std::env::split_paths(&path)))
+                .unwrap_or(path);
+
+        let mut command = Command::new(program);
+        command
+            .current_dir(&self.package_dir)
+            .env("VIRTUAL_ENV", self.venv.root())
+            .env("PATH", path)
+            .envs(&self.env_variables);
+        command
+    }
+
+    /// Returns a command that executes the python interpreter of the virtual environment. See
+    /// [`Self::command`].
+    pub fn python_command(&self) -> Command {
+        self.command(self.python_executable())
+    }
+
+    /// Keeps the work directory on disk instead of removing it when the environment is dropped
+    /// and returns its path. The caller is responsible for removing the directory.
+    pub fn persist(self) -> PathBuf {
+        self.work_dir.into_path()
+    }
+
+    /// Removes the work directory. Unlike dropping the environment this reports errors that
+    /// occur while removing the directory.
+    pub fn close(self) -> std::io::Result<()> {
+        self.work_dir.close()
+    }
+
     /// Get the extra requirements and combine these to the existing requirements
-    /// This uses the `GetRequiresForBuildWheel` entry point of the build backend.
+    /// This uses the `GetRequiresForBuildWheel` entry point of the build backend, or
+    /// `GetRequiresForBuildEditable` when building an editable.
     /// this might not be available for all build backends.
     /// and it can also return an empty list of requirements.
-    fn get_extra_requirements(&self) -> Result<HashSet<Requirement>, WheelBuildError> {
-        let output = self.run_command("GetRequiresForBuildWheel").map_err(|e| {
-            WheelBuildError::CouldNotRunCommand("GetRequiresForBuildWheel".into(), e)
-        })?;
-
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(WheelBuildError::Error(stderr.to_string()));
-        }
-
-        // The extra requirements are stored in a file called extra_requirements.json
-        let extra_requirements_json =
-            std::fs::read_to_string(self.work_dir.path().join("extra_requirements.json"))?;
-        let extra_requirements: Vec<String> = serde_json::from_str(&extra_requirements_json)?;
+    fn get_extra_requirements(
+        &self,
+        target: BuildTarget,
+    ) -> Result<HashSet<Requirement>, WheelBuildError> {
+        let extra_requirements = self
+            .run_stage::<RequiresResult>(target.requires_stage())?
+            .map(|result| result.requires)
+            .unwrap_or_default();
 
-        Ok(HashSet::<Requirement>::from_iter(
-            extra_requirements
-                .iter()
-                .map(|s| Requirement::from_str(s).expect("...")),
-        ))
+        extra_requirements
+            .iter()
+            .map(|requirement| {
+                Requirement::from_str(requirement).map_err(|err| {
+                    WheelBuildError::InvalidBuildRequirement {
+                        backend: self.entry_point.clone(),
+                        requirement: requirement.clone(),
+                        source: err,
+                    }
+                })
+            })
+            .collect()
     }
 
     /// Install extra requirements into the venv, if any extra were found
     /// If the extra requirements are already installed, this will do nothing
     /// for that requirement.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn install_extra_requirements(
-        &self,
+        &mut self,
+        target: BuildTarget,
+        name: &SDistFilename,
         package_db: &'db PackageDb,
         env_markers: &MarkerEnvironment,
         wheel_tags: Option<&WheelTags>,
         resolve_options: &ResolveOptions,
+        build_requirements_hook: Option<&BuildRequirementsHook>,
     ) -> Result<(), WheelBuildError> {
         // Get extra requirements if any
-        let extra_requirements = self.get_extra_requirements()?;
+        let extra_requirements = self.get_extra_requirements(target)?;
 
         // Combine previous requirements with extra requirements
         let combined_requirements = HashSet::from_iter(self.build_requirements.iter().cloned())
             .union(&extra_requirements)
             .cloned()
             .collect::<Vec<_>>();
+        let source = match target {
+            BuildTarget::Wheel => BuildRequirementSource::GetRequiresForBuildWheel,
+            BuildTarget::Editable => BuildRequirementSource::GetRequiresForBuildEditable,
+        };
+        for requirement in extra_requirements.iter() {
+            self.requirement_sources
+                .entry(requirement.clone())
+                .or_insert(source);
+        }
+        let combined_requirements = match build_requirements_hook {
+            Some(hook) if !extra_requirements.is_empty() => {
+                let requirements = hook(name, combined_requirements);
+                record_hook_requirements(&mut self.requirement_sources, &requirements);
+                requirements
+            }
+            _ => combined_requirements,
+        };
 
         // Install extra requirements if any new ones were foujnd
         if !extra_requirements.is_empty()
@@ -107,7 +275,12 @@ impl<'db> BuildEnvironment<'db> {
                 resolve_options,
             )
             .await
-            .map_err(|_| WheelBuildError::CouldNotResolveEnvironment(all_requirements))?;
+            .map_err(|err| {
+                WheelBuildError::CouldNotResolveEnvironment(
+                    with_sources(&self.requirement_sources, &all_requirements),
+                    err.into(),
+                )
+            })?;
 
             // install extra wheels
             for package_info in extra_resolved_wheels {
@@ -119,54 +292,163 @@ impl<'db> BuildEnvironment<'db> {
                     package_info.name,
                     package_info.version
                 );
-                let artifact_info = package_info.artifacts.first().unwrap();
+                let artifact_info = package_info
+                    .artifacts
+                    .first()
+                    .ok_or(WheelBuildError::CouldNotGetArtifact)?;
                 let artifact = package_db
                     .get_artifact::<Wheel>(artifact_info)
                     .await
-                    .expect("could not get artifact");
+                    .map_err(|err| {
+                        WheelBuildError::CouldNotDownloadArtifact(
+                            artifact_info.filename.to_string(),
+                            err.into(),
+                        )
+                    })?;
 
                 self.venv
                     .install_wheel(&artifact, &UnpackWheelOptions::default())?;
+                self.resolved_wheels.push(package_info);
             }
         }
         Ok(())
     }
 
+    /// Runs a stage of the frontend in the build environment and returns its typed result, or
+    /// `None` if the stage is not supported by the build backend or the sdist.
+    ///
+    /// The result is read from the envelope the frontend writes to the work directory, see
+    /// [`StageResult`]. If the build backend raised an exception it is part of the returned error.
+    pub(crate) fn run_stage<T: DeserializeOwned>(
+        &self,
+        stage: Stage,
+    ) -> Result<Option<T>, WheelBuildError> {
+        let invalid = |reason: String| WheelBuildError::InvalidFrontendResult {
+            stage: stage.to_string(),
+            reason,
+        };
+
+        // A result of an earlier run of the stage would be mistaken for the result of this run if
+        // the frontend fails before it writes its result.
+        let result_file = stage.result_file(self.work_dir.path());
+        match std::fs::remove_file(&result_file) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+
+        let output = self
+            .run_command(stage.as_str())
+            .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.to_string(), e))?;
+
+        let json = match std::fs::read_to_string(&result_file) {
+            Ok(json) => json,
+            // The frontend failed before it could write its result, e.g. because the python
+            // interpreter crashed.
+            Err(_) if !output.status.success() => {
+                return Err(WheelBuildError::from_backend_output(&output, None))
+            }
+            Err(err) => return Err(invalid(err.to_string())),
+        };
+        let result = StageResult::<T>::from_json(&json)?;
+        if result.version != RESULT_VERSION {
+            return Err(invalid(format!(
+                "unsupported result version {}, expected {RESULT_VERSION}",
+                result.version
+            )));
+        }
+
+        tracing::debug!(
+            "{} finished with status {:?} in {:?}",
+            result.stage,
+            result.status,
+            result.duration()
+        );
+
+        match result.status {
+            StageStatus::Ok => result
+                .result
+                .map(Some)
+                .ok_or_else(|| invalid("the result is missing".into())),
+            StageStatus::Unsupported => Ok(None),
+            StageStatus::Error => Err(WheelBuildError::from_backend_output(&output, result.error)),
+        }
+    }
+
     /// Run a command in the build environment
+    ///
+    /// The frontend writes the results of a stage to files in the work directory, the output of
+    /// the backend is only logged. This keeps backends that print to stdout from interfering
+    /// with the results.
     pub(crate) fn run_command(&self, stage: &str) -> std::io::Result<Output> {
         // three args: cache.folder, goal
-        Command::new(self.venv.python_executable())
+        let output = Command::new(self.venv.python_executable())
             .current_dir(&self.package_dir)
             .arg(self.work_dir.path().join("build_frontend.py"))
             .arg(self.work_dir.path())
             .arg(&self.entry_point)
             .arg(stage)
-            .output()
+            .envs(&self.env_variables)
+            .output()?;
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            tracing::debug!("[{stage} stdout] {line}");
+        }
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            tracing::debug!("[{stage} stderr] {line}");
+        }
+
+        Ok(output)
+    }
+
+    /// Generates the metadata of the sdist by running `setup.py egg_info`. This is a fallback for
+    /// legacy sdists whose build backend does not support `prepare_metadata_for_build_wheel`.
+    ///
+    /// Returns `None` if the sdist does not contain a `setup.py`.
+    pub(crate) fn egg_info_metadata(
+        &self,
+    ) -> Result<Option<(Vec<u8>, WheelCoreMetadata)>, WheelBuildError> {
+        let Some(result) = self.run_stage::<EggInfoResult>(Stage::EggInfo)? else {
+            return Ok(None);
+        };
+
+        let metadata = std::fs::read(result.egg_info_dir.join("PKG-INFO"))?;
+        let wheel_metadata = WheelCoreMetadata::try_from(metadata.as_slice())?;
+        Ok(Some((metadata, wheel_metadata)))
     }
 
-    /// Setup the build environment so that we can build a wheel from an sdist
+    /// Setup the build environment so that we can build a wheel from an sdist or a project
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn setup(
-        sdist: &SDist,
+        source: BuildSource<'_>,
         package_db: &'db PackageDb,
         env_markers: &MarkerEnvironment,
         wheel_tags: Option<&WheelTags>,
         resolve_options: &ResolveOptions,
+        python_location: &PythonLocation,
+        build_requirements_hook: Option<&BuildRequirementsHook>,
+        env_variables: HashMap<String, String>,
+        config_settings: ConfigSettings,
     ) -> Result<BuildEnvironment<'db>, WheelBuildError> {
         // Setup a work directory and a new env dir
-        let work_dir = tempfile::tempdir().unwrap();
-        let venv = VEnv::create(&work_dir.path().join("venv"), PythonLocation::System).unwrap();
+        let work_dir = tempfile::tempdir().map_err(WheelBuildError::CouldNotCreateWorkDir)?;
+        let venv = VEnv::create(&work_dir.path().join("venv"), python_location.clone())?;
 
         // Find the build system
-        let build_system =
-            sdist
-                .read_build_info()
-                .unwrap_or_else(|_| pyproject_toml::BuildSystem {
-                    requires: Vec::new(),
-                    build_backend: None,
-                    backend_path: None,
-                });
+        let build_system = source.build_system();
         // Find the build requirements
-        let build_requirements = build_requirements(&build_system);
+        let (build_requirements, source) = build_requirements(&build_system);
+        let mut requirement_sources: HashMap<_, _> = build_requirements
+            .iter()
+            .map(|requirement| (requirement.clone(), source))
+            .collect();
+        let build_requirements = match build_requirements_hook {
+            Some(hook) => {
+                let requirements = hook(source.name(), build_requirements);
+                record_hook_requirements(&mut requirement_sources, &requirements);
+                requirements
+            }
+            None => build_requirements,
+        };
         // Resolve the build environment
         let resolved_wheels = resolve(
             package_db,
@@ -178,25 +460,44 @@ impl<'db> BuildEnvironment<'db> {
             resolve_options,
         )
         .await
-        .map_err(|_| WheelBuildError::CouldNotResolveEnvironment(build_requirements.to_vec()))?;
+        .map_err(|err| {
+            WheelBuildError::CouldNotResolveEnvironment(
+                with_sources(&requirement_sources, &build_requirements),
+                err.into(),
+            )
+        })?;
 
         // Install into venv
+        let mut artifacts = Vec::with_capacity(resolved_wheels.len());
         for package_info in resolved_wheels.iter() {
-            let artifact_info = package_info.artifacts.first().unwrap();
+            let artifact_info = package_info
+                .artifacts
+                .first()
+                .ok_or(WheelBuildError::CouldNotGetArtifact)?;
             let artifact = package_db
                 .get_artifact::<Wheel>(artifact_info)
                 .await
-                .map_err(|_| WheelBuildError::CouldNotGetArtifact)?;
-
-            venv.install_wheel(
-                &artifact,
-                &UnpackWheelOptions {
-                    installer: None,
-                    ..Default::default()
-                },
-            )?;
+                .map_err(|err| {
+                    WheelBuildError::CouldNotDownloadArtifact(
+                        artifact_info.filename.to_string(),
+                        err.into(),
+                    )
+                })?;
+            artifacts.push(artifact);
         }
 
+        let unpack_options = UnpackWheelOptions {
+            installer: None,
+            compatible_tags: wheel_tags.cloned(),
+            session_log: package_db.session_log().cloned(),
+            ..Default::default()
+        };
+        let wheels = artifacts
+            .iter()
+            .map(|artifact| (artifact, &unpack_options))
+            .collect::<Vec<_>>();
+        venv.install_wheels(&wheels, None)?;
+
         const DEFAULT_BUILD_BACKEND: &str = "setuptools.build_meta:__legacy__";
         let entry_point = build_system
             .build_backend
@@ -204,20 +505,49 @@ impl<'db> BuildEnvironment<'db> {
             .unwrap_or_else(|| DEFAULT_BUILD_BACKEND.to_string());
 
         // Package dir for the package we need to build
-        let package_dir = work_dir.path().join(format!(
-            "{}-{}",
-            sdist.name().distribution.as_source_str(),
-            sdist.name().version
-        ));
+        let package_dir = source.package_dir(work_dir.path())?;
 
         Ok(BuildEnvironment {
             work_dir,
             package_dir,
             build_system,
             build_requirements,
+            requirement_sources,
             entry_point,
             resolved_wheels,
             venv,
+            env_variables,
+            config_settings,
         })
     }
 }
+
+/// Marks the requirements returned by the [`BuildRequirementsHook`] that were not previously known
+/// as originating from the hook.
+fn record_hook_requirements(
+    sources: &mut HashMap<Requirement, BuildRequirementSource>,
+    requirements: &[Requirement],
+) {
+    for requirement in requirements {
+        sources
+            .entry(requirement.clone())
+            .or_insert(BuildRequirementSource::BuildRequirementsHook);
+    }
+}
+
+/// Pairs each requirement with the place it originates from.
+fn with_sources(
+    sources: &HashMap<Requirement, BuildRequirementSource>,
+    requirements: &[Requirement],
+) -> Vec<(Requirement, BuildRequirementSource)> {
+    requirements
+        .iter()
+        .map(|requirement| {
+            let source = sources
+                .get(requirement)
+                .copied()
+                .unwrap_or(BuildRequirementSource::BuildRequirementsHook);
+            (requirement.clone(), source)
+        })
+        .collect()
+}
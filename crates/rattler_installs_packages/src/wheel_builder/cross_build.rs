@@ -0,0 +1,308 @@
+//! Cross-architecture builds of sdists.
+//!
+//! Build backends determine the platform of the wheel they build from the python interpreter that
+//! runs them. Tools like `cibuildwheel` cross build by overriding what the backend sees through
+//! environment variables: `_PYTHON_HOST_PLATFORM` changes the platform reported by
+//! `sysconfig.get_platform()` and therefore the platform tag of the wheel, while variables like
+//! `ARCHFLAGS` make the compiler target the other architecture. A [`CrossBuildTarget`] sets these
+//! variables for every build, e.g. to build `macosx_11_0_arm64` wheels on an x86_64 mac.
+//!
+//! Compilers for linux and windows only target one architecture, so these targets require a cross
+//! toolchain configured through the `CC` and `CXX` environment variables, either in the environment
+//! of rip or in the build environment variables. Builds without one are refused, see
+//! [`CrossBuildTarget::missing_toolchain`].
+//!
+//! This does not make every sdist cross-compilable, packages that run the code they compile or
+//! that detect the architecture themselves will still build for the host. Wheels that are not
+//! built for the target are rejected, see [`CrossBuildTarget::is_built_for`].
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The platform wheels are cross built for, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrossBuildTarget {
+    platform_tag: String,
+    os: TargetOs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TargetOs {
+    MacOs {
+        deployment_target: String,
+        architecture: String,
+        architectures: Vec<&'static str>,
+    },
+    Linux {
+        architecture: String,
+    },
+    Windows {
+        host_platform: &'static str,
+        architecture: &'static str,
+    },
+}
+
+/// The environment variables that select the C and C++ compilers of a cross toolchain.
+const TOOLCHAIN_VARIABLES: [&str; 2] = ["CC", "CXX"];
+
+/// The platform tag of a [`CrossBuildTarget`] is not supported.
+#[derive(Debug, Clone, Error)]
+#[error("cross builds for the platform '{0}' are not supported")]
+pub struct UnsupportedCrossBuildTarget(pub String);
+
+impl CrossBuildTarget {
+    /// Returns the target for the platform tag of the wheels that should be built, e.g.
+    /// `macosx_11_0_arm64`, `linux_aarch64` or `win_arm64`.
+    pub fn from_platform_tag(platform_tag: &str) -> Result<Self, UnsupportedCrossBuildTarget> {
+        let unsupported = || UnsupportedCrossBuildTarget(platform_tag.to_string());
+        let os = if let Some(rest) = platform_tag.strip_prefix("macosx_") {
+            let mut parts = rest.splitn(3, '_');
+            let (Some(major), Some(minor), Some(architecture)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                return Err(unsupported());
+            };
+            let architectures = match architecture {
+                "arm64" => vec!["arm64"],
+                "x86_64" => vec!["x86_64"],
+                "universal2" => vec!["arm64", "x86_64"],
+                _ => return Err(unsupported()),
+            };
+            TargetOs::MacOs {
+                deployment_target: format!("{major}.{minor}"),
+                architecture: architecture.to_string(),
+                architectures,
+            }
+        } else if let Some(architecture) = linux_architecture(platform_tag) {
+            TargetOs::Linux {
+                architecture: architecture.to_string(),
+            }
+        } else {
+            let (host_platform, architecture) = match platform_tag {
+                "win32" => ("win32", "x86"),
+                "win_amd64" => ("win-amd64", "x64"),
+                "win_arm64" => ("win-arm64", "arm64"),
+                _ => return Err(unsupported()),
+            };
+            TargetOs::Windows {
+                host_platform,
+                architecture,
+            }
+        };
+
+        Ok(Self {
+            platform_tag: platform_tag.to_string(),
+            os,
+        })
+    }
+
+    /// Returns the target for a `_PYTHON_HOST_PLATFORM` value as used by `cibuildwheel`, e.g.
+    /// `macosx-11.0-arm64`.
+    pub fn from_host_platform(host_platform: &str) -> Result<Self, UnsupportedCrossBuildTarget> {
+        Self::from_platform_tag(&host_platform.replace(['-', '.'], "_"))
+    }
+
+    /// Detects a target from the `_PYTHON_HOST_PLATFORM` environment variable of the current
+    /// process, e.g. when rip runs inside `cibuildwheel`. Returns `None` if the variable is not set.
+    pub fn from_env() -> Option<Result<Self, UnsupportedCrossBuildTarget>> {
+        std::env::var("_PYTHON_HOST_PLATFORM")
+            .ok()
+            .map(|host_platform| Self::from_host_platform(&host_platform))
+    }
+
+    /// Returns the platform tag of the wheels that are built for this target.
+    pub fn platform_tag(&self) -> &str {
+        &self.platform_tag
+    }
+
+    /// Returns the environment variables that make build backends target this platform.
+    pub fn environment_variables(&self) -> HashMap<String, String> {
+        let variables: Vec<(&str, String)> = match &self.os {
+            TargetOs::MacOs {
+                deployment_target,
+                architecture,
+                architectures,
+            } => vec![
+                (
+                    "_PYTHON_HOST_PLATFORM",
+                    format!("macosx-{deployment_target}-{architecture}"),
+                ),
+                (
+                    "ARCHFLAGS",
+                    architectures
+                        .iter()
+                        .map(|architecture| format!("-arch {architecture}"))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ),
+                ("MACOSX_DEPLOYMENT_TARGET", deployment_target.clone()),
+                ("CMAKE_OSX_ARCHITECTURES", architectures.join(";")),
+            ],
+            TargetOs::Linux { architecture } => {
+                vec![("_PYTHON_HOST_PLATFORM", format!("linux-{architecture}"))]
+            }
+            TargetOs::Windows {
+                host_platform,
+                architecture,
+            } => vec![
+                ("_PYTHON_HOST_PLATFORM", host_platform.to_string()),
+                ("VSCMD_ARG_TGT_ARCH", architecture.to_string()),
+            ],
+        };
+        variables
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    /// Returns the toolchain variables that must be set to build for this target but are neither
+    /// part of the build environment `variables` nor of the environment of the current process.
+    /// Mac compilers target other architectures through `ARCHFLAGS`, so they never need one.
+    pub fn missing_toolchain(&self, variables: &HashMap<String, String>) -> Vec<&'static str> {
+        if matches!(self.os, TargetOs::MacOs { .. }) {
+            return Vec::new();
+        }
+        TOOLCHAIN_VARIABLES
+            .into_iter()
+            .filter(|name| !variables.contains_key(*name) && std::env::var_os(name).is_none())
+            .collect()
+    }
+
+    /// Returns true if a wheel with the given compressed platform tag set, e.g. `any` or
+    /// `macosx_11_0_arm64`, can be used on this target. The deployment target of mac wheels is
+    /// chosen by the build backend, so only the architecture is compared.
+    pub fn is_built_for(&self, platform_tags: &str) -> bool {
+        platform_tags.split('.').any(|tag| {
+            tag == "any"
+                || match &self.os {
+                    TargetOs::MacOs { architecture, .. } => {
+                        tag.starts_with("macosx_")
+                            && tag.rsplit('_').next() == Some(architecture.as_str())
+                    }
+                    TargetOs::Linux { architecture } => {
+                        linux_architecture(tag) == Some(architecture.as_str())
+                    }
+                    TargetOs::Windows { .. } => tag == self.platform_tag,
+                }
+        })
+    }
+}
+
+/// Returns the architecture of a linux platform tag, e.g. `aarch64` for `linux_aarch64` or
+/// `manylinux_2_17_aarch64`. Wheels are always built with the `linux` platform tag, auditwheel
+/// turns them into `manylinux` wheels afterwards.
+fn linux_architecture(platform_tag: &str) -> Option<&str> {
+    if let Some(architecture) = platform_tag.strip_prefix("linux_") {
+        return Some(architecture);
+    }
+    if let Some(rest) = platform_tag
+        .strip_prefix("manylinux_")
+        .or_else(|| platform_tag.strip_prefix("musllinux_"))
+    {
+        return rest.splitn(3, '_').nth(2);
+    }
+    ["manylinux1_", "manylinux2010_", "manylinux2014_"]
+        .into_iter()
+        .find_map(|prefix| platform_tag.strip_prefix(prefix))
+}
+
+impl FromStr for CrossBuildTarget {
+    type Err = UnsupportedCrossBuildTarget;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_platform_tag(s)
+    }
+}
+
+impl Display for CrossBuildTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.platform_tag)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cross_build_environment_variables() {
+        let target: CrossBuildTarget = "macosx_11_0_arm64".parse().unwrap();
+        let variables = target.environment_variables();
+        assert_eq!(variables["_PYTHON_HOST_PLATFORM"], "macosx-11.0-arm64");
+        assert_eq!(variables["ARCHFLAGS"], "-arch arm64");
+        assert_eq!(variables["MACOSX_DEPLOYMENT_TARGET"], "11.0");
+
+        let target: CrossBuildTarget = "macosx_10_9_universal2".parse().unwrap();
+        let variables = target.environment_variables();
+        assert_eq!(variables["_PYTHON_HOST_PLATFORM"], "macosx-10.9-universal2");
+        assert_eq!(variables["ARCHFLAGS"], "-arch arm64 -arch x86_64");
+        assert_eq!(variables["CMAKE_OSX_ARCHITECTURES"], "arm64;x86_64");
+
+        for tag in [
+            "linux_x86_64",
+            "manylinux_2_17_x86_64",
+            "manylinux2014_x86_64",
+            "musllinux_1_1_x86_64",
+        ] {
+            let target: CrossBuildTarget = tag.parse().unwrap();
+            assert_eq!(
+                target.environment_variables()["_PYTHON_HOST_PLATFORM"],
+                "linux-x86_64"
+            );
+        }
+
+        let target: CrossBuildTarget = "win_arm64".parse().unwrap();
+        let variables = target.environment_variables();
+        assert_eq!(variables["_PYTHON_HOST_PLATFORM"], "win-arm64");
+        assert_eq!(variables["VSCMD_ARG_TGT_ARCH"], "arm64");
+
+        assert!("any".parse::<CrossBuildTarget>().is_err());
+        assert!("macosx_11_0_ppc".parse::<CrossBuildTarget>().is_err());
+    }
+
+    #[test]
+    fn test_cross_build_from_host_platform() {
+        let target = CrossBuildTarget::from_host_platform("macosx-11.0-arm64").unwrap();
+        assert_eq!(target.platform_tag(), "macosx_11_0_arm64");
+        let target = CrossBuildTarget::from_host_platform("linux-aarch64").unwrap();
+        assert_eq!(target.platform_tag(), "linux_aarch64");
+        let target = CrossBuildTarget::from_host_platform("win-arm64").unwrap();
+        assert_eq!(target.platform_tag(), "win_arm64");
+    }
+
+    #[test]
+    fn test_cross_build_missing_toolchain() {
+        let target: CrossBuildTarget = "macosx_11_0_arm64".parse().unwrap();
+        assert!(target.missing_toolchain(&HashMap::new()).is_empty());
+
+        let target: CrossBuildTarget = "win_arm64".parse().unwrap();
+        let toolchain = HashMap::from([
+            (String::from("CC"), String::from("clang-cl")),
+            (String::from("CXX"), String::from("clang-cl")),
+        ]);
+        assert!(target.missing_toolchain(&toolchain).is_empty());
+        if std::env::var_os("CXX").is_none() {
+            let variables = HashMap::from([(String::from("CC"), String::from("clang-cl"))]);
+            assert_eq!(target.missing_toolchain(&variables), vec!["CXX"]);
+        }
+    }
+
+    #[test]
+    fn test_cross_build_is_built_for() {
+        let target: CrossBuildTarget = "macosx_11_0_arm64".parse().unwrap();
+        assert!(target.is_built_for("macosx_12_0_arm64"));
+        assert!(target.is_built_for("any"));
+        assert!(!target.is_built_for("macosx_11_0_x86_64"));
+
+        let target: CrossBuildTarget = "manylinux_2_17_aarch64".parse().unwrap();
+        assert!(target.is_built_for("linux_aarch64"));
+        assert!(target.is_built_for("manylinux_2_17_aarch64.manylinux2014_aarch64"));
+        assert!(!target.is_built_for("linux_x86_64"));
+
+        let target: CrossBuildTarget = "win_arm64".parse().unwrap();
+        assert!(target.is_built_for("win_arm64"));
+        assert!(!target.is_built_for("win_amd64"));
+    }
+}
@@ -0,0 +1,288 @@
+//! Deduplication of concurrent builds of the same sdist.
+//!
+//! Every resolution creates its own [`super::WheelBuilder`], so two resolutions that run at the
+//! same time and need the same sdist would both build it. The [`InFlightBuilds`] of the
+//! [`crate::index::PackageDb`] are shared by all builders: the first caller builds the wheel while
+//! the other callers wait for it and reuse the result, including a failure.
+
+use super::BuildRequirementsHook;
+use parking_lot::Mutex;
+use rattler_digest::Sha256Hash;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Identifies the result of a build: the contents of the sdist and everything that influences how
+/// it is built. Builds with the same key produce interchangeable wheels, which allows sharing
+/// concurrent builds and reusing wheels from the [`super::BuiltWheelCache`].
+///
+/// A [`BuildRequirementsHook`] cannot be described, so a key with a hook is only equal to keys
+/// with the very same hook. Such keys are not stable across processes and wheels built with them
+/// are not stored in the [`super::BuiltWheelCache`].
+#[derive(Clone)]
+pub struct BuildKey {
+    /// The sha256 hash of the sdist archive
+    sdist: Sha256Hash,
+
+    /// A description of the build options, e.g. the interpreter and the environment variables
+    options: String,
+
+    /// The hook that modifies the build requirements, compared by identity
+    build_requirements_hook: Option<BuildRequirementsHook>,
+}
+
+impl BuildKey {
+    /// Constructs a key from the hash of the sdist archive and a description of all the options
+    /// that influence the build.
+    pub fn new(sdist: Sha256Hash, options: String) -> Self {
+        Self {
+            sdist,
+            options,
+            build_requirements_hook: None,
+        }
+    }
+
+    /// Sets the hook that modifies the build requirements of the build, see the
+    /// [type documentation](Self).
+    pub fn with_build_requirements_hook(self, hook: BuildRequirementsHook) -> Self {
+        Self {
+            build_requirements_hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Returns the sha256 hash of the sdist archive.
+    pub fn sdist_sha256(&self) -> &Sha256Hash {
+        &self.sdist
+    }
+
+    /// Returns the description of the build options.
+    pub fn options(&self) -> &str {
+        &self.options
+    }
+
+    /// Returns true if wheels built with this key can be stored and reused by other processes,
+    /// which is not the case for builds with a [`BuildRequirementsHook`].
+    pub fn is_persistent(&self) -> bool {
+        self.build_requirements_hook.is_none()
+    }
+
+    /// Returns a digest of the key that is suitable as a directory name.
+    pub fn digest(&self) -> String {
+        let mut hasher = Sha256::default();
+        hasher.update(self.sdist.as_slice());
+        hasher.update(self.options.as_bytes());
+        let digest = format!("{:x}", hasher.finalize());
+        digest[..32].to_owned()
+    }
+
+    /// Returns the address of the hook, which identifies it as long as this key holds on to it.
+    fn hook_address(&self) -> Option<*const ()> {
+        self.build_requirements_hook
+            .as_ref()
+            .map(|hook| Arc::as_ptr(hook) as *const ())
+    }
+}
+
+impl PartialEq for BuildKey {
+    fn eq(&self, other: &Self) -> bool {
+        let same_hook = match (
+            &self.build_requirements_hook,
+            &other.build_requirements_hook,
+        ) {
+            (None, None) => true,
+            (Some(hook), Some(other_hook)) => Arc::ptr_eq(hook, other_hook),
+            _ => false,
+        };
+        self.sdist == other.sdist && self.options == other.options && same_hook
+    }
+}
+
+impl Eq for BuildKey {}
+
+impl Hash for BuildKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sdist.hash(state);
+        self.options.hash(state);
+        self.hook_address().hash(state);
+    }
+}
+
+impl Debug for BuildKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuildKey")
+            .field("sdist", &format!("{:x}", self.sdist))
+            .field("options", &self.options)
+            .field("build_requirements_hook", &self.hook_address())
+            .finish()
+    }
+}
+
+/// The result of a build as it is shared with the callers that waited for it. Build errors cannot
+/// be cloned so a failure is shared as its message.
+type SharedBuildResult = Result<PathBuf, String>;
+
+/// The slot of a single build. The lock is held while the wheel is built, the slot contains the
+/// result once the build finished.
+type BuildSlot = Arc<futures::lock::Mutex<Option<SharedBuildResult>>>;
+
+/// The error of [`InFlightBuilds::run`].
+#[derive(Debug)]
+pub(crate) enum InFlightBuildError<E> {
+    /// The build of this caller failed
+    Failed(E),
+
+    /// The build of another caller that this caller waited for failed with the given message
+    FailedConcurrently(String),
+}
+
+/// The builds that are currently running, see the [module documentation](self).
+#[derive(Debug, Default)]
+pub(crate) struct InFlightBuilds {
+    builds: Mutex<HashMap<BuildKey, BuildSlot>>,
+}
+
+impl InFlightBuilds {
+    /// Runs `build` for `key` unless a build with the same key is already running, in which case
+    /// that build is awaited and its result is returned instead. A wheel that was removed in the
+    /// meantime is built again.
+    pub async fn run<E, F>(
+        &self,
+        key: &BuildKey,
+        build: impl FnOnce() -> F,
+    ) -> Result<PathBuf, InFlightBuildError<E>>
+    where
+        E: Display,
+        F: Future<Output = Result<PathBuf, E>>,
+    {
+        let slot = self.builds.lock().entry(key.clone()).or_default().clone();
+        let mut shared = slot.lock().await;
+        match shared.as_ref() {
+            Some(Ok(wheel_file)) if wheel_file.is_file() => {
+                tracing::debug!("using concurrently built wheel {}", wheel_file.display());
+                return Ok(wheel_file.clone());
+            }
+            Some(Err(message)) => {
+                return Err(InFlightBuildError::FailedConcurrently(message.clone()));
+            }
+            _ => {}
+        }
+
+        let result = build().await;
+        *shared = Some(match &result {
+            Ok(wheel_file) => Ok(wheel_file.clone()),
+            Err(err) => Err(err.to_string()),
+        });
+        drop(shared);
+
+        // Callers that are still waiting keep their reference to the slot, later callers start
+        // over and find the wheel in the built wheel cache instead.
+        let mut builds = self.builds.lock();
+        if builds
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &slot))
+        {
+            builds.remove(key);
+        }
+        drop(builds);
+
+        result.map_err(InFlightBuildError::Failed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::SDistFilename;
+    use pep508_rs::Requirement;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn key(options: &str) -> BuildKey {
+        BuildKey::new(
+            rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(b"sdist"),
+            String::from(options),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_builds() {
+        let builds = InFlightBuilds::default();
+        let dir = tempfile::tempdir().unwrap();
+        let wheel_file = dir.path().join("foo-1.0-py3-none-any.whl");
+        let count = AtomicUsize::new(0);
+        let (count, wheel_file) = (&count, &wheel_file);
+        let build = move || async move {
+            count.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            std::fs::write(wheel_file, b"wheel").unwrap();
+            Ok::<_, String>(wheel_file.clone())
+        };
+
+        // Concurrent callers with the same key share a single build
+        let key = key("cpython");
+        let results = futures::future::join_all((0..3).map(|_| builds.run(&key, build))).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        for result in results {
+            assert_eq!(&result.unwrap(), wheel_file);
+        }
+        assert!(builds.builds.lock().is_empty());
+
+        // Builds with other options are not shared
+        let other = BuildKey::new(*key.sdist_sha256(), String::from("pypy"));
+        let (result, other_result) =
+            futures::future::join(builds.run(&key, build), builds.run(&other, build)).await;
+        assert!(result.is_ok() && other_result.is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_build_failure() {
+        let builds = InFlightBuilds::default();
+        let count = AtomicUsize::new(0);
+        let count = &count;
+        let build = move || async move {
+            count.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Err::<PathBuf, _>(String::from("the build backend failed"))
+        };
+
+        // The failure is shared with the callers that waited for the build
+        let key = key("cpython");
+        let results = futures::future::join_all((0..3).map(|_| builds.run(&key, build))).await;
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(matches!(&results[0], Err(InFlightBuildError::Failed(_))));
+        for result in &results[1..] {
+            assert!(matches!(
+                result,
+                Err(InFlightBuildError::FailedConcurrently(message)) if message == "the build backend failed"
+            ));
+        }
+
+        // A later caller tries again
+        assert!(builds.run(&key, build).await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_build_key_hook_identity() {
+        let hook: BuildRequirementsHook =
+            Arc::new(|_: &SDistFilename, requirements: Vec<Requirement>| requirements);
+        let same_behavior: BuildRequirementsHook =
+            Arc::new(|_: &SDistFilename, requirements: Vec<Requirement>| requirements);
+
+        let with_hook = key("cpython").with_build_requirements_hook(hook.clone());
+        assert_eq!(with_hook, key("cpython").with_build_requirements_hook(hook));
+        assert_ne!(
+            with_hook,
+            key("cpython").with_build_requirements_hook(same_behavior)
+        );
+        assert_ne!(with_hook, key("cpython"));
+        assert!(!with_hook.is_persistent());
+        assert!(key("cpython").is_persistent());
+    }
+}
@@ -1,32 +1,139 @@
 //! Turn an sdist into a wheel by creating a virtualenv and building the sdist in it
+//!
+//! Building sdists requires the `sdist-build` feature, which is enabled by default. Without it
+//! only wheels that were built before and are stored in the [`BuiltWheelCache`] can be used and
+//! all other builds fail with [`WheelBuildError::BuildingDisabled`].
 
+#[cfg(feature = "sdist-build")]
 mod build_environment;
+mod cross_build;
+mod in_flight;
+mod prerequisites;
+mod project;
+#[cfg(feature = "sdist-build")]
+mod stage;
+mod wheel_cache;
 
+use itertools::Itertools;
+#[cfg(feature = "sdist-build")]
 use parking_lot::Mutex;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 use std::sync::Arc;
-use std::{collections::HashMap, path::PathBuf};
+use std::time::Instant;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use pep508_rs::{MarkerEnvironment, Requirement};
 
 use crate::{
-    artifacts::wheel::UnpackError,
-    artifacts::SDist,
+    artifacts::wheel::{UnpackError, WheelValidationFinding},
     artifacts::Wheel,
+    artifacts::{BuildProvenance, SDist, SDistError},
     index::PackageDb,
-    python_env::WheelTags,
+    python_env::{PythonLocation, WheelTags},
+    session_log::{CacheKind, FileOperation, SessionEvent},
     types::Artifact,
+    types::NormalizedPackageName,
+    types::PackageName,
     types::SDistFilename,
+    types::Version,
     types::{WheelCoreMetaDataError, WheelCoreMetadata},
 };
 
-use crate::resolve::{ResolveOptions, SDistResolution};
-use crate::wheel_builder::build_environment::BuildEnvironment;
+#[cfg(feature = "sdist-build")]
+use crate::python_env::VEnvError;
+use crate::resolve::ResolveOptions;
+#[cfg(feature = "sdist-build")]
+use crate::resolve::SDistResolution;
+#[cfg(feature = "sdist-build")]
+pub use build_environment::BuildEnvironment;
+#[cfg(feature = "sdist-build")]
+use build_environment::BuildSource;
+pub use cross_build::{CrossBuildTarget, UnsupportedCrossBuildTarget};
+pub use in_flight::BuildKey;
+pub(crate) use in_flight::{InFlightBuildError, InFlightBuilds};
+pub use prerequisites::{missing_build_prerequisites, BuildPrerequisite};
+#[cfg(feature = "sdist-build")]
+use stage::{BuildTarget, MetadataResult, WheelResult};
+pub use wheel_cache::{BuiltWheelCache, RetentionPolicy};
+
+#[cfg(feature = "sdist-build")]
+type BuildCache<'db> = Mutex<HashMap<(SDistFilename, BuildTarget), Arc<BuildEnvironment<'db>>>>;
+
+/// A callback that is invoked with the build requirements of an sdist before they are resolved
+/// and installed into the build environment. The returned requirements are used instead.
+///
+/// This is an escape hatch for sdists that fail to build with the latest version of their build
+/// dependencies, e.g. to cap `setuptools<72` for a single package. The hook is called both for
+/// the requirements of the `[build-system]` table and again when the build backend reports
+/// additional requirements.
+pub type BuildRequirementsHook =
+    Arc<dyn Fn(&SDistFilename, Vec<Requirement>) -> Vec<Requirement> + Send + Sync>;
+
+/// The `config_settings` that are passed to the hooks of a PEP 517 build backend, like the
+/// `--config-settings` of pip. A setting with a single value is passed as a string, a setting
+/// with multiple values as a list of strings.
+///
+/// For sdists that are built with the legacy setuptools backend the values of `--global-option`
+/// are also passed to `setup.py` when the metadata is generated with `setup.py egg_info`, and
+/// setuptools passes `--global-option` and `--build-option` to `setup.py` when the wheel is built.
+/// This is the only way to configure some legacy packages, e.g. with `--build-option` set to
+/// `--plat-name=manylinux2014_x86_64`.
+pub type ConfigSettings = HashMap<String, Vec<String>>;
+
+/// Describes where a build requirement of an sdist originates from. This helps to diagnose builds
+/// that fail because of a missing or incompatible build requirement.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum BuildRequirementSource {
+    /// The `requires` key of the `[build-system]` table in the `pyproject.toml` of the sdist.
+    PyprojectToml,
+
+    /// The implicit requirements (`setuptools` and `wheel`) of legacy sdists that do not specify
+    /// their build requirements.
+    LegacyDefault,
+
+    /// Reported by the `get_requires_for_build_wheel` hook of the build backend.
+    GetRequiresForBuildWheel,
+
+    /// Reported by the `get_requires_for_build_editable` hook of the build backend.
+    GetRequiresForBuildEditable,
 
-type BuildCache<'db> = Mutex<HashMap<SDistFilename, Arc<BuildEnvironment<'db>>>>;
+    /// Added or modified by the [`BuildRequirementsHook`].
+    BuildRequirementsHook,
+}
+
+impl Display for BuildRequirementSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildRequirementSource::PyprojectToml => write!(f, "pyproject.toml"),
+            BuildRequirementSource::LegacyDefault => write!(f, "legacy default requirements"),
+            BuildRequirementSource::GetRequiresForBuildWheel => {
+                write!(f, "get_requires_for_build_wheel")
+            }
+            BuildRequirementSource::GetRequiresForBuildEditable => {
+                write!(f, "get_requires_for_build_editable")
+            }
+            BuildRequirementSource::BuildRequirementsHook => write!(f, "build requirements hook"),
+        }
+    }
+}
+
+/// Formats build requirements together with where they originate from.
+fn format_build_requirements(requirements: &[(Requirement, BuildRequirementSource)]) -> String {
+    requirements
+        .iter()
+        .map(|(requirement, source)| format!("{requirement} (from {source})"))
+        .join(", ")
+}
 
 /// A builder for wheels
 pub struct WheelBuilder<'db, 'i> {
     /// A cache for virtualenvs that might be reused later in the process
+    #[cfg(feature = "sdist-build")]
     venv_cache: BuildCache<'db>,
 
     /// The package database to use
@@ -41,7 +148,47 @@ pub struct WheelBuilder<'db, 'i> {
     /// The resolve options. Note that we change the sdist resolution to normal if it's set to
     /// only sdists, because otherwise we run into a chicken & egg problem where a sdist is required
     /// to build a sdist. E.g. `hatchling` requires `hatchling` as build system.
+    #[cfg(feature = "sdist-build")]
     resolve_options: ResolveOptions,
+
+    /// An optional callback to modify the build requirements of an sdist
+    build_requirements_hook: Option<BuildRequirementsHook>,
+
+    /// Environment variables to set when building specific packages
+    build_environment_variables: HashMap<NormalizedPackageName, HashMap<String, String>>,
+
+    /// Config settings to pass to the build backend when building specific packages
+    build_config_settings: HashMap<NormalizedPackageName, ConfigSettings>,
+
+    /// The platform wheels are cross built for, if any
+    cross_build_target: Option<CrossBuildTarget>,
+
+    /// The python interpreter that is used to create the build environments
+    python_location: PythonLocation,
+}
+
+/// An exception that was raised by the build backend.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BackendException {
+    /// The name of the type of the exception, e.g. `CalledProcessError`.
+    #[serde(rename = "type")]
+    pub exception_type: String,
+
+    /// The message of the exception.
+    pub message: String,
+
+    /// The formatted traceback of the exception.
+    pub traceback: String,
+}
+
+impl Display for BackendException {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{}", self.exception_type)
+        } else {
+            write!(f, "{}: {}", self.exception_type, self.message)
+        }
+    }
 }
 
 /// An error that can occur while building a wheel
@@ -51,6 +198,16 @@ pub enum WheelBuildError {
     #[error("Could not build wheel: {0}")]
     Error(String),
 
+    /// The build backend failed. If the output indicates that the build failed because
+    /// prerequisites like a compiler are missing, these are listed and hints on how to install them
+    /// are added to the message, see [`missing_build_prerequisites`].
+    #[error("Could not build wheel: {output}{}", prerequisites::format_prerequisite_hints(.missing_prerequisites))]
+    BuildBackendFailed {
+        output: String,
+        exception: Option<BackendException>,
+        missing_prerequisites: Vec<BuildPrerequisite>,
+    },
+
     #[error("Could not install artifact in virtual environment")]
     UnpackError(#[from] UnpackError),
 
@@ -60,25 +217,165 @@ pub enum WheelBuildError {
     #[error("Could not run command {0} to build wheel: {1}")]
     CouldNotRunCommand(String, std::io::Error),
 
-    #[error("Could not resolve environment for wheel building")]
-    CouldNotResolveEnvironment(Vec<Requirement>),
+    #[error(
+        "Could not resolve environment for wheel building with the build requirements: {}",
+        format_build_requirements(.0)
+    )]
+    CouldNotResolveEnvironment(
+        Vec<(Requirement, BuildRequirementSource)>,
+        #[source] Box<dyn std::error::Error + Send + Sync>,
+    ),
 
-    #[error("Error parsing JSON from extra_requirements.json: {0}")]
+    #[error("Could not parse the result of the build frontend: {0}")]
     JSONError(#[from] serde_json::Error),
 
+    #[error("The build frontend wrote an invalid result for {stage}: {reason}")]
+    InvalidFrontendResult { stage: String, reason: String },
+
     #[error("Could not parse generated wheel metadata: {0}")]
     WheelCoreMetadataError(#[from] WheelCoreMetaDataError),
 
     #[error("Could not get artifact")]
     CouldNotGetArtifact,
+
+    #[error("Could not get artifact {0}")]
+    CouldNotDownloadArtifact(String, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Could not read sdist: {0}")]
+    SDistError(#[from] SDistError),
+
+    #[error("Could not create a work directory for the build environment: {0}")]
+    CouldNotCreateWorkDir(#[source] std::io::Error),
+
+    #[cfg(feature = "sdist-build")]
+    #[error("Could not create virtual environment for the build environment")]
+    CouldNotCreateVenv(#[from] VEnvError),
+
+    #[error(
+        "Could not build {0} because building sdists is disabled, enable the `sdist-build` feature"
+    )]
+    BuildingDisabled(SDistFilename),
+
+    #[error("The build backend of {0} does not support editables")]
+    EditablesNotSupported(SDistFilename),
+
+    #[error(
+        "Cannot cross build {sdist} for {target} without a cross toolchain, set {} in the environment or the build environment variables",
+        .missing.join(" and ")
+    )]
+    MissingCrossToolchain {
+        sdist: SDistFilename,
+        target: String,
+        missing: Vec<&'static str>,
+    },
+
+    #[error("The build backend produced {wheel} for {sdist}, which is not built for the cross build target {target}")]
+    CrossBuiltWheelMismatch {
+        sdist: SDistFilename,
+        wheel: String,
+        target: String,
+    },
+
+    #[error("A concurrent build of {sdist} failed: {message}")]
+    ConcurrentBuildFailed {
+        sdist: SDistFilename,
+        message: String,
+    },
+
+    #[error(
+        "Build backend {backend} reported an invalid build requirement '{requirement}': {source}"
+    )]
+    InvalidBuildRequirement {
+        backend: String,
+        requirement: String,
+        #[source]
+        source: pep508_rs::Pep508Error,
+    },
+
+    #[error(
+        "The build backend produced {wheel} for {sdist}, but its {field} '{found}' does not match the sdist ('{expected}')"
+    )]
+    BuiltWheelMismatch {
+        sdist: SDistFilename,
+        wheel: String,
+        field: &'static str,
+        found: String,
+        expected: String,
+    },
+}
+
+impl WheelBuildError {
+    /// Constructs an error from the output of a failed invocation of the build backend.
+    ///
+    /// The results of the backend are communicated through files, so stdout and stderr only
+    /// contain logs. The error message contains stderr, or stdout if the backend did not write
+    /// anything to stderr. Both, and the traceback of `exception`, are used to detect missing
+    /// prerequisites.
+    #[cfg(feature = "sdist-build")]
+    pub(crate) fn from_backend_output(
+        output: &std::process::Output,
+        exception: Option<BackendException>,
+    ) -> Self {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let traceback = exception
+            .as_ref()
+            .map(|exception| exception.traceback.as_str())
+            .unwrap_or_default();
+        let missing_prerequisites =
+            missing_build_prerequisites(&format!("{stdout}\n{stderr}\n{traceback}"));
+        let output = if stderr.trim().is_empty() {
+            stdout
+        } else {
+            stderr
+        };
+        WheelBuildError::BuildBackendFailed {
+            output: output.into_owned(),
+            exception,
+            missing_prerequisites,
+        }
+    }
+}
+
+/// Checks that the filename of a wheel that was built from `sdist` has the same name and version as
+/// the sdist.
+fn check_built_wheel_name(sdist: &SDistFilename, wheel: &str) -> Result<(), WheelBuildError> {
+    let mismatch = |field, found: &str, expected: String| WheelBuildError::BuiltWheelMismatch {
+        sdist: sdist.clone(),
+        wheel: wheel.to_owned(),
+        field,
+        found: found.to_owned(),
+        expected,
+    };
+
+    // The name and version of a wheel never contain dashes, see
+    // <https://packaging.python.org/en/latest/specifications/binary-distribution-format/#escaping-and-unicode>
+    let mut parts = wheel.strip_suffix(".whl").unwrap_or(wheel).split('-');
+    let (name, version) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+
+    let expected_name = sdist.distribution.as_source_str().to_owned();
+    match PackageName::from_str(name) {
+        Ok(name) if name == sdist.distribution => {}
+        _ => return Err(mismatch("name", name, expected_name)),
+    }
+    match Version::from_str(version) {
+        Ok(version) if version == sdist.version => Ok(()),
+        _ => Err(mismatch("version", version, sdist.version.to_string())),
+    }
 }
 
 /// Get the requirements for the build system from the pyproject.toml
 /// will use a default if there are no requirements specified
-fn build_requirements(build_system: &pyproject_toml::BuildSystem) -> Vec<Requirement> {
+#[cfg(feature = "sdist-build")]
+fn build_requirements(
+    build_system: &pyproject_toml::BuildSystem,
+) -> (Vec<Requirement>, BuildRequirementSource) {
     const DEFAULT_REQUIREMENTS: &[&str; 2] = &["setuptools", "wheel"];
     if build_system.requires.is_empty() {
-        DEFAULT_REQUIREMENTS
+        let requirements = DEFAULT_REQUIREMENTS
             .iter()
             .map(|r| Requirement {
                 name: r.to_string(),
@@ -86,9 +383,13 @@ fn build_requirements(build_system: &pyproject_toml::BuildSystem) -> Vec<Require
                 version_or_url: None,
                 marker: None,
             })
-            .collect()
+            .collect();
+        (requirements, BuildRequirementSource::LegacyDefault)
     } else {
-        build_system.requires.clone()
+        (
+            build_system.requires.clone(),
+            BuildRequirementSource::PyprojectToml,
+        )
     }
 }
 
@@ -99,8 +400,14 @@ impl<'db, 'i> WheelBuilder<'db, 'i> {
         package_db: &'db PackageDb,
         env_markers: &'i MarkerEnvironment,
         wheel_tags: Option<&'i WheelTags>,
-        _resolve_options: &'i ResolveOptions,
+        resolve_options: &'i ResolveOptions,
     ) -> Self {
+        let build_requirements_hook = resolve_options.build_requirements_hook.clone();
+        let build_environment_variables = resolve_options.build_environment_variables.clone();
+        let build_config_settings = resolve_options.build_config_settings.clone();
+        let cross_build_target = resolve_options.cross_build_target.clone();
+        let python_location = resolve_options.python_location.clone();
+
         // TODO: add this back later when we have a wheel cache
         // We are running into a chicken & egg problem if we want to build wheels for packages that
         // require their build system as sdist as well. For example, `hatchling` requires `hatchling` as
@@ -114,26 +421,162 @@ impl<'db, 'i> WheelBuilder<'db, 'i> {
         // } else {
         //     resolve_options.clone()
         // };
+        #[cfg(feature = "sdist-build")]
         let resolve_options = ResolveOptions {
             sdist_resolution: SDistResolution::OnlyWheels,
+            ..Default::default()
         };
 
         Self {
+            #[cfg(feature = "sdist-build")]
             venv_cache: Mutex::new(HashMap::new()),
             package_db,
             env_markers,
             wheel_tags,
+            #[cfg(feature = "sdist-build")]
             resolve_options,
+            build_requirements_hook,
+            build_environment_variables,
+            build_config_settings,
+            cross_build_target,
+            python_location,
+        }
+    }
+
+    /// Sets environment variables that are only set when building sdists of the given package,
+    /// e.g. `CFLAGS` or `CMAKE_ARGS`. Variables that were set before for the same package are
+    /// replaced.
+    #[must_use]
+    pub fn with_build_environment_variables(
+        mut self,
+        package: NormalizedPackageName,
+        variables: HashMap<String, String>,
+    ) -> Self {
+        self.build_environment_variables.insert(package, variables);
+        self
+    }
+
+    /// Sets the config settings that are passed to the build backend when building sdists of the
+    /// given package, see [`ConfigSettings`]. Settings that were set before for the same package
+    /// are replaced.
+    #[must_use]
+    pub fn with_config_settings(
+        mut self,
+        package: NormalizedPackageName,
+        config_settings: ConfigSettings,
+    ) -> Self {
+        self.build_config_settings.insert(package, config_settings);
+        self
+    }
+
+    /// Cross builds wheels for another platform by overriding the platform the build backends
+    /// see, see [`CrossBuildTarget`]. The compatible tags should contain the tags of the target
+    /// platform. Builds for linux and windows targets fail unless a cross toolchain is configured.
+    #[must_use]
+    pub fn with_cross_build_target(self, target: CrossBuildTarget) -> Self {
+        Self {
+            cross_build_target: Some(target),
+            ..self
+        }
+    }
+
+    /// Returns the environment variables that are set when building sdists of the given package:
+    /// the variables of the [`CrossBuildTarget`], if any, overridden by the variables configured
+    /// for the package.
+    fn environment_variables(&self, name: &NormalizedPackageName) -> HashMap<String, String> {
+        let mut variables = self
+            .cross_build_target
+            .as_ref()
+            .map(CrossBuildTarget::environment_variables)
+            .unwrap_or_default();
+        if let Some(package_variables) = self.build_environment_variables.get(name) {
+            variables.extend(package_variables.clone());
+        }
+        variables
+    }
+
+    /// Returns the options `wheel_file`, a wheel of the given package returned by
+    /// [`Self::build_wheel`], was built with, for its [`crate::artifacts::InstallProvenance`].
+    /// Cached wheels might have been built by another builder, their options are read from the
+    /// [`BuiltWheelCache`]. Other wheels were built by this builder.
+    pub fn build_provenance(
+        &self,
+        name: &NormalizedPackageName,
+        wheel_file: &Path,
+    ) -> BuildProvenance {
+        let built_wheels = self.package_db.built_wheels();
+        if !wheel_file.starts_with(built_wheels.root()) {
+            return self.current_build_provenance(name);
+        }
+        built_wheels
+            .build_provenance(wheel_file)
+            .unwrap_or_else(|| {
+                tracing::debug!(
+                    "the build options of {} were not recorded",
+                    wheel_file.display()
+                );
+                BuildProvenance::default()
+            })
+    }
+
+    /// Returns the options that sdists of the given package are built with by this builder.
+    fn current_build_provenance(&self, name: &NormalizedPackageName) -> BuildProvenance {
+        BuildProvenance {
+            python: match &self.python_location {
+                PythonLocation::System => None,
+                PythonLocation::Custom(path) => Some(path.display().to_string()),
+            },
+            environment_variables: self
+                .environment_variables(name)
+                .into_keys()
+                .sorted()
+                .collect(),
+            config_settings: self
+                .build_config_settings
+                .get(name)
+                .map(|settings| settings.clone().into_iter().collect())
+                .unwrap_or_default(),
+            cross_build_target: self
+                .cross_build_target
+                .as_ref()
+                .map(|target| target.platform_tag().to_string()),
+        }
+    }
+
+    /// Sets a callback to inspect and override the build requirements of the sdists that are
+    /// built. See [`BuildRequirementsHook`].
+    #[must_use]
+    pub fn with_build_requirements_hook(self, hook: BuildRequirementsHook) -> Self {
+        Self {
+            build_requirements_hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Sets the python interpreter that is used to build sdists. This should be the interpreter of
+    /// the environment the wheels are built for, which might not be the system interpreter, e.g.
+    /// when the environment is a virtual environment based on another python installation.
+    #[must_use]
+    pub fn with_python_location(self, python_location: PythonLocation) -> Self {
+        Self {
+            python_location,
+            ..self
         }
     }
 
     /// Get a prepared virtualenv for building a wheel (or extracting metadata) from an `[SDist]`
     /// This function also caches the virtualenvs, so that they can be reused later.
+    ///
+    /// Editables can have different build requirements than wheels, so they get their own
+    /// environment.
+    #[cfg(feature = "sdist-build")]
     async fn setup_build_venv(
         &self,
         sdist: &SDist,
-    ) -> Result<Arc<BuildEnvironment>, WheelBuildError> {
-        if let Some(venv) = self.venv_cache.lock().get(sdist.name()) {
+        target: BuildTarget,
+    ) -> Result<Arc<BuildEnvironment<'db>>, WheelBuildError> {
+        let key = (sdist.name().clone(), target);
+        if let Some(venv) = self.venv_cache.lock().get(&key) {
             tracing::debug!(
                 "using cached virtual env for: {:?}",
                 sdist.name().distribution.as_source_str()
@@ -147,41 +590,87 @@ impl<'db, 'i> WheelBuilder<'db, 'i> {
             sdist.name().distribution.as_source_str()
         );
 
-        let build_environment = BuildEnvironment::setup(
-            sdist,
+        let name = NormalizedPackageName::from(sdist.name().distribution.clone());
+        let mut build_environment = BuildEnvironment::setup(
+            BuildSource::SDist(sdist),
             self.package_db,
             self.env_markers,
             self.wheel_tags,
             &self.resolve_options,
+            &self.python_location,
+            self.build_requirements_hook.as_ref(),
+            self.environment_variables(&name),
+            self.build_config_settings
+                .get(&name)
+                .cloned()
+                .unwrap_or_default(),
         )
         .await?;
 
-        build_environment.install_build_files(sdist)?;
+        build_environment.install_build_files(BuildSource::SDist(sdist))?;
 
         // Install extra requirements if any
         build_environment
             .install_extra_requirements(
+                target,
+                sdist.name(),
                 self.package_db,
                 self.env_markers,
                 self.wheel_tags,
                 &self.resolve_options,
+                self.build_requirements_hook.as_ref(),
             )
             .await?;
 
         // Insert into the venv cache
         self.venv_cache
             .lock()
-            .insert(sdist.name().clone(), Arc::new(build_environment));
+            .insert(key.clone(), Arc::new(build_environment));
 
         // Return the cached values
         return self
             .venv_cache
             .lock()
-            .get(sdist.name())
+            .get(&key)
             .cloned()
             .ok_or_else(|| WheelBuildError::Error("Could not get venv from cache".to_string()));
     }
 
+    /// Returns the environment that is used to build the given sdist, setting it up if it does
+    /// not exist yet. The environment can be used to run additional commands, e.g. tests or stub
+    /// generation, against the extracted sdist.
+    ///
+    /// The environment is shared with this builder and stays alive as long as the builder or a
+    /// returned handle exists. Use [`Self::take_build_environment`] to control its lifetime
+    /// explicitly.
+    #[cfg(feature = "sdist-build")]
+    pub async fn build_environment(
+        &self,
+        sdist: &SDist,
+    ) -> Result<Arc<BuildEnvironment<'db>>, WheelBuildError> {
+        self.setup_build_venv(sdist, BuildTarget::Wheel).await
+    }
+
+    /// Removes the build environment of the given sdist from this builder and returns it. This
+    /// allows the caller to keep the environment on disk with [`BuildEnvironment::persist`] or to
+    /// remove it with [`BuildEnvironment::close`].
+    ///
+    /// Returns `None` if there is no environment for the sdist or if a handle returned by
+    /// [`Self::build_environment`] is still alive.
+    #[cfg(feature = "sdist-build")]
+    pub fn take_build_environment(&self, sdist: &SDistFilename) -> Option<BuildEnvironment<'db>> {
+        let key = (sdist.clone(), BuildTarget::Wheel);
+        let mut venv_cache = self.venv_cache.lock();
+        let build_environment = venv_cache.remove(&key)?;
+        match Arc::try_unwrap(build_environment) {
+            Ok(build_environment) => Some(build_environment),
+            Err(build_environment) => {
+                venv_cache.insert(key, build_environment);
+                None
+            }
+        }
+    }
+
     /// Get the metadata for a given sdist by using the build_backend in a virtual env
     /// This function uses the `prepare_metadata_for_build_wheel` entry point of the build backend.
     #[tracing::instrument(skip_all, fields(name = %sdist.name().distribution.as_source_str(), version = %sdist.name().version))]
@@ -189,57 +678,518 @@ impl<'db, 'i> WheelBuilder<'db, 'i> {
         &self,
         sdist: &SDist,
     ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
-        let build_environment = self.setup_build_venv(sdist).await?;
-
-        let output = build_environment.run_command("WheelMetadata")?;
-
-        if !output.status.success() {
-            if output.status.code() == Some(50) {
-                tracing::warn!("SDist build backend does not support metadata generation");
-                // build wheel instead
-                let wheel_file = self.build_wheel(sdist).await?;
-                let wheel =
-                    Wheel::from_path(&wheel_file, &sdist.name().distribution.clone().into())
-                        .map_err(|e| {
-                            WheelBuildError::Error(format!(
-                                "Could not build wheel for metadata extraction: {}",
-                                e
-                            ))
-                        })?;
-
-                return wheel.metadata().map_err(|e| {
-                    WheelBuildError::Error(format!("Could not parse wheel metadata: {}", e))
-                });
-            }
-            let stdout = String::from_utf8_lossy(&output.stderr);
-            return Err(WheelBuildError::Error(stdout.to_string()));
+        // Read the metadata from a wheel we built before with the same options if there is one
+        let key = self.build_key(sdist)?;
+        if let Some(wheel_file) = self.find_built_wheel(sdist, &key) {
+            tracing::debug!(
+                "reading metadata from cached wheel {}",
+                wheel_file.display()
+            );
+            return self.read_wheel_metadata(sdist, &wheel_file);
         }
 
-        let result = std::fs::read_to_string(build_environment.work_dir().join("metadata_result"))?;
-        let folder = PathBuf::from(result.trim());
-        let path = folder.join("METADATA");
+        self.prepare_metadata(sdist).await
+    }
+
+    /// Runs the `prepare_metadata_for_build_wheel` entry point of the build backend. If the
+    /// backend does not support it the metadata is generated with `setup.py egg_info` for legacy
+    /// sdists, or by building the wheel.
+    #[cfg(feature = "sdist-build")]
+    async fn prepare_metadata(
+        &self,
+        sdist: &SDist,
+    ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
+        let build_environment = self.setup_build_venv(sdist, BuildTarget::Wheel).await?;
+
+        let Some(result) =
+            build_environment.run_stage::<MetadataResult>(BuildTarget::Wheel.metadata_stage())?
+        else {
+            tracing::warn!("SDist build backend does not support metadata generation");
+
+            // Legacy sdists can generate their metadata with `setup.py egg_info`, which is a
+            // lot cheaper than building the wheel.
+            match build_environment.egg_info_metadata() {
+                Ok(Some(metadata)) => return Ok(metadata),
+                Ok(None) => {}
+                Err(err) => tracing::warn!("failed to run `setup.py egg_info`: {err}"),
+            }
+
+            // build wheel instead
+            let wheel_file = self.build_wheel(sdist).await?;
+            return self.read_wheel_metadata(sdist, &wheel_file);
+        };
+
+        let path = result.metadata_dir.join("METADATA");
 
         let metadata = std::fs::read(path)?;
         let wheel_metadata = WheelCoreMetadata::try_from(metadata.as_slice())?;
         Ok((metadata, wheel_metadata))
     }
 
+    #[cfg(not(feature = "sdist-build"))]
+    async fn prepare_metadata(
+        &self,
+        sdist: &SDist,
+    ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
+        Err(WheelBuildError::BuildingDisabled(sdist.name().clone()))
+    }
+
+    /// Get the metadata of the editable wheel of an sdist, see
+    /// [PEP 660](https://peps.python.org/pep-0660/). This uses the
+    /// `prepare_metadata_for_build_editable` entry point of the build backend, which is much cheaper
+    /// than building the editable. The editable is only built if the backend does not support it.
+    ///
+    /// Fails with [`WheelBuildError::EditablesNotSupported`] if the build backend cannot build
+    /// editables at all.
+    #[cfg(feature = "sdist-build")]
+    #[tracing::instrument(skip_all, fields(name = %sdist.name().distribution.as_source_str(), version = %sdist.name().version))]
+    pub async fn get_sdist_editable_metadata(
+        &self,
+        sdist: &SDist,
+    ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
+        let target = BuildTarget::Editable;
+        let build_environment = self.setup_build_venv(sdist, target).await?;
+
+        if let Some(result) =
+            build_environment.run_stage::<MetadataResult>(target.metadata_stage())?
+        {
+            let metadata = std::fs::read(result.metadata_dir.join("METADATA"))?;
+            let wheel_metadata = WheelCoreMetadata::try_from(metadata.as_slice())?;
+            return Ok((metadata, wheel_metadata));
+        }
+
+        tracing::debug!("SDist build backend does not support editable metadata generation");
+        match build_environment.run_stage::<WheelResult>(target.build_stage())? {
+            Some(result) => self.read_wheel_metadata(sdist, &result.wheel),
+            None => Err(WheelBuildError::EditablesNotSupported(sdist.name().clone())),
+        }
+    }
+
+    /// Get the metadata of the editable wheel of an sdist. Always fails because building sdists
+    /// is disabled.
+    #[cfg(not(feature = "sdist-build"))]
+    pub async fn get_sdist_editable_metadata(
+        &self,
+        sdist: &SDist,
+    ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
+        Err(WheelBuildError::BuildingDisabled(sdist.name().clone()))
+    }
+
+    /// Builds an editable wheel of the project in `project_dir`, see
+    /// [PEP 660](https://peps.python.org/pep-0660/), and copies it to `wheel_dir`. Returns the
+    /// path of the copied wheel.
+    ///
+    /// Unlike sdists the project is not copied, the build backend runs in `project_dir` itself so
+    /// the installed editable refers to the sources in that directory. Editables are never stored
+    /// in the [`BuiltWheelCache`] because the project can change at any time.
+    ///
+    /// Fails with [`WheelBuildError::EditablesNotSupported`] if the build backend cannot build
+    /// editables.
+    #[cfg(feature = "sdist-build")]
+    #[tracing::instrument(skip_all, fields(project_dir = %project_dir.display()))]
+    pub async fn build_editable(
+        &self,
+        project_dir: &Path,
+        wheel_dir: &Path,
+    ) -> Result<PathBuf, WheelBuildError> {
+        let project = project::ProjectDir::read(project_dir)?;
+        let source = BuildSource::Project(&project);
+        let name = NormalizedPackageName::from(project.name.distribution.clone());
+        let target = BuildTarget::Editable;
+
+        let mut build_environment = BuildEnvironment::setup(
+            source,
+            self.package_db,
+            self.env_markers,
+            self.wheel_tags,
+            &self.resolve_options,
+            &self.python_location,
+            self.build_requirements_hook.as_ref(),
+            self.environment_variables(&name),
+            self.build_config_settings
+                .get(&name)
+                .cloned()
+                .unwrap_or_default(),
+        )
+        .await?;
+        build_environment.install_build_files(source)?;
+        build_environment
+            .install_extra_requirements(
+                target,
+                &project.name,
+                self.package_db,
+                self.env_markers,
+                self.wheel_tags,
+                &self.resolve_options,
+                self.build_requirements_hook.as_ref(),
+            )
+            .await?;
+
+        let result = build_environment
+            .run_stage::<WheelResult>(target.build_stage())?
+            .ok_or_else(|| WheelBuildError::EditablesNotSupported(project.name.clone()))?;
+
+        // Copy the wheel out of the work directory before the build environment removes it
+        std::fs::create_dir_all(wheel_dir)?;
+        let wheel_file = wheel_cache::copy_wheel(&result.wheel, wheel_dir)?;
+        self.log_file_operation(FileOperation::Copy, &wheel_file);
+        Ok(wheel_file)
+    }
+
+    /// Builds an editable wheel of the project in `project_dir`. Always fails because building
+    /// is disabled.
+    #[cfg(not(feature = "sdist-build"))]
+    pub async fn build_editable(
+        &self,
+        project_dir: &Path,
+        _wheel_dir: &Path,
+    ) -> Result<PathBuf, WheelBuildError> {
+        let project = project::ProjectDir::read(project_dir)?;
+        Err(WheelBuildError::BuildingDisabled(project.name))
+    }
+
     /// Build a wheel from an sdist by using the build_backend in a virtual env.
     /// This function uses the `build_wheel` entry point of the build backend.
+    ///
+    /// Built wheels are stored in the [`BuiltWheelCache`] of the package database and the
+    /// returned path points into that cache. If a compatible wheel was built before from the same
+    /// sdist with the same options, see [`BuildKey`], it is returned without building the sdist
+    /// again. If another builder of the same package database is building the same sdist with the
+    /// same options, this waits for that build and returns its wheel.
     #[tracing::instrument(skip_all, fields(name = %sdist.name().distribution.as_source_str(), version = %sdist.name().version))]
     pub async fn build_wheel(&self, sdist: &SDist) -> Result<PathBuf, WheelBuildError> {
-        let build_environment = self.setup_build_venv(sdist).await?;
+        let key = self.build_key(sdist)?;
 
-        let output = build_environment.run_command("Wheel")?;
+        // The cache is checked by the caller that runs the build, so a build of the same sdist
+        // that finishes in the meantime is found there.
+        let result = self
+            .package_db
+            .in_flight_builds()
+            .run(&key, || async {
+                match self.find_built_wheel(sdist, &key) {
+                    Some(wheel_file) => Ok(wheel_file),
+                    None => self.build_wheel_uncached(sdist, &key).await,
+                }
+            })
+            .await;
+        match result {
+            Ok(wheel_file) => Ok(wheel_file),
+            Err(InFlightBuildError::Failed(err)) => Err(err),
+            Err(InFlightBuildError::FailedConcurrently(message)) => {
+                Err(WheelBuildError::ConcurrentBuildFailed {
+                    sdist: sdist.name().clone(),
+                    message,
+                })
+            }
+        }
+    }
 
-        if !output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stderr);
-            return Err(WheelBuildError::Error(stdout.to_string()));
+    /// Returns a wheel from the [`BuiltWheelCache`] that was built before from `sdist` with the
+    /// given key. Wheels of builds with a [`BuildRequirementsHook`] are never cached.
+    fn find_built_wheel(&self, sdist: &SDist, key: &BuildKey) -> Option<PathBuf> {
+        if !key.is_persistent() {
+            return None;
+        }
+        let wheel_file = self
+            .package_db
+            .built_wheels()
+            .find(sdist.name(), key, self.wheel_tags)?;
+        tracing::debug!("using cached wheel {}", wheel_file.display());
+        if let Some(session_log) = self.package_db.session_log() {
+            session_log.record(SessionEvent::CacheHit {
+                cache: CacheKind::BuiltWheels,
+                key: wheel_file.display().to_string(),
+            });
         }
+        Some(wheel_file)
+    }
 
-        let result = std::fs::read_to_string(build_environment.work_dir().join("wheel_result"))?;
-        let wheel_file = PathBuf::from(result.trim());
+    /// Describes the sdist and the options that influence the wheel that is built from it, see
+    /// [`BuildKey`].
+    fn build_key(&self, sdist: &SDist) -> Result<BuildKey, WheelBuildError> {
+        let name = NormalizedPackageName::from(sdist.name().distribution.clone());
+        let environment_variables = self.environment_variables(&name);
+        let environment_variables = environment_variables.iter().sorted().collect::<Vec<_>>();
+        let config_settings = self
+            .build_config_settings
+            .get(&name)
+            .map(|settings| settings.iter().sorted().collect::<Vec<_>>());
+        let wheel_tags = self
+            .wheel_tags
+            .map(|tags| tags.tags().map(ToString::to_string).join(","));
+        let cross_build_target = self
+            .cross_build_target
+            .as_ref()
+            .map(|target| target.platform_tag().to_string());
+        let key = BuildKey::new(
+            sdist.sha256()?,
+            format!(
+                "{:?}|{:?}|{:?}|{:?}|{:?}|{:?}",
+                self.env_markers,
+                wheel_tags,
+                self.python_location,
+                environment_variables,
+                config_settings,
+                cross_build_target,
+            ),
+        );
+        Ok(match &self.build_requirements_hook {
+            Some(hook) => key.with_build_requirements_hook(hook.clone()),
+            None => key,
+        })
+    }
+
+    /// Builds the wheel without looking at the built wheel cache or concurrent builds, see
+    /// [`Self::build_wheel`].
+    async fn build_wheel_uncached(
+        &self,
+        sdist: &SDist,
+        key: &BuildKey,
+    ) -> Result<PathBuf, WheelBuildError> {
+        // Without a cross toolchain the compiler would silently build for the host
+        if let Some(target) = &self.cross_build_target {
+            let name = NormalizedPackageName::from(sdist.name().distribution.clone());
+            let missing = target.missing_toolchain(&self.environment_variables(&name));
+            if !missing.is_empty() {
+                return Err(WheelBuildError::MissingCrossToolchain {
+                    sdist: sdist.name().clone(),
+                    target: target.to_string(),
+                    missing,
+                });
+            }
+        }
+
+        let built_wheels = self.package_db.built_wheels();
+        let session_log = self.package_db.session_log();
+        let start = Instant::now();
+        let result = self.run_build_backend(sdist).await;
+        if let Some(session_log) = session_log {
+            session_log.record(SessionEvent::Build {
+                sdist: sdist.name().to_string(),
+                wheel: result.as_ref().ok().cloned(),
+                duration_ms: start.elapsed().as_millis() as u64,
+                error: result.as_ref().err().map(ToString::to_string),
+            });
+        }
+        let wheel_file = result?;
+
+        // Backends occasionally produce wheels with a different version (e.g. `0.0.0`) than the
+        // sdist, never accept those. Other problems with the wheel are only reported.
+        let wheel_name = wheel_file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        check_built_wheel_name(sdist.name(), &wheel_name)?;
+        if let Some(target) = &self.cross_build_target {
+            let platform_tags = wheel_name
+                .strip_suffix(".whl")
+                .and_then(|stem| stem.rsplit('-').next())
+                .unwrap_or_default();
+            if !target.is_built_for(platform_tags) {
+                return Err(WheelBuildError::CrossBuiltWheelMismatch {
+                    sdist: sdist.name().clone(),
+                    wheel: wheel_name,
+                    target: target.to_string(),
+                });
+            }
+        }
+        match Wheel::from_path(&wheel_file, &sdist.name().distribution.clone().into()) {
+            Ok(wheel) => {
+                for finding in wheel.validate() {
+                    match finding {
+                        WheelValidationFinding::NameMismatch { metadata, .. } => {
+                            return Err(WheelBuildError::BuiltWheelMismatch {
+                                sdist: sdist.name().clone(),
+                                wheel: wheel_name,
+                                field: "name in METADATA",
+                                found: metadata,
+                                expected: sdist.name().distribution.as_source_str().to_owned(),
+                            });
+                        }
+                        WheelValidationFinding::VersionMismatch { metadata, .. } => {
+                            return Err(WheelBuildError::BuiltWheelMismatch {
+                                sdist: sdist.name().clone(),
+                                wheel: wheel_name,
+                                field: "version in METADATA",
+                                found: metadata.to_string(),
+                                expected: sdist.name().version.to_string(),
+                            });
+                        }
+                        finding => tracing::warn!("{}: {finding}", wheel_file.display()),
+                    }
+                }
+            }
+            Err(err) => tracing::warn!("failed to validate {}: {err}", wheel_file.display()),
+        }
+
+        // Wheels built with a hook cannot be reused by other processes, they stay in the build
+        // environment which lives as long as this builder.
+        if !key.is_persistent() {
+            return Ok(wheel_file);
+        }
+
+        // Move the wheel out of the temporary build directory so it outlives the build environment
+        let name = NormalizedPackageName::from(sdist.name().distribution.clone());
+        let build = self.current_build_provenance(&name);
+        let wheel_file = match built_wheels.store(sdist.name(), key, &wheel_file, &build) {
+            Ok(cached_wheel_file) => cached_wheel_file,
+            Err(err) => {
+                tracing::warn!(
+                    "failed to store {} in the built wheel cache: {err}",
+                    wheel_file.display()
+                );
+                return Ok(wheel_file);
+            }
+        };
+        self.log_file_operation(FileOperation::Copy, &wheel_file);
+
+        match built_wheels.prune() {
+            Ok(removed) => {
+                for path in removed {
+                    self.log_file_operation(FileOperation::Remove, &path);
+                }
+            }
+            Err(err) => tracing::warn!("failed to prune the built wheel cache: {err}"),
+        }
 
         Ok(wheel_file)
     }
+
+    /// Runs the `build_wheel` entry point of the build backend and returns the path of the wheel
+    /// in the build environment.
+    #[cfg(feature = "sdist-build")]
+    async fn run_build_backend(&self, sdist: &SDist) -> Result<PathBuf, WheelBuildError> {
+        let build_environment = self.setup_build_venv(sdist, BuildTarget::Wheel).await?;
+
+        let result = build_environment
+            .run_stage::<WheelResult>(BuildTarget::Wheel.build_stage())?
+            .ok_or_else(|| WheelBuildError::InvalidFrontendResult {
+                stage: BuildTarget::Wheel.build_stage().to_string(),
+                reason: "building wheels is not supported".into(),
+            })?;
+        Ok(result.wheel)
+    }
+
+    #[cfg(not(feature = "sdist-build"))]
+    async fn run_build_backend(&self, sdist: &SDist) -> Result<PathBuf, WheelBuildError> {
+        Err(WheelBuildError::BuildingDisabled(sdist.name().clone()))
+    }
+
+    /// Records a file operation in the session log of the package database if there is one.
+    fn log_file_operation(&self, operation: FileOperation, path: &Path) {
+        if let Some(session_log) = self.package_db.session_log() {
+            session_log.record(SessionEvent::FileOperation {
+                operation,
+                path: path.to_path_buf(),
+            });
+        }
+    }
+
+    /// Builds a wheel from an sdist like [`Self::build_wheel`] and copies it into `wheel_dir`.
+    /// The wheel keeps the filename the build backend gave it, e.g.
+    /// `rich-13.6.0-py3-none-any.whl`, which makes the directory usable as a local index or to
+    /// vendor the built wheels. An existing wheel with the same name is replaced.
+    ///
+    /// Returns the path of the wheel in `wheel_dir`.
+    pub async fn build_wheel_into(
+        &self,
+        sdist: &SDist,
+        wheel_dir: &Path,
+    ) -> Result<PathBuf, WheelBuildError> {
+        let wheel_file = self.build_wheel(sdist).await?;
+        std::fs::create_dir_all(wheel_dir)?;
+        let destination = wheel_cache::copy_wheel(&wheel_file, wheel_dir)?;
+        self.log_file_operation(FileOperation::Copy, &destination);
+        Ok(destination)
+    }
+
+    /// Reads the metadata from a wheel that was built from `sdist`.
+    fn read_wheel_metadata(
+        &self,
+        sdist: &SDist,
+        wheel_file: &Path,
+    ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
+        let wheel = Wheel::from_path(wheel_file, &sdist.name().distribution.clone().into())
+            .map_err(|e| {
+                WheelBuildError::Error(format!(
+                    "Could not build wheel for metadata extraction: {}",
+                    e
+                ))
+            })?;
+
+        wheel
+            .metadata()
+            .map_err(|e| WheelBuildError::Error(format!("Could not parse wheel metadata: {}", e)))
+    }
+}
+
+#[cfg(all(test, feature = "sdist-build"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_from_backend_output() {
+        let output = |stdout: &str, stderr: &str| std::process::Output {
+            status: Default::default(),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+
+        // stderr is preferred over the logs the backend printed to stdout
+        let error = WheelBuildError::from_backend_output(&output("running build", "failed"), None);
+        assert!(
+            matches!(error, WheelBuildError::BuildBackendFailed { output, .. } if output == "failed")
+        );
+
+        // stdout is used if the backend didn't write anything to stderr
+        let error = WheelBuildError::from_backend_output(&output("failed", ""), None);
+        assert!(
+            matches!(error, WheelBuildError::BuildBackendFailed { output, .. } if output == "failed")
+        );
+    }
+
+    #[test]
+    fn test_build_requirement_sources() {
+        let mut build_system = pyproject_toml::BuildSystem {
+            requires: Vec::new(),
+            build_backend: None,
+            backend_path: None,
+        };
+        let (requirements, source) = build_requirements(&build_system);
+        assert_eq!(source, BuildRequirementSource::LegacyDefault);
+        assert_eq!(requirements.len(), 2);
+
+        build_system.requires = vec![Requirement::from_str("hatchling>=1.8").unwrap()];
+        let (requirements, source) = build_requirements(&build_system);
+        assert_eq!(source, BuildRequirementSource::PyprojectToml);
+
+        let requirements = requirements
+            .into_iter()
+            .map(|requirement| (requirement, source))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            format_build_requirements(&requirements),
+            "hatchling>=1.8 (from pyproject.toml)"
+        );
+    }
+
+    #[test]
+    fn test_check_built_wheel_name() {
+        let sdist =
+            SDistFilename::from_filename("My.Package-1.2.3.tar.gz", &"my-package".parse().unwrap())
+                .unwrap();
+        check_built_wheel_name(&sdist, "my_package-1.2.3-py3-none-any.whl").unwrap();
+
+        let err = check_built_wheel_name(&sdist, "my_package-0.0.0-py3-none-any.whl").unwrap_err();
+        assert!(
+            matches!(&err, WheelBuildError::BuiltWheelMismatch { field: "version", found, .. } if found == "0.0.0"),
+            "{err}"
+        );
+
+        let err = check_built_wheel_name(&sdist, "other-1.2.3-py3-none-any.whl").unwrap_err();
+        assert!(matches!(
+            err,
+            WheelBuildError::BuiltWheelMismatch { field: "name", .. }
+        ));
+    }
 }
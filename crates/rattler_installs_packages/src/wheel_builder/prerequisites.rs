@@ -0,0 +1,229 @@
+//! Detects system prerequisites that are commonly missing when building sdists with native code,
+//! like a C compiler or the python headers, so a failed build can tell the user how to fix it.
+
+use std::fmt::{Display, Formatter};
+
+/// A tool or library outside of the python environment that building an sdist might require.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuildPrerequisite {
+    /// A C compiler, e.g. `gcc`, `clang` or MSVC.
+    CCompiler,
+
+    /// A C++ compiler, e.g. `g++`, `clang++` or MSVC.
+    CxxCompiler,
+
+    /// The header files of python (`Python.h`).
+    PythonHeaders,
+
+    /// The rust toolchain (`cargo` and `rustc`).
+    Rust,
+
+    /// CMake
+    CMake,
+}
+
+impl BuildPrerequisite {
+    /// All prerequisites that are detected.
+    const ALL: [BuildPrerequisite; 5] = [
+        BuildPrerequisite::CCompiler,
+        BuildPrerequisite::CxxCompiler,
+        BuildPrerequisite::PythonHeaders,
+        BuildPrerequisite::Rust,
+        BuildPrerequisite::CMake,
+    ];
+
+    /// Returns a short instruction on how to install the prerequisite on the current platform.
+    pub fn install_hint(&self) -> &'static str {
+        match self {
+            BuildPrerequisite::CCompiler | BuildPrerequisite::CxxCompiler
+                if cfg!(target_os = "macos") =>
+            {
+                "install the Xcode command line tools with `xcode-select --install`"
+            }
+            BuildPrerequisite::CCompiler | BuildPrerequisite::CxxCompiler if cfg!(windows) => {
+                "install the Microsoft C++ Build Tools from https://visualstudio.microsoft.com/visual-cpp-build-tools/"
+            }
+            BuildPrerequisite::CCompiler => {
+                "install gcc, e.g. with `apt install build-essential` or `dnf install gcc`"
+            }
+            BuildPrerequisite::CxxCompiler => {
+                "install g++, e.g. with `apt install build-essential` or `dnf install gcc-c++`"
+            }
+            BuildPrerequisite::PythonHeaders if cfg!(target_os = "linux") => {
+                "install the development package of python, e.g. with `apt install python3-dev` or `dnf install python3-devel`"
+            }
+            BuildPrerequisite::PythonHeaders => {
+                "use a python installation that includes its header files"
+            }
+            BuildPrerequisite::Rust => "install the rust toolchain from https://rustup.rs",
+            BuildPrerequisite::CMake => {
+                "install cmake from https://cmake.org/download/ or with your system package manager"
+            }
+        }
+    }
+
+    /// Lowercase fragments of build output that indicate that the prerequisite is missing.
+    fn symptoms(&self) -> &'static [&'static str] {
+        match self {
+            BuildPrerequisite::CCompiler => &[
+                "unable to execute 'gcc'",
+                "unable to execute 'cc'",
+                "gcc' failed: no such file or directory",
+                "command 'cc' failed: no such file or directory",
+                "gcc: not found",
+                "gcc: command not found",
+                "cc: command not found",
+                "c compiler cannot create executables",
+                "no cmake_c_compiler could be found",
+                "microsoft visual c++ 14.0 or greater is required",
+            ],
+            BuildPrerequisite::CxxCompiler => &[
+                "unable to execute 'g++'",
+                "unable to execute 'c++'",
+                "g++' failed: no such file or directory",
+                "c++' failed: no such file or directory",
+                "g++: not found",
+                "g++: command not found",
+                "c++: command not found",
+                "no cmake_cxx_compiler could be found",
+            ],
+            BuildPrerequisite::PythonHeaders => &[
+                "python.h: no such file or directory",
+                "'python.h' file not found",
+                "cannot open include file: 'python.h'",
+            ],
+            BuildPrerequisite::Rust => &[
+                "can't find rust compiler",
+                "cargo: not found",
+                "cargo: command not found",
+                "rustc: not found",
+                "rustc: command not found",
+            ],
+            BuildPrerequisite::CMake => &[
+                "cmake: not found",
+                "cmake: command not found",
+                "cmake must be installed",
+                "no such file or directory: 'cmake'",
+                "could not find cmake",
+            ],
+        }
+    }
+
+    /// The executables of which at least one is found on the `PATH` if the prerequisite is
+    /// installed. Empty if the prerequisite cannot be detected this way.
+    fn executables(&self) -> &'static [&'static str] {
+        match self {
+            // MSVC is usually not on the PATH outside of a developer prompt.
+            BuildPrerequisite::CCompiler | BuildPrerequisite::CxxCompiler if cfg!(windows) => &[],
+            BuildPrerequisite::CCompiler => &["cc", "gcc", "clang"],
+            BuildPrerequisite::CxxCompiler => &["c++", "g++", "clang++"],
+            BuildPrerequisite::PythonHeaders => &[],
+            BuildPrerequisite::Rust => &["cargo"],
+            BuildPrerequisite::CMake => &["cmake"],
+        }
+    }
+}
+
+impl Display for BuildPrerequisite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildPrerequisite::CCompiler => write!(f, "a C compiler"),
+            BuildPrerequisite::CxxCompiler => write!(f, "a C++ compiler"),
+            BuildPrerequisite::PythonHeaders => write!(f, "the python development headers"),
+            BuildPrerequisite::Rust => write!(f, "the rust toolchain"),
+            BuildPrerequisite::CMake => write!(f, "cmake"),
+        }
+    }
+}
+
+/// Returns the prerequisites that the output of a failed build indicates are missing.
+///
+/// A prerequisite is only reported if the output mentions it missing and, for tools that can be
+/// detected on the `PATH`, none of its executables can be found. This avoids misleading hints when
+/// the build failed for a different reason.
+pub fn missing_build_prerequisites(output: &str) -> Vec<BuildPrerequisite> {
+    missing_build_prerequisites_with(output, |executable| which::which(executable).is_ok())
+}
+
+fn missing_build_prerequisites_with(
+    output: &str,
+    is_installed: impl Fn(&str) -> bool,
+) -> Vec<BuildPrerequisite> {
+    let output = output.to_lowercase();
+    BuildPrerequisite::ALL
+        .into_iter()
+        .filter(|prerequisite| {
+            prerequisite
+                .symptoms()
+                .iter()
+                .any(|symptom| output.contains(symptom))
+        })
+        .filter(|prerequisite| {
+            !prerequisite
+                .executables()
+                .iter()
+                .any(|executable| is_installed(executable))
+        })
+        .collect()
+}
+
+/// Formats hints for the given missing prerequisites that can be appended to an error message.
+pub(crate) fn format_prerequisite_hints(prerequisites: &[BuildPrerequisite]) -> String {
+    prerequisites
+        .iter()
+        .map(|prerequisite| {
+            format!(
+                "\nhint: {prerequisite} seems to be missing, {}",
+                prerequisite.install_hint()
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_missing_build_prerequisites() {
+        let output = "building 'foo._speedups' extension\n\
+            src/speedups.c:1:10: fatal error: Python.h: No such file or directory\n\
+            error: command '/usr/bin/x86_64-linux-gnu-gcc' failed: No such file or directory";
+
+        assert_eq!(
+            missing_build_prerequisites_with(output, |_| false),
+            [
+                BuildPrerequisite::CCompiler,
+                BuildPrerequisite::PythonHeaders
+            ]
+        );
+
+        // A compiler that is found on the PATH is not reported
+        if !cfg!(windows) {
+            assert_eq!(
+                missing_build_prerequisites_with(output, |executable| executable == "gcc"),
+                [BuildPrerequisite::PythonHeaders]
+            );
+        }
+
+        let output = "error: can't find Rust compiler\n\
+            If you are using an outdated pip version, it is possible a prebuilt wheel is available";
+        assert_eq!(
+            missing_build_prerequisites_with(output, |_| false),
+            [BuildPrerequisite::Rust]
+        );
+
+        assert!(
+            missing_build_prerequisites_with("SyntaxError: invalid syntax", |_| false).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_format_prerequisite_hints() {
+        assert_eq!(format_prerequisite_hints(&[]), "");
+        assert_eq!(
+            format_prerequisite_hints(&[BuildPrerequisite::Rust]),
+            "\nhint: the rust toolchain seems to be missing, install the rust toolchain from https://rustup.rs"
+        );
+    }
+}
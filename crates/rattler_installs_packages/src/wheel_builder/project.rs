@@ -0,0 +1,128 @@
+//! Projects that are built in place from their source directory instead of from an sdist, see
+//! [`super::WheelBuilder::build_editable`].
+
+use crate::artifacts::{parse_pyproject_toml, SDistError};
+use crate::types::{PackageName, SDistFilename, SDistFormat, Version};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A project directory that contains a `pyproject.toml` or a legacy `setup.py`.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "sdist-build"), allow(dead_code))]
+pub(crate) struct ProjectDir {
+    /// The absolute path of the project directory
+    pub path: PathBuf,
+
+    /// The name and version of the project as if it was packaged as an sdist. Build requirement
+    /// hooks and errors identify builds by the filename of their sdist.
+    pub name: SDistFilename,
+
+    /// The `[build-system]` table of the project, the legacy setuptools backend is used if it is
+    /// missing.
+    pub build_system: pyproject_toml::BuildSystem,
+}
+
+impl ProjectDir {
+    /// Reads the project in `path`.
+    ///
+    /// The name and version are read from the `[project]` table. Projects that don't declare
+    /// their name statically are named after their directory, projects with a dynamic version get
+    /// version `0`.
+    pub fn read(path: &Path) -> Result<Self, SDistError> {
+        let path = path.canonicalize()?;
+        let pyproject_toml = match std::fs::read(path.join("pyproject.toml")) {
+            Ok(bytes) => Some(parse_pyproject_toml(bytes)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                if !path.join("setup.py").is_file() {
+                    return Err(SDistError::NoPyProjectTomlFound);
+                }
+                None
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let project = pyproject_toml
+            .as_ref()
+            .and_then(|pyproject_toml| pyproject_toml.project.as_ref());
+        let name = project
+            .map(|project| project.name.clone())
+            .or_else(|| Some(path.file_name()?.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        let distribution = PackageName::from_str(&name).map_err(|_| {
+            SDistError::PyProjectTomlParseError(format!("invalid project name '{name}'"))
+        })?;
+        let version = project
+            .and_then(|project| project.version.as_ref())
+            .map(|version| Version::from_str(&version.to_string()))
+            .transpose()
+            .map_err(|e| SDistError::PyProjectTomlParseError(format!("invalid version: {e}")))?
+            .unwrap_or_else(|| Version::from_str("0").expect("0 is a valid version"));
+
+        let build_system = pyproject_toml
+            .and_then(|pyproject_toml| pyproject_toml.build_system)
+            .unwrap_or_else(|| pyproject_toml::BuildSystem {
+                requires: Vec::new(),
+                build_backend: None,
+                backend_path: None,
+            });
+
+        Ok(Self {
+            path,
+            name: SDistFilename {
+                distribution,
+                version,
+                format: SDistFormat::TarGz,
+            },
+            build_system,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_project_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            r#"
+[build-system]
+requires = ["hatchling"]
+build-backend = "hatchling.build"
+
+[project]
+name = "My_Project"
+version = "1.2.0"
+"#,
+        )
+        .unwrap();
+
+        let project = ProjectDir::read(dir.path()).unwrap();
+        assert_eq!(project.path, dir.path().canonicalize().unwrap());
+        assert_eq!(project.name.distribution.as_source_str(), "My_Project");
+        assert_eq!(project.name.version.to_string(), "1.2.0");
+        assert_eq!(
+            project.build_system.build_backend.as_deref(),
+            Some("hatchling.build")
+        );
+
+        // A project with a dynamic version and without a build system
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nname = \"foo\"\ndynamic = [\"version\"]\n",
+        )
+        .unwrap();
+        let project = ProjectDir::read(dir.path()).unwrap();
+        assert_eq!(project.name.version.to_string(), "0");
+        assert!(project.build_system.build_backend.is_none());
+
+        // A directory without a pyproject.toml or setup.py
+        std::fs::remove_file(dir.path().join("pyproject.toml")).unwrap();
+        assert!(matches!(
+            ProjectDir::read(dir.path()),
+            Err(SDistError::NoPyProjectTomlFound)
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+//! Typed results of the stages that `wheel_builder_frontend.py` runs in a build environment.
+//!
+//! Every stage writes a versioned JSON envelope to `<stage>.json` in the work directory, which
+//! contains the status of the stage, the result if it succeeded, the exception the build backend
+//! raised if it failed, and how long the stage took.
+
+use super::BackendException;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The version of the result envelope written by the frontend.
+pub(crate) const RESULT_VERSION: u32 = 1;
+
+/// A stage of building a wheel that is run by the frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// Calls `get_requires_for_build_wheel` of the build backend.
+    GetRequiresForBuildWheel,
+
+    /// Calls `prepare_metadata_for_build_wheel` of the build backend.
+    WheelMetadata,
+
+    /// Runs `setup.py egg_info` for legacy sdists.
+    EggInfo,
+
+    /// Calls `build_wheel` of the build backend.
+    Wheel,
+
+    /// Calls `get_requires_for_build_editable` of the build backend.
+    GetRequiresForBuildEditable,
+
+    /// Calls `prepare_metadata_for_build_editable` of the build backend.
+    EditableMetadata,
+
+    /// Calls `build_editable` of the build backend.
+    Editable,
+}
+
+impl Stage {
+    /// The name of the stage as it is passed to the frontend.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Stage::GetRequiresForBuildWheel => "GetRequiresForBuildWheel",
+            Stage::WheelMetadata => "WheelMetadata",
+            Stage::EggInfo => "EggInfo",
+            Stage::Wheel => "Wheel",
+            Stage::GetRequiresForBuildEditable => "GetRequiresForBuildEditable",
+            Stage::EditableMetadata => "EditableMetadata",
+            Stage::Editable => "Editable",
+        }
+    }
+
+    /// The path of the file the frontend writes the result envelope of the stage to.
+    pub(crate) fn result_file(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join(format!("{}.json", self.as_str()))
+    }
+}
+
+/// What a build environment builds, which determines the hooks of the build backend that are used,
+/// see [PEP 660](https://peps.python.org/pep-0660/) for editables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum BuildTarget {
+    /// A regular wheel.
+    Wheel,
+
+    /// An editable wheel.
+    Editable,
+}
+
+impl BuildTarget {
+    /// The stage that returns the additional build requirements.
+    pub(crate) fn requires_stage(&self) -> Stage {
+        match self {
+            BuildTarget::Wheel => Stage::GetRequiresForBuildWheel,
+            BuildTarget::Editable => Stage::GetRequiresForBuildEditable,
+        }
+    }
+
+    /// The stage that prepares the metadata.
+    pub(crate) fn metadata_stage(&self) -> Stage {
+        match self {
+            BuildTarget::Wheel => Stage::WheelMetadata,
+            BuildTarget::Editable => Stage::EditableMetadata,
+        }
+    }
+
+    /// The stage that builds the wheel.
+    pub(crate) fn build_stage(&self) -> Stage {
+        match self {
+            BuildTarget::Wheel => Stage::Wheel,
+            BuildTarget::Editable => Stage::Editable,
+        }
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The status of a stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum StageStatus {
+    /// The stage completed and the envelope contains its result.
+    Ok,
+
+    /// The stage is not supported by the build backend or the sdist.
+    Unsupported,
+
+    /// The build backend raised an exception.
+    Error,
+}
+
+/// The result envelope of a stage.
+#[derive(Debug, Deserialize)]
+pub(crate) struct StageResult<T> {
+    pub version: u32,
+    pub stage: String,
+    pub status: StageStatus,
+    pub result: Option<T>,
+    pub error: Option<BackendException>,
+    #[serde(default)]
+    pub duration: f64,
+}
+
+impl<T: DeserializeOwned> StageResult<T> {
+    /// Parses the result envelope of a stage.
+    pub(crate) fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl<T> StageResult<T> {
+    /// How long the stage took.
+    pub(crate) fn duration(&self) -> Duration {
+        Duration::try_from_secs_f64(self.duration).unwrap_or_default()
+    }
+}
+
+/// The result of [`Stage::GetRequiresForBuildWheel`] and [`Stage::GetRequiresForBuildEditable`].
+#[derive(Debug, Deserialize)]
+pub(crate) struct RequiresResult {
+    pub requires: Vec<String>,
+}
+
+/// The result of [`Stage::WheelMetadata`] and [`Stage::EditableMetadata`].
+#[derive(Debug, Deserialize)]
+pub(crate) struct MetadataResult {
+    pub metadata_dir: PathBuf,
+}
+
+/// The result of [`Stage::EggInfo`].
+#[derive(Debug, Deserialize)]
+pub(crate) struct EggInfoResult {
+    pub egg_info_dir: PathBuf,
+}
+
+/// The result of [`Stage::Wheel`] and [`Stage::Editable`].
+#[derive(Debug, Deserialize)]
+pub(crate) struct WheelResult {
+    pub wheel: PathBuf,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_stage_result() {
+        let result = StageResult::<RequiresResult>::from_json(
+            r#"{"version": 1, "stage": "GetRequiresForBuildWheel", "status": "ok", "result": {"requires": ["wheel"]}, "duration": 0.5}"#,
+        )
+        .unwrap();
+        assert_eq!(result.status, StageStatus::Ok);
+        assert_eq!(result.result.unwrap().requires, ["wheel"]);
+        assert_eq!(result.duration(), Duration::from_millis(500));
+
+        let result = StageResult::<MetadataResult>::from_json(
+            r#"{"version": 1, "stage": "WheelMetadata", "status": "unsupported", "duration": 0.1}"#,
+        )
+        .unwrap();
+        assert_eq!(result.status, StageStatus::Unsupported);
+        assert!(result.result.is_none());
+
+        let result = StageResult::<WheelResult>::from_json(
+            r#"{"version": 1, "stage": "Wheel", "status": "error", "error": {"type": "CalledProcessError", "message": "exit status 1", "traceback": "Traceback (most recent call last):\n"}, "duration": 2.0}"#,
+        )
+        .unwrap();
+        assert_eq!(result.status, StageStatus::Error);
+        assert_eq!(
+            result.error.unwrap().to_string(),
+            "CalledProcessError: exit status 1"
+        );
+    }
+}
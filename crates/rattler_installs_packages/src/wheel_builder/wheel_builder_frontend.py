@@ -5,6 +5,9 @@ from importlib import import_module
 from json import loads
 from types import ModuleType
 import json
+import subprocess
+import time
+import traceback
 
 ################################################################
 # Begin janky attempt to workaround
@@ -44,78 +47,197 @@ def get_backend_from_entry_point(entrypoint: str) -> ModuleType:
 
     return backend
 
-def get_requires_for_build_wheel(backend: ModuleType, work_dir: Path) -> [str]:
+# The version of the result envelope, this must match the version that is
+# expected by the rust side.
+RESULT_VERSION = 1
+
+class Unsupported(Exception):
+    """Raised by a stage if it is not supported for the sdist."""
+
+def result_file(work_dir: Path, goal: str) -> Path:
+    """The file the result envelope of a stage is written to."""
+    return work_dir / f"{goal}.json"
+
+def read_result(work_dir: Path, goal: str):
+    """Returns the result of a stage that completed successfully before, or None."""
+    path = result_file(work_dir, goal)
+    if not path.exists():
+        return None
+    envelope = json.loads(path.read_text())
+    if envelope.get("status") != "ok":
+        return None
+    return envelope.get("result")
+
+def read_config_settings(work_dir: Path):
+    """
+    Returns the config settings for the build backend, or None if there are
+    none. Like pip, a setting with a single value is passed as a string and a
+    setting with multiple values as a list of strings.
+    """
+    path = work_dir / "config_settings.json"
+    if not path.exists():
+        return None
+    settings = json.loads(path.read_text())
+    if not settings:
+        return None
+    return {
+        key: values[0] if len(values) == 1 else values
+        for key, values in settings.items()
+    }
+
+def global_options(config_settings) -> list:
     """
-    Returns a list of requirements. This is only necessary if we do not
+    The `--global-option` config settings, which the legacy setuptools backend
+    passes to `setup.py` before the command.
+    """
+    if config_settings is None:
+        return []
+    options = config_settings.get("--global-option", [])
+    if isinstance(options, str):
+        options = [options]
+    return options
+
+def get_requires(backend: ModuleType, hook: str, config_settings):
+    """
+    Returns a list of requirements using the `get_requires_for_build_wheel` or
+    `get_requires_for_build_editable` hook. This is only necessary if we do not
     have a pyproject.toml file.
     """
-    f = getattr(backend, "get_requires_for_build_wheel")
+    f = getattr(backend, hook, None)
     if f is None:
-        result = []
+        requires = []
     else:
-        result = f()
+        requires = f(config_settings)
 
-    j = json.dumps(result)
-    out_json_file = work_dir / "extra_requirements.json"
-    out_json_file.write_text(j)
-    print(j)
+    return {"requires": list(requires)}
 
-def metadata_dirs(work_dir: Path):
-    return work_dir / "metadata"
+def metadata_dirs(work_dir: Path, hook: str):
+    return work_dir / ("editable_metadata" if "editable" in hook else "metadata")
 
-def prepare_metadata_for_build_wheel(backend: ModuleType, work_dir: Path):
+def prepare_metadata(backend: ModuleType, work_dir: Path, hook: str, config_settings):
     """
-    Prepare any files that need to be generated before building the wheel.
+    Prepare any files that need to be generated before building the wheel, using
+    the `prepare_metadata_for_build_wheel` or
+    `prepare_metadata_for_build_editable` hook.
     """
-    if hasattr(backend, "prepare_metadata_for_build_wheel"):
-        # Create an output file for the metadata
-        result_file = work_dir / "metadata_result"
-
-        # Create the metadata output directory
-        d = metadata_dirs(work_dir)
-        d.mkdir()
-        dist_info = backend.prepare_metadata_for_build_wheel(str(d))
-        # Path to the dist-info directory
-        result = str(d / dist_info)
-        # Write the path to the dist-info directory to a file
-        result_file.write_text(result)
-    else:
-        exit(50)
+    if not hasattr(backend, hook):
+        raise Unsupported()
+
+    # Create the metadata output directory
+    d = metadata_dirs(work_dir, hook)
+    d.mkdir(parents=True, exist_ok=True)
+    dist_info = getattr(backend, hook)(str(d), config_settings)
+    # Path to the dist-info directory
+    return {"metadata_dir": str(d / dist_info)}
+
+def egg_info(work_dir: Path, config_settings):
+    """
+    Legacy fallback to generate the metadata of sdists whose build backend does
+    not support `prepare_metadata_for_build_wheel`, by running
+    `setup.py egg_info`.
+    """
+    if not Path("setup.py").exists():
+        raise Unsupported()
+
+    egg_base = work_dir / "egg_info"
+    egg_base.mkdir(parents=True, exist_ok=True)
+    # The output of setup.py is forwarded to stderr so it ends up in the error
+    # message if the command fails
+    subprocess.run(
+        [
+            sys.executable,
+            "setup.py",
+            *global_options(config_settings),
+            "egg_info",
+            "--egg-base",
+            str(egg_base),
+        ],
+        check=True,
+        stdout=sys.stderr,
+    )
+    egg_info_dir = next(egg_base.glob("*.egg-info"))
+    return {"egg_info_dir": str(egg_info_dir)}
 
-def wheel_dirs(work_dir: Path):
-    return work_dir / "wheel"
+def wheel_dirs(work_dir: Path, hook: str):
+    return work_dir / ("editable" if hook == "build_editable" else "wheel")
+
+def build(backend: ModuleType, work_dir: Path, hook: str, metadata_goal: str, config_settings):
+    """
+    Take a folder with an SDist or a project and build a wheel from it, using the
+    `build_wheel` or `build_editable` hook.
+    """
+    if not hasattr(backend, hook):
+        raise Unsupported()
 
-def build_wheel(backend: ModuleType, work_dir: Path):
-    """Take a folder with an SDist and build a wheel from it."""
-    wheel_dir = wheel_dirs(work_dir)
-    result_file = work_dir / "wheel_result"
+    wheel_dir = wheel_dirs(work_dir, hook)
 
     # Use the metadata result if it exists, otherwise set this to None
-    metadata_result = work_dir / "metadata_result"
-    if metadata_result.exists():
-        metadata_dir = metadata_result.read_text().strip()
+    metadata_result = read_result(work_dir, metadata_goal)
+    if metadata_result is not None:
+        metadata_dir = metadata_result["metadata_dir"]
     else:
         metadata_dir = None
 
-    wheel_dir.mkdir()
-    wheel_basename = backend.build_wheel(
+    wheel_dir.mkdir(parents=True, exist_ok=True)
+    wheel_basename = getattr(backend, hook)(
         str(wheel_dir),
+        config_settings=config_settings,
         metadata_directory=metadata_dir,
     )
 
-    result_file.write_text(str(wheel_dir / wheel_basename))
+    return {"wheel": str(wheel_dir / wheel_basename)}
+
+def run_stage(work_dir: Path, entry_point: str, goal: str) -> bool:
+    """
+    Runs a stage and writes its result envelope. The status of the envelope is
+    `ok` with the result of the stage, `unsupported` if the stage is not
+    supported, or `error` with the exception raised by the backend. Returns
+    False if the stage failed.
+    """
+    envelope = {"version": RESULT_VERSION, "stage": goal}
+    start = time.monotonic()
+    try:
+        config_settings = read_config_settings(work_dir)
+        if goal == "EggInfo":
+            result = egg_info(work_dir, config_settings)
+        else:
+            backend = get_backend_from_entry_point(entry_point)
+            if goal == "GetRequiresForBuildWheel":
+                result = get_requires(backend, "get_requires_for_build_wheel", config_settings)
+            elif goal == "WheelMetadata":
+                result = prepare_metadata(backend, work_dir, "prepare_metadata_for_build_wheel", config_settings)
+            elif goal == "Wheel":
+                result = build(backend, work_dir, "build_wheel", "WheelMetadata", config_settings)
+            elif goal == "GetRequiresForBuildEditable":
+                result = get_requires(backend, "get_requires_for_build_editable", config_settings)
+            elif goal == "EditableMetadata":
+                result = prepare_metadata(backend, work_dir, "prepare_metadata_for_build_editable", config_settings)
+            elif goal == "Editable":
+                result = build(backend, work_dir, "build_editable", "EditableMetadata", config_settings)
+            else:
+                raise ValueError(f"unknown goal '{goal}'")
+        envelope["status"] = "ok"
+        envelope["result"] = result
+    except Unsupported:
+        envelope["status"] = "unsupported"
+    except (Exception, SystemExit) as e:
+        # Also print the traceback to stderr so it ends up in the logs
+        traceback.print_exc()
+        envelope["status"] = "error"
+        envelope["error"] = {
+            "type": type(e).__name__,
+            "message": str(e),
+            "traceback": traceback.format_exc(),
+        }
+    envelope["duration"] = time.monotonic() - start
+
+    result_file(work_dir, goal).write_text(json.dumps(envelope))
+    return envelope["status"] != "error"
 
 if __name__ == "__main__":
     work_dir, entry_point, goal = sys.argv[1:]
-    backend = get_backend_from_entry_point(entry_point)
-
-    work_dir = Path(work_dir)
 
-    if goal == "GetRequiresForBuildWheel":
-        get_requires_for_build_wheel(backend, work_dir)
-    if goal == "WheelMetadata":
-        prepare_metadata_for_build_wheel(backend, work_dir)
-    elif goal == "Wheel":
-        build_wheel(backend, work_dir)
+    if not run_stage(Path(work_dir), entry_point, goal):
+        exit(1)
 
     exit(0)
@@ -0,0 +1,363 @@
+use super::BuildKey;
+use crate::artifacts::BuildProvenance;
+use crate::python_env::WheelTags;
+use crate::types::{NormalizedPackageName, SDistFilename, WheelFilename};
+use itertools::Itertools;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The name of the file next to the cached wheels that records the options they were built with.
+const BUILD_FILE: &str = "build.json";
+
+/// Determines which wheels are removed from a [`BuiltWheelCache`] when it is pruned. The most
+/// recently built wheels are kept first. By default all wheels are retained.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct RetentionPolicy {
+    /// Wheels that were built longer ago than this are removed.
+    pub max_age: Option<Duration>,
+
+    /// The maximum number of wheels to keep.
+    pub max_wheels: Option<usize>,
+
+    /// The maximum total size of the wheels in bytes.
+    pub max_size: Option<u64>,
+}
+
+/// A directory that stores the wheels built from sdists, so they don't have to be built again and
+/// can be reused or vendored by users.
+///
+/// Wheels are stored under the filename of the sdist they were built from and the digest of the
+/// [`BuildKey`] of the build, e.g.
+/// `<root>/rich-13.6.0.tar.gz/<digest>/rich-13.6.0-py3-none-any.whl`. The key covers the contents
+/// of the sdist and all options that influence the build, so a wheel is only reused for a build
+/// with exactly the same inputs. Sdists with the same filename from different indexes do not
+/// share wheels. The options of the build are stored next to the wheels, see
+/// [`Self::build_provenance`].
+#[derive(Debug, Clone)]
+pub struct BuiltWheelCache {
+    root: PathBuf,
+    retention_policy: RetentionPolicy,
+}
+
+impl BuiltWheelCache {
+    /// Constructs a new cache that stores wheels in `root`. The directory is created if it does
+    /// not exist.
+    pub fn new(root: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            retention_policy: RetentionPolicy::default(),
+        })
+    }
+
+    /// Sets the policy that is applied when the cache is pruned.
+    pub fn with_retention_policy(self, retention_policy: RetentionPolicy) -> Self {
+        Self {
+            retention_policy,
+            ..self
+        }
+    }
+
+    /// Returns the root directory of the cache.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the retention policy of the cache.
+    pub fn retention_policy(&self) -> &RetentionPolicy {
+        &self.retention_policy
+    }
+
+    /// Returns the path of a wheel built from `sdist` with the given [`BuildKey`] if one is cached.
+    /// If `compatible_tags` are specified only wheels compatible with these tags are considered
+    /// and the most specific one is returned. Otherwise the most recently built wheel is returned.
+    pub fn find(
+        &self,
+        sdist: &SDistFilename,
+        key: &BuildKey,
+        compatible_tags: Option<&WheelTags>,
+    ) -> Option<PathBuf> {
+        let wheels = wheels_in(&self.root.join(sdist.to_string()).join(key.digest()), sdist);
+
+        match compatible_tags {
+            None => wheels
+                .into_iter()
+                .max_by_key(|(path, _)| {
+                    std::fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                })
+                .map(|(path, _)| path),
+            Some(compatible_tags) => wheels
+                .into_iter()
+                .filter_map(|(path, name)| {
+                    let score = name
+                        .all_tags_iter()
+                        .filter_map(|tag| compatible_tags.compatibility(&tag))
+                        .max()?;
+                    Some((path, score))
+                })
+                .max_by(|(a_path, a_score), (b_path, b_score)| {
+                    a_score.cmp(b_score).then_with(|| b_path.cmp(a_path))
+                })
+                .map(|(path, _)| path),
+        }
+    }
+
+    /// Returns the paths of all wheels that were built from `sdist`, regardless of the options
+    /// they were built with, sorted by path.
+    pub fn wheels(&self, sdist: &SDistFilename) -> Vec<PathBuf> {
+        let Ok(entries) = std::fs::read_dir(self.root.join(sdist.to_string())) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(Result::ok)
+            .flat_map(|entry| wheels_in(&entry.path(), sdist))
+            .map(|(path, _)| path)
+            .sorted()
+            .collect()
+    }
+
+    /// Copies a wheel that was built from `sdist` with the given [`BuildKey`] and `build` options
+    /// into the cache and returns its new location. An existing wheel with the same name and key
+    /// is replaced.
+    pub fn store(
+        &self,
+        sdist: &SDistFilename,
+        key: &BuildKey,
+        wheel: &Path,
+        build: &BuildProvenance,
+    ) -> std::io::Result<PathBuf> {
+        let directory = self.root.join(sdist.to_string()).join(key.digest());
+        std::fs::create_dir_all(&directory)?;
+        let mut build_file = tempfile::NamedTempFile::new_in(&directory)?;
+        serde_json::to_writer(build_file.as_file_mut(), build)?;
+        build_file
+            .persist(directory.join(BUILD_FILE))
+            .map_err(|err| err.error)?;
+        copy_wheel(wheel, &directory)
+    }
+
+    /// Returns the options a wheel in this cache was built with. Returns `None` if the wheel is
+    /// not part of the cache or was stored without its options.
+    pub fn build_provenance(&self, wheel: &Path) -> Option<BuildProvenance> {
+        if !wheel.starts_with(&self.root) {
+            return None;
+        }
+        let contents = std::fs::read(wheel.parent()?.join(BUILD_FILE)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    /// Removes the wheels that are not retained by the [`RetentionPolicy`] of this cache. Returns
+    /// the paths of the removed wheels.
+    pub fn prune(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut wheels = Vec::new();
+        for sdist_directory in std::fs::read_dir(&self.root)? {
+            let sdist_directory = sdist_directory?;
+            if !sdist_directory.file_type()?.is_dir() {
+                continue;
+            }
+            for key_directory in std::fs::read_dir(sdist_directory.path())? {
+                let key_directory = key_directory?;
+                if !key_directory.file_type()?.is_dir() {
+                    continue;
+                }
+                for entry in std::fs::read_dir(key_directory.path())? {
+                    let entry = entry?;
+                    let metadata = entry.metadata()?;
+                    if metadata.is_file() && entry.file_name().to_string_lossy().ends_with(".whl") {
+                        wheels.push((entry.path(), metadata.modified()?, metadata.len()));
+                    }
+                }
+            }
+        }
+
+        // Newest wheels first, they are the ones we want to keep
+        wheels.sort_by(|(_, a, _), (_, b, _)| b.cmp(a));
+
+        let now = SystemTime::now();
+        let policy = &self.retention_policy;
+        let mut total_size = 0;
+        let mut removed = Vec::new();
+        for (idx, (path, modified, size)) in wheels.into_iter().enumerate() {
+            total_size += size;
+            let too_old = policy
+                .max_age
+                .is_some_and(|max_age| now.duration_since(modified).unwrap_or_default() > max_age);
+            let too_many = policy
+                .max_wheels
+                .is_some_and(|max_wheels| idx >= max_wheels);
+            let too_large = policy
+                .max_size
+                .is_some_and(|max_size| total_size > max_size);
+            if too_old || too_many || too_large {
+                tracing::debug!("removing {} from the built wheel cache", path.display());
+                std::fs::remove_file(&path)?;
+
+                // Remove the directories of the build and the sdist if this was their last wheel
+                if let Some(key_directory) = path.parent() {
+                    let has_wheels = std::fs::read_dir(key_directory)?
+                        .filter_map(Result::ok)
+                        .any(|entry| entry.file_name().to_string_lossy().ends_with(".whl"));
+                    if !has_wheels {
+                        let _ = std::fs::remove_file(key_directory.join(BUILD_FILE));
+                    }
+                    if std::fs::remove_dir(key_directory).is_ok() {
+                        if let Some(sdist_directory) = key_directory.parent() {
+                            let _ = std::fs::remove_dir(sdist_directory);
+                        }
+                    }
+                }
+                removed.push(path);
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Returns the wheels built from `sdist` in `directory` together with their parsed filenames.
+fn wheels_in(directory: &Path, sdist: &SDistFilename) -> Vec<(PathBuf, WheelFilename)> {
+    let normalized_name: NormalizedPackageName = sdist.distribution.clone().into();
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let name = WheelFilename::from_filename(file_name.to_str()?, &normalized_name).ok()?;
+            Some((entry.path(), name))
+        })
+        .collect()
+}
+
+/// Atomically copies a wheel into `directory` keeping its filename.
+pub(crate) fn copy_wheel(wheel: &Path, directory: &Path) -> std::io::Result<PathBuf> {
+    let file_name = wheel.file_name().ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a file", wheel.display()),
+        )
+    })?;
+    let destination = directory.join(file_name);
+
+    let mut temp_file = tempfile::NamedTempFile::new_in(directory)?;
+    std::io::copy(&mut std::fs::File::open(wheel)?, temp_file.as_file_mut())?;
+    temp_file.persist(&destination).map_err(|err| err.error)?;
+
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sdist(name: &str) -> SDistFilename {
+        let normalized_name: NormalizedPackageName =
+            name.split('-').next().unwrap().parse().unwrap();
+        SDistFilename::from_filename(name, &normalized_name).unwrap()
+    }
+
+    fn built_wheel(dir: &Path, name: &str, size: usize) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![0u8; size]).unwrap();
+        path
+    }
+
+    fn key(sdist: &str, options: &str) -> BuildKey {
+        BuildKey::new(
+            rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(sdist.as_bytes()),
+            options.to_owned(),
+        )
+    }
+
+    #[test]
+    fn test_store_and_find() {
+        let build_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = BuiltWheelCache::new(cache_dir.path()).unwrap();
+
+        let rich = sdist("rich-13.6.0.tar.gz");
+        let rich_key = key("rich", "cpython");
+        assert_eq!(cache.find(&rich, &rich_key, None), None);
+
+        let wheel = built_wheel(build_dir.path(), "rich-13.6.0-py3-none-any.whl", 10);
+        let build = BuildProvenance {
+            environment_variables: vec![String::from("CFLAGS")],
+            ..Default::default()
+        };
+        assert_eq!(cache.build_provenance(&wheel), None);
+        let cached = cache.store(&rich, &rich_key, &wheel, &build).unwrap();
+        assert_eq!(
+            cached,
+            cache_dir
+                .path()
+                .join("rich-13.6.0.tar.gz")
+                .join(rich_key.digest())
+                .join("rich-13.6.0-py3-none-any.whl")
+        );
+        assert_eq!(cache.find(&rich, &rich_key, None), Some(cached.clone()));
+        assert_eq!(cache.wheels(&rich), vec![cached.clone()]);
+        assert_eq!(cache.build_provenance(&cached), Some(build));
+
+        let tags: WheelTags = ["cp311-cp311-linux_x86_64", "py3-none-any"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        assert_eq!(cache.find(&rich, &rich_key, Some(&tags)), Some(cached));
+
+        let tags: WheelTags = ["cp27-cp27m-linux_x86_64"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        assert_eq!(cache.find(&rich, &rich_key, Some(&tags)), None);
+
+        // Wheels built with other options or from another sdist with the same filename are not
+        // reused.
+        assert_eq!(cache.find(&rich, &key("rich", "pypy"), None), None);
+        assert_eq!(cache.find(&rich, &key("other rich", "cpython"), None), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let build_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = BuiltWheelCache::new(cache_dir.path()).unwrap();
+
+        let wheels = [
+            ("rich-13.6.0.tar.gz", "rich-13.6.0-py3-none-any.whl"),
+            ("trio-0.18.0.tar.gz", "trio-0.18.0-py3-none-any.whl"),
+            ("pytz-2023.3.tar.gz", "pytz-2023.3-py2.py3-none-any.whl"),
+        ];
+        for (sdist_name, wheel_name) in wheels {
+            let wheel = built_wheel(build_dir.path(), wheel_name, 10);
+            cache
+                .store(
+                    &sdist(sdist_name),
+                    &key(sdist_name, "cpython"),
+                    &wheel,
+                    &BuildProvenance::default(),
+                )
+                .unwrap();
+        }
+
+        // The default policy keeps everything
+        assert!(cache.prune().unwrap().is_empty());
+
+        let cache = cache.with_retention_policy(RetentionPolicy {
+            max_age: Some(Duration::from_secs(3600)),
+            max_size: Some(25),
+            ..Default::default()
+        });
+        assert_eq!(cache.prune().unwrap().len(), 1);
+
+        let cache = cache.with_retention_policy(RetentionPolicy {
+            max_wheels: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(cache.prune().unwrap().len(), 2);
+        assert_eq!(std::fs::read_dir(cache_dir.path()).unwrap().count(), 0);
+    }
+}
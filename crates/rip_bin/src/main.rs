@@ -1,7 +1,10 @@
 use rip_bin::{global_multi_progress, IndicatifWriter};
+use server::Server;
 use std::collections::HashMap;
 use std::io::Write;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use clap::Parser;
 use itertools::Itertools;
@@ -10,26 +13,109 @@ use tracing_subscriber::filter::Directive;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use url::Url;
 
-use rattler_installs_packages::python_env::WheelTags;
+use rattler_installs_packages::config::Config;
+use rattler_installs_packages::index::{
+    AttestationPolicy, AttestationStatus, CacheProxy, PipCache,
+};
+use rattler_installs_packages::python_env::{InterpreterCache, PyodideTarget};
+use rattler_installs_packages::session_log::SessionLog;
 use rattler_installs_packages::{
-    normalize_index_url, python_env::Pep508EnvMakers, resolve, resolve::resolve,
-    resolve::ResolveOptions, types::Requirement,
+    normalize_index_url,
+    python_env::Pep508EnvMakers,
+    resolve,
+    resolve::check_attestations,
+    resolve::resolve,
+    resolve::to_requirements_txt,
+    resolve::{attest_requirements_txt, verify_requirements_txt, ResolutionInputs},
+    resolve::{resolve_cached, SolutionCache},
+    types::RequirementSpec,
+    wheel_builder::CrossBuildTarget,
 };
 
+mod server;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[clap(num_args=1.., required=true)]
-    specs: Vec<Requirement>,
+    #[clap(num_args=1.., required_unless_present_any = ["server", "serve_cache"])]
+    specs: Vec<RequirementSpec>,
 
     /// Base URL of the Python Package Index (default <https://pypi.org/simple>). This should point
-    /// to a repository compliant with PEP 503 (the simple repository API).
-    #[clap(default_value = "https://pypi.org/simple/", long)]
-    index_url: Url,
+    /// to a repository compliant with PEP 503 (the simple repository API). Overrides the index urls
+    /// of the `rip.toml` configuration files.
+    #[clap(long)]
+    index_url: Option<Url>,
 
     #[clap(short)]
     verbose: bool,
 
+    /// Also read the index configuration of pip from its configuration files and the
+    /// `PIP_INDEX_URL`, `PIP_EXTRA_INDEX_URL`, `PIP_NO_INDEX` and `PIP_FIND_LINKS` environment
+    /// variables.
+    #[clap(long)]
+    pip_compat: bool,
+
+    /// Read artifacts from the pip cache of the current user before downloading them.
+    #[clap(long)]
+    use_pip_cache: bool,
+
+    /// Write the resolved environment to a pip compatible requirements.txt file with hashes.
+    #[clap(long)]
+    export_requirements: Option<PathBuf>,
+
+    /// Sign the attestation of the exported requirements.txt with the key in this file, or verify
+    /// the signature when used with `--verify-requirements`. The key is shared by everyone that
+    /// generates or verifies the file.
+    #[clap(long)]
+    signing_key: Option<PathBuf>,
+
+    /// Verify that this requirements.txt was exported for the given specs, indexes, options and
+    /// platform and was not modified afterwards, instead of resolving the environment.
+    #[clap(long, conflicts_with = "export_requirements")]
+    verify_requirements: Option<PathBuf>,
+
+    /// Check the PEP 740 attestations of the resolved artifacts if the index publishes them and
+    /// fail if they do not match the artifacts. The signatures of the attestations are not
+    /// verified.
+    #[clap(long)]
+    check_attestations: bool,
+
+    /// Reuse the solution of an earlier identical resolution if the indexes did not change, and
+    /// store the solution for later runs.
+    #[clap(long)]
+    cache_solutions: bool,
+
+    /// Remove all cached solutions before resolving.
+    #[clap(long)]
+    clear_solution_cache: bool,
+
+    /// Resolve for the Pyodide (WebAssembly) interpreter instead of the system python. Only wheels
+    /// are selected, make sure the index provides emscripten wheels.
+    #[clap(long)]
+    pyodide: bool,
+
+    /// Build sdists for this platform (e.g. `macosx_11_0_arm64`) instead of the platform of the
+    /// system python. Defaults to the platform in `_PYTHON_HOST_PLATFORM` if that is set. Linux
+    /// and windows targets require a cross toolchain in the `CC` and `CXX` environment variables.
+    #[clap(long, value_name = "PLATFORM_TAG", conflicts_with = "pyodide")]
+    cross_build_target: Option<CrossBuildTarget>,
+
+    /// Write a JSON lines log of all network requests, cache hits, builds and file operations to
+    /// this file.
+    #[clap(long)]
+    session_log: Option<PathBuf>,
+
+    /// Run a long-running server that reads JSON-RPC requests from stdin and writes the responses
+    /// to stdout, one per line. Supports the `resolve`, `metadata`, `build`, `install` and
+    /// `shutdown` methods.
+    #[clap(long, conflicts_with_all = ["specs", "export_requirements", "verify_requirements"])]
+    server: bool,
+
+    /// Serve the indexes and caches as a simple index on this address (e.g. `0.0.0.0:8080`), so
+    /// other machines on the network can share the cache by using it as their index url.
+    #[clap(long, value_name = "ADDR", conflicts_with_all = ["specs", "export_requirements", "verify_requirements", "server"])]
+    serve_cache: Option<String>,
+
     #[clap(flatten)]
     sdist_resolution: SDistResolution,
 }
@@ -54,18 +140,18 @@ struct SDistResolution {
     only_sdists: bool,
 }
 
-impl From<SDistResolution> for resolve::SDistResolution {
+impl From<SDistResolution> for Option<resolve::SDistResolution> {
     fn from(value: SDistResolution) -> Self {
         if value.only_sdists {
-            resolve::SDistResolution::OnlySDists
+            Some(resolve::SDistResolution::OnlySDists)
         } else if value.only_wheels {
-            resolve::SDistResolution::OnlyWheels
+            Some(resolve::SDistResolution::OnlyWheels)
         } else if value.prefer_sdists {
-            resolve::SDistResolution::PreferSDists
+            Some(resolve::SDistResolution::PreferSDists)
         } else if value.prefer_wheels {
-            resolve::SDistResolution::PreferWheels
+            Some(resolve::SDistResolution::PreferWheels)
         } else {
-            resolve::SDistResolution::Normal
+            None
         }
     }
 }
@@ -82,61 +168,258 @@ async fn actual_main() -> miette::Result<()> {
         )
         .init();
 
+    // Read the configuration files, command line arguments take precedence
+    let current_dir = std::env::current_dir().into_diagnostic()?;
+    let mut config = if args.pip_compat {
+        Config::load_pip_compatible(Some(&current_dir))
+    } else {
+        Config::load(Some(&current_dir))
+    }
+    .into_diagnostic()?;
+    if let Some(index_url) = &args.index_url {
+        config.index_urls = vec![normalize_index_url(index_url.clone())];
+    }
+    if let Some(sdist_resolution) = args.sdist_resolution.into() {
+        config.sdist_resolution = sdist_resolution;
+    }
+
     // Determine cache directory
-    let cache_dir = dirs::cache_dir()
-        .ok_or_else(|| miette::miette!("failed to determine cache directory"))?
-        .join("rattler/pypi");
+    let cache_dir = config.cache_dir.clone();
     tracing::info!("cache directory: {}", cache_dir.display());
 
     // Construct a package database
-    let package_db = rattler_installs_packages::index::PackageDb::new(
-        Default::default(),
-        &[normalize_index_url(args.index_url.clone())],
-        &cache_dir,
-    )
-    .into_diagnostic()
-    .wrap_err_with(|| {
-        format!(
-            "failed to construct package database for index {}",
-            args.index_url
-        )
-    })?;
-
-    // Determine the environment markers for the current machine
-    let env_markers = Pep508EnvMakers::from_env()
-        .await
+    let client = config
+        .client()
+        .into_diagnostic()
+        .wrap_err("failed to construct the http client")?;
+    let package_db = config
+        .package_db(client)
         .into_diagnostic()
         .wrap_err_with(|| {
-            "failed to determine environment markers for the current machine (could not run Python)"
+            format!(
+                "failed to construct package database for index {}",
+                config.index_urls.iter().format(", ")
+            )
         })?;
-    tracing::debug!(
-        "extracted the following environment markers from the system python interpreter:\n{:#?}",
-        env_markers
-    );
-
-    let compatible_tags = WheelTags::from_env().await.into_diagnostic()?;
-    tracing::debug!(
-        "extracted the following compatible wheel tags from the system python interpreter: {}",
-        compatible_tags.tags().format(", ")
-    );
-
-    // Solve the environment
-    let blueprint = match resolve(
-        &package_db,
-        &args.specs,
-        &env_markers,
-        Some(&compatible_tags),
-        HashMap::default(),
-        HashMap::default(),
-        &ResolveOptions {
-            sdist_resolution: args.sdist_resolution.into(),
-        },
-    )
-    .await
-    {
+
+    // Optionally read artifacts from the pip cache
+    let package_db = if args.use_pip_cache {
+        match PipCache::from_env() {
+            Some(pip_cache) => {
+                tracing::info!("using pip cache at {}", pip_cache.root().display());
+                package_db.with_pip_cache(pip_cache)
+            }
+            None => {
+                tracing::warn!("could not find the pip cache directory");
+                package_db
+            }
+        }
+    } else {
+        package_db
+    };
+
+    // Optionally log the session
+    let package_db = match &args.session_log {
+        Some(path) => {
+            let session_log = SessionLog::create(path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to create session log {}", path.display()))?;
+            package_db.with_session_log(session_log)
+        }
+        None => package_db,
+    };
+
+    // Serve the cache to other machines until the process is stopped
+    if let Some(addr) = &args.serve_cache {
+        let proxy = CacheProxy::bind(Arc::new(package_db), addr.as_str())
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to listen on {addr}"))?;
+        tracing::info!(
+            "serving the cache as a simple index on {}",
+            proxy.url().into_diagnostic()?
+        );
+        return proxy.serve().await.into_diagnostic();
+    }
+
+    // The markers and tags of the interpreter are cached to avoid running python every time
+    let interpreter_cache = InterpreterCache::new(cache_dir.join("interpreters"));
+
+    let (env_markers, compatible_tags, resolve_options) = if args.pyodide {
+        let target = PyodideTarget::default();
+        (
+            Pep508EnvMakers::from(target.env_markers()),
+            target.wheel_tags(),
+            target.resolve_options(),
+        )
+    } else {
+        // Determine the environment markers for the current machine
+        let env_markers = interpreter_cache
+            .system_env_markers()
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                "failed to determine environment markers for the current machine (could not run Python)"
+            })?;
+        tracing::debug!(
+            "extracted the following environment markers from the system python interpreter:\n{:#?}",
+            env_markers
+        );
+
+        let compatible_tags = interpreter_cache
+            .system_wheel_tags()
+            .await
+            .into_diagnostic()?;
+        tracing::debug!(
+            "extracted the following compatible wheel tags from the system python interpreter: {}",
+            compatible_tags.tags().format(", ")
+        );
+
+        (env_markers, compatible_tags, config.resolve_options())
+    };
+
+    // A requirements file is only useful if every artifact has a hash
+    let mut resolve_options = resolve_options;
+    resolve_options.compute_missing_hashes = args.export_requirements.is_some();
+
+    // Cross build sdists for another platform, e.g. when running inside cibuildwheel
+    if !args.pyodide {
+        resolve_options.cross_build_target = match &args.cross_build_target {
+            Some(target) => Some(target.clone()),
+            None => CrossBuildTarget::from_env()
+                .transpose()
+                .into_diagnostic()
+                .wrap_err("invalid _PYTHON_HOST_PLATFORM")?,
+        };
+    }
+
+    // The resolver only understands requirements that can be looked up in an index
+    let specs = args
+        .specs
+        .iter()
+        .map(RequirementSpec::to_pep508)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Read the key that is used to sign and verify requirements files
+    let signing_key = args
+        .signing_key
+        .as_ref()
+        .map(|path| {
+            std::fs::read(path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read signing key {}", path.display()))
+        })
+        .transpose()?;
+    let resolution_inputs =
+        || ResolutionInputs::new(&specs, package_db.indexes(), &resolve_options, &env_markers);
+
+    // Verify an existing requirements file instead of resolving
+    if let Some(path) = &args.verify_requirements {
+        let contents = std::fs::read_to_string(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        verify_requirements_txt(
+            &contents,
+            Some(&resolution_inputs()),
+            signing_key.as_deref(),
+        )
+        .wrap_err_with(|| format!("failed to verify {}", path.display()))?;
+        println!("{} is up to date", path.display());
+        return Ok(());
+    }
+
+    // Handle requests until the client is done
+    if args.server {
+        let mut server = Server::new(
+            &package_db,
+            &env_markers,
+            &compatible_tags,
+            &resolve_options,
+        );
+        if !args.pyodide {
+            server = server.with_interpreter_cache(interpreter_cache);
+        }
+        return server.run().await;
+    }
+
+    // Solve the environment, optionally reusing an earlier solution
+    let solution_cache = SolutionCache::new(cache_dir.join("solutions"));
+    if args.clear_solution_cache {
+        solution_cache
+            .clear()
+            .into_diagnostic()
+            .wrap_err("failed to clear the solution cache")?;
+    }
+    let blueprint = if args.cache_solutions {
+        resolve_cached(
+            &solution_cache,
+            &package_db,
+            &specs,
+            &env_markers,
+            Some(&compatible_tags),
+            &resolve_options,
+        )
+        .await
+        .map(|(blueprint, _)| blueprint)
+    } else {
+        resolve(
+            &package_db,
+            &specs,
+            &env_markers,
+            Some(&compatible_tags),
+            HashMap::default(),
+            HashMap::default(),
+            &resolve_options,
+        )
+        .await
+    };
+    let blueprint = match blueprint {
         Ok(blueprint) => blueprint,
         Err(err) => miette::bail!("Could not solve for the requested requirements:\n{err}"),
     };
+    tracing::info!("{}", package_db.cache_statistics());
+
+    // Check the attestations of the artifacts that would be installed
+    let attestation_policy = if args.check_attestations {
+        AttestationPolicy::CheckIfPublished
+    } else {
+        AttestationPolicy::Ignore
+    };
+    if attestation_policy != AttestationPolicy::Ignore {
+        let attestations = check_attestations(&package_db, &blueprint, attestation_policy).await?;
+        for attestation in attestations.iter() {
+            if let AttestationStatus::Unverified { publishers, .. } = &attestation.status {
+                tracing::info!(
+                    "{} has unverified attestations by {}",
+                    attestation.artifact,
+                    publishers.iter().format(", ")
+                );
+            }
+        }
+        let rejected = attestations
+            .iter()
+            .filter(|attestation| !attestation.accepted)
+            .map(|attestation| attestation.artifact.to_string())
+            .collect::<Vec<_>>();
+        if !rejected.is_empty() {
+            miette::bail!(
+                "the attestations of the following artifacts do not match: {}",
+                rejected.join(", ")
+            );
+        }
+    }
+
+    // Export the resolved environment if requested
+    if let Some(path) = &args.export_requirements {
+        let requirements = to_requirements_txt(&blueprint, Some(&env_markers.0))
+            .into_diagnostic()
+            .wrap_err("failed to export the resolved environment")?;
+        let requirements =
+            attest_requirements_txt(&requirements, &resolution_inputs(), signing_key.as_deref());
+        std::fs::write(path, requirements)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    }
 
     // Output the selected versions
     println!("{}:", console::style("Resolved environment").bold());
@@ -0,0 +1,594 @@
+//! A long-running server mode that reads [JSON-RPC 2.0](https://www.jsonrpc.org/specification)
+//! requests from stdin and writes the responses to stdout, one JSON object per line.
+//!
+//! Editors and other tools can keep a single `rip --server` process around and send it requests,
+//! which avoids paying the process startup and cache warm-up for every request. Requests are
+//! handled in order. The following methods are supported:
+//!
+//! * `resolve` `{"requirements": ["rich>=13"]}` resolves an environment and returns
+//!   `{"packages": [{"name", "version", "extras", "url"}]}`. With `"best_effort": true` the
+//!   requirements that cannot be satisfied are dropped instead of failing the request, they are
+//!   returned as `"unsatisfiable": [{"requirement", "reason", "help"}]` next to the packages.
+//!   With `"python": "/path/to/python"` the environment is resolved for that interpreter instead
+//!   of the interpreter of the session.
+//! * `metadata` `{"name": "rich", "version": "13.6.0"}` returns the core metadata of a package
+//!   version as `{"name", "version", "requires_dist", "requires_python", "extras"}`.
+//! * `build` `{"name": "rich", "version": "13.6.0"}` builds a wheel from the sdist of a package
+//!   version and returns `{"wheel": "/path/to/the.whl"}`.
+//! * `install` `{"requirements": ["rich"], "target": "/path/to/venv"}` resolves an environment,
+//!   installs it into the virtual environment at `target` and returns the packages like `resolve`.
+//!   The environment is resolved for the interpreter of the virtual environment.
+//! * `shutdown` stops the server after responding with `null`.
+//!
+//! The server also stops when stdin is closed.
+
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_installs_packages::artifacts::wheel::{InstallPaths, UnpackWheelOptions};
+use rattler_installs_packages::artifacts::SDist;
+use rattler_installs_packages::index::{redact_url, PackageDb};
+use rattler_installs_packages::install::InstallBuilder;
+use rattler_installs_packages::python_env::{InterpreterCache, Pep508EnvMakers, WheelTags};
+use rattler_installs_packages::resolve::{
+    resolve, resolve_best_effort, PartialResolution, PinnedPackage, ResolveOptions,
+};
+use rattler_installs_packages::types::{
+    ArtifactName, NormalizedPackageName, RequirementSpec, Version,
+};
+use rattler_installs_packages::wheel_builder::WheelBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// The JSON-RPC error code of a request that is not valid JSON.
+const PARSE_ERROR: i64 = -32700;
+
+/// The JSON-RPC error code of a request that is not a valid request object.
+const INVALID_REQUEST: i64 = -32600;
+
+/// The JSON-RPC error code of a request for an unknown method.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The JSON-RPC error code of a request with invalid parameters.
+const INVALID_PARAMS: i64 = -32602;
+
+/// The JSON-RPC error code of a request that failed while it was handled.
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolveParams {
+    requirements: Vec<RequirementSpec>,
+    #[serde(default)]
+    best_effort: bool,
+    #[serde(default)]
+    python: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageVersionParams {
+    name: NormalizedPackageName,
+    version: Version,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallParams {
+    requirements: Vec<RequirementSpec>,
+    target: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+struct UnsatisfiableRequirement {
+    requirement: String,
+    reason: String,
+    help: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResolvedPackage {
+    name: String,
+    version: String,
+    extras: Vec<String>,
+    url: Option<String>,
+}
+
+/// The state that is shared between the requests of a session.
+pub struct Server<'db, 'i> {
+    package_db: &'db PackageDb,
+    env_markers: &'i Pep508EnvMakers,
+    compatible_tags: &'i WheelTags,
+    resolve_options: &'i ResolveOptions,
+    wheel_builder: WheelBuilder<'db, 'i>,
+    interpreter_cache: Option<InterpreterCache>,
+}
+
+impl<'db, 'i> Server<'db, 'i> {
+    /// Constructs a new server that resolves for the given environment.
+    pub fn new(
+        package_db: &'db PackageDb,
+        env_markers: &'i Pep508EnvMakers,
+        compatible_tags: &'i WheelTags,
+        resolve_options: &'i ResolveOptions,
+    ) -> Self {
+        Self {
+            package_db,
+            env_markers,
+            compatible_tags,
+            resolve_options,
+            wheel_builder: WheelBuilder::new(
+                package_db,
+                env_markers,
+                Some(compatible_tags),
+                resolve_options,
+            ),
+            interpreter_cache: None,
+        }
+    }
+
+    /// Sets the cache that is used to determine the environment markers and tags of the
+    /// interpreters that requests refer to. Without it every request is resolved for the
+    /// interpreter of the session.
+    pub fn with_interpreter_cache(self, interpreter_cache: InterpreterCache) -> Self {
+        Self {
+            interpreter_cache: Some(interpreter_cache),
+            ..self
+        }
+    }
+
+    /// Returns the environment markers and tags of the given interpreter, or of the interpreter of
+    /// the session if `python` is `None`.
+    async fn interpreter(
+        &self,
+        python: Option<&Path>,
+    ) -> miette::Result<(Pep508EnvMakers, WheelTags)> {
+        let Some(python) = python else {
+            return Ok((self.env_markers.clone(), self.compatible_tags.clone()));
+        };
+        let Some(interpreter_cache) = &self.interpreter_cache else {
+            miette::bail!(
+                "cannot query {}, the server only resolves for its own interpreter",
+                python.display()
+            );
+        };
+        let env_markers = interpreter_cache
+            .env_markers(python)
+            .await
+            .into_diagnostic()?;
+        let compatible_tags = interpreter_cache
+            .wheel_tags(python)
+            .await
+            .into_diagnostic()?;
+        Ok((env_markers, compatible_tags))
+    }
+
+    /// Handles requests from stdin until stdin is closed or a `shutdown` request is received.
+    pub async fn run(&self) -> miette::Result<()> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+        while let Some(line) = lines.next_line().await.into_diagnostic()? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (response, shutdown) = self.handle_line(&line).await;
+            if let Some(response) = response {
+                let mut response = serde_json::to_vec(&response).into_diagnostic()?;
+                response.push(b'\n');
+                stdout.write_all(&response).await.into_diagnostic()?;
+                stdout.flush().await.into_diagnostic()?;
+            }
+            if shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a single line of input. Returns the response, if any, and whether the server should
+    /// shut down.
+    async fn handle_line(&self, line: &str) -> (Option<Response>, bool) {
+        let request: Request = match serde_json::from_str::<Value>(line) {
+            Err(err) => {
+                let error = ResponseError::new(PARSE_ERROR, err.to_string());
+                return (Some(error_response(Value::Null, error)), false);
+            }
+            Ok(value) => match serde_json::from_value(value) {
+                Err(err) => {
+                    let error = ResponseError::new(INVALID_REQUEST, err.to_string());
+                    return (Some(error_response(Value::Null, error)), false);
+                }
+                Ok(request) => request,
+            },
+        };
+
+        let shutdown = request.method == "shutdown";
+        let result = self.handle_request(&request.method, request.params).await;
+
+        // Notifications, requests without an id, don't get a response.
+        let Some(id) = request.id else {
+            if let Err(err) = result {
+                tracing::warn!("notification '{}' failed: {}", request.method, err.message);
+            }
+            return (None, shutdown);
+        };
+        let response = match result {
+            Ok(result) => Response {
+                jsonrpc: "2.0",
+                id,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => error_response(id, error),
+        };
+        (Some(response), shutdown)
+    }
+
+    async fn handle_request(&self, method: &str, params: Value) -> Result<Value, ResponseError> {
+        match method {
+            "resolve" => {
+                let params: ResolveParams = parse_params(params)?;
+                let (env_markers, compatible_tags) = self
+                    .interpreter(params.python.as_deref())
+                    .await
+                    .map_err(server_error)?;
+                if params.best_effort {
+                    let resolution = self
+                        .resolve_best_effort(&params.requirements, &env_markers, &compatible_tags)
+                        .await
+                        .map_err(server_error)?;
+                    let unsatisfiable = resolution
+                        .unsatisfiable
+                        .into_iter()
+                        .map(|unsatisfiable| UnsatisfiableRequirement {
+                            requirement: unsatisfiable.requirement.to_string(),
+                            reason: unsatisfiable.reason,
+                            help: unsatisfiable.help,
+                        })
+                        .collect::<Vec<_>>();
+                    return Ok(serde_json::json!({
+                        "packages": to_resolved_packages(&resolution.packages),
+                        "unsatisfiable": unsatisfiable,
+                    }));
+                }
+                let packages = self
+                    .resolve(&params.requirements, &env_markers, &compatible_tags)
+                    .await
+                    .map_err(server_error)?;
+                Ok(serde_json::json!({ "packages": to_resolved_packages(&packages) }))
+            }
+            "metadata" => {
+                let params: PackageVersionParams = parse_params(params)?;
+                self.metadata(&params.name, &params.version)
+                    .await
+                    .map_err(server_error)
+            }
+            "build" => {
+                let params: PackageVersionParams = parse_params(params)?;
+                let wheel = self
+                    .build(&params.name, &params.version)
+                    .await
+                    .map_err(server_error)?;
+                Ok(serde_json::json!({ "wheel": wheel }))
+            }
+            "install" => {
+                let params: InstallParams = parse_params(params)?;
+                let packages = self
+                    .install(&params.requirements, &params.target)
+                    .await
+                    .map_err(server_error)?;
+                Ok(serde_json::json!({ "packages": to_resolved_packages(&packages) }))
+            }
+            "shutdown" => Ok(Value::Null),
+            _ => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{method}'"),
+            )),
+        }
+    }
+
+    async fn resolve(
+        &self,
+        requirements: &[RequirementSpec],
+        env_markers: &Pep508EnvMakers,
+        compatible_tags: &WheelTags,
+    ) -> miette::Result<Vec<PinnedPackage<'db>>> {
+        let requirements = requirements
+            .iter()
+            .map(RequirementSpec::to_pep508)
+            .collect::<Result<Vec<_>, _>>()?;
+        resolve(
+            self.package_db,
+            &requirements,
+            env_markers,
+            Some(compatible_tags),
+            HashMap::default(),
+            HashMap::default(),
+            self.resolve_options,
+        )
+        .await
+    }
+
+    async fn resolve_best_effort(
+        &self,
+        requirements: &[RequirementSpec],
+        env_markers: &Pep508EnvMakers,
+        compatible_tags: &WheelTags,
+    ) -> miette::Result<PartialResolution<'db>> {
+        let requirements = requirements
+            .iter()
+            .map(RequirementSpec::to_pep508)
+            .collect::<Result<Vec<_>, _>>()?;
+        resolve_best_effort(
+            self.package_db,
+            &requirements,
+            env_markers,
+            Some(compatible_tags),
+            HashMap::default(),
+            HashMap::default(),
+            self.resolve_options,
+        )
+        .await
+    }
+
+    async fn metadata(
+        &self,
+        name: &NormalizedPackageName,
+        version: &Version,
+    ) -> miette::Result<Value> {
+        let artifacts = self.package_db.available_artifacts(name.clone()).await?;
+        let Some(artifacts) = artifacts.get(version) else {
+            miette::bail!("there is no version {version} of {}", name.as_str());
+        };
+        let artifacts = artifacts.iter().collect::<Vec<_>>();
+        let Some((_, metadata)) = self
+            .package_db
+            .get_metadata(&artifacts, Some(&self.wheel_builder))
+            .await?
+        else {
+            miette::bail!(
+                "could not determine the metadata of {} {version}",
+                name.as_str()
+            );
+        };
+        Ok(serde_json::json!({
+            "name": metadata.name.as_str(),
+            "version": metadata.version.to_string(),
+            "requires_dist": metadata.requires_dist.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "requires_python": metadata.requires_python.as_ref().map(ToString::to_string),
+            "extras": metadata.extras.iter().map(|extra| extra.as_str()).sorted().collect::<Vec<_>>(),
+        }))
+    }
+
+    async fn build(
+        &self,
+        name: &NormalizedPackageName,
+        version: &Version,
+    ) -> miette::Result<PathBuf> {
+        let artifacts = self.package_db.available_artifacts(name.clone()).await?;
+        let Some(artifact_info) = artifacts.get(version).and_then(|artifacts| {
+            artifacts
+                .iter()
+                .find(|artifact| matches!(artifact.filename, ArtifactName::SDist(_)))
+        }) else {
+            miette::bail!("there is no sdist of {} {version}", name.as_str());
+        };
+        let sdist = self.package_db.get_artifact::<SDist>(artifact_info).await?;
+        self.wheel_builder
+            .build_wheel(&sdist)
+            .await
+            .into_diagnostic()
+    }
+
+    async fn install(
+        &self,
+        requirements: &[RequirementSpec],
+        target: &Path,
+    ) -> miette::Result<Vec<PinnedPackage<'db>>> {
+        let python_executable = if cfg!(windows) {
+            target.join("Scripts").join("python.exe")
+        } else {
+            target.join("bin").join("python")
+        };
+
+        // Resolve for the interpreter of the environment, if it can be queried
+        let python = self
+            .interpreter_cache
+            .is_some()
+            .then_some(python_executable.as_path());
+        let (env_markers, compatible_tags) = self.interpreter(python).await?;
+        let packages = self
+            .resolve(requirements, &env_markers, &compatible_tags)
+            .await?;
+
+        let release = &env_markers.python_full_version.version.release;
+        let python_version = (
+            release.first().copied().unwrap_or(3) as u32,
+            release.get(1).copied().unwrap_or(0) as u32,
+            release.get(2).copied().unwrap_or(0) as u32,
+        );
+        let install_paths = InstallPaths::for_venv(python_version, cfg!(windows));
+        let mut unpack_options = UnpackWheelOptions::default();
+        unpack_options.installer = Some(String::from("rip"));
+        unpack_options.compatible_tags = Some(compatible_tags.clone());
+        InstallBuilder::new(
+            self.package_db,
+            &env_markers,
+            target,
+            &install_paths,
+            &python_executable,
+        )
+        .with_compatible_tags(&compatible_tags)
+        .with_resolve_options(self.resolve_options.clone())
+        .with_unpack_options(unpack_options)
+        .install(&packages)
+        .await?;
+
+        Ok(packages)
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, ResponseError> {
+    serde_json::from_value(params)
+        .map_err(|err| ResponseError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn server_error(err: miette::Report) -> ResponseError {
+    ResponseError::new(
+        SERVER_ERROR,
+        err.chain().map(ToString::to_string).join(": "),
+    )
+}
+
+fn error_response(id: Value, error: ResponseError) -> Response {
+    Response {
+        jsonrpc: "2.0",
+        id,
+        result: None,
+        error: Some(error),
+    }
+}
+
+fn to_resolved_packages(packages: &[PinnedPackage<'_>]) -> Vec<ResolvedPackage> {
+    packages
+        .iter()
+        .map(|package| ResolvedPackage {
+            name: package.name.as_str().to_owned(),
+            version: package.version.to_string(),
+            extras: package
+                .extras
+                .iter()
+                .map(|extra| extra.as_str().to_owned())
+                .sorted()
+                .collect(),
+            url: package
+                .artifacts
+                .first()
+                .map(|artifact| redact_url(&artifact.url).to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rattler_installs_packages::index::generate::generate_index;
+    use rattler_installs_packages::index::test_server::TestIndexServer;
+
+    /// Handles a single request and returns the response as JSON.
+    async fn request(server: &Server<'_, '_>, line: &str) -> Value {
+        let (response, _) = server.handle_line(line).await;
+        serde_json::to_value(response.expect("expected a response")).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_requests() {
+        let wheels_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/wheels");
+        let index_dir = tempfile::tempdir().unwrap();
+        generate_index(&wheels_dir, index_dir.path()).unwrap();
+        let index_server = TestIndexServer::start(index_dir.path(), Default::default())
+            .await
+            .unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let package_db =
+            PackageDb::new(Default::default(), &[index_server.url()], cache_dir.path()).unwrap();
+
+        let env_markers = Pep508EnvMakers(test_utils::linux_environment_markers("3.10.4"));
+        let compatible_tags: WheelTags = ["py3-none-any"]
+            .into_iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let resolve_options = ResolveOptions::default();
+        let server = Server::new(
+            &package_db,
+            &env_markers,
+            &compatible_tags,
+            &resolve_options,
+        );
+
+        let response = request(
+            &server,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "resolve", "params": {"requirements": ["wordle_python"]}}"#,
+        )
+        .await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["packages"][0]["name"], "wordle-python");
+        assert_eq!(response["result"]["packages"][0]["version"], "2.3.32");
+
+        // The server cannot query other interpreters without an interpreter cache
+        let response = request(
+            &server,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "resolve", "params": {"requirements": ["wordle_python"], "python": "/usr/bin/python3"}}"#,
+        )
+        .await;
+        assert_eq!(response["error"]["code"], SERVER_ERROR);
+
+        let response = request(
+            &server,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "frobnicate"}"#,
+        )
+        .await;
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = request(
+            &server,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "resolve", "params": {}}"#,
+        )
+        .await;
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+        let response = request(&server, "{").await;
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+
+        // Notifications don't get a response
+        let (response, shutdown) = server
+            .handle_line(r#"{"jsonrpc": "2.0", "method": "frobnicate"}"#)
+            .await;
+        assert!(response.is_none());
+        assert!(!shutdown);
+
+        let (response, shutdown) = server
+            .handle_line(r#"{"jsonrpc": "2.0", "id": 5, "method": "shutdown"}"#)
+            .await;
+        assert_eq!(
+            serde_json::to_value(response).unwrap()["result"],
+            Value::Null
+        );
+        assert!(shutdown);
+    }
+}
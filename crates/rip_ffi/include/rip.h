@@ -0,0 +1,52 @@
+/*
+ * C interface to the resolver and installer of rattler_installs_packages.
+ *
+ * All requests and responses are JSON encoded, nul-terminated UTF-8 strings. Every response is a
+ * JSON object that either contains the result or an "error" key with a message. Strings returned
+ * by this library are owned by the caller and must be freed with rip_string_free.
+ */
+
+#ifndef RIP_H
+#define RIP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle that owns a package database and its runtime. */
+typedef struct RipContext RipContext;
+
+/*
+ * Creates a new context from a JSON configuration, e.g.
+ * {"index_urls": ["https://pypi.org/simple/"], "cache_dir": "/tmp/rip"}.
+ *
+ * Returns NULL on failure. In that case, if error is not NULL, it is set to a message that must be
+ * freed with rip_string_free.
+ */
+RipContext *rip_context_new(const char *config, char **error);
+
+/* Frees a context created with rip_context_new. Passing NULL is a no-op. */
+void rip_context_free(RipContext *context);
+
+/*
+ * Resolves the requirements of a request, e.g. {"requirements": ["rich>=13"]}. The request may
+ * also specify the "python" interpreter to resolve for and the "sdist_resolution".
+ *
+ * Returns {"packages": [{"name", "version", "extras", "url"}]}.
+ */
+char *rip_resolve(const RipContext *context, const char *request);
+
+/*
+ * Resolves the requirements of a request and installs them into the virtual environment specified
+ * by its "target" key. Returns the installed packages like rip_resolve.
+ */
+char *rip_install(const RipContext *context, const char *request);
+
+/* Frees a string returned by this library. Passing NULL is a no-op. */
+void rip_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RIP_H */
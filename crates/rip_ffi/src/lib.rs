@@ -0,0 +1,443 @@
+//! A C ABI to embed the resolver and installer of `rattler_installs_packages` in non-Rust tools.
+//!
+//! All requests and responses are exchanged as JSON encoded, nul-terminated UTF-8 strings, which
+//! keeps the ABI small and stable. See `include/rip.h` for the C declarations.
+//!
+//! A typical session looks like this:
+//!
+//! ```c
+//! char *error = NULL;
+//! RipContext *ctx = rip_context_new("{\"cache_dir\": \"/tmp/rip\"}", &error);
+//! char *response = rip_resolve(ctx, "{\"requirements\": [\"rich\"]}");
+//! /* ... use the response ... */
+//! rip_string_free(response);
+//! rip_context_free(ctx);
+//! ```
+//!
+//! Every response is a JSON object that either contains the result or an `error` key with a
+//! message describing what went wrong.
+
+#![deny(missing_docs)]
+
+use itertools::Itertools;
+use rattler_installs_packages::artifacts::wheel::{InstallPaths, UnpackWheelOptions};
+use rattler_installs_packages::blocking::PackageDb;
+use rattler_installs_packages::index::redact_url;
+use rattler_installs_packages::python_env::{
+    InterpreterCache, InterpreterCacheError, Pep508EnvMakers, WheelTags,
+};
+use rattler_installs_packages::resolve::{PinnedPackage, ResolveOptions, SDistResolution};
+use rattler_installs_packages::types::{Requirement, RequirementSpec};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use url::Url;
+
+/// The configuration of a [`RipContext`].
+#[derive(Debug, Deserialize)]
+struct ContextConfig {
+    /// The indexes to use, defaults to PyPI.
+    #[serde(default = "default_index_urls")]
+    index_urls: Vec<Url>,
+
+    /// The directory in which downloaded artifacts and metadata are cached.
+    cache_dir: PathBuf,
+}
+
+fn default_index_urls() -> Vec<Url> {
+    vec![Url::parse("https://pypi.org/simple/").expect("invalid url")]
+}
+
+/// A request to resolve or install a set of requirements.
+#[derive(Debug, Deserialize)]
+struct Request {
+    /// The requirements to resolve, e.g. `rich>=13`.
+    requirements: Vec<String>,
+
+    /// The python interpreter to resolve for, defaults to the python on the `PATH`.
+    #[serde(default)]
+    python: Option<PathBuf>,
+
+    /// How sdists are handled, one of `normal`, `prefer-wheels`, `prefer-sdists`,
+    /// `only-wheels` or `only-sdists`.
+    #[serde(default)]
+    sdist_resolution: Option<String>,
+
+    /// The root of the virtual environment to install into, required by `rip_install`.
+    #[serde(default)]
+    target: Option<PathBuf>,
+}
+
+/// A package of a resolved environment as it is returned to the caller.
+#[derive(Debug, Serialize)]
+struct ResolvedPackage {
+    name: String,
+    version: String,
+    extras: Vec<String>,
+    url: Option<String>,
+}
+
+/// Opaque handle that owns a package database and its runtime. Reusing a context between
+/// requests avoids paying for the cache warm-up on every request.
+pub struct RipContext {
+    package_db: PackageDb,
+    interpreter_cache: InterpreterCache,
+}
+
+impl RipContext {
+    fn new(config: &str) -> miette::Result<Self> {
+        let config: ContextConfig = serde_json::from_str(config)
+            .map_err(|e| miette::miette!("invalid configuration: {e}"))?;
+        let package_db = PackageDb::new(Default::default(), &config.index_urls, &config.cache_dir)
+            .map_err(|e| miette::miette!("failed to create the package database: {e}"))?;
+        Ok(Self {
+            package_db,
+            interpreter_cache: InterpreterCache::new(config.cache_dir.join("interpreters")),
+        })
+    }
+
+    /// Determines the environment markers and tags of the interpreter of the request.
+    fn interpreter(&self, python: Option<&Path>) -> miette::Result<(Pep508EnvMakers, WheelTags)> {
+        let cache = &self.interpreter_cache;
+        self.package_db
+            .block_on(async {
+                match python {
+                    Some(python) => Ok((
+                        cache.env_markers(python).await?,
+                        cache.wheel_tags(python).await?,
+                    )),
+                    None => Ok((
+                        cache.system_env_markers().await?,
+                        cache.system_wheel_tags().await?,
+                    )),
+                }
+            })
+            .map_err(|e: InterpreterCacheError| miette::miette!("{e}"))
+    }
+
+    fn handle_resolve(&self, request: &str) -> miette::Result<serde_json::Value> {
+        let (request, requirements, options) = parse_request(request)?;
+        let (env_markers, tags) = self.interpreter(request.python.as_deref())?;
+        let packages = self.package_db.resolve(
+            &requirements,
+            &env_markers,
+            Some(&tags),
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )?;
+        Ok(serde_json::json!({ "packages": to_resolved_packages(&packages) }))
+    }
+
+    fn handle_install(&self, request: &str) -> miette::Result<serde_json::Value> {
+        let (request, requirements, options) = parse_request(request)?;
+        let Some(target) = request.target.as_deref() else {
+            miette::bail!("the request does not specify a target environment");
+        };
+        let (env_markers, tags) = self.interpreter(request.python.as_deref())?;
+        let packages = self.package_db.resolve(
+            &requirements,
+            &env_markers,
+            Some(&tags),
+            HashMap::default(),
+            HashMap::default(),
+            &options,
+        )?;
+
+        let release = &env_markers.python_full_version.version.release;
+        let version = (
+            release.first().copied().unwrap_or(3) as u32,
+            release.get(1).copied().unwrap_or(0) as u32,
+            release.get(2).copied().unwrap_or(0) as u32,
+        );
+        let install_paths = InstallPaths::for_venv(version, cfg!(windows));
+        let python_executable = venv_python(target);
+        let mut unpack_options = UnpackWheelOptions::default();
+        unpack_options.compatible_tags = Some(tags.clone());
+        self.package_db.install(
+            &packages,
+            &env_markers,
+            Some(&tags),
+            &options,
+            target,
+            &install_paths,
+            &python_executable,
+            &unpack_options,
+        )?;
+
+        Ok(serde_json::json!({ "packages": to_resolved_packages(&packages) }))
+    }
+}
+
+/// Parses a request together with its requirements and resolve options.
+fn parse_request(request: &str) -> miette::Result<(Request, Vec<Requirement>, ResolveOptions)> {
+    let request: Request =
+        serde_json::from_str(request).map_err(|e| miette::miette!("invalid request: {e}"))?;
+    let requirements = request
+        .requirements
+        .iter()
+        .map(|requirement| {
+            let requirement = RequirementSpec::from_str(requirement)
+                .map_err(|e| miette::miette!("invalid requirement '{requirement}': {e}"))?;
+            Ok(requirement.to_pep508()?)
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+    let mut options = ResolveOptions::default();
+    options.sdist_resolution = parse_sdist_resolution(request.sdist_resolution.as_deref())?;
+    Ok((request, requirements, options))
+}
+
+fn parse_sdist_resolution(value: Option<&str>) -> miette::Result<SDistResolution> {
+    Ok(match value {
+        None | Some("normal") => SDistResolution::Normal,
+        Some("prefer-wheels") => SDistResolution::PreferWheels,
+        Some("prefer-sdists") => SDistResolution::PreferSDists,
+        Some("only-wheels") => SDistResolution::OnlyWheels,
+        Some("only-sdists") => SDistResolution::OnlySDists,
+        Some(other) => miette::bail!("invalid sdist resolution '{other}'"),
+    })
+}
+
+/// Returns the path of the python executable of the virtual environment at `root`.
+fn venv_python(root: &Path) -> PathBuf {
+    if cfg!(windows) {
+        root.join("Scripts").join("python.exe")
+    } else {
+        root.join("bin").join("python")
+    }
+}
+
+fn to_resolved_packages(packages: &[PinnedPackage<'_>]) -> Vec<ResolvedPackage> {
+    packages
+        .iter()
+        .map(|package| ResolvedPackage {
+            name: package.name.as_str().to_owned(),
+            version: package.version.to_string(),
+            extras: package
+                .extras
+                .iter()
+                .map(|extra| extra.as_str().to_owned())
+                .sorted()
+                .collect(),
+            url: package
+                .artifacts
+                .first()
+                .map(|artifact| redact_url(&artifact.url).to_string()),
+        })
+        .collect()
+}
+
+/// Converts the result of a request to a JSON response that is owned by the caller.
+fn into_response(result: miette::Result<serde_json::Value>) -> *mut c_char {
+    let value = match result {
+        Ok(value) => value,
+        Err(err) => serde_json::json!({ "error": format_error(&err) }),
+    };
+    into_c_string(value.to_string())
+}
+
+/// Formats an error together with its causes.
+fn format_error(err: &miette::Report) -> String {
+    err.chain().map(ToString::to_string).join(": ")
+}
+
+/// Runs `f` and turns a panic into an error. Unwinding across the C ABI is undefined behavior, so
+/// every exported function runs its body through this function.
+fn catch_panic<T>(f: impl FnOnce() -> miette::Result<T>) -> miette::Result<T> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        Err(miette::miette!("internal error: {message}"))
+    })
+}
+
+fn into_c_string(value: String) -> *mut c_char {
+    // JSON never contains interior nul bytes, they are always escaped.
+    CString::new(value)
+        .expect("string contains a nul byte")
+        .into_raw()
+}
+
+/// Reads a string argument, returns an error if it is null or not valid UTF-8.
+///
+/// # Safety
+///
+/// `value` must be null or point to a valid nul-terminated string.
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> miette::Result<&'a str> {
+    if value.is_null() {
+        miette::bail!("{name} must not be null");
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| miette::miette!("{name} is not valid UTF-8"))
+}
+
+/// Creates a new context from a JSON configuration, e.g.
+/// `{"index_urls": ["https://pypi.org/simple/"], "cache_dir": "/tmp/rip"}`.
+///
+/// Returns null if the context could not be created. In that case, if `error` is not null, it is
+/// set to a message that must be freed with [`rip_string_free`].
+///
+/// # Safety
+///
+/// `config` must point to a valid nul-terminated string and `error` must be null or point to
+/// writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn rip_context_new(
+    config: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut RipContext {
+    match catch_panic(|| read_str(config, "config").and_then(RipContext::new)) {
+        Ok(context) => Box::into_raw(Box::new(context)),
+        Err(err) => {
+            if !error.is_null() {
+                // Unlike JSON, error messages may contain nul bytes
+                *error = into_c_string(format_error(&err).replace('\0', "\\0"));
+            }
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a context created with [`rip_context_new`]. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `context` must be null or a pointer returned by [`rip_context_new`] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rip_context_free(context: *mut RipContext) {
+    if !context.is_null() {
+        let _ = catch_panic(|| {
+            drop(Box::from_raw(context));
+            Ok(())
+        });
+    }
+}
+
+/// Resolves the requirements of a JSON request, e.g. `{"requirements": ["rich>=13"]}`, and
+/// returns the resolved packages as `{"packages": [{"name", "version", "extras", "url"}]}`.
+///
+/// The returned string must be freed with [`rip_string_free`].
+///
+/// # Safety
+///
+/// `context` must be a valid pointer returned by [`rip_context_new`] and `request` must point to
+/// a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rip_resolve(
+    context: *const RipContext,
+    request: *const c_char,
+) -> *mut c_char {
+    into_response(catch_panic(|| {
+        let Some(context) = context.as_ref() else {
+            miette::bail!("context must not be null");
+        };
+        read_str(request, "request").and_then(|request| context.handle_resolve(request))
+    }))
+}
+
+/// Resolves the requirements of a JSON request and installs them into the virtual environment
+/// specified by its `target` key. Returns the installed packages like [`rip_resolve`].
+///
+/// The returned string must be freed with [`rip_string_free`].
+///
+/// # Safety
+///
+/// `context` must be a valid pointer returned by [`rip_context_new`] and `request` must point to
+/// a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rip_install(
+    context: *const RipContext,
+    request: *const c_char,
+) -> *mut c_char {
+    into_response(catch_panic(|| {
+        let Some(context) = context.as_ref() else {
+            miette::bail!("context must not be null");
+        };
+        read_str(request, "request").and_then(|request| context.handle_install(request))
+    }))
+}
+
+/// Frees a string returned by any of the functions of this library. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `value` must be null or a pointer returned by this library that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rip_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        let _ = catch_panic(|| {
+            drop(CString::from_raw(value));
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    unsafe fn take_string(value: *mut c_char) -> String {
+        let result = CStr::from_ptr(value).to_str().unwrap().to_owned();
+        rip_string_free(value);
+        result
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config =
+            CString::new(serde_json::json!({ "cache_dir": cache_dir.path() }).to_string()).unwrap();
+
+        unsafe {
+            let mut error = std::ptr::null_mut();
+            let context = rip_context_new(config.as_ptr(), &mut error);
+            assert!(!context.is_null());
+            assert!(error.is_null());
+
+            let request =
+                CString::new(r#"{"requirements": ["rich"], "sdist_resolution": "sometimes"}"#)
+                    .unwrap();
+            let response = take_string(rip_resolve(context, request.as_ptr()));
+            assert!(response.contains("invalid sdist resolution"), "{response}");
+
+            let request = CString::new(r#"{"requirements": ["rich"]}"#).unwrap();
+            let response = take_string(rip_install(context, request.as_ptr()));
+            assert!(response.contains("does not specify a target"), "{response}");
+
+            let response = take_string(rip_resolve(context, std::ptr::null()));
+            assert!(response.contains("request must not be null"), "{response}");
+
+            rip_context_free(context);
+
+            let response = take_string(rip_resolve(std::ptr::null(), request.as_ptr()));
+            assert!(response.contains("context must not be null"), "{response}");
+
+            let config = CString::new("{}").unwrap();
+            let context = rip_context_new(config.as_ptr(), &mut error);
+            assert!(context.is_null());
+            assert!(take_string(error).contains("invalid configuration"));
+        }
+    }
+
+    #[test]
+    fn test_panics_are_errors() {
+        let result = catch_panic::<()>(|| panic!("something went wrong"));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "internal error: something went wrong"
+        );
+
+        let result = catch_panic::<()>(|| panic!("{} went wrong", "something"));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "internal error: something went wrong"
+        );
+    }
+}
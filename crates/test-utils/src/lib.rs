@@ -1,3 +1,4 @@
+use pep508_rs::MarkerEnvironment;
 use rattler_digest::Sha256;
 use reqwest::blocking::Client;
 use std::time::Instant;
@@ -127,3 +128,48 @@ pub fn download_and_cache_file(url: Url, expected_sha256: &str) -> Result<PathBu
 
     Ok(final_path)
 }
+
+/// Returns the `MarkerEnvironment` of a CPython interpreter with the given version (e.g. `3.10.4`)
+/// on a x86_64 Linux system.
+pub fn linux_environment_markers(python_full_version: &str) -> MarkerEnvironment {
+    MarkerEnvironment {
+        implementation_name: "cpython".to_string(),
+        implementation_version: python_full_version.parse().unwrap(),
+        os_name: "posix".to_string(),
+        platform_machine: "x86_64".to_string(),
+        platform_python_implementation: "CPython".to_string(),
+        platform_release: "6.0".to_string(),
+        platform_system: "Linux".to_string(),
+        platform_version: "#1 SMP".to_string(),
+        python_full_version: python_full_version.parse().unwrap(),
+        python_version: python_version(python_full_version).parse().unwrap(),
+        sys_platform: "linux".to_string(),
+    }
+}
+
+/// Returns the `MarkerEnvironment` of a CPython interpreter with the given version (e.g. `3.11.4`)
+/// on a x86_64 Windows system.
+pub fn windows_environment_markers(python_full_version: &str) -> MarkerEnvironment {
+    MarkerEnvironment {
+        implementation_name: "cpython".to_string(),
+        implementation_version: python_full_version.parse().unwrap(),
+        os_name: "nt".to_string(),
+        platform_machine: "AMD64".to_string(),
+        platform_python_implementation: "CPython".to_string(),
+        platform_release: "10".to_string(),
+        platform_system: "Windows".to_string(),
+        platform_version: "10.0.19045".to_string(),
+        python_full_version: python_full_version.parse().unwrap(),
+        python_version: python_version(python_full_version).parse().unwrap(),
+        sys_platform: "win32".to_string(),
+    }
+}
+
+/// Returns the `major.minor` part of a full python version.
+fn python_version(python_full_version: &str) -> String {
+    python_full_version
+        .split('.')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(".")
+}